@@ -0,0 +1,4 @@
+fn main() {
+    // Embed version, git, target and dependency metadata for `wx-uploader -V --verbose`
+    built::write_built_file().expect("Failed to acquire build-time information");
+}
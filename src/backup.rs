@@ -0,0 +1,352 @@
+//! Backups of source files
+//!
+//! Implements the `backup` setting: before an upload run first modifies a
+//! file, e.g. to record its draft ID or a generated cover name, the original
+//! is copied to `.wx-uploader/backup/<timestamp>/<path>` in the working
+//! directory, with `<path>` relative to it. A run gets one backup set, named
+//! after the time it started and created only once something is backed up.
+//! Only the `backup_keep` most recent sets are kept.
+//!
+//! `wx-uploader restore --from <timestamp>` copies a set back over the
+//! working tree. Files created by the run, such as generated covers, are not
+//! part of a set and are left in place by a restore.
+
+use crate::error::{Error, Result};
+use crate::output::{FORMATTER, OutputFormatter};
+use chrono::NaiveDateTime;
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use tokio::sync::Mutex;
+use walkdir::WalkDir;
+
+/// Directory in the working directory where wx-uploader keeps its own files
+pub const DATA_DIR: &str = ".wx-uploader";
+
+/// Number of backup sets kept when `backup_keep` is not set
+pub const DEFAULT_BACKUP_KEEP: usize = 10;
+
+/// Format of the timestamp a backup set is named after
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+/// Returns the directory the backup sets of `work_dir` are kept in
+pub fn backup_root(work_dir: &Path) -> PathBuf {
+    work_dir.join(DATA_DIR).join("backup")
+}
+
+/// The backup set of one run
+///
+/// Each file is backed up once, before its first modification; later
+/// modifications in the same run keep the original copy.
+#[derive(Debug)]
+pub struct Backup {
+    work_dir: PathBuf,
+    keep: usize,
+    timestamp: String,
+    state: Mutex<BackupState>,
+}
+
+/// What a run has backed up so far
+#[derive(Debug, Default)]
+struct BackupState {
+    /// Directory of the set, once created
+    set_dir: Option<PathBuf>,
+    /// Absolute paths of the files already handled
+    saved: HashSet<PathBuf>,
+}
+
+impl PartialEq for Backup {
+    fn eq(&self, other: &Self) -> bool {
+        self.work_dir == other.work_dir && self.keep == other.keep && self.timestamp == other.timestamp
+    }
+}
+
+impl Backup {
+    /// Creates the backup of a run in `work_dir`, named after `timestamp`
+    ///
+    /// Nothing is written until the first file is backed up.
+    pub fn new(work_dir: impl Into<PathBuf>, keep: usize, timestamp: impl Into<String>) -> Self {
+        Self {
+            work_dir: work_dir.into(),
+            keep: keep.max(1),
+            timestamp: timestamp.into(),
+            state: Mutex::default(),
+        }
+    }
+
+    /// Creates the backup of a run in `work_dir`, named after the current local time
+    pub fn starting_now(work_dir: impl Into<PathBuf>, keep: usize) -> Self {
+        let timestamp = chrono::Local::now().format(TIMESTAMP_FORMAT).to_string();
+        Self::new(work_dir, keep, timestamp)
+    }
+
+    /// Returns the directory of the backup set, if anything has been backed up
+    pub async fn set_dir(&self) -> Option<PathBuf> {
+        self.state.lock().await.set_dir.clone()
+    }
+
+    /// Copies `path` into the backup set unless it has been backed up already
+    ///
+    /// A file that does not exist yet has nothing to back up, and a file
+    /// outside the working directory is reported and left out. The first
+    /// backup creates the set and prunes old sets.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the set cannot be created or the file cannot be copied
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        let absolute = std::path::absolute(path).map_err(|e| Error::io_at(path, e))?;
+        let mut state = self.state.lock().await;
+        if !state.saved.insert(absolute.clone()) || !absolute.is_file() {
+            return Ok(());
+        }
+
+        let work_dir = std::path::absolute(&self.work_dir)?;
+        let relative = match absolute.strip_prefix(&work_dir) {
+            Ok(relative) if relative.components().all(|c| matches!(c, Component::Normal(_))) => {
+                relative
+            }
+            _ => {
+                FORMATTER.print_file_warning(
+                    path,
+                    None,
+                    "not backed up: outside the working directory",
+                );
+                return Ok(());
+            }
+        };
+
+        let set_dir = match &state.set_dir {
+            Some(set_dir) => set_dir.clone(),
+            None => {
+                let set_dir = self.create_set().await?;
+                state.set_dir = Some(set_dir.clone());
+                set_dir
+            }
+        };
+        let target = set_dir.join(relative);
+        if let Some(parent) = target.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        if let Err(e) = tokio::fs::copy(&absolute, &target).await {
+            // The next modification tries again
+            state.saved.remove(&absolute);
+            return Err(Error::io_at(path, e));
+        }
+        Ok(())
+    }
+
+    /// Creates the directory of this run's set and prunes the oldest sets
+    ///
+    /// A second run within the same second gets a numbered set.
+    async fn create_set(&self) -> Result<PathBuf> {
+        let root = backup_root(&self.work_dir);
+        tokio::fs::create_dir_all(&root).await?;
+
+        let mut name = self.timestamp.clone();
+        let mut n = 2;
+        let set_dir = loop {
+            let set_dir = root.join(&name);
+            match tokio::fs::create_dir(&set_dir).await {
+                Ok(()) => break set_dir,
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    name = format!("{}-{}", self.timestamp, n);
+                    n += 1;
+                }
+                Err(e) => return Err(Error::io_at(&set_dir, e)),
+            }
+        };
+
+        let sets = list_sets(&self.work_dir)?;
+        let excess = sets.len().saturating_sub(self.keep);
+        for old in sets.iter().take(excess).filter(|old| **old != name) {
+            tokio::fs::remove_dir_all(root.join(old)).await?;
+        }
+        Ok(set_dir)
+    }
+}
+
+/// Splits the name of a backup set into its timestamp and number
+///
+/// The first set of a second has no number and sorts before the numbered ones.
+fn parse_set_name(name: &str) -> Option<(&str, u32)> {
+    let timestamp = name.get(..15)?;
+    NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).ok()?;
+    match &name[15..] {
+        "" => Some((timestamp, 1)),
+        suffix => {
+            let n = suffix.strip_prefix('-')?;
+            if !n.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            Some((timestamp, n.parse().ok()?))
+        }
+    }
+}
+
+/// Returns true if `name` is the name of a backup set
+fn is_set_name(name: &str) -> bool {
+    parse_set_name(name).is_some()
+}
+
+/// Returns the names of the backup sets of `work_dir`, oldest first
+///
+/// # Errors
+///
+/// Returns an error if the backup directory exists but cannot be read
+pub fn list_sets(work_dir: &Path) -> Result<Vec<String>> {
+    let root = backup_root(work_dir);
+    let entries = match std::fs::read_dir(&root) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(Error::io_at(&root, e)),
+    };
+
+    let mut sets: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| is_set_name(name))
+        .collect();
+    sets.sort_by_key(|name| parse_set_name(name).map(|(timestamp, n)| (timestamp.to_string(), n)));
+    Ok(sets)
+}
+
+/// Returns the files of the backup set `name`, relative to the working directory
+///
+/// # Errors
+///
+/// Returns a configuration error naming the available sets if there is no
+/// set called `name`
+pub fn set_files(work_dir: &Path, name: &str) -> Result<Vec<PathBuf>> {
+    let set_dir = backup_root(work_dir).join(name);
+    if !is_set_name(name) || !set_dir.is_dir() {
+        let sets = list_sets(work_dir)?;
+        let available = if sets.is_empty() {
+            "there are no backups".to_string()
+        } else {
+            format!("available: {}", sets.join(", "))
+        };
+        return Err(Error::config(format!(
+            "No backup set '{}' in {} ({})",
+            name,
+            backup_root(work_dir).display(),
+            available
+        )));
+    }
+
+    let mut files: Vec<PathBuf> = WalkDir::new(&set_dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.path().strip_prefix(&set_dir).ok().map(Path::to_path_buf))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Copies the files of the backup set `name` back over the working tree
+///
+/// Files are replaced atomically; the set itself is kept.
+///
+/// # Returns
+///
+/// The restored files, relative to the working directory
+///
+/// # Errors
+///
+/// Returns an error if there is no such set or a file cannot be copied
+pub async fn restore(work_dir: &Path, name: &str) -> Result<Vec<PathBuf>> {
+    let set_dir = backup_root(work_dir).join(name);
+    let files = set_files(work_dir, name)?;
+    for file in &files {
+        let source = set_dir.join(file);
+        let target = work_dir.join(file);
+        let content = tokio::fs::read(&source)
+            .await
+            .map_err(|e| Error::io_at(&source, e))?;
+        if let Some(parent) = target.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        crate::markdown::write_file_atomic(&target, &content).await?;
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_save_backs_up_each_file_once() {
+        let temp_dir = TempDir::new().unwrap();
+        let work_dir = temp_dir.path();
+        let article = work_dir.join("posts/a.md");
+        fs::create_dir_all(article.parent().unwrap()).unwrap();
+        fs::write(&article, "original").unwrap();
+
+        let backup = Backup::new(work_dir, 3, "20261017-120000");
+        backup.save(&work_dir.join("posts/missing.md")).await.unwrap();
+        assert_eq!(backup.set_dir().await, None);
+
+        backup.save(&article).await.unwrap();
+        fs::write(&article, "modified").unwrap();
+        backup.save(&article).await.unwrap();
+
+        let set_dir = backup.set_dir().await.unwrap();
+        assert_eq!(set_dir, backup_root(work_dir).join("20261017-120000"));
+        assert_eq!(fs::read_to_string(set_dir.join("posts/a.md")).unwrap(), "original");
+        assert_eq!(set_files(work_dir, "20261017-120000").unwrap(), vec![PathBuf::from("posts/a.md")]);
+
+        // A file outside the working directory is left out
+        let outside = TempDir::new().unwrap();
+        let other = outside.path().join("b.md");
+        fs::write(&other, "other").unwrap();
+        backup.save(&other).await.unwrap();
+        assert_eq!(set_files(work_dir, "20261017-120000").unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_old_sets_are_pruned() {
+        let temp_dir = TempDir::new().unwrap();
+        let work_dir = temp_dir.path();
+        let article = work_dir.join("a.md");
+        fs::write(&article, "text").unwrap();
+        fs::create_dir_all(backup_root(work_dir).join("notes")).unwrap();
+
+        for timestamp in ["20261017-090000", "20261017-100000", "20261017-110000", "20261017-110000"] {
+            Backup::new(work_dir, 2, timestamp).save(&article).await.unwrap();
+        }
+
+        assert_eq!(list_sets(work_dir).unwrap(), vec!["20261017-110000", "20261017-110000-2"]);
+        // Directories that are no backup sets are left alone
+        assert!(backup_root(work_dir).join("notes").is_dir());
+    }
+
+    #[tokio::test]
+    async fn test_restore_copies_set_over_working_tree() {
+        let temp_dir = TempDir::new().unwrap();
+        let work_dir = temp_dir.path();
+        let article = work_dir.join("posts/a.md");
+        let untouched = work_dir.join("b.md");
+        fs::create_dir_all(article.parent().unwrap()).unwrap();
+        fs::write(&article, "---\ntitle: A\n---\nbody").unwrap();
+        fs::write(&untouched, "b").unwrap();
+
+        let backup = Backup::new(work_dir, 5, "20261017-120000");
+        backup.save(&article).await.unwrap();
+        fs::write(&article, "---\ntitle: A\npublished: draft\n---\nbody").unwrap();
+        fs::remove_file(&untouched).unwrap();
+
+        let restored = restore(work_dir, "20261017-120000").await.unwrap();
+        assert_eq!(restored, vec![PathBuf::from("posts/a.md")]);
+        assert_eq!(fs::read_to_string(&article).unwrap(), "---\ntitle: A\n---\nbody");
+        assert!(!untouched.exists());
+
+        let err = restore(work_dir, "20200101-000000").await.unwrap_err();
+        assert!(err.is_config());
+        assert!(err.to_string().contains("available: 20261017-120000"), "{}", err);
+        assert!(restore(work_dir, "../..").await.is_err());
+    }
+}
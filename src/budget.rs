@@ -0,0 +1,298 @@
+//! Per-run limits on AI image generation
+//!
+//! A misconfigured run can regenerate covers for a whole directory. The budget
+//! caps the number of images generated in one run, either directly or through
+//! an estimated cost based on a static price table. Once the cap is reached,
+//! files that still need a cover are deferred to a later run, while files that
+//! already have one are uploaded as usual. Before a run, the same prices give
+//! an estimate of its cost (see [`CostEstimate`]).
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Approximate list price in USD of one generated cover, by image model
+///
+/// Only images are counted; the scene description text calls cost a small
+/// fraction of a cent and are left out.
+pub const IMAGE_PRICES: &[(&str, f64)] = &[
+    ("dall-e-2", 0.02),
+    ("dall-e-3", 0.08),
+    ("gpt-image-1", 0.063),
+    ("imagen-3.0-generate-002", 0.03),
+    ("imagen-4.0-fast-generate-001", 0.02),
+    ("imagen-4.0-generate-001", 0.04),
+    ("imagen-4.0-ultra-generate-001", 0.06),
+];
+
+/// Returns the price of one image from `model`
+///
+/// Models missing from [`IMAGE_PRICES`] are assumed to cost as much as the
+/// most expensive listed one, so a cost limit never lets more images through.
+pub fn image_price(model: &str) -> f64 {
+    IMAGE_PRICES
+        .iter()
+        .find(|(name, _)| *name == model)
+        .map(|(_, price)| *price)
+        .unwrap_or_else(|| IMAGE_PRICES.iter().map(|(_, price)| *price).fold(0.0, f64::max))
+}
+
+/// Price in USD of one scene description when none is configured
+pub const DEFAULT_DESCRIPTION_PRICE: f64 = 0.001;
+
+/// Prices in USD the AI cost of a run is estimated with
+///
+/// List prices change over time, so both can be set in the configuration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AiPrices {
+    /// Price of one image; the list price of the model (see [`image_price`]) if unset
+    pub image: Option<f64>,
+    /// Price of one scene description
+    pub description: f64,
+}
+
+impl Default for AiPrices {
+    fn default() -> Self {
+        Self {
+            image: None,
+            description: DEFAULT_DESCRIPTION_PRICE,
+        }
+    }
+}
+
+impl AiPrices {
+    /// Returns the price of one image from `model`
+    pub fn image_price(&self, model: &str) -> f64 {
+        self.image.unwrap_or_else(|| image_price(model))
+    }
+}
+
+/// AI usage a run is expected to have, estimated before it starts
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CostEstimate {
+    /// Files that need an AI cover
+    pub files: usize,
+    /// Images to generate
+    pub images: u32,
+    /// Scene descriptions to request
+    pub descriptions: u32,
+    /// Estimated cost in USD
+    pub cost: f64,
+}
+
+impl CostEstimate {
+    /// Adds a file needing `images` images at `image_price` each and, with a
+    /// `description_price`, one scene description
+    pub fn add_file(&mut self, images: u32, image_price: f64, description_price: Option<f64>) {
+        self.files += 1;
+        self.images += images;
+        self.cost += f64::from(images) * image_price;
+        if let Some(price) = description_price {
+            self.descriptions += 1;
+            self.cost += price;
+        }
+    }
+
+    /// Adds the files, images and cost of `other`
+    pub fn merge(&mut self, other: &CostEstimate) {
+        self.files += other.files;
+        self.images += other.images;
+        self.descriptions += other.descriptions;
+        self.cost += other.cost;
+    }
+
+    /// One-line summary, e.g. `2 file(s) need an AI cover: 2 image(s) and 2 scene description(s), about $0.16`
+    pub fn summary(&self) -> String {
+        format!(
+            "{} file(s) need an AI cover: {} image(s) and {} scene description(s), about ${:.2}",
+            self.files, self.images, self.descriptions, self.cost
+        )
+    }
+}
+
+/// Limits on AI image generation per run; zero means unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BudgetLimits {
+    /// Maximum number of generated images
+    pub max_images: u32,
+    /// Maximum estimated cost in USD
+    pub max_cost: f64,
+}
+
+impl BudgetLimits {
+    /// Returns the number of images allowed at `price` each, or `None` if unlimited
+    ///
+    /// With both limits set, the stricter one applies.
+    pub fn image_limit(&self, price: f64) -> Option<u32> {
+        let by_count = (self.max_images > 0).then_some(self.max_images);
+        let by_cost =
+            (self.max_cost > 0.0 && price > 0.0).then(|| (self.max_cost / price).floor() as u32);
+        match (by_count, by_cost) {
+            (Some(count), Some(cost)) => Some(count.min(cost)),
+            (count, cost) => count.or(cost),
+        }
+    }
+}
+
+/// Image generation budget of a run, shared by all of its concurrent tasks
+#[derive(Debug, Default)]
+pub struct AiBudget {
+    limit: Option<u32>,
+    generated: AtomicU32,
+}
+
+impl AiBudget {
+    /// Creates a budget for images costing `price` each
+    pub fn new(limits: BudgetLimits, price: f64) -> Self {
+        Self {
+            limit: limits.image_limit(price),
+            generated: AtomicU32::new(0),
+        }
+    }
+
+    /// Creates a budget without limits
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// Reserves one image, returning false once the limit is reached
+    ///
+    /// A reservation is kept even if the generation then fails, since the
+    /// provider may already have charged for it.
+    pub fn try_reserve(&self) -> bool {
+        match self.limit {
+            None => {
+                self.generated.fetch_add(1, Ordering::SeqCst);
+                true
+            }
+            Some(limit) => self
+                .generated
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| (n < limit).then_some(n + 1))
+                .is_ok(),
+        }
+    }
+
+    /// Reserves up to `count` images at once and returns how many were granted
+    pub fn reserve_up_to(&self, count: u32) -> u32 {
+        match self.limit {
+            None => {
+                self.generated.fetch_add(count, Ordering::SeqCst);
+                count
+            }
+            Some(limit) => self
+                .generated
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                    (n < limit).then(|| n + count.min(limit - n))
+                })
+                .map_or(0, |previous| count.min(limit - previous)),
+        }
+    }
+
+    /// Number of images reserved so far
+    pub fn generated(&self) -> u32 {
+        self.generated.load(Ordering::SeqCst)
+    }
+
+    /// Maximum number of images, or `None` if unlimited
+    pub fn limit(&self) -> Option<u32> {
+        self.limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_image_price() {
+        assert_eq!(image_price("dall-e-3"), 0.08);
+        assert_eq!(image_price("imagen-4.0-generate-001"), 0.04);
+        assert_eq!(image_price("some-future-model"), 0.08);
+    }
+
+    #[test]
+    fn test_configured_prices_and_estimate() {
+        let list = AiPrices::default();
+        assert_eq!(list.image_price("dall-e-3"), 0.08);
+        let configured = AiPrices {
+            image: Some(0.05),
+            description: 0.01,
+        };
+        assert_eq!(configured.image_price("dall-e-3"), 0.05);
+
+        let mut estimate = CostEstimate::default();
+        estimate.add_file(1, configured.image_price("dall-e-3"), Some(configured.description));
+        estimate.add_file(3, configured.image_price("dall-e-3"), None);
+        assert_eq!(estimate.files, 2);
+        assert_eq!(estimate.images, 4);
+        assert_eq!(estimate.descriptions, 1);
+        assert!((estimate.cost - 0.21).abs() < 1e-9);
+        assert_eq!(
+            estimate.summary(),
+            "2 file(s) need an AI cover: 4 image(s) and 1 scene description(s), about $0.21"
+        );
+    }
+
+    #[test]
+    fn test_image_limit() {
+        assert_eq!(BudgetLimits::default().image_limit(0.04), None);
+
+        let count = BudgetLimits {
+            max_images: 5,
+            max_cost: 0.0,
+        };
+        assert_eq!(count.image_limit(0.04), Some(5));
+
+        let cost = BudgetLimits {
+            max_images: 0,
+            max_cost: 1.0,
+        };
+        assert_eq!(cost.image_limit(0.08), Some(12));
+        assert_eq!(cost.image_limit(2.0), Some(0));
+
+        let both = BudgetLimits {
+            max_images: 5,
+            max_cost: 0.1,
+        };
+        assert_eq!(both.image_limit(0.04), Some(2));
+        assert_eq!(both.image_limit(0.01), Some(5));
+    }
+
+    #[test]
+    fn test_budget_is_shared_across_threads() {
+        let budget = Arc::new(AiBudget::new(
+            BudgetLimits {
+                max_images: 10,
+                max_cost: 0.0,
+            },
+            0.04,
+        ));
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let budget = Arc::clone(&budget);
+                std::thread::spawn(move || (0..5).filter(|_| budget.try_reserve()).count())
+            })
+            .collect();
+        let granted: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+
+        assert_eq!(granted, 10);
+        assert_eq!(budget.generated(), 10);
+        assert!(!budget.try_reserve());
+
+        let partial = AiBudget::new(
+            BudgetLimits {
+                max_images: 5,
+                max_cost: 0.0,
+            },
+            0.04,
+        );
+        assert_eq!(partial.reserve_up_to(3), 3);
+        assert_eq!(partial.reserve_up_to(3), 2);
+        assert_eq!(partial.reserve_up_to(3), 0);
+        assert_eq!(partial.generated(), 5);
+
+        let unlimited = AiBudget::unlimited();
+        assert!((0..100).all(|_| unlimited.try_reserve()));
+        assert_eq!(unlimited.limit(), None);
+    }
+}
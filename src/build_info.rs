@@ -0,0 +1,141 @@
+//! Build-time metadata
+//!
+//! Exposes the crate version, git commit, build date, target triple, enabled
+//! features and the versions of key dependencies compiled into the binary.
+//! The values are generated by `build.rs` using the `built` crate.
+
+use serde::Serialize;
+
+/// Raw values generated at build time
+#[allow(dead_code)]
+mod built_info {
+    include!(concat!(env!("OUT_DIR"), "/built.rs"));
+}
+
+/// Placeholder used when a value is not available at build time
+const UNKNOWN: &str = "unknown";
+
+/// Build metadata of the running binary
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct BuildInfo {
+    /// Crate version from `CARGO_PKG_VERSION`
+    pub version: &'static str,
+    /// Short git commit hash, with a `-dirty` suffix for uncommitted changes
+    pub git_commit: Option<String>,
+    /// Build timestamp (RFC 2822, UTC)
+    pub build_date: &'static str,
+    /// Target triple the binary was compiled for
+    pub target: &'static str,
+    /// Build profile (debug or release)
+    pub profile: &'static str,
+    /// Cargo features enabled for this crate
+    pub features: Vec<&'static str>,
+    /// Version of `wechat-pub-rs` compiled in
+    pub wechat_pub_rs: &'static str,
+    /// Version of `reqwest` compiled in
+    pub reqwest: &'static str,
+}
+
+impl BuildInfo {
+    /// Returns the build metadata of the current binary
+    pub fn current() -> Self {
+        let git_commit = built_info::GIT_COMMIT_HASH_SHORT.map(|hash| {
+            if built_info::GIT_DIRTY == Some(true) {
+                format!("{}-dirty", hash)
+            } else {
+                hash.to_string()
+            }
+        });
+
+        Self {
+            version: built_info::PKG_VERSION,
+            git_commit,
+            build_date: built_info::BUILT_TIME_UTC,
+            target: built_info::TARGET,
+            profile: built_info::PROFILE,
+            features: built_info::FEATURES_LOWERCASE
+                .iter()
+                .copied()
+                .filter(|feature| !feature.is_empty())
+                .collect(),
+            wechat_pub_rs: dependency_version("wechat-pub-rs").unwrap_or(UNKNOWN),
+            reqwest: dependency_version("reqwest").unwrap_or(UNKNOWN),
+        }
+    }
+
+    /// Formats the version output
+    ///
+    /// The short form is `wx-uploader <version>`; the verbose form adds the git
+    /// commit, build date, target, features and dependency versions, one per line.
+    pub fn render(&self, verbose: bool) -> String {
+        let mut output = format!("wx-uploader {}", self.version);
+
+        if verbose {
+            let features = if self.features.is_empty() {
+                "none".to_string()
+            } else {
+                self.features.join(", ")
+            };
+
+            output.push_str(&format!(
+                "\ngit commit:     {}\
+                 \nbuild date:     {}\
+                 \ntarget:         {}\
+                 \nprofile:        {}\
+                 \nfeatures:       {}\
+                 \nwechat-pub-rs:  {}\
+                 \nreqwest:        {}",
+                self.git_commit.as_deref().unwrap_or(UNKNOWN),
+                self.build_date,
+                self.target,
+                self.profile,
+                features,
+                self.wechat_pub_rs,
+                self.reqwest,
+            ));
+        }
+
+        output
+    }
+}
+
+/// Returns the resolved version of a dependency from the build's `Cargo.lock`
+pub fn dependency_version(name: &str) -> Option<&'static str> {
+    built_info::DEPENDENCIES
+        .iter()
+        .find(|(dep, _)| *dep == name)
+        .map(|(_, version)| *version)
+}
+
+/// Returns the version text printed by `-V`, optionally with full build details
+pub fn version_text(verbose: bool) -> String {
+    BuildInfo::current().render(verbose)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_text_short() {
+        let text = version_text(false);
+        assert_eq!(text, format!("wx-uploader {}", env!("CARGO_PKG_VERSION")));
+    }
+
+    #[test]
+    fn test_version_text_verbose_contains_details() {
+        let text = version_text(true);
+        assert!(text.contains(env!("CARGO_PKG_VERSION")));
+        assert!(text.contains("wechat-pub-rs:"));
+        assert!(text.contains("reqwest:"));
+        assert!(text.contains("target:"));
+        assert!(text.lines().count() > 1);
+    }
+
+    #[test]
+    fn test_dependency_versions_resolved() {
+        assert!(dependency_version("wechat-pub-rs").is_some());
+        assert!(dependency_version("reqwest").is_some());
+        assert!(dependency_version("definitely-not-a-dependency").is_none());
+    }
+}
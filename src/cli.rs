@@ -3,13 +3,23 @@
 //! This module handles argument parsing, colored help display, and CLI-specific
 //! functionality for the WeChat uploader tool.
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use colored::*;
-use std::path::PathBuf;
-use crate::models::{Config, ConfigFile, WeChatAccount, AiProviderConfig, GlobalSettings};
+use std::path::{Path, PathBuf};
+use tracing_appender::non_blocking::WorkerGuard;
+use crate::error::Error;
+use crate::models::{
+    AccountKind, AiProvider, AnchorLinks, Config, ConfigFile, CoverSelect, WeChatAccount, AiProviderConfig, GlobalSettings,
+    PipelineLimits, PublicationStatus, ThemeFallback, WikiLinks, MAX_COVER_VARIATIONS, env_secret,
+    Tone, code_highlighters, resolve_content_root, resolve_obsidian_vault, themes,
+};
+use crate::http_trace::{DEFAULT_TRACE_BODY_LIMIT, REDACTED};
+use crate::output::{FORMATTER, OutputFormatter, OutputMode};
+use crate::prompt::{PromptPolicy, Prompter};
+use crate::provenance::{self, ConfigSource, ShowConfigFormat};
 
 /// Command-line arguments for the wx-uploader application
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Default)]
 #[command(
     author,
     version,
@@ -35,16 +45,19 @@ EXAMPLES:
   Multi-account setup:
     wx-uploader --init-config config.yaml # Generate config template  
     wx-uploader -c config.yaml --list-accounts  # List available accounts
+    wx-uploader -c config.yaml --show-config    # Show the effective configuration
     wx-uploader -c config.yaml -a work ./posts  # Upload using 'work' account
 
   AI provider override:
     wx-uploader --provider gemini ./posts  # Use Gemini instead of OpenAI
     wx-uploader --ai-key custom_key ./posts # Override API key
+    wx-uploader --check-ai --skip-image    # Test the AI key and text model
 
 SUPPORTED THEMES: default, lapis, maize, orangeheart, phycat, pie, purple, rainbow
 SUPPORTED HIGHLIGHTERS: github, github-dark, vscode, atom-one-dark, atom-one-light, 
                         monokai, solarized-dark, solarized-light, vs, vs2015",
     override_help = "Run with --help to see colored help",
+    disable_version_flag = true,
     color = clap::ColorChoice::Always,
     styles = clap::builder::Styles::styled()
         .header(clap::builder::styling::AnsiColor::Yellow.on_default())
@@ -53,6 +66,10 @@ SUPPORTED HIGHLIGHTERS: github, github-dark, vscode, atom-one-dark, atom-one-lig
         .placeholder(clap::builder::styling::AnsiColor::Green.on_default())
 )]
 pub struct Args {
+    /// Command to run instead of an upload
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Path to markdown file or directory to upload
     #[arg(
         help = "Path to markdown file or directory to upload\n\
@@ -61,6 +78,18 @@ pub struct Args {
     )]
     pub path: Option<PathBuf>,
 
+    /// File listing exactly which articles to upload
+    #[arg(
+        long = "manifest",
+        value_name = "FILE",
+        help = "Upload exactly the articles listed in FILE, in its order, instead of a path\n\
+                • Text: one path per line; YAML (.yaml/.yml): paths or entries\n\
+                  with path, account and theme overrides\n\
+                • Paths are relative to the manifest's directory",
+        conflicts_with_all = ["path", "validate", "show_status", "normalize_frontmatter", "dry_run", "diff"]
+    )]
+    pub manifest: Option<PathBuf>,
+
     /// Enable verbose logging with detailed tracing information
     #[arg(
         short,
@@ -69,6 +98,15 @@ pub struct Args {
     )]
     pub verbose: bool,
 
+    /// Print version information
+    #[arg(
+        short = 'V',
+        long = "version",
+        help = "Print version information\n\
+                Combine with --verbose to include git commit, build date, target and dependency versions"
+    )]
+    pub show_version: bool,
+
     /// Force refresh WeChat access token before operation
     #[arg(
         short = 'r',
@@ -115,12 +153,48 @@ pub struct Args {
         long = "account",
         help = "WeChat account name to use from configuration file\n\
                 • Must exist in the accounts section of config file\n\
+                • @group uploads to every account of a config group\n\
+                • tag:TAG selects the one account tagged TAG\n\
                 • If omitted, uses default_account from config\n\
-                • Use --list-accounts to see available accounts",
+                • Use --list-accounts to see available accounts and groups",
         value_name = "NAME"
     )]
     pub account: Option<String>,
 
+    /// WeChat app ID of a one-off account, replacing the configured accounts
+    #[arg(
+        long = "app-id",
+        help = "WeChat app ID of a one-off account, used instead of any configured one\n\
+                • Requires --app-secret; no config file or WECHAT_* variables needed\n\
+                • Every file goes to this account, whatever its frontmatter says",
+        value_name = "ID",
+        requires = "app_secret",
+        conflicts_with = "account"
+    )]
+    pub app_id: Option<String>,
+
+    /// WeChat app secret of the account given with --app-id
+    #[arg(
+        long = "app-secret",
+        help = "WeChat app secret for --app-id\n\
+                • Discouraged in shared shells: the secret is kept in the shell history\n\
+                  and visible in process listings; pass it from a variable instead,\n\
+                  e.g. --app-secret \"$WECHAT_APP_SECRET_2\"",
+        value_name = "SECRET",
+        requires = "app_id"
+    )]
+    pub app_secret: Option<String>,
+
+    /// WeChat API base URL override
+    #[arg(
+        long = "wechat-base-url",
+        help = "Override the WeChat API base URL for all accounts\n\
+                • Useful for mock servers in tests or API gateways/proxies\n\
+                • Overrides wechat_base_url from the configuration file",
+        value_name = "URL"
+    )]
+    pub wechat_base_url: Option<String>,
+
     /// List all available WeChat accounts from config file
     #[arg(
         long = "list-accounts",
@@ -131,6 +205,15 @@ pub struct Args {
     )]
     pub list_accounts: bool,
 
+    /// List the themes and code highlighters articles can use
+    #[arg(
+        long = "list-themes",
+        help = "List the themes and code highlighters for the frontmatter theme and code\n\
+                fields, with a short description and whether each is light or dark, and exit",
+        conflicts_with = "path"
+    )]
+    pub list_themes: bool,
+
     /// Generate example configuration file template
     #[arg(
         long = "init-config",
@@ -142,6 +225,625 @@ pub struct Args {
         conflicts_with = "path"
     )]
     pub init_config: Option<PathBuf>,
+
+    /// Print the effective configuration and exit
+    #[arg(
+        long = "show-config",
+        value_name = "FORMAT",
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "text",
+        help = "Print the effective configuration and exit\n\
+                • Shows each value with its source: default, env, file or flag\n\
+                • Secrets are masked\n\
+                • --show-config=json prints JSON",
+        conflicts_with = "path"
+    )]
+    pub show_config: Option<ShowConfigFormat>,
+
+    /// Validate articles without uploading
+    #[arg(
+        long = "validate",
+        help = "Validate articles without uploading and exit\n\
+                • Reports relative links and images pointing at missing files\n\
+                • Warns about markdown WeChat renders poorly, like task lists\n\
+                • Does not require WeChat credentials\n\
+                • Exits with a non-zero status if problems are found"
+    )]
+    pub validate: bool,
+
+    /// Fail on duplicate frontmatter keys
+    #[arg(
+        long = "strict-frontmatter",
+        help = "Treat duplicate frontmatter keys as errors instead of warnings\n\
+                By default the last value wins and a warning lists both line numbers"
+    )]
+    pub strict_frontmatter: bool,
+
+    /// Fail files whose pre_upload hook fails
+    #[arg(
+        long = "strict-hooks",
+        help = "Fail a file whose pre_upload hook exits non-zero instead of skipping it\n\
+                • Hooks are set under hooks in the config settings\n\
+                • Overrides strict_hooks in the config settings"
+    )]
+    pub strict_hooks: bool,
+
+    /// Fail when environment variables disagree with the config file
+    #[arg(
+        long = "strict-config",
+        help = "Fail instead of warning when WECHAT_APP_ID, WECHAT_APP_SECRET or an AI API key\n\
+                in the environment disagrees with the configuration file"
+    )]
+    pub strict_config: bool,
+
+    /// Show what a run would do without uploading or writing files
+    #[arg(
+        long = "dry-run",
+        help = "Show which files would be uploaded or skipped without uploading,\n\
+                generating covers or modifying any file",
+        conflicts_with_all = ["validate", "show_status"]
+    )]
+    pub dry_run: bool,
+
+    /// Print the frontmatter changes a run would make
+    #[arg(
+        long = "diff",
+        help = "Print a unified diff of the frontmatter changes each file would\n\
+                receive, without uploading or modifying any file\n\
+                • Combine with --dry-run to also list uploaded and skipped files",
+        conflicts_with_all = ["validate", "show_status"]
+    )]
+    pub diff: bool,
+
+    /// Show the publish state of each file without uploading
+    #[arg(
+        long = "show-status",
+        help = "List each markdown file with its publish state and draft ID and exit\n\
+                • Shows which files a directory run would skip\n\
+                • Does not require WeChat credentials or an AI provider",
+        conflicts_with = "validate"
+    )]
+    pub show_status: bool,
+
+    /// Rewrite legacy `published` values to their canonical form
+    #[arg(
+        long = "normalize-frontmatter",
+        help = "Rewrite published values such as \"true\", yes or pending to true,\n\
+                false, draft or skip and exit\n\
+                • Only the published line changes; comments are kept\n\
+                • Files are backed up first, per the backup settings of --config",
+        conflicts_with_all = ["validate", "show_status", "dry_run", "diff"]
+    )]
+    pub normalize_frontmatter: bool,
+
+    /// JSON file the run summary is written to
+    #[arg(
+        long = "report-json",
+        value_name = "FILE",
+        help = "Write the summary of the upload run as JSON to FILE\n\
+                • Lists each file with its status, draft ID and cover source\n\
+                • Cover sources: existing, generated:<provider>, template, default, absent"
+    )]
+    pub report_json: Option<PathBuf>,
+
+    /// Fail the run if any warning was reported
+    #[arg(
+        long = "warnings-as-errors",
+        help = "Exit with code 6 if the upload run reported warnings, even though\n\
+                nothing failed\n\
+                • Warnings are listed in --report-json and --report"
+    )]
+    pub warnings_as_errors: bool,
+
+    /// Markdown file the run report is written to, `-` for stdout
+    #[arg(
+        long = "report",
+        value_name = "FILE",
+        help = "Write a Markdown report of the upload run to FILE, or to stdout for -\n\
+                • Account, time and version of the run\n\
+                • Uploaded articles with titles, draft IDs and covers, skipped files\n  \
+                  with reasons, failures with errors, and totals"
+    )]
+    pub report: Option<PathBuf>,
+
+    /// File the rendered HTML of a single article is written to, `-` for stdout
+    #[arg(
+        long = "dump-html",
+        value_name = "FILE",
+        help = "Write the HTML a single file is rendered to for WeChat to FILE,\n\
+                or to stdout for -, then upload it\n\
+                • Starts with a comment naming the resolved theme and code highlighter\n\
+                • Local images keep their paths\n\
+                • Combine with --dry-run to write it without uploading",
+        conflicts_with_all = ["manifest", "validate", "show_status", "normalize_frontmatter"]
+    )]
+    pub dump_html: Option<PathBuf>,
+
+    /// How errors, warnings and progress are printed
+    #[arg(
+        long = "output",
+        value_name = "MODE",
+        value_enum,
+        default_value_t = OutputMode::Text,
+        help = "Output format for --validate and uploads\n\
+                • text: colored messages (default)\n\
+                • github: GitHub Actions ::error/::warning annotations with file and line;\n  \
+                progress output is suppressed"
+    )]
+    pub output: OutputMode,
+
+    /// Also check remote links during validation
+    #[arg(
+        long = "check-remote-links",
+        help = "Check remote http(s) links with HEAD requests during --validate",
+        requires = "validate"
+    )]
+    pub check_remote_links: bool,
+
+    /// Build cover scene descriptions locally instead of asking the AI
+    #[arg(
+        long = "no-ai-text",
+        help = "Build cover prompts from the title, description and headings\n\
+                instead of asking the AI for a scene description (one API call per cover)"
+    )]
+    pub no_ai_text: bool,
+
+    /// Stream scene descriptions and print their tokens as they arrive
+    #[arg(
+        long = "stream-scene",
+        help = "Stream AI scene descriptions and print them as they arrive (with --verbose)\n\
+                Falls back to a regular request if the provider or gateway cannot stream"
+    )]
+    pub stream_scene: bool,
+
+    /// Check the AI key and models and exit
+    #[arg(
+        long = "check-ai",
+        help = "Check that the AI provider accepts the API key and serves the models, then exit\n\
+                • Sends a tiny scene description request and one image request\n\
+                • Reports the latency of each and any key or model errors\n\
+                • Processes no files; the API key is redacted from the output",
+        conflicts_with = "path"
+    )]
+    pub check_ai: bool,
+
+    /// Skip the image request of --check-ai
+    #[arg(
+        long = "skip-image",
+        help = "Skip the image request of --check-ai, which is billed like a cover",
+        requires = "check_ai"
+    )]
+    pub skip_image: bool,
+
+    /// Locate risky text after a content security rejection
+    #[arg(
+        long = "diagnose-content",
+        help = "When WeChat rejects an article as risky (errcode 87014), bisect it with\n\
+                msg_sec_check and report the flagged heading and line range\n\
+                (costs extra API calls)"
+    )]
+    pub diagnose_content: bool,
+
+    /// Match files against existing WeChat drafts before uploading
+    #[arg(
+        long = "reconcile",
+        help = "Before uploading, fetch the account's recent drafts and skip files whose\n\
+                title already has one, writing the recovered draft ID to the frontmatter\n\
+                • For when local state and WeChat have drifted apart\n\
+                • With --dry-run, shows which files would be matched"
+    )]
+    pub reconcile: bool,
+
+    /// Leave source files untouched
+    #[arg(
+        long = "no-write-back",
+        help = "Never modify the markdown files (for read-only content stores)\n\
+                • Publish state and draft IDs are recorded in --state-file instead\n\
+                • Directory runs skip files the state file records as uploaded\n\
+                • Generated covers are written to a temporary directory"
+    )]
+    pub no_write_back: bool,
+
+    /// Leave the published field untouched
+    #[arg(
+        long = "no-status-update",
+        help = "Do not set 'published: draft' and the draft ID after an upload\n\
+                • For workflows that manage the published field themselves\n\
+                • Uploads are recorded in --state-file instead\n\
+                • Generated covers are still written back"
+    )]
+    pub no_status_update: bool,
+
+    /// State file used with --no-write-back or --no-status-update
+    #[arg(
+        long = "state-file",
+        value_name = "PATH",
+        help = "JSON file recording uploads when write-back or status updates are disabled\n\
+                [default: .wx-uploader/state.json]"
+    )]
+    pub state_file: Option<PathBuf>,
+
+    /// Derive slugs from titles for files without one
+    #[arg(
+        long = "auto-slug",
+        help = "Give files without a slug one derived from the title and save it\n\
+                • Chinese titles are transliterated to pinyin\n\
+                • Generated covers are named after the slug, not the file name\n\
+                • Collisions in a directory run get -2, -3, ... in path order"
+    )]
+    pub auto_slug: bool,
+
+    /// What to do with files whose theme or code highlighter is unknown
+    #[arg(
+        long = "theme-fallback",
+        value_name = "POLICY",
+        value_enum,
+        help = "What to do with a file whose theme or code highlighter is unknown\n\
+                • error: fail the file (default)\n\
+                • default: warn and use the configured default_theme / default_code_highlighter\n\
+                • skip: warn and skip the file"
+    )]
+    pub theme_fallback: Option<ThemeFallback>,
+
+    /// Which image to use when `cover` names a directory
+    #[arg(
+        long = "cover-select",
+        value_name = "POLICY",
+        value_enum,
+        help = "Which image to use when a file's cover names a directory of candidates\n\
+                • newest: the most recently modified image (default)\n\
+                • first: the first image by file name\n\
+                • random: any image, picked at random\n\
+                • Overrides cover_select in the config settings"
+    )]
+    pub cover_select: Option<CoverSelect>,
+
+    /// What to do with links to headings of the same article
+    #[arg(
+        long = "anchor-links",
+        value_name = "MODE",
+        value_enum,
+        help = "What to do with links to headings of the same article, which WeChat cannot follow\n\
+                • keep: upload them unchanged (default)\n\
+                • text: upload each link as its plain text\n\
+                • Overrides anchor_links in the config settings"
+    )]
+    pub anchor_links: Option<AnchorLinks>,
+
+    /// Convert Obsidian embeds and wiki links on upload
+    #[arg(
+        long = "obsidian",
+        help = "Convert Obsidian syntax in the uploaded copy of each article\n\
+                • ![[image.png]] embeds become images, resolved against the vault\n\
+                • [[Note]] links become plain text or numbered notes (see --wiki-links)\n\
+                • Code blocks are left alone; the markdown files are not changed\n\
+                • Overrides obsidian in the config settings"
+    )]
+    pub obsidian: bool,
+
+    /// Vault root Obsidian embeds are resolved against
+    #[arg(
+        long = "obsidian-vault",
+        value_name = "DIR",
+        help = "Resolve ![[image]] embeds against the Obsidian vault in DIR\n\
+                • Defaults to content_root, or else the article's directory\n\
+                • Overrides obsidian_vault in the config settings"
+    )]
+    pub obsidian_vault: Option<PathBuf>,
+
+    /// What to do with Obsidian wiki links
+    #[arg(
+        long = "wiki-links",
+        value_name = "MODE",
+        value_enum,
+        help = "What --obsidian does with [[Note]] links\n\
+                • text: upload each link as its alias or note name (default)\n\
+                • footnote: number each link and list the notes at the end\n\
+                • Overrides wiki_links in the config settings"
+    )]
+    pub wiki_links: Option<WikiLinks>,
+
+    /// Only process files in this publish state
+    #[arg(
+        long = "only",
+        value_name = "STATE",
+        value_enum,
+        help = "Only process the files of a directory in this publish state\n\
+                • unpublished: never uploaded\n\
+                • draft: uploaded as a draft (published: draft)\n\
+                • published: published: true; reported as skipped, never re-uploaded"
+    )]
+    pub only: Option<PublicationStatus>,
+
+    /// Descend into symlinked directories
+    #[arg(
+        long = "follow-symlinks",
+        help = "Descend into symlinked directories when processing a directory\n\
+                • A file reachable through several links is uploaded once\n\
+                • Broken symlinks are reported as warnings and skipped"
+    )]
+    pub follow_symlinks: bool,
+
+    /// Bypass the scene description cache
+    #[arg(
+        long = "no-cache",
+        help = "Ask the AI for every scene description instead of reusing cached ones\n\
+                • Descriptions are cached by a hash of the article content, language and prompt\n\
+                • The cache lives in cache_dir [default: <temp dir>/wx-uploader/cache]"
+    )]
+    pub no_cache: bool,
+
+    /// Fail files that have no cover image after cover processing
+    #[arg(
+        long = "require-cover",
+        help = "Fail files that still have no cover image after cover processing\n\
+                • Catches failed generation, which otherwise uploads without a cover\n\
+                • The cover file must exist; `cover: none` fails as well"
+    )]
+    pub require_cover: bool,
+
+    /// Stop the run at the first failed file
+    #[arg(
+        long = "fail-fast",
+        help = "Stop the run at the first failed file\n\
+                • Later files are not processed and not listed in the summary"
+    )]
+    pub fail_fast: bool,
+
+    /// List each already published file that was skipped
+    #[arg(
+        long = "show-skipped",
+        help = "List each already published file a directory run skips\n\
+                • By default they are counted in one line at the end of the run"
+    )]
+    pub show_skipped: bool,
+
+    /// Upload outside the configured allowed hours
+    #[arg(
+        long = "ignore-schedule",
+        help = "Upload even outside the allowed_hours window of the config file"
+    )]
+    pub ignore_schedule: bool,
+
+    /// Do not back up files before modifying them
+    #[arg(
+        long = "no-backup",
+        help = "Do not copy files to .wx-uploader/backup before the run first modifies them\n\
+                • Overrides backup in the config settings"
+    )]
+    pub no_backup: bool,
+
+    /// Maximum number of AI images generated in this run
+    #[arg(
+        long = "max-images",
+        value_name = "N",
+        help = "Generate at most N AI images in this run (0: unlimited)\n\
+                • Files that still need a cover afterwards are reported as cover deferred\n\
+                • Files that already have covers are still uploaded\n\
+                • Overrides max_ai_images_per_run in the config settings"
+    )]
+    pub max_images: Option<u32>,
+
+    /// Answer yes to confirmations
+    #[arg(
+        short = 'y',
+        long = "yes",
+        visible_alias = "assume-yes",
+        global = true,
+        help = "Answer every question with its non-destructive default instead of asking\n\
+                • The first cover candidate is used\n\
+                • Runs whose estimated AI cost exceeds confirm_ai_cost_above stop\n\
+                  (--accept-ai-cost starts them)\n\
+                • restore leaves the files alone (restore --force overwrites them)"
+    )]
+    pub assume_yes: bool,
+
+    /// Start runs over the AI cost threshold without asking
+    #[arg(
+        long = "accept-ai-cost",
+        help = "Start a run whose estimated AI cost exceeds confirm_ai_cost_above\n\
+                without asking"
+    )]
+    pub accept_ai_cost: bool,
+
+    /// Fail instead of asking
+    #[arg(
+        long = "no-input",
+        global = true,
+        help = "Fail wherever a question would be asked instead of waiting for\n\
+                an answer, e.g. in scripts"
+    )]
+    pub no_input: bool,
+
+    /// Ask even without a terminal
+    #[arg(
+        long = "interactive",
+        global = true,
+        help = "Ask questions even when standard input is not a terminal,\n\
+                reading the answers from it"
+    )]
+    pub interactive: bool,
+
+    /// Seconds to wait for another run over the same directory
+    #[arg(
+        long = "lock-wait",
+        value_name = "SECS",
+        help = "Wait up to SECS seconds for another run over the same directory\n\
+                to finish instead of failing right away (directory runs only)"
+    )]
+    pub lock_wait: Option<u64>,
+
+    /// Take over the lock of another run over the same directory
+    #[arg(
+        long = "force-lock",
+        help = "Take over the lock of a directory even if the run holding it seems\n\
+                alive, e.g. a stale lock whose PID was reused (directory runs only)"
+    )]
+    pub force_lock: bool,
+
+    /// Retries of a request rejected for the rate limit
+    #[arg(
+        long = "max-retries",
+        value_name = "N",
+        help = "Retry a WeChat upload or AI request rejected for the rate limit\n\
+                up to N times (default: 3); 0 fails right away\n\
+                • A Retry-After header sets the wait, up to max_retry_delay seconds"
+    )]
+    pub max_retries: Option<u32>,
+
+    /// Milliseconds each upload worker pauses after creating a draft
+    #[arg(
+        long = "delay",
+        value_name = "MS",
+        help = "Pause MS milliseconds after each created draft before the next upload\n\
+                • Skipped files do not wait; with --pipeline each concurrent upload pauses\n\
+                • Overrides upload_delay_ms in the config settings"
+    )]
+    pub delay: Option<u64>,
+
+    /// Number of candidate covers generated per missing cover
+    #[arg(
+        long = "cover-variations",
+        value_name = "N",
+        value_parser = clap::builder::RangedU64ValueParser::<u32>::new()
+            .range(1..=MAX_COVER_VARIATIONS as u64),
+        help = "Generate N candidate covers for a missing cover and pick one\n\
+                • Saved as <stem>-cover-1.png ... <stem>-cover-N.png next to the article\n\
+                • In a terminal you are asked which one becomes the cover; otherwise the first\n\
+                • Each candidate counts against --max-images; frontmatter cover_variations wins"
+    )]
+    pub cover_variations: Option<u32>,
+
+    /// Directory that cover paths starting with / are resolved against
+    #[arg(
+        long = "content-root",
+        value_name = "DIR",
+        help = "Resolve cover and thumb paths starting with / against DIR\n\
+                • e.g. /assets/covers/x.png becomes DIR/assets/covers/x.png\n\
+                • Lets a run process a subdirectory of a static site repository\n\
+                • Overrides content_root in the config settings"
+    )]
+    pub content_root: Option<PathBuf>,
+
+    /// Losslessly recompress PNG covers before upload
+    #[arg(
+        long = "optimize-covers",
+        help = "Losslessly recompress PNG covers and thumbnails before upload\n\
+                • Files under 256 KB are left alone\n\
+                • The result is checked pixel by pixel before it replaces the file"
+    )]
+    pub optimize_covers: bool,
+
+    /// Embed provenance metadata in generated PNG covers
+    #[arg(
+        long = "embed-provenance",
+        help = "Record the AI provider, model, prompt and time in generated PNG covers\n\
+                • Written as PNG text chunks; the image data is unchanged\n\
+                • JPEG and WebP covers are saved without it"
+    )]
+    pub embed_provenance: bool,
+
+    /// Convert existing covers to the configured cover format
+    #[arg(
+        long = "convert-covers",
+        help = "Convert existing covers to cover_format and update their frontmatter\n\
+                • The original image is replaced by one with the new extension\n\
+                • Requires write-back; covers already in the format are left alone"
+    )]
+    pub convert_covers: bool,
+
+    /// Generate covers while earlier files upload
+    #[arg(
+        long = "pipeline",
+        help = "Generate covers for upcoming files while earlier files upload\n\
+                • Directory runs only; the report keeps walk order\n\
+                • Concurrency is set with --ai-jobs and --upload-jobs"
+    )]
+    pub pipeline: bool,
+
+    /// Files prepared at once in pipelined mode
+    #[arg(
+        long = "ai-jobs",
+        value_name = "N",
+        default_value_t = 2,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
+        help = "With --pipeline, number of files whose covers are generated at once",
+        requires = "pipeline"
+    )]
+    pub ai_jobs: usize,
+
+    /// Concurrent WeChat uploads in pipelined mode
+    #[arg(
+        long = "upload-jobs",
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
+        help = "With --pipeline, number of concurrent WeChat uploads",
+        requires = "pipeline"
+    )]
+    pub upload_jobs: usize,
+
+    /// Log AI provider HTTP traffic with secrets redacted
+    #[arg(
+        long = "trace-http",
+        help = "Log AI provider requests, responses and image downloads at debug level\n\
+                • API keys are masked in URLs and headers\n\
+                • Bodies are truncated to --trace-body-limit bytes"
+    )]
+    pub trace_http: bool,
+
+    /// Maximum body bytes logged per traced request or response
+    #[arg(
+        long = "trace-body-limit",
+        value_name = "BYTES",
+        default_value_t = DEFAULT_TRACE_BODY_LIMIT,
+        help = "Maximum number of body bytes logged per request or response",
+        requires = "trace_http"
+    )]
+    pub trace_body_limit: usize,
+
+    /// Format of log lines
+    #[arg(
+        long = "log-format",
+        value_name = "FORMAT",
+        value_enum,
+        default_value_t = LogFormat::Text,
+        help = "Format of log lines\n\
+                • text: human-readable lines (default)\n\
+                • json: one JSON object per line, for log collectors"
+    )]
+    pub log_format: LogFormat,
+
+    /// File log lines are appended to instead of stdout
+    #[arg(
+        long = "log-file",
+        value_name = "FILE",
+        help = "Append log lines to FILE instead of printing them\n\
+                Logs at the --verbose level even without --verbose"
+    )]
+    pub log_file: Option<PathBuf>,
+}
+
+/// Commands run instead of an upload
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Copy a backup set back over the working tree
+    Restore {
+        /// Timestamp of the backup set, e.g. 20261017-153045
+        #[arg(long = "from", value_name = "TIMESTAMP")]
+        from: String,
+        /// Overwrite the files without asking
+        #[arg(long = "force")]
+        force: bool,
+    },
+    /// Re-attempt the uploads that failed in earlier runs
+    Retry {
+        /// Failed attempts after which a file leaves the queue
+        #[arg(long = "max-attempts", value_name = "N")]
+        max_attempts: Option<u32>,
+    },
 }
 
 /// Print colored help message with detailed information about usage and features
@@ -178,6 +880,17 @@ pub fn print_colored_help() {
     );
     println!();
 
+    println!("{}", "Commands:".bright_yellow().bold());
+    println!(
+        "  {}    Copy backup set TIMESTAMP from .wx-uploader/backup back over the working tree",
+        "restore --from <TIMESTAMP> [--force]".bright_cyan()
+    );
+    println!(
+        "  {}    Re-attempt the failed uploads queued in .wx-uploader/retry.json",
+        "retry [--max-attempts <N>]".bright_cyan()
+    );
+    println!();
+
     println!("{}", "Options:".bright_yellow().bold());
     println!();
     println!("  {}", "BASIC OPTIONS:".bright_white());
@@ -197,7 +910,7 @@ pub fn print_colored_help() {
         "--help".bright_cyan()
     );
     println!(
-        "    {}, {}       Print version information",
+        "    {}, {}       Print version information (add -v for build details)",
         "-V".bright_cyan(),
         "--version".bright_cyan()
     );
@@ -211,20 +924,182 @@ pub fn print_colored_help() {
         "<FILE>".bright_green()
     );
     println!(
-        "    {}, {} {}  WeChat account name from config file",
+        "    {}, {} {}  WeChat account name, @group for several, or tag:TAG, from config file",
         "-a".bright_cyan(),
         "--account".bright_cyan(),
         "<NAME>".bright_green()
     );
+    println!(
+        "    {} {}           WeChat app ID of a one-off account instead of the configured ones",
+        "--app-id".bright_cyan(),
+        "<ID>".bright_green()
+    );
+    println!(
+        "    {} {}   Its app secret; avoid in shared shells, it lands in the history",
+        "--app-secret".bright_cyan(),
+        "<SECRET>".bright_green()
+    );
+    println!(
+        "    {} {} WeChat API base URL (mock server or gateway)",
+        "--wechat-base-url".bright_cyan(),
+        "<URL>".bright_green()
+    );
     println!(
         "    {}       List available accounts from config file",
         "--list-accounts".bright_cyan()
     );
+    println!(
+        "    {}         List themes and code highlighters",
+        "--list-themes".bright_cyan()
+    );
     println!(
         "    {} {} Generate example configuration file",
         "--init-config".bright_cyan(),
         "<FILE>".bright_green()
     );
+    println!(
+        "    {}{} Show the effective configuration and where each value comes from",
+        "--show-config".bright_cyan(),
+        "[=json]".bright_green()
+    );
+    println!();
+
+    println!("  {}", "VALIDATION OPTIONS:".bright_white());
+    println!(
+        "    {}            Check articles for broken links without uploading",
+        "--validate".bright_cyan()
+    );
+    println!(
+        "    {}         List publish state and draft ID of each file",
+        "--show-status".bright_cyan()
+    );
+    println!(
+        "    {} Rewrite legacy published values such as \"true\" to canonical ones",
+        "--normalize-frontmatter".bright_cyan()
+    );
+    println!(
+        "    {}             Show what would be uploaded without changing anything",
+        "--dry-run".bright_cyan()
+    );
+    println!(
+        "    {}                Preview frontmatter changes without changing anything",
+        "--diff".bright_cyan()
+    );
+    println!(
+        "    {}  Also HEAD remote http(s) links (with --validate)",
+        "--check-remote-links".bright_cyan()
+    );
+    println!(
+        "    {} {}  Write the run summary as JSON to FILE",
+        "--report-json".bright_cyan(),
+        "<FILE>".bright_green()
+    );
+    println!(
+        "    {}  Exit with code 6 if the run reported warnings",
+        "--warnings-as-errors".bright_cyan()
+    );
+    println!(
+        "    {} {}       Write a Markdown run report to FILE (- for stdout)",
+        "--report".bright_cyan(),
+        "<FILE>".bright_green()
+    );
+    println!(
+        "    {} {}    Write the rendered HTML of a single file to FILE (- for stdout)",
+        "--dump-html".bright_cyan(),
+        "<FILE>".bright_green()
+    );
+    println!(
+        "    {} {}       Output format: text, or github for Actions annotations",
+        "--output".bright_cyan(),
+        "<MODE>".bright_green()
+    );
+    println!(
+        "    {}  Fail on duplicate frontmatter keys",
+        "--strict-frontmatter".bright_cyan()
+    );
+    println!(
+        "    {}        Fail files whose pre_upload hook fails instead of skipping",
+        "--strict-hooks".bright_cyan()
+    );
+    println!(
+        "    {}       Fail when WECHAT_* or AI key env vars disagree with the config",
+        "--strict-config".bright_cyan()
+    );
+    println!(
+        "    {} {} Unknown theme or highlighter: error, default or skip",
+        "--theme-fallback".bright_cyan(),
+        "<POLICY>".bright_green()
+    );
+    println!(
+        "    {} {}   Image of a cover directory: newest, first or random",
+        "--cover-select".bright_cyan(),
+        "<POLICY>".bright_green()
+    );
+    println!(
+        "    {} {}     Links to headings: keep, or text to unlink them",
+        "--anchor-links".bright_cyan(),
+        "<MODE>".bright_green()
+    );
+    println!(
+        "    {}            Convert Obsidian ![[image]] embeds and [[Note]] links",
+        "--obsidian".bright_cyan()
+    );
+    println!(
+        "    {} {}  Vault root embeds are resolved against",
+        "--obsidian-vault".bright_cyan(),
+        "<DIR>".bright_green()
+    );
+    println!(
+        "    {} {}       Wiki links with --obsidian: text or footnote",
+        "--wiki-links".bright_cyan(),
+        "<MODE>".bright_green()
+    );
+    println!(
+        "    {} {}            Only process files that are unpublished, draft or published",
+        "--only".bright_cyan(),
+        "<STATE>".bright_green()
+    );
+    println!(
+        "    {} {}        Upload exactly the files listed in FILE, in its order",
+        "--manifest".bright_cyan(),
+        "<FILE>".bright_green()
+    );
+    println!(
+        "    {}     Descend into symlinked directories, uploading each file once",
+        "--follow-symlinks".bright_cyan()
+    );
+    println!(
+        "    {}            Ask the AI again instead of reusing cached scene descriptions",
+        "--no-cache".bright_cyan()
+    );
+    println!(
+        "    {}       Fail files that have no cover image after cover processing",
+        "--require-cover".bright_cyan()
+    );
+    println!(
+        "    {}           Stop the run at the first failed file",
+        "--fail-fast".bright_cyan()
+    );
+    println!(
+        "    {}        List each skipped already published file",
+        "--show-skipped".bright_cyan()
+    );
+    println!(
+        "    {}     Upload even outside the configured allowed_hours",
+        "--ignore-schedule".bright_cyan()
+    );
+    println!(
+        "    {}           Do not back up files before the run modifies them",
+        "--no-backup".bright_cyan()
+    );
+    println!(
+        "    {}    Locate risky text after an errcode 87014 rejection",
+        "--diagnose-content".bright_cyan()
+    );
+    println!(
+        "    {}           Skip files that already have a draft on WeChat",
+        "--reconcile".bright_cyan()
+    );
     println!();
 
     println!("  {}", "AI PROVIDER OPTIONS:".bright_white());
@@ -238,6 +1113,135 @@ pub fn print_colored_help() {
         "--ai-key".bright_cyan(),
         "<KEY>".bright_green()
     );
+    println!(
+        "    {}          Build cover prompts locally, skipping the AI text call",
+        "--no-ai-text".bright_cyan()
+    );
+    println!(
+        "    {}        Stream scene descriptions as they arrive (with --verbose)",
+        "--stream-scene".bright_cyan()
+    );
+    println!(
+        "    {}            Check the AI key and models, then exit",
+        "--check-ai".bright_cyan()
+    );
+    println!(
+        "    {}          Skip the billed image request (with --check-ai)",
+        "--skip-image".bright_cyan()
+    );
+    println!(
+        "    {} {}      Generate at most N AI images per run (0: unlimited)",
+        "--max-images".bright_cyan(),
+        "<N>".bright_green()
+    );
+    println!(
+        "    {}, {}             Answer every question with its non-destructive default (alias: --assume-yes)",
+        "-y".bright_cyan(),
+        "--yes".bright_cyan()
+    );
+    println!(
+        "    {}        Start runs over confirm_ai_cost_above without asking",
+        "--accept-ai-cost".bright_cyan()
+    );
+    println!(
+        "    {}             Fail wherever a question would be asked",
+        "--no-input".bright_cyan()
+    );
+    println!(
+        "    {}          Ask even when standard input is not a terminal",
+        "--interactive".bright_cyan()
+    );
+    println!(
+        "    {} {}      Wait for another run over the same directory to finish",
+        "--lock-wait".bright_cyan(),
+        "<SECS>".bright_green()
+    );
+    println!(
+        "    {}            Take over a lock that seems held by another run",
+        "--force-lock".bright_cyan()
+    );
+    println!(
+        "    {} {}       Retry rate-limited requests up to N times (0: never)",
+        "--max-retries".bright_cyan(),
+        "<N>".bright_green()
+    );
+    println!(
+        "    {} {}            Pause MS milliseconds after each created draft",
+        "--delay".bright_cyan(),
+        "<MS>".bright_green()
+    );
+    println!(
+        "    {} {} Generate N candidate covers and pick one",
+        "--cover-variations".bright_cyan(),
+        "<N>".bright_green()
+    );
+    println!(
+        "    {} {}  Resolve covers starting with / against DIR",
+        "--content-root".bright_cyan(),
+        "<DIR>".bright_green()
+    );
+    println!(
+        "    {}       Never modify markdown files; record state externally",
+        "--no-write-back".bright_cyan()
+    );
+    println!(
+        "    {}    Leave the published field untouched after uploads",
+        "--no-status-update".bright_cyan()
+    );
+    println!(
+        "    {} {}   State file used with --no-write-back or --no-status-update",
+        "--state-file".bright_cyan(),
+        "<PATH>".bright_green()
+    );
+    println!(
+        "    {}           Derive and save slugs for files without one",
+        "--auto-slug".bright_cyan()
+    );
+    println!(
+        "    {}     Losslessly recompress PNG covers before upload",
+        "--optimize-covers".bright_cyan()
+    );
+    println!(
+        "    {}    Record provider, model and prompt in generated PNG covers",
+        "--embed-provenance".bright_cyan()
+    );
+    println!(
+        "    {}      Convert existing covers to the configured cover_format",
+        "--convert-covers".bright_cyan()
+    );
+    println!(
+        "    {}            Generate covers while earlier files upload",
+        "--pipeline".bright_cyan()
+    );
+    println!(
+        "    {} {}         Covers generated at once with --pipeline (default: 2)",
+        "--ai-jobs".bright_cyan(),
+        "<N>".bright_green()
+    );
+    println!(
+        "    {} {}     Concurrent uploads with --pipeline (default: 1)",
+        "--upload-jobs".bright_cyan(),
+        "<N>".bright_green()
+    );
+    println!(
+        "    {}          Log AI HTTP traffic (debug level, secrets masked)",
+        "--trace-http".bright_cyan()
+    );
+    println!(
+        "    {} {}  Max body bytes per traced message (default: 2048)",
+        "--trace-body-limit".bright_cyan(),
+        "<BYTES>".bright_green()
+    );
+    println!(
+        "    {} {}   Log lines as text (default) or json",
+        "--log-format".bright_cyan(),
+        "<FORMAT>".bright_green()
+    );
+    println!(
+        "    {} {}       Append log lines to FILE instead of stdout",
+        "--log-file".bright_cyan(),
+        "<FILE>".bright_green()
+    );
     println!();
 
     println!("{}", "CONFIGURATION:".bright_magenta().bold());
@@ -245,6 +1249,7 @@ pub fn print_colored_help() {
     println!("  {}", "Environment Variables (single account):".bright_white());
     println!("    {} {}      WeChat application ID", "WECHAT_APP_ID".bright_cyan(), "(required)".bright_red());
     println!("    {} {}   WeChat application secret", "WECHAT_APP_SECRET".bright_cyan(), "(required)".bright_red());
+    println!("    {}     Appending {} reads a secret from that file instead", "•".bright_white(), "_FILE".bright_cyan());
     println!("    {} {}    OpenAI API key for cover generation", "OPENAI_API_KEY".bright_cyan(), "(optional)".bright_blue());
     println!("    {} {}     Gemini API key for cover generation", "GEMINI_API_KEY".bright_cyan(), "(optional)".bright_blue());
     println!("    {} {}      AI provider preference (openai|gemini)", "AI_PROVIDER".bright_cyan(), "(optional)".bright_blue());
@@ -257,6 +1262,16 @@ pub fn print_colored_help() {
     println!("    {} Use {} to generate example file", "•".bright_white(), "--init-config".bright_cyan());
     println!();
 
+    println!("{}", "EXIT CODES:".bright_magenta().bold());
+    println!();
+    println!("    {}  Success (including nothing to upload)", "0".bright_green());
+    println!("    {}  Configuration or usage error", "2".bright_red());
+    println!("    {}  Partial failure: some files failed (or --validate found problems)", "3".bright_yellow());
+    println!("    {}  WeChat authentication failure", "4".bright_red());
+    println!("    {}  All files failed", "5".bright_red());
+    println!("    {}  Warnings were reported (with --warnings-as-errors)", "6".bright_yellow());
+    println!();
+
     println!("{}", "EXAMPLES:".bright_blue().bold());
     println!();
     println!("  {}", "Basic usage:".bright_white());
@@ -275,6 +1290,11 @@ pub fn print_colored_help() {
         "wx-uploader --verbose ./posts".bright_white().bold(),
         "#".bright_black()
     );
+    println!(
+        "    {} {} Undo the changes of a run",
+        "wx-uploader restore --from 20261017-153045".bright_white().bold(),
+        "#".bright_black()
+    );
     println!();
 
     println!("  {}", "Multi-account setup:".bright_white());
@@ -293,6 +1313,11 @@ pub fn print_colored_help() {
         "wx-uploader -c config.yaml -a work ./posts".bright_white().bold(),
         "#".bright_black()
     );
+    println!(
+        "    {} {} Upload to every account of a group",
+        "wx-uploader -c config.yaml -a @all ./post.md".bright_white().bold(),
+        "#".bright_black()
+    );
     println!("    {} {} Upload using default account",
         "wx-uploader -c config.yaml ./posts".bright_white().bold(),
         "#".bright_black()
@@ -310,6 +1335,11 @@ pub fn print_colored_help() {
         "wx-uploader --ai-key custom_key ./posts".bright_white().bold(),
         "#".bright_black()
     );
+    println!(
+        "    {}     {} Test the AI key without generating an image",
+        "wx-uploader --check-ai --skip-image".bright_white().bold(),
+        "#".bright_black()
+    );
     println!();
 
     println!("{}", "FRONTMATTER THEMING:".bright_green().bold());
@@ -350,12 +1380,40 @@ pub fn print_colored_help() {
 
 /// Validates command-line arguments
 pub fn validate_args(args: &Args) -> Result<(), String> {
+    if args.interactive && args.no_input {
+        return Err("--interactive and --no-input cannot be used together".to_string());
+    }
+    if args.interactive && args.assume_yes {
+        return Err("--interactive and --yes cannot be used together".to_string());
+    }
+
+    // A retry uploads the queued files, so it needs the same configuration
+    if let Some(Command::Retry { .. }) = &args.command {
+        return validate_config_args(args);
+    }
+
     // Skip path validation for special commands
-    if args.list_accounts || args.init_config.is_some() {
+    if args.list_accounts
+        || args.list_themes
+        || args.init_config.is_some()
+        || args.show_config.is_some()
+        || args.check_ai
+        || args.command.is_some()
+    {
         return Ok(());
     }
 
-    let path = args.path.as_ref().ok_or("Path is required for upload operations")?;
+    if let Some(manifest) = &args.manifest {
+        if !manifest.is_file() {
+            return Err(format!("Manifest file does not exist: {}", manifest.display()));
+        }
+        return validate_config_args(args);
+    }
+
+    let path = args
+        .path
+        .as_ref()
+        .ok_or("Path is required for upload, validation and status operations")?;
 
     if !path.exists() {
         return Err(format!("Path does not exist: {}", path.display()));
@@ -368,6 +1426,18 @@ pub fn validate_args(args: &Args) -> Result<(), String> {
         ));
     }
 
+    if args.dump_html.is_some() && !path.is_file() {
+        return Err(format!(
+            "--dump-html renders a single markdown file, not a directory: {}",
+            path.display()
+        ));
+    }
+
+    validate_config_args(args)
+}
+
+/// Checks the configuration file and account selection of the arguments
+fn validate_config_args(args: &Args) -> Result<(), String> {
     // Validate config file if specified
     if let Some(config_file) = &args.config_file {
         if !config_file.exists() {
@@ -394,11 +1464,89 @@ pub fn validate_args(args: &Args) -> Result<(), String> {
     Ok(())
 }
 
+/// Format of log lines
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, for log collectors
+    Json,
+}
+
 /// Initializes logging based on the verbose flag
-pub fn init_logging(verbose: bool) {
-    if verbose {
-        tracing_subscriber::fmt::init();
+///
+/// Logs go to stdout, or to `log_file` through a background writer that is
+/// flushed when the returned guard is dropped, so keep the guard alive for
+/// as long as the program logs. A log file starts with the build information.
+///
+/// # Errors
+///
+/// Returns an error if the log file cannot be opened or a global logger is
+/// already set, e.g. when called twice
+pub fn init_logging(
+    verbose: bool,
+    trace_http: bool,
+    format: LogFormat,
+    log_file: Option<&Path>,
+) -> Result<Option<WorkerGuard>, Error> {
+    use tracing::Level;
+    use tracing_subscriber::filter::Targets;
+    use tracing_subscriber::fmt::writer::BoxMakeWriter;
+    use tracing_subscriber::prelude::*;
+
+    if !verbose && !trace_http && log_file.is_none() {
+        return Ok(None);
+    }
+
+    // A log file records what --verbose would show; HTTP traces are logged at
+    // debug level and other output keeps its usual level
+    let level = if verbose || log_file.is_some() { Level::INFO } else { Level::ERROR };
+    let mut filter = Targets::new().with_default(level);
+    if trace_http {
+        filter = filter.with_target("wx_uploader::http_trace", Level::DEBUG);
     }
+
+    let (writer, guard) = match log_file {
+        Some(path) => {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| Error::io_at(path, e))?;
+            let (writer, guard) = tracing_appender::non_blocking(file);
+            (BoxMakeWriter::new(writer), Some(guard))
+        }
+        None => (BoxMakeWriter::new(std::io::stdout), None),
+    };
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(log_file.is_none());
+    let layer = match format {
+        LogFormat::Text => layer.boxed(),
+        LogFormat::Json => layer.json().boxed(),
+    };
+    tracing_subscriber::registry()
+        .with(layer)
+        .with(filter)
+        .try_init()
+        .map_err(|e| Error::generic_with_source("Logging is already initialized", e))?;
+
+    // A log file starts with the build that wrote it, for bug reports
+    if log_file.is_some() {
+        let build = crate::build_info::BuildInfo::current();
+        tracing::info!(
+            version = build.version,
+            git_commit = build.git_commit.as_deref().unwrap_or("unknown"),
+            build_date = build.build_date,
+            target = build.target,
+            profile = build.profile,
+            features = %build.features.join(","),
+            wechat_pub_rs = build.wechat_pub_rs,
+            "wx-uploader build"
+        );
+    }
+    Ok(guard)
 }
 
 /// Display startup banner with configuration information
@@ -412,19 +1560,22 @@ pub fn display_banner(args: &Args) {
     println!("{}", "=".repeat(40).bright_black());
     
     // Only show path info for upload operations
-    if !args.list_accounts && args.init_config.is_none() {
-        if let Some(path) = &args.path {
-            println!("Path: {}", path.display().to_string().bright_white());
-            println!(
-                "Mode: {}",
-                if path.is_file() {
-                    "Single file"
-                } else {
-                    "Directory"
-                }
-                .bright_green()
-            );
-        }
+    if let Some(manifest) = &args.manifest {
+        println!("Manifest: {}", manifest.display().to_string().bright_white());
+    } else if !args.list_accounts
+        && args.init_config.is_none()
+        && let Some(path) = &args.path
+    {
+        println!("Path: {}", path.display().to_string().bright_white());
+        println!(
+            "Mode: {}",
+            if path.is_file() {
+                "Single file"
+            } else {
+                "Directory"
+            }
+            .bright_green()
+        );
     }
     
     // Show configuration source
@@ -433,9 +1584,18 @@ pub fn display_banner(args: &Args) {
             "Config: {}",
             config_file.display().to_string().bright_magenta()
         );
+    } else if args.app_id.is_some() {
+        println!("Config: {}", "Command line".bright_magenta());
     } else {
         println!("Config: {}", "Environment variables".bright_magenta());
     }
+    if let (Some(app_id), Some(app_secret)) = (&args.app_id, &args.app_secret) {
+        println!(
+            "Account: {} (app secret {})",
+            app_id.bright_white(),
+            mask_secret(app_secret)
+        );
+    }
     
     println!("Verbose: {}", args.verbose.to_string().bright_blue());
     println!("{}", "=".repeat(40).bright_black());
@@ -454,6 +1614,11 @@ pub async fn generate_example_config(path: &PathBuf) -> Result<(), String> {
             app_id: "your_personal_app_id_here".to_string(),
             app_secret: "your_personal_app_secret_here".to_string(),
             description: Some("Personal WeChat public account".to_string()),
+            wechat_base_url: None,
+            default_cover: None,
+            kind: AccountKind::Normal,
+            tags: Vec::new(),
+            owner: None,
         },
     );
     
@@ -464,16 +1629,28 @@ pub async fn generate_example_config(path: &PathBuf) -> Result<(), String> {
             app_id: "your_work_app_id_here".to_string(),
             app_secret: "your_work_app_secret_here".to_string(),
             description: Some("Work WeChat public account".to_string()),
+            wechat_base_url: None,
+            default_cover: None,
+            kind: AccountKind::Normal,
+            tags: vec!["work".to_string(), "regional".to_string()],
+            owner: Some("Marketing team".to_string()),
         },
     );
     
     // Set default account
     example_config.default_account = Some("personal".to_string());
+
+    // Add an account group for uploading to several accounts at once
+    example_config.groups.insert(
+        "all".to_string(),
+        vec!["personal".to_string(), "work".to_string()],
+    );
     
     // Add AI provider configuration example
     example_config.ai_provider = Some(AiProviderConfig {
         provider: "openai".to_string(),
-        api_key: "your_openai_api_key_here".to_string(),
+        api_key: Some("your_openai_api_key_here".to_string()),
+        api_key_file: None,
         base_url: None,
     });
     
@@ -482,6 +1659,52 @@ pub async fn generate_example_config(path: &PathBuf) -> Result<(), String> {
         verbose: Some(false),
         default_theme: Some("lapis".to_string()),
         default_code_highlighter: Some("github".to_string()),
+        wechat_base_url: None,
+        default_cover: None,
+        strict_frontmatter: None,
+        no_ai_text: None,
+        stream_scene: None,
+        optimize_covers: None,
+        embed_provenance: None,
+        cover_format: None,
+        cover_quality: None,
+        write_back: None,
+        set_draft_status: None,
+        state_file: None,
+        cache_dir: None,
+        auto_slug: None,
+        scene_prompts: None,
+        fallback_scene: None,
+        original: None,
+        theme_fallback: None,
+        on_material_denied: None,
+        cover_select: None,
+        retry_max_attempts: None,
+        max_ai_images_per_run: None,
+        max_ai_cost_per_run: None,
+        ai_image_price: None,
+        ai_description_price: None,
+        confirm_ai_cost_above: None,
+        content_root: None,
+        cover_template: None,
+        hooks: None,
+        strict_hooks: None,
+        wechat_rpm: None,
+        max_retries: None,
+        max_retry_delay: None,
+        upload_delay_ms: None,
+        digest_limit: None,
+        disabled_lint_rules: None,
+        anchor_links: None,
+        obsidian: None,
+        obsidian_vault: None,
+        wiki_links: None,
+        transforms: None,
+        markdown_extensions: None,
+        sidecar_frontmatter: None,
+        allowed_hours: None,
+        backup: None,
+        backup_keep: None,
     });
     
     // Determine output format based on file extension
@@ -514,6 +1737,61 @@ pub async fn generate_example_config(path: &PathBuf) -> Result<(), String> {
     Ok(())
 }
 
+/// Returns the label marking sandbox accounts in account listings
+pub fn sandbox_label(kind: AccountKind) -> String {
+    match kind {
+        AccountKind::Sandbox => format!(" {}", "[sandbox]".bright_yellow()),
+        AccountKind::Normal => String::new(),
+    }
+}
+
+/// Returns the tags of an account for display after its name, or an empty string
+pub fn tags_label(tags: &[String]) -> String {
+    tags.iter()
+        .map(|tag| format!("  {}", format!("#{}", tag).bright_cyan()))
+        .collect()
+}
+
+/// Returns the description of an account for display
+fn account_description(account: &WeChatAccount) -> &str {
+    account.description.as_deref().unwrap_or("No description")
+}
+
+/// Pads `text` with spaces to `width` characters
+fn pad(text: &str, width: usize) -> String {
+    format!("{}{}", text, " ".repeat(width.saturating_sub(text.chars().count())))
+}
+
+/// Lists the themes and code highlighters articles can use
+pub fn list_themes() {
+    let sections = [
+        ("🎨", "Themes", "theme", themes()),
+        ("💻", "Code highlighters", "code", code_highlighters()),
+    ];
+    for (icon, title, field, styles) in sections {
+        println!(
+            "{} {} (frontmatter {}):",
+            icon.bright_blue(),
+            title.bright_white().bold(),
+            field.bright_cyan()
+        );
+        let width = styles.iter().map(|style| style.name.len()).max().unwrap_or(0);
+        for style in styles {
+            let tone = match style.tone {
+                Tone::Light => style.tone.to_string().bright_yellow(),
+                Tone::Dark => style.tone.to_string().bright_black(),
+            };
+            println!(
+                "  {}  {:<5}  {}",
+                format!("{:<width$}", style.name).bright_green(),
+                tone,
+                style.description
+            );
+        }
+        println!();
+    }
+}
+
 /// Lists all available accounts from configuration file
 pub async fn list_accounts_from_config(config_path: &PathBuf) -> Result<(), String> {
     let config = Config::from_file(config_path, None)
@@ -533,53 +1811,97 @@ pub async fn list_accounts_from_config(config_path: &PathBuf) -> Result<(), Stri
         return Ok(());
     }
     
+    // Columns are padded only if a later column follows
+    let has_tags = accounts.iter().any(|account| !account.tags.is_empty());
+    let has_owner = accounts.iter().any(|account| account.owner.is_some());
+    let width = |texts: Vec<&str>| texts.iter().map(|t| t.chars().count()).max().unwrap_or(0);
+    let name_width = width(accounts.iter().map(|a| a.name.as_str()).collect());
+    let description_width = width(accounts.iter().map(|a| account_description(a)).collect());
+    let owner_width = width(accounts.iter().filter_map(|a| a.owner.as_deref()).collect());
+
     for account in accounts {
         let is_current = account.name == config.wechat_account.name;
-        let marker = if is_current { "●" } else { "○" };
-        let color = if is_current { "bright_green" } else { "bright_white" };
-        
-        match color {
-            "bright_green" => {
-                println!(
-                    "  {} {} {} {}",
-                    marker.bright_green(),
-                    account.name.bright_green().bold(),
-                    "-".bright_black(),
-                    account.description.as_deref().unwrap_or("No description").bright_green()
-                );
-                println!(
-                    "    {} App ID: {} (current)",
-                    "│".bright_green(),
-                    account.app_id.bright_green()
-                );
-            }
-            _ => {
-                println!(
-                    "  {} {} {} {}",
-                    marker.bright_white(),
-                    account.name.bright_white().bold(),
-                    "-".bright_black(),
-                    account.description.as_deref().unwrap_or("No description")
-                );
-                println!(
-                    "    {} App ID: {}",
-                    "│".bright_black(),
-                    account.app_id.bright_black()
-                );
-            }
+        let name = pad(&account.name, name_width);
+        let description = if has_owner || has_tags {
+            pad(account_description(account), description_width)
+        } else {
+            account_description(account).to_string()
+        };
+
+        let mut line = if is_current {
+            format!(
+                "  {} {}  {}",
+                "●".bright_green(),
+                name.bright_green().bold(),
+                description.bright_green()
+            )
+        } else {
+            format!("  {} {}  {}", "○".bright_white(), name.bright_white().bold(), description)
+        };
+        if has_owner {
+            let owner = account.owner.as_deref().unwrap_or("-");
+            let owner = if has_tags { pad(owner, owner_width) } else { owner.to_string() };
+            line.push_str(&format!("  {}", owner.bright_magenta()));
+        }
+        line.push_str(&tags_label(&account.tags));
+        line.push_str(&sandbox_label(account.kind));
+        println!("{}", line);
+
+        if is_current {
+            println!(
+                "    {} App ID: {} (current)",
+                "│".bright_green(),
+                account.app_id.bright_green()
+            );
+        } else {
+            println!(
+                "    {} App ID: {}",
+                "│".bright_black(),
+                account.app_id.bright_black()
+            );
+        }
+        println!();
+    }
+
+    if !config.account_groups.is_empty() {
+        println!("{} Account groups:", "👥".bright_blue());
+        let mut groups: Vec<_> = config.account_groups.iter().collect();
+        groups.sort_by_key(|(name, _)| name.as_str());
+        for (name, members) in groups {
+            println!(
+                "  {} {} {}",
+                format!("@{}", name).bright_cyan().bold(),
+                "→".bright_black(),
+                members.join(", ")
+            );
         }
         println!();
     }
     
     println!(
-        "{}: Use {} to select an account",
+        "{}: Use {} to select an account, group or tagged account",
         "Usage".bright_blue().bold(),
-        "-a/--account <name>".bright_cyan()
+        "-a/--account <name|@group|tag:TAG>".bright_cyan()
     );
     
     Ok(())
 }
 
+/// Prints the effective configuration with the source of each value
+pub async fn show_config(args: &Args, format: ShowConfigFormat) -> Result<(), String> {
+    let config = create_config_from_args(args).await?;
+    let entries = provenance::config_entries(&config);
+    match format {
+        ShowConfigFormat::Text => println!("{}", provenance::entries_to_text(&entries)),
+        ShowConfigFormat::Json => {
+            let json = serde_json::to_string_pretty(&provenance::entries_to_json(&entries))
+                .map_err(|e| e.to_string())?;
+            println!("{}", json);
+        }
+    }
+    Ok(())
+}
+
 /// Creates configuration based on command-line arguments
 pub async fn create_config_from_args(args: &Args) -> Result<Config, String> {
     let config = if let Some(config_file) = &args.config_file {
@@ -587,6 +1909,9 @@ pub async fn create_config_from_args(args: &Args) -> Result<Config, String> {
         Config::from_file(config_file, args.account.as_deref())
             .await
             .map_err(|e| format!("Failed to load configuration: {}", e))?
+    } else if let (Some(app_id), Some(app_secret)) = (&args.app_id, &args.app_secret) {
+        Config::from_credentials(app_id.clone(), app_secret.clone())
+            .map_err(|e| format!("Failed to configure the --app-id/--app-secret account: {}", e))?
     } else {
         // Load from environment variables (legacy mode)
         Config::from_env()
@@ -595,36 +1920,212 @@ pub async fn create_config_from_args(args: &Args) -> Result<Config, String> {
     
     // Override AI provider if specified via CLI
     let mut final_config = config;
+    if args.account.is_some() {
+        final_config.provenance.set("account", ConfigSource::Flag);
+    }
+    // The account on the command line replaces the ones in the config file
+    if let (Some(app_id), Some(app_secret)) = (&args.app_id, &args.app_secret) {
+        final_config.use_cli_account(app_id.clone(), app_secret.clone());
+    }
     if args.ai_provider.is_some() || args.ai_api_key.is_some() {
-        let provider = args.ai_provider.as_deref().unwrap_or("openai");
-        let api_key = if let Some(key) = &args.ai_api_key {
-            key.clone()
-        } else {
-            // Try to get from environment based on provider
-            let env_var = match provider {
-                "gemini" => "GEMINI_API_KEY",
-                _ => "OPENAI_API_KEY", // Default to OpenAI
-            };
-            std::env::var(env_var)
-                .map_err(|_| format!("AI provider '{}' specified but {} not set", provider, env_var))?
-        };
-        
-        use crate::models::AiProvider;
-        final_config.ai_provider = Some(match provider {
-            "gemini" => AiProvider::Gemini {
-                api_key,
-                base_url: None,
-            },
-            _ => AiProvider::OpenAI {
-                api_key,
-                base_url: None,
-            },
-        });
+        final_config.ai_provider = merge_ai_provider(
+            final_config.ai_provider.take(),
+            args.ai_provider.as_deref(),
+            args.ai_api_key.as_deref(),
+            |name| env_secret(name).map_err(|e| e.to_string()),
+        )?;
+        final_config.provenance.set("ai_provider", ConfigSource::Flag);
     }
     
+    // Override WeChat API base URL if specified via CLI
+    if let Some(base_url) = &args.wechat_base_url {
+        final_config.set_wechat_base_url(base_url.clone());
+        final_config.provenance.set("wechat_base_url", ConfigSource::Flag);
+    }
+
+    if args.strict_frontmatter {
+        final_config.strict_frontmatter = true;
+        final_config.provenance.set("strict_frontmatter", ConfigSource::Flag);
+    }
+
+    if args.strict_hooks {
+        final_config.strict_hooks = true;
+        final_config.provenance.set("strict_hooks", ConfigSource::Flag);
+    }
+
+    if args.no_ai_text {
+        final_config.no_ai_text = true;
+        final_config.provenance.set("no_ai_text", ConfigSource::Flag);
+    }
+
+    if args.stream_scene {
+        final_config.stream_scene = true;
+        final_config.provenance.set("stream_scene", ConfigSource::Flag);
+    }
+
+    if args.diagnose_content {
+        final_config.diagnose_content = true;
+        final_config.provenance.set("diagnose_content", ConfigSource::Flag);
+    }
+
+    if args.reconcile {
+        final_config.reconcile = true;
+        final_config.provenance.set("reconcile", ConfigSource::Flag);
+    }
+
+    if args.no_write_back {
+        final_config.write_back = false;
+        final_config.provenance.set("write_back", ConfigSource::Flag);
+    }
+
+    if args.no_status_update {
+        final_config.set_draft_status = false;
+        final_config.provenance.set("set_draft_status", ConfigSource::Flag);
+    }
+
+    if let Some(state_file) = &args.state_file {
+        final_config.state_file = Some(state_file.clone());
+        final_config.provenance.set("state_file", ConfigSource::Flag);
+    }
+
+    if args.optimize_covers {
+        final_config.optimize_covers = true;
+        final_config.provenance.set("optimize_covers", ConfigSource::Flag);
+    }
+
+    if args.embed_provenance {
+        final_config.embed_provenance = true;
+        final_config.provenance.set("embed_provenance", ConfigSource::Flag);
+    }
+    if args.convert_covers {
+        final_config.convert_covers = true;
+        final_config.provenance.set("convert_covers", ConfigSource::Flag);
+    }
+
+    if args.auto_slug {
+        final_config.auto_slug = true;
+        final_config.provenance.set("auto_slug", ConfigSource::Flag);
+    }
+
+    if let Some(max_images) = args.max_images {
+        final_config.ai_budget.max_images = max_images;
+        final_config.provenance.set("max_ai_images_per_run", ConfigSource::Flag);
+    }
+
+    if let Some(cover_variations) = args.cover_variations {
+        final_config.cover_variations = cover_variations;
+        final_config.provenance.set("cover_variations", ConfigSource::Flag);
+    }
+
+    final_config.prompt = prompter(args);
+    if let Some(lock_wait) = args.lock_wait {
+        final_config.lock_wait = lock_wait;
+    }
+    final_config.force_lock = args.force_lock;
+    final_config.accept_ai_cost = args.accept_ai_cost;
+
+    if let Some(max_retries) = args.max_retries {
+        final_config.max_retries = max_retries;
+        final_config.provenance.set("max_retries", ConfigSource::Flag);
+    }
+    if let Some(delay) = args.delay {
+        final_config.upload_delay_ms = delay;
+        final_config.provenance.set("upload_delay_ms", ConfigSource::Flag);
+    }
+
+    if let Some(content_root) = &args.content_root {
+        final_config.content_root =
+            Some(resolve_content_root(content_root).map_err(|e| e.to_string())?);
+        final_config.provenance.set("content_root", ConfigSource::Flag);
+    }
+
+    if let Some(theme_fallback) = args.theme_fallback {
+        final_config.theme_fallback = theme_fallback;
+        final_config.provenance.set("theme_fallback", ConfigSource::Flag);
+    }
+    if let Some(Command::Retry { max_attempts: Some(max_attempts) }) = &args.command {
+        final_config.retry_max_attempts = *max_attempts;
+        final_config.provenance.set("retry_max_attempts", ConfigSource::Flag);
+    }
+    if let Some(cover_select) = args.cover_select {
+        final_config.cover_select = cover_select;
+        final_config.provenance.set("cover_select", ConfigSource::Flag);
+    }
+
+    if let Some(anchor_links) = args.anchor_links {
+        final_config.anchor_links = anchor_links;
+        final_config.provenance.set("anchor_links", ConfigSource::Flag);
+    }
+
+    if args.obsidian {
+        final_config.obsidian = true;
+        final_config.provenance.set("obsidian", ConfigSource::Flag);
+    }
+    if let Some(vault) = &args.obsidian_vault {
+        final_config.obsidian_vault =
+            Some(resolve_obsidian_vault(vault).map_err(|e| e.to_string())?);
+        final_config.provenance.set("obsidian_vault", ConfigSource::Flag);
+    }
+    if let Some(wiki_links) = args.wiki_links {
+        final_config.wiki_links = wiki_links;
+        final_config.provenance.set("wiki_links", ConfigSource::Flag);
+    }
+
+    if let Some(state) = args.only {
+        final_config.only = Some(state);
+        final_config.provenance.set("only", ConfigSource::Flag);
+    }
+
+    if args.follow_symlinks {
+        final_config.follow_symlinks = true;
+        final_config.provenance.set("follow_symlinks", ConfigSource::Flag);
+    }
+    if args.no_cache {
+        final_config.no_cache = true;
+        final_config.provenance.set("no_cache", ConfigSource::Flag);
+    }
+    if args.require_cover {
+        final_config.require_cover = true;
+        final_config.provenance.set("require_cover", ConfigSource::Flag);
+    }
+    if args.fail_fast {
+        final_config.fail_fast = true;
+        final_config.provenance.set("fail_fast", ConfigSource::Flag);
+    }
+    if args.ignore_schedule {
+        final_config.ignore_schedule = true;
+        final_config.provenance.set("ignore_schedule", ConfigSource::Flag);
+    }
+    if args.no_backup {
+        final_config.backup = false;
+        final_config.provenance.set("backup", ConfigSource::Flag);
+    }
+
+    if args.pipeline {
+        final_config.pipeline = Some(PipelineLimits {
+            ai_jobs: args.ai_jobs,
+            upload_jobs: args.upload_jobs,
+        });
+        final_config.provenance.set("pipeline", ConfigSource::Flag);
+    }
+
+    if args.trace_http {
+        final_config.trace_http = true;
+        final_config.trace_body_limit = args.trace_body_limit;
+        final_config.provenance.set("trace_http", ConfigSource::Flag);
+        final_config.provenance.set("trace_body_limit", ConfigSource::Flag);
+    }
+
     // Override verbose setting
     if args.verbose {
         final_config.verbose = true;
+        final_config.provenance.set("verbose", ConfigSource::Flag);
+    }
+
+    if args.config_file.is_some() {
+        check_env_conflicts(&final_config, args.strict_config, |name| {
+            env_secret(name).ok().flatten()
+        })?;
     }
     
     // Validate the final configuration
@@ -634,6 +2135,175 @@ pub async fn create_config_from_args(args: &Args) -> Result<Config, String> {
     Ok(final_config)
 }
 
+/// Warns about legacy environment variables that disagree with the
+/// configuration file, or fails with `--strict-config`
+fn check_env_conflicts(
+    config: &Config,
+    strict: bool,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<(), String> {
+    let conflicts = env_conflicts(config, env);
+    if strict && !conflicts.is_empty() {
+        return Err(format!(
+            "Environment disagrees with the configuration file (--strict-config): {}",
+            conflicts.join("; ")
+        ));
+    }
+    for conflict in &conflicts {
+        FORMATTER.print_warning(&format!("{}; the configuration file is used", conflict));
+    }
+    Ok(())
+}
+
+/// Compares the legacy `WECHAT_*` and AI key environment variables with the
+/// values the configuration file selected, describing each disagreement
+fn env_conflicts(config: &Config, env: impl Fn(&str) -> Option<String>) -> Vec<String> {
+    let mut conflicts = Vec::new();
+    let account = &config.wechat_account;
+
+    // An account given with --app-id is meant to differ from the environment
+    let differs = |value: &String, configured: &String| !config.cli_account && value != configured;
+    if let Some(app_id) = env("WECHAT_APP_ID").filter(|id| differs(id, &account.app_id)) {
+        conflicts.push(format!(
+            "WECHAT_APP_ID (env) is '{}' but account '{}' (config file) has app ID '{}'",
+            app_id, account.name, account.app_id
+        ));
+    } else if let Some(secret) =
+        env("WECHAT_APP_SECRET").filter(|s| differs(s, &account.app_secret))
+    {
+        conflicts.push(format!(
+            "WECHAT_APP_SECRET (env) is {} but account '{}' (config file) has app secret {}",
+            mask_secret(&secret),
+            account.name,
+            mask_secret(&account.app_secret)
+        ));
+    }
+
+    // A key the file left to the environment cannot disagree with it
+    if config.provenance.source("ai_provider") == ConfigSource::File {
+        let (env_var, api_key) = match &config.ai_provider {
+            Some(AiProvider::OpenAI { api_key, .. }) => ("OPENAI_API_KEY", api_key),
+            Some(AiProvider::Gemini { api_key, .. }) => ("GEMINI_API_KEY", api_key),
+            None => return conflicts,
+        };
+        if let Some(env_key) = env(env_var).filter(|key| key != api_key) {
+            conflicts.push(format!(
+                "{} (env) is {} but the config file has API key {}",
+                env_var,
+                mask_secret(&env_key),
+                mask_secret(api_key)
+            ));
+        }
+    }
+
+    conflicts
+}
+
+/// AI provider names accepted by `--provider`
+const AI_PROVIDER_NAMES: [&str; 2] = ["openai", "gemini"];
+
+/// Applies `--provider` and `--ai-key` to the AI provider from the config
+/// file or environment
+///
+/// Only what the flags name changes: a key alone keeps the configured
+/// provider and its base URL, and a provider alone keeps the configured key
+/// when the type does not change. Switching to another provider without
+/// `--ai-key` takes the key from that provider's environment variable.
+fn merge_ai_provider(
+    current: Option<AiProvider>,
+    provider: Option<&str>,
+    api_key: Option<&str>,
+    env: impl Fn(&str) -> Result<Option<String>, String>,
+) -> Result<Option<AiProvider>, String> {
+    let gemini = match provider {
+        Some(name) => parse_ai_provider(name)?,
+        None => matches!(current, Some(AiProvider::Gemini { .. })),
+    };
+    let (current_key, base_url) = match current {
+        Some(AiProvider::OpenAI { api_key, base_url }) if !gemini => (Some(api_key), base_url),
+        Some(AiProvider::Gemini { api_key, base_url }) if gemini => (Some(api_key), base_url),
+        _ => (None, None),
+    };
+    let (name, env_var) = if gemini {
+        ("gemini", "GEMINI_API_KEY")
+    } else {
+        ("openai", "OPENAI_API_KEY")
+    };
+    let api_key = match (api_key, current_key) {
+        (Some(key), _) => key.to_string(),
+        (None, Some(key)) => key,
+        (None, None) => env(env_var)?.ok_or_else(|| {
+            format!(
+                "AI provider '{}' has no API key: pass --ai-key, set {} or add it to the config file",
+                name, env_var
+            )
+        })?,
+    };
+    Ok(Some(if gemini {
+        AiProvider::Gemini { api_key, base_url }
+    } else {
+        AiProvider::OpenAI { api_key, base_url }
+    }))
+}
+
+/// Parses a `--provider` name, returning whether it names Gemini
+fn parse_ai_provider(name: &str) -> Result<bool, String> {
+    match name.to_ascii_lowercase().as_str() {
+        "openai" => Ok(false),
+        "gemini" => Ok(true),
+        lower => {
+            let hint = AI_PROVIDER_NAMES
+                .iter()
+                .find(|known| edit_distance(lower, known) <= 2)
+                .map(|known| format!("did you mean '{}'? ", known))
+                .unwrap_or_default();
+            Err(format!(
+                "unknown AI provider '{}'; {}supported providers: {}",
+                name,
+                hint,
+                AI_PROVIDER_NAMES.join(", ")
+            ))
+        }
+    }
+}
+
+/// Levenshtein distance between two strings, counted in characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = (above + 1).min(row[j] + 1).min(diagonal + usize::from(ca != *cb));
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// Masks a secret, keeping its last four characters when it is long enough
+/// that they do not give it away
+pub fn mask_secret(secret: &str) -> String {
+    let chars: Vec<char> = secret.chars().collect();
+    if chars.len() < 12 {
+        return REDACTED.to_string();
+    }
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}{}", REDACTED, tail)
+}
+
+/// Returns the prompter of the run: the `--yes`/`--no-input` policy, asking
+/// when standard input and output are a terminal or `--interactive` is set
+pub fn prompter(args: &Args) -> Prompter {
+    use std::io::IsTerminal;
+
+    let interactive = args.interactive
+        || (std::io::stdin().is_terminal() && std::io::stdout().is_terminal());
+    Prompter::new(PromptPolicy::from_flags(args.assume_yes, args.no_input), interactive)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -649,13 +2319,7 @@ mod tests {
         let args = Args {
             path: Some(file_path),
             verbose: false,
-            clear_cache: false,
-            ai_provider: None,
-            ai_api_key: None,
-            config_file: None,
-            account: None,
-            list_accounts: false,
-            init_config: None,
+            ..Default::default()
         };
 
         assert!(validate_args(&args).is_ok());
@@ -667,16 +2331,16 @@ mod tests {
         let args = Args {
             path: Some(temp_dir.path().to_path_buf()),
             verbose: false,
-            clear_cache: false,
-            ai_provider: None,
-            ai_api_key: None,
-            config_file: None,
-            account: None,
-            list_accounts: false,
-            init_config: None,
+            ..Default::default()
         };
 
         assert!(validate_args(&args).is_ok());
+
+        let args = Args {
+            dump_html: Some(PathBuf::from("post.html")),
+            ..args
+        };
+        assert!(validate_args(&args).unwrap_err().contains("single markdown file"));
     }
 
     #[test]
@@ -684,13 +2348,7 @@ mod tests {
         let args = Args {
             path: Some(PathBuf::from("nonexistent/path")),
             verbose: false,
-            clear_cache: false,
-            ai_provider: None,
-            ai_api_key: None,
-            config_file: None,
-            account: None,
-            list_accounts: false,
-            init_config: None,
+            ..Default::default()
         };
 
         assert!(validate_args(&args).is_err());
@@ -698,11 +2356,98 @@ mod tests {
     }
 
     #[test]
-    fn test_init_logging_verbose() {
-        // This test mainly ensures the function doesn't panic
-        // In practice, logging setup would be tested differently
-        init_logging(true);
-        init_logging(false);
+    fn test_validate_args_manifest_replaces_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let manifest = temp_dir.path().join("changed.txt");
+        let args = Args {
+            manifest: Some(manifest.clone()),
+            ..Default::default()
+        };
+        assert!(validate_args(&args).unwrap_err().contains("Manifest file does not exist"));
+
+        fs::write(&manifest, "a.md\n").unwrap();
+        assert!(validate_args(&args).is_ok());
+
+        let err = Args::try_parse_from(["wx-uploader", "--manifest", "changed.txt", "posts"])
+            .unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn test_prompt_flags_select_one_policy() {
+        let args = Args::try_parse_from(["wx-uploader", "--no-input", "restore", "--from", "x"])
+            .unwrap();
+        assert!(validate_args(&args).is_ok());
+        assert_eq!(prompter(&args).policy(), PromptPolicy::NoInput);
+
+        let args = Args::try_parse_from(["wx-uploader", "restore", "--from", "x", "--assume-yes"])
+            .unwrap();
+        assert_eq!(prompter(&args).policy(), PromptPolicy::AssumeYes);
+        assert_eq!(
+            args.command,
+            Some(Command::Restore {
+                from: "x".to_string(),
+                force: false
+            })
+        );
+
+        // Overwriting takes its own flag rather than --yes
+        let args = Args::try_parse_from(["wx-uploader", "restore", "--from", "x", "--force"])
+            .unwrap();
+        assert!(matches!(args.command, Some(Command::Restore { force: true, .. })));
+
+        for flag in ["--no-input", "--yes"] {
+            let args = Args::try_parse_from(["wx-uploader", "--interactive", flag, "posts"])
+                .unwrap();
+            let err = validate_args(&args).unwrap_err();
+            assert!(err.contains("--interactive and"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_init_logging_twice_writes_json_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let log_file = temp_dir.path().join("run.log");
+
+        // Nothing to log without --verbose, --trace-http or --log-file
+        assert!(init_logging(false, false, LogFormat::Text, None).unwrap().is_none());
+
+        let guard = init_logging(false, false, LogFormat::Json, Some(&log_file))
+            .unwrap()
+            .expect("file logging has a guard");
+        let err = init_logging(true, false, LogFormat::Text, None).unwrap_err();
+        assert!(err.to_string().contains("already initialized"), "{}", err);
+
+        tracing::info!(answer = 42, "json log line");
+        // The background writer appends shortly after the event
+        let mut logged = String::new();
+        for _ in 0..100 {
+            logged = std::fs::read_to_string(&log_file).unwrap();
+            if logged.contains("json log line") {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        let lines: Vec<serde_json::Value> = logged
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("log lines are JSON"))
+            .collect();
+        let position = |message: &str| {
+            lines
+                .iter()
+                .position(|line| line["fields"]["message"] == message)
+                .expect("the event was logged")
+        };
+        // The build is logged before anything else the run logs
+        let build = &lines[position("wx-uploader build")];
+        assert_eq!(build["fields"]["version"], env!("CARGO_PKG_VERSION"));
+        assert!(position("wx-uploader build") < position("json log line"));
+        let line = &lines[position("json log line")];
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["fields"]["answer"], 42);
+
+        // Other tests keep logging through the global logger
+        std::mem::forget(guard);
     }
 
     #[test]
@@ -711,13 +2456,7 @@ mod tests {
         let args = Args {
             path: Some(temp_dir.path().to_path_buf()),
             verbose: true,
-            clear_cache: false,
-            ai_provider: None,
-            ai_api_key: None,
-            config_file: None,
-            account: None,
-            list_accounts: false,
-            init_config: None,
+            ..Default::default()
         };
 
         // This test mainly ensures the function doesn't panic
@@ -726,13 +2465,7 @@ mod tests {
         let args = Args {
             path: Some(temp_dir.path().to_path_buf()),
             verbose: false,
-            clear_cache: false,
-            ai_provider: None,
-            ai_api_key: None,
-            config_file: None,
-            account: None,
-            list_accounts: false,
-            init_config: None,
+            ..Default::default()
         };
 
         display_banner(&args);
@@ -744,16 +2477,338 @@ mod tests {
         let args = Args {
             path: Some(PathBuf::from("test.md")),
             verbose: true,
-            clear_cache: false,
-            ai_provider: None,
-            ai_api_key: None,
-            config_file: None,
-            account: None,
-            list_accounts: false,
-            init_config: None,
+            ..Default::default()
         };
 
         assert_eq!(args.path, Some(PathBuf::from("test.md")));
         assert!(args.verbose);
     }
+
+    #[test]
+    fn test_show_config_parsing() {
+        let args = Args::try_parse_from(["wx-uploader", "--show-config"]).unwrap();
+        assert_eq!(args.show_config, Some(ShowConfigFormat::Text));
+        let args = Args::try_parse_from(["wx-uploader", "--show-config=json"]).unwrap();
+        assert_eq!(args.show_config, Some(ShowConfigFormat::Json));
+        assert!(Args::try_parse_from(["wx-uploader", "--show-config", "posts"]).is_err());
+    }
+
+    #[test]
+    fn test_check_ai_parsing() {
+        let args = Args::try_parse_from(["wx-uploader", "--check-ai", "--skip-image"]).unwrap();
+        assert!(args.check_ai && args.skip_image);
+        assert!(validate_args(&args).is_ok());
+        assert!(Args::try_parse_from(["wx-uploader", "--skip-image"]).is_err());
+        assert!(Args::try_parse_from(["wx-uploader", "--check-ai", "posts"]).is_err());
+    }
+
+    #[test]
+    fn test_log_format_parsing() {
+        let args = Args::try_parse_from(["wx-uploader", "posts"]).unwrap();
+        assert_eq!(args.log_format, LogFormat::Text);
+        assert!(args.log_file.is_none());
+        let args = Args::try_parse_from([
+            "wx-uploader", "--log-format", "json", "--log-file", "run.log", "posts",
+        ])
+        .unwrap();
+        assert_eq!(args.log_format, LogFormat::Json);
+        assert_eq!(args.log_file, Some(PathBuf::from("run.log")));
+        assert!(Args::try_parse_from(["wx-uploader", "--log-format", "xml", "posts"]).is_err());
+    }
+
+    #[test]
+    fn test_only_parsing() {
+        let args = Args::try_parse_from(["wx-uploader", "--only", "draft", "posts"]).unwrap();
+        assert_eq!(args.only, Some(PublicationStatus::Draft));
+        assert!(Args::try_parse_from(["wx-uploader", "--only", "pending", "posts"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_config_provenance_of_overrides() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(
+            &config_path,
+            "accounts:\n  main: { name: main, app_id: a, app_secret: s }\n\
+             \x20 work: { name: work, app_id: w, app_secret: s }\n\
+             default_account: main\n\
+             settings:\n  optimize_covers: false\n  wechat_rpm: 5\n",
+        )
+        .unwrap();
+        let path = config_path.to_str().unwrap();
+
+        let args = Args::try_parse_from([
+            "wx-uploader",
+            "-c",
+            path,
+            "-a",
+            "work",
+            "--optimize-covers",
+            "--show-config",
+        ])
+        .unwrap();
+        let config = create_config_from_args(&args).await.unwrap();
+        let source = |key| config.provenance.source(key);
+
+        assert_eq!(config.wechat_account.name, "work");
+        assert_eq!(source("account"), ConfigSource::Flag);
+        assert_eq!(source("accounts"), ConfigSource::File);
+        // A flag wins over the file
+        assert!(config.optimize_covers);
+        assert_eq!(source("optimize_covers"), ConfigSource::Flag);
+        assert_eq!(source("wechat_rpm"), ConfigSource::File);
+        assert_eq!(source("digest_limit"), ConfigSource::Default);
+
+        let args = Args::try_parse_from(["wx-uploader", "-c", path]).unwrap();
+        let config = create_config_from_args(&args).await.unwrap();
+        assert_eq!(config.provenance.source("account"), ConfigSource::File);
+        assert_eq!(config.provenance.source("optimize_covers"), ConfigSource::File);
+    }
+
+    #[tokio::test]
+    async fn test_retry_command_max_attempts() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(
+            &config_path,
+            "accounts:\n  main: { name: main, app_id: a, app_secret: s }\n\
+             default_account: main\n\
+             settings:\n  retry_max_attempts: 5\n",
+        )
+        .unwrap();
+        let path = config_path.to_str().unwrap();
+
+        let args = Args::try_parse_from(["wx-uploader", "-c", path, "retry"]).unwrap();
+        assert!(validate_args(&args).is_ok());
+        let config = create_config_from_args(&args).await.unwrap();
+        assert_eq!(config.retry_max_attempts, 5);
+        assert_eq!(config.provenance.source("retry_max_attempts"), ConfigSource::File);
+
+        let args =
+            Args::try_parse_from(["wx-uploader", "-c", path, "retry", "--max-attempts", "2"])
+                .unwrap();
+        let config = create_config_from_args(&args).await.unwrap();
+        assert_eq!(config.retry_max_attempts, 2);
+        assert_eq!(config.provenance.source("retry_max_attempts"), ConfigSource::Flag);
+
+        let args = Args::try_parse_from(["wx-uploader", "-a", "main", "retry"]).unwrap();
+        assert!(validate_args(&args).unwrap_err().contains("--config"));
+    }
+
+    #[tokio::test]
+    async fn test_app_id_overrides_configured_accounts() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(
+            &config_path,
+            "accounts:\n  main: { name: main, app_id: a, app_secret: s }\n\
+             \x20 work: { name: work, app_id: w, app_secret: s }\n\
+             default_account: main\n\
+             groups:\n  all: [main, work]\n\
+             settings:\n  wechat_rpm: 5\n",
+        )
+        .unwrap();
+        let path = config_path.to_str().unwrap();
+        let secret = "one-off-secret-7890";
+
+        // The account on the command line wins over the file; its settings stay
+        for args in [
+            vec!["wx-uploader", "-c", path, "--app-id", "wx_a", "--app-secret", secret],
+            vec!["wx-uploader", "--app-id", "wx_a", "--app-secret", secret],
+        ] {
+            let args = Args::try_parse_from(args).unwrap();
+            let config = create_config_from_args(&args).await.unwrap();
+            assert!(config.cli_account);
+            assert_eq!(config.wechat_account.app_id, "wx_a");
+            assert_eq!(config.available_account_names(), vec![crate::models::CLI_ACCOUNT_NAME]);
+            assert!(config.account_groups.is_empty());
+            assert_eq!(config.provenance.source("account"), ConfigSource::Flag);
+            assert_eq!(config.wechat_rpm == 5, args.config_file.is_some());
+
+            // The secret is masked wherever the configuration is shown
+            let json = provenance::entries_to_json(&provenance::config_entries(&config));
+            assert!(!json.to_string().contains(secret), "{}", json);
+            assert!(!format!("{:?}", config).contains(secret));
+        }
+        assert_eq!(mask_secret(secret), "***7890");
+
+        let err = Args::try_parse_from(["wx-uploader", "--app-id", "wx_a", "posts"]).unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+        let err = Args::try_parse_from(["wx-uploader", "--app-secret", secret, "posts"]).unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+        let err = Args::try_parse_from([
+            "wx-uploader", "--app-id", "wx_a", "--app-secret", secret, "-a", "work", "posts",
+        ])
+        .unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::ArgumentConflict);
+
+        // The environment is not compared with an account given on the command line
+        let config = create_config_from_args(
+            &Args::try_parse_from(["wx-uploader", "--app-id", "wx_a", "--app-secret", secret]).unwrap(),
+        )
+        .await
+        .unwrap();
+        let env = |name: &str| (name == "WECHAT_APP_ID").then(|| "wx_first".to_string());
+        assert!(env_conflicts(&config, env).is_empty());
+    }
+
+    #[test]
+    fn test_env_conflicts_with_config_file() {
+        let mut config = Config::new(
+            "wx_file".to_string(),
+            "file-secret".to_string(),
+            Some(AiProvider::openai("sk-file-key-0000".to_string())),
+            false,
+        );
+        config.provenance.set("ai_provider", ConfigSource::File);
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+
+        // Agreeing or absent variables are fine, even in strict mode
+        let agree = env(&[("WECHAT_APP_ID", "wx_file"), ("OPENAI_API_KEY", "sk-file-key-0000")]);
+        assert!(env_conflicts(&config, agree).is_empty());
+        assert!(check_env_conflicts(&config, true, env(&[])).is_ok());
+
+        let disagree = env(&[
+            ("WECHAT_APP_ID", "wx_stale"),
+            ("OPENAI_API_KEY", "sk-stale-key-1234"),
+        ]);
+        let conflicts = env_conflicts(&config, disagree);
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(
+            conflicts[0],
+            "WECHAT_APP_ID (env) is 'wx_stale' but account 'main' (config file) has app ID 'wx_file'"
+        );
+        assert_eq!(
+            conflicts[1],
+            "OPENAI_API_KEY (env) is ***1234 but the config file has API key ***0000"
+        );
+        assert!(check_env_conflicts(&config, false, disagree).is_ok());
+
+        let stale_secret = env(&[("WECHAT_APP_SECRET", "old-secret")]);
+        let err = check_env_conflicts(&config, true, stale_secret).unwrap_err();
+        assert!(err.contains("--strict-config"), "{}", err);
+        assert!(err.contains("WECHAT_APP_SECRET (env) is ***"), "{}", err);
+        assert!(!err.contains("old-secret") && !err.contains("file-secret"), "{}", err);
+
+        // A key taken from the environment in the first place is not compared
+        config.provenance.set("ai_provider", ConfigSource::Env);
+        assert!(env_conflicts(&config, env(&[("OPENAI_API_KEY", "sk-other")])).is_empty());
+    }
+
+    #[test]
+    fn test_merge_ai_provider_matrix() {
+        let proxied = |api_key: &str| AiProvider::OpenAI {
+            api_key: api_key.to_string(),
+            base_url: Some("https://proxy.example".to_string()),
+        };
+        let gemini = |api_key: &str| AiProvider::Gemini {
+            api_key: api_key.to_string(),
+            base_url: None,
+        };
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                Ok(vars
+                    .iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string()))
+            }
+        };
+        type Case = (
+            Option<AiProvider>,
+            &'static [(&'static str, &'static str)],
+            Option<&'static str>,
+            Option<&'static str>,
+            Result<Option<AiProvider>, &'static str>,
+        );
+        let cases: Vec<Case> = vec![
+            // A key alone keeps the configured provider and its base URL
+            (Some(proxied("sk-file")), &[], None, Some("sk-flag"), Ok(Some(proxied("sk-flag")))),
+            (Some(gemini("g-file")), &[], None, Some("g-flag"), Ok(Some(gemini("g-flag")))),
+            (None, &[], None, Some("sk-flag"), Ok(Some(AiProvider::openai("sk-flag".to_string())))),
+            // Naming the configured provider changes nothing and reads no env
+            (
+                Some(proxied("sk-file")),
+                &[("OPENAI_API_KEY", "sk-env")],
+                Some("openai"),
+                None,
+                Ok(Some(proxied("sk-file"))),
+            ),
+            (Some(gemini("g-file")), &[], Some("Gemini"), None, Ok(Some(gemini("g-file")))),
+            // Switching provider takes the other key from the environment
+            (
+                Some(proxied("sk-file")),
+                &[("GEMINI_API_KEY", "g-env")],
+                Some("gemini"),
+                None,
+                Ok(Some(gemini("g-env"))),
+            ),
+            (
+                Some(gemini("g-file")),
+                &[("OPENAI_API_KEY", "sk-env")],
+                Some("openai"),
+                None,
+                Ok(Some(AiProvider::openai("sk-env".to_string()))),
+            ),
+            (
+                None,
+                &[("GEMINI_API_KEY", "g-env")],
+                Some("gemini"),
+                None,
+                Ok(Some(gemini("g-env"))),
+            ),
+            // ... or fails when no source has one
+            (
+                Some(proxied("sk-file")),
+                &[("OPENAI_API_KEY", "sk-env")],
+                Some("gemini"),
+                None,
+                Err("AI provider 'gemini' has no API key: pass --ai-key, set GEMINI_API_KEY or add it to the config file"),
+            ),
+            (None, &[], Some("openai"), None, Err("set OPENAI_API_KEY")),
+            // Both flags replace the provider; the base URL belongs to the old type
+            (Some(proxied("sk-file")), &[], Some("gemini"), Some("g-flag"), Ok(Some(gemini("g-flag")))),
+            (
+                Some(proxied("sk-file")),
+                &[],
+                Some("openai"),
+                Some("sk-flag"),
+                Ok(Some(proxied("sk-flag"))),
+            ),
+            // Unknown names are rejected rather than treated as OpenAI
+            (
+                Some(gemini("g-file")),
+                &[],
+                Some("gemeni"),
+                Some("key"),
+                Err("unknown AI provider 'gemeni'; did you mean 'gemini'? supported providers: openai, gemini"),
+            ),
+            (None, &[], Some("claude"), Some("key"), Err("supported providers: openai, gemini")),
+        ];
+
+        for (i, (current, vars, provider, api_key, expected)) in cases.into_iter().enumerate() {
+            let merged = merge_ai_provider(current, provider, api_key, env(vars));
+            match expected {
+                Ok(expected) => assert_eq!(merged, Ok(expected), "case {}", i),
+                Err(message) => {
+                    let err = merged.unwrap_err();
+                    assert!(err.contains(message), "case {}: {}", i, err);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_merge_ai_provider_reports_env_errors() {
+        let err = merge_ai_provider(None, Some("gemini"), None, |name| {
+            Err(format!("Set either {} or {}_FILE, not both", name, name))
+        })
+        .unwrap_err();
+        assert_eq!(err, "Set either GEMINI_API_KEY or GEMINI_API_KEY_FILE, not both");
+    }
 }
@@ -0,0 +1,400 @@
+//! Content security diagnosis
+//!
+//! WeChat rejects drafts whose text trips its content security filter with
+//! errcode 87014, without saying which part of the article is to blame. This
+//! module splits an article into sections and paragraphs and bisects them with
+//! the `msg_sec_check` endpoint to locate the offending segments.
+
+use crate::error::{Error, Result};
+use crate::markdown::{ParseOptions, parse_markdown_with};
+use crate::output::{FORMATTER, OutputFormatter, annotations_enabled};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::path::Path;
+
+/// WeChat error code for content rejected by the security filter
+pub const CONTENT_RISK_ERRCODE: i32 = 87014;
+
+/// Path of the WeChat text security check endpoint
+pub const MSG_SEC_CHECK_PATH: &str = "/wxa/msg_sec_check";
+
+/// Response of the `msg_sec_check` endpoint
+#[derive(Debug, Deserialize)]
+pub struct SecCheckResponse {
+    /// 0 if the text passed, 87014 if it was flagged
+    #[serde(default)]
+    pub errcode: i32,
+    /// Error message
+    #[serde(default)]
+    pub errmsg: String,
+}
+
+impl SecCheckResponse {
+    /// Converts the response to a verdict; `Ok(false)` means the text was flagged
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for any errcode other than success or 87014
+    pub fn into_verdict(self) -> Result<bool> {
+        match self.errcode {
+            0 => Ok(true),
+            CONTENT_RISK_ERRCODE => Ok(false),
+            code => Err(Error::from_wechat(
+                wechat_pub_rs::WeChatError::from_api_response(code, self.errmsg),
+            )),
+        }
+    }
+}
+
+/// Checks text against the WeChat content security filter
+#[async_trait]
+pub trait ContentChecker: Send + Sync {
+    /// Returns `Ok(false)` if WeChat flags the text as risky
+    async fn is_text_safe(&self, text: &str) -> Result<bool>;
+}
+
+/// A contiguous range of article lines
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    /// Nearest heading above the segment, without the leading `#`s
+    pub heading: Option<String>,
+    /// First line of the segment in the file (1-based)
+    pub start_line: usize,
+    /// Last line of the segment in the file (1-based, inclusive)
+    pub end_line: usize,
+    /// Raw markdown text of the segment
+    pub text: String,
+}
+
+impl Segment {
+    /// Human readable location, e.g. `"Setup" (lines 12-18)`
+    pub fn location(&self) -> String {
+        let lines = if self.start_line == self.end_line {
+            format!("line {}", self.start_line)
+        } else {
+            format!("lines {}-{}", self.start_line, self.end_line)
+        };
+        match &self.heading {
+            Some(heading) => format!("\"{}\" ({})", heading, lines),
+            None => format!("before the first heading ({})", lines),
+        }
+    }
+}
+
+/// Splits a markdown body into sections, one per heading
+///
+/// `first_line` is the file line number of the first body line, so reported
+/// ranges match the file even when it has frontmatter.
+pub fn split_sections(body: &str, first_line: usize) -> Vec<Segment> {
+    let mut sections: Vec<Segment> = Vec::new();
+    let mut in_fence = false;
+
+    for (index, line) in body.lines().enumerate() {
+        let line_number = first_line + index;
+        let trimmed = line.trim_start();
+
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+
+        let heading = if in_fence { None } else { heading_text(trimmed) };
+        match (heading, sections.last_mut()) {
+            (None, Some(section)) => {
+                section.end_line = line_number;
+                section.text.push('\n');
+                section.text.push_str(line);
+            }
+            (heading, _) => sections.push(Segment {
+                heading,
+                start_line: line_number,
+                end_line: line_number,
+                text: line.to_string(),
+            }),
+        }
+    }
+
+    sections.retain(|section| !section.text.trim().is_empty());
+    sections
+}
+
+/// Splits a section into blank-line separated paragraphs, keeping code fences whole
+pub fn split_paragraphs(section: &Segment) -> Vec<Segment> {
+    let mut paragraphs: Vec<Segment> = Vec::new();
+    let mut current: Option<Segment> = None;
+    let mut in_fence = false;
+
+    for (index, line) in section.text.lines().enumerate() {
+        let line_number = section.start_line + index;
+        let trimmed = line.trim_start();
+
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+
+        if line.trim().is_empty() && !in_fence {
+            paragraphs.extend(current.take());
+            continue;
+        }
+
+        match current.as_mut() {
+            Some(paragraph) => {
+                paragraph.end_line = line_number;
+                paragraph.text.push('\n');
+                paragraph.text.push_str(line);
+            }
+            None => {
+                current = Some(Segment {
+                    heading: section.heading.clone(),
+                    start_line: line_number,
+                    end_line: line_number,
+                    text: line.to_string(),
+                })
+            }
+        }
+    }
+
+    paragraphs.extend(current);
+    paragraphs
+}
+
+/// Returns the text of an ATX heading line, or `None` if the line is not a heading
+fn heading_text(line: &str) -> Option<String> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let rest = &line[level..];
+    if !(rest.is_empty() || rest.starts_with(' ')) {
+        return None;
+    }
+    Some(rest.trim().trim_end_matches('#').trim().to_string())
+}
+
+/// Bisects segments and returns the indices of those that fail the check
+///
+/// Each step checks the concatenated text of a range; ranges that pass are
+/// discarded and failing ranges are halved until single segments remain.
+pub async fn find_risky_segments(
+    checker: &dyn ContentChecker,
+    segments: &[Segment],
+) -> Result<Vec<usize>> {
+    let mut risky = Vec::new();
+    let mut pending = vec![(0, segments.len())];
+
+    while let Some((start, end)) = pending.pop() {
+        if start >= end {
+            continue;
+        }
+
+        let text = segments[start..end]
+            .iter()
+            .map(|segment| segment.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        if checker.is_text_safe(&text).await? {
+            continue;
+        }
+
+        if end - start == 1 {
+            risky.push(start);
+        } else {
+            let mid = start + (end - start) / 2;
+            // Pushed in reverse so segments are visited in document order
+            pending.push((mid, end));
+            pending.push((start, mid));
+        }
+    }
+
+    risky.sort_unstable();
+    Ok(risky)
+}
+
+/// Locates the parts of a markdown file that trip the content security filter
+///
+/// Sections are bisected first; each flagged section is then narrowed down to
+/// its flagged paragraphs. A section is reported whole if none of its
+/// paragraphs fails on its own.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or a check request fails
+pub async fn diagnose_file(checker: &dyn ContentChecker, path: &Path) -> Result<Vec<Segment>> {
+    let content = tokio::fs::read_to_string(path).await?;
+    let quiet = ParseOptions {
+        quiet: true,
+        ..Default::default()
+    };
+    let (_, body) = parse_markdown_with(&content, &quiet)?;
+    let first_line = body_first_line(&content, &body);
+
+    let sections = split_sections(&body, first_line);
+    let mut findings = Vec::new();
+
+    for index in find_risky_segments(checker, &sections).await? {
+        let section = &sections[index];
+        let paragraphs = split_paragraphs(section);
+        let flagged = if paragraphs.len() > 1 {
+            find_risky_segments(checker, &paragraphs).await?
+        } else {
+            Vec::new()
+        };
+
+        if flagged.is_empty() {
+            findings.push(section.clone());
+        } else {
+            findings.extend(flagged.into_iter().map(|i| paragraphs[i].clone()));
+        }
+    }
+
+    Ok(findings)
+}
+
+/// Returns the 1-based file line on which the markdown body starts
+fn body_first_line(content: &str, body: &str) -> usize {
+    content
+        .len()
+        .checked_sub(body.len())
+        .filter(|offset| content.is_char_boundary(*offset) && content[*offset..] == *body)
+        .map(|offset| content[..offset].matches('\n').count() + 1)
+        .unwrap_or(1)
+}
+
+/// Prints the diagnosis result for a file
+pub fn print_diagnosis(path: &Path, findings: &[Segment]) {
+    if findings.is_empty() {
+        FORMATTER.print_info(&format!(
+            "content check found no single flagged section in {}; the combination of sections may be the trigger",
+            path.display()
+        ));
+        return;
+    }
+
+    if annotations_enabled() {
+        for segment in findings {
+            FORMATTER.print_file_warning(
+                path,
+                Some(segment.start_line),
+                &format!(
+                    "risky content (errcode {}): {}",
+                    CONTENT_RISK_ERRCODE,
+                    segment.location()
+                ),
+            );
+        }
+        return;
+    }
+
+    FORMATTER.print_warning(&format!(
+        "risky content in {} (errcode {}):",
+        path.display(),
+        CONTENT_RISK_ERRCODE
+    ));
+    for segment in findings {
+        println!("  {}:{} {}", path.display(), segment.start_line, segment.location());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Flags any text containing the given word and counts requests
+    struct WordChecker {
+        word: &'static str,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl ContentChecker for WordChecker {
+        async fn is_text_safe(&self, text: &str) -> Result<bool> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(!text.contains(self.word))
+        }
+    }
+
+    const BODY: &str = "Intro line\n\n## Setup\n\nInstall it.\n\n## Usage\n\nFirst paragraph.\n\nSecond has forbidden words.\nStill second.\n\n```\n## not a heading\n```\n\n## Summary\n\nDone.\n";
+
+    #[test]
+    fn test_split_sections() {
+        let sections = split_sections(BODY, 4);
+        let headings: Vec<_> = sections.iter().map(|s| s.heading.as_deref()).collect();
+        assert_eq!(headings, vec![None, Some("Setup"), Some("Usage"), Some("Summary")]);
+
+        let usage = &sections[2];
+        assert_eq!(usage.start_line, 10);
+        assert_eq!(usage.end_line, 20);
+        assert!(usage.text.contains("## not a heading"));
+    }
+
+    #[test]
+    fn test_split_paragraphs() {
+        let sections = split_sections(BODY, 1);
+        let paragraphs = split_paragraphs(&sections[2]);
+        let ranges: Vec<_> = paragraphs.iter().map(|p| (p.start_line, p.end_line)).collect();
+        assert_eq!(ranges, vec![(7, 7), (9, 9), (11, 12), (14, 16)]);
+        assert!(paragraphs.iter().all(|p| p.heading.as_deref() == Some("Usage")));
+    }
+
+    #[tokio::test]
+    async fn test_find_risky_segments_bisects() {
+        let checker = WordChecker {
+            word: "forbidden",
+            calls: AtomicUsize::new(0),
+        };
+        let sections = split_sections(BODY, 1);
+
+        let risky = find_risky_segments(&checker, &sections).await.unwrap();
+        assert_eq!(risky, vec![2]);
+        // whole, two halves, then the two sections of the failing half
+        assert_eq!(checker.calls.load(Ordering::SeqCst), 5);
+    }
+
+    #[tokio::test]
+    async fn test_diagnose_file_narrows_to_paragraph() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("post.md");
+        tokio::fs::write(&path, format!("---\ntitle: Post\n---\n{}", BODY))
+            .await
+            .unwrap();
+
+        let checker = WordChecker {
+            word: "forbidden",
+            calls: AtomicUsize::new(0),
+        };
+        let findings = diagnose_file(&checker, &path).await.unwrap();
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].heading.as_deref(), Some("Usage"));
+        assert_eq!((findings[0].start_line, findings[0].end_line), (14, 15));
+        assert_eq!(findings[0].location(), "\"Usage\" (lines 14-15)");
+    }
+
+    #[test]
+    fn test_sec_check_verdict() {
+        let verdict = |errcode| {
+            SecCheckResponse {
+                errcode,
+                errmsg: String::new(),
+            }
+            .into_verdict()
+        };
+        assert!(verdict(0).unwrap());
+        assert!(!verdict(CONTENT_RISK_ERRCODE).unwrap());
+        assert!(verdict(40001).unwrap_err().is_auth());
+    }
+
+    #[tokio::test]
+    async fn test_diagnose_clean_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("post.md");
+        tokio::fs::write(&path, BODY).await.unwrap();
+
+        let checker = WordChecker {
+            word: "absent",
+            calls: AtomicUsize::new(0),
+        };
+        assert!(diagnose_file(&checker, &path).await.unwrap().is_empty());
+        assert_eq!(checker.calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,298 @@
+//! Image format of generated covers
+//!
+//! Implements the `cover_format` and `cover_quality` settings. AI covers are
+//! downloaded as PNG and transcoded to the configured format before they are
+//! saved, and template covers are encoded in it. Generated cover names carry
+//! the matching extension, so the frontmatter `cover` always names the format
+//! actually on disk.
+//!
+//! A cover whose file name already has an image extension, e.g. one named in
+//! the frontmatter, is written in the format of that extension instead.
+//! Existing covers are only converted with `--convert-covers`.
+
+use crate::error::{Error, Result, ResultExt};
+use image::ImageFormat;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// JPEG quality used when `cover_quality` is not set
+pub const DEFAULT_COVER_QUALITY: u8 = 85;
+
+/// Image format generated covers are saved in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum CoverFormat {
+    /// Lossless PNG, as returned by the image providers
+    #[default]
+    Png,
+    /// JPEG at `cover_quality`
+    #[serde(alias = "jpg")]
+    #[value(alias = "jpg")]
+    Jpeg,
+    /// Lossless WebP
+    Webp,
+}
+
+impl CoverFormat {
+    /// Returns the file extension of covers in this format, without the dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+            Self::Webp => "webp",
+        }
+    }
+
+    /// Returns the format named by the extension of `path`, if it is one of these
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" => Some(Self::Jpeg),
+            "webp" => Some(Self::Webp),
+            _ => None,
+        }
+    }
+
+    fn image_format(self) -> ImageFormat {
+        match self {
+            Self::Png => ImageFormat::Png,
+            Self::Jpeg => ImageFormat::Jpeg,
+            Self::Webp => ImageFormat::WebP,
+        }
+    }
+}
+
+impl std::fmt::Display for CoverFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpeg",
+            Self::Webp => "webp",
+        })
+    }
+}
+
+/// Format and quality generated covers are encoded with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoverEncoding {
+    /// Format of covers with generated names
+    pub format: CoverFormat,
+    /// JPEG quality from 1 to 100; PNG and WebP are lossless
+    pub quality: u8,
+}
+
+impl Default for CoverEncoding {
+    fn default() -> Self {
+        Self {
+            format: CoverFormat::default(),
+            quality: DEFAULT_COVER_QUALITY,
+        }
+    }
+}
+
+impl CoverEncoding {
+    /// Returns the format of a cover written to `path`
+    ///
+    /// The extension of `path` wins, so a cover is never saved under a name
+    /// that claims another format.
+    pub fn format_for(&self, path: &Path) -> CoverFormat {
+        CoverFormat::from_path(path).unwrap_or(self.format)
+    }
+
+    /// Encodes `data`, an image in any supported format, for a cover written to `path`
+    ///
+    /// Data that is already in the right format is returned unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `data` is no supported image or cannot be encoded
+    pub fn encode(&self, data: Vec<u8>, path: &Path) -> Result<Vec<u8>> {
+        let format = self.format_for(path);
+        if image::guess_format(&data).ok() == Some(format.image_format()) {
+            return Ok(data);
+        }
+        let image = image::load_from_memory(&data)
+            .map_err(|e| Error::cover_image(path, format!("cannot decode image: {}", e)))?;
+        self.encode_image(&image, path)
+    }
+
+    /// Encodes `image` for a cover written to `path`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the image cannot be encoded
+    pub fn encode_image(&self, image: &image::DynamicImage, path: &Path) -> Result<Vec<u8>> {
+        let format = self.format_for(path);
+        let mut encoded = std::io::Cursor::new(Vec::new());
+        let result = match format {
+            CoverFormat::Jpeg => {
+                // JPEG has no alpha channel
+                let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(
+                    &mut encoded,
+                    self.quality.clamp(1, 100),
+                );
+                image.to_rgb8().write_with_encoder(encoder)
+            }
+            CoverFormat::Png | CoverFormat::Webp => image.write_to(&mut encoded, format.image_format()),
+        };
+        result.map_err(|e| {
+            Error::cover_image(path, format!("cannot encode {} cover: {}", format, e))
+        })?;
+        Ok(encoded.into_inner())
+    }
+}
+
+/// Returns `cover` with the extension of `format`, keeping its directory
+pub fn cover_name_with_format(cover: &str, format: CoverFormat) -> String {
+    Path::new(cover)
+        .with_extension(format.extension())
+        .display()
+        .to_string()
+}
+
+/// Copies the image `from` to `to`, transcoding it if their formats differ
+///
+/// # Errors
+///
+/// Returns an error if `from` cannot be read or transcoded, or `to` cannot be written
+pub async fn copy_cover(from: &Path, to: &Path, encoding: CoverEncoding) -> Result<()> {
+    if CoverFormat::from_path(from) == Some(encoding.format_for(to)) {
+        tokio::fs::copy(from, to).await?;
+        return Ok(());
+    }
+    let data = tokio::fs::read(from).await?;
+    let to_path = to.to_path_buf();
+    let encoded = tokio::task::spawn_blocking(move || encoding.encode(data, &to_path))
+        .await
+        .context("cover encoding task failed")??;
+    crate::markdown::write_file_atomic(to, &encoded).await
+}
+
+/// Converts the existing cover at `path` to the format of `encoding`
+///
+/// The converted image is written next to the original with the extension
+/// of the format, and the original is removed. Covers already in the format
+/// are left alone.
+///
+/// # Returns
+///
+/// The path of the converted cover, or `None` if nothing was converted
+///
+/// # Errors
+///
+/// Returns an error if the cover cannot be read, transcoded or written; the
+/// original is kept then
+pub async fn convert_cover(path: &Path, encoding: CoverEncoding) -> Result<Option<PathBuf>> {
+    if CoverFormat::from_path(path) == Some(encoding.format) {
+        return Ok(None);
+    }
+    let target = path.with_extension(encoding.format.extension());
+    if target.exists() {
+        return Err(Error::cover_image(
+            path,
+            format!("cannot convert cover, {} already exists", target.display()),
+        ));
+    }
+    copy_cover(path, &target, encoding).await?;
+    tokio::fs::remove_file(path).await?;
+    Ok(Some(target))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const JPEG_MAGIC: &[u8] = b"\xff\xd8\xff";
+    const PNG_MAGIC: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    fn sample_png() -> Vec<u8> {
+        let image = image::RgbaImage::from_fn(64, 32, |x, y| {
+            image::Rgba([(x * 4) as u8, (y * 8) as u8, 128, 255])
+        });
+        let mut data = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut data, ImageFormat::Png).unwrap();
+        data.into_inner()
+    }
+
+    fn encoding(format: CoverFormat) -> CoverEncoding {
+        CoverEncoding {
+            format,
+            ..CoverEncoding::default()
+        }
+    }
+
+    #[test]
+    fn test_encode_uses_configured_format_or_extension() {
+        let png = sample_png();
+
+        let jpeg = encoding(CoverFormat::Jpeg)
+            .encode(png.clone(), Path::new("post_cover_1.jpg"))
+            .unwrap();
+        assert!(jpeg.starts_with(JPEG_MAGIC));
+
+        let webp = encoding(CoverFormat::Webp)
+            .encode(png.clone(), Path::new("post_cover_1.webp"))
+            .unwrap();
+        assert_eq!(&webp[..4], b"RIFF");
+        assert_eq!(&webp[8..12], b"WEBP");
+
+        // Already the right format: kept byte for byte
+        let kept = CoverEncoding::default()
+            .encode(png.clone(), Path::new("cover.png"))
+            .unwrap();
+        assert_eq!(kept, png);
+
+        // A named cover keeps the format of its extension
+        let named = encoding(CoverFormat::Jpeg)
+            .encode(png.clone(), Path::new("images/cover.png"))
+            .unwrap();
+        assert!(named.starts_with(PNG_MAGIC));
+        let no_extension = encoding(CoverFormat::Jpeg)
+            .encode(png, Path::new("cover"))
+            .unwrap();
+        assert!(no_extension.starts_with(JPEG_MAGIC));
+    }
+
+    #[test]
+    fn test_encode_rejects_non_images() {
+        let err = encoding(CoverFormat::Jpeg)
+            .encode(b"not an image".to_vec(), Path::new("cover.jpg"))
+            .unwrap_err();
+        assert!(err.to_string().contains("cannot decode image"), "{}", err);
+    }
+
+    #[test]
+    fn test_cover_name_with_format() {
+        assert_eq!(
+            cover_name_with_format("images/post_cover_1.png", CoverFormat::Jpeg),
+            "images/post_cover_1.jpg"
+        );
+        assert_eq!(cover_name_with_format("cover", CoverFormat::Webp), "cover.webp");
+        assert_eq!(CoverFormat::from_path(Path::new("a.JPEG")), Some(CoverFormat::Jpeg));
+        assert_eq!(CoverFormat::from_path(Path::new("a.gif")), None);
+    }
+
+    #[tokio::test]
+    async fn test_convert_cover_replaces_original() {
+        let temp_dir = TempDir::new().unwrap();
+        let png_path = temp_dir.path().join("cover.png");
+        std::fs::write(&png_path, sample_png()).unwrap();
+
+        let converted = convert_cover(&png_path, encoding(CoverFormat::Jpeg))
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(converted, temp_dir.path().join("cover.jpg"));
+        assert!(std::fs::read(&converted).unwrap().starts_with(JPEG_MAGIC));
+        assert!(!png_path.exists());
+
+        // Nothing to do for a cover already in the format
+        assert_eq!(
+            convert_cover(&converted, encoding(CoverFormat::Jpeg)).await.unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! Lossless PNG cover optimization
+//!
+//! Implements the opt-in `optimize_covers` setting: covers and thumbnails are
+//! recompressed with `oxipng` before upload. The optimized image is decoded and
+//! compared pixel by pixel with the original before it replaces the file, so a
+//! failed or lossy optimization leaves the original untouched.
+
+use crate::error::{Error, Result, ResultExt};
+use std::path::{Path, PathBuf};
+
+/// Files smaller than this are not optimized
+pub const OPTIMIZE_THRESHOLD_BYTES: u64 = 256 * 1024;
+
+/// oxipng preset; 2 is the CLI default and a good speed/size balance
+const OXIPNG_PRESET: u8 = 2;
+
+/// PNG file signature
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Result of optimizing a single image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptimizeOutcome {
+    /// The file was replaced with a smaller, pixel-identical version
+    Optimized {
+        /// Size before optimization in bytes
+        before: u64,
+        /// Size after optimization in bytes
+        after: u64,
+    },
+    /// The file was left as is
+    Skipped {
+        /// Why the file was not optimized
+        reason: String,
+    },
+}
+
+impl OptimizeOutcome {
+    /// Bytes saved by the optimization
+    pub fn saved(&self) -> u64 {
+        match self {
+            Self::Optimized { before, after } => before - after,
+            Self::Skipped { .. } => 0,
+        }
+    }
+
+    fn skipped(reason: impl Into<String>) -> Self {
+        Self::Skipped {
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Optimizes a PNG file in place if it is at least `threshold` bytes
+///
+/// Non-PNG files and files under the threshold are skipped. The file is only
+/// replaced if the result is smaller and decodes to the same pixels.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or written, or if optimization
+/// fails or changes the image
+pub async fn optimize_png(path: &Path, threshold: u64) -> Result<OptimizeOutcome> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || optimize_png_blocking(&path, threshold))
+        .await
+        .context("PNG optimization task failed")?
+}
+
+fn optimize_png_blocking(path: &Path, threshold: u64) -> Result<OptimizeOutcome> {
+    let original = std::fs::read(path)?;
+    let before = original.len() as u64;
+
+    if !original.starts_with(PNG_SIGNATURE) {
+        return Ok(OptimizeOutcome::skipped("not a PNG"));
+    }
+    if before < threshold {
+        return Ok(OptimizeOutcome::skipped(format!(
+            "under {} KB",
+            threshold / 1024
+        )));
+    }
+
+    let optimized =
+        oxipng::optimize_from_memory(&original, &oxipng::Options::from_preset(OXIPNG_PRESET))
+            .map_err(|e| Error::cover_image(path, format!("PNG optimization failed: {}", e)))?;
+    let after = optimized.len() as u64;
+    if after >= before {
+        return Ok(OptimizeOutcome::skipped("already optimal"));
+    }
+
+    // Never replace the cover with something that does not decode to the same image
+    let original_pixels = decode_rgba(&original)
+        .map_err(|e| Error::cover_image(path, format!("cannot decode original PNG: {}", e)))?;
+    let optimized_pixels = decode_rgba(&optimized)
+        .map_err(|e| Error::cover_image(path, format!("optimized PNG is invalid: {}", e)))?;
+    if original_pixels != optimized_pixels {
+        return Err(Error::cover_image(
+            path,
+            "optimized PNG does not match the original, keeping the original",
+        ));
+    }
+
+    // Write next to the original and rename so an interruption cannot truncate it
+    let temp_path = optimized_temp_path(path);
+    std::fs::write(&temp_path, &optimized)?;
+    if let Err(e) = std::fs::rename(&temp_path, path) {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(e.into());
+    }
+
+    Ok(OptimizeOutcome::Optimized { before, after })
+}
+
+fn optimized_temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".optimizing");
+    path.with_file_name(name)
+}
+
+/// Decodes a PNG to its dimensions and 8-bit RGBA pixels
+fn decode_rgba(data: &[u8]) -> std::result::Result<(u32, u32, Vec<u8>), png::DecodingError> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer)?;
+    buffer.truncate(info.buffer_size());
+
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => buffer,
+        png::ColorType::Rgb => buffer
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buffer
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => buffer.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+        // Palettes are expanded by the normalizing transformation
+        png::ColorType::Indexed => buffer,
+    };
+
+    Ok((info.width, info.height, rgba))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Encodes an uncompressed-ish RGBA gradient so oxipng has something to save
+    fn sample_png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut data, width, height);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_compression(png::Compression::Fast);
+            encoder.set_filter(png::FilterType::NoFilter);
+            let mut writer = encoder.write_header().unwrap();
+            let pixels: Vec<u8> = (0..width * height)
+                .flat_map(|i| [(i % 256) as u8, (i / 256 % 256) as u8, 128, 255])
+                .collect();
+            writer.write_image_data(&pixels).unwrap();
+        }
+        data
+    }
+
+    #[tokio::test]
+    async fn test_optimize_png_shrinks_and_keeps_pixels() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("cover.png");
+        let original = sample_png(256, 256);
+        std::fs::write(&path, &original).unwrap();
+
+        let outcome = optimize_png(&path, 0).await.unwrap();
+
+        let OptimizeOutcome::Optimized { before, after } = outcome else {
+            panic!("expected optimization, got {:?}", outcome);
+        };
+        assert_eq!(before, original.len() as u64);
+        assert!(after < before);
+
+        let optimized = std::fs::read(&path).unwrap();
+        assert_eq!(optimized.len() as u64, after);
+        assert_eq!(
+            decode_rgba(&optimized).unwrap(),
+            decode_rgba(&original).unwrap()
+        );
+        assert!(!optimized_temp_path(&path).exists());
+    }
+
+    #[tokio::test]
+    async fn test_optimize_png_skips_small_and_non_png_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let small = temp_dir.path().join("small.png");
+        std::fs::write(&small, sample_png(16, 16)).unwrap();
+        let jpeg = temp_dir.path().join("cover.jpg");
+        std::fs::write(&jpeg, b"\xff\xd8\xff\xe0 not a png").unwrap();
+
+        assert!(matches!(
+            optimize_png(&small, OPTIMIZE_THRESHOLD_BYTES)
+                .await
+                .unwrap(),
+            OptimizeOutcome::Skipped { .. }
+        ));
+        assert_eq!(
+            optimize_png(&jpeg, 0).await.unwrap(),
+            OptimizeOutcome::Skipped {
+                reason: "not a PNG".to_string()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_optimize_png_leaves_corrupt_file_untouched() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("broken.png");
+        let mut data = sample_png(64, 64);
+        data.truncate(data.len() / 2);
+        std::fs::write(&path, &data).unwrap();
+
+        assert!(optimize_png(&path, 0).await.is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+    }
+}
@@ -0,0 +1,169 @@
+//! Provenance metadata for AI-generated covers
+//!
+//! Implements the opt-in `--embed-provenance` flag: PNG covers generated by an
+//! AI provider get text chunks naming the provider, model, prompt and time of
+//! generation. The chunks are inserted after the PNG header, so the image data
+//! is left byte for byte as the provider returned it.
+
+use crate::error::{Error, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use png::text_metadata::{EncodableTextChunk, ITXtChunk, TEXtChunk};
+use std::path::Path;
+
+/// PNG file signature
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Keyword of the chunk naming the AI provider
+pub const PROVIDER_KEYWORD: &str = "AI Provider";
+/// Keyword of the chunk naming the image model
+pub const MODEL_KEYWORD: &str = "AI Model";
+/// Keyword of the chunk holding the image prompt
+pub const PROMPT_KEYWORD: &str = "Prompt";
+/// Standard PNG keyword of the chunk holding the generation time
+pub const CREATION_TIME_KEYWORD: &str = "Creation Time";
+
+/// How a cover was generated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoverProvenance {
+    /// Name of the AI provider, e.g. `OpenAI`
+    pub provider: String,
+    /// Image model that generated the cover
+    pub model: String,
+    /// Prompt the image was generated from
+    pub prompt: String,
+    /// When the image was generated
+    pub created: DateTime<Utc>,
+}
+
+impl CoverProvenance {
+    /// Returns the keyword and text of each chunk, the software first
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Software", format!("wx-uploader {}", env!("CARGO_PKG_VERSION"))),
+            (PROVIDER_KEYWORD, self.provider.clone()),
+            (MODEL_KEYWORD, self.model.clone()),
+            (PROMPT_KEYWORD, self.prompt.clone()),
+            (
+                CREATION_TIME_KEYWORD,
+                self.created.to_rfc3339_opts(SecondsFormat::Secs, true),
+            ),
+        ]
+    }
+}
+
+/// Returns `png` with the provenance inserted as text chunks after its header
+///
+/// Text that Latin-1 cannot encode, such as a prompt quoting a Chinese title,
+/// is written as a UTF-8 `iTXt` chunk instead of `tEXt`.
+///
+/// # Errors
+///
+/// Returns an error naming `path` if `png` is not a PNG image
+pub fn embed_provenance(png: &[u8], provenance: &CoverProvenance, path: &Path) -> Result<Vec<u8>> {
+    let header_end = header_end(png)
+        .ok_or_else(|| Error::cover_image(path, "cannot embed provenance: not a PNG image"))?;
+
+    let mut chunks = Vec::new();
+    for (keyword, text) in provenance.entries() {
+        let mut chunk = Vec::new();
+        if TEXtChunk::new(keyword, text.as_str()).encode(&mut chunk).is_err() {
+            chunk.clear();
+            ITXtChunk::new(keyword, text.as_str())
+                .encode(&mut chunk)
+                .map_err(|e| {
+                    Error::cover_image(path, format!("cannot embed provenance: {}", e))
+                })?;
+        }
+        chunks.extend(chunk);
+    }
+
+    let mut output = Vec::with_capacity(png.len() + chunks.len());
+    output.extend_from_slice(&png[..header_end]);
+    output.extend(chunks);
+    output.extend_from_slice(&png[header_end..]);
+    Ok(output)
+}
+
+/// Returns the offset just past the `IHDR` chunk, which PNG requires first
+fn header_end(png: &[u8]) -> Option<usize> {
+    let rest = png.strip_prefix(PNG_SIGNATURE)?;
+    let length = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+    if rest.get(4..8)? != b"IHDR" {
+        return None;
+    }
+    // Length, type, data and CRC
+    let end = PNG_SIGNATURE.len() + 12 + length;
+    (end <= png.len()).then_some(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn sample_png() -> Vec<u8> {
+        let image = image::RgbaImage::from_fn(16, 8, |x, y| {
+            image::Rgba([(x * 16) as u8, (y * 32) as u8, 64, 255])
+        });
+        let mut data = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut data, image::ImageFormat::Png).unwrap();
+        data.into_inner()
+    }
+
+    fn provenance(prompt: &str) -> CoverProvenance {
+        CoverProvenance {
+            provider: "OpenAI".to_string(),
+            model: "dall-e-3".to_string(),
+            prompt: prompt.to_string(),
+            created: Utc.with_ymd_and_hms(2026, 10, 17, 8, 30, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_embed_provenance_keeps_the_image() {
+        let original = sample_png();
+        let path = Path::new("cover.png");
+        let tagged = embed_provenance(&original, &provenance("A quiet harbor"), path).unwrap();
+
+        let decoder = png::Decoder::new(tagged.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let texts: Vec<(String, String)> = reader
+            .info()
+            .uncompressed_latin1_text
+            .iter()
+            .map(|chunk| (chunk.keyword.clone(), chunk.text.clone()))
+            .collect();
+        assert!(texts.contains(&("AI Provider".to_string(), "OpenAI".to_string())));
+        assert!(texts.contains(&("AI Model".to_string(), "dall-e-3".to_string())));
+        assert!(texts.contains(&("Prompt".to_string(), "A quiet harbor".to_string())));
+        assert!(texts.contains(&("Creation Time".to_string(), "2026-10-17T08:30:00Z".to_string())));
+
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        let image = image::load_from_memory(&original).unwrap();
+        assert_eq!(pixels, image.to_rgba8().into_raw());
+        // Everything but the inserted chunks is unchanged
+        assert!(tagged.ends_with(&original[33..]));
+    }
+
+    #[test]
+    fn test_embed_provenance_writes_utf8_prompts_as_itxt() {
+        let tagged =
+            embed_provenance(&sample_png(), &provenance("春天的花园"), Path::new("c.png")).unwrap();
+        let reader = png::Decoder::new(tagged.as_slice()).read_info().unwrap();
+        let prompt = reader
+            .info()
+            .utf8_text
+            .iter()
+            .find(|chunk| chunk.keyword == "Prompt")
+            .expect("the prompt is an iTXt chunk");
+        assert_eq!(prompt.get_text().unwrap(), "春天的花园");
+    }
+
+    #[test]
+    fn test_embed_provenance_rejects_other_formats() {
+        let err = embed_provenance(b"\xff\xd8\xff\xe0", &provenance("x"), Path::new("c.jpg"))
+            .unwrap_err();
+        assert!(err.to_string().contains("not a PNG image"), "{}", err);
+    }
+}
@@ -0,0 +1,524 @@
+//! Template covers
+//!
+//! Implements `cover_template`: instead of asking an AI provider, a missing
+//! cover is rendered by drawing the article title onto a fixed background
+//! image. Titles are wrapped to a maximum width, breaking between CJK
+//! characters as well as at spaces, so long Chinese titles wrap without
+//! splitting punctuation from the text it belongs to.
+
+use crate::cover_format::CoverEncoding;
+use crate::error::{Error, Result, ResultExt};
+use crate::language::is_cjk;
+use ab_glyph::{Font, FontVec, PxScale, ScaleFont, point};
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Font size in pixels when none is configured
+pub const DEFAULT_FONT_SIZE: u32 = 64;
+
+/// Line height as a multiple of the font size when none is configured
+pub const DEFAULT_LINE_HEIGHT: f32 = 1.3;
+
+/// Distance of the text from the top-left corner when no position is configured
+pub const DEFAULT_MARGIN: u32 = 64;
+
+/// Text color when none is configured
+pub const DEFAULT_COLOR: &str = "#ffffff";
+
+/// Punctuation that must not start a line
+const CLOSING_PUNCTUATION: &str = "，。、；：！？）」』》】〉〕’”…・．,.;:!?)]}%";
+
+/// Punctuation that must not end a line
+const OPENING_PUNCTUATION: &str = "（「『《【〈〔‘“([{";
+
+/// Layout of a template cover
+///
+/// Set in the configuration `settings` for every article, or in the
+/// frontmatter for a single one, where each field overrides the configured
+/// value. Paths in the frontmatter are resolved like the cover path.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CoverTemplate {
+    /// Background image the title is drawn on; its size is the cover size
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    /// TrueType or OpenType font file, which needs CJK glyphs for Chinese titles
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub font: Option<String>,
+    /// Font size in pixels (default: 64)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub font_size: Option<u32>,
+    /// Text color as `#rrggbb` or `#rrggbbaa` (default: white)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// Left edge of the text in pixels (default: 64)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x: Option<u32>,
+    /// Top edge of the first line in pixels (default: 64)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y: Option<u32>,
+    /// Width the title is wrapped to in pixels (default: up to the same margin on the right)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_width: Option<u32>,
+    /// Line height as a multiple of the font size (default: 1.3)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_height: Option<f32>,
+}
+
+impl CoverTemplate {
+    /// Returns this template with unset fields taken from `base`
+    pub fn or(self, base: &CoverTemplate) -> Self {
+        Self {
+            background: self.background.or_else(|| base.background.clone()),
+            font: self.font.or_else(|| base.font.clone()),
+            font_size: self.font_size.or(base.font_size),
+            color: self.color.or_else(|| base.color.clone()),
+            x: self.x.or(base.x),
+            y: self.y.or(base.y),
+            max_width: self.max_width.or(base.max_width),
+            line_height: self.line_height.or(base.line_height),
+        }
+    }
+
+    /// Returns this template with the background and font paths passed through `resolve`
+    pub fn map_paths(self, resolve: impl Fn(&str) -> PathBuf) -> Self {
+        let resolve = |path: Option<String>| path.map(|p| resolve(&p).display().to_string());
+        Self {
+            background: resolve(self.background),
+            font: resolve(self.font),
+            ..self
+        }
+    }
+
+    /// Checks the values that do not depend on the background and font files
+    ///
+    /// # Errors
+    ///
+    /// Returns a configuration error naming the invalid field
+    pub fn validate(&self) -> Result<()> {
+        if let Some(color) = &self.color
+            && parse_color(color).is_none()
+        {
+            return Err(Error::config(format!(
+                "cover_template: invalid color '{}', expected #rrggbb or #rrggbbaa",
+                color
+            )));
+        }
+        if self.font_size == Some(0) {
+            return Err(Error::config("cover_template: font_size must be positive"));
+        }
+        if self
+            .line_height
+            .is_some_and(|height| !height.is_finite() || height <= 0.0)
+        {
+            return Err(Error::config("cover_template: line_height must be positive"));
+        }
+        if self.max_width == Some(0) {
+            return Err(Error::config("cover_template: max_width must be positive"));
+        }
+        Ok(())
+    }
+}
+
+/// Parses a `#rrggbb` or `#rrggbbaa` color
+pub fn parse_color(color: &str) -> Option<Rgba<u8>> {
+    let hex = color.strip_prefix('#')?;
+    if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    let alpha = if hex.len() == 8 { channel(6)? } else { 255 };
+    Some(Rgba([channel(0)?, channel(2)?, channel(4)?, alpha]))
+}
+
+/// Returns true for characters laid out one per cell, between which lines may break
+fn is_wide(c: char) -> bool {
+    is_cjk(c)
+        || matches!(c,
+            '\u{3000}'..='\u{303F}'     // CJK punctuation
+            | '\u{3040}'..='\u{30FF}'   // Kana
+            | '\u{AC00}'..='\u{D7AF}'   // Hangul syllables
+            | '\u{FF00}'..='\u{FFEF}')  // Fullwidth forms
+        || (!c.is_ascii() && (CLOSING_PUNCTUATION.contains(c) || OPENING_PUNCTUATION.contains(c)))
+}
+
+/// Returns true if a line may break between the adjacent characters `a` and `b`
+fn can_break_between(a: char, b: char) -> bool {
+    (is_wide(a) || is_wide(b))
+        && !OPENING_PUNCTUATION.contains(a)
+        && !CLOSING_PUNCTUATION.contains(b)
+}
+
+/// Splits `text` into lines no wider than `max_width` as measured by `measure`
+///
+/// Lines break at whitespace and between CJK characters, which are not
+/// separated by spaces. Closing punctuation such as `，` or `）` stays on the
+/// line before it and opening punctuation such as `《` moves to the next one.
+/// A word wider than a whole line is broken between its characters, with
+/// closing punctuation allowed to hang past the end of the line.
+pub fn wrap_text(text: &str, max_width: f32, measure: impl Fn(&str) -> f32) -> Vec<String> {
+    // Unbreakable segments, each with whether whitespace precedes it
+    let mut segments: Vec<(bool, String)> = Vec::new();
+    let mut previous: Option<char> = None;
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            space = true;
+            continue;
+        }
+        match (segments.last_mut(), previous) {
+            (Some((_, segment)), Some(p)) if !space && !can_break_between(p, c) => segment.push(c),
+            _ => segments.push((space && previous.is_some(), c.to_string())),
+        }
+        space = false;
+        previous = Some(c);
+    }
+
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for (space, segment) in segments {
+        if !line.is_empty() {
+            let candidate = format!("{}{}{}", line, if space { " " } else { "" }, segment);
+            if measure(&candidate) <= max_width {
+                line = candidate;
+                continue;
+            }
+            lines.push(std::mem::take(&mut line));
+        }
+
+        // Start a new line, breaking a segment that does not fit on one;
+        // closing punctuation hangs past the end rather than starting a line
+        for c in segment.chars() {
+            let candidate = format!("{}{}", line, c);
+            if !line.is_empty()
+                && measure(&candidate) > max_width
+                && !CLOSING_PUNCTUATION.contains(c)
+            {
+                lines.push(std::mem::replace(&mut line, c.to_string()));
+            } else {
+                line = candidate;
+            }
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Renders `title` onto the template background and writes the cover to `output`
+///
+/// The cover is encoded in the format the extension of `output` names,
+/// otherwise in the format of `encoding`.
+///
+/// # Errors
+///
+/// Returns an error if the template is incomplete or invalid, or if the
+/// background, font or output file cannot be read or written
+pub async fn render_cover(
+    template: &CoverTemplate,
+    title: &str,
+    output: &Path,
+    encoding: CoverEncoding,
+) -> Result<()> {
+    let template = template.clone();
+    let title = title.to_string();
+    let output = output.to_path_buf();
+    tokio::task::spawn_blocking(move || render_cover_blocking(&template, &title, &output, encoding))
+        .await
+        .context("cover template task failed")?
+}
+
+fn render_cover_blocking(
+    template: &CoverTemplate,
+    title: &str,
+    output: &Path,
+    encoding: CoverEncoding,
+) -> Result<()> {
+    template.validate()?;
+    let background = template
+        .background
+        .as_deref()
+        .ok_or_else(|| Error::config("cover_template: background is required"))?;
+    let font_path = template
+        .font
+        .as_deref()
+        .ok_or_else(|| Error::config("cover_template: font is required"))?;
+
+    let mut canvas = image::open(background)
+        .map_err(|e| Error::cover_image(background, format!("cannot read background: {}", e)))?
+        .into_rgba8();
+    let font = FontVec::try_from_vec(std::fs::read(font_path)?)
+        .map_err(|e| Error::cover_image(font_path, format!("cannot load font: {}", e)))?;
+
+    let font_size = template.font_size.unwrap_or(DEFAULT_FONT_SIZE) as f32;
+    let font = font.as_scaled(PxScale::from(font_size));
+    let color = parse_color(template.color.as_deref().unwrap_or(DEFAULT_COLOR))
+        .expect("color is validated");
+    let x = template.x.unwrap_or(DEFAULT_MARGIN);
+    let y = template.y.unwrap_or(DEFAULT_MARGIN);
+    let max_width = template
+        .max_width
+        .unwrap_or_else(|| canvas.width().saturating_sub(x.saturating_mul(2)).max(1));
+    let line_height = font_size * template.line_height.unwrap_or(DEFAULT_LINE_HEIGHT);
+
+    let lines = wrap_text(title, max_width as f32, |line| line_width(&font, line));
+    for (index, line) in lines.iter().enumerate() {
+        let baseline = y as f32 + font.ascent() + index as f32 * line_height;
+        draw_line(&mut canvas, &font, line, x as f32, baseline, color);
+    }
+
+    let encoded = encoding.encode_image(&canvas.into(), output)?;
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(output, encoded)
+        .map_err(|e| Error::cover_image(output, format!("cannot write cover: {}", e)))
+}
+
+/// Width of `text` in pixels, including kerning
+fn line_width<F: Font>(font: &impl ScaleFont<F>, text: &str) -> f32 {
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let glyph = font.glyph_id(c);
+        if let Some(previous) = previous {
+            width += font.kern(previous, glyph);
+        }
+        width += font.h_advance(glyph);
+        previous = Some(glyph);
+    }
+    width
+}
+
+/// Draws one line of text with its baseline at `baseline`, blending over the canvas
+fn draw_line<F: Font>(
+    canvas: &mut RgbaImage,
+    font: &impl ScaleFont<F>,
+    text: &str,
+    x: f32,
+    baseline: f32,
+    color: Rgba<u8>,
+) {
+    let mut caret = x;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = font.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += font.kern(previous, id);
+        }
+        let glyph = id.with_scale_and_position(font.scale(), point(caret, baseline));
+        caret += font.h_advance(id);
+        previous = Some(id);
+
+        let Some(outline) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outline.px_bounds();
+        outline.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i64 + i64::from(gx);
+            let py = bounds.min.y as i64 + i64::from(gy);
+            let inside = (0..i64::from(canvas.width())).contains(&px)
+                && (0..i64::from(canvas.height())).contains(&py);
+            if !inside {
+                return;
+            }
+            let alpha = coverage.clamp(0.0, 1.0) * f32::from(color[3]) / 255.0;
+            let pixel = canvas.get_pixel_mut(px as u32, py as u32);
+            for channel in 0..3 {
+                let blended =
+                    f32::from(color[channel]) * alpha + f32::from(pixel[channel]) * (1.0 - alpha);
+                pixel[channel] = blended.round() as u8;
+            }
+            pixel[3] = pixel[3].max((alpha * 255.0).round() as u8);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Two units per wide character and one per other character
+    fn cells(text: &str) -> f32 {
+        text.chars().map(|c| if is_wide(c) { 2.0 } else { 1.0 }).sum()
+    }
+
+    fn fixture_font() -> String {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/DejaVuSansMono.ttf")
+            .display()
+            .to_string()
+    }
+
+    #[test]
+    fn test_wrap_latin_text_at_spaces() {
+        assert_eq!(
+            wrap_text("Weekly digest of Rust news", 12.0, cells),
+            vec!["Weekly", "digest of", "Rust news"]
+        );
+        assert_eq!(wrap_text("  Short  ", 12.0, cells), vec!["Short"]);
+        assert!(wrap_text("", 12.0, cells).is_empty());
+
+        // A word longer than a line is broken between characters
+        assert_eq!(wrap_text("abcdefgh xy", 4.0, cells), vec!["abcd", "efgh", "xy"]);
+    }
+
+    #[test]
+    fn test_wrap_chinese_text_between_characters() {
+        assert_eq!(
+            wrap_text("深入理解异步编程模型", 8.0, cells),
+            vec!["深入理解", "异步编程", "模型"]
+        );
+
+        // Mixed text breaks around the Latin word, never inside it
+        assert_eq!(
+            wrap_text("用Rust写异步程序", 7.0, cells),
+            vec!["用Rust", "写异步", "程序"]
+        );
+    }
+
+    #[test]
+    fn test_wrap_keeps_punctuation_with_its_text() {
+        // `，` would start the second line; it stays with `步`
+        assert_eq!(
+            wrap_text("理解异步，编程模型", 8.0, cells),
+            vec!["理解异", "步，编程", "模型"]
+        );
+
+        // `《` would end the first line; it moves on with the title it opens
+        assert_eq!(
+            wrap_text("本周推荐《异步编程》", 10.0, cells),
+            vec!["本周推荐", "《异步编", "程》"]
+        );
+
+        // Closing punctuation after a Latin word stays attached too
+        assert_eq!(wrap_text("周报（Rust）", 8.0, cells), vec!["周报", "（Rust）"]);
+
+        // Broken inside an overlong segment, closing punctuation hangs instead
+        assert_eq!(wrap_text("周报（Rust）", 6.0, cells), vec!["周报", "（Rust）"]);
+
+        for line in wrap_text("第一章：从零开始，理解异步运行时。下一步！", 7.0, cells) {
+            let first = line.chars().next().unwrap();
+            let last = line.chars().last().unwrap();
+            assert!(!CLOSING_PUNCTUATION.contains(first), "line starts with {}", first);
+            assert!(!OPENING_PUNCTUATION.contains(last), "line ends with {}", last);
+            assert!(cells(&line) <= 7.0, "line too wide: {}", line);
+        }
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#ffffff"), Some(Rgba([255, 255, 255, 255])));
+        assert_eq!(parse_color("#10203080"), Some(Rgba([16, 32, 48, 128])));
+        assert_eq!(parse_color("ffffff"), None);
+        assert_eq!(parse_color("#fff"), None);
+        assert_eq!(parse_color("#gggggg"), None);
+    }
+
+    #[test]
+    fn test_template_or_and_validate() {
+        let base = CoverTemplate {
+            background: Some("bg.png".to_string()),
+            font: Some("font.ttf".to_string()),
+            font_size: Some(48),
+            ..Default::default()
+        };
+        let own = CoverTemplate {
+            background: Some("weekly.png".to_string()),
+            color: Some("#000000".to_string()),
+            ..Default::default()
+        };
+
+        let merged = own.or(&base);
+        assert_eq!(merged.background.as_deref(), Some("weekly.png"));
+        assert_eq!(merged.font.as_deref(), Some("font.ttf"));
+        assert_eq!(merged.font_size, Some(48));
+        assert_eq!(merged.color.as_deref(), Some("#000000"));
+        assert!(merged.validate().is_ok());
+
+        let invalid = CoverTemplate {
+            color: Some("white".to_string()),
+            ..Default::default()
+        };
+        assert!(invalid.validate().unwrap_err().to_string().contains("invalid color 'white'"));
+        let invalid = CoverTemplate {
+            font_size: Some(0),
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_render_cover_draws_title_on_background() {
+        let temp_dir = TempDir::new().unwrap();
+        let background = temp_dir.path().join("background.png");
+        RgbaImage::from_pixel(900, 383, Rgba([20, 40, 80, 255]))
+            .save(&background)
+            .unwrap();
+
+        let template = CoverTemplate {
+            background: Some(background.display().to_string()),
+            font: Some(fixture_font()),
+            font_size: Some(40),
+            color: Some("#ffffff".to_string()),
+            x: Some(50),
+            y: Some(60),
+            max_width: Some(400),
+            ..Default::default()
+        };
+        let output = temp_dir.path().join("covers").join("cover.png");
+        render_cover(
+            &template,
+            "Weekly Digest: async Rust in production",
+            &output,
+            CoverEncoding::default(),
+        )
+        .await
+        .unwrap();
+
+        let cover = image::open(&output).unwrap().into_rgba8();
+        assert_eq!(cover.dimensions(), (900, 383));
+
+        // Text is drawn inside the layout box and nowhere else
+        let is_text = |p: &Rgba<u8>| p[0] > 128;
+        let text_pixels: Vec<_> = cover
+            .enumerate_pixels()
+            .filter(|(_, _, p)| is_text(p))
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        assert!(text_pixels.len() > 500, "only {} text pixels", text_pixels.len());
+        assert!(text_pixels.iter().all(|&(x, y)| (50..=450).contains(&x) && y >= 60));
+
+        // The title wraps onto more than one line
+        let max_y = text_pixels.iter().map(|&(_, y)| y).max().unwrap();
+        assert!(max_y > 60 + 40 * 13 / 10, "text ends at y={}", max_y);
+    }
+
+    #[tokio::test]
+    async fn test_render_cover_reports_missing_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let output = temp_dir.path().join("cover.png");
+
+        let template = CoverTemplate {
+            font: Some(fixture_font()),
+            ..Default::default()
+        };
+        let err = render_cover(&template, "Title", &output, CoverEncoding::default())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("background is required"));
+
+        let template = CoverTemplate {
+            background: Some(temp_dir.path().join("missing.png").display().to_string()),
+            font: Some(fixture_font()),
+            ..Default::default()
+        };
+        let err = render_cover(&template, "Title", &output, CoverEncoding::default())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("cannot read background"));
+        assert!(!output.exists());
+    }
+}
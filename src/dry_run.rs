@@ -0,0 +1,684 @@
+//! Dry-run planning
+//!
+//! Implements `--dry-run` and `--diff`: works out what an upload run would do
+//! to each file without uploading, generating covers or writing to disk.
+//! Frontmatter changes are applied in memory with the same helpers the real
+//! run uses and rendered through `format_markdown`, so the diff matches what
+//! would be written. With `--verbose`, the plan also shows where each cover
+//! would come from, the style the article would be rendered with and the
+//! estimated AI cost. The skip, cover and style decisions are the ones the
+//! upload makes, and no network calls are made.
+
+use crate::budget::CostEstimate;
+use crate::cover_format::{CoverFormat, cover_name_with_format};
+use crate::error::Result;
+use crate::markdown::{fill_new_frontmatter, format_markdown, parse_markdown_file_with};
+use crate::models::{Config, Frontmatter};
+use crate::output::{DiffFormatter, FORMATTER, FilePathFormatter, OutputFormatter};
+use crate::providers::{UniversalAIClient, generated_cover_filename};
+use crate::slug::{plan_slugs, slug_for};
+use crate::wechat::{
+    CoverPlan, DraftIndex, ProcessOptions, collect_directory_files, cover_estimate,
+    default_cover_filename, existing_draft_reason, group_files_by_account,
+    mark_uploaded, mark_uploaded_to_accounts, plan_cover, resolve_cover_path_in_root,
+    resolve_upload_style, select_directory_cover, unrecognized_extension_warning,
+    upload_skip_reason,
+};
+use crate::wechat_api::{DEFAULT_CODE_HIGHLIGHTER, DEFAULT_THEME};
+use similar::TextDiff;
+use std::path::{Path, PathBuf};
+
+/// Draft ID shown in dry-run diffs in place of the ID WeChat would assign
+pub const PLACEHOLDER_DRAFT_ID: &str = "<draft id>";
+
+/// Unique part of a generated cover filename shown in dry-run diffs
+pub const PLACEHOLDER_COVER_ID: &str = "<generated>";
+
+/// What a run would do with a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlannedAction {
+    /// The file would be uploaded as a draft
+    Upload,
+    /// The file would be skipped
+    Skip {
+        /// Why the file would be skipped
+        reason: String,
+    },
+}
+
+/// Planned outcome for a single file
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilePlan {
+    /// Path of the markdown file
+    pub path: PathBuf,
+    /// What the run would do
+    pub action: PlannedAction,
+    /// Frontmatter as currently on disk
+    pub before: Frontmatter,
+    /// Frontmatter the run would leave behind
+    pub after: Frontmatter,
+    /// How a planned upload would be made, or `None` for a skip
+    pub upload: Option<PlannedUpload>,
+}
+
+/// Cover, style and cost of a planned upload
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedUpload {
+    /// Where the cover would come from
+    pub cover: CoverPlan,
+    /// Theme the article would be rendered with, after the manifest and the theme fallback
+    pub theme: String,
+    /// Code highlighter the article would be rendered with
+    pub code: String,
+    /// Estimated AI usage of generating the cover
+    pub cost: CostEstimate,
+}
+
+impl PlannedUpload {
+    /// Returns the lines `--dry-run --verbose` prints under the file
+    pub fn detail_lines(&self) -> Vec<String> {
+        let cover = match self.cover {
+            CoverPlan::Generate { .. } => format!("{}, about ${:.2}", self.cover, self.cost.cost),
+            cover => cover.to_string(),
+        };
+        vec![
+            format!("    cover: {}", cover),
+            format!("    style: theme {}, code highlighter {}", self.theme, self.code),
+        ]
+    }
+}
+
+impl FilePlan {
+    /// Returns a unified diff of the frontmatter block, or an empty string if unchanged
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either frontmatter cannot be serialized
+    pub fn frontmatter_diff(&self) -> Result<String> {
+        let before = format_markdown(&self.before, "")?;
+        let after = format_markdown(&self.after, "")?;
+        if before == after {
+            return Ok(String::new());
+        }
+
+        let path = self.path.display().to_string();
+        Ok(TextDiff::from_lines(&before, &after)
+            .unified_diff()
+            .header(&format!("a/{}", path), &format!("b/{}", path))
+            .to_string())
+    }
+}
+
+/// Plans the run for a single file
+///
+/// Mirrors the frontmatter changes of `wechat::upload_file`: a cover naming a
+/// directory of candidates is replaced by the chosen image, a missing cover
+/// is generated when an AI provider or a cover template is configured
+/// (recorded with a placeholder name, after the slug if there is one), or
+/// else the account's default cover is copied next to the article,
+/// `--auto-slug` derives a missing slug, a successful upload marks the file
+/// as a draft with its draft ID, and a file without frontmatter gets a title.
+/// With more than one account in `accounts`, a draft per account is recorded
+/// under `uploads` instead. Without status updates (`--no-status-update` or
+/// `--no-write-back`) the published state and draft IDs are left as they are.
+///
+/// The skip, cover and style decisions are made with the functions the
+/// upload uses. With `ai_client`, a missing cover is planned as generated and
+/// its cost estimated from the client's prices; nothing is requested.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or parsed
+pub async fn plan_file(
+    path: &Path,
+    force: bool,
+    options: &ProcessOptions,
+    ai_client: Option<&UniversalAIClient>,
+    accounts: &[String],
+) -> Result<FilePlan> {
+    let (before, body) = parse_markdown_file_with(path, &options.parse_options()).await?;
+
+    if let Some(reason) = upload_skip_reason(path, &before, force, options).await {
+        return Ok(FilePlan {
+            path: path.to_path_buf(),
+            action: PlannedAction::Skip { reason },
+            after: before.clone(),
+            before,
+            upload: None,
+        });
+    }
+
+    let mut after = before.clone();
+    if options.auto_slug && after.slug.is_none() {
+        after.slug = Some(slug_for(&after, &body, path));
+    }
+    if let Some(selected) = select_directory_cover(&after, path, options) {
+        after.set_cover(selected);
+    }
+    let cover = plan_cover(&after, path, ai_client.is_some(), options);
+    match cover {
+        CoverPlan::Template | CoverPlan::Generate { .. }
+            if after.cover.is_none() && options.write_back =>
+        {
+            after.set_cover(generated_cover_filename(
+                &after.asset_base_name(path),
+                PLACEHOLDER_COVER_ID,
+                options.cover_encoding.format,
+            ));
+        }
+        CoverPlan::Default if after.cover.is_none() && options.write_back => {
+            if let Some(default_cover) = &options.default_cover {
+                after.set_cover(default_cover_filename(&after.asset_base_name(path), default_cover));
+            }
+        }
+        CoverPlan::Existing if options.convert_covers && options.write_back => {
+            if let Some(cover) = before.cover_image()
+                && CoverFormat::from_path(Path::new(cover)) != Some(options.cover_encoding.format)
+                && resolve_cover_path_in_root(path, cover, options.content_root.as_deref()).1
+            {
+                after.set_cover(cover_name_with_format(cover, options.cover_encoding.format));
+            }
+        }
+        _ => {}
+    }
+    let cost = match (cover, ai_client) {
+        (CoverPlan::Generate { images }, Some(ai_client)) => cover_estimate(&after, images, ai_client),
+        _ => CostEstimate::default(),
+    };
+
+    // The style only applies to the upload; the file keeps its own values
+    let mut style = after.clone();
+    resolve_upload_style(&mut style, path, options);
+    let upload = PlannedUpload {
+        cover,
+        theme: style.theme.unwrap_or_else(|| DEFAULT_THEME.to_string()),
+        code: style.code.unwrap_or_else(|| DEFAULT_CODE_HIGHLIGHTER.to_string()),
+        cost,
+    };
+
+    if !options.updates_status() {
+        // Recorded in the state file instead
+    } else if accounts.len() > 1 {
+        let drafts: Vec<_> = accounts
+            .iter()
+            .map(|account| (account.as_str(), PLACEHOLDER_DRAFT_ID.to_string()))
+            .collect();
+        mark_uploaded_to_accounts(&mut after, &drafts);
+    } else {
+        mark_uploaded(&mut after, PLACEHOLDER_DRAFT_ID);
+    }
+    // Like the upload, only a block that gets written is given a title
+    if before.is_empty() && !after.is_empty() {
+        fill_new_frontmatter(&mut after, &body, path);
+    }
+
+    Ok(FilePlan {
+        path: path.to_path_buf(),
+        action: PlannedAction::Upload,
+        before,
+        after,
+        upload: Some(upload),
+    })
+}
+
+/// Turns a planned upload into a skip if its title already has a draft in `drafts`
+///
+/// Mirrors `--reconcile`: the existing draft is recorded in the frontmatter
+/// instead of a new one being created.
+pub fn reconcile_plan(plan: &mut FilePlan, drafts: &DraftIndex, options: &ProcessOptions) {
+    if plan.action != PlannedAction::Upload {
+        return;
+    }
+    let Some(draft_id) = drafts.find_for(&plan.before) else {
+        return;
+    };
+
+    plan.after = plan.before.clone();
+    if options.updates_status() {
+        mark_uploaded(&mut plan.after, draft_id);
+    }
+    plan.action = PlannedAction::Skip {
+        reason: existing_draft_reason(draft_id),
+    };
+    plan.upload = None;
+}
+
+/// What a dry run prints
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DryRunOutput {
+    /// Which files would be uploaded or skipped (`--dry-run`)
+    Plan,
+    /// The plan and the frontmatter diff of every changed file (`--dry-run --diff`)
+    PlanAndDiff,
+    /// Only the frontmatter diffs (`--diff`)
+    Diff,
+}
+
+impl DryRunOutput {
+    /// Chooses the output for the `--dry-run` and `--diff` flags, or `None` for a real run
+    pub fn from_flags(dry_run: bool, diff: bool) -> Option<Self> {
+        match (dry_run, diff) {
+            (true, true) => Some(Self::PlanAndDiff),
+            (true, false) => Some(Self::Plan),
+            (false, true) => Some(Self::Diff),
+            (false, false) => None,
+        }
+    }
+
+    fn shows_plan(self) -> bool {
+        self != Self::Diff
+    }
+
+    fn shows_diff(self) -> bool {
+        self != Self::Plan
+    }
+}
+
+/// Counts of a dry run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DryRunSummary {
+    /// Files that would be uploaded
+    pub upload: usize,
+    /// Files that would be skipped
+    pub skip: usize,
+    /// Files whose frontmatter would change
+    pub changed: usize,
+    /// Files that could not be read or parsed
+    pub errors: usize,
+}
+
+/// Prints the plan for a file or directory without touching anything
+///
+/// A single file is planned as a forced upload, like a real single-file run.
+/// `output` selects whether the plan, the frontmatter diffs or both are printed.
+/// With `drafts` (`--reconcile`), files of the current account whose title
+/// already has a draft are planned as skipped. With `--verbose`, each planned
+/// upload is followed by its cover, style and estimated cost.
+pub async fn run(
+    path: &Path,
+    config: &Config,
+    output: DryRunOutput,
+    drafts: Option<&DraftIndex>,
+) -> DryRunSummary {
+    let options = ProcessOptions::from(config);
+    // Only its prices are used; creating the client makes no requests
+    let ai_client = crate::WxUploader::create_ai_client(config);
+    let details = config.verbose && output.shows_plan();
+    let (files, force) = if path.is_file() {
+        if let Some(warning) = unrecognized_extension_warning(path, &config.markdown_extensions) {
+            FORMATTER.print_file_warning(path, None, &warning);
+        }
+        (vec![path.to_path_buf()], true)
+    } else {
+        (collect_directory_files(path, config.only, &options).await, false)
+    };
+
+    let mut summary = DryRunSummary::default();
+    let mut cost = CostEstimate::default();
+    let groups = match group_files_by_account(&files, config).await {
+        Ok(groups) => groups,
+        Err(e) => {
+            summary.errors += 1;
+            FORMATTER.print_error(&e.to_string());
+            return summary;
+        }
+    };
+
+    // Slugs of a directory run are disambiguated across all files
+    let slugs = if options.auto_slug && !force {
+        plan_slugs(&files, &options.parse_options()).await
+    } else {
+        Default::default()
+    };
+
+    for file in &files {
+        let accounts = groups
+            .iter()
+            .find(|group| group.files.contains(file))
+            .map(|group| group.accounts.as_slice())
+            .unwrap_or_default();
+        let plan = match plan_file(file, force, &options, ai_client.as_ref(), accounts).await {
+            Ok(mut plan) => {
+                if let Some(slug) = slugs.get(file) {
+                    plan.after.slug = Some(slug.clone());
+                    if plan.before.cover.is_none()
+                        && let Some(cover) = plan.after.cover.as_deref()
+                    {
+                        let renamed = match &options.default_cover {
+                            Some(default_cover) if !cover.contains(PLACEHOLDER_COVER_ID) => {
+                                default_cover_filename(slug, default_cover)
+                            }
+                            _ => generated_cover_filename(
+                                slug,
+                                PLACEHOLDER_COVER_ID,
+                                options.cover_encoding.format,
+                            ),
+                        };
+                        plan.after.set_cover(renamed);
+                    }
+                }
+                if let Some(drafts) = drafts
+                    && accounts.is_empty()
+                {
+                    reconcile_plan(&mut plan, drafts, &options);
+                }
+                plan
+            }
+            Err(e) => {
+                summary.errors += 1;
+                FORMATTER.print_error(&format!("{}: {}", file.display(), e));
+                continue;
+            }
+        };
+
+        match &plan.action {
+            PlannedAction::Upload => {
+                summary.upload += 1;
+                if output.shows_plan() {
+                    FORMATTER
+                        .print_progress(&FORMATTER.format_file_operation("would upload", file));
+                }
+                if let Some(upload) = &plan.upload {
+                    cost.merge(&upload.cost);
+                    if details {
+                        upload.detail_lines().iter().for_each(|line| FORMATTER.print_detail(line));
+                    }
+                }
+            }
+            PlannedAction::Skip { reason } => {
+                summary.skip += 1;
+                if output.shows_plan() {
+                    FORMATTER.print_skip(&format!("would skip ({}): {}", reason, file.display()));
+                }
+            }
+        }
+
+        match plan.frontmatter_diff() {
+            Ok(diff) if diff.is_empty() => {}
+            Ok(diff) => {
+                summary.changed += 1;
+                if output.shows_diff() {
+                    print!("{}", FORMATTER.format_diff(&diff));
+                }
+            }
+            Err(e) => {
+                summary.errors += 1;
+                FORMATTER.print_error(&format!("{}: {}", file.display(), e));
+            }
+        }
+    }
+
+    if details && cost.files > 0 {
+        FORMATTER.print_info(&cost.summary());
+    }
+    if output.shows_plan() {
+        FORMATTER.print_info(&format!(
+            "dry run: {} would be uploaded, {} skipped, {} unreadable; no files were changed",
+            summary.upload, summary.skip, summary.errors
+        ));
+    } else {
+        FORMATTER.print_info(&format!(
+            "diff: frontmatter of {} file(s) would change, {} unreadable; no files were changed",
+            summary.changed, summary.errors
+        ));
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{PublicationStatus, ThemeFallback};
+    use crate::providers::ProviderConfig;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn ai_client() -> UniversalAIClient {
+        UniversalAIClient::new(
+            ProviderConfig::OpenAI {
+                api_key: "test-key".to_string(),
+                base_url: None,
+            },
+            None,
+        )
+    }
+
+    const ARTICLE: &str = "---\ntitle: Rust Async\ndescription: Futures explained\ntheme: lapis\n---\n# Body\n";
+
+    #[tokio::test]
+    async fn test_frontmatter_diff_snapshot() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("post.md");
+        fs::write(&path, ARTICLE).unwrap();
+
+        let mut plan = plan_file(&path, false, &ProcessOptions::default(), Some(&ai_client()), &[])
+            .await
+            .unwrap();
+        plan.path = PathBuf::from("posts/post.md");
+
+        assert_eq!(plan.action, PlannedAction::Upload);
+        assert_eq!(
+            plan.frontmatter_diff().unwrap(),
+            "\
+--- a/posts/post.md
++++ b/posts/post.md
+@@ -1,5 +1,8 @@
+ ---
+ title: Rust Async
++published: draft
++draft_id: <draft id>
++cover: post_cover_<generated>.png
+ theme: lapis
+ description: Futures explained
+ ---
+"
+        );
+
+        // Planning must not modify the file
+        assert_eq!(fs::read_to_string(&path).unwrap(), ARTICLE);
+    }
+
+    #[test]
+    fn test_dry_run_output_from_flags() {
+        assert_eq!(DryRunOutput::from_flags(false, false), None);
+        assert_eq!(DryRunOutput::from_flags(true, false), Some(DryRunOutput::Plan));
+        assert_eq!(DryRunOutput::from_flags(true, true), Some(DryRunOutput::PlanAndDiff));
+        assert_eq!(DryRunOutput::from_flags(false, true), Some(DryRunOutput::Diff));
+        assert!(!DryRunOutput::Diff.shows_plan());
+        assert!(!DryRunOutput::Plan.shows_diff());
+    }
+
+    #[tokio::test]
+    async fn test_plan_skips_published_without_diff() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("done.md");
+        fs::write(&path, "---\ntitle: Done\npublished: \"true\"\n---\nbody").unwrap();
+
+        let plan = plan_file(&path, false, &ProcessOptions::default(), Some(&ai_client()), &[])
+            .await
+            .unwrap();
+        assert!(matches!(plan.action, PlannedAction::Skip { .. }));
+        assert_eq!(plan.frontmatter_diff().unwrap(), "");
+
+        let forced = plan_file(&path, true, &ProcessOptions::default(), None, &[])
+            .await
+            .unwrap();
+        assert_eq!(forced.action, PlannedAction::Upload);
+        assert_eq!(forced.after.publication_status(), PublicationStatus::Draft);
+        assert_eq!(forced.after.cover, None);
+    }
+
+    #[tokio::test]
+    async fn test_plan_names_cover_after_slug() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("draft-2024.md");
+        fs::write(&path, "---\ntitle: 异步 Rust\n---\nbody").unwrap();
+
+        let options = ProcessOptions {
+            auto_slug: true,
+            ..Default::default()
+        };
+        let plan = plan_file(&path, false, &options, Some(&ai_client()), &[]).await.unwrap();
+
+        assert_eq!(plan.after.slug.as_deref(), Some("yi-bu-rust"));
+        assert_eq!(
+            plan.after.cover.as_deref(),
+            Some("yi-bu-rust_cover_<generated>.png")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_plan_records_draft_per_account() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("regional.md");
+        fs::write(&path, "---\ntitle: Regional\n---\nbody").unwrap();
+
+        let accounts = vec!["north".to_string(), "south".to_string()];
+        let plan = plan_file(&path, false, &ProcessOptions::default(), None, &accounts)
+            .await
+            .unwrap();
+
+        assert_eq!(plan.after.publication_status(), PublicationStatus::Draft);
+        assert_eq!(plan.after.draft_id, None);
+        let uploads = plan.after.uploads.unwrap();
+        assert_eq!(uploads.keys().collect::<Vec<_>>(), vec!["north", "south"]);
+        assert_eq!(uploads["north"], PLACEHOLDER_DRAFT_ID);
+    }
+
+    #[tokio::test]
+    async fn test_plan_without_status_update_keeps_published() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("managed.md");
+        fs::write(&path, "---\ntitle: Managed\npublished: review\n---\nbody").unwrap();
+
+        let options = ProcessOptions {
+            set_draft_status: false,
+            ..ProcessOptions::default()
+        };
+        let plan = plan_file(&path, false, &options, None, &[]).await.unwrap();
+
+        assert_eq!(plan.action, PlannedAction::Upload);
+        assert_eq!(plan.after, plan.before);
+        assert_eq!(plan.after.published.as_ref().and_then(|p| p.raw()), Some("review"));
+
+        // Without write-back the status is not updated either
+        let options = ProcessOptions {
+            write_back: false,
+            ..ProcessOptions::default()
+        };
+        let plan = plan_file(&path, false, &options, None, &[]).await.unwrap();
+        assert_eq!(plan.after, plan.before);
+    }
+
+    #[tokio::test]
+    async fn test_plan_without_write_back_has_empty_diff() {
+        let temp_dir = TempDir::new().unwrap();
+        let plain = temp_dir.path().join("plain.md");
+        let covered = temp_dir.path().join("covered.md");
+        fs::write(&plain, "# Plain\n\nNo frontmatter.\n").unwrap();
+        fs::write(&covered, "---\ntitle: Covered\n---\nbody").unwrap();
+
+        let options = ProcessOptions {
+            write_back: false,
+            ..ProcessOptions::default()
+        };
+        for path in [&plain, &covered] {
+            let plan = plan_file(path, false, &options, Some(&ai_client()), &[]).await.unwrap();
+            assert_eq!(plan.action, PlannedAction::Upload);
+            // The cover is still generated, into a temporary directory
+            assert_eq!(plan.upload.as_ref().unwrap().cover, CoverPlan::Generate { images: 1 });
+            assert_eq!(plan.frontmatter_diff().unwrap(), "", "{}", path.display());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_plan_records_existing_draft() {
+        use crate::wechat::DraftSummary;
+
+        let temp_dir = TempDir::new().unwrap();
+        let known = temp_dir.path().join("known.md");
+        let new = temp_dir.path().join("new.md");
+        fs::write(&known, "---\ntitle: Known\n---\nbody").unwrap();
+        fs::write(&new, "---\ntitle: New\n---\nbody").unwrap();
+        let drafts = DraftIndex::new(&[DraftSummary {
+            media_id: "media_1".to_string(),
+            title: "Known".to_string(),
+            update_time: 0,
+        }]);
+        let options = ProcessOptions::default();
+
+        let mut plan = plan_file(&known, false, &options, None, &[]).await.unwrap();
+        reconcile_plan(&mut plan, &drafts, &options);
+        assert_eq!(
+            plan.action,
+            PlannedAction::Skip {
+                reason: "draft media_1 already exists on WeChat".to_string()
+            }
+        );
+        assert_eq!(plan.after.draft_id.as_deref(), Some("media_1"));
+        assert_eq!(plan.after.publication_status(), PublicationStatus::Draft);
+
+        let mut plan = plan_file(&new, false, &options, None, &[]).await.unwrap();
+        reconcile_plan(&mut plan, &drafts, &options);
+        assert_eq!(plan.action, PlannedAction::Upload);
+    }
+
+    #[tokio::test]
+    async fn test_plan_details_cover_style_and_cost() {
+        let temp_dir = TempDir::new().unwrap();
+        let new = temp_dir.path().join("new.md");
+        let covered = temp_dir.path().join("covered.md");
+        let bare = temp_dir.path().join("bare.md");
+        fs::write(&new, "---\ntitle: New\ntheme: nope\n---\nbody").unwrap();
+        fs::write(temp_dir.path().join("covered.png"), b"cover").unwrap();
+        fs::write(&covered, "---\ntitle: Covered\ncover: covered.png\ncode: github\n---\nbody").unwrap();
+        fs::write(&bare, "---\ntitle: Bare\ncover: false\n---\nbody").unwrap();
+        let options = ProcessOptions {
+            theme_fallback: ThemeFallback::Default,
+            default_theme: Some("lapis".to_string()),
+            ..Default::default()
+        };
+        let client = ai_client();
+
+        let plan = plan_file(&new, false, &options, Some(&client), &[]).await.unwrap();
+        let upload = plan.upload.unwrap();
+        assert_eq!(upload.cover, CoverPlan::Generate { images: 1 });
+        assert_eq!((upload.theme.as_str(), upload.code.as_str()), ("lapis", DEFAULT_CODE_HIGHLIGHTER));
+        assert_eq!(upload.cost.images, 1);
+        assert!(upload.cost.cost > 0.0);
+        assert_eq!(
+            upload.detail_lines()[1],
+            format!("    style: theme lapis, code highlighter {}", DEFAULT_CODE_HIGHLIGHTER)
+        );
+        // The fallback only applies to the upload
+        assert_eq!(plan.after.theme.as_deref(), Some("nope"));
+
+        let plan = plan_file(&covered, false, &options, Some(&client), &[]).await.unwrap();
+        let upload = plan.upload.unwrap();
+        assert_eq!(upload.cover, CoverPlan::Existing);
+        assert_eq!((upload.theme.as_str(), upload.code.as_str()), (DEFAULT_THEME, "github"));
+        assert_eq!(upload.cost, CostEstimate::default());
+        assert_eq!(upload.detail_lines()[0], "    cover: reuse existing");
+
+        // A disabled cover is not generated, as in the upload
+        let plan = plan_file(&bare, false, &options, Some(&client), &[]).await.unwrap();
+        assert_eq!(plan.upload.unwrap().cover, CoverPlan::Disabled);
+        assert_eq!(plan.after.cover, plan.before.cover);
+
+        let plan = plan_file(&new, false, &options, None, &[]).await.unwrap();
+        assert_eq!(plan.upload.unwrap().cover, CoverPlan::Absent);
+
+        // A shared material is neither generated nor looked for on disk
+        let series = temp_dir.path().join("series.md");
+        fs::write(&series, "---
+title: Part 2
+cover_media_id: SERIES_COVER
+---
+body").unwrap();
+        let plan = plan_file(&series, false, &options, Some(&client), &[]).await.unwrap();
+        let upload = plan.upload.unwrap();
+        assert_eq!(upload.cover, CoverPlan::Material);
+        assert_eq!(upload.cost, CostEstimate::default());
+        assert_eq!(upload.detail_lines()[0], "    cover: reuse WeChat material");
+    }
+}
@@ -4,11 +4,15 @@
 //! library errors and integrates with `anyhow` for application-level error handling.
 
 use std::path::PathBuf;
+use std::time::Duration;
 use thiserror::Error;
 
 /// Result type alias for the wx-uploader library
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Underlying error kept as the source of a wrapping error
+pub type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
 /// Main error type for the wx-uploader library
 #[derive(Error, Debug)]
 pub enum Error {
@@ -18,7 +22,31 @@ pub enum Error {
 
     /// WeChat API error
     #[error("WeChat API error: {message}")]
-    WeChat { message: String },
+    WeChat {
+        message: String,
+        source: Option<BoxError>,
+    },
+
+    /// WeChat authentication failed (invalid credentials, token or IP whitelist)
+    #[error("WeChat authentication failed: {message}")]
+    Auth {
+        message: String,
+        source: Option<BoxError>,
+    },
+
+    /// WeChat answered HTTP 429 Too Many Requests, e.g. through an API gateway
+    #[error("WeChat API request failed with status 429 Too Many Requests")]
+    WeChatTooManyRequests {
+        /// Wait asked for by the `Retry-After` header
+        retry_after: Option<Duration>,
+    },
+
+    /// WeChat rejected the article text in its content security check (errcode 87014)
+    #[error("WeChat content security check failed: {message}")]
+    ContentRisk {
+        message: String,
+        source: Option<BoxError>,
+    },
 
     /// HTTP request failed
     #[error("HTTP request failed: {0}")]
@@ -50,7 +78,25 @@ pub enum Error {
 
     /// OpenAI API error
     #[error("OpenAI API error: {message}")]
-    OpenAI { message: String },
+    OpenAI {
+        message: String,
+        source: Option<BoxError>,
+    },
+
+    /// AI provider answered with an HTTP error status
+    #[error("{provider} API request failed with status {status}: {message} ({failure})")]
+    AiRequest {
+        provider: String,
+        status: u16,
+        failure: AiFailure,
+        message: String,
+        /// Wait asked for by the `Retry-After` header
+        retry_after: Option<Duration>,
+    },
+
+    /// A user hook command failed
+    #[error("{hook} hook failed: {message}")]
+    Hook { hook: String, message: String },
 
     /// Cover image error
     #[error("Cover image error for {path}: {reason}")]
@@ -66,7 +112,10 @@ pub enum Error {
 
     /// Generic error with context
     #[error("Operation failed: {message}")]
-    Generic { message: String },
+    Generic {
+        message: String,
+        source: Option<BoxError>,
+    },
 }
 
 impl Error {
@@ -83,6 +132,15 @@ impl Error {
         }
     }
 
+    /// Wraps an I/O error on `path`, naming the path if the file does not exist
+    pub fn io_at(path: impl Into<PathBuf>, error: std::io::Error) -> Self {
+        if error.kind() == std::io::ErrorKind::NotFound {
+            Self::file_not_found(path)
+        } else {
+            Self::Io(error)
+        }
+    }
+
     /// Creates a new missing environment variable error
     pub fn missing_env_var(var: impl Into<String>) -> Self {
         Self::MissingEnvVar { var: var.into() }
@@ -92,6 +150,56 @@ impl Error {
     pub fn openai(message: impl Into<String>) -> Self {
         Self::OpenAI {
             message: message.into(),
+            source: None,
+        }
+    }
+
+    /// Creates a new OpenAI API error caused by `source`
+    pub fn openai_with_source(message: impl Into<String>, source: impl Into<BoxError>) -> Self {
+        Self::OpenAI {
+            message: message.into(),
+            source: Some(source.into()),
+        }
+    }
+
+    /// Creates an AI provider request error, classifying the failure from
+    /// the HTTP status and response body
+    pub fn ai_request(provider: impl Into<String>, status: u16, body: impl Into<String>) -> Self {
+        let message = body.into();
+        Self::AiRequest {
+            provider: provider.into(),
+            status,
+            failure: AiFailure::classify(status, &message),
+            message,
+            retry_after: None,
+        }
+    }
+
+    /// Sets the wait a rate-limited response asked for with `Retry-After`
+    pub fn with_retry_after(mut self, wait: Option<Duration>) -> Self {
+        if let Self::AiRequest { retry_after, .. } | Self::WeChatTooManyRequests { retry_after } =
+            &mut self
+        {
+            *retry_after = wait;
+        }
+        self
+    }
+
+    /// Returns the wait a rate-limited response asked for with `Retry-After`
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::AiRequest { retry_after, .. } | Self::WeChatTooManyRequests { retry_after } => {
+                *retry_after
+            }
+            _ => None,
+        }
+    }
+
+    /// Creates a new hook error for the hook named `hook`
+    pub fn hook(hook: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::Hook {
+            hook: hook.into(),
+            message: message.into(),
         }
     }
 
@@ -122,6 +230,15 @@ impl Error {
     pub fn generic(message: impl Into<String>) -> Self {
         Self::Generic {
             message: message.into(),
+            source: None,
+        }
+    }
+
+    /// Creates a generic error caused by `source`
+    pub fn generic_with_source(message: impl Into<String>, source: impl Into<BoxError>) -> Self {
+        Self::Generic {
+            message: message.into(),
+            source: Some(source.into()),
         }
     }
 
@@ -129,8 +246,285 @@ impl Error {
     pub fn wechat(message: impl Into<String>) -> Self {
         Self::WeChat {
             message: message.into(),
+            source: None,
+        }
+    }
+
+    /// Creates a new WeChat API error caused by `source`
+    pub fn wechat_with_source(message: impl Into<String>, source: impl Into<BoxError>) -> Self {
+        Self::WeChat {
+            message: message.into(),
+            source: Some(source.into()),
+        }
+    }
+
+    /// Creates a new WeChat authentication error
+    pub fn auth(message: impl Into<String>) -> Self {
+        Self::Auth {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    /// Creates a new WeChat authentication error caused by `source`
+    pub fn auth_with_source(message: impl Into<String>, source: impl Into<BoxError>) -> Self {
+        Self::Auth {
+            message: message.into(),
+            source: Some(source.into()),
+        }
+    }
+
+    /// Creates a new content security rejection error
+    pub fn content_risk(message: impl Into<String>) -> Self {
+        Self::ContentRisk {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    /// Converts a `wechat_pub_rs` error, classifying authentication failures
+    /// and content security rejections
+    ///
+    /// The original error is kept as the source.
+    pub fn from_wechat(error: wechat_pub_rs::WeChatError) -> Self {
+        use wechat_pub_rs::WeChatError;
+
+        let is_auth = match &error {
+            WeChatError::InvalidCredentials | WeChatError::InvalidToken => true,
+            WeChatError::WeChatApi { code, .. } => is_auth_errcode(*code),
+            _ => false,
+        };
+        let is_content_risk = matches!(
+            &error,
+            WeChatError::WeChatApi { code, .. } if *code == crate::content_check::CONTENT_RISK_ERRCODE
+        );
+
+        let message = error.to_string();
+        let source: Option<BoxError> = Some(Box::new(error));
+        if is_auth {
+            Self::Auth { message, source }
+        } else if is_content_risk {
+            Self::ContentRisk { message, source }
+        } else {
+            Self::WeChat { message, source }
+        }
+    }
+
+    /// Formats the error followed by its causes, e.g. `OpenAI API error: Failed
+    /// to decode base64 image: Invalid byte 42, offset 0.`
+    ///
+    /// Causes whose text already appears in the message are left out.
+    pub fn display_chain(&self) -> String {
+        let mut text = self.to_string();
+        let mut cause = std::error::Error::source(self);
+        while let Some(error) = cause {
+            let cause_text = error.to_string();
+            if !text.contains(&cause_text) {
+                text.push_str(": ");
+                text.push_str(&cause_text);
+            }
+            cause = error.source();
         }
+        text
+    }
+
+    /// Returns true if this error is a WeChat authentication failure
+    pub fn is_auth(&self) -> bool {
+        matches!(self, Self::Auth { .. })
     }
+
+    /// Returns true if WeChat rejected the content as risky
+    pub fn is_content_risk(&self) -> bool {
+        matches!(self, Self::ContentRisk { .. })
+    }
+
+    /// Returns true if WeChat refused an API the account is not authorized for
+    /// (errcode 48001), as it does permanent material uploads of subscription
+    /// and unverified accounts
+    pub fn is_api_unauthorized(&self) -> bool {
+        self.wechat_errcode() == Some(API_UNAUTHORIZED_ERRCODE)
+    }
+
+    /// Returns the WeChat API `errcode` this error or one of its causes carries
+    pub fn wechat_errcode(&self) -> Option<i32> {
+        let mut cause: Option<&(dyn std::error::Error + 'static)> = Some(self);
+        while let Some(error) = cause {
+            if let Some(wechat_pub_rs::WeChatError::WeChatApi { code, .. }) = error.downcast_ref()
+            {
+                return Some(*code);
+            }
+            cause = error.source();
+        }
+        None
+    }
+
+    /// Returns true if WeChat rejected the request for exceeding its call
+    /// frequency limit, with errcode 45009 or HTTP 429
+    pub fn is_rate_limited(&self) -> bool {
+        let mut cause: Option<&(dyn std::error::Error + 'static)> = Some(self);
+        while let Some(error) = cause {
+            if let Some(Self::WeChatTooManyRequests { .. }) = error.downcast_ref() {
+                return true;
+            }
+            cause = error.source();
+        }
+        self.wechat_errcode() == Some(RATE_LIMIT_ERRCODE)
+    }
+
+    /// Classifies an AI provider failure, or returns `None` for errors that
+    /// did not come from talking to a provider
+    ///
+    /// Timeouts and connection failures are transient like 429 and 5xx answers.
+    pub fn ai_failure(&self) -> Option<AiFailure> {
+        match self {
+            Self::AiRequest { failure, .. } => Some(*failure),
+            Self::Http(e) if e.is_timeout() => Some(AiFailure::Timeout),
+            Self::Http(e) if e.is_connect() => Some(AiFailure::Connection),
+            Self::Http(e) => e.status().map(|status| AiFailure::classify(status.as_u16(), "")),
+            _ => None,
+        }
+    }
+
+    /// Returns true if repeating the failed AI request cannot succeed
+    pub fn is_permanent(&self) -> bool {
+        self.ai_failure().is_some_and(AiFailure::is_permanent)
+    }
+
+    /// Returns true if the failed AI request may succeed when retried
+    pub fn is_transient(&self) -> bool {
+        self.ai_failure().is_some_and(AiFailure::is_transient)
+    }
+
+    /// Returns true if this error stems from configuration or usage problems
+    pub fn is_config(&self) -> bool {
+        matches!(self, Self::Config { .. } | Self::MissingEnvVar { .. })
+    }
+}
+
+/// Why an AI provider request failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AiFailure {
+    /// 401: the API key is missing, invalid or revoked
+    InvalidApiKey,
+    /// 403: the key may not use this model or endpoint
+    PermissionDenied,
+    /// 404: the model name or base URL is wrong
+    NotFound,
+    /// The provider refused the prompt under its content policy
+    ContentPolicy,
+    /// 429: rate limit or quota exceeded
+    RateLimited,
+    /// 5xx: the provider had a server-side problem
+    ServerError,
+    /// The request timed out (including 408)
+    Timeout,
+    /// The provider could not be reached
+    Connection,
+    /// Any other error status
+    Other,
+}
+
+impl AiFailure {
+    /// Classifies a failed response from its HTTP status and body
+    pub fn classify(status: u16, body: &str) -> Self {
+        match status {
+            401 => Self::InvalidApiKey,
+            403 => Self::PermissionDenied,
+            404 => Self::NotFound,
+            408 => Self::Timeout,
+            429 => Self::RateLimited,
+            500..=599 => Self::ServerError,
+            400..=499 if is_content_policy_body(body) => Self::ContentPolicy,
+            _ => Self::Other,
+        }
+    }
+
+    /// Returns true if retrying the same request cannot succeed
+    pub fn is_permanent(self) -> bool {
+        matches!(
+            self,
+            Self::InvalidApiKey | Self::PermissionDenied | Self::NotFound | Self::ContentPolicy
+        )
+    }
+
+    /// Returns true if the same request may succeed when retried
+    pub fn is_transient(self) -> bool {
+        matches!(
+            self,
+            Self::RateLimited | Self::ServerError | Self::Timeout | Self::Connection
+        )
+    }
+
+    /// Returns the suspected cause of the failure
+    pub fn suspected_cause(self) -> &'static str {
+        match self {
+            Self::InvalidApiKey => "the API key is missing, invalid or revoked",
+            Self::PermissionDenied => "the API key has no access to this model or endpoint",
+            Self::NotFound => "the model name or base URL is wrong",
+            Self::ContentPolicy => "the prompt was refused under the content policy",
+            Self::RateLimited => "rate limit or quota exceeded",
+            Self::ServerError => "provider server error",
+            Self::Timeout => "request timed out",
+            Self::Connection => "provider unreachable",
+            Self::Other => "unexpected error status",
+        }
+    }
+}
+
+impl std::fmt::Display for AiFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_permanent() {
+            write!(f, "permanent, suspected cause: {}", self.suspected_cause())
+        } else if self.is_transient() {
+            write!(f, "transient: {}", self.suspected_cause())
+        } else {
+            f.write_str(self.suspected_cause())
+        }
+    }
+}
+
+/// Markers providers use when refusing a prompt under their content policy
+const CONTENT_POLICY_MARKERS: &[&str] = &[
+    "content_policy_violation", // OpenAI
+    "content policy",
+    "safety system",
+    "blocked due to safety", // Gemini
+    "safety settings",
+];
+
+fn is_content_policy_body(body: &str) -> bool {
+    let body = body.to_lowercase();
+    CONTENT_POLICY_MARKERS.iter().any(|marker| body.contains(marker))
+}
+
+/// WeChat API error code for calls exceeding the frequency limit of an endpoint
+pub const RATE_LIMIT_ERRCODE: i32 = 45009;
+
+/// WeChat API error code for APIs the account is not authorized for, e.g.
+/// permanent material uploads of subscription or unverified accounts
+pub const API_UNAUTHORIZED_ERRCODE: i32 = 48001;
+
+/// WeChat API error code for a media ID that names no material of the
+/// account, e.g. a `cover_media_id` that was deleted or belongs to another account
+pub const INVALID_MEDIA_ID_ERRCODE: i32 = 40007;
+
+/// WeChat API error codes that indicate invalid credentials or access tokens
+const AUTH_ERRCODES: &[i32] = &[
+    40001, // invalid credential / access_token
+    40002, // invalid grant_type
+    40013, // invalid appid
+    40014, // invalid access_token
+    40125, // invalid appsecret
+    40164, // caller IP not in whitelist
+    41001, // access_token missing
+    41002, // appid missing
+    41004, // appsecret missing
+    42001, // access_token expired
+];
+
+/// Returns true if a WeChat API error code is an authentication failure
+pub fn is_auth_errcode(code: i32) -> bool {
+    AUTH_ERRCODES.contains(&code)
 }
 
 /// Conversion from anyhow::Error for compatibility
@@ -138,10 +532,37 @@ impl From<anyhow::Error> for Error {
     fn from(err: anyhow::Error) -> Self {
         Self::Generic {
             message: err.to_string(),
+            source: Some(err.into()),
         }
     }
 }
 
+/// Adds context to a failed result, keeping the original error as the source
+pub trait ResultExt<T> {
+    /// Wraps the error in a WeChat API error with `message`
+    fn wechat_context(self, message: impl Into<String>) -> Result<T>;
+
+    /// Wraps the error in an AI provider error with `message`
+    fn openai_context(self, message: impl Into<String>) -> Result<T>;
+
+    /// Wraps the error in a generic error with `message`
+    fn context(self, message: impl Into<String>) -> Result<T>;
+}
+
+impl<T, E: Into<BoxError>> ResultExt<T> for std::result::Result<T, E> {
+    fn wechat_context(self, message: impl Into<String>) -> Result<T> {
+        self.map_err(|e| Error::wechat_with_source(message, e))
+    }
+
+    fn openai_context(self, message: impl Into<String>) -> Result<T> {
+        self.map_err(|e| Error::openai_with_source(message, e))
+    }
+
+    fn context(self, message: impl Into<String>) -> Result<T> {
+        self.map_err(|e| Error::generic_with_source(message, e))
+    }
+}
+
 // Note: We don't implement From<wechat_pub_rs::Error> because the exact error type
 // varies between versions. Instead, we handle WeChat errors manually in the wechat module.
 
@@ -158,6 +579,11 @@ mod tests {
         assert!(matches!(file_not_found, Error::FileNotFound { .. }));
         assert!(file_not_found.to_string().contains("test.md"));
 
+        let missing = Error::io_at(path, std::io::ErrorKind::NotFound.into());
+        assert_eq!(missing.to_string(), "File not found: test.md");
+        let denied = Error::io_at(path, std::io::ErrorKind::PermissionDenied.into());
+        assert!(matches!(denied, Error::Io(_)));
+
         let invalid_format = Error::invalid_format(path, "malformed YAML");
         assert!(matches!(invalid_format, Error::InvalidFormat { .. }));
         assert!(invalid_format.to_string().contains("malformed YAML"));
@@ -187,6 +613,92 @@ mod tests {
         assert!(generic_error.to_string().contains("something went wrong"));
     }
 
+    #[test]
+    fn test_wechat_error_classification() {
+        use wechat_pub_rs::WeChatError;
+
+        let auth = Error::from_wechat(WeChatError::from_api_response(40125, "invalid appsecret"));
+        assert!(auth.is_auth());
+        assert!(auth.to_string().contains("40125"));
+
+        assert!(Error::from_wechat(WeChatError::InvalidCredentials).is_auth());
+
+        let other = Error::from_wechat(WeChatError::from_api_response(45009, "rate limited"));
+        assert!(matches!(other, Error::WeChat { .. }));
+        assert!(!other.is_auth());
+        assert!(!other.is_content_risk());
+        assert!(other.is_rate_limited());
+        assert_eq!(other.wechat_errcode(), Some(45009));
+
+        // The code is found behind added context too
+        let wrapped = Error::wechat_with_source("WeChat upload failed", other);
+        assert!(wrapped.is_rate_limited());
+        assert!(!auth.is_rate_limited());
+        assert_eq!(Error::wechat("quota").wechat_errcode(), None);
+
+        // A gateway answering 429 is rate limiting too, and may say how long to wait
+        let wait = Some(std::time::Duration::from_secs(7));
+        let too_many = Error::WeChatTooManyRequests { retry_after: None }.with_retry_after(wait);
+        assert_eq!(too_many.retry_after(), wait);
+        assert!(Error::wechat_with_source("WeChat upload failed", too_many).is_rate_limited());
+        assert_eq!(Error::ai_request("OpenAI", 429, "slow down").with_retry_after(wait).retry_after(), wait);
+        assert_eq!(Error::wechat("quota").with_retry_after(wait).retry_after(), None);
+
+        let unauthorized = Error::wechat_with_source(
+            "WeChat upload failed",
+            Error::from_wechat(WeChatError::from_api_response(48001, "api unauthorized")),
+        );
+        assert!(unauthorized.is_api_unauthorized());
+        assert!(!unauthorized.is_auth());
+        assert!(!auth.is_api_unauthorized());
+
+        let risky = Error::from_wechat(WeChatError::from_api_response(87014, "risky content"));
+        assert!(risky.is_content_risk());
+        assert!(!risky.is_auth());
+
+        assert!(Error::config("bad").is_config());
+        assert!(Error::missing_env_var("WECHAT_APP_ID").is_config());
+        assert!(!Error::wechat("x").is_config());
+    }
+
+    #[test]
+    fn test_ai_failure_classification() {
+        let unauthorized = Error::ai_request("OpenAI", 401, "Incorrect API key provided");
+        assert_eq!(unauthorized.ai_failure(), Some(AiFailure::InvalidApiKey));
+        assert!(unauthorized.is_permanent());
+        assert!(!unauthorized.is_transient());
+        let message = unauthorized.to_string();
+        assert!(message.starts_with("OpenAI API request failed with status 401"));
+        assert!(message.contains("suspected cause: the API key is missing"));
+
+        assert!(Error::ai_request("Gemini", 403, "").is_permanent());
+        let not_found = Error::ai_request("Gemini", 404, "models/imagen-x is not found");
+        assert_eq!(not_found.ai_failure(), Some(AiFailure::NotFound));
+        assert!(not_found.to_string().contains("Gemini"));
+        assert!(not_found.to_string().contains("model name or base URL"));
+
+        let policy = Error::ai_request(
+            "OpenAI",
+            400,
+            r#"{"error":{"code":"content_policy_violation","message":"rejected"}}"#,
+        );
+        assert_eq!(policy.ai_failure(), Some(AiFailure::ContentPolicy));
+        assert!(policy.is_permanent());
+
+        for status in [408, 429, 500, 503] {
+            let error = Error::ai_request("OpenAI", status, "try again");
+            assert!(error.is_transient(), "{status}");
+            assert!(!error.is_permanent(), "{status}");
+        }
+
+        let other = Error::ai_request("OpenAI", 400, "invalid size");
+        assert_eq!(other.ai_failure(), Some(AiFailure::Other));
+        assert!(!other.is_permanent() && !other.is_transient());
+
+        assert_eq!(Error::openai("bad response").ai_failure(), None);
+        assert!(!Error::config("x").is_permanent());
+    }
+
     #[test]
     fn test_anyhow_conversion() {
         let anyhow_error = anyhow::anyhow!("test error message");
@@ -196,6 +708,62 @@ mod tests {
         assert!(our_error.to_string().contains("test error message"));
     }
 
+    #[test]
+    fn test_source_is_kept() {
+        use std::error::Error as _;
+        use wechat_pub_rs::WeChatError;
+
+        let wechat = Error::from_wechat(WeChatError::from_api_response(45009, "rate limited"));
+        let source = wechat.source().expect("source");
+        assert!(source.downcast_ref::<WeChatError>().is_some());
+
+        let auth = Error::from_wechat(WeChatError::InvalidCredentials);
+        assert!(auth.is_auth());
+        assert!(auth.source().unwrap().downcast_ref::<WeChatError>().is_some());
+
+        let io = std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out");
+        let wrapped = Error::wechat_with_source("upload failed", io);
+        let source = wrapped.source().expect("source");
+        assert_eq!(
+            source.downcast_ref::<std::io::Error>().unwrap().kind(),
+            std::io::ErrorKind::TimedOut
+        );
+
+        let anyhow_error: Error = anyhow::anyhow!("root cause").into();
+        assert!(anyhow_error.source().is_some());
+
+        assert!(Error::wechat("no cause").source().is_none());
+    }
+
+    #[test]
+    fn test_context_helpers_and_display_chain() {
+        use base64::Engine;
+        use std::error::Error as _;
+
+        let decode = base64::engine::general_purpose::STANDARD.decode("*");
+        let error = decode.openai_context("Failed to decode base64 image").unwrap_err();
+        assert!(matches!(error, Error::OpenAI { .. }));
+        assert!(error.source().unwrap().downcast_ref::<base64::DecodeError>().is_some());
+        assert!(
+            error
+                .display_chain()
+                .starts_with("OpenAI API error: Failed to decode base64 image: Invalid")
+        );
+
+        // Nested errors keep their whole chain; repeated text is printed once
+        let inner = Error::from_wechat(wechat_pub_rs::WeChatError::from_api_response(45009, "rate limited"));
+        let outer: Result<()> = Err(inner).wechat_context("WeChat upload failed");
+        let chain = outer.unwrap_err().display_chain();
+        assert!(chain.starts_with("WeChat API error: WeChat upload failed: WeChat API error: "));
+        assert_eq!(chain.matches("rate limited").count(), 1);
+
+        let generic: Result<()> = Err(std::io::Error::other("disk full")).context("saving cover");
+        assert_eq!(
+            generic.unwrap_err().display_chain(),
+            "Operation failed: saving cover: disk full"
+        );
+    }
+
     #[test]
     fn test_error_chain() {
         use std::io;
@@ -0,0 +1,161 @@
+//! Process exit codes
+//!
+//! Central mapping from run results and errors to the documented exit codes,
+//! so scripts and CI can distinguish failure modes:
+//!
+//! | Code | Meaning                                                   |
+//! |------|-----------------------------------------------------------|
+//! | 0    | Success, including "nothing to upload"                    |
+//! | 2    | Configuration or usage error                              |
+//! | 3    | Partial failure: some files failed, others succeeded      |
+//! | 4    | WeChat authentication failure                             |
+//! | 5    | All files failed                                          |
+//! | 6    | Warnings were reported under `--warnings-as-errors`       |
+
+use crate::error::Error;
+use crate::report::UploadReport;
+
+/// Exit code of the `wx-uploader` binary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    /// Everything succeeded (or there was nothing to do)
+    Success,
+    /// Invalid configuration or command-line usage
+    ConfigError,
+    /// Some files failed while others succeeded or were skipped
+    PartialFailure,
+    /// WeChat rejected the credentials or access token
+    AuthFailure,
+    /// Every processed file failed
+    AllFailed,
+    /// Nothing failed, but warnings were reported and are treated as errors
+    Warnings,
+}
+
+impl ExitCode {
+    /// Numeric process exit code
+    pub fn code(self) -> u8 {
+        match self {
+            ExitCode::Success => 0,
+            ExitCode::ConfigError => 2,
+            ExitCode::PartialFailure => 3,
+            ExitCode::AuthFailure => 4,
+            ExitCode::AllFailed => 5,
+            ExitCode::Warnings => 6,
+        }
+    }
+
+    /// Chooses the exit code for a completed upload run
+    pub fn from_report(report: &UploadReport) -> Self {
+        let failed = report.failed();
+        if failed == 0 {
+            ExitCode::Success
+        } else if report.has_auth_failure() {
+            ExitCode::AuthFailure
+        } else if failed == report.files.len() {
+            ExitCode::AllFailed
+        } else {
+            ExitCode::PartialFailure
+        }
+    }
+
+    /// Chooses the exit code for a completed upload run, failing a run without
+    /// failures if it reported warnings and `warnings_as_errors` is set
+    pub fn from_report_with(report: &UploadReport, warnings_as_errors: bool) -> Self {
+        match Self::from_report(report) {
+            ExitCode::Success if warnings_as_errors && !report.warnings.is_empty() => {
+                ExitCode::Warnings
+            }
+            code => code,
+        }
+    }
+
+    /// Chooses the exit code for an error that aborted the run
+    pub fn from_error(error: &Error) -> Self {
+        if error.is_auth() {
+            ExitCode::AuthFailure
+        } else if error.is_config() || matches!(error, Error::FileNotFound { .. }) {
+            // A missing file was named on the command line or in the config
+            ExitCode::ConfigError
+        } else {
+            ExitCode::AllFailed
+        }
+    }
+}
+
+impl From<ExitCode> for std::process::ExitCode {
+    fn from(code: ExitCode) -> Self {
+        std::process::ExitCode::from(code.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::FileWarning;
+
+    #[test]
+    fn test_exit_code_values() {
+        assert_eq!(ExitCode::Success.code(), 0);
+        assert_eq!(ExitCode::ConfigError.code(), 2);
+        assert_eq!(ExitCode::PartialFailure.code(), 3);
+        assert_eq!(ExitCode::AuthFailure.code(), 4);
+        assert_eq!(ExitCode::AllFailed.code(), 5);
+        assert_eq!(ExitCode::Warnings.code(), 6);
+    }
+
+    #[test]
+    fn test_exit_code_from_report() {
+        let mut report = UploadReport::new();
+        assert_eq!(ExitCode::from_report(&report), ExitCode::Success);
+
+        report.record_skipped("a.md", "already published");
+        assert_eq!(ExitCode::from_report(&report), ExitCode::Success);
+
+        report.record_failed("b.md", &Error::wechat("boom"));
+        assert_eq!(ExitCode::from_report(&report), ExitCode::PartialFailure);
+
+        let mut all_failed = UploadReport::new();
+        all_failed.record_failed("a.md", &Error::wechat("boom"));
+        assert_eq!(ExitCode::from_report(&all_failed), ExitCode::AllFailed);
+
+        report.record_failed("c.md", &Error::auth("invalid credential"));
+        assert_eq!(ExitCode::from_report(&report), ExitCode::AuthFailure);
+    }
+
+    #[test]
+    fn test_exit_code_from_report_with_warnings() {
+        let mut report = UploadReport::new();
+        report.record_uploaded("a.md", "draft");
+        report.warnings.push(FileWarning {
+            path: "a.md".into(),
+            message: "theme fallback not applied".to_string(),
+        });
+        assert_eq!(ExitCode::from_report_with(&report, false), ExitCode::Success);
+        assert_eq!(ExitCode::from_report_with(&report, true), ExitCode::Warnings);
+
+        // Failures keep their own code
+        report.record_failed("b.md", &Error::wechat("boom"));
+        assert_eq!(ExitCode::from_report_with(&report, true), ExitCode::PartialFailure);
+    }
+
+    #[test]
+    fn test_exit_code_from_error() {
+        assert_eq!(
+            ExitCode::from_error(&Error::config("bad")),
+            ExitCode::ConfigError
+        );
+        assert_eq!(
+            ExitCode::from_error(&Error::auth("bad")),
+            ExitCode::AuthFailure
+        );
+        assert_eq!(
+            ExitCode::from_error(&Error::file_not_found("missing.yaml")),
+            ExitCode::ConfigError
+        );
+        assert_eq!(
+            ExitCode::from_error(&Error::wechat("bad")),
+            ExitCode::AllFailed
+        );
+    }
+}
@@ -0,0 +1,321 @@
+//! User commands run around uploads
+//!
+//! The `hooks` settings name shell commands that run before and after each
+//! file is uploaded and once after the run, e.g. a linter before the upload
+//! and a chat notification after it. Each command runs with `sh -c` (`cmd /C`
+//! on Windows) and learns about the file from environment variables:
+//!
+//! | Variable      | Value                                                  |
+//! |---------------|--------------------------------------------------------|
+//! | `WX_FILE`     | Path of the markdown file                              |
+//! | `WX_TITLE`    | Frontmatter title, empty if there is none              |
+//! | `WX_DRAFT_ID` | Draft media ID after a successful upload, else empty   |
+//! | `WX_STATUS`   | `pending` before the upload, `uploaded` or `failed`    |
+//! | `WX_ERROR`    | Error message of a failed upload, else empty           |
+//!
+//! `post_run` gets `WX_STATUS` (`success`, `partial` or `failed`) and the
+//! counts `WX_UPLOADED`, `WX_SKIPPED` and `WX_FAILED` instead.
+//!
+//! Output of a hook is captured and only shown when it fails or in verbose
+//! mode. A hook that runs longer than its timeout is killed and counts as
+//! failed.
+
+use crate::error::{Error, Result};
+use crate::output::{FORMATTER, OutputFormatter};
+use crate::report::UploadReport;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::info;
+
+/// Seconds a hook may run before it is killed, unless configured otherwise
+pub const DEFAULT_HOOK_TIMEOUT_SECS: u64 = 60;
+
+/// Shell commands run around uploads
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hooks {
+    /// Runs before each file is uploaded; a non-zero exit skips the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_upload: Option<String>,
+    /// Runs after each upload attempt, successful or not
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_upload: Option<String>,
+    /// Runs once after all files are processed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_run: Option<String>,
+    /// Seconds each hook may run (default: 60)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+}
+
+impl Hooks {
+    /// Returns how long each hook may run
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs.unwrap_or(DEFAULT_HOOK_TIMEOUT_SECS))
+    }
+
+    /// Checks that the hook commands are not blank and the timeout is positive
+    ///
+    /// # Errors
+    ///
+    /// Returns a configuration error naming the invalid hook
+    pub fn validate(&self) -> Result<()> {
+        for (name, command) in [
+            ("pre_upload", &self.pre_upload),
+            ("post_upload", &self.post_upload),
+            ("post_run", &self.post_run),
+        ] {
+            if command.as_deref().is_some_and(|c| c.trim().is_empty()) {
+                return Err(Error::config(format!("hooks.{} must not be empty", name)));
+            }
+        }
+        if self.timeout_secs == Some(0) {
+            return Err(Error::config("hooks.timeout_secs must be at least 1"));
+        }
+        Ok(())
+    }
+
+    /// Runs `pre_upload` for a file about to be uploaded
+    ///
+    /// # Errors
+    ///
+    /// Returns a hook error if the command exits non-zero, times out or cannot be started
+    pub async fn pre_upload(&self, path: &Path, title: Option<&str>, verbose: bool) -> Result<()> {
+        let Some(command) = &self.pre_upload else {
+            return Ok(());
+        };
+        let env = file_env(path, title, None, "pending", None);
+        run_hook("pre_upload", command, &env, self.timeout(), verbose).await
+    }
+
+    /// Runs `post_upload` after a file was uploaded, or failed to upload
+    ///
+    /// # Errors
+    ///
+    /// Returns a hook error if the command exits non-zero, times out or cannot be started
+    pub async fn post_upload(
+        &self,
+        path: &Path,
+        title: Option<&str>,
+        result: &Result<String>,
+        verbose: bool,
+    ) -> Result<()> {
+        let Some(command) = &self.post_upload else {
+            return Ok(());
+        };
+        let env = match result {
+            Ok(draft_id) => file_env(path, title, Some(draft_id), "uploaded", None),
+            Err(e) => file_env(path, title, None, "failed", Some(&e.display_chain())),
+        };
+        run_hook("post_upload", command, &env, self.timeout(), verbose).await
+    }
+
+    /// Runs `post_run` with the outcome of the run
+    ///
+    /// # Errors
+    ///
+    /// Returns a hook error if the command exits non-zero, times out or cannot be started
+    pub async fn post_run(&self, report: &UploadReport, verbose: bool) -> Result<()> {
+        let Some(command) = &self.post_run else {
+            return Ok(());
+        };
+        let status = if report.failed() == 0 {
+            "success"
+        } else if report.uploaded() > 0 {
+            "partial"
+        } else {
+            "failed"
+        };
+        let env = [
+            ("WX_STATUS", status.to_string()),
+            ("WX_UPLOADED", report.uploaded().to_string()),
+            ("WX_SKIPPED", (report.skipped() + report.cover_deferred()).to_string()),
+            ("WX_FAILED", report.failed().to_string()),
+        ];
+        run_hook("post_run", command, &env, self.timeout(), verbose).await
+    }
+}
+
+/// Returns the environment of a per-file hook
+fn file_env(
+    path: &Path,
+    title: Option<&str>,
+    draft_id: Option<&str>,
+    status: &str,
+    error: Option<&str>,
+) -> [(&'static str, String); 5] {
+    [
+        ("WX_FILE", path.display().to_string()),
+        ("WX_TITLE", title.unwrap_or_default().to_string()),
+        ("WX_DRAFT_ID", draft_id.unwrap_or_default().to_string()),
+        ("WX_STATUS", status.to_string()),
+        ("WX_ERROR", error.unwrap_or_default().to_string()),
+    ]
+}
+
+/// Runs a hook command with `env` added to the environment
+///
+/// Captured output is printed when the hook fails, and logged in verbose mode.
+async fn run_hook(
+    name: &str,
+    command: &str,
+    env: &[(&str, String)],
+    timeout: Duration,
+    verbose: bool,
+) -> Result<()> {
+    let mut process = shell(command);
+    process
+        .envs(env.iter().map(|(key, value)| (*key, value.as_str())))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    if verbose {
+        info!("Running {} hook: {}", name, command);
+    }
+    let child = process
+        .spawn()
+        .map_err(|e| Error::hook(name, format!("could not start '{}': {}", command, e)))?;
+
+    let output = match tokio::time::timeout(timeout, child.wait_with_output()).await {
+        Ok(output) => output.map_err(|e| Error::hook(name, e.to_string()))?,
+        // Dropping the future kills the child
+        Err(_) => {
+            return Err(Error::hook(
+                name,
+                format!("timed out after {}s", timeout.as_secs()),
+            ));
+        }
+    };
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if output.status.success() {
+        if verbose {
+            for line in stdout.lines().chain(stderr.lines()) {
+                info!("[{}] {}", name, line);
+            }
+        }
+        return Ok(());
+    }
+
+    for line in stdout.lines().chain(stderr.lines()) {
+        FORMATTER.print_detail(&format!("  [{}] {}", name, line));
+    }
+    let reason = match output.status.code() {
+        Some(code) => format!("exited with status {}", code),
+        None => "was terminated by a signal".to_string(),
+    };
+    Err(Error::hook(name, reason))
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut process = Command::new("sh");
+    process.arg("-c").arg(command);
+    process
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut process = Command::new("cmd");
+    process.arg("/C").arg(command);
+    process
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn hooks(pre_upload: &str) -> Hooks {
+        Hooks {
+            pre_upload: Some(pre_upload.to_string()),
+            ..Hooks::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pre_upload_passes_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("env.txt");
+        let command = format!(
+            "printf '%s|%s|%s|%s' \"$WX_FILE\" \"$WX_TITLE\" \"$WX_STATUS\" \"$WX_DRAFT_ID\" > '{}'",
+            out.display()
+        );
+
+        hooks(&command)
+            .pre_upload(Path::new("posts/a.md"), Some("Hello"), false)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "posts/a.md|Hello|pending|");
+    }
+
+    #[tokio::test]
+    async fn test_hook_failure_and_timeout() {
+        let err = hooks("echo lint failed >&2; exit 3")
+            .pre_upload(Path::new("a.md"), None, false)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Hook { .. }));
+        assert_eq!(err.to_string(), "pre_upload hook failed: exited with status 3");
+
+        let slow = Hooks {
+            timeout_secs: Some(1),
+            ..hooks("sleep 5")
+        };
+        let started = std::time::Instant::now();
+        let err = slow.pre_upload(Path::new("a.md"), None, false).await.unwrap_err();
+        assert!(err.to_string().contains("timed out after 1s"));
+        assert!(started.elapsed() < Duration::from_secs(4));
+    }
+
+    #[tokio::test]
+    async fn test_post_upload_and_post_run_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("env.txt");
+        let hooks = Hooks {
+            post_upload: Some(format!(
+                "printf '%s %s %s;' \"$WX_STATUS\" \"$WX_DRAFT_ID\" \"$WX_ERROR\" >> '{}'",
+                out.display()
+            )),
+            post_run: Some(format!(
+                "printf '%s %s %s %s' \"$WX_STATUS\" \"$WX_UPLOADED\" \"$WX_SKIPPED\" \"$WX_FAILED\" >> '{}'",
+                out.display()
+            )),
+            ..Hooks::default()
+        };
+        let path = Path::new("a.md");
+
+        hooks.post_upload(path, None, &Ok("media-1".to_string()), false).await.unwrap();
+        hooks
+            .post_upload(path, None, &Err(Error::wechat("quota")), false)
+            .await
+            .unwrap();
+
+        let mut report = UploadReport::new();
+        report.record_uploaded("a.md", "media-1");
+        report.record_skipped("b.md", "published");
+        report.record_failed("c.md", &Error::wechat("quota"));
+        hooks.post_run(&report, false).await.unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "uploaded media-1 ;failed  WeChat API error: quota;partial 1 1 1"
+        );
+    }
+
+    #[test]
+    fn test_validate_hooks() {
+        assert!(Hooks::default().validate().is_ok());
+        assert!(hooks("  ").validate().unwrap_err().is_config());
+        let zero = Hooks {
+            timeout_secs: Some(0),
+            ..hooks("true")
+        };
+        assert!(zero.validate().is_err());
+        assert_eq!(Hooks::default().timeout(), Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,139 @@
+//! Rendered HTML of a single article
+//!
+//! Implements `--dump-html`: renders a file with the theme renderer of
+//! `wechat_pub_rs`, the same one the upload uses, and writes the HTML for
+//! inspection. The body goes through the same local rewrites as an upload
+//! (theme fallback and body transforms), but local images keep
+//! their paths, since WeChat URLs are only known once they are uploaded.
+
+use crate::error::{Error, Result};
+use crate::markdown::{ArticleContext, format_markdown, parse_markdown_file_with};
+use crate::wechat::{ProcessOptions, resolve_upload_style};
+use crate::wechat_api::{DEFAULT_CODE_HIGHLIGHTER, DEFAULT_THEME, render_content};
+use std::path::Path;
+use wechat_pub_rs::markdown::MarkdownParser;
+use wechat_pub_rs::theme::ThemeManager;
+
+/// HTML of an article together with the style it was rendered with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedHtml {
+    /// Theme the article was rendered with
+    pub theme: String,
+    /// Code highlighter the article was rendered with
+    pub code: String,
+    /// Rendered HTML body, as sent to WeChat
+    pub html: String,
+}
+
+impl RenderedHtml {
+    /// Returns the HTML headed by a comment naming `source` and the resolved
+    /// theme and code highlighter
+    pub fn to_document(&self, source: &Path) -> String {
+        format!(
+            "<!-- wx-uploader {}: {}\n     theme: {}\n     code highlighter: {} -->\n{}\n",
+            env!("CARGO_PKG_VERSION"),
+            source.display(),
+            self.theme,
+            self.code,
+            self.html
+        )
+    }
+}
+
+/// Renders the markdown file at `path` as it would be uploaded with `options`
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be parsed, its theme is unknown and
+/// the theme fallback does not replace it, or a body transform fails
+pub async fn render_file(path: &Path, options: &ProcessOptions) -> Result<RenderedHtml> {
+    let (mut frontmatter, body) = parse_markdown_file_with(path, &options.parse_options()).await?;
+
+    resolve_upload_style(&mut frontmatter, path, options);
+
+    let ctx = ArticleContext {
+        path,
+        frontmatter: &frontmatter,
+        verbose: options.verbose,
+    };
+    let body = options.transform_pipeline(&frontmatter)?.apply(&ctx, body)?;
+
+    let markdown = format_markdown(&frontmatter, &body)?;
+    let content = MarkdownParser::new()
+        .parse(&markdown)
+        .map_err(|e| Error::markdown_parse(path, e.to_string()))?;
+    let html = render_content(&ThemeManager::new(), &content)?;
+
+    Ok(RenderedHtml {
+        theme: content.theme.unwrap_or_else(|| DEFAULT_THEME.to_string()),
+        code: content.code.unwrap_or_else(|| DEFAULT_CODE_HIGHLIGHTER.to_string()),
+        html,
+    })
+}
+
+/// Renders the markdown file at `path` and writes the HTML to `output`, or
+/// to stdout if `output` is `-`
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be rendered or `output` cannot be written
+pub async fn dump_html(path: &Path, output: &Path, options: &ProcessOptions) -> Result<RenderedHtml> {
+    let rendered = render_file(path, options).await?;
+    let document = rendered.to_document(path);
+    if output.as_os_str() == "-" {
+        print!("{}", document);
+    } else {
+        tokio::fs::write(output, document).await?;
+    }
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AnchorLinks, ThemeFallback};
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_dump_html_names_resolved_style() {
+        let temp_dir = TempDir::new().unwrap();
+        let md_file = temp_dir.path().join("post.md");
+        tokio::fs::write(
+            &md_file,
+            "---\ntitle: Post\ntheme: nope\ncode: github\n---\n\n# Heading\n\n[up](#heading)\n",
+        )
+        .await
+        .unwrap();
+        let output = temp_dir.path().join("post.html");
+
+        let options = ProcessOptions {
+            theme_fallback: ThemeFallback::Default,
+            default_theme: Some("lapis".to_string()),
+            anchor_links: AnchorLinks::Text,
+            ..Default::default()
+        };
+        let rendered = dump_html(&md_file, &output, &options).await.unwrap();
+        assert_eq!(rendered.theme, "lapis");
+        assert_eq!(rendered.code, "github");
+
+        let document = tokio::fs::read_to_string(&output).await.unwrap();
+        assert!(document.starts_with("<!-- wx-uploader "), "{}", document);
+        assert!(document.contains("theme: lapis\n"));
+        assert!(document.contains("code highlighter: github -->"));
+        assert!(document.contains("Heading"));
+        assert!(!document.contains("href=\"#heading\""));
+    }
+
+    #[tokio::test]
+    async fn test_render_file_defaults_and_unknown_theme() {
+        let temp_dir = TempDir::new().unwrap();
+        let md_file = temp_dir.path().join("post.md");
+        tokio::fs::write(&md_file, "# Plain\n").await.unwrap();
+        let rendered = render_file(&md_file, &ProcessOptions::default()).await.unwrap();
+        assert_eq!(rendered.theme, DEFAULT_THEME);
+        assert_eq!(rendered.code, DEFAULT_CODE_HIGHLIGHTER);
+
+        tokio::fs::write(&md_file, "---\ntheme: nope\n---\n# Plain\n").await.unwrap();
+        assert!(render_file(&md_file, &ProcessOptions::default()).await.is_err());
+    }
+}
@@ -0,0 +1,304 @@
+//! HTTP tracing with redaction
+//!
+//! Implements `--trace-http`: AI provider requests, responses and image
+//! downloads are logged at debug level under the `wx_uploader::http_trace`
+//! target. All URLs and headers pass through [`redact_url`] and
+//! [`redact_header`] before they are logged, so a provider only has to route
+//! its calls through [`HttpTracer`] to get secrets masked.
+
+use tracing::debug;
+
+/// Default number of body bytes logged per request or response
+pub const DEFAULT_TRACE_BODY_LIMIT: usize = 2048;
+
+/// Replacement text for redacted secrets
+pub const REDACTED: &str = "***";
+
+/// Query parameters whose values are secrets (compared case-insensitively)
+const SECRET_QUERY_PARAMS: &[&str] = &[
+    "key",
+    "api_key",
+    "apikey",
+    "access_token",
+    "token",
+    "secret",
+    "appsecret",
+];
+
+/// Headers whose values are secrets (compared case-insensitively)
+const SECRET_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "x-api-key",
+    "x-goog-api-key",
+    "api-key",
+    "cookie",
+];
+
+/// Returns the URL with the values of secret query parameters masked
+///
+/// # Examples
+///
+/// ```
+/// use wx_uploader::http_trace::redact_url;
+///
+/// assert_eq!(
+///     redact_url("https://example.com/v1/models/m:predict?key=SECRET&alt=json"),
+///     "https://example.com/v1/models/m:predict?key=***&alt=json"
+/// );
+/// ```
+pub fn redact_url(url: &str) -> String {
+    let Some((base, rest)) = url.split_once('?') else {
+        return url.to_string();
+    };
+    let (query, fragment) = match rest.split_once('#') {
+        Some((query, fragment)) => (query, Some(fragment)),
+        None => (rest, None),
+    };
+
+    let query = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) if is_secret(name, SECRET_QUERY_PARAMS) => {
+                format!("{}={}", name, REDACTED)
+            }
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+
+    match fragment {
+        Some(fragment) => format!("{}?{}#{}", base, query, fragment),
+        None => format!("{}?{}", base, query),
+    }
+}
+
+/// Returns the header value to log, masking secrets
+///
+/// The authentication scheme of `Authorization` headers is kept, e.g.
+/// `Bearer ***`.
+pub fn redact_header(name: &str, value: &str) -> String {
+    if !is_secret(name, SECRET_HEADERS) {
+        return value.to_string();
+    }
+
+    match value.split_once(' ') {
+        Some((scheme, _)) if name.to_ascii_lowercase().ends_with("authorization") => {
+            format!("{} {}", scheme, REDACTED)
+        }
+        _ => REDACTED.to_string(),
+    }
+}
+
+/// Truncates a body to at most `limit` bytes on a character boundary
+pub fn truncate_body(body: &str, limit: usize) -> String {
+    if body.len() <= limit {
+        return body.to_string();
+    }
+
+    let mut end = limit;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!(
+        "{}... ({} bytes truncated)",
+        &body[..end],
+        body.len() - end
+    )
+}
+
+fn is_secret(name: &str, secrets: &[&str]) -> bool {
+    secrets.iter().any(|secret| secret.eq_ignore_ascii_case(name))
+}
+
+/// Logs HTTP traffic of a provider when tracing is enabled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HttpTracer {
+    enabled: bool,
+    body_limit: usize,
+}
+
+impl Default for HttpTracer {
+    fn default() -> Self {
+        Self::disabled()
+    }
+}
+
+impl HttpTracer {
+    /// Creates a tracer that logs bodies up to `body_limit` bytes
+    pub fn new(body_limit: usize) -> Self {
+        Self {
+            enabled: true,
+            body_limit,
+        }
+    }
+
+    /// Creates a tracer that logs nothing
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            body_limit: DEFAULT_TRACE_BODY_LIMIT,
+        }
+    }
+
+    /// Returns true if traffic is logged
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Logs an outgoing request
+    pub fn request(
+        &self,
+        provider: &str,
+        method: &str,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: Option<&str>,
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        let headers = headers
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name, redact_header(name, value)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        debug!(
+            target: "wx_uploader::http_trace",
+            "{} request: {} {} headers=[{}] body={}",
+            provider,
+            method,
+            redact_url(url),
+            headers,
+            body.map(|b| truncate_body(b, self.body_limit))
+                .unwrap_or_else(|| "<none>".to_string())
+        );
+    }
+
+    /// Logs a response with a text body
+    pub fn response(&self, provider: &str, url: &str, status: u16, body: &str) {
+        if !self.enabled {
+            return;
+        }
+
+        debug!(
+            target: "wx_uploader::http_trace",
+            "{} response: {} {} body={}",
+            provider,
+            status,
+            redact_url(url),
+            truncate_body(body, self.body_limit)
+        );
+    }
+
+    /// Logs a response with a binary body, such as a downloaded image
+    pub fn binary_response(&self, provider: &str, url: &str, status: u16, len: usize) {
+        if !self.enabled {
+            return;
+        }
+
+        debug!(
+            target: "wx_uploader::http_trace",
+            "{} response: {} {} body=<{} bytes>",
+            provider,
+            status,
+            redact_url(url),
+            len
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_gemini_key_is_masked_in_logged_url() {
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+
+        let url = "https://generativelanguage.googleapis.com/v1beta/models/gemini-2.5-flash:generateContent?key=SECRET";
+        tracing::subscriber::with_default(subscriber, || {
+            let tracer = HttpTracer::new(DEFAULT_TRACE_BODY_LIMIT);
+            tracer.request(
+                "Gemini",
+                "POST",
+                url,
+                &[("Content-Type", "application/json")],
+                Some("{\"contents\":[]}"),
+            );
+            tracer.response("Gemini", url, 200, "{\"candidates\":[]}");
+        });
+
+        let logged = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        assert!(!logged.contains("SECRET"), "{}", logged);
+        assert!(logged.contains(":generateContent?key=***"), "{}", logged);
+        assert!(logged.contains("Gemini request: POST"), "{}", logged);
+        assert!(logged.contains("Gemini response: 200"), "{}", logged);
+    }
+
+    #[test]
+    fn test_redact_url() {
+        assert_eq!(redact_url("https://x.test/a"), "https://x.test/a");
+        assert_eq!(
+            redact_url("https://x.test/a?alt=json&KEY=s1&access_token=s2#frag"),
+            "https://x.test/a?alt=json&KEY=***&access_token=***#frag"
+        );
+    }
+
+    #[test]
+    fn test_redact_header() {
+        assert_eq!(redact_header("Authorization", "Bearer sk-123"), "Bearer ***");
+        assert_eq!(redact_header("x-goog-api-key", "abc"), "***");
+        assert_eq!(
+            redact_header("Content-Type", "application/json"),
+            "application/json"
+        );
+    }
+
+    #[test]
+    fn test_truncate_body() {
+        assert_eq!(truncate_body("short", 10), "short");
+        assert_eq!(truncate_body("abcdef", 3), "abc... (3 bytes truncated)");
+        // Never splits a multi-byte character
+        assert_eq!(truncate_body("日本", 4), "日... (3 bytes truncated)");
+    }
+
+    #[test]
+    fn test_disabled_tracer_logs_nothing() {
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_writer(move || writer.clone())
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, || {
+            HttpTracer::disabled().request("OpenAI", "GET", "https://x.test", &[], None);
+        });
+
+        assert!(capture.0.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,338 @@
+//! Inline base64 images
+//!
+//! WeChat does not render `data:` URIs, so images embedded in an article body
+//! as `![alt](data:image/png;base64,...)` or `<img src="data:image/...">` are
+//! decoded to temporary files, uploaded like local body images and replaced
+//! by the returned URLs before the article is sent. Such `<img>` tags become
+//! markdown images, since raw HTML is left out of the rendered article.
+//!
+//! Body images are deduplicated by the BLAKE3 hash of their content, the
+//! same way `wechat_pub_rs` does, so an image that is both embedded and
+//! referenced as a local file, or used by several articles, is uploaded once.
+
+use crate::error::{Error, Result};
+use crate::validation::{LinkKind, extract_links};
+use crate::wechat::WeChatUploader;
+use base64::Engine;
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use tokio::sync::Mutex;
+use tracing::info;
+
+/// Matches an HTML `<img>` tag
+static IMG_TAG_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)<img\b[^>]*>").expect("valid img tag regex"));
+
+/// Matches the `src` attribute of an `<img>` tag
+static SRC_ATTR_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)\ssrc\s*=\s*["']\s*([^"']*?)\s*["']"#).expect("valid src regex")
+});
+
+/// Matches the `alt` attribute of an `<img>` tag
+static ALT_ATTR_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)\salt\s*=\s*["']([^"']*)["']"#).expect("valid alt regex")
+});
+
+/// Image decoded from a data URI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InlineImage {
+    /// File extension matching the image type, e.g. `png`
+    pub extension: &'static str,
+    /// Decoded image bytes
+    pub bytes: Vec<u8>,
+}
+
+/// Returns the hex BLAKE3 hash of an image, used to deduplicate uploads
+pub fn content_hash(bytes: &[u8]) -> String {
+    blake3::hash(bytes).to_hex().to_string()
+}
+
+/// Returns the distinct image data URIs in a markdown body, in order of appearance
+///
+/// Markdown images in code blocks and code spans are ignored, like other links.
+pub fn find_data_uris(body: &str) -> Vec<String> {
+    let markdown = extract_links(body)
+        .into_iter()
+        .filter(|link| link.kind == LinkKind::Image && is_data_uri(&link.target))
+        .map(|link| link.target);
+    let html = IMG_TAG_RE
+        .find_iter(body)
+        .filter_map(|tag| img_data_src(tag.as_str()))
+        .map(str::to_string);
+
+    let mut uris: Vec<String> = Vec::new();
+    for uri in markdown.chain(html) {
+        if !uris.contains(&uri) {
+            uris.push(uri);
+        }
+    }
+    uris
+}
+
+fn is_data_uri(target: &str) -> bool {
+    target
+        .get(..5)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("data:"))
+}
+
+/// Returns the `src` of an `<img>` tag if it is a data URI
+fn img_data_src(tag: &str) -> Option<&str> {
+    let src = SRC_ATTR_RE.captures(tag)?.get(1)?.as_str();
+    is_data_uri(src).then_some(src)
+}
+
+/// Rewrites `<img>` tags with a data URI as markdown images, keeping their `alt` text
+fn img_tags_to_markdown(body: &str) -> Cow<'_, str> {
+    IMG_TAG_RE.replace_all(body, |captures: &regex::Captures| {
+        let tag = &captures[0];
+        match img_data_src(tag) {
+            Some(src) => {
+                let alt = ALT_ATTR_RE
+                    .captures(tag)
+                    .map(|alt| alt[1].replace(['[', ']'], ""))
+                    .unwrap_or_default();
+                format!("![{}]({})", alt, src)
+            }
+            None => tag.to_string(),
+        }
+    })
+}
+
+/// Decodes a `data:image/<type>;base64,<payload>` URI
+///
+/// # Errors
+///
+/// Returns an error for URIs that are not base64 encoded, not an image type
+/// WeChat accepts (PNG, JPEG, GIF or WebP), or whose payload is not valid base64
+pub fn decode_data_uri(uri: &str) -> Result<InlineImage> {
+    let invalid = |reason: &str| {
+        let preview: String = uri.chars().take(40).collect();
+        Error::generic(format!("invalid inline image '{}...': {}", preview, reason))
+    };
+
+    let rest = uri
+        .get(5..)
+        .filter(|_| is_data_uri(uri))
+        .ok_or_else(|| invalid("not a data URI"))?;
+    let (header, payload) = rest
+        .split_once(',')
+        .ok_or_else(|| invalid("missing data"))?;
+    let mut parameters = header.split(';');
+    let media_type = parameters.next().unwrap_or_default().to_ascii_lowercase();
+    if !parameters.any(|parameter| parameter.eq_ignore_ascii_case("base64")) {
+        return Err(invalid("only base64 encoded images are supported"));
+    }
+    let extension = match media_type.as_str() {
+        "image/png" => "png",
+        "image/jpeg" | "image/jpg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        _ => return Err(invalid(&format!("unsupported type '{}'", media_type))),
+    };
+
+    // Line breaks and URL-safe characters turn up in hand-edited files
+    let payload: String = payload
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace())
+        .map(|c| match c {
+            '-' => '+',
+            '_' => '/',
+            c => c,
+        })
+        .collect();
+    let bytes = base64::engine::general_purpose::STANDARD_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .map_err(|e| invalid(&e.to_string()))?;
+    if bytes.is_empty() {
+        return Err(invalid("empty image"));
+    }
+
+    Ok(InlineImage { extension, bytes })
+}
+
+/// Uploaded body image URLs by content hash
+///
+/// Shared by local and inline body images so identical content is uploaded
+/// once per client, whatever file or data URI it came from.
+#[derive(Debug, Default)]
+pub struct BodyImageCache {
+    urls: Mutex<HashMap<String, String>>,
+}
+
+impl BodyImageCache {
+    /// Creates an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the URL of an image with the same content as `image_path`,
+    /// calling `upload` only if none was uploaded yet
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the image cannot be read or the upload fails
+    pub async fn get_or_upload<F, Fut>(&self, image_path: &Path, upload: F) -> Result<String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<String>>,
+    {
+        let hash = content_hash(&tokio::fs::read(image_path).await?);
+        if let Some(url) = self.urls.lock().await.get(&hash) {
+            return Ok(url.clone());
+        }
+
+        let url = upload().await?;
+        self.urls.lock().await.insert(hash, url.clone());
+        Ok(url)
+    }
+
+    /// Returns the number of distinct images uploaded
+    pub async fn len(&self) -> usize {
+        self.urls.lock().await.len()
+    }
+
+    /// Returns true if no image was uploaded yet
+    pub async fn is_empty(&self) -> bool {
+        self.urls.lock().await.is_empty()
+    }
+}
+
+/// Decoded image written to a temporary file, removed on drop
+struct TempImage {
+    path: PathBuf,
+}
+
+impl Drop for TempImage {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Uploads the inline images of a body and returns it with their URLs in place
+///
+/// Returns `None` if the body has no inline images.
+///
+/// # Errors
+///
+/// Returns an error if an image cannot be decoded or uploaded
+pub async fn upload_inline_images(
+    client: &dyn WeChatUploader,
+    body: &str,
+    verbose: bool,
+) -> Result<Option<String>> {
+    let body = img_tags_to_markdown(body);
+    let uris = find_data_uris(&body);
+    if uris.is_empty() {
+        return Ok(None);
+    }
+
+    let mut body = body.into_owned();
+    for uri in &uris {
+        let image = decode_data_uri(uri)?;
+        let name = format!("wx-inline-{}.{}", uuid::Uuid::new_v4(), image.extension);
+        let temp = TempImage {
+            path: std::env::temp_dir().join(name),
+        };
+        tokio::fs::write(&temp.path, &image.bytes).await?;
+
+        let url = client.upload_body_image(&temp.path).await?;
+        if verbose {
+            info!("Uploaded inline image ({} bytes) as {}", image.bytes.len(), url);
+        }
+        body = body.replace(uri.as_str(), &url);
+    }
+    Ok(Some(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 1x1 transparent PNG
+    const PNG_BASE64: &str = concat!(
+        "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJ",
+        "RU5ErkJggg=="
+    );
+
+    #[test]
+    fn test_find_data_uris() {
+        let uri = format!("data:image/png;base64,{}", PNG_BASE64);
+        let body = format!(
+            "![dot]({uri})\n\n<img alt=\"dot\" src=\"{uri}\">\n\n\
+             <IMG src='data:image/gif;base64,R0lGODlhAQABAAAAACw='/>\n\n\
+             ![local](images/a.png) ![remote](https://example.com/a.png)\n\n\
+             `![code](data:image/png;base64,AAAA)`\n"
+        );
+
+        assert_eq!(
+            find_data_uris(&body),
+            vec![uri, "data:image/gif;base64,R0lGODlhAQABAAAAACw=".to_string()]
+        );
+        assert!(find_data_uris("No images here.").is_empty());
+    }
+
+    #[test]
+    fn test_img_tags_to_markdown() {
+        let body = "<img alt=\"A [dot]\" width=\"1\" src=\"data:image/png;base64,AAAA\" />\n\
+                    <img src=\"images/a.png\">";
+        assert_eq!(
+            img_tags_to_markdown(body),
+            "![A dot](data:image/png;base64,AAAA)\n<img src=\"images/a.png\">"
+        );
+        assert!(matches!(img_tags_to_markdown("plain"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_decode_data_uri() {
+        let image = decode_data_uri(&format!("data:image/png;base64,{}", PNG_BASE64)).unwrap();
+        assert_eq!(image.extension, "png");
+        assert!(image.bytes.starts_with(b"\x89PNG"));
+
+        // Parameters, case, line breaks and missing padding are tolerated
+        let wrapped = format!("DATA:image/PNG;charset=utf-8;base64,{}", &PNG_BASE64[..20])
+            + "\n"
+            + PNG_BASE64[20..].trim_end_matches('=');
+        assert_eq!(decode_data_uri(&wrapped).unwrap(), image);
+
+        let err = decode_data_uri("data:image/png,rawbytes").unwrap_err();
+        assert!(err.to_string().contains("only base64"), "{}", err);
+        let err = decode_data_uri("data:image/svg+xml;base64,PHN2Zz4=").unwrap_err();
+        assert!(err.to_string().contains("unsupported type 'image/svg+xml'"), "{}", err);
+        assert!(decode_data_uri("data:image/png;base64,***").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_body_image_cache_dedupes_by_content() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let first = temp_dir.path().join("a.png");
+        let copy = temp_dir.path().join("b.png");
+        let other = temp_dir.path().join("c.png");
+        std::fs::write(&first, b"same").unwrap();
+        std::fs::write(&copy, b"same").unwrap();
+        std::fs::write(&other, b"other").unwrap();
+
+        let cache = BodyImageCache::new();
+        let uploads = std::sync::atomic::AtomicUsize::new(0);
+        let upload = || async {
+            let n = uploads.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(format!("https://mmbiz.qpic.cn/{}", n))
+        };
+
+        assert_eq!(cache.get_or_upload(&first, upload).await.unwrap(), "https://mmbiz.qpic.cn/0");
+        assert_eq!(cache.get_or_upload(&copy, upload).await.unwrap(), "https://mmbiz.qpic.cn/0");
+        assert_eq!(cache.get_or_upload(&other, upload).await.unwrap(), "https://mmbiz.qpic.cn/1");
+        assert_eq!(cache.len().await, 2);
+        assert_eq!(uploads.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        // A failed upload is not cached
+        let failing = || async { Err(Error::wechat("quota")) };
+        let missing = temp_dir.path().join("d.png");
+        std::fs::write(&missing, b"new").unwrap();
+        assert!(cache.get_or_upload(&missing, failing).await.is_err());
+        assert_eq!(cache.len().await, 2);
+    }
+}
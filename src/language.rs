@@ -0,0 +1,163 @@
+//! Article language detection and per-language scene prompts
+//!
+//! The AI scene description for a cover is requested with a prompt chosen by
+//! the article's language. The language comes from the `lang` frontmatter
+//! field, or is detected from the share of CJK characters in the text. Prompt
+//! templates can be overridden per language in the configuration file; English
+//! is the default for latin-script content and any language without a template.
+
+use std::collections::HashMap;
+
+/// Language used when no other language is detected or configured
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// Share of CJK characters among all letters above which text counts as Chinese
+///
+/// Each Chinese character carries roughly a word, so a Chinese article with
+/// English terms and code identifiers still clears this threshold.
+const CJK_RATIO_THRESHOLD: f64 = 0.2;
+
+/// Default prompt for latin-script content
+const ENGLISH_SCENE_PROMPT: &str = "Generate a 2-sentence visual scene description in English for a cover image based on the article content.";
+
+/// Default prompt for Chinese content
+///
+/// The description is still requested in English, which image models follow
+/// best, but the model is told to read the article as Chinese text.
+const CHINESE_SCENE_PROMPT: &str = "The article below is written in Chinese. Read it in Chinese, then generate a 2-sentence visual scene description in English for a cover image that reflects its themes, imagery and any Chinese cultural context.";
+
+/// Returns true for CJK unified ideographs, including extensions and compatibility forms
+pub fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{4E00}'..='\u{9FFF}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{20000}'..='\u{2A6DF}')
+}
+
+/// Detects the language of `text`: `zh` for mostly CJK text, otherwise `en`
+pub fn detect_language(text: &str) -> &'static str {
+    let (cjk, letters) = text
+        .chars()
+        .filter(|c| c.is_alphabetic())
+        .fold((0usize, 0usize), |(cjk, letters), c| {
+            (cjk + usize::from(is_cjk(c)), letters + 1)
+        });
+
+    if letters > 0 && cjk as f64 / letters as f64 >= CJK_RATIO_THRESHOLD {
+        "zh"
+    } else {
+        DEFAULT_LANGUAGE
+    }
+}
+
+/// Normalizes a language tag for lookups, e.g. `zh_CN` becomes `zh-cn`
+pub fn normalize_language(lang: &str) -> String {
+    lang.trim().replace('_', "-").to_ascii_lowercase()
+}
+
+/// Returns true if `lang` looks like a language tag such as `en`, `zh` or `zh-CN`
+pub fn is_valid_language(lang: &str) -> bool {
+    let mut parts = lang.split(['-', '_']);
+    let primary = parts.next().unwrap_or_default();
+    (2..=3).contains(&primary.len())
+        && primary.chars().all(|c| c.is_ascii_alphabetic())
+        && parts.all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// Scene description prompts keyed by language
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScenePrompts {
+    templates: HashMap<String, String>,
+}
+
+impl Default for ScenePrompts {
+    fn default() -> Self {
+        let templates = [
+            (DEFAULT_LANGUAGE, ENGLISH_SCENE_PROMPT),
+            ("zh", CHINESE_SCENE_PROMPT),
+        ]
+        .into_iter()
+        .map(|(lang, prompt)| (lang.to_string(), prompt.to_string()))
+        .collect();
+        Self { templates }
+    }
+}
+
+impl ScenePrompts {
+    /// Returns the default prompts with `overrides` added or replacing them
+    pub fn with_overrides(overrides: &HashMap<String, String>) -> Self {
+        let mut prompts = Self::default();
+        for (lang, prompt) in overrides {
+            prompts
+                .templates
+                .insert(normalize_language(lang), prompt.trim().to_string());
+        }
+        prompts
+    }
+
+    /// Returns the prompt for `lang`
+    ///
+    /// Tries the full tag, then its primary language (`zh-cn` falls back to
+    /// `zh`), then English.
+    pub fn for_language(&self, lang: &str) -> &str {
+        let lang = normalize_language(lang);
+        let primary = lang.split('-').next().unwrap_or_default();
+        [lang.as_str(), primary, DEFAULT_LANGUAGE]
+            .into_iter()
+            .find_map(|key| self.templates.get(key))
+            .map(String::as_str)
+            .unwrap_or(ENGLISH_SCENE_PROMPT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_language() {
+        assert_eq!(detect_language("Understanding async Rust"), "en");
+        assert_eq!(detect_language("深入理解 Rust 异步编程"), "zh");
+        assert_eq!(
+            detect_language("本文介绍 tokio runtime 的 scheduler 和 waker 机制"),
+            "zh"
+        );
+        assert_eq!(
+            detect_language("A long English article that mentions 微信 once in passing"),
+            "en"
+        );
+        assert_eq!(detect_language("1234 !!!"), "en");
+        assert_eq!(detect_language(""), "en");
+    }
+
+    #[test]
+    fn test_is_valid_language() {
+        assert!(is_valid_language("en"));
+        assert!(is_valid_language("zh-CN"));
+        assert!(is_valid_language("zh_Hans"));
+        assert!(!is_valid_language(""));
+        assert!(!is_valid_language("chinese"));
+        assert!(!is_valid_language("zh-"));
+        assert!(!is_valid_language("中文"));
+    }
+
+    #[test]
+    fn test_scene_prompts_lookup_and_overrides() {
+        let defaults = ScenePrompts::default();
+        assert_eq!(defaults.for_language("en"), ENGLISH_SCENE_PROMPT);
+        assert_eq!(defaults.for_language("zh"), CHINESE_SCENE_PROMPT);
+        assert_eq!(defaults.for_language("zh_CN"), CHINESE_SCENE_PROMPT);
+        assert_eq!(defaults.for_language("fr"), ENGLISH_SCENE_PROMPT);
+
+        let overrides = HashMap::from([
+            ("ZH-tw".to_string(), "繁體提示".to_string()),
+            ("ja".to_string(), "日本語のプロンプト".to_string()),
+        ]);
+        let prompts = ScenePrompts::with_overrides(&overrides);
+        assert_eq!(prompts.for_language("zh-TW"), "繁體提示");
+        assert_eq!(prompts.for_language("zh-CN"), CHINESE_SCENE_PROMPT);
+        assert_eq!(prompts.for_language("ja"), "日本語のプロンプト");
+        assert_eq!(prompts.for_language("en"), ENGLISH_SCENE_PROMPT);
+    }
+}
@@ -5,7 +5,7 @@
 //!
 //! ## Features
 //!
-//! - Upload individual markdown files or process directories recursively
+//! - Upload individual markdown files, a chosen set of files, or process directories recursively
 //! - Parse and manage YAML frontmatter to track publication status
 //! - Automatically generate cover images using AI (OpenAI, Gemini) when missing
 //! - Skip already published files in directory processing mode
@@ -25,53 +25,184 @@
 //!     uploader.upload_file("article.md", true).await?;
 //!
 //!     // Process a directory
-//!     uploader.process_directory("./articles").await?;
+//!     let report = uploader.process_directory("./articles").await?;
+//!     println!("{}", report.summary());
+//!
+//!     // Upload a set of files chosen by the caller
+//!     let files = vec!["a.md".into(), "b.md".into()];
+//!     let report = uploader.upload_files(&files, false).await?;
+//!     println!("{}", report.summary());
 //!
 //!     Ok(())
 //! }
 //! ```
 
+pub mod backup;
+pub mod budget;
+pub mod build_info;
 pub mod cli;
+pub mod content_check;
+pub mod cover_format;
+pub mod cover_optimize;
+pub mod cover_provenance;
+pub mod cover_template;
+pub mod dry_run;
 pub mod error;
+pub mod exit_code;
+pub mod hooks;
+pub mod html_dump;
+pub mod http_trace;
+pub mod inline_images;
+pub mod language;
+pub mod lint;
+pub mod lock;
+pub mod manifest;
 pub mod markdown;
 pub mod models;
+pub mod normalize;
+pub mod obsidian;
 pub mod openai;
 pub mod output;
+pub mod prompt;
+pub mod provenance;
 pub mod providers;
+pub mod rate_limit;
+pub mod report;
+pub mod retry_queue;
+pub mod scene_cache;
+pub mod schedule;
+pub mod slug;
+pub mod state;
+pub mod status;
+pub mod validation;
 pub mod wechat;
+pub mod wechat_api;
 
 pub use error::{Error, Result};
-pub use models::{Config, Frontmatter, AiProvider};
+pub use exit_code::ExitCode;
+pub use models::{Config, Frontmatter, AiProvider, PublicationStatus};
+pub use report::{FileOutcome, UploadReport};
 // Core uploader functionality is implemented directly in this module
 
-use std::path::Path;
+use output::OutputFormatter;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::OnceCell;
 
 /// Core uploader functionality combining WeChat and AI provider clients
 pub struct WxUploader {
-    wechat_client: wechat::WeChatClient,
+    wechat_client: LazyWeChatClient,
     ai_client: Option<providers::UniversalAIClient>,
+    /// Draft creation limits per account, shared by all clients of the run
+    rate_limiters: rate_limit::RateLimiters,
+    /// Backup set of the run, shared by all uploads
+    backup: Option<Arc<backup::Backup>>,
+    /// Body transforms added with [`WxUploader::with_transforms`]
+    transforms: markdown::TransformPipeline,
+    /// Pause after each draft, shared by all uploads of the run
+    pacer: rate_limit::UploadPacer,
     config: Config,
 }
 
 impl WxUploader {
     /// Creates a new uploader instance with the provided configuration
     ///
+    /// The WeChat client is created on first use, so operations that never
+    /// reach WeChat work without valid credentials. Use
+    /// [`WxUploader::check_auth`] to verify the credentials up front.
+    ///
     /// # Arguments
     ///
     /// * `config` - Configuration containing API keys and settings
     ///
     /// # Errors
     ///
-    /// Returns an error if WeChat client initialization fails
+    /// Currently infallible; WeChat credential errors surface on first use
     pub async fn new(config: Config) -> Result<Self> {
-        let wechat_client = wechat::WeChatClient::new(
-            config.wechat_account.app_id.clone(),
-            config.wechat_account.app_secret.clone(),
-        )
-        .await
-        .map_err(|e| Error::wechat(e.to_string()))?;
+        let rate_limiters = rate_limit::RateLimiters::new(config.wechat_rpm)
+            .with_retry_policy(config.retry_policy());
+        let wechat_client = LazyWeChatClient::new(
+            config.wechat_account.clone(),
+            rate_limiters.for_account(&config.wechat_account.name),
+        );
+        let ai_client = Self::create_ai_client(&config);
+        let backup = Self::create_backup(&config);
+
+        Ok(Self {
+            wechat_client,
+            ai_client,
+            rate_limiters,
+            backup,
+            transforms: markdown::TransformPipeline::default(),
+            pacer: config.upload_pacer(),
+            config,
+        })
+    }
+
+    /// Adds custom body transforms, run on the body of each uploaded article
+    ///
+    /// Without a `transforms` list in the configuration or frontmatter, they
+    /// run in the given order after the built-in transforms; a list selects
+    /// them by [`markdown::BodyTransform::name`], which takes precedence over
+    /// a built-in of the same name.
+    pub fn with_transforms(mut self, transforms: Vec<Box<dyn markdown::BodyTransform>>) -> Self {
+        for transform in transforms {
+            self.transforms.push(Arc::from(transform));
+        }
+        self
+    }
 
-        let ai_client = config.ai_provider.as_ref().map(|provider| {
+    /// Re-reads the configuration file and swaps in the new configuration
+    ///
+    /// New accounts, keys and settings take effect for the next upload: the
+    /// WeChat client is recreated on first use and the AI client is rebuilt,
+    /// with a fresh image budget. See [`Config::reload`] for which options
+    /// are kept. On error nothing is replaced.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configuration was not loaded from a file, the
+    /// file is invalid, or the current account was removed from it
+    pub async fn reload_config(&mut self) -> Result<()> {
+        let config = self.config.reload().await?;
+        let ai_client = Self::create_ai_client(&config);
+
+        // Keep the pacing of earlier uploads unless the rate or retries changed
+        if config.wechat_rpm != self.rate_limiters.rpm()
+            || config.retry_policy() != self.rate_limiters.retry_policy()
+        {
+            self.rate_limiters = rate_limit::RateLimiters::new(config.wechat_rpm)
+                .with_retry_policy(config.retry_policy());
+        }
+        self.wechat_client = LazyWeChatClient::new(
+            config.wechat_account.clone(),
+            self.rate_limiters.for_account(&config.wechat_account.name),
+        );
+        self.ai_client = ai_client;
+        // Files already backed up in this run stay backed up
+        if config.backup != self.backup.is_some() {
+            self.backup = Self::create_backup(&config);
+        }
+        if config.upload_delay_ms != self.config.upload_delay_ms {
+            self.pacer = config.upload_pacer();
+        }
+        self.config = config;
+        Ok(())
+    }
+
+    /// Creates the backup set of the run in the current directory, if backups are on
+    fn create_backup(config: &Config) -> Option<Arc<backup::Backup>> {
+        if !config.backup {
+            return None;
+        }
+        let work_dir = std::env::current_dir().ok()?;
+        Some(Arc::new(backup::Backup::starting_now(work_dir, config.backup_keep)))
+    }
+
+    /// Creates the AI client for cover generation, if a provider is configured
+    pub(crate) fn create_ai_client(config: &Config) -> Option<providers::UniversalAIClient> {
+        config.ai_provider.as_ref().map(|provider| {
             let provider_config = match provider {
                 models::AiProvider::OpenAI { api_key, base_url } => {
                     providers::ProviderConfig::OpenAI {
@@ -87,16 +218,26 @@ impl WxUploader {
                 }
             };
             providers::UniversalAIClient::new(provider_config, None)
-        });
-
-        Ok(Self {
-            wechat_client,
-            ai_client,
-            config,
+                .with_ai_scene_text(!config.no_ai_text)
+                .with_scene_streaming(config.stream_scene && config.verbose)
+                .with_scene_prompts(language::ScenePrompts::with_overrides(&config.scene_prompts))
+                .with_fallback_scene(config.fallback_scene.clone())
+                .with_scene_cache(
+                    (!config.no_cache)
+                        .then(|| scene_cache::SceneCache::new(config.cache_dir_path())),
+                )
+                .with_cover_encoding(config.cover_encoding())
+                .with_embed_provenance(config.embed_provenance)
+                .with_http_tracer(config.http_tracer())
+                .with_prices(config.ai_prices)
+                .with_budget(config.ai_budget)
+                .with_retry_policy(config.retry_policy())
         })
     }
 
-    /// Switches to a different WeChat account and reinitializes the client
+    /// Switches to a different WeChat account
+    ///
+    /// The client for the new account is created on first use.
     ///
     /// # Arguments
     ///
@@ -104,24 +245,110 @@ impl WxUploader {
     ///
     /// # Errors
     ///
-    /// Returns an error if:
-    /// - The account is not found in configuration
-    /// - WeChat client reinitialization fails
+    /// Returns an error if the account is not found in configuration
     pub async fn switch_account(&mut self, account_name: &str) -> Result<()> {
         // Switch the account in config
         self.config.switch_account(account_name)?;
-        
-        // Reinitialize WeChat client with new credentials
-        self.wechat_client = wechat::WeChatClient::new(
-            self.config.wechat_account.app_id.clone(),
-            self.config.wechat_account.app_secret.clone(),
-        )
-        .await
-        .map_err(|e| Error::wechat(e.to_string()))?;
-        
+
+        // Drop the old client; the new credentials are used on first use
+        self.wechat_client = LazyWeChatClient::new(
+            self.config.wechat_account.clone(),
+            self.rate_limiters.for_account(account_name),
+        );
+
         Ok(())
     }
-    
+
+    /// Creates the WeChat client of the current account and fetches an access token
+    ///
+    /// # Errors
+    ///
+    /// Returns an authentication error if the credentials are malformed or
+    /// rejected by WeChat
+    pub async fn check_auth(&self) -> Result<()> {
+        self.wechat_client.get().await?.refresh_token().await?;
+        Ok(())
+    }
+
+    /// Checks that the configured AI provider accepts the key and serves the models
+    ///
+    /// See [`providers::UniversalAIClient::check_connectivity`]. No file is read
+    /// or written.
+    ///
+    /// # Errors
+    ///
+    /// Returns a configuration error if no AI provider is configured
+    pub async fn check_ai(&self, include_image: bool) -> Result<Vec<providers::AiCheck>> {
+        let client = self.ai_client.as_ref().ok_or_else(|| {
+            Error::config(
+                "no AI provider is configured; set OPENAI_API_KEY or GEMINI_API_KEY, \
+                 or ai_provider in the configuration file",
+            )
+        })?;
+        Ok(client.check_connectivity(include_image).await)
+    }
+
+    /// Returns the AI client used for cover generation, if a provider is configured
+    pub fn ai_client(&self) -> Option<&providers::UniversalAIClient> {
+        self.ai_client.as_ref()
+    }
+
+    /// Creates the WeChat client for an account
+    ///
+    /// Accounts with a `wechat_base_url` override use the crate's own API client
+    /// so requests can be routed to a mock server or gateway; all other accounts
+    /// use the `wechat_pub_rs` client against the official endpoints. An account
+    /// `default_cover` becomes the thumbnail of articles with `cover: none`.
+    /// With a `rate_limiter`, draft creation is paced and retried on 45009.
+    /// Sandbox accounts leave out what they do not support.
+    async fn create_wechat_client(
+        account: &models::WeChatAccount,
+        rate_limiter: Option<Arc<rate_limit::RateLimiter>>,
+    ) -> Result<Box<dyn wechat::WeChatUploader>> {
+        let client: Box<dyn wechat::WeChatUploader> = match &account.wechat_base_url {
+            Some(base_url) => Box::new(wechat_api::WeChatApiClient::new(
+                account.app_id.clone(),
+                account.app_secret.clone(),
+                base_url,
+            )?),
+            // Construction fails on malformed credentials, before any request is made
+            None => Box::new(wechat::DefaultEndpointUploader::new(
+                Box::new(
+                    wechat::WeChatClient::new(account.app_id.clone(), account.app_secret.clone())
+                        .await
+                        .map_err(|e| {
+                            Error::auth_with_source(
+                                format!("invalid credentials for account '{}': {}", account.name, e),
+                                e,
+                            )
+                        })?,
+                ),
+                wechat_api::WeChatApiClient::new(
+                    account.app_id.clone(),
+                    account.app_secret.clone(),
+                    wechat_api::DEFAULT_WECHAT_BASE_URL,
+                )?,
+            )),
+        };
+
+        let client: Box<dyn wechat::WeChatUploader> = match rate_limiter {
+            Some(limiter) => Box::new(rate_limit::RateLimitedUploader::new(client, limiter)),
+            None => client,
+        };
+        let client: Box<dyn wechat::WeChatUploader> = match account.kind {
+            models::AccountKind::Sandbox => {
+                Box::new(wechat::SandboxUploader::new(client, account.kind))
+            }
+            models::AccountKind::Normal => client,
+        };
+        Ok(match &account.default_cover {
+            Some(default_cover) => {
+                Box::new(wechat::DefaultCoverUploader::new(client, default_cover))
+            }
+            None => client,
+        })
+    }
+
     /// Gets the current WeChat account information
     pub fn current_account(&self) -> &models::WeChatAccount {
         &self.config.wechat_account
@@ -138,12 +365,26 @@ impl WxUploader {
     ///
     /// # Errors
     ///
-    /// Returns an error if the token refresh fails
+    /// Returns an error if the WeChat client cannot be created or the token refresh fails
     pub async fn refresh_token(&self) -> Result<String> {
-        self.wechat_client
-            .refresh_token()
-            .await
-            .map_err(|e| Error::wechat(e.to_string()))
+        self.wechat_client.get().await?.refresh_token().await
+    }
+
+    /// Lists the recent drafts of the current account, indexed by title
+    ///
+    /// `--reconcile` matches files against this index before uploading them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the WeChat client cannot be created or the drafts cannot be listed
+    pub async fn draft_index(&self) -> Result<wechat::DraftIndex> {
+        let drafts = self
+            .wechat_client
+            .get()
+            .await?
+            .list_recent_drafts(wechat::RECONCILE_DRAFT_LIMIT)
+            .await?;
+        Ok(wechat::DraftIndex::new(&drafts))
     }
 
     /// Uploads a single markdown file to WeChat
@@ -153,23 +394,142 @@ impl WxUploader {
     /// * `path` - Path to the markdown file
     /// * `force` - If true, uploads regardless of published status
     ///
+    /// If the file's frontmatter names an `account`, it is uploaded with that
+    /// account instead of the current one. Files that target several accounts
+    /// must be uploaded with [`WxUploader::upload_file_to_accounts`], which
+    /// also reports the warnings of the upload.
+    ///
     /// # Errors
     ///
-    /// Returns an error if the upload process fails
-    pub async fn upload_file<P: AsRef<Path>>(&self, path: P, force: bool) -> Result<()> {
-        wechat::upload_file(
-            &self.wechat_client,
-            self.ai_client.as_ref(),
-            path.as_ref(),
-            force,
-            self.config.verbose,
-        )
-        .await
+    /// Returns an error if the frontmatter names an unknown account, the file
+    /// targets several accounts, or the upload process fails
+    pub async fn upload_file<P: AsRef<Path>>(&self, path: P, force: bool) -> Result<FileOutcome> {
+        let path = path.as_ref();
+        let targets = self.file_targets(path).await?;
+        if targets.len() > 1 {
+            return Err(Error::config(format!(
+                "{} targets {} accounts; upload it with upload_file_to_accounts",
+                path.display(),
+                targets.len()
+            )));
+        }
+        let warnings = report::WarningLog::default();
+        self.upload_file_with(path, force, targets.first().map(|s| s.as_str()), &warnings)
+            .await
+    }
+
+    /// Uploads a single markdown file to every account it targets
+    ///
+    /// Like [`WxUploader::upload_file`], but a file whose frontmatter (or
+    /// `--account @group`) names several accounts gets one draft per account.
+    /// The report names the account of every result when more than one
+    /// account was used. The `post_run` hook runs once the report is complete.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frontmatter names an unknown account or group,
+    /// or if the upload of a file with a single target fails
+    pub async fn upload_file_to_accounts<P: AsRef<Path>>(
+        &self,
+        path: P,
+        force: bool,
+    ) -> Result<UploadReport> {
+        let path = path.as_ref();
+        let targets = self.file_targets(path).await?;
+        self.check_ai_cost(&[path.to_path_buf()], force, &self.options_for(&targets))
+            .await?;
+        if targets.len() <= 1 {
+            let warnings = report::WarningLog::default();
+            self.check_extension(path, &warnings);
+            let outcome = self
+                .upload_file_with(path, force, targets.first().map(|s| s.as_str()), &warnings)
+                .await?;
+            let mut report = UploadReport::new();
+            report.record(path, outcome);
+            report.warnings = warnings.take();
+            self.run_post_run_hook(&report).await;
+            return Ok(report);
+        }
+
+        let options = self.options_for(&targets);
+        self.check_extension(path, &options.warnings);
+        let mut clients = HashMap::new();
+        let mut report = self
+            .upload_group_to_accounts(&mut clients, &targets, &[path.to_path_buf()], force, &options)
+            .await;
+        report.warnings = options.warnings.take();
+
+        if !self.config.updates_status() {
+            self.record_state(&report).await;
+        }
+        self.run_post_run_hook(&report).await;
+
+        Ok(report)
+    }
+
+    /// Warns if `path` was given explicitly but a directory run would skip it
+    fn check_extension(&self, path: &Path, warnings: &report::WarningLog) {
+        if let Some(warning) =
+            wechat::unrecognized_extension_warning(path, &self.config.markdown_extensions)
+        {
+            warnings.warn_file(path, &warning);
+        }
+    }
+
+    /// Returns the accounts a file is uploaded to; empty for the current account
+    async fn file_targets(&self, path: &Path) -> Result<Vec<String>> {
+        let groups = wechat::group_files_by_account(&[path.to_path_buf()], &self.config).await?;
+        Ok(groups.into_iter().next().map(|g| g.accounts).unwrap_or_default())
+    }
+
+    /// Uploads a single file with the named account, or the current one,
+    /// recording its warnings in `warnings`
+    async fn upload_file_with(
+        &self,
+        path: &Path,
+        force: bool,
+        account: Option<&str>,
+        warnings: &report::WarningLog,
+    ) -> Result<FileOutcome> {
+        let accounts: Vec<_> = account.map(str::to_string).into_iter().collect();
+        let mut options = self.options_for(&accounts);
+        options.warnings = warnings.clone();
+
+        let account_client = match account {
+            Some(name) => Some(self.client_for_account(name).await?),
+            None => None,
+        };
+        let client = account_client
+            .as_deref()
+            .unwrap_or(&self.wechat_client);
+
+        let existing = self.existing_drafts(client, &[path.to_path_buf()], force, &options).await;
+        if let Some((_, draft_id)) = existing.first() {
+            return wechat::record_existing_draft(path, draft_id, &options).await;
+        }
+
+        let outcome =
+            wechat::upload_file(client, self.ai_client.as_ref(), path, force, &options).await?;
+
+        if !self.config.updates_status() {
+            let mut report = UploadReport::new();
+            report.record(path, outcome.clone());
+            self.record_state(&report).await;
+        }
+
+        Ok(outcome)
     }
 
     /// Processes all markdown files in a directory recursively
     ///
-    /// Files marked as published will be skipped unless forced.
+    /// Collects the markdown files under `dir`, keeps those in the `--only`
+    /// state if one is set, and uploads them with
+    /// [`WxUploader::upload_files`]. Published files are skipped. The run holds
+    /// the [`lock`] of `dir` until it finishes.
+    ///
+    /// Invalid frontmatter values of all files, e.g. unknown themes, are
+    /// reported up front, grouped by field. The files are then uploaded and
+    /// fail one by one, unless `--fail-fast` stops the run before any upload.
     ///
     /// # Arguments
     ///
@@ -177,15 +537,548 @@ impl WxUploader {
     ///
     /// # Errors
     ///
-    /// Returns an error if directory processing fails
-    pub async fn process_directory<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
-        wechat::process_directory(
-            &self.wechat_client,
-            self.ai_client.as_ref(),
+    /// Returns an error before anything is uploaded if another run over `dir`
+    /// still holds its lock after `--lock-wait`, if a file's frontmatter
+    /// names an account or group that is not configured, or if a file has an
+    /// invalid frontmatter value under `--fail-fast`
+    pub async fn process_directory<P: AsRef<Path>>(&self, dir: P) -> Result<UploadReport> {
+        let _lock = lock::RunLock::acquire_with(
             dir.as_ref(),
-            self.config.verbose,
+            std::time::Duration::from_secs(self.config.lock_wait),
+            self.config.force_lock,
         )
-        .await
+        .await?;
+        let options = wechat::ProcessOptions::from(&self.config);
+        let files = wechat::collect_directory_files(dir.as_ref(), self.config.only, &options).await;
+
+        let issues = validation::collect_frontmatter_issues(&files, &options.parse_options()).await;
+        if !issues.is_empty() {
+            issues.print();
+            if self.config.fail_fast {
+                return Err(Error::config(format!(
+                    "{} file(s) have invalid frontmatter; nothing was uploaded (--fail-fast)",
+                    issues.file_count()
+                )));
+            }
+        }
+
+        if files.is_empty() {
+            let message = match self.config.only {
+                Some(state) => format!("No {} markdown files found in directory", state),
+                None => "No markdown files found in directory".to_string(),
+            };
+            output::FORMATTER.print_info(&message);
+        }
+        self.upload_files(&files, false).await
+    }
+
+    /// Uploads the given markdown files as one batch
+    ///
+    /// Files marked as published or skip will be skipped unless `force` is set. Files
+    /// whose frontmatter names an `account` are uploaded with that account,
+    /// and files naming several accounts or an `@group` get one draft per
+    /// account. Files are grouped by their target accounts and each WeChat
+    /// client is created only once per batch. The `post_run` hook runs at the
+    /// end, also for an empty batch.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - Markdown files to upload, in upload order
+    /// * `force` - If true, uploads regardless of published status
+    ///
+    /// # Returns
+    ///
+    /// A report with the outcome of every file; individual file failures are
+    /// recorded there rather than aborting the batch, unless `--fail-fast`
+    /// stops it at the first failure. When more than one account is used,
+    /// every result names its account.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error before anything is uploaded if a file's frontmatter
+    /// names an account or group that is not configured, or if the estimated
+    /// AI cost is over `confirm_ai_cost_above` and the run is not confirmed
+    pub async fn upload_files(&self, paths: &[PathBuf], force: bool) -> Result<UploadReport> {
+        self.upload_batch(paths, force, None).await
+    }
+
+    /// Uploads the articles listed in a manifest
+    ///
+    /// Like [`WxUploader::upload_files`] without `force`, so published files
+    /// and files marked skip are still skipped. The account of an entry
+    /// replaces the frontmatter `account`/`accounts` and `--account`, and its
+    /// theme replaces the frontmatter theme for the upload only. Files are
+    /// uploaded in manifest order within each account, and the report lists
+    /// the results in manifest order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error before anything is uploaded if an entry or a file's
+    /// frontmatter names an account or group that is not configured
+    pub async fn upload_manifest(&self, manifest: &manifest::Manifest) -> Result<UploadReport> {
+        self.upload_batch(&manifest.files(), false, Some(manifest)).await
+    }
+
+    /// Re-attempts the uploads queued in a [`retry_queue`] file
+    ///
+    /// Entries are retried one by one with the account and `force` they
+    /// failed with. Entries queued with other options than the current ones
+    /// are left alone. An entry that fails for the `retry_max_attempts`-th
+    /// time is dropped. The queue file is rewritten after each file, so an
+    /// interrupted run keeps what it already settled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the queue file cannot be read, parsed or written;
+    /// failed uploads are recorded in the report instead
+    pub async fn retry_queued(&self, queue_file: &Path) -> Result<retry_queue::RetryReport> {
+        let mut queue = retry_queue::RetryQueue::load(queue_file).await?;
+        let options_hash = retry_queue::options_hash(&self.config);
+        let mut retry = retry_queue::RetryReport::default();
+
+        for entry in queue.entries.clone() {
+            if entry.options_hash != options_hash {
+                retry.held.push(entry);
+                continue;
+            }
+
+            let warnings = report::WarningLog::default();
+            let outcome = match self
+                .upload_file_with(&entry.path, entry.force, entry.account.as_deref(), &warnings)
+                .await
+            {
+                Ok(outcome) => outcome,
+                Err(e) => FileOutcome::failed(&e),
+            };
+            retry.report.warnings.extend(warnings.take());
+            match queue.settle(&entry, &outcome, self.config.retry_max_attempts) {
+                retry_queue::Settled::Cleared => retry.cleared += 1,
+                retry_queue::Settled::Queued => {}
+                retry_queue::Settled::GivenUp(dropped) => retry.given_up.push(dropped),
+            }
+            match &entry.account {
+                Some(account) => retry.report.record_for_account(&entry.path, account, outcome),
+                None => retry.report.record(&entry.path, outcome),
+            }
+            queue.save(queue_file).await?;
+        }
+
+        retry.remaining = queue.entries.len();
+        Ok(retry)
+    }
+
+    /// Uploads files as one batch, with the overrides and order of `manifest` if given
+    async fn upload_batch(
+        &self,
+        paths: &[PathBuf],
+        force: bool,
+        manifest: Option<&manifest::Manifest>,
+    ) -> Result<UploadReport> {
+        if paths.is_empty() {
+            let report = UploadReport::new();
+            self.run_post_run_hook(&report).await;
+            return Ok(report);
+        }
+
+        let options = self.options_for(&[]);
+        let account_overrides = manifest.map(|m| m.account_overrides()).unwrap_or_default();
+        let theme_overrides = manifest.map(|m| m.theme_overrides()).unwrap_or_default();
+        let groups =
+            wechat::group_files_by_account_with(paths, &self.config, &account_overrides).await?;
+        self.check_ai_cost(paths, force, &options).await?;
+
+        // Assign slugs before any upload so collisions are resolved across the run
+        if options.auto_slug && options.write_back {
+            let assigned = slug::assign_slugs(paths, &options).await;
+            if assigned > 0 && !options.verbose {
+                output::FORMATTER.print_info(&format!("assigned slugs to {} file(s)", assigned));
+            }
+        }
+
+        let multi_account = groups.len() > 1 || groups.iter().any(|g| g.accounts.len() > 1);
+
+        let mut clients = HashMap::new();
+        let mut report = UploadReport::new();
+        for group in groups {
+            let mut options = self.options_for(&group.accounts);
+            options.theme_overrides = theme_overrides.clone();
+            let mut group_report = match group.accounts.as_slice() {
+                [] => {
+                    let mut group_report = self
+                        .upload_reconciled(&self.wechat_client, &group.files, force, &options)
+                        .await;
+                    group_report.tag_account(&self.config.wechat_account.name);
+                    group_report
+                }
+                [name] => {
+                    self.announce_group(&group.files, std::slice::from_ref(name), &options);
+
+                    let mut group_report = UploadReport::new();
+                    match self.cache_client(&mut clients, name).await {
+                        Ok(()) => group_report.merge(
+                            self.upload_reconciled(
+                                self.cached_client(&clients, name),
+                                &group.files,
+                                force,
+                                &options,
+                            )
+                            .await,
+                        ),
+                        Err(e) => {
+                            for path in &group.files {
+                                group_report.record_failed(path, &e);
+                            }
+                        }
+                    }
+                    group_report.tag_account(name);
+                    group_report
+                }
+                names => {
+                    self.announce_group(&group.files, names, &options);
+                    if self.config.reconcile {
+                        output::FORMATTER.print_warning(&format!(
+                            "--reconcile does not apply to {} file(s) with several accounts",
+                            group.files.len()
+                        ));
+                    }
+                    self.upload_group_to_accounts(&mut clients, names, &group.files, force, &options)
+                        .await
+                }
+            };
+
+            group_report.warnings.extend(options.warnings.take());
+            if !multi_account {
+                for file in &mut group_report.files {
+                    file.account = None;
+                }
+            }
+            report.merge(group_report);
+            if options.fail_fast && report.failed() > 0 {
+                break;
+            }
+        }
+
+        // Groups are uploaded one after another; a manifest run reports in its own order
+        if manifest.is_some() {
+            report
+                .files
+                .sort_by_key(|file| paths.iter().position(|path| *path == file.path));
+        }
+
+        if !self.config.updates_status() {
+            self.record_state(&report).await;
+        }
+        self.run_post_run_hook(&report).await;
+
+        Ok(report)
+    }
+
+    /// Prints the estimated AI cost of uploading `paths` and, above
+    /// `confirm_ai_cost_above`, asks before the run starts
+    ///
+    /// # Errors
+    ///
+    /// Returns a configuration error if the estimate is over the threshold and
+    /// the run is neither confirmed nor started with `--accept-ai-cost`;
+    /// `--yes` declines it and under `--no-input` it is not asked
+    async fn check_ai_cost(
+        &self,
+        paths: &[PathBuf],
+        force: bool,
+        options: &wechat::ProcessOptions,
+    ) -> Result<()> {
+        let Some(ai_client) = &self.ai_client else {
+            return Ok(());
+        };
+        let estimate = wechat::estimate_ai_cost(paths, force, ai_client, options).await;
+        if estimate.images == 0 {
+            return Ok(());
+        }
+        output::FORMATTER.print_info(&estimate.summary());
+
+        let Some(threshold) = self.config.confirm_ai_cost_above else {
+            return Ok(());
+        };
+        if estimate.cost <= threshold {
+            return Ok(());
+        }
+        let question = format!(
+            "The estimate is over confirm_ai_cost_above (${:.2}). Continue?",
+            threshold
+        );
+        if self.config.accept_ai_cost || self.config.prompt.confirm(&question, false).await? {
+            return Ok(());
+        }
+        Err(Error::config(format!(
+            "estimated AI cost of about ${:.2} exceeds confirm_ai_cost_above (${:.2}); \
+             pass --accept-ai-cost to run anyway",
+            estimate.cost, threshold
+        )))
+    }
+
+    /// Returns the processing options for files uploaded with `accounts`
+    ///
+    /// The default cover comes from the first of the accounts that has one, or
+    /// from the current account if `accounts` is empty.
+    fn options_for(&self, accounts: &[String]) -> wechat::ProcessOptions {
+        let mut options = wechat::ProcessOptions::from(&self.config);
+        options.backup = self.backup.clone();
+        options.custom_transforms = self.transforms.clone();
+        options.pacer = self.pacer.clone();
+        if !accounts.is_empty() {
+            options.default_cover = accounts
+                .iter()
+                .filter_map(|name| self.config.account(name).ok())
+                .find_map(|account| account.default_cover.clone());
+        }
+        options
+    }
+
+    /// Prints which accounts a group of files is uploaded with
+    fn announce_group(&self, files: &[PathBuf], accounts: &[String], options: &wechat::ProcessOptions) {
+        let names = accounts
+            .iter()
+            .map(|name| format!("'{}'", name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let label = if accounts.len() == 1 { "account" } else { "accounts" };
+
+        if options.verbose {
+            tracing::info!("Uploading {} file(s) with {} {}", files.len(), label, names);
+        } else {
+            output::FORMATTER.print_info(&format!(
+                "using {} {} for {} file(s)",
+                label,
+                names,
+                files.len()
+            ));
+        }
+    }
+
+    /// Uploads files to several accounts, recording accounts whose client cannot be created as failed
+    async fn upload_group_to_accounts(
+        &self,
+        clients: &mut HashMap<String, Box<dyn wechat::WeChatUploader>>,
+        accounts: &[String],
+        files: &[PathBuf],
+        force: bool,
+        options: &wechat::ProcessOptions,
+    ) -> UploadReport {
+        let mut report = UploadReport::new();
+        let mut available = Vec::new();
+        for name in accounts {
+            match self.cache_client(clients, name).await {
+                Ok(()) => available.push(name.as_str()),
+                Err(e) => {
+                    for path in files {
+                        report.record_for_account(path, name, FileOutcome::failed(&e));
+                    }
+                }
+            }
+        }
+
+        let clients: Vec<_> = available
+            .into_iter()
+            .map(|name| (name, self.cached_client(clients, name)))
+            .collect();
+        report.merge(
+            wechat::upload_files_to_accounts(&clients, self.ai_client.as_ref(), files, force, options)
+                .await,
+        );
+        report
+    }
+
+    /// Uploads files with a single client, first skipping those that already have a draft
+    ///
+    /// Without `--reconcile` this is [`wechat::upload_files_with`]. Files
+    /// skipped for an existing draft come first in the report.
+    async fn upload_reconciled(
+        &self,
+        client: &dyn wechat::WeChatUploader,
+        files: &[PathBuf],
+        force: bool,
+        options: &wechat::ProcessOptions,
+    ) -> UploadReport {
+        let existing = self.existing_drafts(client, files, force, options).await;
+
+        let mut report = UploadReport::new();
+        for (path, draft_id) in &existing {
+            match wechat::record_existing_draft(path, draft_id, options).await {
+                Ok(outcome) => report.record(path, outcome),
+                Err(e) => report.record_failed(path, &e),
+            }
+        }
+
+        let remaining: Vec<_> = files
+            .iter()
+            .filter(|file| !existing.iter().any(|(path, _)| path == *file))
+            .cloned()
+            .collect();
+        report.merge(
+            wechat::upload_files_with(client, self.ai_client.as_ref(), &remaining, force, options)
+                .await,
+        );
+        report
+    }
+
+    /// Returns the files that already have a draft with `client` under `--reconcile`
+    ///
+    /// If the drafts cannot be listed, a warning is printed and no file
+    /// matches. Without status updates the drafts found are recorded in the
+    /// state file, since they are not written to the frontmatter.
+    async fn existing_drafts(
+        &self,
+        client: &dyn wechat::WeChatUploader,
+        files: &[PathBuf],
+        force: bool,
+        options: &wechat::ProcessOptions,
+    ) -> Vec<(PathBuf, String)> {
+        if !self.config.reconcile || files.is_empty() {
+            return Vec::new();
+        }
+
+        let index = match client.list_recent_drafts(wechat::RECONCILE_DRAFT_LIMIT).await {
+            Ok(drafts) => wechat::DraftIndex::new(&drafts),
+            Err(e) => {
+                output::FORMATTER.print_warning(&format!(
+                    "could not list drafts to reconcile, uploading without: {}",
+                    e
+                ));
+                return Vec::new();
+            }
+        };
+        let existing = wechat::match_existing_drafts(&index, files, force, options).await;
+
+        if !existing.is_empty() && !self.config.updates_status() {
+            let state_file = self.config.state_file_path();
+            let result = async {
+                let mut state = state::UploadState::load(&state_file).await?;
+                for (path, draft_id) in &existing {
+                    state.record_upload(path, None, draft_id);
+                }
+                state.save(&state_file).await
+            }
+            .await;
+            if let Err(e) = result {
+                output::FORMATTER.print_warning(&format!(
+                    "failed to record upload state in {}: {}",
+                    state_file.display(),
+                    e
+                ));
+            }
+        }
+        existing
+    }
+
+    /// Runs the `post_run` hook with the outcome of the run
+    ///
+    /// The uploads already happened, so a failing hook is only a warning.
+    async fn run_post_run_hook(&self, report: &UploadReport) {
+        if let Err(e) = self.config.hooks.post_run(report, self.config.verbose).await {
+            output::FORMATTER.print_warning(&e.to_string());
+        }
+    }
+
+    /// Records uploads in the state file when they are not written to the files
+    ///
+    /// The uploads already happened, so a state file error is only a warning.
+    async fn record_state(&self, report: &UploadReport) {
+        let state_file = self.config.state_file_path();
+        if let Err(e) = state::record_uploads(&state_file, report).await {
+            output::FORMATTER.print_warning(&format!(
+                "failed to record upload state in {}: {}",
+                state_file.display(),
+                e
+            ));
+        }
+    }
+
+    /// Creates a WeChat client for a configured account other than the current one
+    async fn client_for_account(&self, name: &str) -> Result<Box<dyn wechat::WeChatUploader>> {
+        let account = self.config.account(name)?;
+        Self::create_wechat_client(account, self.rate_limiters.for_account(name)).await
+    }
+
+    /// Creates the client for an account unless it is current or already cached
+    async fn cache_client(
+        &self,
+        clients: &mut HashMap<String, Box<dyn wechat::WeChatUploader>>,
+        name: &str,
+    ) -> Result<()> {
+        if name != self.config.wechat_account.name && !clients.contains_key(name) {
+            let client = self.client_for_account(name).await?;
+            clients.insert(name.to_string(), client);
+        }
+        Ok(())
+    }
+
+    /// Returns the client for an account prepared by [`Self::cache_client`]
+    fn cached_client<'a>(
+        &'a self,
+        clients: &'a HashMap<String, Box<dyn wechat::WeChatUploader>>,
+        name: &str,
+    ) -> &'a dyn wechat::WeChatUploader {
+        clients
+            .get(name)
+            .map(|client| client.as_ref())
+            .unwrap_or(&self.wechat_client)
+    }
+}
+
+/// WeChat client of an account, created on first use
+///
+/// A run in which every file is skipped never creates one, so it needs no
+/// valid credentials. A failed creation is retried on the next use.
+struct LazyWeChatClient {
+    account: models::WeChatAccount,
+    rate_limiter: Option<Arc<rate_limit::RateLimiter>>,
+    client: OnceCell<Box<dyn wechat::WeChatUploader>>,
+}
+
+impl LazyWeChatClient {
+    fn new(
+        account: models::WeChatAccount,
+        rate_limiter: Option<Arc<rate_limit::RateLimiter>>,
+    ) -> Self {
+        Self {
+            account,
+            rate_limiter,
+            client: OnceCell::new(),
+        }
+    }
+
+    /// Returns the client, creating it on first use
+    async fn get(&self) -> Result<&dyn wechat::WeChatUploader> {
+        let client = self
+            .client
+            .get_or_try_init(|| {
+                WxUploader::create_wechat_client(&self.account, self.rate_limiter.clone())
+            })
+            .await?;
+        Ok(client.as_ref())
+    }
+}
+
+#[async_trait::async_trait]
+impl wechat::WeChatUploader for LazyWeChatClient {
+    async fn upload(&self, file_path: &str, options: &wechat::ArticleOptions) -> Result<String> {
+        self.get().await?.upload(file_path, options).await
+    }
+
+    async fn refresh_token(&self) -> Result<String> {
+        self.get().await?.refresh_token().await
+    }
+
+    async fn content_checker(&self) -> Result<Box<dyn content_check::ContentChecker + '_>> {
+        self.get().await?.content_checker().await
+    }
+
+    async fn list_recent_drafts(&self, limit: u32) -> Result<Vec<wechat::DraftSummary>> {
+        self.get().await?.list_recent_drafts(limit).await
+    }
+
+    async fn upload_body_image(&self, image_path: &Path) -> Result<String> {
+        self.get().await?.upload_body_image(image_path).await
     }
 }
 
@@ -202,13 +1095,53 @@ mod tests {
             false,
         );
 
-        // This would fail in real scenario without valid WeChat credentials,
-        // but tests the structure
-        let result = WxUploader::new(config).await;
-        // We expect this to fail with network/auth error, not a compilation error
-        assert!(result.is_err());
+        // The WeChat client is only created when it is first needed
+        let uploader = WxUploader::new(config).await.unwrap();
+        assert!(uploader.ai_client.is_none());
+
+        // Malformed credentials fail before any request is made
+        let err = uploader.check_auth().await.unwrap_err();
+        assert!(err.is_auth());
+        assert!(err.to_string().contains("account 'main'"));
     }
     
+    #[tokio::test]
+    async fn test_reload_config_swaps_clients() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yaml");
+        let accounts = "accounts:\n  main: { name: main, app_id: a, app_secret: s }\n";
+        std::fs::write(
+            &path,
+            format!("{}ai_provider: {{ provider: gemini, api_key: g }}\n", accounts),
+        )
+        .unwrap();
+
+        let config = Config::from_file(&path, None).await.unwrap();
+        let mut uploader = WxUploader::new(config).await.unwrap();
+        assert!(matches!(uploader.config.ai_provider, Some(AiProvider::Gemini { .. })));
+
+        std::fs::write(
+            &path,
+            format!(
+                "{}  blog: {{ name: blog, app_id: b, app_secret: t }}\n\
+                 ai_provider: {{ provider: openai, api_key: o }}\n",
+                accounts
+            ),
+        )
+        .unwrap();
+        uploader.reload_config().await.unwrap();
+        assert_eq!(uploader.list_accounts().len(), 2);
+        assert_eq!(uploader.current_account().name, "main");
+        assert!(matches!(uploader.config.ai_provider, Some(AiProvider::OpenAI { .. })));
+        assert!(uploader.ai_client.is_some());
+
+        // A broken file leaves the running configuration in place
+        std::fs::write(&path, "accounts: {").unwrap();
+        assert!(uploader.reload_config().await.is_err());
+        assert_eq!(uploader.list_accounts().len(), 2);
+        assert!(matches!(uploader.config.ai_provider, Some(AiProvider::OpenAI { .. })));
+    }
+
     #[test]
     fn test_account_switching() {
         use std::collections::HashMap;
@@ -221,6 +1154,11 @@ mod tests {
                 app_id: "app1".to_string(),
                 app_secret: "secret1".to_string(),
                 description: None,
+                wechat_base_url: None,
+                default_cover: None,
+                kind: models::AccountKind::Normal,
+                tags: Vec::new(),
+                owner: None,
             },
         );
         accounts.insert(
@@ -230,6 +1168,11 @@ mod tests {
                 app_id: "app2".to_string(),
                 app_secret: "secret2".to_string(),
                 description: None,
+                wechat_base_url: None,
+                default_cover: None,
+                kind: models::AccountKind::Normal,
+                tags: Vec::new(),
+                owner: None,
             },
         );
         
@@ -272,6 +1215,11 @@ mod tests {
                 app_id: "personal_app_id".to_string(),
                 app_secret: "personal_secret".to_string(),
                 description: Some("Personal account".to_string()),
+                wechat_base_url: None,
+                default_cover: None,
+                kind: models::AccountKind::Normal,
+                tags: Vec::new(),
+                owner: None,
             },
         );
         accounts.insert(
@@ -281,6 +1229,11 @@ mod tests {
                 app_id: "work_app_id".to_string(),
                 app_secret: "work_secret".to_string(),
                 description: Some("Work account".to_string()),
+                wechat_base_url: None,
+                default_cover: None,
+                kind: models::AccountKind::Normal,
+                tags: Vec::new(),
+                owner: None,
             },
         );
         
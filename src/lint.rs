@@ -0,0 +1,276 @@
+//! Markdown constructs that render poorly in WeChat
+//!
+//! WeChat's article renderer drops or mangles some markdown that looks fine
+//! elsewhere. [`lint_body`] scans an article body, outside code blocks and
+//! inline code, for the constructs in [`RULES`]:
+//!
+//! | Rule          | Construct                                     |
+//! |---------------|-----------------------------------------------|
+//! | `html-table`  | Raw HTML `<table>` markup                     |
+//! | `footnote`    | Footnote references and definitions, `[^1]`   |
+//! | `task-list`   | Task list items, `- [ ]` and `- [x]`          |
+//! | `html-embed`  | `<iframe>`, `<script>`, `<video>` and similar |
+//! | `details`     | Collapsible `<details>` sections              |
+//! | `anchor-link` | Links to headings of the article, `(#setup)`  |
+//! | `mdx`         | MDX `import`/`export` lines, `<Chart />`      |
+//!
+//! Warnings are printed by `--validate` and, in verbose mode, before each
+//! upload. Rules listed in the `disabled_lint_rules` setting are skipped. To
+//! add a rule, append it to [`RULES`] and to the table above.
+
+use crate::error::{Error, Result};
+use crate::markdown::{ParseOptions, parse_markdown_at};
+use crate::validation::{body_line_offset, prose_lines};
+use regex::Regex;
+use std::path::Path;
+use std::sync::LazyLock;
+use tracing::warn;
+
+/// A construct WeChat renders poorly
+#[derive(Debug)]
+pub struct LintRule {
+    /// Identifier shown with warnings and used in `disabled_lint_rules`
+    pub id: &'static str,
+    /// What goes wrong in WeChat
+    pub message: &'static str,
+    /// Matches a line containing the construct
+    pattern: Regex,
+}
+
+impl LintRule {
+    fn new(id: &'static str, message: &'static str, pattern: &str) -> Self {
+        Self {
+            id,
+            message,
+            pattern: Regex::new(pattern).expect("valid lint rule regex"),
+        }
+    }
+}
+
+/// Every lint rule, in the order warnings for the same line are reported
+pub static RULES: LazyLock<Vec<LintRule>> = LazyLock::new(|| {
+    vec![
+        LintRule::new(
+            "html-table",
+            "raw HTML tables lose their layout in WeChat; use a markdown table or an image",
+            r"(?i)<table\b",
+        ),
+        LintRule::new(
+            "footnote",
+            "footnotes are not rendered by WeChat; write the note inline",
+            r"\[\^[^\]\s]+\]",
+        ),
+        LintRule::new(
+            "task-list",
+            "task list checkboxes are shown as plain [ ] text in WeChat",
+            r"^\s*(?:[-*+]|\d+[.)])\s+\[[ xX]\](?:\s|$)",
+        ),
+        LintRule::new(
+            "html-embed",
+            "WeChat strips embedded iframes, scripts, media and forms",
+            r"(?i)<(?:iframe|script|embed|object|video|audio|form)\b",
+        ),
+        LintRule::new(
+            "details",
+            "collapsible <details> sections are not supported by WeChat",
+            r"(?i)<details\b",
+        ),
+        LintRule::new(
+            "anchor-link",
+            "links to headings do not work in WeChat; anchor_links: text uploads them as text",
+            r"\]\(\s*<?#|^\s{0,3}\[[^\]]+\]:\s*<?#",
+        ),
+        LintRule::new(
+            "mdx",
+            "MDX imports, exports and components are uploaded as plain text; WeChat renders markdown only",
+            r#"^\s*(?:import\s.*\bfrom\s*['"]|import\s*['"]|export\s+(?:default|const|let|function)\b)|</?[A-Z][a-z][A-Za-z0-9]*(?:\.[A-Za-z0-9]+)*(?:\s|/?>)"#,
+        ),
+    ]
+});
+
+/// A construct found in an article body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintWarning {
+    /// Identifier of the rule that matched
+    pub rule: &'static str,
+    /// What goes wrong in WeChat
+    pub message: &'static str,
+    /// 1-based line number within the scanned text
+    pub line: usize,
+}
+
+impl std::fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} [{}]", self.message, self.rule)
+    }
+}
+
+/// Checks that every disabled rule exists
+///
+/// # Errors
+///
+/// Returns a configuration error naming the unknown rule and the known ones
+pub fn validate_disabled_rules(disabled: &[String]) -> Result<()> {
+    if let Some(unknown) = disabled
+        .iter()
+        .find(|id| !RULES.iter().any(|rule| rule.id == id.as_str()))
+    {
+        let known: Vec<_> = RULES.iter().map(|rule| rule.id).collect();
+        return Err(Error::config(format!(
+            "Unknown lint rule '{}' in disabled_lint_rules; known rules: {}",
+            unknown,
+            known.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Scans a markdown body for constructs WeChat renders poorly, skipping the
+/// rules in `disabled`
+pub fn lint_body(body: &str, disabled: &[String]) -> Vec<LintWarning> {
+    let rules: Vec<&LintRule> = RULES
+        .iter()
+        .filter(|rule| !disabled.iter().any(|id| id == rule.id))
+        .collect();
+
+    prose_lines(body)
+        .into_iter()
+        .flat_map(|(line, text)| {
+            rules
+                .iter()
+                .filter(|rule| rule.pattern.is_match(&text))
+                .map(|rule| LintWarning {
+                    rule: rule.id,
+                    message: rule.message,
+                    line,
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Lints a single markdown file.
+///
+/// Line numbers in the result refer to the whole file, including frontmatter.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or its frontmatter is invalid
+pub async fn lint_file(path: &Path, disabled: &[String]) -> Result<Vec<LintWarning>> {
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| Error::io_at(path, e))?;
+    // Invalid frontmatter values are reported by validation, not by the linter
+    let options = ParseOptions {
+        quiet: true,
+        defer_validation: true,
+        ..ParseOptions::default()
+    };
+    let (_frontmatter, body) = parse_markdown_at(&content, path, &options)?;
+    let line_offset = body_line_offset(&content, &body);
+
+    let mut warnings = lint_body(&body, disabled);
+    for warning in &mut warnings {
+        warning.line += line_offset;
+    }
+    Ok(warnings)
+}
+
+/// Logs a warning for each unsupported construct in a body about to be uploaded
+pub fn warn_unsupported(path: &Path, body: &str, disabled: &[String]) {
+    for warning in lint_body(body, disabled) {
+        warn!("{} (body line {}): {}", path.display(), warning.line, warning);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(body: &str, disabled: &[String]) -> Vec<(&'static str, usize)> {
+        lint_body(body, disabled)
+            .into_iter()
+            .map(|warning| (warning.rule, warning.line))
+            .collect()
+    }
+
+    #[test]
+    fn test_lint_body_finds_unsupported_constructs() {
+        let body = "# Title
+
+<TABLE><tr><td>a</td></tr></table>
+Some claim[^1] and a list:
+
+- [ ] todo
+- [x] done
+- [link](a.md) is not a task
+
+<iframe src=\"https://example.com\"></iframe>
+<details><summary>More</summary>hidden</details>
+
+[^1]: The source.
+";
+        assert_eq!(
+            rules(body, &[]),
+            vec![
+                ("html-table", 3),
+                ("footnote", 4),
+                ("task-list", 6),
+                ("task-list", 7),
+                ("html-embed", 10),
+                ("details", 11),
+                ("footnote", 13),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_body_skips_code_and_disabled_rules() {
+        let body = "```html\n<table></table>\n```\nUse `<iframe>` tags.\n- [ ] todo[^note]\n";
+        let disabled = vec!["task-list".to_string()];
+
+        assert_eq!(rules(body, &disabled), vec![("footnote", 5)]);
+        assert_eq!(
+            rules("[Back to top](#top)\n[intro]: <#intro>\n[site](https://a.b/#top)\n", &[]),
+            vec![("anchor-link", 1), ("anchor-link", 2)]
+        );
+        assert_eq!(
+            lint_body(body, &[])[1].to_string(),
+            "task list checkboxes are shown as plain [ ] text in WeChat [task-list]"
+        );
+    }
+
+    #[test]
+    fn test_lint_body_finds_mdx_syntax() {
+        let body = "import Chart from './chart.js'
+export const meta = { draft: true }
+
+Import the data first, then <Chart data={sales} /> and <Tabs.Item>
+a</Tabs.Item>. HTML like <TABLE> or <br> is another rule.
+";
+        assert_eq!(
+            rules(body, &[]),
+            vec![("mdx", 1), ("mdx", 2), ("mdx", 4), ("html-table", 5), ("mdx", 5)]
+        );
+    }
+
+    #[test]
+    fn test_validate_disabled_rules() {
+        assert!(validate_disabled_rules(&["footnote".to_string()]).is_ok());
+        let err = validate_disabled_rules(&["tables".to_string()]).unwrap_err();
+        assert!(err.is_config());
+        assert!(err.to_string().contains("Unknown lint rule 'tables'"));
+        assert!(err.to_string().contains("html-table, footnote, task-list"));
+    }
+
+    #[tokio::test]
+    async fn test_lint_file_reports_file_line_numbers() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("post.md");
+        std::fs::write(&path, "---\ntitle: Test\n---\n# Heading\n\n- [ ] todo\n").unwrap();
+
+        let warnings = lint_file(&path, &[]).await.unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line, 6);
+    }
+}
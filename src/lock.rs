@@ -0,0 +1,313 @@
+//! Run locks
+//!
+//! A directory run holds an advisory lock, `.wx-uploader/lock` in the
+//! directory it uploads, so that two overlapping runs, e.g. from cron, do not
+//! interleave frontmatter writes and create duplicate drafts. The lock file
+//! names the PID and start time of the run holding it. A second run fails
+//! right away, or waits for up to `--lock-wait` seconds for the lock to be
+//! released. A lock left behind by a run that no longer exists is reclaimed.
+//! `--force-lock` takes over a lock whose run still seems alive, e.g. when its
+//! PID was reused or it was taken on another host sharing the directory; the
+//! run that lost the lock then leaves the new lock file in place.
+
+use crate::backup::DATA_DIR;
+use crate::error::{Error, Result};
+use crate::output::{FORMATTER, OutputFormatter};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How often a waiting run checks whether the lock was released
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Returns the lock file of runs over `dir`
+pub fn lock_path(dir: &Path) -> PathBuf {
+    dir.join(DATA_DIR).join("lock")
+}
+
+/// The run holding a lock, as recorded in the lock file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockHolder {
+    /// Process ID of the run
+    pub pid: u32,
+    /// When the run took the lock, as written in the file
+    pub started: String,
+}
+
+impl LockHolder {
+    /// Parses the `pid:` and `started:` lines of a lock file
+    fn parse(content: &str) -> Option<Self> {
+        let field = |name: &str| {
+            content
+                .lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+                .map(str::trim)
+        };
+        Some(Self {
+            pid: field("pid")?.parse().ok()?,
+            started: field("started").unwrap_or_default().to_string(),
+        })
+    }
+}
+
+/// An acquired run lock; the lock file is removed when it is dropped, and
+/// with it the data directory if nothing else is in it
+#[derive(Debug)]
+pub struct RunLock {
+    path: PathBuf,
+    /// What this run wrote to the lock file
+    content: String,
+}
+
+impl RunLock {
+    /// Takes the run lock of `dir`, waiting up to `wait` while another run holds it
+    ///
+    /// A lock whose run is no longer alive, or whose file cannot be parsed, is
+    /// reclaimed with a warning. Returns `None` with a warning if `dir` is
+    /// read-only, as with `write_back: false` over a read-only tree; such a
+    /// run goes unlocked.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the run holding the lock if it is still held
+    /// after `wait`, or if the lock file cannot be created
+    pub async fn acquire(dir: &Path, wait: Duration) -> Result<Option<Self>> {
+        Self::acquire_with(dir, wait, false).await
+    }
+
+    /// Takes the run lock of `dir` like [`RunLock::acquire`], taking over a
+    /// lock held by a live run with a warning if `force` is set
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lock file cannot be created, or without
+    /// `force` if the lock is still held after `wait`
+    pub async fn acquire_with(dir: &Path, wait: Duration, force: bool) -> Result<Option<Self>> {
+        let path = lock_path(dir);
+        let deadline = Instant::now() + wait;
+        loop {
+            match create_lock_file(&path) {
+                Ok(content) => return Ok(Some(Self { path, content })),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+                Err(e) if is_read_only(&e) => {
+                    FORMATTER.print_warning(&format!(
+                        "cannot create lock {} ({}), running without a lock",
+                        path.display(),
+                        e
+                    ));
+                    return Ok(None);
+                }
+                Err(e) => return Err(Error::io_at(&path, e)),
+            }
+
+            let content = match tokio::fs::read_to_string(&path).await {
+                Ok(content) => content,
+                // Released between the attempt and the read
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(Error::io_at(&path, e)),
+            };
+            let holder = LockHolder::parse(&content).filter(|holder| process_alive(holder.pid));
+            let Some(holder) = holder else {
+                FORMATTER.print_warning(&format!(
+                    "removing stale lock {} of a run that is no longer running",
+                    path.display()
+                ));
+                match tokio::fs::remove_file(&path).await {
+                    Ok(()) => continue,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(Error::io_at(&path, e)),
+                }
+            };
+
+            if force {
+                FORMATTER.print_warning(&format!(
+                    "taking over lock {} of the run with PID {}, started {} (--force-lock)",
+                    path.display(),
+                    holder.pid,
+                    holder.started
+                ));
+                match tokio::fs::remove_file(&path).await {
+                    Ok(()) => continue,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(Error::io_at(&path, e)),
+                }
+            }
+            if Instant::now() >= deadline {
+                let hint = if wait.is_zero() {
+                    "wait for it to finish or pass --lock-wait SECS \
+                     (--force-lock takes over a lock that is not in use)"
+                } else {
+                    "it is still running after --lock-wait"
+                };
+                return Err(Error::generic(format!(
+                    "another run (PID {}, started {}) is uploading {}; {}",
+                    holder.pid,
+                    holder.started,
+                    dir.display(),
+                    hint
+                )));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Returns the path of the lock file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        // A lock taken over with --force-lock belongs to the other run now
+        if std::fs::read_to_string(&self.path).is_ok_and(|content| content != self.content) {
+            return;
+        }
+        let _ = std::fs::remove_file(&self.path);
+        // Leaves no data directory behind in trees that had none
+        if let Some(parent) = self.path.parent() {
+            let _ = std::fs::remove_dir(parent);
+        }
+    }
+}
+
+/// Creates the lock file for this process and returns its content; fails if
+/// it already exists
+///
+/// The file is written under a name of its own first and then linked into
+/// place, so that another run never reads a lock that is only partly written.
+fn create_lock_file(path: &Path) -> std::io::Result<String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let pending = path.with_extension(std::process::id().to_string());
+    let content = format!(
+        "pid: {}\nstarted: {}\n",
+        std::process::id(),
+        chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false)
+    );
+    std::fs::write(&pending, &content)?;
+    let linked = std::fs::hard_link(&pending, path);
+    let _ = std::fs::remove_file(&pending);
+    linked.map(|()| content)
+}
+
+/// Returns true if `error` means the lock cannot be written at all
+fn is_read_only(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        std::io::ErrorKind::PermissionDenied | std::io::ErrorKind::ReadOnlyFilesystem
+    )
+}
+
+/// Returns true if a process with `pid` is running
+#[cfg(target_os = "linux")]
+fn process_alive(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+/// Returns true if a process with `pid` is running
+#[cfg(all(unix, not(target_os = "linux")))]
+fn process_alive(pid: u32) -> bool {
+    std::process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Returns true if a process with `pid` may be running; without a way to
+/// check, locks are never considered stale
+#[cfg(not(unix))]
+fn process_alive(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// PIDs are limited to 2^22 on Linux, so this one never runs
+    const DEAD_PID: u32 = 4_194_305;
+
+    #[tokio::test]
+    async fn test_live_lock_is_refused_and_released_on_drop() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock = RunLock::acquire(temp_dir.path(), Duration::ZERO).await.unwrap().unwrap();
+        let content = fs::read_to_string(lock.path()).unwrap();
+        let holder = LockHolder::parse(&content).unwrap();
+        assert_eq!(holder.pid, std::process::id());
+        assert!(!holder.started.is_empty());
+
+        let err = RunLock::acquire(temp_dir.path(), Duration::ZERO).await.unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains(&format!("another run (PID {}", holder.pid)), "{}", message);
+        assert!(message.contains("--lock-wait"), "{}", message);
+
+        drop(lock);
+        assert!(!temp_dir.path().join(DATA_DIR).exists());
+        assert!(RunLock::acquire(temp_dir.path(), Duration::ZERO).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_stale_lock_is_reclaimed() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = lock_path(temp_dir.path());
+
+        for stale in [format!("pid: {}\nstarted: earlier\n", DEAD_PID), "garbage".to_string()] {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, stale).unwrap();
+            let lock = RunLock::acquire(temp_dir.path(), Duration::ZERO).await.unwrap().unwrap();
+            let holder = LockHolder::parse(&fs::read_to_string(lock.path()).unwrap()).unwrap();
+            assert_eq!(holder.pid, std::process::id());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_force_takes_over_a_live_lock() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = lock_path(temp_dir.path());
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        // Held by a live run, as far as the PID tells
+        let other = format!("pid: {}\nstarted: on another host\n", std::process::id());
+        fs::write(&path, &other).unwrap();
+
+        let err = RunLock::acquire(temp_dir.path(), Duration::ZERO).await.unwrap_err();
+        assert!(err.to_string().contains("--force-lock"), "{}", err);
+        assert_eq!(fs::read_to_string(&path).unwrap(), other);
+
+        let lock = RunLock::acquire_with(temp_dir.path(), Duration::ZERO, true)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_ne!(fs::read_to_string(&path).unwrap(), other);
+
+        // The run that lost its lock leaves the new holder's file in place
+        fs::write(&path, &other).unwrap();
+        drop(lock);
+        assert_eq!(fs::read_to_string(&path).unwrap(), other);
+    }
+
+    #[tokio::test]
+    async fn test_waits_for_the_lock_to_be_released() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock = RunLock::acquire(temp_dir.path(), Duration::ZERO).await.unwrap().unwrap();
+        let release = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            drop(lock);
+        });
+
+        let started = Instant::now();
+        let lock = RunLock::acquire(temp_dir.path(), Duration::from_secs(10)).await.unwrap();
+        assert!(lock.is_some());
+        assert!(started.elapsed() >= Duration::from_millis(300));
+        release.await.unwrap();
+
+        // A lock still held after the wait is refused
+        let err = RunLock::acquire(temp_dir.path(), Duration::from_millis(300))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("still running after --lock-wait"), "{}", err);
+    }
+}
@@ -1,69 +1,263 @@
 //! WeChat Public Account Markdown Uploader
 //!
 //! A command-line tool for uploading markdown files to WeChat public accounts.
+//!
+//! The process exit code follows the scheme documented in
+//! [`wx_uploader::exit_code`]: 0 success, 2 config/usage error, 3 partial
+//! failure, 4 authentication failure, 5 all files failed, 6 warnings under
+//! `--warnings-as-errors`.
 
-use anyhow::{Context, Result};
 use clap::Parser;
-use wx_uploader::{WxUploader, cli};
+use std::fmt::Display;
+use std::path::Path;
+use wx_uploader::output::{self, FORMATTER, OutputFormatter};
+use wx_uploader::prompt::Prompter;
+use wx_uploader::error::AiFailure;
+use wx_uploader::report::RunMetadata;
+use wx_uploader::schedule::SystemClock;
+use wx_uploader::wechat::{self, DraftIndex, ProcessOptions};
+use wx_uploader::{
+    Config, Error, ExitCode, Result, UploadReport, WxUploader, backup, build_info, cli, dry_run,
+    html_dump, manifest, normalize, retry_queue, status, validation,
+};
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> std::process::ExitCode {
+    run().await.into()
+}
+
+/// Runs the CLI and returns the exit code to terminate with
+async fn run() -> ExitCode {
     // Check if help is requested before clap processes args
     if std::env::args().any(|arg| arg == "--help" || arg == "-h") {
         cli::print_colored_help();
-        std::process::exit(0);
+        return ExitCode::Success;
     }
 
-    let args = cli::Args::parse();
+    let args = match cli::Args::try_parse() {
+        Ok(args) => args,
+        Err(e) => {
+            let _ = e.print();
+            return if e.use_stderr() {
+                ExitCode::ConfigError
+            } else {
+                ExitCode::Success
+            };
+        }
+    };
+
+    output::set_output_mode(args.output);
+
+    if args.show_version {
+        println!("{}", build_info::version_text(args.verbose));
+        return ExitCode::Success;
+    }
 
     // Handle special commands first
     if let Some(config_path) = &args.init_config {
         if let Err(error_msg) = cli::generate_example_config(config_path).await {
             eprintln!("Error: {}", error_msg);
-            std::process::exit(1);
+            return ExitCode::ConfigError;
         }
-        return Ok(());
+        return ExitCode::Success;
+    }
+
+    if args.list_themes {
+        cli::list_themes();
+        return ExitCode::Success;
     }
 
     if args.list_accounts {
         if let Some(config_path) = &args.config_file {
             if let Err(error_msg) = cli::list_accounts_from_config(config_path).await {
                 eprintln!("Error: {}", error_msg);
-                std::process::exit(1);
+                return ExitCode::ConfigError;
             }
         } else {
             eprintln!("Error: --list-accounts requires a configuration file (--config)");
-            std::process::exit(1);
+            return ExitCode::ConfigError;
         }
-        return Ok(());
+        return ExitCode::Success;
+    }
+
+    if let Some(format) = args.show_config {
+        if let Err(error_msg) = cli::show_config(&args, format).await {
+            eprintln!("Error: {}", error_msg);
+            return ExitCode::ConfigError;
+        }
+        return ExitCode::Success;
     }
 
     // Validate arguments
     if let Err(error_msg) = cli::validate_args(&args) {
         eprintln!("Error: {}", error_msg);
-        std::process::exit(1);
+        return ExitCode::ConfigError;
     }
 
-    // Initialize logging
-    cli::init_logging(args.verbose);
+    if let Some(cli::Command::Restore { from, force }) = &args.command {
+        return restore_backup(from, *force, &cli::prompter(&args)).await;
+    }
 
-    // Create configuration from CLI arguments (handles both env vars and config files)
-    let config = cli::create_config_from_args(&args)
+    // Initialize logging; the guard flushes the log file when the run ends
+    let _log_guard = match cli::init_logging(
+        args.verbose,
+        args.trace_http,
+        args.log_format,
+        args.log_file.as_deref(),
+    ) {
+        Ok(guard) => guard,
+        Err(e) => return fail("Failed to initialize logging", &e),
+    };
+
+    // Validation mode runs locally and does not need WeChat credentials
+    if args.validate {
+        let path = args.path.as_deref().expect("path checked by validate_args");
+        // Only the lint settings are used, so credentials are not required
+        let (disabled_rules, extensions) = match &args.config_file {
+            Some(config_file) => match Config::from_file(config_file, None).await {
+                Ok(config) => (config.disabled_lint_rules, config.markdown_extensions),
+                Err(e) => return fail("Failed to load configuration", &e),
+            },
+            None => (Vec::new(), wechat::default_markdown_extensions()),
+        };
+        return match validation::validate_path(
+            path,
+            args.check_remote_links,
+            &disabled_rules,
+            &extensions,
+            args.verbose,
+        )
         .await
-        .map_err(|e| anyhow::anyhow!("Failed to create configuration: {}", e))?;
-    
+        {
+            Ok(0) => ExitCode::Success,
+            Ok(_) => ExitCode::PartialFailure,
+            Err(e) => fail(format!("Failed to validate: {}", path.display()), &e),
+        };
+    }
+
+    // Status overview only reads frontmatter
+    if args.show_status {
+        let path = args.path.as_deref().expect("path checked by validate_args");
+        status::show_status(path).await;
+        return ExitCode::Success;
+    }
+
+    // Normalization only rewrites frontmatter; --config is read for the backup settings
+    if args.normalize_frontmatter {
+        let path = args.path.as_deref().expect("path checked by validate_args");
+        let (backup, backup_keep) = match &args.config_file {
+            Some(config_file) => match Config::from_file(config_file, None).await {
+                Ok(config) => (config.backup, config.backup_keep),
+                Err(e) => return fail("Failed to load configuration", &e),
+            },
+            None => (true, backup::DEFAULT_BACKUP_KEEP),
+        };
+        let backup = match std::env::current_dir() {
+            Ok(work_dir) if backup => Some(backup::Backup::starting_now(work_dir, backup_keep)),
+            _ => None,
+        };
+        let report = normalize::run(path, backup.as_ref()).await;
+        return if report.errors() > 0 {
+            ExitCode::PartialFailure
+        } else {
+            ExitCode::Success
+        };
+    }
+
+    // Create configuration from CLI arguments (handles both env vars and config files)
+    let config = match cli::create_config_from_args(&args).await {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: Failed to create configuration: {}", e);
+            return ExitCode::ConfigError;
+        }
+    };
+
+    if args.check_ai {
+        return check_ai(config, !args.skip_image).await;
+    }
+
+    // The HTML is written before the upload, or instead of it with --dry-run
+    if let Some(output) = &args.dump_html {
+        let path = args.path.as_deref().expect("path checked by validate_args");
+        let options = ProcessOptions::from(&config);
+        match html_dump::dump_html(path, output, &options).await {
+            Ok(rendered) if output.as_os_str() != "-" => FORMATTER.print_info(&format!(
+                "wrote {} (theme {}, code highlighter {})",
+                output.display(),
+                rendered.theme,
+                rendered.code
+            )),
+            Ok(_) => {}
+            Err(e) => return fail(format!("Failed to render HTML: {}", path.display()), &e),
+        }
+    }
+
+    // Dry run and diff plan the upload without creating a WeChat client,
+    // unless --reconcile needs one to list the existing drafts
+    if let Some(output) = dry_run::DryRunOutput::from_flags(args.dry_run, args.diff) {
+        let path = args.path.as_deref().expect("path checked by validate_args");
+        let drafts = match dry_run_drafts(&config).await {
+            Ok(drafts) => drafts,
+            Err(e) => return fail("Failed to list WeChat drafts", &e),
+        };
+        let summary = dry_run::run(path, &config, output, drafts.as_ref()).await;
+        return if summary.errors > 0 {
+            ExitCode::PartialFailure
+        } else {
+            ExitCode::Success
+        };
+    }
+
+    // Missing manifest entries are reported before anything is uploaded
+    let manifest = match &args.manifest {
+        Some(path) => match manifest::Manifest::load(path).await {
+            Ok(manifest) => Some(manifest),
+            Err(e) => return fail("Failed to load manifest", &e),
+        },
+        None => None,
+    };
+
+    // Dry runs above are exempt; an upload run only starts inside allowed_hours
+    if let Err(e) = config.check_schedule(&SystemClock) {
+        return fail("Refusing to upload", &e);
+    }
+
     // Display banner if verbose
     cli::display_banner(&args);
 
+    let untouched_note = if !config.write_back {
+        Some(format!(
+            "source files were left untouched; upload state recorded in {}",
+            config.state_file_path().display()
+        ))
+    } else if !config.set_draft_status {
+        Some(format!(
+            "published fields were left unchanged; upload state recorded in {}",
+            config.state_file_path().display()
+        ))
+    } else {
+        None
+    };
+
+    let retry_queue = match std::env::current_dir() {
+        Ok(work_dir) => retry_queue::retry_queue_path(&work_dir),
+        Err(e) => return fail("Failed to locate the retry queue", &Error::from(e)),
+    };
+    let options_hash = retry_queue::options_hash(&config);
+
     // Create the uploader
-    let uploader = WxUploader::new(config)
-        .await
-        .context("Failed to initialize WeChat uploader")?;
-    
+    let uploader = match WxUploader::new(config).await {
+        Ok(uploader) => uploader,
+        Err(e) => return fail("Failed to initialize WeChat uploader", &e),
+    };
+
     if args.verbose {
-        println!("Using account: {} ({})", 
+        println!("Using account: {}{} ({}){}",
             uploader.current_account().name,
-            uploader.current_account().description.as_deref().unwrap_or("No description")
+            cli::sandbox_label(uploader.current_account().kind),
+            uploader.current_account().description.as_deref().unwrap_or("No description"),
+            cli::tags_label(&uploader.current_account().tags)
         );
     }
 
@@ -72,35 +266,306 @@ async fn main() -> Result<()> {
         if args.verbose {
             println!("Refreshing WeChat access token...");
         }
-        uploader
-            .refresh_token()
-            .await
-            .context("Failed to refresh WeChat token")?;
+        if let Err(e) = uploader.refresh_token().await {
+            return fail("Failed to refresh WeChat token", &e);
+        }
         if !args.verbose {
-            println!("WeChat access token refreshed");
+            FORMATTER.print_info("WeChat access token refreshed");
         }
     }
 
+    if let Some(cli::Command::Retry { .. }) = &args.command {
+        return retry_failed(&uploader, &retry_queue, &args).await;
+    }
+
+    if let Some(manifest) = &manifest {
+        return match uploader.upload_manifest(manifest).await {
+            Ok(report) => {
+                print_report(&report, &args);
+                write_reports(&report, &args, &uploader).await;
+                queue_failures(&retry_queue, &report, &options_hash, false).await;
+                print_untouched_note(untouched_note.as_deref());
+                ExitCode::from_report_with(&report, args.warnings_as_errors)
+            }
+            Err(e) => fail("Failed to process manifest", &e),
+        };
+    }
+
     // Process the input path
-    if let Some(path) = &args.path {
-        if path.is_file() {
-            // Force upload single file
-            uploader
-                .upload_file(path, true)
-                .await
-                .with_context(|| format!("Failed to upload file: {}", path.display()))?;
-        } else if path.is_dir() {
-            // Process directory
-            uploader
-                .process_directory(path)
-                .await
-                .with_context(|| format!("Failed to process directory: {}", path.display()))?;
-        } else {
-            anyhow::bail!("Path must be a file or directory: {}", path.display());
+    let Some(path) = &args.path else {
+        eprintln!("Error: No path specified for upload operation");
+        return ExitCode::ConfigError;
+    };
+
+    if path.is_file() {
+        // Force upload single file, to every account it targets
+        match uploader.upload_file_to_accounts(path, true).await {
+            Ok(report) => {
+                if report.accounts().len() > 1 {
+                    print_report(&report, &args);
+                }
+                write_reports(&report, &args, &uploader).await;
+                queue_failures(&retry_queue, &report, &options_hash, true).await;
+                print_untouched_note(untouched_note.as_deref());
+                ExitCode::from_report_with(&report, args.warnings_as_errors)
+            }
+            Err(e) => fail(format!("Failed to upload file: {}", path.display()), &e),
+        }
+    } else if path.is_dir() {
+        // Process directory
+        match uploader.process_directory(path).await {
+            Ok(report) => {
+                print_report(&report, &args);
+                write_reports(&report, &args, &uploader).await;
+                queue_failures(&retry_queue, &report, &options_hash, false).await;
+                print_untouched_note(untouched_note.as_deref());
+                ExitCode::from_report_with(&report, args.warnings_as_errors)
+            }
+            Err(e) => fail(format!("Failed to process directory: {}", path.display()), &e),
+        }
+    } else {
+        eprintln!("Error: Path must be a file or directory: {}", path.display());
+        ExitCode::ConfigError
+    }
+}
+
+/// Prints an error that aborted the run and returns the matching exit code
+fn fail(context: impl Display, error: &Error) -> ExitCode {
+    // Missing files and covers name the path themselves, so the context adds nothing
+    let message = match error {
+        Error::FileNotFound { .. } | Error::CoverImage { .. } => error.to_string(),
+        _ => format!("{}: {}", context, error.display_chain()),
+    };
+    if output::annotations_enabled() {
+        FORMATTER.print_error(&message);
+    } else {
+        eprintln!("Error: {}", message);
+    }
+    ExitCode::from_error(error)
+}
+
+/// Lists the drafts of the current account when a dry run reconciles
+async fn dry_run_drafts(config: &Config) -> Result<Option<DraftIndex>> {
+    if !config.reconcile {
+        return Ok(None);
+    }
+    let uploader = WxUploader::new(config.clone()).await?;
+    uploader.draft_index().await.map(Some)
+}
+
+/// Makes test requests against the configured AI provider and reports each
+async fn check_ai(config: Config, include_image: bool) -> ExitCode {
+    let uploader = match WxUploader::new(config).await {
+        Ok(uploader) => uploader,
+        Err(e) => return fail("Failed to initialize WeChat uploader", &e),
+    };
+    if let Some(client) = uploader.ai_client() {
+        FORMATTER.print_info(&format!(
+            "checking {} with API key {}",
+            client.provider_name(),
+            cli::mask_secret(client.api_key())
+        ));
+    }
+    let checks = match uploader.check_ai(include_image).await {
+        Ok(checks) => checks,
+        Err(e) => return fail("Failed to check the AI provider", &e),
+    };
+
+    for check in &checks {
+        let label = format!("{} ({})", check.name, check.model);
+        let latency = check.latency.as_millis();
+        match &check.outcome {
+            Ok(summary) => {
+                FORMATTER.print_success(&format!("{}: {} ms, {}", label, latency, summary))
+            }
+            Err(error) => {
+                FORMATTER.print_error(&format!("{} failed after {} ms: {}", label, latency, error))
+            }
         }
+    }
+    if !include_image {
+        FORMATTER.print_info("image generation skipped (--skip-image)");
+    }
+
+    let rejected = checks.iter().any(|check| {
+        matches!(check.failure, Some(AiFailure::InvalidApiKey | AiFailure::PermissionDenied))
+    });
+    if rejected {
+        ExitCode::AuthFailure
+    } else if checks.iter().all(|check| check.is_ok()) {
+        ExitCode::Success
     } else {
-        anyhow::bail!("No path specified for upload operation");
+        ExitCode::AllFailed
+    }
+}
+
+/// Copies backup set `name` back over the current directory once the user
+/// confirms, or right away with `force`
+async fn restore_backup(name: &str, force: bool, prompt: &Prompter) -> ExitCode {
+    let work_dir = match std::env::current_dir() {
+        Ok(work_dir) => work_dir,
+        Err(e) => return fail("Failed to restore backup", &Error::from(e)),
+    };
+    let files = match backup::set_files(&work_dir, name) {
+        Ok(files) => files,
+        Err(e) => return fail("Failed to restore backup", &e),
+    };
+
+    println!("Backup {} holds {} file(s):", name, files.len());
+    for file in &files {
+        println!("  {}", file.display());
+    }
+    let question = format!("Overwrite them in {}?", work_dir.display());
+    let confirmed = if force { Ok(true) } else { prompt.confirm(&question, false).await };
+    match confirmed {
+        Ok(true) => {}
+        Ok(false) => {
+            FORMATTER.print_info("restore cancelled, nothing was changed; pass --force to overwrite");
+            return ExitCode::Success;
+        }
+        Err(e) => return fail("Failed to restore backup", &e),
+    }
+
+    match backup::restore(&work_dir, name).await {
+        Ok(restored) => {
+            FORMATTER.print_info(&format!("restored {} file(s) from backup {}", restored.len(), name));
+            ExitCode::Success
+        }
+        Err(e) => fail("Failed to restore backup", &e),
+    }
+}
+
+/// Records the failures of a run in the retry queue, and clears the files that
+/// no longer fail
+///
+/// The uploads already happened, so a queue file error is only a warning.
+async fn queue_failures(queue_file: &Path, report: &UploadReport, options_hash: &str, force: bool) {
+    if let Err(e) = retry_queue::record_failures(queue_file, report, options_hash, force).await {
+        FORMATTER.print_warning(&format!("failed to update {}: {}", queue_file.display(), e));
+    } else if report.failed() > 0 {
+        FORMATTER.print_info("failed files were queued; run `wx-uploader retry` to re-attempt them");
+    }
+}
+
+/// Re-attempts the queued failures of earlier runs and reports what is left
+async fn retry_failed(uploader: &WxUploader, queue_file: &Path, args: &cli::Args) -> ExitCode {
+    let retry = match uploader.retry_queued(queue_file).await {
+        Ok(retry) => retry,
+        Err(e) => return fail("Failed to retry queued uploads", &e),
+    };
+
+    if retry.report.files.is_empty() && retry.held.is_empty() {
+        FORMATTER.print_info("no failed uploads are queued");
+        return ExitCode::Success;
+    }
+    print_report(&retry.report, args);
+    write_reports(&retry.report, args, uploader).await;
+    for entry in &retry.given_up {
+        FORMATTER.print_file_warning(
+            &entry.path,
+            None,
+            &format!("gave up after {} attempts: {}", entry.attempts, entry.error),
+        );
+    }
+    for entry in &retry.held {
+        FORMATTER.print_file_warning(
+            &entry.path,
+            None,
+            "queued with other options; retry it with the flags of the run it failed in",
+        );
+    }
+    FORMATTER.print_info(&retry.summary());
+    ExitCode::from_report_with(&retry.report, args.warnings_as_errors)
+}
+
+/// Notes that write-back was disabled, so the files on disk did not change
+fn print_untouched_note(note: Option<&str>) {
+    if let Some(note) = note {
+        FORMATTER.print_info(note);
+    }
+}
+
+/// Prints the summary of a run, broken down per account if several were used, listing failed files
+///
+/// Already published files are counted in one line, or listed with
+/// `--show-skipped`; `--verbose` logged them as they were skipped.
+fn print_report(report: &UploadReport, args: &cli::Args) {
+    if report.files.is_empty() {
+        return;
+    }
+
+    // Annotations only carry the failures; the exit code reports the outcome
+    if output::annotations_enabled() {
+        for (path, error) in report.failures() {
+            FORMATTER.print_file_error(path, None, &error);
+        }
+        return;
+    }
+
+    let accounts = report.accounts();
+    if accounts.len() > 1 {
+        for account in accounts {
+            let account_report = report.for_account(account);
+            let line = format!("{}: {}", account, account_report.summary());
+            if account_report.failed() == 0 {
+                FORMATTER.print_info(&line);
+            } else {
+                FORMATTER.print_warning(&line);
+            }
+        }
     }
 
-    Ok(())
+    if args.show_skipped && !args.verbose {
+        for path in report.skipped_published() {
+            FORMATTER.print_skip(&format!("skipped: {}", path.display()));
+        }
+    } else if !args.verbose
+        && let Some(skipped) = report.skipped_published_summary()
+    {
+        FORMATTER.print_skip(&skipped);
+    }
+
+    if let Some(covers) = report.cover_summary() {
+        FORMATTER.print_info(&covers);
+        for (path, cover) in report.covers() {
+            FORMATTER.print_detail(&format!("  {}: {}", path.display(), cover));
+        }
+    }
+
+    if report.failed() == 0 {
+        FORMATTER.print_info(&report.summary());
+        return;
+    }
+
+    FORMATTER.print_warning(&report.summary());
+    for (path, error) in report.failures() {
+        FORMATTER.print_file_error(path, None, &error);
+    }
+}
+
+/// Writes the run summary to the `--report-json` and `--report` files, if given
+///
+/// A `--report` of `-` prints the Markdown report to stdout. The uploads
+/// already happened, so a write error is only a warning.
+async fn write_reports(report: &UploadReport, args: &cli::Args, uploader: &WxUploader) {
+    if let Some(path) = &args.report_json
+        && let Err(e) = report.write_json(path).await
+    {
+        FORMATTER.print_warning(&format!("failed to write {}: {}", path.display(), e));
+    }
+
+    let Some(path) = &args.report else {
+        return;
+    };
+    let accounts = report.accounts();
+    let metadata = RunMetadata::new(if accounts.is_empty() {
+        uploader.current_account().name.clone()
+    } else {
+        accounts.join(", ")
+    });
+    if path.as_os_str() == "-" {
+        print!("{}", report.to_markdown(&metadata));
+    } else if let Err(e) = report.write_markdown(path, &metadata).await {
+        FORMATTER.print_warning(&format!("failed to write {}: {}", path.display(), e));
+    }
 }
@@ -0,0 +1,264 @@
+//! Upload manifests
+//!
+//! Implements `--manifest FILE`: instead of walking a directory, upload exactly
+//! the articles a manifest lists, in its order. A manifest is either a text
+//! file with one path per line, or a YAML list (`.yaml`, `.yml` or `.json`)
+//! whose entries are paths or mappings that also override the account or theme
+//! of that article:
+//!
+//! ```yaml
+//! - posts/intro.md
+//! - path: posts/release.md
+//!   account: work
+//!   theme: lapis
+//! ```
+//!
+//! Paths are resolved against the directory of the manifest.
+
+use crate::error::{Error, Result};
+use crate::models::is_valid_theme;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// One article listed in a manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// Path of the markdown file, resolved against the manifest's directory
+    pub path: PathBuf,
+    /// Account name or `@group` replacing the frontmatter `account`/`accounts`
+    pub account: Option<String>,
+    /// Theme replacing the frontmatter `theme` for the upload
+    pub theme: Option<String>,
+}
+
+/// Overrides of a YAML manifest entry
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EntrySpec {
+    path: PathBuf,
+    #[serde(default)]
+    account: Option<String>,
+    #[serde(default)]
+    theme: Option<String>,
+}
+
+/// Articles to upload, in upload order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    /// Listed articles in manifest order
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    /// Reads a manifest and checks that every listed file exists
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the manifest cannot be read or parsed, or one error
+    /// listing every entry that is missing, not a file or listed twice
+    pub async fn load(path: &Path) -> Result<Self> {
+        let content = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| Error::io_at(path, e))?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        let is_yaml = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| matches!(ext.to_ascii_lowercase().as_str(), "yaml" | "yml" | "json"));
+
+        let manifest = if is_yaml {
+            Self::parse_yaml(&content, base)
+        } else {
+            Ok(Self::parse_text(&content, base))
+        }
+        .map_err(|e| Error::config(format!("invalid manifest {}: {}", path.display(), e)))?;
+        manifest.check_files(path)?;
+        Ok(manifest)
+    }
+
+    /// Parses a text manifest: one path per line, skipping blank lines and `#` comments
+    pub fn parse_text(content: &str, base: &Path) -> Self {
+        let entries = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| ManifestEntry {
+                path: base.join(line),
+                account: None,
+                theme: None,
+            })
+            .collect();
+        Self { entries }
+    }
+
+    /// Parses a YAML manifest: a list of paths or `path`/`account`/`theme` mappings
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the entry that is neither, or whose theme is unknown
+    pub fn parse_yaml(content: &str, base: &Path) -> Result<Self> {
+        let items: Vec<serde_yaml::Value> = match serde_yaml::from_str(content)? {
+            serde_yaml::Value::Null => Vec::new(),
+            serde_yaml::Value::Sequence(items) => items,
+            _ => return Err(Error::config("expected a list of paths")),
+        };
+
+        let mut entries = Vec::with_capacity(items.len());
+        for (index, item) in items.into_iter().enumerate() {
+            let spec = match item {
+                serde_yaml::Value::String(path) => EntrySpec {
+                    path: PathBuf::from(path),
+                    account: None,
+                    theme: None,
+                },
+                item @ serde_yaml::Value::Mapping(_) => serde_yaml::from_value(item)
+                    .map_err(|e| Error::config(format!("entry {}: {}", index + 1, e)))?,
+                _ => {
+                    return Err(Error::config(format!(
+                        "entry {}: expected a path or a mapping with a path",
+                        index + 1
+                    )));
+                }
+            };
+            if let Some(theme) = &spec.theme
+                && !is_valid_theme(theme)
+            {
+                return Err(Error::config(format!(
+                    "entry {}: unknown theme '{}'",
+                    index + 1,
+                    theme
+                )));
+            }
+            entries.push(ManifestEntry {
+                path: base.join(spec.path),
+                account: spec.account,
+                theme: spec.theme,
+            });
+        }
+        Ok(Self { entries })
+    }
+
+    /// Returns the listed files in manifest order
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.entries.iter().map(|entry| entry.path.clone()).collect()
+    }
+
+    /// Returns the account override of each entry that has one
+    pub fn account_overrides(&self) -> HashMap<PathBuf, String> {
+        self.entries
+            .iter()
+            .filter_map(|entry| Some((entry.path.clone(), entry.account.clone()?)))
+            .collect()
+    }
+
+    /// Returns the theme override of each entry that has one
+    pub fn theme_overrides(&self) -> HashMap<PathBuf, String> {
+        self.entries
+            .iter()
+            .filter_map(|entry| Some((entry.path.clone(), entry.theme.clone()?)))
+            .collect()
+    }
+
+    /// Fails with every entry that is missing, not a file or listed twice
+    fn check_files(&self, manifest: &Path) -> Result<()> {
+        let mut problems = Vec::new();
+        for (index, entry) in self.entries.iter().enumerate() {
+            let problem = if !entry.path.exists() {
+                "not found"
+            } else if !entry.path.is_file() {
+                "not a file"
+            } else if self.entries[..index].iter().any(|e| e.path == entry.path) {
+                "listed twice"
+            } else {
+                continue;
+            };
+            problems.push(format!("  {}: {}", entry.path.display(), problem));
+        }
+
+        if problems.is_empty() {
+            return Ok(());
+        }
+        Err(Error::config(format!(
+            "manifest {} lists {} unusable file(s):\n{}",
+            manifest.display(),
+            problems.len(),
+            problems.join("\n")
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_text_manifest() {
+        let manifest = Manifest::parse_text(
+            "# changed in this release\nposts/b.md\n\n  posts/a.md  \n",
+            Path::new("release"),
+        );
+        assert_eq!(
+            manifest.files(),
+            vec![
+                PathBuf::from("release/posts/b.md"),
+                PathBuf::from("release/posts/a.md")
+            ]
+        );
+        assert!(manifest.account_overrides().is_empty());
+    }
+
+    #[test]
+    fn test_parse_yaml_manifest_with_overrides() {
+        let yaml = "- posts/b.md\n- path: posts/a.md\n  account: work\n  theme: lapis\n";
+        let manifest = Manifest::parse_yaml(yaml, Path::new("release")).unwrap();
+        assert_eq!(
+            manifest.entries,
+            vec![
+                ManifestEntry {
+                    path: PathBuf::from("release/posts/b.md"),
+                    account: None,
+                    theme: None,
+                },
+                ManifestEntry {
+                    path: PathBuf::from("release/posts/a.md"),
+                    account: Some("work".to_string()),
+                    theme: Some("lapis".to_string()),
+                },
+            ]
+        );
+        assert_eq!(
+            manifest.theme_overrides().get(Path::new("release/posts/a.md")),
+            Some(&"lapis".to_string())
+        );
+
+        let err = Manifest::parse_yaml("- path: a.md\n  theme: nope\n", Path::new("")).unwrap_err();
+        assert!(err.to_string().contains("entry 1: unknown theme 'nope'"), "{}", err);
+        let err = Manifest::parse_yaml("- a.md\n- path: b.md\n  acount: x\n", Path::new(""))
+            .unwrap_err();
+        assert!(err.to_string().contains("entry 2: unknown field `acount`"), "{}", err);
+        assert!(Manifest::parse_yaml("path: a.md\n", Path::new("")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_load_lists_unusable_entries_up_front() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        fs::write(dir.join("a.md"), "# A").unwrap();
+        fs::create_dir(dir.join("posts")).unwrap();
+        fs::write(dir.join("changed.txt"), "a.md\nmissing.md\nposts\na.md\n").unwrap();
+
+        let err = Manifest::load(&dir.join("changed.txt")).await.unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("lists 3 unusable file(s)"), "{}", message);
+        assert!(message.contains("missing.md: not found"), "{}", message);
+        assert!(message.contains("posts: not a file"), "{}", message);
+        assert!(message.contains("a.md: listed twice"), "{}", message);
+
+        fs::write(dir.join("changed.txt"), "a.md\n").unwrap();
+        let manifest = Manifest::load(&dir.join("changed.txt")).await.unwrap();
+        assert_eq!(manifest.files(), vec![dir.join("a.md")]);
+    }
+}
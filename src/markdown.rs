@@ -2,11 +2,83 @@
 //!
 //! This module provides functionality for parsing markdown files with YAML frontmatter
 //! and formatting them back into complete markdown files.
+//!
+//! A file can have its frontmatter in a sidecar next to it: a
+//! `<file name>.wx.yaml` (e.g. `article.md.wx.yaml`) whenever one exists,
+//! and with the opt-in `sidecar_frontmatter` setting a `<file name>.meta.yaml`
+//! otherwise. Updates are written there, so the markdown file itself is never
+//! modified:
+//!
+//! - A file without a frontmatter block reads its frontmatter from the sidecar,
+//!   which is created on the first update.
+//! - A file with a frontmatter block and a sidecar has the sidecar merged over
+//!   the block: a top-level sidecar key replaces the embedded value whole, and
+//!   a key set to null removes it. The sidecar only holds what differs from
+//!   the block.
+//! - A file with a frontmatter block and no sidecar is read and updated in place.
+//!
+//! The body an upload sends goes through a [`TransformPipeline`] of
+//! [`BodyTransform`] stages, e.g. Obsidian conversion, in memory only: the
+//! markdown file keeps the body as written.
 
 use crate::error::{Error, Result};
-use crate::models::Frontmatter;
+use crate::models::{Frontmatter, ThemeFallback};
+use crate::output::{FORMATTER, OutputFormatter, annotations_enabled};
 use regex::Regex;
-use std::path::Path;
+use std::collections::HashMap;
+use std::io::SeekFrom;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+/// Matches a top-level (unindented) YAML mapping key, optionally quoted
+static TOP_LEVEL_KEY_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^("[^"]+"|'[^']+'|[^\s#'"\-?][^:]*?)\s*:(?:\s|$)"#)
+        .expect("valid top-level key regex")
+});
+
+/// Options controlling how markdown files are parsed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Treat duplicate top-level frontmatter keys as an error instead of a warning
+    pub strict_frontmatter: bool,
+    /// Suppress the duplicate key warning (used when a file is parsed more than once)
+    pub quiet: bool,
+    /// Unknown themes and code highlighters fail parsing only under
+    /// [`ThemeFallback::Error`]; otherwise the caller applies the policy
+    pub theme_fallback: ThemeFallback,
+    /// Read the frontmatter of a file from its sidecar (see [`sidecar_path`])
+    /// when it has no frontmatter block, or merge an existing sidecar over
+    /// the block; only file-based functions look for it. An existing
+    /// `.wx.yaml` sidecar is used either way.
+    pub sidecar_frontmatter: bool,
+    /// Leave [`Frontmatter::issues`] to the caller instead of failing on the
+    /// first invalid value, e.g. to report the invalid values of many files at once
+    pub defer_validation: bool,
+}
+
+/// A top-level frontmatter key that appears more than once
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateKey {
+    /// The key name, without quotes
+    pub key: String,
+    /// 1-based line numbers in the file of every occurrence, in order
+    pub lines: Vec<usize>,
+}
+
+impl std::fmt::Display for DuplicateKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let lines = self
+            .lines
+            .iter()
+            .map(|line| line.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(f, "'{}' on lines {}", self.key, lines)
+    }
+}
 
 /// Parses a markdown file with optional YAML frontmatter.
 ///
@@ -60,24 +132,142 @@ use std::path::Path;
 /// assert_eq!(body, content);
 /// ```
 pub fn parse_markdown(content: &str) -> Result<(Frontmatter, String)> {
-    // Use (?s) flag to make . match newlines
-    let re = Regex::new(r"(?s)^---\n(.*?)\n---\n(.*)$")?;
+    parse_markdown_with(content, &ParseOptions::default())
+}
 
-    if let Some(captures) = re.captures(content) {
-        let yaml_str = captures.get(1).unwrap().as_str();
-        let body = captures.get(2).unwrap().as_str();
+/// Parses markdown content with explicit parse options.
+///
+/// Duplicate top-level frontmatter keys are reported as warnings and the last
+/// occurrence wins, unless `options.strict_frontmatter` is set, in which case
+/// they are an error.
+///
+/// # Errors
+///
+/// Returns an error if the YAML frontmatter is malformed, fails validation, or
+/// contains duplicate keys in strict mode
+pub fn parse_markdown_with(content: &str, options: &ParseOptions) -> Result<(Frontmatter, String)> {
+    parse_markdown_source(content, None, options)
+}
 
-        let frontmatter: Frontmatter = serde_yaml::from_str(yaml_str)?;
+/// Parses markdown content, attributing duplicate key reports to `source` if given
+fn parse_markdown_source(
+    content: &str,
+    source: Option<&Path>,
+    options: &ParseOptions,
+) -> Result<(Frontmatter, String)> {
+    match split_frontmatter(content.as_bytes()) {
+        Some((yaml, body_start)) => {
+            let frontmatter = parse_frontmatter_yaml(&content[yaml], source, options)?;
+            Ok((frontmatter, content[body_start..].to_string()))
+        }
+        // No frontmatter, create default
+        None => Ok((Frontmatter::default(), content.to_string())),
+    }
+}
 
-        // Validate the frontmatter
-        frontmatter.validate()?;
+/// Returns the byte range of the YAML block and the offset of the body if
+/// `content` starts with a `---` delimited frontmatter block
+///
+/// Only the frontmatter is scanned, so the cost does not grow with the body.
+fn split_frontmatter(content: &[u8]) -> Option<(Range<usize>, usize)> {
+    let rest = content.strip_prefix(b"---\n")?;
+    let end = rest.windows(5).position(|window| window == b"\n---\n")?;
+    Some((4..4 + end, 4 + end + 5))
+}
 
-        Ok((frontmatter, body.to_string()))
+/// Parses and validates the YAML of a frontmatter block
+fn parse_frontmatter_yaml(
+    yaml_str: &str,
+    source: Option<&Path>,
+    options: &ParseOptions,
+) -> Result<Frontmatter> {
+    let tab_line = find_tab_indentation(yaml_str);
+    let duplicates = find_duplicate_frontmatter_keys(yaml_str);
+    let yaml_str = if duplicates.is_empty() {
+        yaml_str.to_string()
     } else {
-        // No frontmatter, create default
-        let frontmatter = Frontmatter::default();
-        Ok((frontmatter, content.to_string()))
+        let summary = duplicates
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>()
+            .join("; ");
+
+        if options.strict_frontmatter {
+            let reason = format!("Duplicate frontmatter keys: {}", summary);
+            return Err(match source {
+                Some(path) => Error::markdown_parse(path, reason),
+                None => Error::config(reason),
+            });
+        }
+
+        if !options.quiet
+            && annotations_enabled()
+            && let Some(path) = source
+        {
+            // One annotation per key, on its last (effective) definition
+            for duplicate in &duplicates {
+                FORMATTER.print_file_warning(
+                    path,
+                    duplicate.lines.last().copied(),
+                    &format!("duplicate frontmatter key {}, using the last value", duplicate),
+                );
+            }
+        } else if !options.quiet {
+            let prefix = source
+                .map(|path| format!("{}: ", path.display()))
+                .unwrap_or_default();
+            FORMATTER.print_warning(&format!(
+                "{}duplicate frontmatter keys, using the last value: {}",
+                prefix, summary
+            ));
+        }
+        dedupe_frontmatter_keys(yaml_str)
+    };
+
+    // Older versions wrote an empty frontmatter as a bare `null` block
+    let frontmatter: Frontmatter = if is_null_yaml(&yaml_str) {
+        Frontmatter::default()
+    } else {
+        serde_yaml::from_str(&yaml_str).map_err(|e| match tab_line {
+            // serde_yaml's own message does not point at the tab
+            Some(line) => {
+                let reason = format!(
+                    "frontmatter uses tab indentation on line {}; YAML requires spaces",
+                    line
+                );
+                match source {
+                    Some(path) => Error::markdown_parse(path, reason),
+                    None => Error::config(reason),
+                }
+            }
+            None => Error::from(e),
+        })?
+    };
+
+    // Validate the frontmatter
+    if !options.defer_validation {
+        frontmatter.validate_with(options.theme_fallback)?;
     }
+
+    Ok(frontmatter)
+}
+
+/// Returns the line of the first frontmatter line indented with a tab
+///
+/// Line numbers account for the opening delimiter so they match the file.
+fn find_tab_indentation(yaml: &str) -> Option<usize> {
+    yaml.lines()
+        .position(|line| {
+            line.chars()
+                .take_while(|c| c.is_whitespace())
+                .any(|c| c == '\t')
+        })
+        .map(|index| index + 2)
+}
+
+/// Returns true if a frontmatter block holds only a YAML null
+fn is_null_yaml(yaml: &str) -> bool {
+    matches!(yaml.trim(), "null" | "Null" | "NULL" | "~")
 }
 
 /// Parses a markdown file from a file path
@@ -94,14 +284,364 @@ pub fn parse_markdown(content: &str) -> Result<(Frontmatter, String)> {
 ///
 /// Returns an error if the file cannot be read or parsed
 pub async fn parse_markdown_file<P: AsRef<Path>>(path: P) -> Result<(Frontmatter, String)> {
+    parse_markdown_file_with(path, &ParseOptions::default()).await
+}
+
+/// Parses markdown content read from `path`, reporting duplicate keys against that file
+///
+/// # Errors
+///
+/// Returns an error if the content cannot be parsed, or contains duplicate
+/// frontmatter keys in strict mode
+pub fn parse_markdown_at(
+    content: &str,
+    path: &Path,
+    options: &ParseOptions,
+) -> Result<(Frontmatter, String)> {
+    parse_markdown_source(content, Some(path), options)
+}
+
+/// Parses a markdown file from a file path with explicit parse options
+///
+/// With `options.sidecar_frontmatter`, the frontmatter of a file without a
+/// frontmatter block is read from its sidecar, and the body is the whole file.
+/// An existing sidecar of a file with a block is merged over the block.
+///
+/// # Errors
+///
+/// Returns an error if the file or its sidecar cannot be read or parsed, or
+/// contains duplicate frontmatter keys in strict mode
+pub async fn parse_markdown_file_with<P: AsRef<Path>>(
+    path: P,
+    options: &ParseOptions,
+) -> Result<(Frontmatter, String)> {
+    let path = path.as_ref();
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| Error::io_at(path, e))?;
+
+    if sidecar_enabled(path, options) {
+        match split_frontmatter(content.as_bytes()) {
+            None => return Ok((read_sidecar(path, options).await?, content)),
+            Some((yaml, body_start)) if has_sidecar(path).await => {
+                let frontmatter = read_merged_sidecar(path, &content[yaml], options).await?;
+                return Ok((frontmatter, content[body_start..].to_string()));
+            }
+            Some(_) => {}
+        }
+    }
+    parse_markdown_at(&content, path, options).map_err(|e| file_parse_error(path, e))
+}
+
+/// Parses only the frontmatter of a markdown file; the body is not read
+///
+/// Use this when scanning many files for their metadata, so large articles
+/// cost no more than small ones. A file without frontmatter yields an empty
+/// [`Frontmatter`], or its sidecar's with `options.sidecar_frontmatter`,
+/// which also merges an existing sidecar over a frontmatter block.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or its frontmatter cannot be
+/// parsed, or contains duplicate keys in strict mode
+pub async fn parse_frontmatter_file_with<P: AsRef<Path>>(
+    path: P,
+    options: &ParseOptions,
+) -> Result<Frontmatter> {
     let path = path.as_ref();
-    let content = tokio::fs::read_to_string(path).await?;
+    match read_frontmatter_head(path).await? {
+        Some(head) if sidecar_enabled(path, options) && has_sidecar(path).await => {
+            read_merged_sidecar(path, &head.yaml, options).await
+        }
+        Some(head) => parse_frontmatter_yaml(&head.yaml, Some(path), options)
+            .map_err(|e| file_parse_error(path, e)),
+        None if sidecar_enabled(path, options) => read_sidecar(path, options).await,
+        None => Ok(Frontmatter::default()),
+    }
+}
+
+/// Returns the sidecar frontmatter file of the markdown file at `path`
+///
+/// That is `<file name>.wx.yaml` if it exists, and `<file name>.meta.yaml`
+/// otherwise, so a `.wx.yaml` wins when a file has both.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use wx_uploader::markdown::sidecar_path;
+///
+/// assert_eq!(
+///     sidecar_path(Path::new("posts/article.md")),
+///     Path::new("posts/article.md.meta.yaml")
+/// );
+/// ```
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let wx_sidecar = sidecar_with_suffix(path, ".wx.yaml");
+    if wx_sidecar.exists() {
+        return wx_sidecar;
+    }
+    sidecar_with_suffix(path, ".meta.yaml")
+}
+
+/// Returns the file named after `path` plus `suffix`
+fn sidecar_with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
 
-    parse_markdown(&content).map_err(|e| match e {
+/// Returns true if sidecars are looked for next to `path`: with
+/// `options.sidecar_frontmatter`, or when its `.wx.yaml` sidecar exists
+fn sidecar_enabled(path: &Path, options: &ParseOptions) -> bool {
+    options.sidecar_frontmatter || sidecar_with_suffix(path, ".wx.yaml").exists()
+}
+
+/// Returns true if the frontmatter of the file at `path` lives in its sidecar
+///
+/// That is the case for a file with a `.wx.yaml` sidecar, and with
+/// `options.sidecar_frontmatter` for a file without a frontmatter block,
+/// whether or not the sidecar exists yet, and for a file with a block whose
+/// sidecar exists.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read
+pub async fn uses_sidecar(path: &Path, options: &ParseOptions) -> Result<bool> {
+    Ok(sidecar_enabled(path, options)
+        && (has_sidecar(path).await || read_frontmatter_head(path).await?.is_none()))
+}
+
+/// Returns true if the sidecar of `path` exists
+async fn has_sidecar(path: &Path) -> bool {
+    tokio::fs::try_exists(sidecar_path(path)).await.unwrap_or(false)
+}
+
+/// Reads the sidecar frontmatter of `path`; a missing or empty sidecar yields
+/// an empty [`Frontmatter`]
+async fn read_sidecar(path: &Path, options: &ParseOptions) -> Result<Frontmatter> {
+    let sidecar = sidecar_path(path);
+    let yaml = match tokio::fs::read_to_string(&sidecar).await {
+        Ok(yaml) => yaml,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Frontmatter::default()),
+        Err(e) => return Err(Error::io_at(&sidecar, e)),
+    };
+    if yaml.trim().is_empty() {
+        return Ok(Frontmatter::default());
+    }
+    parse_frontmatter_yaml(&yaml, Some(&sidecar), options).map_err(|e| file_parse_error(&sidecar, e))
+}
+
+/// Returns the frontmatter block `yaml` of `path` with its sidecar merged over it
+///
+/// Errors of the merged frontmatter are reported against the sidecar, since
+/// the block parsed fine on its own.
+async fn read_merged_sidecar(
+    path: &Path,
+    yaml: &str,
+    options: &ParseOptions,
+) -> Result<Frontmatter> {
+    let embedded =
+        parse_frontmatter_yaml(yaml, Some(path), options).map_err(|e| file_parse_error(path, e))?;
+    let sidecar = sidecar_path(path);
+    let content = tokio::fs::read_to_string(&sidecar)
+        .await
+        .map_err(|e| Error::io_at(&sidecar, e))?;
+    let overlay = match serde_yaml::from_str(&content) {
+        Ok(serde_yaml::Value::Mapping(overlay)) => overlay,
+        Ok(serde_yaml::Value::Null) => serde_yaml::Mapping::new(),
+        Ok(_) => {
+            return Err(Error::markdown_parse(&sidecar, "sidecar frontmatter must be a mapping"));
+        }
+        Err(e) => return Err(file_parse_error(&sidecar, Error::from(e))),
+    };
+
+    let mut merged = frontmatter_mapping(&embedded)?;
+    for (key, value) in overlay {
+        if value.is_null() {
+            merged.remove(&key);
+        } else {
+            merged.insert(key, value);
+        }
+    }
+    let frontmatter: Frontmatter = serde_yaml::from_value(serde_yaml::Value::Mapping(merged))
+        .map_err(|e| file_parse_error(&sidecar, Error::from(e)))?;
+    if !options.defer_validation {
+        frontmatter
+            .validate_with(options.theme_fallback)
+            .map_err(|e| file_parse_error(&sidecar, e))?;
+    }
+    Ok(frontmatter)
+}
+
+/// Returns the top-level keys of `frontmatter` as they are serialized
+fn frontmatter_mapping(frontmatter: &Frontmatter) -> Result<serde_yaml::Mapping> {
+    match serde_yaml::to_value(frontmatter)? {
+        serde_yaml::Value::Mapping(mapping) => Ok(mapping),
+        _ => Ok(serde_yaml::Mapping::new()),
+    }
+}
+
+/// Writes `frontmatter` to the sidecar of `path`
+///
+/// For a file with a frontmatter block, only the keys that differ from the
+/// block are written, with null for the keys to remove from it.
+async fn write_sidecar(path: &Path, frontmatter: &Frontmatter) -> Result<()> {
+    let target = frontmatter_mapping(frontmatter)?;
+    let mut overlay = target.clone();
+    if let Some(head) = read_frontmatter_head(path).await? {
+        let options = ParseOptions {
+            quiet: true,
+            theme_fallback: ThemeFallback::Default,
+            ..Default::default()
+        };
+        let embedded = parse_frontmatter_yaml(&head.yaml, Some(path), &options)
+            .map_err(|e| file_parse_error(path, e))?;
+        let embedded = frontmatter_mapping(&embedded)?;
+        overlay.retain(|key, value| embedded.get(key) != Some(value));
+        for key in embedded.keys().filter(|key| !target.contains_key(*key)) {
+            overlay.insert(key.clone(), serde_yaml::Value::Null);
+        }
+    }
+
+    let yaml = if overlay.is_empty() {
+        String::new()
+    } else {
+        serde_yaml::to_string(&overlay)?
+    };
+    write_file_atomic(&sidecar_path(path), yaml.as_bytes()).await
+}
+
+/// Attributes YAML and regex errors to the file being parsed
+fn file_parse_error(path: &Path, error: Error) -> Error {
+    match error {
         Error::Yaml(_) => Error::markdown_parse(path, "Failed to parse YAML frontmatter"),
         Error::Regex(_) => Error::markdown_parse(path, "Failed to parse markdown structure"),
         other => other,
-    })
+    }
+}
+
+/// Bytes read at a time while looking for the end of the frontmatter
+const HEAD_CHUNK_SIZE: usize = 8 * 1024;
+
+/// The frontmatter block at the start of a file
+#[derive(Debug)]
+struct FileHead {
+    /// YAML between the `---` delimiters
+    yaml: String,
+    /// Offset of the body within the file
+    body_start: u64,
+}
+
+/// Reads a file up to the end of its frontmatter block
+///
+/// Returns `None`, having read at most the first chunk, if the file does not
+/// start with a frontmatter block; a block that is never closed is read to
+/// the end of the file, like [`split_frontmatter`] would.
+async fn read_frontmatter_head(path: &Path) -> Result<Option<FileHead>> {
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .map_err(|e| Error::io_at(path, e))?;
+    let mut head = Vec::with_capacity(HEAD_CHUNK_SIZE);
+    let mut chunk = vec![0; HEAD_CHUNK_SIZE];
+    // Closing delimiters starting before this offset have been searched for
+    let mut searched = 4;
+
+    loop {
+        let read = file.read(&mut chunk).await?;
+        head.extend_from_slice(&chunk[..read]);
+
+        let opening = &head[..head.len().min(4)];
+        if !b"---\n".starts_with(opening) {
+            return Ok(None);
+        }
+        if head.len() >= 9 {
+            // Delimiters split across chunks are found by backing up 4 bytes
+            let from = searched.max(4);
+            if let Some(end) = head[from..].windows(5).position(|window| window == b"\n---\n") {
+                let end = from + end;
+                let yaml = String::from_utf8(head[4..end].to_vec()).map_err(|_| {
+                    Error::markdown_parse(path, "Frontmatter is not valid UTF-8")
+                })?;
+                return Ok(Some(FileHead {
+                    yaml,
+                    body_start: (end + 5) as u64,
+                }));
+            }
+            searched = head.len() - 4;
+        }
+        if read == 0 {
+            return Ok(None);
+        }
+    }
+}
+
+/// Finds top-level frontmatter keys that are defined more than once.
+///
+/// `yaml` is the raw frontmatter block without the `---` delimiters; reported
+/// line numbers account for the opening delimiter so they match the file.
+pub fn find_duplicate_frontmatter_keys(yaml: &str) -> Vec<DuplicateKey> {
+    let mut occurrences: Vec<DuplicateKey> = Vec::new();
+
+    for (index, line) in yaml.lines().enumerate() {
+        let Some(key) = top_level_key(line) else {
+            continue;
+        };
+        let line_no = index + 2;
+        match occurrences.iter_mut().find(|d| d.key == key) {
+            Some(existing) => existing.lines.push(line_no),
+            None => occurrences.push(DuplicateKey {
+                key,
+                lines: vec![line_no],
+            }),
+        }
+    }
+
+    occurrences.retain(|d| d.lines.len() > 1);
+    occurrences
+}
+
+/// Removes all but the last definition of each duplicated top-level key.
+///
+/// A definition spans the key line and every following line up to the next
+/// top-level key, so nested mappings, sequences and block scalars move with it.
+fn dedupe_frontmatter_keys(yaml: &str) -> String {
+    // Split into (key, lines) entries; leading comments/blank lines have no key
+    let mut entries: Vec<(Option<String>, Vec<&str>)> = Vec::new();
+    for line in yaml.lines() {
+        match top_level_key(line) {
+            Some(key) => entries.push((Some(key), vec![line])),
+            None => match entries.last_mut() {
+                Some((_, lines)) => lines.push(line),
+                None => entries.push((None, vec![line])),
+            },
+        }
+    }
+
+    let mut last_index: HashMap<&str, usize> = HashMap::new();
+    for (index, (key, _)) in entries.iter().enumerate() {
+        if let Some(key) = key {
+            last_index.insert(key.as_str(), index);
+        }
+    }
+
+    entries
+        .iter()
+        .enumerate()
+        .filter(|(index, (key, _))| match key {
+            Some(key) => last_index.get(key.as_str()) == Some(index),
+            None => true,
+        })
+        .flat_map(|(_, (_, lines))| lines.iter().copied())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Returns the unquoted key if the line starts a top-level mapping entry
+fn top_level_key(line: &str) -> Option<String> {
+    let captures = TOP_LEVEL_KEY_RE.captures(line)?;
+    let key = captures[1].trim();
+    Some(key.trim_matches(|c| c == '"' || c == '\'').to_string())
 }
 
 /// Formats frontmatter and body content back into a complete markdown file.
@@ -119,6 +659,9 @@ pub async fn parse_markdown_file<P: AsRef<Path>>(path: P) -> Result<(Frontmatter
 ///
 /// A complete markdown file as a string with frontmatter and body
 ///
+/// An empty frontmatter produces no block at all, so the result never
+/// contains a bare `{}` or `null` marker.
+///
 /// # Errors
 ///
 /// Returns an error if the frontmatter cannot be serialized to YAML
@@ -126,11 +669,11 @@ pub async fn parse_markdown_file<P: AsRef<Path>>(path: P) -> Result<(Frontmatter
 /// # Examples
 ///
 /// ```
-/// use wx_uploader::{models::Frontmatter, markdown::format_markdown};
+/// use wx_uploader::{models::{Frontmatter, PublicationStatus}, markdown::format_markdown};
 ///
 /// let mut frontmatter = Frontmatter::default();
 /// frontmatter.set_title("My Article");
-/// frontmatter.set_published("draft");
+/// frontmatter.set_published(PublicationStatus::Draft);
 ///
 /// let body = "# Hello World\n\nThis is content.";
 /// let result = format_markdown(&frontmatter, body).unwrap();
@@ -140,13 +683,212 @@ pub async fn parse_markdown_file<P: AsRef<Path>>(path: P) -> Result<(Frontmatter
 /// assert!(result.contains("Hello World"));
 /// ```
 pub fn format_markdown(frontmatter: &Frontmatter, body: &str) -> Result<String> {
+    if frontmatter.is_empty() {
+        return Ok(body.to_string());
+    }
+
     let yaml = serde_yaml::to_string(frontmatter)?;
 
     Ok(format!("---\n{yaml}---\n{body}"))
 }
 
+/// The frontmatter block of a markdown file exactly as written
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RawFrontmatter {
+    /// YAML between the `---` delimiters, comments and formatting included;
+    /// empty if the file has no frontmatter
+    pub yaml: String,
+    /// Byte range of `yaml` within the parsed content
+    pub span: Range<usize>,
+}
+
+impl RawFrontmatter {
+    /// Returns true if the file had no frontmatter block
+    pub fn is_empty(&self) -> bool {
+        self.yaml.is_empty()
+    }
+}
+
+/// A targeted change to a single top-level frontmatter key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrontmatterEdit {
+    /// Sets the key to a string, replacing its effective (last) definition,
+    /// or appending it if the key is missing
+    Set(String, String),
+    /// Sets the key to any YAML value, such as the boolean `true`, like [`Set`](Self::Set)
+    SetValue(String, serde_yaml::Value),
+    /// Removes every definition of the key
+    Remove(String),
+}
+
+/// Parses markdown content like [`parse_markdown`], also returning the
+/// frontmatter block as written
+///
+/// # Errors
+///
+/// Returns an error if the YAML frontmatter is malformed or fails validation
+///
+/// # Examples
+///
+/// ```
+/// use wx_uploader::markdown::parse_markdown_raw;
+///
+/// let content = "---\ntitle: Hello # shown in the list\n---\nBody\n";
+/// let (raw, frontmatter, body) = parse_markdown_raw(content).unwrap();
+/// assert_eq!(raw.yaml, "title: Hello # shown in the list");
+/// assert_eq!(&content[raw.span.clone()], raw.yaml);
+/// assert_eq!(frontmatter.title.as_deref(), Some("Hello"));
+/// assert_eq!(body, "Body\n");
+/// ```
+pub fn parse_markdown_raw(content: &str) -> Result<(RawFrontmatter, Frontmatter, String)> {
+    let (frontmatter, body) = parse_markdown(content)?;
+    let raw = split_frontmatter(content.as_bytes())
+        .map(|(span, _)| RawFrontmatter {
+            yaml: content[span.clone()].to_string(),
+            span,
+        })
+        .unwrap_or_default();
+    Ok((raw, frontmatter, body))
+}
+
+/// Formats a markdown file from its raw frontmatter with `edits` applied
+///
+/// Unlike [`format_markdown`], the block is not re-serialized: comments,
+/// key order, quoting and indentation of untouched keys are kept, and a
+/// replaced value keeps the trailing comment of its key line. Removing every
+/// key drops the block like [`format_markdown`] does for an empty frontmatter.
+///
+/// # Errors
+///
+/// Returns an error if the edited frontmatter is no longer valid
+///
+/// # Examples
+///
+/// ```
+/// use wx_uploader::markdown::{FrontmatterEdit, format_markdown_preserving, parse_markdown_raw};
+///
+/// let content = "---\n# managed by hand\ntitle: Hello\npublished: draft # by wx\n---\nBody\n";
+/// let (raw, _, body) = parse_markdown_raw(content).unwrap();
+/// let edits = [FrontmatterEdit::Set("published".into(), "published".into())];
+///
+/// assert_eq!(
+///     format_markdown_preserving(&raw, &edits, &body).unwrap(),
+///     "---\n# managed by hand\ntitle: Hello\npublished: published # by wx\n---\nBody\n"
+/// );
+/// ```
+pub fn format_markdown_preserving(
+    raw: &RawFrontmatter,
+    edits: &[FrontmatterEdit],
+    body: &str,
+) -> Result<String> {
+    let mut lines: Vec<String> = raw.yaml.lines().map(str::to_string).collect();
+    for edit in edits {
+        match edit {
+            FrontmatterEdit::Set(key, value) => {
+                set_raw_key(&mut lines, key, &serde_yaml::Value::String(value.clone()))?
+            }
+            FrontmatterEdit::SetValue(key, value) => set_raw_key(&mut lines, key, value)?,
+            FrontmatterEdit::Remove(key) => {
+                for entry in key_entries(&lines, key).into_iter().rev() {
+                    lines.drain(entry);
+                }
+            }
+        }
+    }
+
+    let yaml = lines.join("\n");
+    if yaml.trim().is_empty() {
+        return Ok(body.to_string());
+    }
+
+    // Catch edits that leave a block the next parse would reject
+    let value: serde_yaml::Value = serde_yaml::from_str(&dedupe_frontmatter_keys(&yaml))?;
+    if !value.is_null() {
+        serde_yaml::from_value::<Frontmatter>(value)?;
+    }
+
+    Ok(format!("---\n{yaml}\n---\n{body}"))
+}
+
+/// Points `key` at `value` in raw frontmatter lines
+fn set_raw_key(lines: &mut Vec<String>, key: &str, value: &serde_yaml::Value) -> Result<()> {
+    let value = serde_yaml::to_string(value)?;
+    let value = value.trim_end();
+
+    let Some(entry) = key_entries(lines, key).pop() else {
+        lines.push(format!("{}: {}", key, value));
+        return Ok(());
+    };
+
+    let line = &lines[entry.start];
+    // `key:` as written, keeping its quoting
+    let prefix = TOP_LEVEL_KEY_RE
+        .find(line)
+        .map_or(line.as_str(), |key| key.as_str())
+        .trim_end();
+    let comment = inline_comment(line)
+        .map(|comment| format!(" {}", comment))
+        .unwrap_or_default();
+    let replacement = format!("{} {}{}", prefix, value, comment);
+    lines.splice(entry, [replacement]);
+    Ok(())
+}
+
+/// Returns the line ranges of every definition of top-level `key`
+///
+/// A definition spans its key line and the indented lines and column-0
+/// sequence items after it. Blank lines and column-0 comments that follow are
+/// left out, so they survive when the definition is replaced or removed.
+fn key_entries(lines: &[String], key: &str) -> Vec<Range<usize>> {
+    let mut entries = Vec::new();
+    for (start, line) in lines.iter().enumerate() {
+        if top_level_key(line).as_deref() != Some(key) {
+            continue;
+        }
+        let mut end = start + 1;
+        for (index, line) in lines.iter().enumerate().skip(start + 1) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if !line.starts_with([' ', '\t', '-']) {
+                break;
+            }
+            end = index + 1;
+        }
+        entries.push(start..end);
+    }
+    entries
+}
+
+/// Returns the trailing `# comment` of a YAML line, ignoring `#` in quotes
+fn inline_comment(line: &str) -> Option<&str> {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut prev = ' ';
+    for (index, c) in line.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some('\'') if c == '\'' && line[index + 1..].starts_with('\'') => escaped = true,
+            Some(open) if c == open => quote = None,
+            Some(_) => {}
+            // Quotes only open a scalar, so the apostrophe in `don't` is text
+            None if matches!(c, '"' | '\'') && (prev.is_whitespace() || "[{,:".contains(prev)) => {
+                quote = Some(c);
+            }
+            None if c == '#' && prev.is_whitespace() => return Some(&line[index..]),
+            None => {}
+        }
+        prev = c;
+    }
+    None
+}
+
 /// Writes a markdown file with frontmatter to disk
 ///
+/// The file is replaced atomically (see [`write_file_atomic`]), so readers and
+/// concurrent writers never see a half-written file.
+///
 /// # Arguments
 ///
 /// * `path` - Path where to write the file
@@ -162,9 +904,164 @@ pub async fn write_markdown_file<P: AsRef<Path>>(
     body: &str,
 ) -> Result<()> {
     let content = format_markdown(frontmatter, body)?;
-    let path = path.as_ref();
+    write_file_atomic(path.as_ref(), content.as_bytes()).await
+}
+
+/// Writes a markdown file like [`write_markdown_file`], or only its frontmatter
+/// to its sidecar if [`uses_sidecar`] says so
+///
+/// The markdown file is left untouched in the latter case.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or written or frontmatter
+/// cannot be serialized
+pub async fn write_markdown_file_with(
+    path: &Path,
+    frontmatter: &Frontmatter,
+    body: &str,
+    options: &ParseOptions,
+) -> Result<()> {
+    if uses_sidecar(path, options).await? {
+        return write_sidecar(path, frontmatter).await;
+    }
+    write_markdown_file(path, frontmatter, body).await
+}
+
+/// Hash of a markdown file and its sidecar, taken to tell whether either
+/// changed on disk since they were read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileFingerprint(blake3::Hash);
+
+impl FileFingerprint {
+    /// Hashes the file at `path` and, if sidecars are used, its sidecar
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file or an existing sidecar cannot be read
+    pub async fn of(path: &Path, options: &ParseOptions) -> Result<Self> {
+        let content = tokio::fs::read(path)
+            .await
+            .map_err(|e| Error::io_at(path, e))?;
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&(content.len() as u64).to_le_bytes());
+        hasher.update(&content);
+
+        if sidecar_enabled(path, options) {
+            let sidecar = sidecar_path(path);
+            match tokio::fs::read(&sidecar).await {
+                Ok(content) => {
+                    hasher.update(b"+");
+                    hasher.update(&content);
+                }
+                // A sidecar created since is a change too
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    hasher.update(b"-");
+                }
+                Err(e) => return Err(Error::io_at(&sidecar, e)),
+            }
+        }
+        Ok(Self(hasher.finalize()))
+    }
+}
 
-    tokio::fs::write(path, content).await.map_err(Error::from)
+/// Distinguishes temp files of concurrent writes within this process
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Replaces a file by writing a temp file in the same directory and renaming it
+///
+/// A crash or a concurrent reader sees either the old or the new content,
+/// never a truncated file. A symlink is followed so the link itself is kept,
+/// and on unix the permissions of the original file are preserved.
+///
+/// # Errors
+///
+/// Returns an error if the temp file cannot be written or renamed; the
+/// original file is left untouched in that case
+pub async fn write_file_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    replace_file_atomic(path, async |temp_path: &Path| tokio::fs::write(temp_path, content).await)
+        .await
+}
+
+/// Replaces a file with a temp file filled by `write`, as [`write_file_atomic`] does
+async fn replace_file_atomic(
+    path: &Path,
+    write: impl AsyncFnOnce(&Path) -> std::io::Result<()>,
+) -> Result<()> {
+    let target = match tokio::fs::symlink_metadata(path).await {
+        Ok(metadata) if metadata.file_type().is_symlink() => tokio::fs::canonicalize(path).await?,
+        _ => path.to_path_buf(),
+    };
+
+    let file_name = target.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = target.with_file_name(format!(
+        ".{}.{}.{}.tmp",
+        file_name,
+        std::process::id(),
+        TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let result = async {
+        write(&temp_path).await?;
+
+        #[cfg(unix)]
+        if let Ok(metadata) = tokio::fs::metadata(&target).await {
+            tokio::fs::set_permissions(&temp_path, metadata.permissions()).await?;
+        }
+
+        tokio::fs::rename(&temp_path, &target).await
+    }
+    .await;
+
+    if let Err(e) = result {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(Error::from(e));
+    }
+    Ok(())
+}
+
+/// Returns the text of the first H1 (`# `) heading, ignoring fenced code blocks
+pub fn extract_h1_title(body: &str) -> Option<String> {
+    let mut in_fence = false;
+
+    for line in body.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+
+        if let Some(heading) = trimmed.strip_prefix("# ") {
+            let heading = heading.trim().trim_end_matches('#').trim();
+            if !heading.is_empty() {
+                return Some(heading.to_string());
+            }
+        }
+    }
+
+    None
+}
+
+/// Fills in the title of a frontmatter block created for a file that had none
+///
+/// The title comes from the first H1 heading, falling back to the file stem.
+/// An existing title is left untouched.
+pub fn fill_new_frontmatter(frontmatter: &mut Frontmatter, body: &str, path: &Path) {
+    if frontmatter.title.is_some() {
+        return;
+    }
+
+    let title = extract_h1_title(body).or_else(|| {
+        path.file_stem()
+            .and_then(|s| s.to_str())
+            .map(str::to_string)
+    });
+    if let Some(title) = title {
+        frontmatter.set_title(title);
+    }
 }
 
 /// Updates the frontmatter of a markdown file in place
@@ -181,21 +1078,236 @@ pub async fn write_markdown_file<P: AsRef<Path>>(
 ///
 /// Returns an error if the file cannot be read, parsed, or written
 pub async fn update_frontmatter<P: AsRef<Path>, F>(path: P, updater: F) -> Result<()>
+where
+    F: FnOnce(&mut Frontmatter) -> Result<()>,
+{
+    update_frontmatter_with(path, &ParseOptions::default(), updater).await
+}
+
+/// Updates the frontmatter of a markdown file like [`update_frontmatter`], or
+/// of its sidecar
+///
+/// With `options.sidecar_frontmatter`, a file without a frontmatter block
+/// has its sidecar updated, or created, and is itself left untouched, as is a
+/// file with a block and a sidecar, whose sidecar gets the changes. The other
+/// options are not used.
+///
+/// # Errors
+///
+/// Returns an error if the file or its sidecar cannot be read, parsed, or written
+pub async fn update_frontmatter_with<P: AsRef<Path>, F>(
+    path: P,
+    options: &ParseOptions,
+    updater: F,
+) -> Result<()>
 where
     F: FnOnce(&mut Frontmatter) -> Result<()>,
 {
     let path = path.as_ref();
-    let (mut frontmatter, body) = parse_markdown_file(path).await?;
+    // An unknown theme is kept as it is; the upload run has applied its policy
+    let options = ParseOptions {
+        theme_fallback: ThemeFallback::Default,
+        sidecar_frontmatter: options.sidecar_frontmatter,
+        ..Default::default()
+    };
+
+    let head = read_frontmatter_head(path).await?;
+    if let Some(head) = &head
+        && sidecar_enabled(path, &options)
+        && has_sidecar(path).await
+    {
+        let mut frontmatter = read_merged_sidecar(path, &head.yaml, &options).await?;
+        updater(&mut frontmatter)?;
+        return write_sidecar(path, &frontmatter).await;
+    }
+
+    // Only the frontmatter is rewritten; the body is copied byte for byte
+    if let Some(head) = head {
+        let mut frontmatter = parse_frontmatter_yaml(&head.yaml, Some(path), &options)
+            .map_err(|e| file_parse_error(path, e))?;
+        updater(&mut frontmatter)?;
+        let block = format_markdown(&frontmatter, "")?;
+
+        return replace_file_atomic(path, async |temp_path: &Path| {
+            let mut source = tokio::fs::File::open(path).await?;
+            source.seek(SeekFrom::Start(head.body_start)).await?;
+            let mut temp = tokio::fs::File::create(temp_path).await?;
+            temp.write_all(block.as_bytes()).await?;
+            tokio::io::copy(&mut source, &mut temp).await?;
+            temp.flush().await
+        })
+        .await;
+    }
+
+    if sidecar_enabled(path, &options) {
+        let mut frontmatter = read_sidecar(path, &options).await?;
+        let created = frontmatter.is_empty();
+        updater(&mut frontmatter)?;
+        if created && !frontmatter.is_empty() {
+            let body = tokio::fs::read_to_string(path)
+                .await
+                .map_err(|e| Error::io_at(path, e))?;
+            fill_new_frontmatter(&mut frontmatter, &body, path);
+        }
+        return write_sidecar(path, &frontmatter).await;
+    }
+
+    let (mut frontmatter, body) = parse_markdown_file_with(path, &options).await?;
+    let created = frontmatter.is_empty();
+
+    updater(&mut frontmatter)?;
+
+    // A file without frontmatter gets a minimal block with a proper title
+    if created && !frontmatter.is_empty() {
+        fill_new_frontmatter(&mut frontmatter, &body, path);
+    }
+
+    write_markdown_file(path, &frontmatter, &body).await
+}
+
+/// What a [`BodyTransform`] knows about the article whose body it rewrites
+#[derive(Debug, Clone, Copy)]
+pub struct ArticleContext<'a> {
+    /// Path of the markdown file
+    pub path: &'a Path,
+    /// Frontmatter of the file, with the theme and code highlighter of the upload
+    pub frontmatter: &'a Frontmatter,
+    /// Whether verbose output is on, for stages that log what they changed
+    pub verbose: bool,
+}
+
+/// A stage that rewrites the body of an article before it is uploaded
+///
+/// Stages run on the body in memory and must not touch the file. They are
+/// selected and ordered by [`BodyTransform::name`] in the `transforms`
+/// setting or frontmatter field; see [`TransformPipeline`].
+pub trait BodyTransform: Send + Sync {
+    /// Name the stage is selected by, e.g. `anchor-links`
+    fn name(&self) -> &str;
+
+    /// Returns the rewritten body, or `body` itself if there is nothing to change
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the body cannot be rewritten; the upload of the
+    /// file then fails
+    fn apply(&self, ctx: &ArticleContext<'_>, body: String) -> Result<String>;
+}
+
+/// A [`BodyTransform`] made from a name and a closure
+pub struct FnTransform<F> {
+    name: String,
+    transform: F,
+}
+
+impl<F> FnTransform<F>
+where
+    F: Fn(&ArticleContext<'_>, String) -> Result<String> + Send + Sync,
+{
+    /// Creates a stage named `name` that rewrites the body with `transform`
+    pub fn new(name: impl Into<String>, transform: F) -> Self {
+        Self {
+            name: name.into(),
+            transform,
+        }
+    }
+}
+
+impl<F> BodyTransform for FnTransform<F>
+where
+    F: Fn(&ArticleContext<'_>, String) -> Result<String> + Send + Sync,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn apply(&self, ctx: &ArticleContext<'_>, body: String) -> Result<String> {
+        (self.transform)(ctx, body)
+    }
+}
+
+/// Ordered [`BodyTransform`] stages, each fed the body the previous one returned
+#[derive(Clone, Default)]
+pub struct TransformPipeline {
+    stages: Vec<Arc<dyn BodyTransform>>,
+}
+
+impl TransformPipeline {
+    /// Creates a pipeline running `stages` in order
+    pub fn new(stages: Vec<Arc<dyn BodyTransform>>) -> Self {
+        Self { stages }
+    }
+
+    /// Appends a stage
+    pub fn push(&mut self, stage: Arc<dyn BodyTransform>) {
+        self.stages.push(stage);
+    }
+
+    /// Returns the stages in the order they run
+    pub fn stages(&self) -> &[Arc<dyn BodyTransform>] {
+        &self.stages
+    }
 
-    updater(&mut frontmatter)?;
+    /// Returns the stage named `name`, if any
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn BodyTransform>> {
+        self.stages.iter().find(|stage| stage.name() == name)
+    }
 
-    write_markdown_file(path, &frontmatter, &body).await
+    /// Returns the names of the stages in the order they run
+    pub fn names(&self) -> Vec<&str> {
+        self.stages.iter().map(|stage| stage.name()).collect()
+    }
+
+    /// Returns true if the pipeline has no stages
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// Runs every stage over `body` in order
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the first failing stage, naming it
+    pub fn apply(&self, ctx: &ArticleContext<'_>, body: String) -> Result<String> {
+        self.stages
+            .iter()
+            .try_fold(body, |body, stage| apply_stage(stage.as_ref(), ctx, body))
+    }
+}
+
+/// Runs one stage, naming it in its error
+pub fn apply_stage(
+    stage: &dyn BodyTransform,
+    ctx: &ArticleContext<'_>,
+    body: String,
+) -> Result<String> {
+    stage.apply(ctx, body).map_err(|e| {
+        Error::markdown_parse(ctx.path, format!("body transform '{}' failed: {}", stage.name(), e))
+    })
+}
+
+impl std::fmt::Debug for TransformPipeline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("TransformPipeline").field(&self.names()).finish()
+    }
+}
+
+/// Pipelines are equal if they run the same stage instances in the same order
+impl PartialEq for TransformPipeline {
+    fn eq(&self, other: &Self) -> bool {
+        self.stages.len() == other.stages.len()
+            && self
+                .stages
+                .iter()
+                .zip(&other.stages)
+                .all(|(a, b)| Arc::ptr_eq(a, b))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::Frontmatter;
+    use crate::models::{Frontmatter, PublicationStatus};
     use tempfile::TempDir;
 
     #[test]
@@ -213,7 +1325,7 @@ This is the content of the article.
         let (frontmatter, body) = parse_markdown(content).unwrap();
 
         assert_eq!(frontmatter.title, Some("Test Article".to_string()));
-        assert_eq!(frontmatter.published, Some("draft".to_string()));
+        assert_eq!(frontmatter.publication_status(), PublicationStatus::Draft);
         assert_eq!(
             body,
             "# Hello World\n\nThis is the content of the article.\n"
@@ -266,10 +1378,126 @@ Content here.
         let (frontmatter, body) = parse_markdown(content).unwrap();
 
         assert_eq!(frontmatter.title, None);
-        assert_eq!(frontmatter.published, Some("true".to_string()));
+        assert_eq!(frontmatter.publication_status(), PublicationStatus::Published);
         assert_eq!(body, "Content here.\n");
     }
 
+    #[test]
+    fn test_find_duplicate_frontmatter_keys() {
+        let yaml = "title: First\npublished: draft\ntags:\n  - a\ntitle: Second\n\"published\": \"true\"";
+        let duplicates = find_duplicate_frontmatter_keys(yaml);
+
+        assert_eq!(
+            duplicates,
+            vec![
+                DuplicateKey {
+                    key: "title".to_string(),
+                    lines: vec![2, 6],
+                },
+                DuplicateKey {
+                    key: "published".to_string(),
+                    lines: vec![3, 7],
+                },
+            ]
+        );
+        assert!(find_duplicate_frontmatter_keys("title: A\nnested:\n  title: B").is_empty());
+    }
+
+    #[test]
+    fn test_parse_markdown_duplicate_typed_key_last_wins() {
+        let content = "---\npublished: draft\ntitle: Dup\npublished: \"true\"\n---\nBody\n";
+
+        let (frontmatter, body) = parse_markdown(content).unwrap();
+
+        assert_eq!(frontmatter.publication_status(), PublicationStatus::Published);
+        assert_eq!(frontmatter.title, Some("Dup".to_string()));
+        assert_eq!(body, "Body\n");
+    }
+
+    #[test]
+    fn test_parse_markdown_duplicate_untyped_key_last_wins() {
+        let content = "---\nauthor: Alice\ntags:\n  - old\nauthor: Bob\ntags:\n  - new\n---\nBody\n";
+
+        let (frontmatter, _body) = parse_markdown(content).unwrap();
+
+        let serde_yaml::Value::Mapping(map) = &frontmatter.other else {
+            panic!("Expected mapping for other fields");
+        };
+        assert_eq!(
+            map.get(serde_yaml::Value::String("author".to_string())),
+            Some(&serde_yaml::Value::String("Bob".to_string()))
+        );
+        let tags = map
+            .get(serde_yaml::Value::String("tags".to_string()))
+            .unwrap();
+        assert_eq!(serde_yaml::to_string(tags).unwrap(), "- new\n");
+    }
+
+    #[test]
+    fn test_parse_markdown_duplicate_keys_strict() {
+        let content = "---\ntitle: One\ndescription: x\ntitle: Two\n---\nBody\n";
+        let options = ParseOptions {
+            strict_frontmatter: true,
+            ..Default::default()
+        };
+
+        let err = parse_markdown_with(content, &options).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("'title' on lines 2, 4"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn test_update_frontmatter_writes_single_instance() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("dup.md");
+        tokio::fs::write(
+            &file_path,
+            "---\ntitle: Old\nauthor: A\ntitle: New\nauthor: B\n---\nBody\n",
+        )
+        .await
+        .unwrap();
+
+        update_frontmatter(&file_path, |fm| {
+            fm.set_published(PublicationStatus::Draft);
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        let written = tokio::fs::read_to_string(&file_path).await.unwrap();
+        assert_eq!(written.matches("title:").count(), 1);
+        assert_eq!(written.matches("author:").count(), 1);
+        assert!(written.contains("title: New"));
+        assert!(written.contains("author: B"));
+
+        let strict = ParseOptions {
+            strict_frontmatter: true,
+            ..Default::default()
+        };
+        assert!(parse_markdown_file_with(&file_path, &strict).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_parse_markdown_file_reports_tab_indentation() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("tabs.md");
+        std::fs::write(&path, "---\ntitle: Tabs\ntags:\n\t- rust\n---\nBody\n").unwrap();
+
+        let err = parse_markdown_file(&path).await.unwrap_err();
+        assert!(matches!(err, Error::MarkdownParse { .. }));
+        assert!(
+            err.to_string()
+                .contains("frontmatter uses tab indentation on line 4; YAML requires spaces"),
+            "{}",
+            err
+        );
+
+        // A tab inside a value is fine
+        std::fs::write(&path, "---\ntitle: \"a\tb\"\n---\nBody\n").unwrap();
+        let (frontmatter, _) = parse_markdown_file(&path).await.unwrap();
+        assert_eq!(frontmatter.title.as_deref(), Some("a\tb"));
+    }
+
     #[test]
     fn test_parse_markdown_multiline_content() {
         let content = r#"---
@@ -300,7 +1528,7 @@ fn test() {
     fn test_format_markdown_basic() {
         let mut frontmatter = Frontmatter::default();
         frontmatter.set_title("Test Title");
-        frontmatter.set_published("draft");
+        frontmatter.set_published(PublicationStatus::Draft);
 
         let body = "# Content\n\nSome text.";
 
@@ -320,10 +1548,272 @@ fn test() {
 
         let result = format_markdown(&frontmatter, body).unwrap();
 
-        assert!(result.starts_with("---\n"));
-        assert!(result.ends_with("---\nJust content."));
-        // Should only contain the YAML null marker and body
-        assert!(result.contains("null\n") || result.contains("{}\n"));
+        // No frontmatter block and no `{}`/`null` marker
+        assert_eq!(result, "Just content.");
+        assert!(!result.contains("null"));
+
+        // Only the serde defaults set: still effectively empty
+        let frontmatter = Frontmatter {
+            other: serde_yaml::Value::Mapping(Default::default()),
+            ..Default::default()
+        };
+        let result = format_markdown(&frontmatter, body).unwrap();
+        assert!(!result.contains("null"));
+        assert!(!result.contains("{}"));
+    }
+
+    #[test]
+    fn test_parse_legacy_null_frontmatter() {
+        let (frontmatter, body) = parse_markdown("---\nnull\n---\nJust content.").unwrap();
+        assert!(frontmatter.is_empty());
+        assert_eq!(body, "Just content.");
+
+        // Rewriting drops the stale block instead of keeping `null`
+        assert_eq!(
+            format_markdown(&frontmatter, &body).unwrap(),
+            "Just content."
+        );
+    }
+
+    #[test]
+    fn test_extract_h1_title() {
+        assert_eq!(
+            extract_h1_title("```\n# comment\n```\n\n# Real Title #\n## Sub"),
+            Some("Real Title".to_string())
+        );
+        assert_eq!(extract_h1_title("## Only H2\ntext"), None);
+    }
+
+    #[tokio::test]
+    async fn test_update_frontmatter_creates_minimal_block() {
+        let temp_dir = TempDir::new().unwrap();
+        let with_h1 = temp_dir.path().join("with-h1.md");
+        let plain = temp_dir.path().join("quick-note.md");
+        tokio::fs::write(&with_h1, "# Hello World\n\nText.").await.unwrap();
+        tokio::fs::write(&plain, "Just text.").await.unwrap();
+
+        for path in [&with_h1, &plain] {
+            update_frontmatter(path, |fm| {
+                fm.set_published(PublicationStatus::Draft);
+                Ok(())
+            })
+            .await
+            .unwrap();
+        }
+
+        assert_eq!(
+            tokio::fs::read_to_string(&with_h1).await.unwrap(),
+            "---\ntitle: Hello World\npublished: draft\n---\n# Hello World\n\nText."
+        );
+        assert_eq!(
+            tokio::fs::read_to_string(&plain).await.unwrap(),
+            "---\ntitle: quick-note\npublished: draft\n---\nJust text."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sidecar_frontmatter_is_read_and_updated() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("article.md");
+        let sidecar = temp_dir.path().join("article.md.meta.yaml");
+        let body = "# Generated\n\nBody.\n";
+        tokio::fs::write(&path, body).await.unwrap();
+        tokio::fs::write(&sidecar, "title: From Sidecar\ncover: a.png\n")
+            .await
+            .unwrap();
+        let options = ParseOptions {
+            sidecar_frontmatter: true,
+            ..Default::default()
+        };
+
+        let (frontmatter, parsed_body) = parse_markdown_file_with(&path, &options).await.unwrap();
+        assert_eq!(frontmatter.title.as_deref(), Some("From Sidecar"));
+        assert_eq!(parsed_body, body);
+        let head = parse_frontmatter_file_with(&path, &options).await.unwrap();
+        assert_eq!(head.cover.as_deref(), Some("a.png"));
+        // Without the option the sidecar is ignored
+        let (frontmatter, _) = parse_markdown_file(&path).await.unwrap();
+        assert!(frontmatter.is_empty());
+
+        update_frontmatter_with(&path, &options, |fm| {
+            fm.set_published(PublicationStatus::Draft);
+            Ok(())
+        })
+        .await
+        .unwrap();
+        assert_eq!(tokio::fs::read_to_string(&path).await.unwrap(), body);
+        let updated = tokio::fs::read_to_string(&sidecar).await.unwrap();
+        assert!(updated.contains("title: From Sidecar"), "{}", updated);
+        assert!(updated.contains("published: draft"), "{}", updated);
+
+        // Without a sidecar, inline frontmatter is read and updated in place
+        tokio::fs::remove_file(&sidecar).await.unwrap();
+        tokio::fs::write(&path, "---\ntitle: Inline\n---\nBody.\n").await.unwrap();
+        assert!(!uses_sidecar(&path, &options).await.unwrap());
+        let (frontmatter, _) = parse_markdown_file_with(&path, &options).await.unwrap();
+        assert_eq!(frontmatter.title.as_deref(), Some("Inline"));
+        update_frontmatter_with(&path, &options, |fm| {
+            fm.set_published(PublicationStatus::Draft);
+            Ok(())
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            tokio::fs::read_to_string(&path).await.unwrap(),
+            "---\ntitle: Inline\npublished: draft\n---\nBody.\n"
+        );
+        assert!(!sidecar.exists());
+    }
+
+    #[tokio::test]
+    async fn test_wx_sidecar_is_used_without_the_setting() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("article.md");
+        let sidecar = temp_dir.path().join("article.md.wx.yaml");
+        let body = "# Generated\n\nBody.\n";
+        tokio::fs::write(&path, body).await.unwrap();
+        tokio::fs::write(&sidecar, "title: From Sidecar\n").await.unwrap();
+        let options = ParseOptions::default();
+
+        // Sidecar only: its frontmatter is read and updated, the file kept as is
+        assert_eq!(sidecar_path(&path), sidecar);
+        assert!(uses_sidecar(&path, &options).await.unwrap());
+        let (frontmatter, parsed_body) = parse_markdown_file_with(&path, &options).await.unwrap();
+        assert_eq!(frontmatter.title.as_deref(), Some("From Sidecar"));
+        assert_eq!(parsed_body, body);
+        update_frontmatter_with(&path, &options, |fm| {
+            fm.set_published(PublicationStatus::Draft);
+            Ok(())
+        })
+        .await
+        .unwrap();
+        assert_eq!(tokio::fs::read_to_string(&path).await.unwrap(), body);
+        let updated = tokio::fs::read_to_string(&sidecar).await.unwrap();
+        assert!(updated.contains("published: draft"), "{}", updated);
+
+        // Both present: merged over the block, and preferred to a .meta.yaml
+        let content = "---\ntitle: Embedded\nauthor: Ann\n---\nBody.\n";
+        tokio::fs::write(&path, content).await.unwrap();
+        tokio::fs::write(&sidecar, "title: From Sidecar\n").await.unwrap();
+        tokio::fs::write(temp_dir.path().join("article.md.meta.yaml"), "title: Meta\n")
+            .await
+            .unwrap();
+        let with_setting = ParseOptions {
+            sidecar_frontmatter: true,
+            ..Default::default()
+        };
+        for options in [options, with_setting] {
+            let frontmatter = parse_frontmatter_file_with(&path, &options).await.unwrap();
+            assert_eq!(frontmatter.title.as_deref(), Some("From Sidecar"));
+            assert_eq!(frontmatter.author(), Some("Ann"));
+        }
+
+        // Embedded only: the .meta.yaml needs the setting
+        tokio::fs::remove_file(&sidecar).await.unwrap();
+        let (frontmatter, _) = parse_markdown_file_with(&path, &options).await.unwrap();
+        assert_eq!(frontmatter.title.as_deref(), Some("Embedded"));
+        assert!(!uses_sidecar(&path, &options).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_sidecar_frontmatter_is_merged_over_inline_block() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("export.md");
+        let sidecar = temp_dir.path().join("export.md.meta.yaml");
+        let content = "---\ntitle: Embedded\ncover: a.png\ntags: [x]\nauthor: Ann\n---\nBody.\n";
+        tokio::fs::write(&path, content).await.unwrap();
+        tokio::fs::write(&sidecar, "title: From Sidecar\ntags: [y, z]\nauthor: ~\n")
+            .await
+            .unwrap();
+        let options = ParseOptions {
+            sidecar_frontmatter: true,
+            ..Default::default()
+        };
+        assert!(uses_sidecar(&path, &options).await.unwrap());
+
+        // Sidecar keys replace embedded ones whole; null removes them
+        let (frontmatter, body) = parse_markdown_file_with(&path, &options).await.unwrap();
+        assert_eq!(body, "Body.\n");
+        assert_eq!(frontmatter.title.as_deref(), Some("From Sidecar"));
+        assert_eq!(frontmatter.cover.as_deref(), Some("a.png"));
+        let tags: serde_yaml::Value = serde_yaml::from_str("[y, z]").unwrap();
+        assert_eq!(frontmatter.other["tags"], tags);
+        assert!(frontmatter.other.get("author").is_none());
+        assert_eq!(parse_frontmatter_file_with(&path, &options).await.unwrap(), frontmatter);
+
+        // Updates go to the sidecar, which keeps only what differs from the block
+        update_frontmatter_with(&path, &options, |fm| {
+            fm.set_published(PublicationStatus::Draft);
+            fm.title = Some("Embedded".to_string());
+            fm.cover = None;
+            Ok(())
+        })
+        .await
+        .unwrap();
+        assert_eq!(tokio::fs::read_to_string(&path).await.unwrap(), content);
+        assert_eq!(
+            tokio::fs::read_to_string(&sidecar).await.unwrap(),
+            "published: draft\ntags:\n- y\n- z\ncover: null\nauthor: null\n"
+        );
+        let head = parse_frontmatter_file_with(&path, &options).await.unwrap();
+        assert_eq!(head.title.as_deref(), Some("Embedded"));
+        assert_eq!(head.cover, None);
+        assert_eq!(head.publication_status(), PublicationStatus::Draft);
+
+        // A sidecar that is not a mapping is an error naming the sidecar
+        tokio::fs::write(&sidecar, "- a\n").await.unwrap();
+        let err = parse_frontmatter_file_with(&path, &options).await.unwrap_err();
+        assert!(err.to_string().contains("export.md.meta.yaml"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_sidecar_frontmatter_is_created_for_new_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("note.md");
+        tokio::fs::write(&path, "# Hello\n").await.unwrap();
+        let options = ParseOptions {
+            sidecar_frontmatter: true,
+            ..Default::default()
+        };
+
+        let mut frontmatter = Frontmatter::default();
+        frontmatter.set_cover("note_cover_1.png".to_string());
+        write_markdown_file_with(&path, &frontmatter, "# Hello\n", &options)
+            .await
+            .unwrap();
+        assert_eq!(tokio::fs::read_to_string(&path).await.unwrap(), "# Hello\n");
+        let head = parse_frontmatter_file_with(&path, &options).await.unwrap();
+        assert_eq!(head.cover.as_deref(), Some("note_cover_1.png"));
+
+        tokio::fs::remove_file(sidecar_path(&path)).await.unwrap();
+        update_frontmatter_with(&path, &options, |fm| {
+            fm.set_published(PublicationStatus::Draft);
+            Ok(())
+        })
+        .await
+        .unwrap();
+        let head = parse_frontmatter_file_with(&path, &options).await.unwrap();
+        assert_eq!(head.title.as_deref(), Some("Hello"));
+        assert_eq!(head.publication_status(), PublicationStatus::Draft);
+    }
+
+    #[tokio::test]
+    async fn test_update_frontmatter_keeps_existing_block_untitled() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("post.md");
+        tokio::fs::write(&path, "---\ntheme: lapis\n---\n# Heading\n")
+            .await
+            .unwrap();
+
+        update_frontmatter(&path, |fm| {
+            fm.set_published(PublicationStatus::Draft);
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        let content = tokio::fs::read_to_string(&path).await.unwrap();
+        assert!(!content.contains("title:"));
     }
 
     #[test]
@@ -419,7 +1909,7 @@ cover: "my-cover.png"
         let (frontmatter, body) = parse_markdown(content).unwrap();
 
         assert_eq!(frontmatter.title, Some("Article with Cover".to_string()));
-        assert_eq!(frontmatter.published, Some("draft".to_string()));
+        assert_eq!(frontmatter.publication_status(), PublicationStatus::Draft);
         assert_eq!(frontmatter.cover, Some("my-cover.png".to_string()));
         assert!(body.contains("Article Content"));
     }
@@ -436,7 +1926,33 @@ published: "draft"
         let (frontmatter, _) = parse_markdown(content).unwrap();
 
         assert_eq!(frontmatter.title, Some("Article without Cover".to_string()));
-        assert_eq!(frontmatter.published, Some("draft".to_string()));
+        assert_eq!(frontmatter.publication_status(), PublicationStatus::Draft);
+        assert_eq!(frontmatter.cover, None);
+    }
+
+    #[test]
+    fn test_cover_opt_out_round_trip() {
+        let cases = [
+            ("none", true),
+            ("None", true),
+            ("false", true),
+            ("'false'", true),
+            ("cover.png", false),
+        ];
+        for (value, disabled) in cases {
+            let content = format!("---\ntitle: Notice\ncover: {}\n---\nText only.\n", value);
+            let (frontmatter, body) = parse_markdown(&content).unwrap();
+            assert_eq!(frontmatter.cover_disabled(), disabled, "cover: {}", value);
+            assert_eq!(frontmatter.cover_image().is_none(), disabled, "cover: {}", value);
+
+            // Written back without being "fixed"
+            let written = format_markdown(&frontmatter, &body).unwrap();
+            let expected = if value == "'false'" { "false" } else { value };
+            assert!(written.contains(&format!("cover: {}\n", expected)), "{}", written);
+        }
+
+        assert!(parse_markdown("---\ncover: true\n---\nbody").is_err());
+        let (frontmatter, _) = parse_markdown("---\ncover: null\n---\nbody").unwrap();
         assert_eq!(frontmatter.cover, None);
     }
 
@@ -444,7 +1960,7 @@ published: "draft"
     fn test_format_markdown_with_cover() {
         let mut frontmatter = Frontmatter::default();
         frontmatter.set_title("Test Article");
-        frontmatter.set_published("draft");
+        frontmatter.set_published(PublicationStatus::Draft);
         frontmatter.set_cover("test-cover.png");
 
         let body = "# Test Content";
@@ -463,7 +1979,7 @@ published: "draft"
 
         let mut frontmatter = Frontmatter::default();
         frontmatter.set_title("File Test");
-        frontmatter.set_published("draft");
+        frontmatter.set_published(PublicationStatus::Draft);
 
         let body = "# Test Content\n\nThis is a test.";
 
@@ -497,7 +2013,7 @@ published: "draft"
         // Update frontmatter
         update_frontmatter(&file_path, |fm| {
             fm.set_title("Updated Title");
-            fm.set_published("draft");
+            fm.set_published(PublicationStatus::Draft);
             Ok(())
         })
         .await
@@ -506,6 +2022,315 @@ published: "draft"
         // Verify update
         let (updated_frontmatter, _) = parse_markdown_file(&file_path).await.unwrap();
         assert_eq!(updated_frontmatter.title, Some("Updated Title".to_string()));
-        assert_eq!(updated_frontmatter.published, Some("draft".to_string()));
+        assert_eq!(updated_frontmatter.publication_status(), PublicationStatus::Draft);
+    }
+
+    /// A 10 MB article whose body contains a `---` rule and bytes that are
+    /// not valid UTF-8
+    fn large_article() -> (Vec<u8>, Vec<u8>) {
+        let head = b"---\ntitle: Big\npublished: draft\n---\n".to_vec();
+        let mut body = b"# Big\n\n---\n".to_vec();
+        while body.len() < 10 * 1024 * 1024 {
+            body.extend_from_slice(b"A long paragraph of text that repeats. \xff\n");
+        }
+        (head, body)
+    }
+
+    #[tokio::test]
+    async fn test_parse_frontmatter_file_reads_only_the_head() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("big.md");
+        let (head, body) = large_article();
+        std::fs::write(&path, [head.as_slice(), &body].concat()).unwrap();
+
+        let frontmatter = parse_frontmatter_file_with(&path, &ParseOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(frontmatter.title.as_deref(), Some("Big"));
+
+        // The body is never decoded, so the invalid bytes in it do not matter
+        let file_head = read_frontmatter_head(&path).await.unwrap().unwrap();
+        assert_eq!(file_head.body_start, head.len() as u64);
+        assert!(parse_markdown_file(&path).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_frontmatter_head_edge_cases() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("post.md");
+
+        // The closing delimiter straddles the first chunk boundary
+        let yaml = format!("summary: {}", "x".repeat(HEAD_CHUNK_SIZE - 15));
+        std::fs::write(&path, format!("---\n{}\n---\nBody\n", yaml)).unwrap();
+        let file_head = read_frontmatter_head(&path).await.unwrap().unwrap();
+        assert_eq!(file_head.yaml, yaml);
+        assert_eq!(file_head.body_start as usize, yaml.len() + 9);
+
+        for content in ["# No frontmatter\n", "---\ntitle: Unclosed\n", "--", ""] {
+            std::fs::write(&path, content).unwrap();
+            assert!(read_frontmatter_head(&path).await.unwrap().is_none(), "{:?}", content);
+            let frontmatter = parse_frontmatter_file_with(&path, &ParseOptions::default())
+                .await
+                .unwrap();
+            assert!(frontmatter.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_update_frontmatter_copies_large_body() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("big.md");
+        let (head, body) = large_article();
+        std::fs::write(&path, [head.as_slice(), &body].concat()).unwrap();
+
+        update_frontmatter(&path, |fm| {
+            fm.set_published(PublicationStatus::Published);
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        let written = std::fs::read(&path).unwrap();
+        assert!(written.ends_with(&body));
+        let new_head = String::from_utf8(written[..written.len() - body.len()].to_vec()).unwrap();
+        assert_eq!(new_head, "---\ntitle: Big\npublished: true\n---\n");
+    }
+
+    #[tokio::test]
+    async fn test_write_file_atomic_leaves_no_temp_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("post.md");
+        std::fs::write(&path, "old").unwrap();
+
+        // Concurrent writers each replace the whole file
+        let writes = (0..8).map(|i| {
+            let path = path.clone();
+            tokio::spawn(async move {
+                let content = format!("content {}\n", i).repeat(1000);
+                write_file_atomic(&path, content.as_bytes()).await
+            })
+        });
+        for write in writes {
+            write.await.unwrap().unwrap();
+        }
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let first_line = content.lines().next().unwrap();
+        assert!(content.lines().all(|line| line == first_line));
+        assert_eq!(content.lines().count(), 1000);
+
+        let entries: Vec<_> = std::fs::read_dir(temp_dir.path()).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_write_markdown_file_preserves_permissions_and_symlinks() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("post.md");
+        std::fs::write(&path, "---\ntitle: Old\n---\nbody").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+        let link = temp_dir.path().join("link.md");
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+
+        update_frontmatter(&link, |fm| {
+            fm.set_published(PublicationStatus::Draft);
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        assert!(std::fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+        let (frontmatter, _) = parse_markdown_file(&path).await.unwrap();
+        assert_eq!(frontmatter.publication_status(), PublicationStatus::Draft);
+    }
+
+    fn set(key: &str, value: &str) -> FrontmatterEdit {
+        FrontmatterEdit::Set(key.to_string(), value.to_string())
+    }
+
+    fn remove(key: &str) -> FrontmatterEdit {
+        FrontmatterEdit::Remove(key.to_string())
+    }
+
+    /// Parses `content` and formats it again with `edits` applied
+    fn edit_raw(content: &str, edits: &[FrontmatterEdit]) -> String {
+        let (raw, _, body) = parse_markdown_raw(content).unwrap();
+        format_markdown_preserving(&raw, edits, &body).unwrap()
+    }
+
+    const ODD_FRONTMATTER: &str = "---
+# Article metadata, edited by hand
+title:   \"C# in 10 minutes\"   # keep the quotes
+tags: [rust,
+    wechat]
+meta:
+      series: intro
+      order:    2
+links: {home: \"/\", docs: '#intro'}
+cover:    'old.png' # replaced by the tool
+
+# Publishing
+published: draft
+---
+# Body
+
+text
+";
+
+    #[test]
+    fn test_parse_markdown_raw_keeps_block_as_written() {
+        let (raw, frontmatter, body) = parse_markdown_raw(ODD_FRONTMATTER).unwrap();
+
+        assert!(raw.yaml.starts_with("# Article metadata"));
+        assert!(raw.yaml.ends_with("published: draft"));
+        assert_eq!(&ODD_FRONTMATTER[raw.span.clone()], raw.yaml);
+        assert_eq!(frontmatter.title.as_deref(), Some("C# in 10 minutes"));
+        assert_eq!(frontmatter.publication_status(), PublicationStatus::Draft);
+        assert_eq!(body, "# Body\n\ntext\n");
+
+        // Without edits the file is reproduced byte for byte
+        assert_eq!(edit_raw(ODD_FRONTMATTER, &[]), ODD_FRONTMATTER);
+
+        let (raw, frontmatter, body) = parse_markdown_raw("# Title\n\nBody").unwrap();
+        assert!(raw.is_empty());
+        assert_eq!(raw.span, 0..0);
+        assert!(frontmatter.is_empty());
+        assert_eq!(body, "# Title\n\nBody");
+    }
+
+    #[test]
+    fn test_format_markdown_preserving_sets_values() {
+        let result = edit_raw(
+            ODD_FRONTMATTER,
+            &[
+                set("published", "published"),
+                set("cover", "images/new cover.png"),
+                set("tags", "rust"),
+                set("links", "https://example.com/#home"),
+            ],
+        );
+
+        assert_eq!(
+            result,
+            ODD_FRONTMATTER
+                .replace("published: draft", "published: published")
+                .replace(
+                    "cover:    'old.png' # replaced by the tool",
+                    "cover: images/new cover.png # replaced by the tool"
+                )
+                .replace("tags: [rust,\n    wechat]", "tags: rust")
+                .replace("links: {home: \"/\", docs: '#intro'}", "links: https://example.com/#home")
+        );
+        let (frontmatter, _) = parse_markdown(&result).unwrap();
+        assert_eq!(frontmatter.cover.as_deref(), Some("images/new cover.png"));
+        assert_eq!(frontmatter.title.as_deref(), Some("C# in 10 minutes"));
+    }
+
+    #[test]
+    fn test_format_markdown_preserving_removes_multiline_values() {
+        let result = edit_raw(
+            ODD_FRONTMATTER,
+            &[remove("tags"), remove("meta"), remove("links"), remove("cover")],
+        );
+
+        assert_eq!(
+            result,
+            "---
+# Article metadata, edited by hand
+title:   \"C# in 10 minutes\"   # keep the quotes
+
+# Publishing
+published: draft
+---
+# Body
+
+text
+"
+        );
+
+        // Column-0 sequence items belong to their key
+        let content = "---\ntitle: A\ntags:\n- one\n- two\n# trailing note\n---\nbody";
+        assert_eq!(
+            edit_raw(content, &[remove("tags")]),
+            "---\ntitle: A\n# trailing note\n---\nbody"
+        );
+    }
+
+    #[test]
+    fn test_format_markdown_preserving_appends_and_quotes() {
+        // A missing key is appended, and values are quoted when YAML needs it
+        let content = "---\n'title': Hello\n---\nbody";
+        assert_eq!(
+            edit_raw(content, &[set("published", "draft"), set("cover", "a: b.png")]),
+            "---\n'title': Hello\npublished: draft\ncover: 'a: b.png'\n---\nbody"
+        );
+
+        // Quoted keys keep their quoting, and `#` inside quotes is not a comment
+        let content = "---\n\"published\": 'it''s # not a comment'\n---\nbody";
+        assert_eq!(
+            edit_raw(content, &[set("published", "draft")]),
+            "---\n\"published\": draft\n---\nbody"
+        );
+
+        // A file without frontmatter gets a new block
+        assert_eq!(
+            edit_raw("body", &[set("published", "draft")]),
+            "---\npublished: draft\n---\nbody"
+        );
+    }
+
+    #[test]
+    fn test_format_markdown_preserving_duplicates_and_empty_blocks() {
+        // The effective (last) definition is set; removal drops every definition
+        let content = "---\npublished: draft\ntitle: A\npublished: draft # last\n---\nbody";
+        assert_eq!(
+            edit_raw(content, &[set("published", "published")]),
+            "---\npublished: draft\ntitle: A\npublished: published # last\n---\nbody"
+        );
+        assert_eq!(edit_raw(content, &[remove("published")]), "---\ntitle: A\n---\nbody");
+
+        // Removing the last key drops the block, like format_markdown
+        assert_eq!(edit_raw("---\npublished: draft\n---\nbody", &[remove("published")]), "body");
+    }
+
+    #[test]
+    fn test_inline_comment() {
+        assert_eq!(inline_comment("cover: a.png # note"), Some("# note"));
+        assert_eq!(inline_comment("title: \"C# tips\" #x"), Some("#x"));
+        assert_eq!(inline_comment("title: \"say \\\" # hi\""), None);
+        assert_eq!(inline_comment("title: don't # x"), Some("# x"));
+        assert_eq!(inline_comment("url: https://a.b/#anchor"), None);
+        assert_eq!(inline_comment("tags: ['#rust', b] # c"), Some("# c"));
+    }
+
+    #[test]
+    fn test_transform_pipeline_names_failing_stage() {
+        let suffix = |suffix: &'static str| {
+            Arc::new(FnTransform::new(suffix, move |_: &ArticleContext<'_>, body: String| {
+                Ok(body + suffix)
+            })) as Arc<dyn BodyTransform>
+        };
+        let mut pipeline = TransformPipeline::new(vec![suffix("a"), suffix("b")]);
+        let frontmatter = Frontmatter::new();
+        let ctx = ArticleContext {
+            path: Path::new("post.md"),
+            frontmatter: &frontmatter,
+            verbose: false,
+        };
+        assert_eq!(pipeline.apply(&ctx, "body ".to_string()).unwrap(), "body ab");
+        assert_eq!(pipeline, pipeline.clone());
+        assert_ne!(pipeline, TransformPipeline::new(vec![suffix("a"), suffix("b")]));
+
+        pipeline.push(Arc::new(FnTransform::new("broken", |_: &ArticleContext<'_>, _| {
+            Err(Error::generic("no closing fence"))
+        })));
+        let err = pipeline.apply(&ctx, "body".to_string()).unwrap_err().to_string();
+        assert!(err.contains("body transform 'broken' failed"), "{}", err);
+        assert!(err.contains("no closing fence"), "{}", err);
     }
 }
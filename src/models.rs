@@ -3,12 +3,32 @@
 //! This module contains core data structures used throughout the application,
 //! including configuration, frontmatter parsing, and validation logic.
 
+use crate::backup::DEFAULT_BACKUP_KEEP;
+use crate::budget::{AiPrices, BudgetLimits, DEFAULT_DESCRIPTION_PRICE};
+use crate::cover_format::{CoverEncoding, CoverFormat, DEFAULT_COVER_QUALITY};
+use crate::cover_template::CoverTemplate;
+use crate::hooks::Hooks;
 use crate::error::{Error, Result};
+use crate::http_trace::{DEFAULT_TRACE_BODY_LIMIT, HttpTracer, REDACTED};
+use crate::language::is_valid_language;
+use crate::lint::validate_disabled_rules;
+use crate::prompt::Prompter;
+use crate::provenance::{ConfigSource, Provenance};
+use crate::retry_queue::DEFAULT_RETRY_MAX_ATTEMPTS;
+use crate::rate_limit::{
+    DEFAULT_MAX_RETRIES, DEFAULT_MAX_RETRY_DELAY, DEFAULT_WECHAT_RPM, RetryPolicy, UploadPacer,
+};
+use crate::schedule::{AllowedHours, Clock};
+use crate::slug::is_valid_slug;
+use crate::wechat::default_markdown_extensions;
+use crate::wechat_api::normalize_base_url;
 use serde::{Deserialize, Serialize};
-use std::{env, path::Path, collections::HashMap};
+use std::borrow::Cow;
+use std::{env, path::{Path, PathBuf}, collections::{BTreeMap, HashMap}};
+use unicode_segmentation::UnicodeSegmentation;
 
 /// AI provider configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum AiProvider {
     /// Use OpenAI directly
     OpenAI {
@@ -22,8 +42,11 @@ pub enum AiProvider {
     },
 }
 
+/// Name of the account given with `--app-id` and `--app-secret`
+pub const CLI_ACCOUNT_NAME: &str = "cli";
+
 /// WeChat account configuration
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
 pub struct WeChatAccount {
     /// Account name/identifier
     pub name: String,
@@ -33,6 +56,95 @@ pub struct WeChatAccount {
     pub app_secret: String,
     /// Optional description for this account
     pub description: Option<String>,
+    /// Optional WeChat API base URL override (e.g. a mock server or gateway)
+    #[serde(default, alias = "api_base_url", skip_serializing_if = "Option::is_none")]
+    pub wechat_base_url: Option<String>,
+    /// Cover used when one is missing and cannot be generated, and the
+    /// thumbnail of articles with `cover: none`; relative to the config file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_cover: Option<PathBuf>,
+    /// Whether this is a regular account or a sandbox (mp test) account
+    #[serde(default, skip_serializing_if = "AccountKind::is_normal")]
+    pub kind: AccountKind,
+    /// Labels for finding the account, e.g. `regional`; `--account tag:<tag>`
+    /// selects the one account with a tag
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Who runs the account, shown by `--list-accounts`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+}
+
+/// Kind of a WeChat account, which decides what it supports
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountKind {
+    /// Official account with the full API
+    #[default]
+    Normal,
+    /// Sandbox test account (mp test account) with a reduced API
+    Sandbox,
+}
+
+impl AccountKind {
+    /// Returns true for regular accounts
+    pub fn is_normal(&self) -> bool {
+        *self == Self::Normal
+    }
+
+    /// Returns true if accounts of this kind support `capability`
+    pub fn supports(self, capability: Capability) -> bool {
+        match capability {
+            Capability::OriginalDeclaration => self == Self::Normal,
+        }
+    }
+
+    /// Returns why accounts of this kind cannot do `capability`, if they cannot
+    pub fn unsupported(self, capability: Capability) -> Option<String> {
+        (!self.supports(capability))
+            .then(|| format!("{} is not supported on {} accounts", capability, self))
+    }
+}
+
+impl std::fmt::Display for AccountKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Normal => "normal",
+            Self::Sandbox => "sandbox",
+        })
+    }
+}
+
+/// WeChat operations that not every kind of account supports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    /// Declaring an article as original content (`is_original`)
+    OriginalDeclaration,
+}
+
+impl std::fmt::Display for Capability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::OriginalDeclaration => "the original declaration",
+        })
+    }
+}
+
+// The secret must not end up in logs
+impl std::fmt::Debug for WeChatAccount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WeChatAccount")
+            .field("name", &self.name)
+            .field("app_id", &self.app_id)
+            .field("app_secret", &REDACTED)
+            .field("description", &self.description)
+            .field("wechat_base_url", &self.wechat_base_url)
+            .field("default_cover", &self.default_cover)
+            .field("kind", &self.kind)
+            .field("tags", &self.tags)
+            .field("owner", &self.owner)
+            .finish()
+    }
 }
 
 /// Configuration file structure for multiple accounts and settings
@@ -42,6 +154,9 @@ pub struct ConfigFile {
     pub accounts: HashMap<String, WeChatAccount>,
     /// Default account name to use
     pub default_account: Option<String>,
+    /// Named account groups; members are account names or `@group` references
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub groups: HashMap<String, Vec<String>>,
     /// AI provider configuration
     pub ai_provider: Option<AiProviderConfig>,
     /// Global settings
@@ -54,11 +169,324 @@ pub struct AiProviderConfig {
     /// Provider type
     pub provider: String, // "openai" or "gemini"
     /// API key
-    pub api_key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    /// File containing the API key, e.g. a mounted container secret
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_file: Option<String>,
     /// Optional base URL
     pub base_url: Option<String>,
 }
 
+impl AiProviderConfig {
+    /// Returns the API key, reading it from `api_key_file` if that is set
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if both or neither of `api_key` and `api_key_file` are
+    /// set, or if the key file cannot be read or is empty
+    pub fn resolve_api_key(&self) -> Result<String> {
+        match (&self.api_key, &self.api_key_file) {
+            (Some(_), Some(_)) => Err(Error::config(
+                "ai_provider: set either api_key or api_key_file, not both",
+            )),
+            (Some(api_key), None) => Ok(api_key.clone()),
+            (None, Some(path)) => read_secret_file(path),
+            (None, None) => Err(Error::config(
+                "ai_provider: api_key or api_key_file is required",
+            )),
+        }
+    }
+}
+
+/// Returns `root` as an absolute path, checking that it is a directory
+///
+/// A relative root is taken relative to the current directory.
+pub fn resolve_content_root(root: &Path) -> Result<PathBuf> {
+    resolve_directory_setting("content_root", root)
+}
+
+/// Returns the Obsidian vault `root` as an absolute path, checking that it is a directory
+///
+/// A relative root is taken relative to the current directory.
+pub fn resolve_obsidian_vault(root: &Path) -> Result<PathBuf> {
+    resolve_directory_setting("obsidian_vault", root)
+}
+
+/// Returns the `markdown_extensions` setting without leading dots, in lowercase
+///
+/// # Errors
+///
+/// Returns an error if the list or one of its extensions is empty
+pub fn normalize_markdown_extensions(extensions: &[String]) -> Result<Vec<String>> {
+    if extensions.is_empty() {
+        return Err(Error::config("markdown_extensions must name at least one extension"));
+    }
+    extensions
+        .iter()
+        .map(|extension| {
+            let extension = extension.trim().trim_start_matches('.').to_lowercase();
+            if extension.is_empty() {
+                Err(Error::config("markdown_extensions: an extension is empty"))
+            } else {
+                Ok(extension)
+            }
+        })
+        .collect()
+}
+
+/// Canonicalizes the directory given for `setting`
+fn resolve_directory_setting(setting: &str, root: &Path) -> Result<PathBuf> {
+    match std::fs::canonicalize(root) {
+        Ok(root) if root.is_dir() => Ok(root),
+        _ => Err(Error::config(format!(
+            "{} '{}' is not a directory",
+            setting,
+            root.display()
+        ))),
+    }
+}
+
+/// Returns the default cover of `account` as an absolute path, checking that it is a file
+///
+/// A relative path is taken relative to the directory of the config file.
+fn resolve_default_cover(config_path: &Path, cover: &Path, account: &str) -> Result<PathBuf> {
+    let config_dir = config_path.parent().unwrap_or_else(|| Path::new(""));
+    let resolved = config_dir.join(cover);
+    match std::fs::canonicalize(&resolved) {
+        Ok(cover) if cover.is_file() => Ok(cover),
+        _ => Err(Error::config(format!(
+            "default_cover '{}' of account '{}' is not a file",
+            resolved.display(),
+            account
+        ))),
+    }
+}
+
+/// Checks that the default theme and code highlighter are known
+fn validate_style_defaults(settings: &GlobalSettings) -> Result<()> {
+    if let Some(theme) = &settings.default_theme
+        && !is_valid_theme(theme)
+    {
+        return Err(Error::config(format!(
+            "Invalid default_theme '{}'. Available themes: {}",
+            theme,
+            VALID_THEMES.join(", ")
+        )));
+    }
+    if let Some(code) = &settings.default_code_highlighter
+        && !is_valid_code_highlighter(code)
+    {
+        return Err(Error::config(format!(
+            "Invalid default_code_highlighter '{}'. Available highlighters: {}",
+            code,
+            VALID_CODE_HIGHLIGHTERS.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Checks that scene prompts are keyed by language tags and not empty
+fn validate_scene_prompts(prompts: &HashMap<String, String>) -> Result<()> {
+    for lang in sorted_keys(prompts) {
+        if !is_valid_language(lang) {
+            return Err(Error::config(format!(
+                "Invalid language '{}' in scene_prompts. Use a tag such as 'en' or 'zh-CN'",
+                lang
+            )));
+        }
+        if prompts[lang].trim().is_empty() {
+            return Err(Error::config(format!(
+                "Scene prompt for language '{}' is empty",
+                lang
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Expands account groups to the account names they contain
+///
+/// Members are account names or `@group` references to other groups, which
+/// are expanded recursively in member order without duplicates.
+///
+/// # Errors
+///
+/// Returns a configuration error for an empty group, a member that is neither
+/// a configured account nor a defined group, or a cycle between groups
+fn expand_account_groups(
+    groups: &HashMap<String, Vec<String>>,
+    accounts: &HashMap<String, WeChatAccount>,
+) -> Result<HashMap<String, Vec<String>>> {
+    fn expand(
+        name: &str,
+        groups: &HashMap<String, Vec<String>>,
+        accounts: &HashMap<String, WeChatAccount>,
+        stack: &mut Vec<String>,
+        out: &mut Vec<String>,
+    ) -> Result<()> {
+        if let Some(start) = stack.iter().position(|g| g == name) {
+            let mut cycle = stack[start..].to_vec();
+            cycle.push(name.to_string());
+            return Err(Error::config(format!(
+                "Account group cycle: {}",
+                cycle.iter().map(|g| format!("@{}", g)).collect::<Vec<_>>().join(" -> ")
+            )));
+        }
+
+        let members = &groups[name];
+        if members.is_empty() {
+            return Err(Error::config(format!("Account group '{}' has no members", name)));
+        }
+
+        stack.push(name.to_string());
+        for member in members {
+            match member.strip_prefix('@') {
+                Some(group) if groups.contains_key(group) => {
+                    expand(group, groups, accounts, stack, out)?
+                }
+                Some(group) => {
+                    return Err(Error::config(format!(
+                        "Account group '{}' references undefined group '@{}'",
+                        name, group
+                    )));
+                }
+                None if accounts.contains_key(member) => {
+                    if !out.contains(member) {
+                        out.push(member.clone());
+                    }
+                }
+                None => {
+                    return Err(Error::config(format!(
+                        "Account group '{}' references undefined account '{}'. Available accounts: {}",
+                        name,
+                        member,
+                        sorted_keys(accounts).join(", ")
+                    )));
+                }
+            }
+        }
+        stack.pop();
+        Ok(())
+    }
+
+    let mut expanded = HashMap::new();
+    // Sorted so the reported error does not depend on hash order
+    for name in sorted_keys(groups) {
+        let mut members = Vec::new();
+        expand(name, groups, accounts, &mut Vec::new(), &mut members)?;
+        expanded.insert(name.to_string(), members);
+    }
+    Ok(expanded)
+}
+
+/// Prefix of an `--account` value that selects the account with a tag
+pub const TAG_PREFIX: &str = "tag:";
+
+/// Resolves an account name, `@group` or `tag:<tag>` to account names
+///
+/// `groups` must be expanded already, see [`expand_account_groups`]. A tag
+/// selects the one account that has it.
+///
+/// # Errors
+///
+/// Returns a configuration error for an unknown account or group, or a tag
+/// that no account or several accounts have
+fn resolve_account_spec(
+    spec: &str,
+    accounts: &HashMap<String, WeChatAccount>,
+    groups: &HashMap<String, Vec<String>>,
+) -> Result<Vec<String>> {
+    if let Some(group) = spec.strip_prefix('@') {
+        return groups.get(group).cloned().ok_or_else(|| {
+            Error::config(format!(
+                "Account group '{}' not found. Available groups: {}",
+                group,
+                sorted_keys(groups).join(", ")
+            ))
+        });
+    }
+    if let Some(tag) = spec.strip_prefix(TAG_PREFIX) {
+        let mut tagged: Vec<&str> = sorted_keys(accounts)
+            .into_iter()
+            .filter(|name| accounts[*name].tags.iter().any(|t| t == tag))
+            .collect();
+        return match tagged.len() {
+            1 => Ok(vec![tagged.remove(0).to_string()]),
+            0 => {
+                let mut tags: Vec<&str> =
+                    accounts.values().flat_map(|a| a.tags.iter().map(String::as_str)).collect();
+                tags.sort_unstable();
+                tags.dedup();
+                Err(Error::config(format!(
+                    "No account is tagged '{}'. Available tags: {}",
+                    tag,
+                    tags.join(", ")
+                )))
+            }
+            _ => Err(Error::config(format!(
+                "Tag '{}' matches {} accounts: {}. Name one of them, or use a group to upload to all",
+                tag,
+                tagged.len(),
+                tagged.join(", ")
+            ))),
+        };
+    }
+    match accounts.get(spec) {
+        Some(account) => Ok(vec![account.name.clone()]),
+        None => Err(Error::config(format!(
+            "Account '{}' not found. Available accounts: {}",
+            spec,
+            sorted_keys(accounts).join(", ")
+        ))),
+    }
+}
+
+fn sorted_keys<V>(map: &HashMap<String, V>) -> Vec<&str> {
+    let mut keys: Vec<&str> = map.keys().map(|s| s.as_str()).collect();
+    keys.sort_unstable();
+    keys
+}
+
+/// Reads a secret from a file, trimming trailing whitespace such as the final newline
+///
+/// # Errors
+///
+/// Returns a configuration error if the file cannot be read or is empty
+pub fn read_secret_file(path: impl AsRef<Path>) -> Result<String> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        Error::config(format!("Failed to read secret file {}: {}", path.display(), e))
+    })?;
+
+    let secret = content.trim_end();
+    if secret.is_empty() {
+        return Err(Error::config(format!("Secret file {} is empty", path.display())));
+    }
+    Ok(secret.to_string())
+}
+
+/// Reads a secret from `name` or from the file named by `name_FILE`
+///
+/// Follows the common container convention where `FOO_FILE` points at a
+/// mounted secret instead of putting the value in `FOO`.
+///
+/// # Errors
+///
+/// Returns a configuration error if both variables are set or the file cannot be read
+pub fn env_secret(name: &str) -> Result<Option<String>> {
+    let file_var = format!("{}_FILE", name);
+    match (env::var(name), env::var(&file_var)) {
+        (Ok(_), Ok(_)) => Err(Error::config(format!(
+            "Set either {} or {}, not both",
+            name, file_var
+        ))),
+        (Ok(value), Err(_)) => Ok(Some(value)),
+        (Err(_), Ok(path)) => read_secret_file(path).map(Some),
+        (Err(_), Err(_)) => Ok(None),
+    }
+}
+
 /// Global settings
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct GlobalSettings {
@@ -68,6 +496,157 @@ pub struct GlobalSettings {
     pub default_theme: Option<String>,
     /// Default code highlighter
     pub default_code_highlighter: Option<String>,
+    /// WeChat API base URL applied to accounts that do not set their own
+    #[serde(default, alias = "api_base_url", skip_serializing_if = "Option::is_none")]
+    pub wechat_base_url: Option<String>,
+    /// Default cover applied to accounts that do not set their own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_cover: Option<PathBuf>,
+    /// Treat duplicate frontmatter keys as errors instead of warnings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strict_frontmatter: Option<bool>,
+    /// Build cover scene descriptions locally instead of asking the AI
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_ai_text: Option<bool>,
+    /// Stream AI scene descriptions and print them as they arrive in verbose mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_scene: Option<bool>,
+    /// Losslessly recompress PNG covers before upload
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub optimize_covers: Option<bool>,
+    /// Embed the provider, model, prompt and time in generated PNG covers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embed_provenance: Option<bool>,
+    /// Image format generated covers are saved in: png, jpeg or webp (default: png)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cover_format: Option<CoverFormat>,
+    /// JPEG quality of generated covers, from 1 to 100 (default: 85)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cover_quality: Option<u8>,
+    /// Write publish state and generated cover names back to the source files
+    /// (default: true)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub write_back: Option<bool>,
+    /// Set `published: draft` and the draft ID in uploaded files (default:
+    /// true); when false the uploads are recorded in the state file instead
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub set_draft_status: Option<bool>,
+    /// JSON file recording uploads when write-back or status updates are disabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_file: Option<String>,
+    /// Directory AI scene descriptions are cached in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_dir: Option<String>,
+    /// Derive a slug from the title for files without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_slug: Option<bool>,
+    /// Scene description prompts keyed by language, e.g. `zh`, replacing or
+    /// adding to the built-in ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scene_prompts: Option<HashMap<String, String>>,
+    /// Scene description used when the AI returns none, with `{title}`,
+    /// `{description}`, `{tags}` and `{headings}` filled in from the article
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback_scene: Option<String>,
+    /// Declare articles as original content unless their frontmatter says
+    /// otherwise (default: false)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original: Option<bool>,
+    /// What to do with files whose theme or code highlighter is unknown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub theme_fallback: Option<ThemeFallback>,
+    /// What to do when the account may not upload the cover as permanent
+    /// material (default: skip)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_material_denied: Option<MaterialDenied>,
+    /// Which image is used when `cover` names a directory (default: newest)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cover_select: Option<CoverSelect>,
+    /// Maximum number of AI images generated per run (0: unlimited)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_ai_images_per_run: Option<u32>,
+    /// Maximum estimated AI image cost in USD per run (0: unlimited)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_ai_cost_per_run: Option<f64>,
+    /// Estimated price in USD of one AI image (default: list price of the image model)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ai_image_price: Option<f64>,
+    /// Estimated price in USD of one AI scene description (default: 0.001)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ai_description_price: Option<f64>,
+    /// Ask before a run whose estimated AI cost in USD exceeds this amount
+    /// (default: never ask)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirm_ai_cost_above: Option<f64>,
+    /// Directory that cover paths starting with `/` are resolved against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_root: Option<String>,
+    /// Render missing covers from a background image and the title instead of with AI
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cover_template: Option<CoverTemplate>,
+    /// Shell commands run before and after each upload and after the run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<Hooks>,
+    /// Fail a file whose `pre_upload` hook fails instead of skipping it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strict_hooks: Option<bool>,
+    /// Drafts each account may create per minute; 0 disables rate limiting (default: 20)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wechat_rpm: Option<u32>,
+    /// Retries of a request rejected for the rate limit; 0 disables retries (default: 3)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_retries: Option<u32>,
+    /// Longest wait in seconds before such a retry, also when the response
+    /// asks for a longer one with `Retry-After` (default: 60)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_retry_delay: Option<u64>,
+    /// Milliseconds each upload worker pauses after creating a draft (default: 0)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upload_delay_ms: Option<u64>,
+    /// Runs of `wx-uploader retry` a failed upload gets before it leaves the
+    /// retry queue (default: 3)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_max_attempts: Option<u32>,
+    /// Characters of `description` sent as the digest before it is truncated
+    /// (default: 120)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest_limit: Option<usize>,
+    /// Lint rules whose warnings about WeChat-unsupported markdown are silenced
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disabled_lint_rules: Option<Vec<String>>,
+    /// What to do with links to headings of the same article (default: keep)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anchor_links: Option<AnchorLinks>,
+    /// Convert Obsidian `![[image]]` embeds and `[[Note]]` links on upload
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub obsidian: Option<bool>,
+    /// Vault root `![[image]]` embeds are resolved against (default:
+    /// `content_root`, or else the article's directory)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub obsidian_vault: Option<String>,
+    /// What to do with `[[Note]]` links when `obsidian` is on (default: text)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wiki_links: Option<WikiLinks>,
+    /// Body transforms run on upload, by name and in order (default: the
+    /// ones `obsidian` and `anchor_links` turn on)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transforms: Option<Vec<String>>,
+    /// File extensions of markdown files, without the dot (default: `[md]`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub markdown_extensions: Option<Vec<String>>,
+    /// Read the frontmatter from `<file name>.meta.yaml` for files without one,
+    /// or merged over the embedded one where it exists
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sidecar_frontmatter: Option<bool>,
+    /// Daily window uploads may start in, e.g. `09:00-18:00 Asia/Shanghai`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_hours: Option<String>,
+    /// Back up files to `.wx-uploader/backup` before modifying them (default: true)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup: Option<bool>,
+    /// Number of backup sets kept (default: 10)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup_keep: Option<usize>,
 }
 
 impl AiProvider {
@@ -104,6 +683,87 @@ impl AiProvider {
     }
 }
 
+/// What an upload run does with a file whose theme or code highlighter is unknown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeFallback {
+    /// Fail the file, as validation always has
+    #[default]
+    Error,
+    /// Warn and upload with the configured default theme or highlighter
+    Default,
+    /// Warn and skip the file
+    Skip,
+}
+
+/// What an upload does when WeChat refuses the cover as permanent material
+/// because the account may not upload any (errcode 48001), as subscription
+/// and unverified accounts cannot
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MaterialDenied {
+    /// Warn and create the draft without a cover
+    #[default]
+    Skip,
+    /// Fail the upload
+    Fail,
+}
+
+/// Which image of a directory of cover candidates an upload uses
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum CoverSelect {
+    /// The most recently modified image
+    #[default]
+    Newest,
+    /// The first image by file name
+    First,
+    /// Any image, picked at random
+    Random,
+}
+
+/// What an upload does with links to headings of the same article, which
+/// WeChat cannot follow because it drops heading ids
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum AnchorLinks {
+    /// Upload them unchanged; `--validate` and verbose uploads warn about them
+    #[default]
+    Keep,
+    /// Upload each link as its plain text
+    Text,
+}
+
+/// What an upload with `obsidian` enabled does with `[[Note]]` wiki links,
+/// which have no target outside the vault
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum WikiLinks {
+    /// Upload each link as its alias or note name
+    #[default]
+    Text,
+    /// Upload each link as its text with a number, listing the notes at the end
+    Footnote,
+}
+
+/// Concurrency limits of the pipelined upload mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PipelineLimits {
+    /// Files prepared at once (parsing, AI cover generation, frontmatter update)
+    pub ai_jobs: usize,
+    /// Concurrent WeChat uploads
+    pub upload_jobs: usize,
+}
+
+impl Default for PipelineLimits {
+    fn default() -> Self {
+        Self {
+            ai_jobs: 2,
+            upload_jobs: 1,
+        }
+    }
+}
+
 /// Configuration for the wx-uploader application
 ///
 /// Contains all necessary API keys and settings for WeChat and AI provider integration.
@@ -119,6 +779,145 @@ pub struct Config {
     pub verbose: bool,
     /// Configuration file path (if loaded from file)
     pub config_file_path: Option<String>,
+    /// Treat duplicate frontmatter keys as errors instead of warnings
+    pub strict_frontmatter: bool,
+    /// Build cover scene descriptions locally instead of asking the AI
+    pub no_ai_text: bool,
+    /// Stream AI scene descriptions and print them as they arrive in verbose mode
+    pub stream_scene: bool,
+    /// Locate the offending text when WeChat rejects content as risky
+    pub diagnose_content: bool,
+    /// Skip files whose title already has a draft on WeChat and record that draft
+    pub reconcile: bool,
+    /// Log AI provider HTTP traffic at debug level, with secrets redacted
+    pub trace_http: bool,
+    /// Maximum number of body bytes logged per traced request or response
+    pub trace_body_limit: usize,
+    /// Generate covers while earlier files upload, with these limits
+    pub pipeline: Option<PipelineLimits>,
+    /// Losslessly recompress PNG covers before upload
+    pub optimize_covers: bool,
+    /// Embed the provider, model, prompt and time in generated PNG covers
+    pub embed_provenance: bool,
+    /// Image format generated covers are saved in
+    pub cover_format: CoverFormat,
+    /// JPEG quality of generated covers, from 1 to 100
+    pub cover_quality: u8,
+    /// Convert existing covers to `cover_format`
+    pub convert_covers: bool,
+    /// Write publish state and generated cover names back to the source files
+    pub write_back: bool,
+    /// Set `published: draft` and the draft ID in uploaded files
+    pub set_draft_status: bool,
+    /// State file recording uploads when write-back or status updates are disabled
+    pub state_file: Option<PathBuf>,
+    /// Directory AI scene descriptions are cached in
+    pub cache_dir: Option<PathBuf>,
+    /// Always request new scene descriptions instead of using the cache
+    pub no_cache: bool,
+    /// Account groups with nested groups expanded to account names
+    pub account_groups: HashMap<String, Vec<String>>,
+    /// Derive a slug from the title for files without one
+    pub auto_slug: bool,
+    /// Scene description prompts from the configuration file, keyed by language
+    pub scene_prompts: HashMap<String, String>,
+    /// Template of the scene description used when the AI returns none
+    pub fallback_scene: Option<String>,
+    /// Declare articles as original content unless their frontmatter says otherwise
+    pub original: bool,
+    /// What to do with files whose theme or code highlighter is unknown
+    pub theme_fallback: ThemeFallback,
+    /// What to do when the account may not upload the cover as permanent material
+    pub on_material_denied: MaterialDenied,
+    /// Which image is used when `cover` names a directory of candidates
+    pub cover_select: CoverSelect,
+    /// Theme that replaces an unknown one under [`ThemeFallback::Default`]
+    pub default_theme: Option<String>,
+    /// Code highlighter that replaces an unknown one under [`ThemeFallback::Default`]
+    pub default_code_highlighter: Option<String>,
+    /// Limits on AI image generation per run
+    pub ai_budget: BudgetLimits,
+    /// Prices the AI cost of a run is estimated with
+    pub ai_prices: AiPrices,
+    /// Estimated AI cost in USD above which a run asks before it starts
+    pub confirm_ai_cost_above: Option<f64>,
+    /// The only account was given with `--app-id` and `--app-secret`, so the
+    /// accounts named in frontmatter and manifests are ignored
+    pub cli_account: bool,
+    /// Seconds a directory run waits for another run over the same
+    /// directory to finish (`--lock-wait`)
+    pub lock_wait: u64,
+    /// Take over the lock of a directory even if its run seems alive (`--force-lock`)
+    pub force_lock: bool,
+    /// Start runs estimated above `confirm_ai_cost_above` without asking
+    /// (`--accept-ai-cost`)
+    pub accept_ai_cost: bool,
+    /// Directory that cover paths starting with `/` are resolved against
+    ///
+    /// Unset, such paths are filesystem-absolute. Always an absolute path.
+    pub content_root: Option<PathBuf>,
+    /// Template that missing covers are rendered from instead of with AI
+    pub cover_template: Option<CoverTemplate>,
+    /// Shell commands run before and after each upload and after the run
+    pub hooks: Hooks,
+    /// Fail a file whose `pre_upload` hook fails instead of skipping it
+    pub strict_hooks: bool,
+    /// Drafts each account may create per minute; 0 disables rate limiting
+    pub wechat_rpm: u32,
+    /// Retries of a request rejected for the rate limit; 0 disables retries
+    pub max_retries: u32,
+    /// Longest wait in seconds before a retry, `Retry-After` included
+    pub max_retry_delay: u64,
+    /// Milliseconds each upload worker pauses after creating a draft; 0 disables the pause
+    pub upload_delay_ms: u64,
+    /// Runs of `wx-uploader retry` a failed upload gets before it is dropped
+    pub retry_max_attempts: u32,
+    /// Characters of `description` sent as the digest before it is truncated
+    pub digest_limit: usize,
+    /// Lint rules that are not reported
+    pub disabled_lint_rules: Vec<String>,
+    /// What to do with links to headings of the same article
+    pub anchor_links: AnchorLinks,
+    /// Convert Obsidian embeds and wiki links in uploaded bodies
+    pub obsidian: bool,
+    /// Absolute vault root Obsidian embeds are resolved against
+    pub obsidian_vault: Option<PathBuf>,
+    /// What to do with Obsidian wiki links
+    pub wiki_links: WikiLinks,
+    /// Body transforms run on upload, in order; `None` runs the ones other
+    /// settings turn on
+    pub transforms: Option<Vec<String>>,
+    /// Read and write the frontmatter in sidecar files; see [`crate::markdown`]
+    pub sidecar_frontmatter: bool,
+    /// Number of candidate covers generated for a missing cover
+    pub cover_variations: u32,
+    /// Asks the questions of a run under the `--yes`/`--no-input` policy
+    pub prompt: Prompter,
+    /// Accounts every file is uploaded to when `--account` names a group
+    ///
+    /// Empty uploads to the current account only. Frontmatter `account` or
+    /// `accounts` fields take precedence.
+    pub target_accounts: Vec<String>,
+    /// Only process the files of a directory in this publish state
+    pub only: Option<PublicationStatus>,
+    /// Descend into symlinked directories when collecting a directory's files
+    pub follow_symlinks: bool,
+    /// File extensions a directory's markdown files are recognized by
+    pub markdown_extensions: Vec<String>,
+    /// Fail files that have no cover image after cover processing
+    pub require_cover: bool,
+    /// Stop the run at the first failed file
+    pub fail_fast: bool,
+    /// Daily window uploads may start in
+    pub allowed_hours: Option<AllowedHours>,
+    /// Upload outside `allowed_hours`
+    pub ignore_schedule: bool,
+    /// Back up files before a run first modifies them
+    pub backup: bool,
+    /// Number of backup sets kept
+    pub backup_keep: usize,
+    /// Where each value came from: default, environment, file or flag
+    pub provenance: Provenance,
 }
 
 impl Config {
@@ -142,8 +941,8 @@ impl Config {
         let wechat_app_id =
             env::var("WECHAT_APP_ID").map_err(|_| Error::missing_env_var("WECHAT_APP_ID"))?;
 
-        let wechat_app_secret = env::var("WECHAT_APP_SECRET")
-            .map_err(|_| Error::missing_env_var("WECHAT_APP_SECRET"))?;
+        let wechat_app_secret = env_secret("WECHAT_APP_SECRET")?
+            .ok_or_else(|| Error::missing_env_var("WECHAT_APP_SECRET"))?;
 
         // Create default account from environment variables
         let default_account = WeChatAccount {
@@ -151,13 +950,25 @@ impl Config {
             app_id: wechat_app_id,
             app_secret: wechat_app_secret,
             description: Some("Default account from environment variables".to_string()),
+            wechat_base_url: None,
+            default_cover: None,
+            kind: AccountKind::Normal,
+            tags: Vec::new(),
+            owner: None,
         };
 
         let mut available_accounts = HashMap::new();
         available_accounts.insert("default".to_string(), default_account.clone());
 
         // Determine AI provider based on environment variables
-        let ai_provider = Self::determine_ai_provider_from_env();
+        let ai_provider = Self::determine_ai_provider_from_env()?;
+
+        let mut provenance = Provenance::default();
+        provenance.set("account", ConfigSource::Env);
+        provenance.set("accounts", ConfigSource::Env);
+        if ai_provider.is_some() {
+            provenance.set("ai_provider", ConfigSource::Env);
+        }
 
         Ok(Self {
             wechat_account: default_account,
@@ -165,6 +976,71 @@ impl Config {
             ai_provider,
             verbose: false, // Default to false, can be overridden by CLI
             config_file_path: None,
+            strict_frontmatter: false,
+            no_ai_text: false,
+            stream_scene: false,
+            diagnose_content: false,
+            reconcile: false,
+            trace_http: false,
+            trace_body_limit: DEFAULT_TRACE_BODY_LIMIT,
+            pipeline: None,
+            optimize_covers: false,
+            embed_provenance: false,
+            cover_format: CoverFormat::default(),
+            cover_quality: DEFAULT_COVER_QUALITY,
+            convert_covers: false,
+            write_back: true,
+            set_draft_status: true,
+            state_file: None,
+            cache_dir: None,
+            no_cache: false,
+            auto_slug: false,
+            scene_prompts: HashMap::new(),
+            fallback_scene: None,
+            original: false,
+            theme_fallback: ThemeFallback::default(),
+            on_material_denied: MaterialDenied::default(),
+            cover_select: CoverSelect::default(),
+            default_theme: None,
+            default_code_highlighter: None,
+            ai_budget: BudgetLimits::default(),
+            ai_prices: AiPrices::default(),
+            confirm_ai_cost_above: None,
+            cli_account: false,
+            lock_wait: 0,
+            force_lock: false,
+            accept_ai_cost: false,
+            content_root: None,
+            cover_template: None,
+            hooks: Hooks::default(),
+            strict_hooks: false,
+            wechat_rpm: DEFAULT_WECHAT_RPM,
+            max_retries: DEFAULT_MAX_RETRIES,
+            max_retry_delay: DEFAULT_MAX_RETRY_DELAY.as_secs(),
+            upload_delay_ms: 0,
+            retry_max_attempts: DEFAULT_RETRY_MAX_ATTEMPTS,
+            digest_limit: DIGEST_MAX_CHARS,
+            disabled_lint_rules: Vec::new(),
+            anchor_links: AnchorLinks::default(),
+            obsidian: false,
+            obsidian_vault: None,
+            wiki_links: WikiLinks::default(),
+            transforms: None,
+            sidecar_frontmatter: false,
+            cover_variations: 1,
+            prompt: Prompter::default(),
+            account_groups: HashMap::new(),
+            target_accounts: Vec::new(),
+            only: None,
+            follow_symlinks: false,
+            markdown_extensions: default_markdown_extensions(),
+            require_cover: false,
+            fail_fast: false,
+            allowed_hours: None,
+            ignore_schedule: false,
+            backup: true,
+            backup_keep: DEFAULT_BACKUP_KEEP,
+            provenance,
         })
     }
 
@@ -189,9 +1065,16 @@ impl Config {
         let config_path = config_path.as_ref();
         let config_content = tokio::fs::read_to_string(config_path)
             .await
-            .map_err(|e| Error::config(format!("Failed to read config file: {}", e)))?;
+            .map_err(|e| match Error::io_at(config_path, e) {
+                Error::Io(e) => Error::config(format!(
+                    "Failed to read config file {}: {}",
+                    config_path.display(),
+                    e
+                )),
+                not_found => not_found,
+            })?;
 
-        let config_file: ConfigFile = if config_path.extension().and_then(|s| s.to_str()) == Some("json") {
+        let mut config_file: ConfigFile = if config_path.extension().and_then(|s| s.to_str()) == Some("json") {
             serde_json::from_str(&config_content)
                 .map_err(|e| Error::config(format!("Invalid JSON config: {}", e)))?
         } else {
@@ -204,6 +1087,135 @@ impl Config {
             return Err(Error::config("No WeChat accounts configured".to_string()));
         }
 
+        // Apply the global WeChat base URL to accounts without their own override
+        if let Some(base_url) = config_file
+            .settings
+            .as_ref()
+            .and_then(|s| s.wechat_base_url.clone())
+        {
+            for account in config_file.accounts.values_mut() {
+                if account.wechat_base_url.is_none() {
+                    account.wechat_base_url = Some(base_url.clone());
+                }
+            }
+        }
+        validate_base_urls(config_file.accounts.values())?;
+
+        let default_cover = config_file
+            .settings
+            .as_ref()
+            .and_then(|s| s.default_cover.clone());
+        for (name, account) in &mut config_file.accounts {
+            if account.default_cover.is_none() {
+                account.default_cover = default_cover.clone();
+            }
+            if let Some(cover) = &account.default_cover {
+                account.default_cover = Some(resolve_default_cover(config_path, cover, name)?);
+            }
+        }
+
+        let account_groups = expand_account_groups(&config_file.groups, &config_file.accounts)?;
+
+        if let Some(prompts) = config_file
+            .settings
+            .as_ref()
+            .and_then(|s| s.scene_prompts.as_ref())
+        {
+            validate_scene_prompts(prompts)?;
+        }
+
+        if let Some(settings) = &config_file.settings {
+            validate_style_defaults(settings)?;
+            if let Some(template) = &settings.cover_template {
+                template.validate()?;
+            }
+            if let Some(hooks) = &settings.hooks {
+                hooks.validate()?;
+            }
+            if settings.fallback_scene.as_deref().is_some_and(|s| s.trim().is_empty()) {
+                return Err(Error::config("fallback_scene must not be empty"));
+            }
+            if settings
+                .cover_quality
+                .is_some_and(|quality| !(1..=100).contains(&quality))
+            {
+                return Err(Error::config("cover_quality must be between 1 and 100"));
+            }
+            if settings.digest_limit == Some(0) {
+                return Err(Error::config("digest_limit must be at least 1"));
+            }
+            if settings.backup_keep == Some(0) {
+                return Err(Error::config("backup_keep must be at least 1"));
+            }
+            if let Some(rules) = &settings.disabled_lint_rules {
+                validate_disabled_rules(rules)?;
+            }
+            let amounts = [
+                ("max_ai_cost_per_run", settings.max_ai_cost_per_run),
+                ("ai_image_price", settings.ai_image_price),
+                ("ai_description_price", settings.ai_description_price),
+                ("confirm_ai_cost_above", settings.confirm_ai_cost_above),
+            ];
+            for (name, amount) in amounts {
+                if amount.is_some_and(|amount| !amount.is_finite() || amount < 0.0) {
+                    return Err(Error::config(format!(
+                        "{} must be a non-negative amount in USD",
+                        name
+                    )));
+                }
+            }
+        }
+
+        let allowed_hours = config_file
+            .settings
+            .as_ref()
+            .and_then(|s| s.allowed_hours.as_deref())
+            .map(str::parse::<AllowedHours>)
+            .transpose()?;
+        let backup = config_file
+            .settings
+            .as_ref()
+            .and_then(|s| s.backup)
+            .unwrap_or(true);
+        let backup_keep = config_file
+            .settings
+            .as_ref()
+            .and_then(|s| s.backup_keep)
+            .unwrap_or(DEFAULT_BACKUP_KEEP);
+        let markdown_extensions = match config_file
+            .settings
+            .as_ref()
+            .and_then(|s| s.markdown_extensions.as_deref())
+        {
+            Some(extensions) => normalize_markdown_extensions(extensions)?,
+            None => default_markdown_extensions(),
+        };
+
+        let obsidian_vault = config_file
+            .settings
+            .as_ref()
+            .and_then(|s| s.obsidian_vault.as_deref())
+            .map(|root| resolve_obsidian_vault(Path::new(root)))
+            .transpose()?;
+        let content_root = config_file
+            .settings
+            .as_ref()
+            .and_then(|s| s.content_root.as_deref())
+            .map(|root| resolve_content_root(Path::new(root)))
+            .transpose()?;
+
+        // `--account @group` uploads to every member, starting with the first;
+        // `tag:<tag>` and a name select one account
+        let (target_accounts, account_name) = match account_name {
+            Some(spec) => {
+                let accounts = resolve_account_spec(spec, &config_file.accounts, &account_groups)?;
+                let targets = if spec.starts_with('@') { accounts.clone() } else { Vec::new() };
+                (targets, accounts.into_iter().next())
+            }
+            None => (Vec::new(), None),
+        };
+        let account_name = account_name.as_deref();
+
         // Determine which account to use
         let selected_account_name = account_name
             .or(config_file.default_account.as_deref())
@@ -222,15 +1234,37 @@ impl Config {
             })?
             .clone();
 
+        let mut provenance = Provenance::default();
+        provenance.set("accounts", ConfigSource::File);
+        if account_name.is_some() || config_file.default_account.is_some() {
+            provenance.set("account", ConfigSource::File);
+        }
+        if !account_groups.is_empty() {
+            provenance.set("groups", ConfigSource::File);
+        }
+        if let Some(settings) = &config_file.settings {
+            provenance.record_settings(settings, ConfigSource::File);
+        }
+        // Account-level values are merged with the settings above
+        if selected_account.wechat_base_url.is_some() {
+            provenance.set("wechat_base_url", ConfigSource::File);
+        }
+        if selected_account.default_cover.is_some() {
+            provenance.set("default_cover", ConfigSource::File);
+        }
+        if config_file.ai_provider.is_some() {
+            provenance.set("ai_provider", ConfigSource::File);
+        }
+
         // Convert AI provider configuration
         let ai_provider = if let Some(ai_config) = config_file.ai_provider {
             match ai_config.provider.to_lowercase().as_str() {
                 "openai" => Some(AiProvider::OpenAI {
-                    api_key: ai_config.api_key,
+                    api_key: ai_config.resolve_api_key()?,
                     base_url: ai_config.base_url,
                 }),
                 "gemini" => Some(AiProvider::Gemini {
-                    api_key: ai_config.api_key,
+                    api_key: ai_config.resolve_api_key()?,
                     base_url: ai_config.base_url,
                 }),
                 _ => {
@@ -242,7 +1276,11 @@ impl Config {
             }
         } else {
             // Try environment variables as fallback
-            Self::determine_ai_provider_from_env()
+            let ai_provider = Self::determine_ai_provider_from_env()?;
+            if ai_provider.is_some() {
+                provenance.set("ai_provider", ConfigSource::Env);
+            }
+            ai_provider
         };
 
         Ok(Self {
@@ -251,9 +1289,353 @@ impl Config {
             ai_provider,
             verbose: config_file.settings.as_ref().and_then(|s| s.verbose).unwrap_or(false),
             config_file_path: Some(config_path.to_string_lossy().to_string()),
+            strict_frontmatter: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.strict_frontmatter)
+                .unwrap_or(false),
+            no_ai_text: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.no_ai_text)
+                .unwrap_or(false),
+            stream_scene: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.stream_scene)
+                .unwrap_or(false),
+            diagnose_content: false,
+            reconcile: false,
+            trace_http: false,
+            trace_body_limit: DEFAULT_TRACE_BODY_LIMIT,
+            pipeline: None,
+            optimize_covers: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.optimize_covers)
+                .unwrap_or(false),
+            embed_provenance: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.embed_provenance)
+                .unwrap_or(false),
+            cover_format: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.cover_format)
+                .unwrap_or_default(),
+            cover_quality: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.cover_quality)
+                .unwrap_or(DEFAULT_COVER_QUALITY),
+            convert_covers: false,
+            write_back: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.write_back)
+                .unwrap_or(true),
+            set_draft_status: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.set_draft_status)
+                .unwrap_or(true),
+            state_file: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.state_file.as_ref())
+                .map(PathBuf::from),
+            cache_dir: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.cache_dir.as_ref())
+                .map(PathBuf::from),
+            no_cache: false,
+            auto_slug: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.auto_slug)
+                .unwrap_or(false),
+            scene_prompts: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.scene_prompts.clone())
+                .unwrap_or_default(),
+            fallback_scene: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.fallback_scene.clone()),
+            original: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.original)
+                .unwrap_or(false),
+            theme_fallback: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.theme_fallback)
+                .unwrap_or_default(),
+            on_material_denied: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.on_material_denied)
+                .unwrap_or_default(),
+            cover_select: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.cover_select)
+                .unwrap_or_default(),
+            default_theme: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.default_theme.clone()),
+            default_code_highlighter: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.default_code_highlighter.clone()),
+            ai_budget: BudgetLimits {
+                max_images: config_file
+                    .settings
+                    .as_ref()
+                    .and_then(|s| s.max_ai_images_per_run)
+                    .unwrap_or(0),
+                max_cost: config_file
+                    .settings
+                    .as_ref()
+                    .and_then(|s| s.max_ai_cost_per_run)
+                    .unwrap_or(0.0),
+            },
+            ai_prices: AiPrices {
+                image: config_file.settings.as_ref().and_then(|s| s.ai_image_price),
+                description: config_file
+                    .settings
+                    .as_ref()
+                    .and_then(|s| s.ai_description_price)
+                    .unwrap_or(DEFAULT_DESCRIPTION_PRICE),
+            },
+            confirm_ai_cost_above: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.confirm_ai_cost_above),
+            cli_account: false,
+            lock_wait: 0,
+            force_lock: false,
+            accept_ai_cost: false,
+            content_root,
+            strict_hooks: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.strict_hooks)
+                .unwrap_or(false),
+            hooks: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.hooks.clone())
+                .unwrap_or_default(),
+            wechat_rpm: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.wechat_rpm)
+                .unwrap_or(DEFAULT_WECHAT_RPM),
+            max_retries: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.max_retries)
+                .unwrap_or(DEFAULT_MAX_RETRIES),
+            max_retry_delay: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.max_retry_delay)
+                .unwrap_or(DEFAULT_MAX_RETRY_DELAY.as_secs()),
+            upload_delay_ms: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.upload_delay_ms)
+                .unwrap_or(0),
+            retry_max_attempts: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.retry_max_attempts)
+                .unwrap_or(DEFAULT_RETRY_MAX_ATTEMPTS),
+            digest_limit: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.digest_limit)
+                .unwrap_or(DIGEST_MAX_CHARS),
+            disabled_lint_rules: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.disabled_lint_rules.clone())
+                .unwrap_or_default(),
+            anchor_links: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.anchor_links)
+                .unwrap_or_default(),
+            obsidian: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.obsidian)
+                .unwrap_or(false),
+            obsidian_vault,
+            wiki_links: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.wiki_links)
+                .unwrap_or_default(),
+            transforms: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.transforms.clone()),
+            sidecar_frontmatter: config_file
+                .settings
+                .as_ref()
+                .and_then(|s| s.sidecar_frontmatter)
+                .unwrap_or(false),
+            cover_variations: 1,
+            prompt: Prompter::default(),
+            cover_template: config_file.settings.and_then(|s| s.cover_template),
+            account_groups,
+            target_accounts,
+            only: None,
+            follow_symlinks: false,
+            markdown_extensions,
+            require_cover: false,
+            fail_fast: false,
+            allowed_hours,
+            ignore_schedule: false,
+            backup,
+            backup_keep,
+            provenance,
         })
     }
 
+    /// Re-reads the configuration file this configuration was loaded from
+    ///
+    /// The current account stays selected, as do the accounts of an
+    /// `--account @group` run and an account given with `--app-id`. Options that only exist on the command line
+    /// (`--diagnose-content`, `--trace-http`, `--pipeline`, `--only`,
+    /// `--follow-symlinks`, `--no-cache`, `--convert-covers`, `--require-cover`,
+    /// `--fail-fast`, `--ignore-schedule`, interactivity) are kept; everything
+    /// else, including command-line overrides of file settings, comes from the
+    /// file. `self` is left unchanged on error.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if this configuration was not loaded from a file, the
+    /// file is invalid, or a selected account is no longer configured
+    pub async fn reload(&self) -> Result<Self> {
+        let Some(path) = &self.config_file_path else {
+            return Err(Error::config("configuration was not loaded from a file"));
+        };
+
+        let account = (!self.cli_account).then_some(self.wechat_account.name.as_str());
+        let mut config = Self::from_file(path, account).await?;
+        if self.cli_account {
+            let account = &self.wechat_account;
+            config.use_cli_account(account.app_id.clone(), account.app_secret.clone());
+        }
+        if let Some(missing) = self
+            .target_accounts
+            .iter()
+            .find(|name| !config.available_accounts.contains_key(*name))
+        {
+            return Err(Error::config(format!(
+                "account '{}' is no longer configured in {}",
+                missing, path
+            )));
+        }
+
+        config.target_accounts = self.target_accounts.clone();
+        config.diagnose_content = self.diagnose_content;
+        config.reconcile = self.reconcile;
+        config.trace_http = self.trace_http;
+        config.trace_body_limit = self.trace_body_limit;
+        config.pipeline = self.pipeline;
+        config.prompt = self.prompt.clone();
+        config.only = self.only;
+        config.follow_symlinks = self.follow_symlinks;
+        config.require_cover = self.require_cover;
+        config.fail_fast = self.fail_fast;
+        config.ignore_schedule = self.ignore_schedule;
+        config.no_cache = self.no_cache;
+        config.convert_covers = self.convert_covers;
+        config.lock_wait = self.lock_wait;
+        config.force_lock = self.force_lock;
+        config.accept_ai_cost = self.accept_ai_cost;
+        Ok(config)
+    }
+
+    /// Returns how requests rejected for the rate limit are retried
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_retries: self.max_retries,
+            max_delay: std::time::Duration::from_secs(self.max_retry_delay),
+        }
+    }
+
+    /// Returns the pacer pausing each upload worker for `upload_delay_ms`
+    ///
+    /// There is one worker, or one per concurrent upload with `--pipeline`.
+    pub fn upload_pacer(&self) -> UploadPacer {
+        let workers = self.pipeline.map_or(1, |limits| limits.upload_jobs);
+        UploadPacer::new(std::time::Duration::from_millis(self.upload_delay_ms), workers)
+    }
+
+    /// Returns true if uploads are recorded in the frontmatter of the uploaded files
+    ///
+    /// Otherwise they are recorded in the state file. `--no-write-back`
+    /// disables all writes, status updates included.
+    pub fn updates_status(&self) -> bool {
+        self.write_back && self.set_draft_status
+    }
+
+    /// Returns the state file that records uploads when they are not written to the files
+    pub fn state_file_path(&self) -> PathBuf {
+        self.state_file
+            .clone()
+            .unwrap_or_else(|| {
+                crate::state::default_state_file(&std::env::current_dir().unwrap_or_default())
+            })
+    }
+
+    /// Checks that an upload may start now under `allowed_hours`
+    ///
+    /// # Errors
+    ///
+    /// Returns a configuration error outside the allowed hours, unless
+    /// `ignore_schedule` is set
+    pub fn check_schedule(&self, clock: &dyn Clock) -> Result<()> {
+        match &self.allowed_hours {
+            Some(hours) if !self.ignore_schedule => hours.check(clock),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the format and quality generated covers are encoded with
+    pub fn cover_encoding(&self) -> CoverEncoding {
+        CoverEncoding {
+            format: self.cover_format,
+            quality: self.cover_quality,
+        }
+    }
+
+    /// Returns the directory AI scene descriptions are cached in
+    pub fn cache_dir_path(&self) -> PathBuf {
+        self.cache_dir
+            .clone()
+            .unwrap_or_else(crate::scene_cache::default_cache_dir)
+    }
+
+    /// Returns the HTTP tracer for AI provider clients
+    pub fn http_tracer(&self) -> HttpTracer {
+        if self.trace_http {
+            HttpTracer::new(self.trace_body_limit)
+        } else {
+            HttpTracer::disabled()
+        }
+    }
+
     /// Lists all available accounts in the current configuration
     pub fn list_accounts(&self) -> Vec<&WeChatAccount> {
         self.available_accounts.values().collect()
@@ -269,42 +1651,64 @@ impl Config {
     ///
     /// Returns an error if the account is not found
     pub fn switch_account(&mut self, account_name: &str) -> Result<()> {
-        let account = self
-            .available_accounts
-            .get(account_name)
-            .ok_or_else(|| {
-                Error::config(format!(
-                    "Account '{}' not found. Available accounts: {}",
-                    account_name,
-                    self.available_accounts.keys().map(|s| s.as_str()).collect::<Vec<_>>().join(", ")
-                ))
-            })?
-            .clone();
-
-        self.wechat_account = account;
+        self.wechat_account = self.account(account_name)?.clone();
         Ok(())
     }
 
+    /// Looks up a configured account by name
+    ///
+    /// # Errors
+    ///
+    /// Returns an error listing the available accounts if the account is not found
+    pub fn account(&self, account_name: &str) -> Result<&WeChatAccount> {
+        self.available_accounts.get(account_name).ok_or_else(|| {
+            Error::config(format!(
+                "Account '{}' not found. Available accounts: {}",
+                account_name,
+                self.available_account_names().join(", ")
+            ))
+        })
+    }
+
+    /// Returns the names of all configured accounts, sorted
+    pub fn available_account_names(&self) -> Vec<&str> {
+        sorted_keys(&self.available_accounts)
+    }
+
+    /// Resolves account names, `@group` and `tag:<tag>` references to account names
+    ///
+    /// Groups are expanded in place and duplicates are dropped, keeping the
+    /// first occurrence.
+    ///
+    /// # Errors
+    ///
+    /// Returns a configuration error for an unknown account or group, or a
+    /// tag that no account or several accounts have
+    pub fn resolve_accounts(&self, specs: &[String]) -> Result<Vec<String>> {
+        let mut accounts = Vec::new();
+        for spec in specs {
+            let members =
+                resolve_account_spec(spec, &self.available_accounts, &self.account_groups)?;
+            for member in members {
+                if !accounts.contains(&member) {
+                    accounts.push(member);
+                }
+            }
+        }
+        Ok(accounts)
+    }
+
     /// Determine AI provider from environment variables
-    fn determine_ai_provider_from_env() -> Option<AiProvider> {
+    ///
+    /// API keys may also be given as files via `GEMINI_API_KEY_FILE` or
+    /// `OPENAI_API_KEY_FILE`.
+    fn determine_ai_provider_from_env() -> Result<Option<AiProvider>> {
         let provider_type = env::var("AI_PROVIDER").unwrap_or_else(|_| "openai".to_string()).to_lowercase();
         
         match provider_type.as_str() {
-            "gemini" => {
-                if let Ok(api_key) = env::var("GEMINI_API_KEY") {
-                    Some(AiProvider::gemini(api_key))
-                } else {
-                    None
-                }
-            }
-            "openai" | _ => {
-                // Default to OpenAI, also check legacy OPENAI_API_KEY
-                if let Ok(api_key) = env::var("OPENAI_API_KEY") {
-                    Some(AiProvider::openai(api_key))
-                } else {
-                    None
-                }
-            }
+            "gemini" => Ok(env_secret("GEMINI_API_KEY")?.map(AiProvider::gemini)),
+            // Default to OpenAI, also check legacy OPENAI_API_KEY
+            _ => Ok(env_secret("OPENAI_API_KEY")?.map(AiProvider::openai)),
         }
     }
 
@@ -320,6 +1724,11 @@ impl Config {
             app_id: wechat_app_id,
             app_secret: wechat_app_secret,
             description: Some("Main account".to_string()),
+            wechat_base_url: None,
+            default_cover: None,
+            kind: AccountKind::Normal,
+            tags: Vec::new(),
+            owner: None,
         };
 
         let mut available_accounts = HashMap::new();
@@ -331,6 +1740,71 @@ impl Config {
             ai_provider,
             verbose,
             config_file_path: None,
+            strict_frontmatter: false,
+            no_ai_text: false,
+            stream_scene: false,
+            diagnose_content: false,
+            reconcile: false,
+            trace_http: false,
+            trace_body_limit: DEFAULT_TRACE_BODY_LIMIT,
+            pipeline: None,
+            optimize_covers: false,
+            embed_provenance: false,
+            cover_format: CoverFormat::default(),
+            cover_quality: DEFAULT_COVER_QUALITY,
+            convert_covers: false,
+            write_back: true,
+            set_draft_status: true,
+            state_file: None,
+            cache_dir: None,
+            no_cache: false,
+            auto_slug: false,
+            scene_prompts: HashMap::new(),
+            fallback_scene: None,
+            original: false,
+            theme_fallback: ThemeFallback::default(),
+            on_material_denied: MaterialDenied::default(),
+            cover_select: CoverSelect::default(),
+            default_theme: None,
+            default_code_highlighter: None,
+            ai_budget: BudgetLimits::default(),
+            ai_prices: AiPrices::default(),
+            confirm_ai_cost_above: None,
+            cli_account: false,
+            lock_wait: 0,
+            force_lock: false,
+            accept_ai_cost: false,
+            content_root: None,
+            cover_template: None,
+            hooks: Hooks::default(),
+            strict_hooks: false,
+            wechat_rpm: DEFAULT_WECHAT_RPM,
+            max_retries: DEFAULT_MAX_RETRIES,
+            max_retry_delay: DEFAULT_MAX_RETRY_DELAY.as_secs(),
+            upload_delay_ms: 0,
+            retry_max_attempts: DEFAULT_RETRY_MAX_ATTEMPTS,
+            digest_limit: DIGEST_MAX_CHARS,
+            disabled_lint_rules: Vec::new(),
+            anchor_links: AnchorLinks::default(),
+            obsidian: false,
+            obsidian_vault: None,
+            wiki_links: WikiLinks::default(),
+            transforms: None,
+            sidecar_frontmatter: false,
+            cover_variations: 1,
+            prompt: Prompter::default(),
+            account_groups: HashMap::new(),
+            target_accounts: Vec::new(),
+            only: None,
+            follow_symlinks: false,
+            markdown_extensions: default_markdown_extensions(),
+            require_cover: false,
+            fail_fast: false,
+            allowed_hours: None,
+            ignore_schedule: false,
+            backup: true,
+            backup_keep: DEFAULT_BACKUP_KEEP,
+            provenance: Provenance::default(),
         }
     }
 
@@ -357,11 +1831,118 @@ impl Config {
             ai_provider,
             verbose,
             config_file_path: None,
+            strict_frontmatter: false,
+            no_ai_text: false,
+            stream_scene: false,
+            diagnose_content: false,
+            reconcile: false,
+            trace_http: false,
+            trace_body_limit: DEFAULT_TRACE_BODY_LIMIT,
+            pipeline: None,
+            optimize_covers: false,
+            embed_provenance: false,
+            cover_format: CoverFormat::default(),
+            cover_quality: DEFAULT_COVER_QUALITY,
+            convert_covers: false,
+            write_back: true,
+            set_draft_status: true,
+            state_file: None,
+            cache_dir: None,
+            no_cache: false,
+            auto_slug: false,
+            scene_prompts: HashMap::new(),
+            fallback_scene: None,
+            original: false,
+            theme_fallback: ThemeFallback::default(),
+            on_material_denied: MaterialDenied::default(),
+            cover_select: CoverSelect::default(),
+            default_theme: None,
+            default_code_highlighter: None,
+            ai_budget: BudgetLimits::default(),
+            ai_prices: AiPrices::default(),
+            confirm_ai_cost_above: None,
+            cli_account: false,
+            lock_wait: 0,
+            force_lock: false,
+            accept_ai_cost: false,
+            content_root: None,
+            cover_template: None,
+            hooks: Hooks::default(),
+            strict_hooks: false,
+            wechat_rpm: DEFAULT_WECHAT_RPM,
+            max_retries: DEFAULT_MAX_RETRIES,
+            max_retry_delay: DEFAULT_MAX_RETRY_DELAY.as_secs(),
+            upload_delay_ms: 0,
+            retry_max_attempts: DEFAULT_RETRY_MAX_ATTEMPTS,
+            digest_limit: DIGEST_MAX_CHARS,
+            disabled_lint_rules: Vec::new(),
+            anchor_links: AnchorLinks::default(),
+            obsidian: false,
+            obsidian_vault: None,
+            wiki_links: WikiLinks::default(),
+            transforms: None,
+            sidecar_frontmatter: false,
+            cover_variations: 1,
+            prompt: Prompter::default(),
+            account_groups: HashMap::new(),
+            target_accounts: Vec::new(),
+            only: None,
+            follow_symlinks: false,
+            markdown_extensions: default_markdown_extensions(),
+            require_cover: false,
+            fail_fast: false,
+            allowed_hours: None,
+            ignore_schedule: false,
+            backup: true,
+            backup_keep: DEFAULT_BACKUP_KEEP,
+            provenance: Provenance::default(),
         })
     }
 
-    /// Creates a new configuration with legacy OpenAI key support
-    #[deprecated(note = "Use new() with AiProvider instead")]
+    /// Creates a configuration for an account given on the command line
+    ///
+    /// Needs no config file or `WECHAT_*` environment variables; the AI
+    /// provider still comes from the environment.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an AI key environment variable cannot be read
+    pub fn from_credentials(app_id: String, app_secret: String) -> Result<Self> {
+        let ai_provider = Self::determine_ai_provider_from_env()?;
+        let mut config = Self::new(app_id.clone(), app_secret.clone(), ai_provider, false);
+        if config.ai_provider.is_some() {
+            config.provenance.set("ai_provider", ConfigSource::Env);
+        }
+        config.use_cli_account(app_id, app_secret);
+        Ok(config)
+    }
+
+    /// Replaces all accounts and groups with one account given on the command line
+    ///
+    /// Every file is uploaded to it, whatever account its frontmatter names.
+    pub fn use_cli_account(&mut self, app_id: String, app_secret: String) {
+        let account = WeChatAccount {
+            name: CLI_ACCOUNT_NAME.to_string(),
+            app_id,
+            app_secret,
+            description: Some("Account given with --app-id".to_string()),
+            wechat_base_url: None,
+            default_cover: None,
+            kind: AccountKind::Normal,
+            tags: Vec::new(),
+            owner: None,
+        };
+        self.available_accounts = HashMap::from([(account.name.clone(), account.clone())]);
+        self.wechat_account = account;
+        self.account_groups.clear();
+        self.target_accounts.clear();
+        self.cli_account = true;
+        self.provenance.set("account", ConfigSource::Flag);
+        self.provenance.set("accounts", ConfigSource::Flag);
+    }
+
+    /// Creates a new configuration with legacy OpenAI key support
+    #[deprecated(note = "Use new() with AiProvider instead")]
     pub fn new_with_openai_key(
         wechat_app_id: String,
         wechat_app_secret: String,
@@ -378,6 +1959,15 @@ impl Config {
         self
     }
 
+    /// Overrides the WeChat API base URL for the active and all available accounts
+    pub fn set_wechat_base_url(&mut self, base_url: impl Into<String>) {
+        let base_url = base_url.into();
+        self.wechat_account.wechat_base_url = Some(base_url.clone());
+        for account in self.available_accounts.values_mut() {
+            account.wechat_base_url = Some(base_url.clone());
+        }
+    }
+
     /// Validates the configuration
     ///
     /// # Errors
@@ -414,9 +2004,18 @@ impl Config {
                 )));
             }
         }
+        validate_base_urls(self.available_accounts.values())
+    }
+}
 
-        Ok(())
+/// Checks the WeChat API base URLs of `accounts` before any client is built
+fn validate_base_urls<'a>(accounts: impl IntoIterator<Item = &'a WeChatAccount>) -> Result<()> {
+    for account in accounts {
+        if let Some(base_url) = &account.wechat_base_url {
+            normalize_base_url(base_url)?;
+        }
     }
+    Ok(())
 }
 
 /// YAML frontmatter structure for markdown files.
@@ -431,11 +2030,14 @@ impl Config {
 /// ---
 /// title: "My Article"
 /// published: "draft"
+/// draft_id: "MEDIA_ID"
 /// author: "John Doe"
 /// tags: ["rust", "wechat"]
 /// theme: "lapis"
 /// code: "github"
 /// cover: "cover.png"
+/// thumb: "thumb.png"
+/// account: "work"
 /// ---
 /// ```
 #[derive(Debug, Clone, Deserialize, Serialize, Default, PartialEq)]
@@ -445,20 +2047,89 @@ pub struct Frontmatter {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub title: Option<String>,
 
+    /// URL-safe name of the article, used as the base name of generated covers.
+    ///
+    /// Derived from the title with `--auto-slug` when missing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slug: Option<String>,
+
+    /// Language of the article, e.g. `en` or `zh`.
+    ///
+    /// Selects the prompt used to describe the cover scene; detected from the
+    /// content when missing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+
     /// Publication status of the article.
     ///
     /// Common values:
     /// - `None` or missing: not uploaded
-    /// - `"draft"`: uploaded as draft to WeChat
-    /// - `"true"`: published (will be skipped in directory mode)
+    /// - `draft`: uploaded as draft to WeChat
+    /// - `true`: published (will be skipped in directory mode)
+    /// - `skip`: skipped in directory mode without being uploaded
+    ///
+    /// See [`PublishedField`] for the spellings accepted on read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published: Option<PublishedField>,
+
+    /// Media ID of the WeChat draft created by the last upload.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub published: Option<String>,
+    pub draft_id: Option<String>,
 
     /// Cover image filename for the article.
     /// If missing, the system will attempt to generate one using AI.
-    #[serde(skip_serializing_if = "Option::is_none")]
+    ///
+    /// `none` or `false` opts out: no cover is generated and the draft gets
+    /// the account's `default_cover` as its thumbnail.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_cover",
+        serialize_with = "serialize_cover"
+    )]
     pub cover: Option<String>,
 
+    /// Thumbnail image shown in the WeChat draft/article list.
+    ///
+    /// Defaults to the cover when unset. Set to `first-image` to use the first
+    /// local image in the body. When it differs from the cover, the cover is
+    /// shown as the in-article header image instead.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumb: Option<String>,
+
+    /// Media ID of a permanent WeChat material used as the thumbnail.
+    ///
+    /// Lets a series of articles share one uploaded cover: the cover and
+    /// thumbnail files are neither resolved, generated nor uploaded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cover_media_id: Option<String>,
+
+    /// Template the cover is rendered from when missing, instead of with AI.
+    ///
+    /// Fields set here override the `cover_template` in the configuration.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cover_template: Option<CoverTemplate>,
+
+    /// Number of candidate covers generated when the cover is missing.
+    ///
+    /// Overrides `--cover-variations` for this file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cover_variations: Option<u32>,
+
+    /// AI provider generating this file's cover, `openai` or `gemini`.
+    ///
+    /// Must match the configured provider, whose credentials are used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ai_provider: Option<String>,
+
+    /// Text model describing this file's cover scene, e.g. `gpt-4o`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text_model: Option<String>,
+
+    /// Image model generating this file's cover, e.g. `gpt-image-1`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_model: Option<String>,
+
     /// Theme for the WeChat article styling.
     ///
     /// Available themes: default, lapis, maize, orangeheart, phycat, pie, purple, rainbow
@@ -472,6 +2143,66 @@ pub struct Frontmatter {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub code: Option<String>,
 
+    /// Body transforms run when this article is uploaded, by name and in order.
+    ///
+    /// Overrides the `transforms` setting; an empty list uploads the body as written.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transforms: Option<Vec<String>>,
+
+    /// "Read more" link shown at the end of the WeChat article.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_source_url: Option<String>,
+
+    /// Whether readers can comment on the article (default: false).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub need_open_comment: Option<bool>,
+
+    /// Whether only followers can comment (default: false).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub only_fans_can_comment: Option<bool>,
+
+    /// Whether the article is declared as original content (原创声明).
+    ///
+    /// Overrides the `original` setting of the configuration.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original: Option<bool>,
+
+    /// Author named in the original declaration; defaults to `author`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_author: Option<String>,
+
+    /// 2.35:1 crop of the thumbnail as `X1_Y1_X2_Y2`, fractions of the
+    /// image size, e.g. `0_0.2_1_0.8`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pic_crop_235_1: Option<String>,
+
+    /// 1:1 crop of the thumbnail as `X1_Y1_X2_Y2`, fractions of the image size.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pic_crop_1_1: Option<String>,
+
+    /// Name of the configured WeChat account to upload this article to.
+    ///
+    /// Overrides the CLI/default account for this file; the account must
+    /// exist in the configuration.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+
+    /// Accounts or `@group`s to upload this article to, one draft per account.
+    ///
+    /// Cannot be combined with `account`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accounts: Option<Vec<String>>,
+
+    /// Draft IDs of multi-account uploads, keyed by account name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uploads: Option<BTreeMap<String, String>>,
+
+    /// Marks the file as not an article, e.g. a shared include or snippet.
+    ///
+    /// Directory runs and `--validate` leave out files with `ignore: true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ignore: Option<bool>,
+
     /// Description of the article.
     #[serde(default, skip_serializing_if = "String::is_empty")]
     pub description: String,
@@ -496,14 +2227,51 @@ impl Frontmatter {
         }
     }
 
+    /// Returns true if no field is set, as for a file without frontmatter
+    pub fn is_empty(&self) -> bool {
+        let other_empty = match &self.other {
+            serde_yaml::Value::Null => true,
+            serde_yaml::Value::Mapping(map) => map.is_empty(),
+            _ => false,
+        };
+
+        self.title.is_none()
+            && self.slug.is_none()
+            && self.lang.is_none()
+            && self.published.is_none()
+            && self.draft_id.is_none()
+            && self.cover.is_none()
+            && self.thumb.is_none()
+            && self.cover_media_id.is_none()
+            && self.ai_provider.is_none()
+            && self.text_model.is_none()
+            && self.image_model.is_none()
+            && self.theme.is_none()
+            && self.code.is_none()
+            && self.transforms.is_none()
+            && self.content_source_url.is_none()
+            && self.need_open_comment.is_none()
+            && self.only_fans_can_comment.is_none()
+            && self.original.is_none()
+            && self.original_author.is_none()
+            && self.pic_crop_235_1.is_none()
+            && self.pic_crop_1_1.is_none()
+            && self.account.is_none()
+            && self.accounts.is_none()
+            && self.uploads.is_none()
+            && self.ignore.is_none()
+            && self.description.is_empty()
+            && other_empty
+    }
+
     /// Sets the title
     pub fn set_title(&mut self, title: impl Into<String>) {
         self.title = Some(title.into());
     }
 
-    /// Sets the published status
-    pub fn set_published(&mut self, status: impl Into<String>) {
-        self.published = Some(status.into());
+    /// Sets the published status, written in its canonical form
+    pub fn set_published(&mut self, status: PublicationStatus) {
+        self.published = Some(PublishedField::from(status));
     }
 
     /// Sets the cover image
@@ -511,6 +2279,74 @@ impl Frontmatter {
         self.cover = Some(cover.into());
     }
 
+    /// Sets the thumbnail image
+    pub fn set_thumb(&mut self, thumb: impl Into<String>) {
+        self.thumb = Some(thumb.into());
+    }
+
+    /// Returns the base name for generated assets: the slug, or else the file stem
+    pub fn asset_base_name(&self, path: &Path) -> String {
+        self.slug.clone().unwrap_or_else(|| {
+            path.file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("article")
+                .to_string()
+        })
+    }
+
+    /// Returns the `author` field, which is kept with the other custom fields
+    pub fn author(&self) -> Option<&str> {
+        self.other
+            .get("author")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|a| !a.is_empty())
+    }
+
+    /// Returns the `tags` field, given as a list or a comma-separated string
+    pub fn tags(&self) -> Vec<String> {
+        let tags: Vec<&str> = match self.other.get("tags") {
+            Some(serde_yaml::Value::Sequence(tags)) => {
+                tags.iter().filter_map(|tag| tag.as_str()).collect()
+            }
+            Some(serde_yaml::Value::String(tags)) => tags.split([',', '，']).collect(),
+            _ => Vec::new(),
+        };
+        tags.into_iter()
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Returns the image used as the draft thumbnail, falling back to the cover
+    pub fn thumb_or_cover(&self) -> Option<&str> {
+        self.thumb.as_deref().or(self.cover.as_deref())
+    }
+
+    /// Records the draft created for an account by a multi-account upload
+    pub fn set_upload(&mut self, account: impl Into<String>, draft_id: impl Into<String>) {
+        self.uploads
+            .get_or_insert_with(BTreeMap::new)
+            .insert(account.into(), draft_id.into());
+    }
+
+    /// Returns the accounts or `@group`s named in the frontmatter
+    ///
+    /// # Errors
+    ///
+    /// Returns a configuration error if both `account` and `accounts` are set
+    pub fn account_specs(&self) -> Result<Vec<String>> {
+        match (&self.account, &self.accounts) {
+            (Some(_), Some(_)) => Err(Error::config(
+                "frontmatter sets both account and accounts; use one",
+            )),
+            (Some(account), None) => Ok(vec![account.clone()]),
+            (None, Some(accounts)) => Ok(accounts.clone()),
+            (None, None) => Ok(Vec::new()),
+        }
+    }
+
     /// Sets the theme
     pub fn set_theme(&mut self, theme: impl Into<String>) {
         self.theme = Some(theme.into());
@@ -521,62 +2357,500 @@ impl Frontmatter {
         self.code = Some(code.into());
     }
 
-    /// Checks if the article is published
-    pub fn is_published(&self) -> bool {
-        // Check the published field first
-        if matches!(self.published.as_deref(), Some("true") | Some("\"true\"")) {
-            return true;
-        }
+    /// Returns true if the cover is `none` or `false`, an explicit opt-out of a cover
+    pub fn cover_disabled(&self) -> bool {
+        self.cover.as_deref().is_some_and(is_no_cover)
+    }
 
-        // Check if published is a boolean true in the other field
-        if let serde_yaml::Value::Mapping(map) = &self.other
-            && let Some(serde_yaml::Value::Bool(true)) =
-                map.get(serde_yaml::Value::String("published".to_string()))
-        {
-            return true;
-        }
+    /// Returns the cover image, or `None` if there is none or it is disabled
+    pub fn cover_image(&self) -> Option<&str> {
+        self.cover.as_deref().filter(|cover| !is_no_cover(cover))
+    }
 
-        false
+    /// Returns true if the frontmatter marks the file as not an article (`ignore: true`)
+    pub fn is_ignored(&self) -> bool {
+        self.ignore == Some(true)
     }
 
-    /// Checks if the article is a draft
-    pub fn is_draft(&self) -> bool {
-        matches!(self.published.as_deref(), Some("draft"))
+    /// Returns the publication status; a missing `published` field is unpublished
+    pub fn publication_status(&self) -> PublicationStatus {
+        self.published
+            .as_ref()
+            .map_or(PublicationStatus::Unpublished, PublishedField::status)
     }
 
-    /// Checks if the article is unpublished
-    pub fn is_unpublished(&self) -> bool {
-        self.published.is_none() || self.published.as_deref() == Some("")
+    /// Sets the draft media ID
+    pub fn set_draft_id(&mut self, draft_id: impl Into<String>) {
+        self.draft_id = Some(draft_id.into());
     }
 
-    /// Validates the frontmatter
-    pub fn validate(&self) -> Result<()> {
-        // Validate theme if present
+    /// Returns why the theme or code highlighter is not recognized, if one is not
+    pub fn style_error(&self) -> Option<String> {
         if let Some(theme) = &self.theme
             && !is_valid_theme(theme)
         {
-            return Err(Error::config(format!(
+            return Some(format!(
                 "Invalid theme '{}'. Available themes: {}",
                 theme,
                 VALID_THEMES.join(", ")
-            )));
+            ));
         }
 
-        // Validate code highlighter if present
         if let Some(code) = &self.code
             && !is_valid_code_highlighter(code)
         {
-            return Err(Error::config(format!(
+            return Some(format!(
                 "Invalid code highlighter '{}'. Available highlighters: {}",
                 code,
                 VALID_CODE_HIGHLIGHTERS.join(", ")
-            )));
+            ));
         }
 
-        Ok(())
+        None
+    }
+
+    /// Replaces an unknown theme or code highlighter with `theme` or `code`
+    ///
+    /// `None` removes the field, so the renderer's own default applies.
+    /// Returns a description of each replaced value, e.g. `theme 'nope'`.
+    pub fn replace_unknown_style(&mut self, theme: Option<&str>, code: Option<&str>) -> Vec<String> {
+        let mut replaced = Vec::new();
+        if let Some(unknown) = self.theme.take_if(|t| !is_valid_theme(t)) {
+            replaced.push(format!("theme '{}'", unknown));
+            self.theme = theme.map(str::to_string);
+        }
+        if let Some(unknown) = self.code.take_if(|c| !is_valid_code_highlighter(c)) {
+            replaced.push(format!("code highlighter '{}'", unknown));
+            self.code = code.map(str::to_string);
+        }
+        replaced
+    }
+
+    /// Returns a warning if `description` is too long for the digest, which
+    /// is then truncated to `limit` characters on upload
+    pub fn digest_warning(&self, limit: usize) -> Option<String> {
+        let length = digest_length(self.description.trim());
+        (length > limit).then(|| {
+            format!(
+                "description is {} characters, over the digest limit of {}; the digest is truncated",
+                length, limit
+            )
+        })
+    }
+
+    /// Validates the frontmatter
+    pub fn validate(&self) -> Result<()> {
+        self.validate_with(ThemeFallback::Error)
+    }
+
+    /// Validates the frontmatter, leaving an unknown theme or code highlighter
+    /// to the caller unless `theme_fallback` is [`ThemeFallback::Error`]
+    ///
+    /// Only the first invalid value is reported; see [`Frontmatter::issues`]
+    /// for all of them.
+    pub fn validate_with(&self, theme_fallback: ThemeFallback) -> Result<()> {
+        match self.issues(theme_fallback).into_iter().next() {
+            Some(issue) => Err(Error::config(issue.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns every invalid value of the frontmatter, in field order
+    ///
+    /// An unknown theme or code highlighter is only an issue under
+    /// [`ThemeFallback::Error`]; the other policies leave it to the caller.
+    pub fn issues(&self, theme_fallback: ThemeFallback) -> Vec<FrontmatterIssue> {
+        let mut issues = Vec::new();
+        let mut check = |field, value: &Option<String>, valid: fn(&str) -> bool| {
+            if let Some(value) = value
+                && !valid(value)
+            {
+                issues.push(FrontmatterIssue {
+                    field,
+                    value: value.clone(),
+                });
+            }
+        };
+
+        if theme_fallback == ThemeFallback::Error {
+            check(FrontmatterField::Theme, &self.theme, is_valid_theme);
+            check(FrontmatterField::CodeHighlighter, &self.code, is_valid_code_highlighter);
+        }
+        // Slugs become file names and URLs
+        check(FrontmatterField::Slug, &self.slug, is_valid_slug);
+        check(FrontmatterField::ContentSourceUrl, &self.content_source_url, |url| {
+            url.starts_with("http://") || url.starts_with("https://")
+        });
+        check(FrontmatterField::PicCrop235_1, &self.pic_crop_235_1, is_valid_pic_crop);
+        check(FrontmatterField::PicCrop1_1, &self.pic_crop_1_1, is_valid_pic_crop);
+        check(FrontmatterField::CoverMediaId, &self.cover_media_id, is_valid_media_id);
+        check(FrontmatterField::Language, &self.lang, is_valid_language);
+        issues
+    }
+}
+
+/// Frontmatter field whose value can be invalid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FrontmatterField {
+    /// `theme`
+    Theme,
+    /// `code`
+    CodeHighlighter,
+    /// `slug`
+    Slug,
+    /// `content_source_url`
+    ContentSourceUrl,
+    /// `pic_crop_235_1`
+    PicCrop235_1,
+    /// `pic_crop_1_1`
+    PicCrop1_1,
+    /// `cover_media_id`
+    CoverMediaId,
+    /// `lang`
+    Language,
+}
+
+impl FrontmatterField {
+    /// Name of the field in messages, e.g. `code highlighter`
+    pub fn label(&self) -> &'static str {
+        match self {
+            FrontmatterField::Theme => "theme",
+            FrontmatterField::CodeHighlighter => "code highlighter",
+            FrontmatterField::Slug => "slug",
+            FrontmatterField::ContentSourceUrl => "content_source_url",
+            FrontmatterField::PicCrop235_1 => "pic_crop_235_1",
+            FrontmatterField::PicCrop1_1 => "pic_crop_1_1",
+            FrontmatterField::CoverMediaId => "cover_media_id",
+            FrontmatterField::Language => "language",
+        }
+    }
+
+    /// How to fix an invalid value of the field
+    pub fn hint(&self) -> String {
+        match self {
+            FrontmatterField::Theme => format!("Available themes: {}", VALID_THEMES.join(", ")),
+            FrontmatterField::CodeHighlighter => format!(
+                "Available highlighters: {}",
+                VALID_CODE_HIGHLIGHTERS.join(", ")
+            ),
+            FrontmatterField::Slug => "Use only letters, digits, '-' and '_'".to_string(),
+            FrontmatterField::ContentSourceUrl => "Use an http(s) URL".to_string(),
+            FrontmatterField::PicCrop235_1 | FrontmatterField::PicCrop1_1 => {
+                "Use X1_Y1_X2_Y2 with fractions between 0 and 1, e.g. 0_0.2_1_0.8".to_string()
+            }
+            FrontmatterField::CoverMediaId => {
+                "Use the media_id of a permanent image material: letters, digits, '-' and '_'"
+                    .to_string()
+            }
+            FrontmatterField::Language => "Use a tag such as 'en' or 'zh-CN'".to_string(),
+        }
+    }
+}
+
+/// An invalid frontmatter value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrontmatterIssue {
+    /// Field the value was given for
+    pub field: FrontmatterField,
+    /// The value as written
+    pub value: String,
+}
+
+impl std::fmt::Display for FrontmatterIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid {} '{}'. {}", self.field.label(), self.value, self.field.hint())
+    }
+}
+
+/// Publication status of an article, from its `published` field
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, clap::ValueEnum,
+)]
+pub enum PublicationStatus {
+    /// Never uploaded (`published` missing, `false` or unrecognized)
+    #[default]
+    Unpublished,
+    /// Uploaded as a WeChat draft (`published: draft`)
+    Draft,
+    /// Published; skipped in directory mode (`published: true`)
+    Published,
+    /// Never to be uploaded, e.g. an unfinished article kept next to the
+    /// others; skipped in directory mode (`published: skip` or `ignore`)
+    Skip,
+}
+
+impl PublicationStatus {
+    /// Lowercase name, as shown in status output
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PublicationStatus::Unpublished => "unpublished",
+            PublicationStatus::Draft => "draft",
+            PublicationStatus::Published => "published",
+            PublicationStatus::Skip => "skip",
+        }
+    }
+
+    /// Parses a `published` string, accepting the spellings used over time
+    ///
+    /// Case, surrounding whitespace and a stray pair of quotes (`"\"true\""`)
+    /// are ignored. Returns `None` for values that name no status.
+    pub fn from_spelling(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .unwrap_or(value)
+            .trim();
+        match value.to_ascii_lowercase().as_str() {
+            "true" | "yes" | "published" => Some(PublicationStatus::Published),
+            "draft" => Some(PublicationStatus::Draft),
+            "" | "false" | "no" | "unpublished" | "pending" => Some(PublicationStatus::Unpublished),
+            "skip" | "ignore" => Some(PublicationStatus::Skip),
+            _ => None,
+        }
+    }
+
+    /// Returns the value written to the `published` field for this status
+    pub fn canonical_value(&self) -> serde_yaml::Value {
+        match self {
+            PublicationStatus::Unpublished => serde_yaml::Value::Bool(false),
+            PublicationStatus::Published => serde_yaml::Value::Bool(true),
+            PublicationStatus::Draft | PublicationStatus::Skip => {
+                serde_yaml::Value::String(self.as_str().to_string())
+            }
+        }
+    }
+
+    /// Returns true if the article has been published
+    pub fn is_published(&self) -> bool {
+        *self == PublicationStatus::Published
+    }
+
+    /// Returns true if the article was uploaded as a draft
+    pub fn is_draft(&self) -> bool {
+        *self == PublicationStatus::Draft
+    }
+
+    /// Returns true if the article has not been uploaded
+    pub fn is_unpublished(&self) -> bool {
+        *self == PublicationStatus::Unpublished
+    }
+
+    /// Returns true if the article is marked to be skipped
+    pub fn is_skip(&self) -> bool {
+        *self == PublicationStatus::Skip
+    }
+
+    /// Returns why an upload skips an article in this status unless forced
+    pub fn skip_reason(&self) -> Option<&'static str> {
+        match self {
+            PublicationStatus::Published => Some("already published"),
+            PublicationStatus::Skip => Some("marked skip"),
+            PublicationStatus::Unpublished | PublicationStatus::Draft => None,
+        }
+    }
+}
+
+impl std::fmt::Display for PublicationStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The `published` frontmatter field
+///
+/// Reading accepts booleans and every historical spelling (see
+/// [`PublicationStatus::from_spelling`]); values that name no status count
+/// as unpublished. A value read in any other form than the canonical one
+/// (`true`, `false`, `draft` or `skip`) is kept and written back as it was,
+/// until the status is changed or the field is [normalized](Self::normalized).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublishedField {
+    status: PublicationStatus,
+    raw: Option<String>,
+}
+
+impl PublishedField {
+    /// Returns the publication status
+    pub fn status(&self) -> PublicationStatus {
+        self.status
+    }
+
+    /// Returns the value as written in the file, if it was not canonical
+    pub fn raw(&self) -> Option<&str> {
+        self.raw.as_deref()
+    }
+
+    /// Returns true if the value names a status, even in a legacy spelling
+    ///
+    /// Values such as `review` are read as unpublished but are not rewritten
+    /// by normalization.
+    pub fn is_recognized(&self) -> bool {
+        self.raw
+            .as_deref()
+            .is_none_or(|raw| PublicationStatus::from_spelling(raw).is_some())
+    }
+
+    /// Returns the field with its canonical value, dropping the raw spelling
+    pub fn normalized(&self) -> Self {
+        Self::from(self.status)
+    }
+
+    /// Reads a string value, keeping it unless it is canonical
+    fn from_str_value(value: &str) -> Self {
+        let status = PublicationStatus::from_spelling(value).unwrap_or_default();
+        let canonical = matches!(status, PublicationStatus::Draft | PublicationStatus::Skip)
+            && value == status.as_str();
+        Self {
+            status,
+            raw: (!canonical).then(|| value.to_string()),
+        }
+    }
+}
+
+impl From<PublicationStatus> for PublishedField {
+    fn from(status: PublicationStatus) -> Self {
+        Self { status, raw: None }
+    }
+}
+
+impl std::fmt::Display for PublishedField {
+    /// Formats the value as written, e.g. `true` or `"true"` for a raw string
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.raw {
+            Some(raw) => write!(f, "{:?}", raw),
+            None => match self.status.canonical_value() {
+                serde_yaml::Value::Bool(value) => write!(f, "{}", value),
+                _ => f.write_str(self.status.as_str()),
+            },
+        }
+    }
+}
+
+impl Serialize for PublishedField {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match &self.raw {
+            Some(raw) => serializer.serialize_str(raw),
+            None => self.status.canonical_value().serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for PublishedField {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct PublishedVisitor;
+
+        impl serde::de::Visitor<'_> for PublishedVisitor {
+            type Value = PublishedField;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("a boolean or a publication status such as draft")
+            }
+
+            fn visit_bool<E: serde::de::Error>(self, value: bool) -> std::result::Result<PublishedField, E> {
+                Ok(PublishedField::from(if value {
+                    PublicationStatus::Published
+                } else {
+                    PublicationStatus::Unpublished
+                }))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, value: &str) -> std::result::Result<PublishedField, E> {
+                Ok(PublishedField::from_str_value(value))
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, value: i64) -> std::result::Result<PublishedField, E> {
+                Ok(PublishedField::from_str_value(&value.to_string()))
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, value: u64) -> std::result::Result<PublishedField, E> {
+                Ok(PublishedField::from_str_value(&value.to_string()))
+            }
+
+            fn visit_f64<E: serde::de::Error>(self, value: f64) -> std::result::Result<PublishedField, E> {
+                Ok(PublishedField::from_str_value(&value.to_string()))
+            }
+        }
+
+        deserializer.deserialize_any(PublishedVisitor)
+    }
+}
+
+/// Frontmatter `thumb` value selecting the first local image in the body
+pub const THUMB_FIRST_IMAGE: &str = "first-image";
+
+/// Frontmatter `cover` values that opt out of a cover
+pub const NO_COVER_VALUES: &[&str] = &["none", "false"];
+
+/// Returns true if a `cover` value opts out of a cover
+fn is_no_cover(cover: &str) -> bool {
+    NO_COVER_VALUES
+        .iter()
+        .any(|value| cover.trim().eq_ignore_ascii_case(value))
+}
+
+/// Reads `cover` as a string, accepting `cover: false` as the opt-out
+fn deserialize_cover<'de, D>(deserializer: D) -> std::result::Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    match Option::<serde_yaml::Value>::deserialize(deserializer)? {
+        None | Some(serde_yaml::Value::Null) => Ok(None),
+        Some(serde_yaml::Value::String(cover)) => Ok(Some(cover)),
+        Some(serde_yaml::Value::Bool(false)) => Ok(Some("false".to_string())),
+        Some(other) => Err(serde::de::Error::custom(format!(
+            "invalid cover {:?}: expected an image path, or none/false for no cover",
+            other
+        ))),
+    }
+}
+
+/// Writes `cover: false` back as a boolean so the opt-out survives a round trip
+fn serialize_cover<S>(cover: &Option<String>, serializer: S) -> std::result::Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match cover.as_deref() {
+        Some("false") => serializer.serialize_bool(false),
+        Some(cover) => serializer.serialize_str(cover),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Most characters WeChat accepts in an article digest
+pub const DIGEST_MAX_CHARS: usize = 120;
+
+/// Returns the length of a digest as WeChat counts it: one per character,
+/// so a CJK character counts once and an emoji once per code point
+pub fn digest_length(digest: &str) -> usize {
+    digest.chars().count()
+}
+
+/// Shortens a digest to at most `limit` characters ending with "…"
+///
+/// The cut falls between grapheme clusters, so no emoji or combined
+/// character is split. A digest within the limit is returned unchanged.
+pub fn truncate_digest(digest: &str, limit: usize) -> Cow<'_, str> {
+    if digest_length(digest) <= limit {
+        return Cow::Borrowed(digest);
+    }
+
+    let budget = limit.saturating_sub(1);
+    let mut used = 0;
+    let mut end = 0;
+    for (index, grapheme) in digest.grapheme_indices(true) {
+        used += digest_length(grapheme);
+        if used > budget {
+            break;
+        }
+        end = index + grapheme.len();
     }
+    Cow::Owned(format!("{}…", digest[..end].trim_end()))
 }
 
+/// Most cover candidates generated for one file, the OpenAI limit of `n`
+pub const MAX_COVER_VARIATIONS: u32 = 10;
+
 /// Valid themes for WeChat articles
 pub const VALID_THEMES: &[&str] = &[
     "default",
@@ -603,6 +2877,83 @@ pub const VALID_CODE_HIGHLIGHTERS: &[&str] = &[
     "xcode",
 ];
 
+/// Whether a theme or code highlighter has a light or a dark background
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tone {
+    /// Dark text on a light background
+    Light,
+    /// Light text on a dark background
+    Dark,
+}
+
+impl std::fmt::Display for Tone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Light => "light",
+            Self::Dark => "dark",
+        })
+    }
+}
+
+/// A theme or code highlighter with what a user needs to choose it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct StyleInfo {
+    /// Name used in the frontmatter and settings
+    pub name: &'static str,
+    /// Short description of the look
+    pub description: &'static str,
+    /// Whether the background is light or dark
+    pub tone: Tone,
+}
+
+impl StyleInfo {
+    const fn new(name: &'static str, description: &'static str, tone: Tone) -> Self {
+        Self {
+            name,
+            description,
+            tone,
+        }
+    }
+}
+
+/// Themes of [`VALID_THEMES`], in the same order, with their metadata
+const THEMES: &[StyleInfo] = &[
+    StyleInfo::new("default", "Plain black and white layout", Tone::Light),
+    StyleInfo::new("lapis", "Blue headings and accents", Tone::Light),
+    StyleInfo::new("maize", "Warm yellow accents", Tone::Light),
+    StyleInfo::new("orangeheart", "Orange headings and accents", Tone::Light),
+    StyleInfo::new("phycat", "Mint green headings with rounded blocks", Tone::Light),
+    StyleInfo::new("pie", "Minimal layout with red accents", Tone::Light),
+    StyleInfo::new("purple", "Purple headings and accents", Tone::Light),
+    StyleInfo::new("rainbow", "Headings in changing colors", Tone::Light),
+];
+
+/// Code highlighters of [`VALID_CODE_HIGHLIGHTERS`], in the same order, with their metadata
+const CODE_HIGHLIGHTERS: &[StyleInfo] = &[
+    StyleInfo::new("github", "GitHub's light code colors", Tone::Light),
+    StyleInfo::new("github-dark", "GitHub's dark code colors", Tone::Dark),
+    StyleInfo::new("vscode", "Visual Studio Code's dark code colors", Tone::Dark),
+    StyleInfo::new("atom-one-light", "Atom One, light variant", Tone::Light),
+    StyleInfo::new("atom-one-dark", "Atom One, dark variant", Tone::Dark),
+    StyleInfo::new("solarized-light", "Solarized, light variant", Tone::Light),
+    StyleInfo::new("solarized-dark", "Solarized, dark variant", Tone::Dark),
+    StyleInfo::new("monokai", "Vivid colors on charcoal", Tone::Dark),
+    StyleInfo::new("dracula", "Pastel colors on deep purple", Tone::Dark),
+    StyleInfo::new("xcode", "Xcode's light code colors", Tone::Light),
+];
+
+/// Returns the valid themes with their metadata, in the order of [`VALID_THEMES`]
+pub fn themes() -> &'static [StyleInfo] {
+    THEMES
+}
+
+/// Returns the valid code highlighters with their metadata, in the order of
+/// [`VALID_CODE_HIGHLIGHTERS`]
+pub fn code_highlighters() -> &'static [StyleInfo] {
+    CODE_HIGHLIGHTERS
+}
+
 /// Checks if a theme is valid
 pub fn is_valid_theme(theme: &str) -> bool {
     VALID_THEMES.contains(&theme)
@@ -613,6 +2964,31 @@ pub fn is_valid_code_highlighter(highlighter: &str) -> bool {
     VALID_CODE_HIGHLIGHTERS.contains(&highlighter)
 }
 
+/// Checks if a thumbnail crop is `X1_Y1_X2_Y2` with fractions in 0..=1 and a non-empty area
+pub fn is_valid_pic_crop(crop: &str) -> bool {
+    let coords: std::result::Result<Vec<f64>, _> = crop.split('_').map(str::parse).collect();
+    match coords.as_deref() {
+        Ok(&[x1, y1, x2, y2]) => {
+            [x1, y1, x2, y2].iter().all(|c| (0.0..=1.0).contains(c)) && x1 < x2 && y1 < y2
+        }
+        _ => false,
+    }
+}
+
+/// Longest media ID accepted for `cover_media_id`
+const MAX_MEDIA_ID_LEN: usize = 128;
+
+/// Checks if a WeChat media ID is well-formed: letters, digits, `-` and `_`
+///
+/// Whether the material exists is only known once WeChat answers the upload.
+pub fn is_valid_media_id(media_id: &str) -> bool {
+    !media_id.is_empty()
+        && media_id.len() <= MAX_MEDIA_ID_LEN
+        && media_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -664,6 +3040,11 @@ mod tests {
                 app_id: "".to_string(),
                 app_secret: "secret".to_string(),
                 description: None,
+                wechat_base_url: None,
+                default_cover: None,
+                kind: AccountKind::Normal,
+                tags: Vec::new(),
+                owner: None,
             },
         );
         let empty_app_id = Config::new_with_accounts(accounts, "test", None, false);
@@ -679,6 +3060,11 @@ mod tests {
                 app_id: "app_id".to_string(),
                 app_secret: "".to_string(),
                 description: None,
+                wechat_base_url: None,
+                default_cover: None,
+                kind: AccountKind::Normal,
+                tags: Vec::new(),
+                owner: None,
             },
         );
         let empty_secret = Config::new_with_accounts(accounts, "test", None, false);
@@ -711,6 +3097,364 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_ai_provider_config_api_key_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let key_file = temp_dir.path().join("openai_key");
+        std::fs::write(&key_file, "sk-from-file\n\n").unwrap();
+
+        let from_file = AiProviderConfig {
+            provider: "openai".to_string(),
+            api_key: None,
+            api_key_file: Some(key_file.display().to_string()),
+            base_url: None,
+        };
+        assert_eq!(from_file.resolve_api_key().unwrap(), "sk-from-file");
+
+        let both = AiProviderConfig {
+            api_key: Some("sk-inline".to_string()),
+            ..from_file.clone()
+        };
+        assert!(both.resolve_api_key().unwrap_err().is_config());
+
+        let neither = AiProviderConfig {
+            api_key_file: None,
+            ..from_file
+        };
+        assert!(neither.resolve_api_key().is_err());
+
+        std::fs::write(&key_file, " \n").unwrap();
+        assert!(read_secret_file(&key_file).is_err());
+    }
+
+    #[test]
+    fn test_env_secret_file_convention() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let secret_file = temp_dir.path().join("secret");
+        std::fs::write(&secret_file, "from_file\n").unwrap();
+
+        // Unique names so this cannot race with the other env tests
+        unsafe {
+            env::remove_var("WX_UPLOADER_TEST_SECRET");
+            env::set_var("WX_UPLOADER_TEST_SECRET_FILE", &secret_file);
+        }
+        assert_eq!(
+            env_secret("WX_UPLOADER_TEST_SECRET").unwrap().as_deref(),
+            Some("from_file")
+        );
+
+        unsafe {
+            env::set_var("WX_UPLOADER_TEST_SECRET", "inline");
+        }
+        assert!(env_secret("WX_UPLOADER_TEST_SECRET").unwrap_err().is_config());
+
+        unsafe {
+            env::remove_var("WX_UPLOADER_TEST_SECRET_FILE");
+        }
+        assert_eq!(
+            env_secret("WX_UPLOADER_TEST_SECRET").unwrap().as_deref(),
+            Some("inline")
+        );
+
+        unsafe {
+            env::remove_var("WX_UPLOADER_TEST_SECRET");
+        }
+        assert_eq!(env_secret("WX_UPLOADER_TEST_SECRET").unwrap(), None);
+    }
+
+    async fn config_with_groups(groups: &str) -> Result<Config> {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yaml");
+        std::fs::write(
+            &path,
+            format!(
+                "accounts:
+  main: {{ name: main, app_id: a, app_secret: s }}
+  north: {{ name: north, app_id: n, app_secret: s }}
+  south: {{ name: south, app_id: s, app_secret: s }}
+default_account: main
+groups:
+{}",
+                groups
+            ),
+        )
+        .unwrap();
+        Config::from_file(&path, None).await
+    }
+
+    #[tokio::test]
+    async fn test_scene_prompts_from_config_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yaml");
+        let write = |prompts: &str| {
+            std::fs::write(
+                &path,
+                format!(
+                    "accounts:\n  main: {{ name: main, app_id: a, app_secret: s }}\nsettings:\n  scene_prompts:\n{}",
+                    prompts
+                ),
+            )
+            .unwrap();
+        };
+
+        write("    zh-TW: \"用繁體中文描述封面場景\"\n");
+        let config = Config::from_file(&path, None).await.unwrap();
+        assert_eq!(config.scene_prompts["zh-TW"], "用繁體中文描述封面場景");
+
+        write("    chinese: \"prompt\"\n");
+        let err = Config::from_file(&path, None).await.unwrap_err();
+        assert!(err.to_string().contains("Invalid language 'chinese'"));
+
+        write("    ja: \"  \"\n");
+        let err = Config::from_file(&path, None).await.unwrap_err();
+        assert!(err.to_string().contains("Scene prompt for language 'ja' is empty"));
+
+        write(
+            "    en: \"Describe a scene\"\n  fallback_scene: \"A flat illustration of {title}\"\n",
+        );
+        let config = Config::from_file(&path, None).await.unwrap();
+        assert_eq!(config.fallback_scene.as_deref(), Some("A flat illustration of {title}"));
+
+        write("    en: \"Describe a scene\"\n  fallback_scene: \" \"\n");
+        let err = Config::from_file(&path, None).await.unwrap_err();
+        assert!(err.to_string().contains("fallback_scene must not be empty"));
+
+        write("    en: \"Describe a scene\"\n  digest_limit: 64\n");
+        assert_eq!(Config::from_file(&path, None).await.unwrap().digest_limit, 64);
+
+        write("    en: \"Describe a scene\"\n  digest_limit: 0\n");
+        let err = Config::from_file(&path, None).await.unwrap_err();
+        assert!(err.to_string().contains("digest_limit must be at least 1"));
+    }
+
+    #[tokio::test]
+    async fn test_cover_format_settings() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yaml");
+        let write = |settings: &str| {
+            std::fs::write(
+                &path,
+                format!(
+                    "accounts:\n  main: {{ name: main, app_id: a, app_secret: s }}\nsettings:\n{}",
+                    settings
+                ),
+            )
+            .unwrap();
+        };
+
+        write("  verbose: false\n");
+        let config = Config::from_file(&path, None).await.unwrap();
+        assert_eq!(config.cover_encoding(), CoverEncoding::default());
+
+        write("  cover_format: jpg\n  cover_quality: 70\n");
+        let config = Config::from_file(&path, None).await.unwrap();
+        assert_eq!(config.cover_format, CoverFormat::Jpeg);
+        assert_eq!(config.cover_quality, 70);
+
+        write("  cover_format: webp\n  cover_quality: 0\n");
+        let err = Config::from_file(&path, None).await.unwrap_err();
+        assert!(err.to_string().contains("cover_quality must be between 1 and 100"));
+
+        write("  cover_format: gif\n");
+        assert!(Config::from_file(&path, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_allowed_hours_setting() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yaml");
+        let write = |settings: &str| {
+            std::fs::write(
+                &path,
+                format!(
+                    "accounts:\n  main: {{ name: main, app_id: a, app_secret: s }}\nsettings:\n{}",
+                    settings
+                ),
+            )
+            .unwrap();
+        };
+
+        write("  allowed_hours: \"09:00-18:00 Asia/Shanghai\"\n");
+        let mut config = Config::from_file(&path, None).await.unwrap();
+        let hours = config.allowed_hours.unwrap();
+        assert_eq!(hours.to_string(), "09:00-18:00 Asia/Shanghai");
+
+        // 23:30 in Shanghai
+        struct LateClock;
+        impl Clock for LateClock {
+            fn now(&self) -> chrono::DateTime<chrono::Utc> {
+                "2026-03-02T15:30:00Z".parse().unwrap()
+            }
+        }
+        assert!(config.check_schedule(&LateClock).unwrap_err().is_config());
+        config.ignore_schedule = true;
+        assert!(config.check_schedule(&LateClock).is_ok());
+
+        write("  allowed_hours: \"09:00-18:00\"\n");
+        let err = Config::from_file(&path, None).await.unwrap_err();
+        assert!(err.to_string().contains("Invalid allowed_hours"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_wechat_base_url_is_validated_on_load() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yaml");
+        let write = |account: &str, settings: &str| {
+            std::fs::write(
+                &path,
+                format!(
+                    "accounts:\n  main: {{ name: main, app_id: a, app_secret: s{} }}\nsettings:\n{}",
+                    account, settings
+                ),
+            )
+            .unwrap();
+        };
+
+        write(", api_base_url: \"https://wx-gateway.example.com\"", "  verbose: false\n");
+        let config = Config::from_file(&path, None).await.unwrap();
+        assert_eq!(
+            config.wechat_account.wechat_base_url.as_deref(),
+            Some("https://wx-gateway.example.com")
+        );
+
+        write("", "  api_base_url: \"wx-gateway.example.com\"\n");
+        let err = Config::from_file(&path, None).await.unwrap_err();
+        assert!(err.to_string().contains("Invalid WeChat base URL 'wx-gateway.example.com'"));
+
+        let mut config = Config::new("app_id".to_string(), "secret".to_string(), None, false);
+        config.set_wechat_base_url("localhost:8080");
+        assert!(config.validate().unwrap_err().is_config());
+    }
+
+    #[tokio::test]
+    async fn test_account_kind_gates_capabilities() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yaml");
+        std::fs::write(
+            &path,
+            "accounts:\n  main: { name: main, app_id: a, app_secret: s }\n  \
+             test: { name: test, app_id: b, app_secret: t, kind: sandbox }\n",
+        )
+        .unwrap();
+
+        let config = Config::from_file(&path, None).await.unwrap();
+        assert_eq!(config.account("main").unwrap().kind, AccountKind::Normal);
+        assert_eq!(config.account("test").unwrap().kind, AccountKind::Sandbox);
+
+        assert!(AccountKind::Normal.supports(Capability::OriginalDeclaration));
+        assert_eq!(AccountKind::Normal.unsupported(Capability::OriginalDeclaration), None);
+        assert_eq!(
+            AccountKind::Sandbox.unsupported(Capability::OriginalDeclaration).as_deref(),
+            Some("the original declaration is not supported on sandbox accounts")
+        );
+
+        // Regular accounts do not write the kind back
+        let yaml = serde_yaml::to_string(config.account("main").unwrap()).unwrap();
+        assert!(!yaml.contains("kind"), "{}", yaml);
+
+        std::fs::write(&path, "accounts:\n  main: { name: main, app_id: a, app_secret: s, kind: test }\n")
+            .unwrap();
+        assert!(Config::from_file(&path, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_account_groups_expand_nested_groups() {
+        let config = config_with_groups(
+            "  regional: [north, south]\n  all: [main, \"@regional\", north]\n",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(config.account_groups["all"], vec!["main", "north", "south"]);
+        assert_eq!(
+            config
+                .resolve_accounts(&["@regional".to_string(), "main".to_string()])
+                .unwrap(),
+            vec!["north", "south", "main"]
+        );
+        assert!(config.resolve_accounts(&["@nope".to_string()]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_account_tags_and_owner_are_optional() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yaml");
+        // A file written before accounts had tags or an owner
+        std::fs::write(
+            &path,
+            "accounts:\n  main: { name: main, app_id: a, app_secret: s, description: Blog }\n  \
+             east: { name: east, app_id: b, app_secret: t, tags: [regional], owner: Li }\n",
+        )
+        .unwrap();
+
+        let config = Config::from_file(&path, None).await.unwrap();
+        let main = config.account("main").unwrap();
+        assert!(main.tags.is_empty());
+        assert_eq!(main.owner, None);
+        let east = config.account("east").unwrap();
+        assert_eq!(east.tags, vec!["regional"]);
+        assert_eq!(east.owner.as_deref(), Some("Li"));
+
+        // Accounts without them are written back as before
+        let yaml = serde_yaml::to_string(main).unwrap();
+        assert!(!yaml.contains("tags") && !yaml.contains("owner"), "{}", yaml);
+        let parsed: WeChatAccount = serde_yaml::from_str(&serde_yaml::to_string(east).unwrap()).unwrap();
+        assert_eq!(&parsed, east);
+    }
+
+    #[tokio::test]
+    async fn test_account_selected_by_tag() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yaml");
+        std::fs::write(
+            &path,
+            "accounts:\n  main: { name: main, app_id: a, app_secret: s, tags: [core] }\n  \
+             north: { name: north, app_id: b, app_secret: t, tags: [regional, cold] }\n  \
+             south: { name: south, app_id: c, app_secret: u, tags: [regional] }\n\
+             groups:\n  regional: [north, south]\n",
+        )
+        .unwrap();
+
+        let config = Config::from_file(&path, Some("tag:cold")).await.unwrap();
+        assert_eq!(config.wechat_account.name, "north");
+        assert!(config.target_accounts.is_empty());
+        assert_eq!(
+            config
+                .resolve_accounts(&["tag:core".to_string(), "@regional".to_string()])
+                .unwrap(),
+            vec!["main", "north", "south"]
+        );
+
+        let err = Config::from_file(&path, Some("tag:regional")).await.unwrap_err();
+        assert!(err.is_config());
+        assert!(err.to_string().contains("Tag 'regional' matches 2 accounts: north, south"), "{}", err);
+
+        let err = config.resolve_accounts(&["tag:west".to_string()]).unwrap_err();
+        assert!(
+            err.to_string().contains("No account is tagged 'west'. Available tags: cold, core, regional"),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_account_groups_reject_cycles_and_undefined_members() {
+        let err = config_with_groups("  a: [\"@b\"]\n  b: [north, \"@a\"]\n")
+            .await
+            .unwrap_err();
+        assert!(err.is_config());
+        assert!(err.to_string().contains("cycle: @a -> @b -> @a"), "{}", err);
+
+        let err = config_with_groups("  regional: [north, west]\n").await.unwrap_err();
+        assert!(err.to_string().contains("undefined account 'west'"), "{}", err);
+
+        let err = config_with_groups("  regional: [\"@missing\"]\n").await.unwrap_err();
+        assert!(err.to_string().contains("undefined group '@missing'"), "{}", err);
+
+        let err = config_with_groups("  empty: []\n").await.unwrap_err();
+        assert!(err.to_string().contains("no members"), "{}", err);
+    }
+
     #[test]
     fn test_ai_provider_creation() {
         let openai_provider = AiProvider::openai("test_key".to_string());
@@ -768,20 +3512,35 @@ mod tests {
         let mut frontmatter = Frontmatter::new();
 
         frontmatter.set_title("My Article");
-        frontmatter.set_published("draft");
+        frontmatter.set_published(PublicationStatus::Draft);
         frontmatter.set_cover("cover.png");
         frontmatter.set_theme("lapis");
         frontmatter.set_code_highlighter("github");
 
         assert_eq!(frontmatter.title, Some("My Article".to_string()));
-        assert_eq!(frontmatter.published, Some("draft".to_string()));
+        assert_eq!(frontmatter.publication_status(), PublicationStatus::Draft);
         assert_eq!(frontmatter.cover, Some("cover.png".to_string()));
         assert_eq!(frontmatter.theme, Some("lapis".to_string()));
         assert_eq!(frontmatter.code, Some("github".to_string()));
 
-        assert!(frontmatter.is_draft());
-        assert!(!frontmatter.is_published());
-        assert!(!frontmatter.is_unpublished());
+        assert!(frontmatter.publication_status().is_draft());
+        assert!(!frontmatter.publication_status().is_published());
+        assert!(!frontmatter.publication_status().is_unpublished());
+    }
+
+    #[test]
+    fn test_frontmatter_thumb_defaults_to_cover() {
+        let mut frontmatter = Frontmatter::new();
+        assert_eq!(frontmatter.thumb_or_cover(), None);
+
+        frontmatter.set_cover("cover.png");
+        assert_eq!(frontmatter.thumb_or_cover(), Some("cover.png"));
+
+        frontmatter.set_thumb("thumb.png");
+        assert_eq!(frontmatter.thumb_or_cover(), Some("thumb.png"));
+
+        let yaml = serde_yaml::to_string(&frontmatter).unwrap();
+        assert!(yaml.contains("thumb: thumb.png"));
     }
 
     #[test]
@@ -789,21 +3548,21 @@ mod tests {
         let mut frontmatter = Frontmatter::new();
 
         // Test unpublished
-        assert!(frontmatter.is_unpublished());
-        assert!(!frontmatter.is_draft());
-        assert!(!frontmatter.is_published());
+        assert!(frontmatter.publication_status().is_unpublished());
+        assert!(!frontmatter.publication_status().is_draft());
+        assert!(!frontmatter.publication_status().is_published());
 
         // Test draft
-        frontmatter.set_published("draft");
-        assert!(frontmatter.is_draft());
-        assert!(!frontmatter.is_published());
-        assert!(!frontmatter.is_unpublished());
+        frontmatter.set_published(PublicationStatus::Draft);
+        assert!(frontmatter.publication_status().is_draft());
+        assert!(!frontmatter.publication_status().is_published());
+        assert!(!frontmatter.publication_status().is_unpublished());
 
         // Test published
-        frontmatter.set_published("true");
-        assert!(frontmatter.is_published());
-        assert!(!frontmatter.is_draft());
-        assert!(!frontmatter.is_unpublished());
+        frontmatter.set_published(PublicationStatus::Published);
+        assert!(frontmatter.publication_status().is_published());
+        assert!(!frontmatter.publication_status().is_draft());
+        assert!(!frontmatter.publication_status().is_unpublished());
     }
 
     #[test]
@@ -826,6 +3585,363 @@ mod tests {
         frontmatter.set_theme("lapis");
         frontmatter.set_code_highlighter("invalid_highlighter");
         assert!(frontmatter.validate().is_err());
+
+        // Fix code, slug must be URL-safe
+        frontmatter.set_code_highlighter("github");
+        frontmatter.slug = Some("rust-async".to_string());
+        assert!(frontmatter.validate().is_ok());
+        frontmatter.slug = Some("rust async/1".to_string());
+        assert!(frontmatter.validate().is_err());
+
+        // Fix slug, lang must be a language tag
+        frontmatter.slug = None;
+        frontmatter.lang = Some("zh-CN".to_string());
+        assert!(frontmatter.validate().is_ok());
+        frontmatter.lang = Some("Chinese".to_string());
+        assert!(frontmatter.validate().is_err());
+
+        // Fix lang, the source link must be an http(s) URL
+        frontmatter.lang = None;
+        frontmatter.content_source_url = Some("https://example.com/post".to_string());
+        assert!(frontmatter.validate().is_ok());
+        frontmatter.content_source_url = Some("example.com/post".to_string());
+        assert!(frontmatter.validate().is_err());
+
+        // Fix the link, crops must be valid
+        frontmatter.content_source_url = None;
+        frontmatter.pic_crop_235_1 = Some("0_0.2_1_0.8".to_string());
+        frontmatter.pic_crop_1_1 = Some("0.2_0_0.8_1".to_string());
+        assert!(frontmatter.validate().is_ok());
+        frontmatter.pic_crop_1_1 = Some("0.2_0_0.8".to_string());
+        assert!(frontmatter.validate().is_err());
+
+        // Fix the crop, a material must be named by a well-formed media ID
+        frontmatter.pic_crop_1_1 = None;
+        frontmatter.cover_media_id = Some("Xc3-Lq_8vT0aWk2rBfYp".to_string());
+        assert!(frontmatter.validate().is_ok());
+        for media_id in ["", "series cover", "cover.png", &"x".repeat(MAX_MEDIA_ID_LEN + 1)] {
+            frontmatter.cover_media_id = Some(media_id.to_string());
+            let err = frontmatter.validate().unwrap_err().to_string();
+            assert!(err.contains("cover_media_id"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_frontmatter_issues_lists_every_invalid_value() {
+        let mut frontmatter = Frontmatter::new();
+        frontmatter.set_theme("old");
+        frontmatter.set_code_highlighter("prism");
+        frontmatter.slug = Some("rust async".to_string());
+        frontmatter.lang = Some("zh-CN".to_string());
+
+        let fields: Vec<_> = frontmatter
+            .issues(ThemeFallback::Error)
+            .into_iter()
+            .map(|issue| issue.field)
+            .collect();
+        assert_eq!(
+            fields,
+            [FrontmatterField::Theme, FrontmatterField::CodeHighlighter, FrontmatterField::Slug]
+        );
+        assert_eq!(frontmatter.issues(ThemeFallback::Default).len(), 1);
+
+        // The first issue is the validation error
+        let err = frontmatter.validate().unwrap_err().to_string();
+        assert!(err.contains("Invalid theme 'old'. Available themes: default"), "{}", err);
+    }
+
+    #[test]
+    fn test_pic_crop_validation() {
+        assert!(is_valid_pic_crop("0_0_1_1"));
+        assert!(is_valid_pic_crop("0.1945_0_1_0.5236"));
+        assert!(!is_valid_pic_crop(""));
+        assert!(!is_valid_pic_crop("0_0_1"));
+        assert!(!is_valid_pic_crop("0_0_1_1_1"));
+        assert!(!is_valid_pic_crop("0_0_1.5_1"));
+        assert!(!is_valid_pic_crop("0.8_0_0.2_1"));
+        assert!(!is_valid_pic_crop("a_b_c_d"));
+    }
+
+    #[test]
+    fn test_digest_length_and_truncation() {
+        assert_eq!(digest_length("微信公众号"), 5);
+        assert_eq!(digest_length("Hi 👋"), 4);
+        // A family emoji is one grapheme of five code points
+        assert_eq!(digest_length("👨‍👩‍👧"), 5);
+
+        let chinese = "这是一段很长的中文摘要".repeat(3);
+        assert_eq!(truncate_digest(&chinese, 40), chinese);
+        let cut = truncate_digest(&chinese, 10);
+        assert_eq!(cut, "这是一段很长的中文…");
+        assert_eq!(digest_length(&cut), 10);
+
+        // The cut never splits a grapheme and trailing spaces are dropped
+        assert_eq!(truncate_digest("ab 👨‍👩‍👧 cd", 7), "ab…");
+        assert_eq!(truncate_digest("ab 👨‍👩‍👧 cd", 9), "ab 👨‍👩‍👧…");
+        assert_eq!(truncate_digest("Rust 与 WeChat 🚀 集成指南", 12), "Rust 与 WeCh…");
+        assert_eq!(truncate_digest("e\u{301}e\u{301}e\u{301}", 4), "e\u{301}…");
+        assert_eq!(truncate_digest("abc", 1), "…");
+    }
+
+    #[test]
+    fn test_frontmatter_digest_warning() {
+        let mut frontmatter = Frontmatter::new();
+        frontmatter.description = format!("  {}", "长".repeat(120));
+        assert_eq!(frontmatter.digest_warning(DIGEST_MAX_CHARS), None);
+
+        frontmatter.description.push('长');
+        assert_eq!(
+            frontmatter.digest_warning(DIGEST_MAX_CHARS).unwrap(),
+            "description is 121 characters, over the digest limit of 120; the digest is truncated"
+        );
+        assert!(frontmatter.digest_warning(200).is_none());
+    }
+
+    #[test]
+    fn test_frontmatter_author() {
+        let (frontmatter, _) =
+            crate::markdown::parse_markdown("---\nauthor: \" Jane \"\n---\nBody").unwrap();
+        assert_eq!(frontmatter.author(), Some("Jane"));
+        assert_eq!(Frontmatter::new().author(), None);
+    }
+
+    #[test]
+    fn test_frontmatter_tags() {
+        let parse = |yaml: &str| crate::markdown::parse_markdown(yaml).unwrap().0.tags();
+        assert_eq!(parse("---\ntags: [rust, \" async \", 3]\n---\nBody"), vec!["rust", "async"]);
+        assert_eq!(parse("---\ntags: \"rust, 异步，\"\n---\nBody"), vec!["rust", "异步"]);
+        assert!(Frontmatter::new().tags().is_empty());
+    }
+
+    #[test]
+    fn test_replace_unknown_style() {
+        let mut frontmatter = Frontmatter::new();
+        frontmatter.set_theme("nope");
+        frontmatter.set_code_highlighter("github");
+        assert!(frontmatter.validate().is_err());
+        assert!(frontmatter.validate_with(ThemeFallback::Default).is_ok());
+        assert!(frontmatter.style_error().unwrap().contains("Invalid theme 'nope'"));
+
+        // Known values are kept
+        assert_eq!(
+            frontmatter.replace_unknown_style(Some("lapis"), None),
+            vec!["theme 'nope'".to_string()]
+        );
+        assert_eq!(frontmatter.theme.as_deref(), Some("lapis"));
+        assert_eq!(frontmatter.code.as_deref(), Some("github"));
+        assert_eq!(frontmatter.style_error(), None);
+
+        // Without a configured default the field is removed
+        frontmatter.set_code_highlighter("nope");
+        assert_eq!(
+            frontmatter.replace_unknown_style(None, None),
+            vec!["code highlighter 'nope'".to_string()]
+        );
+        assert_eq!(frontmatter.code, None);
+        assert!(frontmatter.replace_unknown_style(None, None).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_theme_fallback_from_config_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yaml");
+        let write = |settings: &str| {
+            std::fs::write(
+                &path,
+                format!(
+                    "accounts:\n  main: {{ name: main, app_id: a, app_secret: s }}\nsettings:\n{}",
+                    settings
+                ),
+            )
+            .unwrap();
+        };
+
+        write("  theme_fallback: default\n  default_theme: lapis\n");
+        let config = Config::from_file(&path, None).await.unwrap();
+        assert_eq!(config.theme_fallback, ThemeFallback::Default);
+        assert_eq!(config.default_theme.as_deref(), Some("lapis"));
+
+        write("  theme_fallback: skip\n");
+        let config = Config::from_file(&path, None).await.unwrap();
+        assert_eq!(config.theme_fallback, ThemeFallback::Skip);
+
+        write("  verbose: false\n");
+        let config = Config::from_file(&path, None).await.unwrap();
+        assert_eq!(config.theme_fallback, ThemeFallback::Error);
+
+        write("  default_theme: nope\n");
+        let err = Config::from_file(&path, None).await.unwrap_err();
+        assert!(err.to_string().contains("Invalid default_theme 'nope'"));
+    }
+
+    #[tokio::test]
+    async fn test_content_root_from_config_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yaml");
+        let write = |settings: &str| {
+            std::fs::write(
+                &path,
+                format!(
+                    "accounts:\n  main: {{ name: main, app_id: a, app_secret: s }}\nsettings:\n{}",
+                    settings
+                ),
+            )
+            .unwrap();
+        };
+
+        let root = temp_dir.path().join("site");
+        std::fs::create_dir(&root).unwrap();
+        write(&format!("  content_root: {:?}\n", root.display().to_string()));
+        let config = Config::from_file(&path, None).await.unwrap();
+        assert_eq!(config.content_root, Some(root.canonicalize().unwrap()));
+
+        write("  verbose: false\n");
+        let config = Config::from_file(&path, None).await.unwrap();
+        assert_eq!(config.content_root, None);
+
+        let missing = temp_dir.path().join("missing");
+        write(&format!("  content_root: {:?}\n", missing.display().to_string()));
+        let err = Config::from_file(&path, None).await.unwrap_err();
+        assert!(err.to_string().contains("is not a directory"));
+    }
+
+    #[tokio::test]
+    async fn test_obsidian_settings_from_config_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yaml");
+        let write = |settings: &str| {
+            std::fs::write(
+                &path,
+                format!(
+                    "accounts:\n  main: {{ name: main, app_id: a, app_secret: s }}\nsettings:\n{}",
+                    settings
+                ),
+            )
+            .unwrap();
+        };
+
+        write("  verbose: false\n");
+        let config = Config::from_file(&path, None).await.unwrap();
+        assert!(!config.obsidian);
+        assert_eq!(config.obsidian_vault, None);
+        assert_eq!(config.wiki_links, WikiLinks::Text);
+
+        let vault = temp_dir.path().join("vault");
+        std::fs::create_dir(&vault).unwrap();
+        write(&format!(
+            "  obsidian: true\n  obsidian_vault: {:?}\n  wiki_links: footnote\n",
+            vault.display().to_string()
+        ));
+        let config = Config::from_file(&path, None).await.unwrap();
+        assert!(config.obsidian);
+        assert_eq!(config.obsidian_vault, Some(vault.canonicalize().unwrap()));
+        assert_eq!(config.wiki_links, WikiLinks::Footnote);
+
+        write("  obsidian_vault: missing\n");
+        let err = Config::from_file(&path, None).await.unwrap_err();
+        assert!(err.to_string().contains("obsidian_vault 'missing' is not a directory"));
+    }
+
+    #[tokio::test]
+    async fn test_markdown_extensions_from_config_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yaml");
+        let write = |settings: &str| {
+            std::fs::write(
+                &path,
+                format!(
+                    "accounts:\n  main: {{ name: main, app_id: a, app_secret: s }}\nsettings:\n{}",
+                    settings
+                ),
+            )
+            .unwrap();
+        };
+
+        write("  verbose: false\n");
+        let config = Config::from_file(&path, None).await.unwrap();
+        assert_eq!(config.markdown_extensions, vec!["md"]);
+
+        write("  markdown_extensions: [md, .Markdown, mdx]\n");
+        let config = Config::from_file(&path, None).await.unwrap();
+        assert_eq!(config.markdown_extensions, vec!["md", "markdown", "mdx"]);
+
+        write("  markdown_extensions: []\n");
+        let err = Config::from_file(&path, None).await.unwrap_err();
+        assert!(err.is_config());
+        assert!(err.to_string().contains("at least one extension"));
+    }
+
+    #[tokio::test]
+    async fn test_default_cover_from_config_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yaml");
+        let write = |accounts: &str, settings: &str| {
+            std::fs::write(&path, format!("accounts:\n{}settings:\n{}", accounts, settings))
+                .unwrap();
+        };
+        std::fs::create_dir(temp_dir.path().join("assets")).unwrap();
+        std::fs::write(temp_dir.path().join("assets/main.png"), b"main").unwrap();
+        std::fs::write(temp_dir.path().join("assets/shared.png"), b"shared").unwrap();
+        let accounts = "  main: { name: main, app_id: a, app_secret: s, \
+                        default_cover: assets/main.png }\n  \
+                        side: { name: side, app_id: b, app_secret: t }\n";
+
+        // Relative to the config file; the settings default fills in the rest
+        write(accounts, "  default_cover: assets/shared.png\n");
+        let config = Config::from_file(&path, None).await.unwrap();
+        let assets = temp_dir.path().join("assets").canonicalize().unwrap();
+        let cover = |name: &str| config.available_accounts[name].default_cover.clone();
+        assert_eq!(cover("main"), Some(assets.join("main.png")));
+        assert_eq!(cover("side"), Some(assets.join("shared.png")));
+
+        write(accounts, "  verbose: false\n");
+        let config = Config::from_file(&path, None).await.unwrap();
+        assert_eq!(config.available_accounts["side"].default_cover, None);
+
+        write(accounts, "  default_cover: assets/missing.png\n");
+        let err = Config::from_file(&path, None).await.unwrap_err();
+        assert!(err.is_config());
+        assert!(err.to_string().contains("of account 'side' is not a file"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_reload_config_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yaml");
+        let main = "  main: { name: main, app_id: a, app_secret: s }\n";
+        std::fs::write(&path, format!("accounts:\n{}", main)).unwrap();
+
+        let mut config = Config::from_file(&path, None).await.unwrap();
+        config.trace_http = true;
+        config.verbose = true;
+
+        // New accounts and settings are picked up, command-line only options kept
+        std::fs::write(
+            &path,
+            format!(
+                "accounts:\n{}  blog: {{ name: blog, app_id: b, app_secret: t }}\n\
+                 settings:\n  auto_slug: true\n",
+                main
+            ),
+        )
+        .unwrap();
+        let reloaded = config.reload().await.unwrap();
+        assert_eq!(reloaded.available_accounts.len(), 2);
+        assert_eq!(reloaded.wechat_account.name, "main");
+        assert!(reloaded.auto_slug);
+        assert!(reloaded.trace_http);
+        assert!(!reloaded.verbose);
+
+        // An invalid file or a removed account fails without touching the config
+        std::fs::write(&path, "accounts: [").unwrap();
+        assert!(config.reload().await.unwrap_err().is_config());
+        let blog_only = "accounts:\n  blog: { name: blog, app_id: b, app_secret: t }\n";
+        std::fs::write(&path, blog_only).unwrap();
+        assert!(config.reload().await.unwrap_err().to_string().contains("main"));
+        assert_eq!(config.available_accounts.len(), 1);
+
+        let from_env = Config::new("a".to_string(), "s".to_string(), None, false);
+        assert!(from_env.reload().await.unwrap_err().is_config());
     }
 
     #[test]
@@ -844,15 +3960,58 @@ mod tests {
         assert!(!is_valid_code_highlighter(""));
     }
 
+    #[test]
+    fn test_style_metadata_matches_valid_names() {
+        let names = |styles: &[StyleInfo]| styles.iter().map(|s| s.name).collect::<Vec<_>>();
+        assert_eq!(names(themes()), VALID_THEMES);
+        assert_eq!(names(code_highlighters()), VALID_CODE_HIGHLIGHTERS);
+
+        for style in themes().iter().chain(code_highlighters()) {
+            assert!(!style.description.is_empty(), "{} has no description", style.name);
+            if style.name.ends_with("-dark") {
+                assert_eq!(style.tone, Tone::Dark, "{}", style.name);
+            }
+            if style.name.ends_with("-light") {
+                assert_eq!(style.tone, Tone::Light, "{}", style.name);
+            }
+        }
+
+        let json = serde_json::to_value(themes()[1]).unwrap();
+        assert_eq!(json["name"], "lapis");
+        assert_eq!(json["tone"], "light");
+    }
+
     #[test]
     fn test_frontmatter_serialization() {
         let frontmatter = Frontmatter {
             title: Some("Test Article".to_string()),
-            published: Some("draft".to_string()),
+            slug: None,
+            lang: None,
+            published: Some(PublishedField::from(PublicationStatus::Draft)),
+            draft_id: None,
             description: "Test Article".to_string(),
             cover: Some("cover.png".to_string()),
+            thumb: None,
+            cover_media_id: None,
+            cover_template: None,
+            cover_variations: None,
+            ai_provider: None,
+            text_model: None,
+            image_model: None,
             theme: Some("lapis".to_string()),
             code: Some("github".to_string()),
+            transforms: None,
+            content_source_url: None,
+            need_open_comment: None,
+            only_fans_can_comment: None,
+            original: None,
+            original_author: None,
+            pic_crop_235_1: None,
+            pic_crop_1_1: None,
+            account: None,
+            accounts: None,
+            uploads: None,
+            ignore: None,
             other: serde_yaml::Value::Mapping(serde_yaml::Mapping::new()),
         };
 
@@ -866,4 +4025,131 @@ mod tests {
         let deserialized: Frontmatter = serde_yaml::from_str(&yaml).unwrap();
         assert_eq!(frontmatter, deserialized);
     }
+
+    #[test]
+    fn test_published_legacy_spellings() {
+        use PublicationStatus::*;
+
+        // (value as written, status, raw kept, value written back)
+        let cases: &[(&str, PublicationStatus, Option<&str>, &str)] = &[
+            ("true", Published, None, "true"),
+            ("True", Published, None, "true"),
+            ("\"true\"", Published, Some("true"), "'true'"),
+            ("'true'", Published, Some("true"), "'true'"),
+            ("'\"true\"'", Published, Some("\"true\""), "'\"true\"'"),
+            ("yes", Published, Some("yes"), "yes"),
+            ("published", Published, Some("published"), "published"),
+            ("\" Published \"", Published, Some(" Published "), "' Published '"),
+            ("false", Unpublished, None, "false"),
+            ("\"false\"", Unpublished, Some("false"), "'false'"),
+            ("no", Unpublished, Some("no"), "no"),
+            ("pending", Unpublished, Some("pending"), "pending"),
+            ("unpublished", Unpublished, Some("unpublished"), "unpublished"),
+            ("\"\"", Unpublished, Some(""), "''"),
+            ("draft", Draft, None, "draft"),
+            ("\"draft\"", Draft, None, "draft"),
+            ("Draft", Draft, Some("Draft"), "Draft"),
+            ("DRAFT ", Draft, Some("DRAFT"), "DRAFT"),
+            ("skip", Skip, None, "skip"),
+            ("'SKIP'", Skip, Some("SKIP"), "SKIP"),
+            ("ignore", Skip, Some("ignore"), "ignore"),
+            ("\"Ignore\"", Skip, Some("Ignore"), "Ignore"),
+            ("review", Unpublished, Some("review"), "review"),
+            ("1", Unpublished, Some("1"), "'1'"),
+            ("0.5", Unpublished, Some("0.5"), "'0.5'"),
+        ];
+
+        for (value, status, raw, written) in cases {
+            let yaml = format!("published: {}\n", value);
+            let frontmatter: Frontmatter = serde_yaml::from_str(&yaml)
+                .unwrap_or_else(|e| panic!("published: {} failed to parse: {}", value, e));
+            let published = frontmatter.published.as_ref().unwrap();
+
+            assert_eq!(published.status(), *status, "status of {}", value);
+            assert_eq!(frontmatter.publication_status(), *status, "status of {}", value);
+            assert_eq!(published.raw(), *raw, "raw of {}", value);
+            assert_eq!(
+                serde_yaml::to_string(&frontmatter).unwrap(),
+                format!("published: {}\n", written),
+                "round trip of {}",
+                value
+            );
+
+            // Normalizing writes the canonical form, which reads back unchanged
+            let normalized = published.normalized();
+            assert_eq!(normalized.raw(), None);
+            let canonical = serde_yaml::to_string(&normalized).unwrap();
+            assert_eq!(
+                canonical.trim_end(),
+                match status {
+                    Published => "true",
+                    Unpublished => "false",
+                    Draft => "draft",
+                    Skip => "skip",
+                },
+                "canonical form of {}",
+                value
+            );
+            let reread: PublishedField = serde_yaml::from_str(&canonical).unwrap();
+            assert_eq!(reread, normalized, "reread of {}", value);
+        }
+    }
+
+    #[test]
+    fn test_published_recognition_and_display() {
+        let read = |value: &str| -> PublishedField { serde_yaml::from_str(value).unwrap() };
+
+        assert!(read("true").is_recognized());
+        assert!(read("\"yes\"").is_recognized());
+        assert!(read("pending").is_recognized());
+        assert!(!read("review").is_recognized());
+        assert!(!read("2").is_recognized());
+
+        assert_eq!(read("true").to_string(), "true");
+        assert_eq!(read("false").to_string(), "false");
+        assert_eq!(read("draft").to_string(), "draft");
+        assert_eq!(read("\"true\"").to_string(), "\"true\"");
+
+        // Changing the status drops the legacy spelling
+        let mut frontmatter: Frontmatter = serde_yaml::from_str("published: pending").unwrap();
+        frontmatter.set_published(PublicationStatus::Draft);
+        assert_eq!(serde_yaml::to_string(&frontmatter).unwrap(), "published: draft\n");
+
+        // Missing and null values are unpublished
+        let frontmatter: Frontmatter = serde_yaml::from_str("published: null").unwrap();
+        assert_eq!(frontmatter.published, None);
+        assert_eq!(frontmatter.publication_status(), PublicationStatus::Unpublished);
+
+        // Sequences and mappings name no status and are rejected
+        assert!(serde_yaml::from_str::<Frontmatter>("published: [true]").is_err());
+    }
+
+    #[test]
+    fn test_publication_status_from_spelling() {
+        assert_eq!(PublicationStatus::from_spelling("  YES "), Some(PublicationStatus::Published));
+        assert_eq!(PublicationStatus::from_spelling("\"draft\""), Some(PublicationStatus::Draft));
+        assert_eq!(PublicationStatus::from_spelling(""), Some(PublicationStatus::Unpublished));
+        assert_eq!(PublicationStatus::from_spelling("Skip"), Some(PublicationStatus::Skip));
+        assert_eq!(PublicationStatus::from_spelling("ignore"), Some(PublicationStatus::Skip));
+        assert_eq!(PublicationStatus::from_spelling("later"), None);
+
+        assert_eq!(PublicationStatus::Published.skip_reason(), Some("already published"));
+        assert_eq!(PublicationStatus::Skip.skip_reason(), Some("marked skip"));
+        assert_eq!(PublicationStatus::Draft.skip_reason(), None);
+        assert_eq!(PublicationStatus::Unpublished.skip_reason(), None);
+    }
+
+    #[test]
+    fn test_frontmatter_is_ignored() {
+        let frontmatter: Frontmatter = serde_yaml::from_str("title: Footer\nignore: true").unwrap();
+        assert!(frontmatter.is_ignored());
+        assert!(!frontmatter.is_empty());
+        // Independent of the publish state
+        assert_eq!(frontmatter.publication_status(), PublicationStatus::Unpublished);
+        assert!(serde_yaml::to_string(&frontmatter).unwrap().contains("ignore: true"));
+
+        let frontmatter: Frontmatter = serde_yaml::from_str("title: Post\nignore: false").unwrap();
+        assert!(!frontmatter.is_ignored());
+        assert!(!Frontmatter::with_title("Post").is_ignored());
+    }
 }
@@ -0,0 +1,246 @@
+//! Frontmatter normalization
+//!
+//! Implements `--normalize-frontmatter`: rewrites `published` values written
+//! in a legacy spelling, such as `"true"`, `yes` or `pending`, to the canonical
+//! `true`, `false`, `draft` or `skip`. Only the `published` line changes, so
+//! comments and the layout of the rest of the frontmatter are kept.
+
+use crate::backup::Backup;
+use crate::error::{Error, Result};
+use crate::markdown::{FrontmatterEdit, format_markdown_preserving, parse_markdown_raw, write_file_atomic};
+use crate::models::{PublicationStatus, PublishedField};
+use crate::output::{FORMATTER, OutputFormatter};
+use crate::wechat::collect_markdown_files;
+use std::path::{Path, PathBuf};
+
+/// What normalization did to one file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Normalization {
+    /// The file has no `published` field or it is already canonical
+    Unchanged,
+    /// The value as written was replaced by the canonical one for `status`
+    Rewritten {
+        /// Value as it was written
+        from: String,
+        /// Status now written canonically
+        status: PublicationStatus,
+    },
+    /// The value names no status, so it was left as written
+    Unrecognized(String),
+}
+
+/// Outcome of normalizing all files under a path
+#[derive(Debug, Default)]
+pub struct NormalizeReport {
+    /// Per-file outcome in walk order, or the error that stopped the file
+    pub files: Vec<(PathBuf, std::result::Result<Normalization, String>)>,
+}
+
+impl NormalizeReport {
+    /// Number of files that were rewritten
+    pub fn rewritten(&self) -> usize {
+        self.count(|n| matches!(n, Normalization::Rewritten { .. }))
+    }
+
+    /// Number of files whose value was left as written because it names no status
+    pub fn unrecognized(&self) -> usize {
+        self.count(|n| matches!(n, Normalization::Unrecognized(_)))
+    }
+
+    /// Number of files that could not be read or written
+    pub fn errors(&self) -> usize {
+        self.files.iter().filter(|(_, outcome)| outcome.is_err()).count()
+    }
+
+    fn count(&self, matches: impl Fn(&Normalization) -> bool) -> usize {
+        self.files
+            .iter()
+            .filter(|(_, outcome)| outcome.as_ref().is_ok_and(&matches))
+            .count()
+    }
+
+    /// One-line counts, e.g. `3 files: 1 normalized, 2 unchanged`
+    pub fn summary(&self) -> String {
+        let unchanged = self.files.len() - self.rewritten() - self.unrecognized() - self.errors();
+        let mut summary = format!(
+            "{} files: {} normalized, {} unchanged",
+            self.files.len(),
+            self.rewritten(),
+            unchanged
+        );
+        if self.unrecognized() > 0 {
+            summary.push_str(&format!(", {} unrecognized", self.unrecognized()));
+        }
+        if self.errors() > 0 {
+            summary.push_str(&format!(", {} failed", self.errors()));
+        }
+        summary
+    }
+}
+
+/// Rewrites the `published` value of one markdown file to its canonical form
+///
+/// The file is backed up before it is changed, if a backup is given.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read, parsed or written
+pub async fn normalize_file(path: &Path, backup: Option<&Backup>) -> Result<Normalization> {
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| Error::io_at(path, e))?;
+    let (raw, frontmatter, body) = parse_markdown_raw(&content)?;
+
+    let Some(published) = &frontmatter.published else {
+        return Ok(Normalization::Unchanged);
+    };
+    let Some(from) = published.raw() else {
+        return Ok(Normalization::Unchanged);
+    };
+    if !published.is_recognized() {
+        return Ok(Normalization::Unrecognized(from.to_string()));
+    }
+
+    let status = published.status();
+    let edits = [FrontmatterEdit::SetValue(
+        "published".to_string(),
+        status.canonical_value(),
+    )];
+    let updated = format_markdown_preserving(&raw, &edits, &body)?;
+    if let Some(backup) = backup {
+        backup.save(path).await?;
+    }
+    write_file_atomic(path, updated.as_bytes()).await?;
+
+    Ok(Normalization::Rewritten {
+        from: from.to_string(),
+        status,
+    })
+}
+
+/// Normalizes a markdown file or every markdown file in a directory
+pub async fn normalize_path(path: &Path, backup: Option<&Backup>) -> NormalizeReport {
+    let files = if path.is_file() {
+        vec![path.to_path_buf()]
+    } else {
+        collect_markdown_files(path)
+    };
+
+    let mut report = NormalizeReport::default();
+    for file in files {
+        let outcome = normalize_file(&file, backup).await.map_err(|e| e.to_string());
+        report.files.push((file, outcome));
+    }
+    report
+}
+
+/// Normalizes a path for `--normalize-frontmatter`, printing each change
+pub async fn run(path: &Path, backup: Option<&Backup>) -> NormalizeReport {
+    let report = normalize_path(path, backup).await;
+
+    if report.files.is_empty() {
+        println!("No markdown files found in {}", path.display());
+        return report;
+    }
+
+    for (file, outcome) in &report.files {
+        match outcome {
+            Ok(Normalization::Unchanged) => {}
+            Ok(Normalization::Rewritten { from, status }) => {
+                let canonical = PublishedField::from(*status);
+                FORMATTER.print_success(&format!(
+                    "{}: published {:?} -> {}",
+                    file.display(),
+                    from,
+                    canonical
+                ));
+            }
+            Ok(Normalization::Unrecognized(value)) => FORMATTER.print_file_warning(
+                file,
+                None,
+                &format!("published {:?} names no status, left unchanged", value),
+            ),
+            Err(error) => FORMATTER.print_file_error(file, None, error),
+        }
+    }
+    FORMATTER.print_info(&report.summary());
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_normalize_file_rewrites_legacy_spellings() {
+        let temp_dir = TempDir::new().unwrap();
+        let cases = [
+            ("\"true\"", "true"),
+            ("'true'", "true"),
+            ("yes", "true"),
+            ("Published", "true"),
+            ("\"false\"", "false"),
+            ("pending", "false"),
+            ("\"\"", "false"),
+            ("DRAFT", "draft"),
+            ("\"Skip\"", "skip"),
+        ];
+
+        for (index, (value, canonical)) in cases.iter().enumerate() {
+            let path = temp_dir.path().join(format!("{}.md", index));
+            fs::write(
+                &path,
+                format!("---\ntitle: T # kept\npublished: {} # state\n---\nBody\n", value),
+            )
+            .unwrap();
+
+            let outcome = normalize_file(&path, None).await.unwrap();
+            assert!(matches!(outcome, Normalization::Rewritten { .. }), "{}: {:?}", value, outcome);
+            assert_eq!(
+                fs::read_to_string(&path).unwrap(),
+                format!("---\ntitle: T # kept\npublished: {} # state\n---\nBody\n", canonical),
+                "for {}",
+                value
+            );
+            // Canonical values are left alone on a second pass
+            assert_eq!(normalize_file(&path, None).await.unwrap(), Normalization::Unchanged);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_normalize_path_reports_and_backs_up() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        fs::write(dir.join("a.md"), "---\npublished: \"true\"\n---\nA\n").unwrap();
+        fs::write(dir.join("b.md"), "---\npublished: draft\n---\nB\n").unwrap();
+        fs::write(dir.join("c.md"), "---\npublished: review\n---\nC\n").unwrap();
+        fs::write(dir.join("d.md"), "---\npublished: [\n---\nD\n").unwrap();
+        fs::write(dir.join("e.md"), "# No frontmatter\n").unwrap();
+
+        let backup = Backup::new(dir, 1, "20261017-083000");
+        let report = normalize_path(dir, Some(&backup)).await;
+        assert_eq!(report.rewritten(), 1);
+        assert_eq!(report.unrecognized(), 1);
+        assert_eq!(report.errors(), 1);
+        assert_eq!(
+            report.summary(),
+            "5 files: 1 normalized, 2 unchanged, 1 unrecognized, 1 failed"
+        );
+
+        assert_eq!(fs::read_to_string(dir.join("a.md")).unwrap(), "---\npublished: true\n---\nA\n");
+        assert_eq!(
+            fs::read_to_string(dir.join("c.md")).unwrap(),
+            "---\npublished: review\n---\nC\n"
+        );
+        // Only the rewritten file was backed up
+        let set_dir = backup.set_dir().await.expect("a backup set was created");
+        assert_eq!(
+            fs::read_to_string(set_dir.join("a.md")).unwrap(),
+            "---\npublished: \"true\"\n---\nA\n"
+        );
+        assert!(!set_dir.join("b.md").exists());
+    }
+}
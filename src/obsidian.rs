@@ -0,0 +1,400 @@
+//! Obsidian embeds and wiki links
+//!
+//! Obsidian vaults embed images as `![[diagram.png]]` and link notes as
+//! `[[Other Note]]`, which WeChat shows as literal brackets. With `obsidian`
+//! enabled, the uploaded copy of an article has each image embed rewritten
+//! into a standard markdown image, resolved against the vault, so it is
+//! uploaded like any other local body image; wiki links become plain text or
+//! numbered notes listed at the end. Fenced code blocks and code spans are
+//! left alone, and the markdown file itself is not changed.
+
+use crate::error::Result;
+use crate::markdown::{ArticleContext, BodyTransform};
+use crate::models::WikiLinks;
+use crate::output::{FORMATTER, OutputFormatter, annotations_enabled};
+use crate::validation::{Fences, INLINE_CODE_RE};
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use tracing::warn;
+use walkdir::WalkDir;
+
+/// Matches an embed `![[target|alias]]` or a wiki link `[[target|alias]]`
+static WIKI_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(!?)\[\[([^\[\]\n]+?)\]\]").expect("valid wiki link regex"));
+
+/// Matches an embed alias that sets the image size, e.g. `300` or `300x200`
+static SIZE_ALIAS_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\d+(x\d+)?$").expect("valid size alias regex"));
+
+/// Extensions of embeds that are uploaded as images
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "svg", "bmp"];
+
+/// An image embed whose file was not found in the vault
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnresolvedEmbed {
+    /// Target as written between the brackets, without the alias
+    pub target: String,
+    /// 1-based line number within the body
+    pub line: usize,
+}
+
+/// Result of converting the Obsidian syntax of a markdown body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObsidianBody<'a> {
+    /// The converted body, borrowed if nothing was converted
+    pub body: Cow<'a, str>,
+    /// Image embeds left as written because their file was not found
+    pub unresolved: Vec<UnresolvedEmbed>,
+}
+
+/// Converts the image embeds and wiki links of an article body
+///
+/// An embed is looked up next to the article (`article_dir`) and in the
+/// `vault`: a target with a folder, like `assets/diagram.png`, is taken
+/// relative to the vault root first, and a bare file name is found anywhere
+/// in the vault, preferring the shallowest match, like Obsidian does. The
+/// image is referenced relative to the article when it lies below it, and by
+/// its absolute path otherwise. An embed alias becomes the image's alt text,
+/// unless it is a size such as `300x200`. Embeds of anything but images are
+/// converted like wiki links.
+pub fn convert_obsidian<'a>(
+    body: &'a str,
+    article_dir: &Path,
+    vault: &Path,
+    wiki_links: WikiLinks,
+) -> ObsidianBody<'a> {
+    let mut resolver = EmbedResolver::new(article_dir, vault);
+    let mut notes: Vec<String> = Vec::new();
+    let mut unresolved = Vec::new();
+    let mut fences = Fences::default();
+    let mut changed = false;
+    let mut converted = String::with_capacity(body.len());
+
+    for (index, line) in body.split_inclusive('\n').enumerate() {
+        if fences.is_code(line) {
+            converted.push_str(line);
+            continue;
+        }
+        let code: Vec<_> = INLINE_CODE_RE.find_iter(line).map(|m| m.range()).collect();
+        let replaced = WIKI_RE.replace_all(line, |captures: &Captures| {
+            let whole = captures.get(0).expect("whole match");
+            let in_code = code
+                .iter()
+                .any(|span| span.start < whole.end() && whole.start() < span.end);
+            if in_code {
+                return whole.as_str().to_string();
+            }
+
+            let (target, alias) = match captures[2].split_once('|') {
+                Some((target, alias)) => (target.trim(), Some(alias.trim())),
+                None => (captures[2].trim(), None),
+            };
+            if &captures[1] == "!" && is_image(target) {
+                let Some(image) = resolver.resolve(target) else {
+                    unresolved.push(UnresolvedEmbed {
+                        target: target.to_string(),
+                        line: index + 1,
+                    });
+                    return whole.as_str().to_string();
+                };
+                changed = true;
+                let alt = alias.filter(|alias| !SIZE_ALIAS_RE.is_match(alias)).unwrap_or("");
+                return format!("![{}]({})", alt, markdown_destination(&image));
+            }
+
+            changed = true;
+            let name = note_name(target);
+            let text = alias.filter(|alias| !alias.is_empty()).unwrap_or(&name);
+            match wiki_links {
+                WikiLinks::Text => text.to_string(),
+                WikiLinks::Footnote => {
+                    let number = match notes.iter().position(|note| *note == name) {
+                        Some(position) => position + 1,
+                        None => {
+                            notes.push(name.clone());
+                            notes.len()
+                        }
+                    };
+                    format!("{}[{}]", text, number)
+                }
+            }
+        });
+        converted.push_str(&replaced);
+    }
+
+    if !notes.is_empty() {
+        let trimmed = converted.trim_end().len();
+        converted.truncate(trimmed);
+        converted.push_str("\n\n---\n\n");
+        for (index, note) in notes.iter().enumerate() {
+            converted.push_str(&format!("{}. {}\n", index + 1, note));
+        }
+    }
+
+    ObsidianBody {
+        body: if changed {
+            Cow::Owned(converted)
+        } else {
+            Cow::Borrowed(body)
+        },
+        unresolved,
+    }
+}
+
+/// The `obsidian` body transform: [`convert_obsidian`] with the run's vault
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObsidianTransform {
+    /// Vault root embeds are resolved against; the article's directory if unset
+    pub vault: Option<PathBuf>,
+    /// What wiki links become
+    pub wiki_links: WikiLinks,
+}
+
+impl ObsidianTransform {
+    /// Name the transform is selected by
+    pub const NAME: &'static str = "obsidian";
+}
+
+impl BodyTransform for ObsidianTransform {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn apply(&self, ctx: &ArticleContext<'_>, body: String) -> Result<String> {
+        let article_dir = ctx.path.parent().unwrap_or(Path::new(""));
+        let vault = self.vault.as_deref().unwrap_or(article_dir);
+        let converted = convert_obsidian(&body, article_dir, vault, self.wiki_links);
+        warn_unresolved_embeds(ctx.path, &converted.unresolved, ctx.verbose);
+        Ok(match converted.body {
+            Cow::Owned(converted) => converted,
+            Cow::Borrowed(_) => body,
+        })
+    }
+}
+
+/// Reports image embeds that were not found, with the body line they are on
+pub fn warn_unresolved_embeds(path: &Path, unresolved: &[UnresolvedEmbed], verbose: bool) {
+    for embed in unresolved {
+        let message = format!("unresolved Obsidian embed: ![[{}]]", embed.target);
+        if verbose {
+            warn!("{} in {} (body line {})", message, path.display(), embed.line);
+        } else if annotations_enabled() {
+            // Line numbers are relative to the body, so only the file is annotated
+            FORMATTER.print_file_warning(
+                path,
+                None,
+                &format!("body line {}: {}", embed.line, message),
+            );
+        } else {
+            FORMATTER.print_file_warning(path, Some(embed.line), &message);
+        }
+    }
+}
+
+/// Returns true if an embed target names an image file
+fn is_image(target: &str) -> bool {
+    let target = target.split('#').next().unwrap_or(target);
+    Path::new(target)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            IMAGE_EXTENSIONS
+                .iter()
+                .any(|image| extension.eq_ignore_ascii_case(image))
+        })
+}
+
+/// Returns how a wiki link target reads as text: `Note`, `Note > Heading`
+/// or, for a heading of the same note, `Heading`
+///
+/// A `.md` extension and block references (`#^id`) are dropped.
+fn note_name(target: &str) -> String {
+    let (note, heading) = match target.split_once('#') {
+        Some((note, heading)) => (note, Some(heading.trim())),
+        None => (target, None),
+    };
+    let note = note.trim();
+    let note = note.strip_suffix(".md").unwrap_or(note);
+    match heading.filter(|heading| !heading.is_empty() && !heading.starts_with('^')) {
+        Some(heading) if note.is_empty() => heading.to_string(),
+        Some(heading) => format!("{} > {}", note, heading),
+        None => note.to_string(),
+    }
+}
+
+/// Formats an image path as a markdown link destination, using angle
+/// brackets when it contains spaces or parentheses
+fn markdown_destination(image: &str) -> String {
+    if image.contains(char::is_whitespace) || image.contains(['(', ')']) {
+        format!("<{}>", image)
+    } else {
+        image.to_string()
+    }
+}
+
+/// Finds the files of image embeds, listing the vault at most once
+struct EmbedResolver<'a> {
+    article_dir: &'a Path,
+    vault: &'a Path,
+    vault_files: Option<Vec<PathBuf>>,
+}
+
+impl<'a> EmbedResolver<'a> {
+    fn new(article_dir: &'a Path, vault: &'a Path) -> Self {
+        let current = Path::new(".");
+        Self {
+            article_dir: if article_dir.as_os_str().is_empty() { current } else { article_dir },
+            vault: if vault.as_os_str().is_empty() { current } else { vault },
+            vault_files: None,
+        }
+    }
+
+    /// Returns the path the embedded image is referenced by, if it exists
+    fn resolve(&mut self, target: &str) -> Option<String> {
+        let target = target.split('#').next().unwrap_or(target).trim();
+        let candidates = if target.contains('/') {
+            [self.vault.join(target), self.article_dir.join(target)]
+        } else {
+            [self.article_dir.join(target), self.vault.join(target)]
+        };
+        let found = candidates
+            .into_iter()
+            .find(|candidate| candidate.is_file())
+            .or_else(|| self.find_in_vault(target))?;
+
+        let found = std::path::absolute(&found).unwrap_or(found);
+        let article_dir = std::path::absolute(self.article_dir)
+            .unwrap_or_else(|_| self.article_dir.to_path_buf());
+        Some(match found.strip_prefix(&article_dir) {
+            Ok(relative) => relative.to_string_lossy().replace('\\', "/"),
+            Err(_) => found.to_string_lossy().into_owned(),
+        })
+    }
+
+    /// Returns the shallowest file in the vault with the name `target`
+    fn find_in_vault(&mut self, target: &str) -> Option<PathBuf> {
+        if target.contains('/') {
+            return None;
+        }
+        let vault = self.vault;
+        let files = self.vault_files.get_or_insert_with(|| {
+            let mut files: Vec<PathBuf> = WalkDir::new(vault)
+                .into_iter()
+                .filter_entry(|entry| {
+                    entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.')
+                })
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file())
+                .map(|entry| entry.into_path())
+                .collect();
+            files.sort_by(|a, b| {
+                a.components()
+                    .count()
+                    .cmp(&b.components().count())
+                    .then_with(|| a.cmp(b))
+            });
+            files
+        });
+        files
+            .iter()
+            .find(|file| file.file_name().is_some_and(|name| name == target))
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn vault() -> TempDir {
+        let vault = TempDir::new().unwrap();
+        fs::create_dir_all(vault.path().join("notes/rust")).unwrap();
+        fs::create_dir_all(vault.path().join("attachments/diagrams")).unwrap();
+        fs::create_dir_all(vault.path().join(".obsidian")).unwrap();
+        fs::write(vault.path().join("attachments/diagrams/flow.png"), b"png").unwrap();
+        fs::write(vault.path().join(".obsidian/flow.png"), b"png").unwrap();
+        fs::write(vault.path().join("notes/rust/local.png"), b"png").unwrap();
+        fs::write(vault.path().join("notes/my chart.png"), b"png").unwrap();
+        vault
+    }
+
+    #[test]
+    fn test_embeds_resolve_in_nested_folders() {
+        let vault = vault();
+        let article_dir = vault.path().join("notes/rust");
+        let body = "![[local.png]]\n\
+                    ![[flow.png|The data flow]]\n\
+                    ![[attachments/diagrams/flow.png|300x200]]\n";
+        let converted = convert_obsidian(body, &article_dir, vault.path(), WikiLinks::Text);
+        let flow = vault.path().join("attachments/diagrams/flow.png");
+        assert_eq!(
+            converted.body,
+            format!(
+                "![](local.png)\n![The data flow]({flow})\n![]({flow})\n",
+                flow = flow.display()
+            )
+        );
+        assert!(converted.unresolved.is_empty());
+
+        // Images below the article stay relative; names with spaces are bracketed
+        let converted = convert_obsidian(
+            "See ![[rust/local.png|Local]] and ![[my chart.png]].\n",
+            &vault.path().join("notes"),
+            vault.path(),
+            WikiLinks::Text,
+        );
+        assert_eq!(
+            converted.body,
+            "See ![Local](rust/local.png) and ![](<my chart.png>).\n"
+        );
+    }
+
+    #[test]
+    fn test_unresolved_embeds_are_reported_with_their_line() {
+        let vault = vault();
+        let body = "Intro\n\n![[missing.png|Caption]]\n";
+        let converted = convert_obsidian(body, vault.path(), vault.path(), WikiLinks::Text);
+        assert_eq!(converted.body, Cow::Borrowed(body));
+        assert_eq!(
+            converted.unresolved,
+            vec![UnresolvedEmbed {
+                target: "missing.png".to_string(),
+                line: 3,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_wiki_links_as_text_or_footnotes() {
+        let vault = vault();
+        let body = "Read [[Other Note]], [[Other Note|that note]] and [[Guide.md#Setup]].\n\
+                    Also [[#Usage]] and ![[Embedded Note]].\n";
+        let text = convert_obsidian(body, vault.path(), vault.path(), WikiLinks::Text);
+        assert_eq!(
+            text.body,
+            "Read Other Note, that note and Guide > Setup.\nAlso Usage and Embedded Note.\n"
+        );
+
+        let footnotes = convert_obsidian(body, vault.path(), vault.path(), WikiLinks::Footnote);
+        assert_eq!(
+            footnotes.body,
+            "Read Other Note[1], that note[1] and Guide > Setup[2].\n\
+             Also Usage[3] and Embedded Note[4].\n\n---\n\n\
+             1. Other Note\n2. Guide > Setup\n3. Usage\n4. Embedded Note\n"
+        );
+    }
+
+    #[test]
+    fn test_code_is_left_alone() {
+        let vault = vault();
+        let body = "```markdown\n![[flow.png]] and [[Note]]\n```\n\
+                    Use `[[Note]]` to link.\n";
+        let converted = convert_obsidian(body, vault.path(), vault.path(), WikiLinks::Text);
+        assert_eq!(converted.body, Cow::Borrowed(body));
+        assert!(converted.unresolved.is_empty());
+    }
+}
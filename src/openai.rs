@@ -224,10 +224,7 @@ impl OpenAIClient {
                     &format!("{}/{}", self.base_url, endpoint)
                 )
             );
-            return Err(Error::openai(format!(
-                "API request failed with status {}: {}",
-                status, error_text
-            )));
+            return Err(Error::ai_request("OpenAI", status.as_u16(), error_text));
         }
 
         let response_json: Value = response.json().await?;
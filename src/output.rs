@@ -5,6 +5,92 @@
 
 use colored::*;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// How diagnostics and progress are written to the console
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputMode {
+    /// Colored messages for humans
+    #[default]
+    Text,
+    /// GitHub Actions workflow commands for errors and warnings; progress is suppressed
+    Github,
+}
+
+/// Whether errors and warnings are written as GitHub Actions annotations
+static GITHUB_ANNOTATIONS: AtomicBool = AtomicBool::new(false);
+
+/// Sets the output mode for the rest of the process
+pub fn set_output_mode(mode: OutputMode) {
+    GITHUB_ANNOTATIONS.store(mode == OutputMode::Github, Ordering::Relaxed);
+}
+
+/// Returns true if only diagnostics are printed, as GitHub Actions annotations
+pub fn annotations_enabled() -> bool {
+    GITHUB_ANNOTATIONS.load(Ordering::Relaxed)
+}
+
+/// Severity of a GitHub Actions annotation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnotationLevel {
+    /// Shown as a failure on the pull request
+    Error,
+    /// Shown as a warning on the pull request
+    Warning,
+}
+
+/// Formats a GitHub Actions workflow command, e.g. `::error file=posts/foo.md,line=4::message`
+///
+/// A leading `./` is dropped from the file so annotations match repository
+/// paths. Values are escaped as the workflow command syntax requires.
+pub fn format_annotation(
+    level: AnnotationLevel,
+    file: Option<&Path>,
+    line: Option<usize>,
+    message: &str,
+) -> String {
+    let command = match level {
+        AnnotationLevel::Error => "error",
+        AnnotationLevel::Warning => "warning",
+    };
+
+    let mut properties = Vec::new();
+    if let Some(file) = file {
+        let file = file.to_string_lossy();
+        let file = file.strip_prefix("./").unwrap_or(&file);
+        properties.push(format!("file={}", escape_property(file)));
+        if let Some(line) = line {
+            properties.push(format!("line={}", line));
+        }
+    }
+
+    if properties.is_empty() {
+        format!("::{}::{}", command, escape_data(message))
+    } else {
+        format!("::{} {}::{}", command, properties.join(","), escape_data(message))
+    }
+}
+
+/// Escapes an annotation message
+fn escape_data(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escapes an annotation property value
+fn escape_property(value: &str) -> String {
+    escape_data(value).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// Returns `path: message`, or `path:line: message` if the line is known
+fn locate(path: &Path, line: Option<usize>, message: &str) -> String {
+    match line {
+        Some(line) => format!("{}:{}: {}", path.display(), line, message),
+        None => format!("{}: {}", path.display(), message),
+    }
+}
 
 /// Trait for formatting console output with consistent styling
 pub trait OutputFormatter {
@@ -31,37 +117,80 @@ pub trait OutputFormatter {
 
     /// Prints a success message
     fn print_success(&self, message: &str) {
-        println!("{}", self.success(message));
+        if !annotations_enabled() {
+            println!("{}", self.success(message));
+        }
     }
 
     /// Prints an error message
     fn print_error(&self, message: &str) {
-        eprintln!("{}", self.error(message));
+        if annotations_enabled() {
+            println!("{}", format_annotation(AnnotationLevel::Error, None, None, message));
+        } else {
+            eprintln!("{}", self.error(message));
+        }
     }
 
     /// Prints a warning message
     fn print_warning(&self, message: &str) {
-        println!("{}", self.warning(message));
+        if annotations_enabled() {
+            println!("{}", format_annotation(AnnotationLevel::Warning, None, None, message));
+        } else {
+            println!("{}", self.warning(message));
+        }
+    }
+
+    /// Prints an error about a file, at a line if known
+    fn print_file_error(&self, path: &Path, line: Option<usize>, message: &str) {
+        if annotations_enabled() {
+            println!("{}", format_annotation(AnnotationLevel::Error, Some(path), line, message));
+        } else {
+            eprintln!("{}", self.error(&locate(path, line, message)));
+        }
+    }
+
+    /// Prints a warning about a file, at a line if known
+    fn print_file_warning(&self, path: &Path, line: Option<usize>, message: &str) {
+        if annotations_enabled() {
+            println!("{}", format_annotation(AnnotationLevel::Warning, Some(path), line, message));
+        } else {
+            println!("{}", self.warning(&locate(path, line, message)));
+        }
     }
 
     /// Prints an info message
     fn print_info(&self, message: &str) {
-        println!("{}", self.info(message));
+        if !annotations_enabled() {
+            println!("{}", self.info(message));
+        }
     }
 
     /// Prints a progress message
     fn print_progress(&self, message: &str) {
-        println!("{}", self.progress(message));
+        if !annotations_enabled() {
+            println!("{}", self.progress(message));
+        }
     }
 
     /// Prints a skip message
     fn print_skip(&self, message: &str) {
-        println!("{}", self.skip(message));
+        if !annotations_enabled() {
+            println!("{}", self.skip(message));
+        }
     }
 
     /// Prints a generation message
     fn print_generation(&self, message: &str) {
-        println!("{}", self.generation(message));
+        if !annotations_enabled() {
+            println!("{}", self.generation(message));
+        }
+    }
+
+    /// Prints an already formatted line of progress detail
+    fn print_detail(&self, line: &str) {
+        if !annotations_enabled() {
+            println!("{}", line);
+        }
     }
 }
 
@@ -245,6 +374,33 @@ impl<T: OutputFormatter> ApiErrorFormatter for T {
     }
 }
 
+/// Formatter for unified diffs
+pub trait DiffFormatter {
+    /// Colors a unified diff: headers bold, hunk markers cyan, additions green, removals red
+    fn format_diff(&self, diff: &str) -> String;
+}
+
+impl<T: OutputFormatter> DiffFormatter for T {
+    fn format_diff(&self, diff: &str) -> String {
+        diff.lines()
+            .map(|line| {
+                let colored = if line.starts_with("+++") || line.starts_with("---") {
+                    line.bold().to_string()
+                } else if line.starts_with("@@") {
+                    line.cyan().to_string()
+                } else if line.starts_with('+') {
+                    line.green().to_string()
+                } else if line.starts_with('-') {
+                    line.red().to_string()
+                } else {
+                    line.to_string()
+                };
+                colored + "\n"
+            })
+            .collect()
+    }
+}
+
 /// Global formatter instance for consistent usage across the application
 pub const FORMATTER: ConsoleFormatter = ConsoleFormatter;
 
@@ -322,4 +478,43 @@ mod tests {
         let failure_msg = formatter.format_cover_failure();
         assert!(failure_msg.contains("cover generation failed"));
     }
+
+    #[test]
+    fn test_format_annotation() {
+        assert_eq!(
+            format_annotation(
+                AnnotationLevel::Error,
+                Some(Path::new("./posts/foo.md")),
+                Some(4),
+                "broken image: images/missing.png (file not found: posts/images/missing.png)"
+            ),
+            "::error file=posts/foo.md,line=4::broken image: images/missing.png (file not found: posts/images/missing.png)"
+        );
+        assert_eq!(
+            format_annotation(
+                AnnotationLevel::Warning,
+                Some(Path::new("posts/foo.md")),
+                Some(3),
+                "duplicate frontmatter key 'title' on lines 2, 3, using the last value"
+            ),
+            "::warning file=posts/foo.md,line=3::duplicate frontmatter key 'title' on lines 2, 3, using the last value"
+        );
+        assert_eq!(
+            format_annotation(AnnotationLevel::Warning, None, Some(1), "no file"),
+            "::warning::no file"
+        );
+    }
+
+    #[test]
+    fn test_format_annotation_escapes_values() {
+        assert_eq!(
+            format_annotation(
+                AnnotationLevel::Error,
+                Some(Path::new("posts/a,b:c.md")),
+                None,
+                "100% failed\nsecond line"
+            ),
+            "::error file=posts/a%2Cb%3Ac.md::100%25 failed%0Asecond line"
+        );
+    }
 }
@@ -0,0 +1,287 @@
+//! Interactive prompts
+//!
+//! Every question the uploader asks, such as which cover candidate to use,
+//! whether to start a run over its AI cost threshold, or whether to overwrite
+//! files from a backup, goes through a [`Prompter`]. Its [`PromptPolicy`]
+//! decides for all of them alike whether the question is asked, answered with
+//! its default (`--yes`), or turned into an error (`--no-input`). Defaults
+//! never overwrite files or spend money; those take a dedicated flag.
+
+use crate::error::{Error, Result};
+use std::io::BufRead;
+use std::sync::{Arc, Mutex};
+
+/// How questions are handled during a run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PromptPolicy {
+    /// Ask when someone can answer; otherwise every question takes its default
+    #[default]
+    Ask,
+    /// Take the default of every question without asking (`--yes`)
+    AssumeYes,
+    /// Fail wherever a question would be asked (`--no-input`)
+    NoInput,
+}
+
+impl PromptPolicy {
+    /// Returns the policy selected by the `--yes` and `--no-input` flags
+    ///
+    /// `--yes` wins, since with it no question needs an answer.
+    pub fn from_flags(assume_yes: bool, no_input: bool) -> Self {
+        if assume_yes {
+            Self::AssumeYes
+        } else if no_input {
+            Self::NoInput
+        } else {
+            Self::Ask
+        }
+    }
+}
+
+/// Answers read in place of standard input
+type Input = Arc<Mutex<Box<dyn BufRead + Send>>>;
+
+/// Asks questions under a [`PromptPolicy`]
+///
+/// Clones share their input. Questions asked concurrently, e.g. by files
+/// prepared in parallel, are asked one at a time.
+#[derive(Clone, Default)]
+pub struct Prompter {
+    policy: PromptPolicy,
+    interactive: bool,
+    input: Option<Input>,
+}
+
+impl Prompter {
+    /// Creates a prompter reading standard input
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - How questions are handled
+    /// * `interactive` - Whether someone can answer, e.g. standard input is a
+    ///   terminal or `--interactive` is set
+    pub fn new(policy: PromptPolicy, interactive: bool) -> Self {
+        Self {
+            policy,
+            interactive,
+            input: None,
+        }
+    }
+
+    /// Creates an interactive prompter that reads its answers from `input`
+    pub fn with_input(policy: PromptPolicy, input: impl BufRead + Send + 'static) -> Self {
+        Self {
+            policy,
+            interactive: true,
+            input: Some(Arc::new(Mutex::new(Box::new(input)))),
+        }
+    }
+
+    /// Returns the policy questions are handled with
+    pub fn policy(&self) -> PromptPolicy {
+        self.policy
+    }
+
+    /// Asks a yes/no question whose answer is `default` unless given
+    ///
+    /// "y"/"yes" and "n"/"no" answer it; anything else, an empty answer, the
+    /// end of input and [`PromptPolicy::AssumeYes`] take `default`, which
+    /// should be the answer that neither overwrites files nor spends money.
+    ///
+    /// # Errors
+    ///
+    /// Returns a configuration error under [`PromptPolicy::NoInput`]
+    pub async fn confirm(&self, question: &str, default: bool) -> Result<bool> {
+        if !self.should_ask(question)? {
+            return Ok(default);
+        }
+        let prompt = format!("{} [{}] ", question, if default { "Y/n" } else { "y/N" });
+        let answer = self
+            .ask(move |read| read(&prompt))
+            .await
+            .unwrap_or_default();
+        Ok(match answer.trim().to_lowercase().as_str() {
+            "y" | "yes" => true,
+            "n" | "no" => false,
+            _ => default,
+        })
+    }
+
+    /// Asks to choose one of `count` options and returns its index
+    ///
+    /// An empty answer or the end of input picks the first option, and so
+    /// does [`PromptPolicy::AssumeYes`]. Other answers outside `1..=count` are
+    /// asked again.
+    ///
+    /// # Errors
+    ///
+    /// Returns a configuration error under [`PromptPolicy::NoInput`]
+    pub async fn choose(&self, question: &str, count: usize) -> Result<usize> {
+        if !self.should_ask(question)? {
+            return Ok(0);
+        }
+        let prompt = format!("{} [1-{}] (default 1): ", question, count);
+        let chosen = self
+            .ask(move |read| loop {
+                let answer = read(&prompt)?;
+                match parse_choice(&answer, count) {
+                    Some(index) => return Some(index),
+                    None => println!("Please enter a number from 1 to {}", count),
+                }
+            })
+            .await;
+        Ok(chosen.unwrap_or(0))
+    }
+
+    /// Returns true if `question` is to be asked, false if its default applies
+    fn should_ask(&self, question: &str) -> Result<bool> {
+        match self.policy {
+            PromptPolicy::Ask => Ok(self.interactive),
+            PromptPolicy::AssumeYes => Ok(false),
+            PromptPolicy::NoInput => Err(Error::config(format!(
+                "\"{}\" needs an answer, but --no-input is set; pass --yes to take its default instead",
+                question
+            ))),
+        }
+    }
+
+    /// Runs `dialog` on a blocking thread, one dialog at a time
+    ///
+    /// The dialog gets a function that prints a prompt and reads one line of
+    /// the answer, or `None` at the end of input.
+    async fn ask<T, F>(&self, dialog: F) -> Option<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut dyn FnMut(&str) -> Option<String>) -> Option<T> + Send + 'static,
+    {
+        static TURN: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+        let _turn = TURN.lock().await;
+
+        let input = self.input.clone();
+        tokio::task::spawn_blocking(move || {
+            use std::io::Write;
+
+            let mut read = |prompt: &str| {
+                print!("{}", prompt);
+                let _ = std::io::stdout().flush();
+                let mut answer = String::new();
+                let read = match &input {
+                    Some(input) => input.lock().ok()?.read_line(&mut answer),
+                    None => std::io::stdin().read_line(&mut answer),
+                };
+                (read.unwrap_or(0) > 0).then_some(answer)
+            };
+            dialog(&mut read)
+        })
+        .await
+        .ok()
+        .flatten()
+    }
+}
+
+impl std::fmt::Debug for Prompter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Prompter")
+            .field("policy", &self.policy)
+            .field("interactive", &self.interactive)
+            .field("input", &self.input.as_ref().map_or("stdin", |_| "injected"))
+            .finish()
+    }
+}
+
+impl PartialEq for Prompter {
+    fn eq(&self, other: &Self) -> bool {
+        let same_input = match (&self.input, &other.input) {
+            (None, None) => true,
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        };
+        self.policy == other.policy && self.interactive == other.interactive && same_input
+    }
+}
+
+/// Parses an answer to [`Prompter::choose`]; an empty answer picks the first
+fn parse_choice(answer: &str, count: usize) -> Option<usize> {
+    let answer = answer.trim();
+    if answer.is_empty() {
+        return Some(0);
+    }
+    match answer.parse::<usize>() {
+        Ok(choice) if (1..=count).contains(&choice) => Some(choice - 1),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn answering(policy: PromptPolicy, answers: &str) -> Prompter {
+        Prompter::with_input(policy, Cursor::new(answers.to_string()))
+    }
+
+    #[test]
+    fn test_parse_choice() {
+        assert_eq!(parse_choice("\n", 3), Some(0));
+        assert_eq!(parse_choice(" 2 \n", 3), Some(1));
+        assert_eq!(parse_choice("3", 3), Some(2));
+        assert_eq!(parse_choice("0", 3), None);
+        assert_eq!(parse_choice("4", 3), None);
+        assert_eq!(parse_choice("second", 3), None);
+    }
+
+    #[test]
+    fn test_policy_from_flags() {
+        assert_eq!(PromptPolicy::from_flags(false, false), PromptPolicy::Ask);
+        assert_eq!(PromptPolicy::from_flags(true, false), PromptPolicy::AssumeYes);
+        assert_eq!(PromptPolicy::from_flags(false, true), PromptPolicy::NoInput);
+        assert_eq!(PromptPolicy::from_flags(true, true), PromptPolicy::AssumeYes);
+    }
+
+    #[tokio::test]
+    async fn test_confirm_under_each_policy() {
+        let ask = answering(PromptPolicy::Ask, "y\nno\nYES\n\nmaybe\n");
+        assert!(ask.confirm("Continue?", false).await.unwrap());
+        assert!(!ask.confirm("Continue?", true).await.unwrap());
+        // Clones read the same input
+        assert!(ask.clone().confirm("Continue?", false).await.unwrap());
+        // Empty and unclear answers, and the end of input, take the default
+        assert!(ask.confirm("Continue?", true).await.unwrap());
+        assert!(!ask.confirm("Continue?", false).await.unwrap());
+        assert!(ask.confirm("Continue?", true).await.unwrap());
+
+        // Nobody to ask: the default without reading
+        let unattended = Prompter::new(PromptPolicy::Ask, false);
+        assert!(!unattended.confirm("Continue?", false).await.unwrap());
+        assert!(unattended.confirm("Continue?", true).await.unwrap());
+
+        // --yes takes the default, even a "no" that keeps files as they are
+        let yes = answering(PromptPolicy::AssumeYes, "y\n");
+        assert!(!yes.confirm("Overwrite?", false).await.unwrap());
+        assert!(yes.confirm("Continue?", true).await.unwrap());
+
+        let no_input = answering(PromptPolicy::NoInput, "y\n");
+        let err = no_input.confirm("Continue?", false).await.unwrap_err();
+        assert!(err.is_config());
+        assert!(err.to_string().contains("\"Continue?\" needs an answer"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_choose_under_each_policy() {
+        let ask = answering(PromptPolicy::Ask, "5\nfirst\n3\n\n");
+        assert_eq!(ask.choose("Choose a cover", 3).await.unwrap(), 2);
+        assert_eq!(ask.choose("Choose a cover", 3).await.unwrap(), 0);
+        // End of input takes the default
+        assert_eq!(ask.choose("Choose a cover", 3).await.unwrap(), 0);
+
+        let unattended = Prompter::new(PromptPolicy::Ask, false);
+        assert_eq!(unattended.choose("Choose a cover", 3).await.unwrap(), 0);
+
+        let yes = answering(PromptPolicy::AssumeYes, "2\n");
+        assert_eq!(yes.choose("Choose a cover", 3).await.unwrap(), 0);
+
+        let no_input = answering(PromptPolicy::NoInput, "2\n");
+        assert!(no_input.choose("Choose a cover", 3).await.is_err());
+    }
+}
@@ -0,0 +1,308 @@
+//! Effective configuration and where each value comes from
+//!
+//! Settings are layered: built-in defaults, environment variables, the
+//! configuration file and command-line flags, each overriding the ones
+//! before it. [`Provenance`] records which layer set each value while the
+//! [`Config`] is built, and [`config_entries`] lists the merged result for
+//! `--show-config`, with secrets masked.
+
+use crate::http_trace::REDACTED;
+use crate::models::{AiProvider, Config, GlobalSettings, WeChatAccount};
+use crate::providers::ModelConfig;
+use colored::Colorize;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Layer a configuration value was taken from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigSource {
+    /// Built-in default
+    #[default]
+    Default,
+    /// Environment variable
+    Env,
+    /// Configuration file
+    File,
+    /// Command-line flag
+    Flag,
+}
+
+impl ConfigSource {
+    /// Returns the label shown by `--show-config`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Env => "env",
+            Self::File => "file",
+            Self::Flag => "flag",
+        }
+    }
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Sources of configuration values, keyed like the `settings` of the
+/// configuration file plus `account`, `accounts`, `groups` and `ai_provider`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Provenance {
+    sources: BTreeMap<String, ConfigSource>,
+}
+
+impl Provenance {
+    /// Records that `key` was set by `source`, replacing any earlier source
+    pub fn set(&mut self, key: &str, source: ConfigSource) {
+        self.sources.insert(key.to_string(), source);
+    }
+
+    /// Returns the source of `key`; values nobody set are defaults
+    pub fn source(&self, key: &str) -> ConfigSource {
+        self.sources.get(key).copied().unwrap_or_default()
+    }
+
+    /// Records every setting present in `settings` as coming from `source`
+    pub fn record_settings(&mut self, settings: &GlobalSettings, source: ConfigSource) {
+        if let Ok(Value::Object(values)) = serde_json::to_value(settings) {
+            for (key, value) in values {
+                if !value.is_null() {
+                    self.set(&key, source);
+                }
+            }
+        }
+    }
+}
+
+/// Output format of `--show-config`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ShowConfigFormat {
+    /// One `key = value (source)` line per value
+    #[default]
+    Text,
+    /// A JSON object mapping each key to its value and source
+    Json,
+}
+
+/// An effective configuration value and its source
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConfigEntry {
+    /// Setting name
+    #[serde(skip)]
+    pub key: &'static str,
+    /// Effective value, with secrets masked
+    pub value: Value,
+    /// Layer the value was taken from
+    pub source: ConfigSource,
+}
+
+/// Lists the effective configuration, the active account first
+pub fn config_entries(config: &Config) -> Vec<ConfigEntry> {
+    let account = &config.wechat_account;
+    let mut accounts: Vec<&WeChatAccount> = config.available_accounts.values().collect();
+    accounts.sort_by_key(|account| account.name.as_str());
+    let groups: BTreeMap<_, _> = config.account_groups.iter().collect();
+    let scene_prompts: BTreeMap<_, _> = config.scene_prompts.iter().collect();
+    let budget = config.ai_budget;
+
+    let values = [
+        ("account", json!(account.name)),
+        ("accounts", Value::Array(accounts.into_iter().map(account_value).collect())),
+        ("groups", json!(groups)),
+        ("ai_provider", ai_provider_value(config.ai_provider.as_ref())),
+        ("wechat_base_url", json!(account.wechat_base_url)),
+        ("default_cover", json!(account.default_cover)),
+        ("verbose", json!(config.verbose)),
+        ("strict_frontmatter", json!(config.strict_frontmatter)),
+        ("no_ai_text", json!(config.no_ai_text)),
+        ("stream_scene", json!(config.stream_scene)),
+        ("diagnose_content", json!(config.diagnose_content)),
+        ("reconcile", json!(config.reconcile)),
+        ("trace_http", json!(config.trace_http)),
+        ("trace_body_limit", json!(config.trace_body_limit)),
+        (
+            "pipeline",
+            json!(config.pipeline.map(|limits| {
+                json!({ "ai_jobs": limits.ai_jobs, "upload_jobs": limits.upload_jobs })
+            })),
+        ),
+        ("optimize_covers", json!(config.optimize_covers)),
+        ("embed_provenance", json!(config.embed_provenance)),
+        ("cover_format", json!(config.cover_format)),
+        ("cover_quality", json!(config.cover_quality)),
+        ("convert_covers", json!(config.convert_covers)),
+        ("write_back", json!(config.write_back)),
+        ("set_draft_status", json!(config.set_draft_status)),
+        ("state_file", json!(config.state_file_path())),
+        ("cache_dir", json!(config.cache_dir_path())),
+        ("no_cache", json!(config.no_cache)),
+        ("auto_slug", json!(config.auto_slug)),
+        ("scene_prompts", json!(scene_prompts)),
+        ("fallback_scene", json!(config.fallback_scene)),
+        ("original", json!(config.original)),
+        ("theme_fallback", json!(config.theme_fallback)),
+        ("on_material_denied", json!(config.on_material_denied)),
+        ("cover_select", json!(config.cover_select)),
+        ("default_theme", json!(config.default_theme)),
+        ("default_code_highlighter", json!(config.default_code_highlighter)),
+        // 0 means unlimited
+        ("max_ai_images_per_run", json!(Some(budget.max_images).filter(|&max| max > 0))),
+        ("max_ai_cost_per_run", json!(Some(budget.max_cost).filter(|&max| max > 0.0))),
+        ("ai_image_price", json!(config.ai_prices.image)),
+        ("ai_description_price", json!(config.ai_prices.description)),
+        ("confirm_ai_cost_above", json!(config.confirm_ai_cost_above)),
+        ("content_root", json!(config.content_root)),
+        ("cover_template", json!(config.cover_template)),
+        ("hooks", json!(config.hooks)),
+        ("strict_hooks", json!(config.strict_hooks)),
+        ("wechat_rpm", json!(config.wechat_rpm)),
+        ("max_retries", json!(config.max_retries)),
+        ("max_retry_delay", json!(config.max_retry_delay)),
+        ("upload_delay_ms", json!(config.upload_delay_ms)),
+        ("retry_max_attempts", json!(config.retry_max_attempts)),
+        ("digest_limit", json!(config.digest_limit)),
+        ("disabled_lint_rules", json!(config.disabled_lint_rules)),
+        ("anchor_links", json!(config.anchor_links)),
+        ("obsidian", json!(config.obsidian)),
+        ("obsidian_vault", json!(config.obsidian_vault)),
+        ("wiki_links", json!(config.wiki_links)),
+        ("transforms", json!(config.transforms)),
+        ("sidecar_frontmatter", json!(config.sidecar_frontmatter)),
+        ("cover_variations", json!(config.cover_variations)),
+        ("only", json!(config.only.map(|state| state.as_str()))),
+        ("follow_symlinks", json!(config.follow_symlinks)),
+        ("markdown_extensions", json!(config.markdown_extensions)),
+        ("require_cover", json!(config.require_cover)),
+        ("fail_fast", json!(config.fail_fast)),
+        ("allowed_hours", json!(config.allowed_hours.map(|hours| hours.to_string()))),
+        ("ignore_schedule", json!(config.ignore_schedule)),
+        ("backup", json!(config.backup)),
+        ("backup_keep", json!(config.backup_keep)),
+    ];
+
+    values
+        .into_iter()
+        .map(|(key, value)| ConfigEntry {
+            key,
+            value,
+            source: config.provenance.source(key),
+        })
+        .collect()
+}
+
+/// Returns the entries as a JSON object of `{"value": ..., "source": ...}` by key
+pub fn entries_to_json(entries: &[ConfigEntry]) -> Value {
+    Value::Object(
+        entries
+            .iter()
+            .map(|entry| (entry.key.to_string(), json!(entry)))
+            .collect(),
+    )
+}
+
+/// Formats the entries as `key = value (source)` lines
+pub fn entries_to_text(entries: &[ConfigEntry]) -> String {
+    let width = entries.iter().map(|entry| entry.key.len()).max().unwrap_or(0);
+    entries
+        .iter()
+        .map(|entry| {
+            let value = match &entry.value {
+                Value::Null => "(unset)".to_string(),
+                Value::String(text) => text.clone(),
+                other => other.to_string(),
+            };
+            format!(
+                "{} = {} {}",
+                format!("{:width$}", entry.key).bright_cyan(),
+                value,
+                format!("({})", entry.source).bright_black(),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Returns an account with its secret masked
+fn account_value(account: &WeChatAccount) -> Value {
+    json!({
+        "name": account.name,
+        "app_id": account.app_id,
+        "app_secret": REDACTED,
+        "description": account.description,
+        "wechat_base_url": account.wechat_base_url,
+        "default_cover": account.default_cover,
+        "tags": account.tags,
+        "owner": account.owner,
+    })
+}
+
+/// Returns the AI provider with its API key masked and the models it uses
+fn ai_provider_value(provider: Option<&AiProvider>) -> Value {
+    let (name, base_url, models) = match provider {
+        Some(AiProvider::OpenAI { base_url, .. }) => ("openai", base_url, ModelConfig::openai()),
+        Some(AiProvider::Gemini { base_url, .. }) => ("gemini", base_url, ModelConfig::gemini()),
+        None => return Value::Null,
+    };
+    json!({
+        "provider": name,
+        "api_key": REDACTED,
+        "base_url": base_url,
+        "text_model": models.text_model,
+        "image_model": models.image_model,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry<'a>(entries: &'a [ConfigEntry], key: &str) -> &'a ConfigEntry {
+        entries.iter().find(|entry| entry.key == key).unwrap()
+    }
+
+    #[test]
+    fn test_record_settings_marks_only_set_values() {
+        let settings = GlobalSettings {
+            optimize_covers: Some(true),
+            wechat_rpm: Some(5),
+            ..GlobalSettings::default()
+        };
+        let mut provenance = Provenance::default();
+        provenance.record_settings(&settings, ConfigSource::File);
+        provenance.set("wechat_rpm", ConfigSource::Flag);
+
+        assert_eq!(provenance.source("optimize_covers"), ConfigSource::File);
+        assert_eq!(provenance.source("wechat_rpm"), ConfigSource::Flag);
+        assert_eq!(provenance.source("verbose"), ConfigSource::Default);
+    }
+
+    #[test]
+    fn test_config_entries_mask_secrets() {
+        let config = Config::new(
+            "wx_app".to_string(),
+            "wx_secret".to_string(),
+            Some(AiProvider::gemini("gemini-key".to_string())),
+            false,
+        );
+        let entries = config_entries(&config);
+        let json = entries_to_json(&entries);
+
+        assert_eq!(json["account"], json!({ "value": "main", "source": "default" }));
+        assert_eq!(json["accounts"]["value"][0]["app_id"], "wx_app");
+        assert_eq!(json["accounts"]["value"][0]["app_secret"], REDACTED);
+        assert_eq!(json["ai_provider"]["value"]["api_key"], REDACTED);
+        assert_eq!(json["ai_provider"]["value"]["image_model"], "imagen-4.0-generate-001");
+        assert_eq!(json["max_ai_images_per_run"]["value"], Value::Null);
+        assert!(!json.to_string().contains("wx_secret"));
+        assert!(!json.to_string().contains("gemini-key"));
+
+        let text = entries_to_text(&entries);
+        let line = text.lines().find(|line| line.contains("digest_limit")).unwrap();
+        assert!(line.contains(" = 120 ") && line.contains("(default)"), "{}", line);
+        assert_eq!(entry(&entries, "wechat_rpm").value, json!(20));
+    }
+}
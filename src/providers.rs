@@ -3,11 +3,29 @@
 //! This module provides a unified interface for different AI providers
 //! including OpenAI, Google Gemini, and other compatible services.
 
-use crate::error::{Error, Result};
+use crate::budget::{AiBudget, AiPrices, BudgetLimits};
+use crate::cover_format::{CoverEncoding, CoverFormat};
+use crate::cover_provenance::{CoverProvenance, embed_provenance};
+use crate::error::{AiFailure, Error, Result, ResultExt};
+use crate::http_trace::HttpTracer;
+use crate::language::{ScenePrompts, detect_language};
+use crate::rate_limit::{RetryPolicy, retry_after_header, wait_before_retry};
+use crate::scene_cache::SceneCache;
 use async_trait::async_trait;
 use reqwest::Client;
 use serde_json::{Value, json};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Checks that `bytes` decode as an image
+///
+/// Returns why they do not, with their size, for the error of the caller.
+fn probe_image(bytes: &[u8]) -> std::result::Result<(), String> {
+    image::load_from_memory(bytes)
+        .map(|_| ())
+        .map_err(|e| format!("({} bytes) does not decode as an image: {}", bytes.len(), e))
+}
 
 /// Configuration for AI providers
 #[derive(Debug, Clone)]
@@ -54,11 +72,200 @@ impl ProviderConfig {
     }
 }
 
+/// Scene used when neither the AI nor the article provides anything to describe
+///
+/// A configured `fallback_scene` is tried before it.
+const DEFAULT_SCENE_DESCRIPTION: &str = "A serene landscape with rolling hills under a soft, dreamy sky filled with gentle clouds. The scene evokes a sense of peaceful contemplation and infinite possibilities.";
+
+/// Maximum number of content bytes sent with a scene description request
+const MAX_SCENE_CONTENT_BYTES: usize = 2000;
+
+/// Number of section headings used by the local scene description
+const FALLBACK_HEADING_COUNT: usize = 3;
+
+/// Retries of a generated image download that arrives truncated or does not decode
+const IMAGE_DOWNLOAD_RETRIES: u32 = 2;
+
+/// Article details used to describe the cover scene
+///
+/// The description is what gets sent to the AI text model; the title, tags
+/// and headings are only used to build a scene description locally when the
+/// AI call fails, returns nothing or is disabled with `--no-ai-text`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArticleSummary {
+    /// Article title from the frontmatter
+    pub title: Option<String>,
+    /// Article description from the frontmatter
+    pub description: String,
+    /// Tags from the frontmatter
+    pub tags: Vec<String>,
+    /// First H2 headings of the article body
+    pub headings: Vec<String>,
+    /// Language of the article, which selects the scene description prompt
+    pub lang: String,
+}
+
+impl ArticleSummary {
+    /// Builds a summary from the frontmatter fields and markdown body
+    ///
+    /// The language is detected from the title, description and body.
+    pub fn new(title: Option<&str>, description: &str, body: &str) -> Self {
+        let text = format!("{}\n{}\n{}", title.unwrap_or_default(), description, body);
+        Self {
+            title: title
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(str::to_string),
+            description: description.trim().to_string(),
+            tags: Vec::new(),
+            headings: extract_h2_headings(body, FALLBACK_HEADING_COUNT),
+            lang: detect_language(&text).to_string(),
+        }
+    }
+
+    /// Overrides the detected language, e.g. with the `lang` frontmatter field
+    pub fn with_lang(mut self, lang: Option<&str>) -> Self {
+        if let Some(lang) = lang.map(str::trim).filter(|l| !l.is_empty()) {
+            self.lang = lang.to_string();
+        }
+        self
+    }
+
+    /// Sets the tags, e.g. from the `tags` frontmatter field
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Builds a scene description without calling the AI
+    ///
+    /// Combines the title, description, tags and first section headings into
+    /// a deterministic description, e.g. `An illustration for an article
+    /// titled "Rust Async". It explains futures. Themes: rust. Key topics:
+    /// Executors, Wakers.`
+    pub fn fallback_scene_description(&self) -> String {
+        let mut parts = Vec::new();
+
+        if let Some(title) = &self.title {
+            parts.push(format!("An illustration for an article titled \"{}\".", title));
+        }
+        if !self.description.is_empty() {
+            parts.push(format!("{}.", self.description_sentence()));
+        }
+        if !self.tags.is_empty() {
+            parts.push(format!("Themes: {}.", self.tags.join(", ")));
+        }
+        if !self.headings.is_empty() {
+            parts.push(format!("Key topics: {}.", self.headings.join(", ")));
+        }
+
+        if parts.is_empty() {
+            DEFAULT_SCENE_DESCRIPTION.to_string()
+        } else {
+            parts.join(" ")
+        }
+    }
+
+    /// Fills the `{title}`, `{description}`, `{tags}` and `{headings}` placeholders of `template`
+    ///
+    /// Returns `None` if the template has placeholders and the article
+    /// provides none of them, so an empty scene is never described.
+    pub fn render_scene_template(&self, template: &str) -> Option<String> {
+        let values = [
+            ("{title}", self.title.clone().unwrap_or_default()),
+            ("{description}", self.description_sentence().to_string()),
+            ("{tags}", self.tags.join(", ")),
+            ("{headings}", self.headings.join(", ")),
+        ];
+        let mut used = values.iter().filter(|(key, _)| template.contains(key)).peekable();
+        if used.peek().is_some() && used.all(|(_, value)| value.is_empty()) {
+            return None;
+        }
+
+        let scene = values
+            .iter()
+            .fold(template.to_string(), |scene, (key, value)| scene.replace(key, value));
+        Some(scene.trim().to_string()).filter(|scene| !scene.is_empty())
+    }
+
+    /// Returns the scene description used when the AI provides none
+    ///
+    /// A configured `template` comes first, then the description built from
+    /// the article, then a generic landscape.
+    pub fn fallback_scene_with(&self, template: Option<&str>) -> String {
+        template
+            .and_then(|template| self.render_scene_template(template))
+            .unwrap_or_else(|| self.fallback_scene_description())
+    }
+
+    /// Returns the description without its final full stop
+    fn description_sentence(&self) -> &str {
+        self.description.trim_end_matches(['.', '。'])
+    }
+}
+
+impl From<&str> for ArticleSummary {
+    fn from(description: &str) -> Self {
+        Self::new(None, description, "")
+    }
+}
+
+/// Returns the filename used for a generated cover, e.g. `post_cover_<id>.png`
+pub fn generated_cover_filename(base_filename: &str, unique_id: &str, format: CoverFormat) -> String {
+    format!("{}_cover_{}.{}", base_filename, unique_id, format.extension())
+}
+
+/// Returns the file name of the cover candidate numbered `index`, counting from 1
+pub fn cover_variation_filename(base_filename: &str, index: usize, format: CoverFormat) -> String {
+    format!("{}-cover-{}.{}", base_filename, index, format.extension())
+}
+
+/// Returns the longest prefix of `text` of at most `max_bytes` bytes that ends on a character boundary
+fn truncate_at_char_boundary(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Returns up to `limit` H2 (`## `) headings, ignoring fenced code blocks
+fn extract_h2_headings(body: &str, limit: usize) -> Vec<String> {
+    let mut headings = Vec::new();
+    let mut in_fence = false;
+
+    for line in body.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+
+        if let Some(heading) = trimmed.strip_prefix("## ") {
+            let heading = heading.trim().trim_end_matches('#').trim();
+            if !heading.is_empty() {
+                headings.push(heading.to_string());
+                if headings.len() == limit {
+                    break;
+                }
+            }
+        }
+    }
+
+    headings
+}
+
 /// Trait for generating scene descriptions from content
 #[async_trait]
 pub trait SceneDescriptionGenerator {
-    /// Generates a vivid scene description from markdown content
-    async fn generate_scene_description(&self, content: &str) -> Result<String>;
+    /// Generates a vivid scene description from markdown content in language `lang`
+    async fn generate_scene_description(&self, content: &str, lang: &str) -> Result<String>;
 }
 
 /// Trait for generating images from text descriptions
@@ -67,7 +274,22 @@ pub trait ImageGenerator {
     /// Generates an image from a text prompt and returns the URL or base64 data
     async fn generate_image(&self, prompt: &str) -> Result<String>;
 
+    /// Generates `count` images from the same prompt
+    ///
+    /// The default makes one request per image, for providers that return a
+    /// single image per request.
+    async fn generate_images(&self, prompt: &str, count: u32) -> Result<Vec<String>> {
+        let mut images = Vec::new();
+        for _ in 0..count {
+            images.push(self.generate_image(prompt).await?);
+        }
+        Ok(images)
+    }
+
     /// Downloads an image from a URL and saves it to the specified path
+    ///
+    /// The image is saved in the format the extension of the path names,
+    /// otherwise in the configured cover format.
     async fn download_image(&self, url: &str, file_path: &Path) -> Result<()>;
 }
 
@@ -80,10 +302,10 @@ pub trait PromptBuilder {
 /// Trait for processing cover images
 #[async_trait]
 pub trait CoverImageProcessor {
-    /// Generates and saves a cover image for the given markdown content
+    /// Generates and saves a cover image for the given article
     async fn generate_cover_image(
         &self,
-        content: &str,
+        article: &ArticleSummary,
         file_path: &Path,
         base_filename: &str,
     ) -> Result<String>;
@@ -91,10 +313,22 @@ pub trait CoverImageProcessor {
     /// Generates and saves a cover image to a specific path
     async fn generate_cover_image_to_path(
         &self,
-        content: &str,
+        article: &ArticleSummary,
         markdown_file_path: &Path,
         target_cover_path: &Path,
     ) -> Result<()>;
+
+    /// Generates `count` candidate covers from one scene description and saves
+    /// them in `dir` as `<base_filename>-cover-<n>.<ext>`
+    ///
+    /// Returns the paths of the saved candidates in order.
+    async fn generate_cover_variations(
+        &self,
+        article: &ArticleSummary,
+        dir: &Path,
+        base_filename: &str,
+        count: u32,
+    ) -> Result<Vec<PathBuf>>;
 }
 
 /// Model configurations for different providers
@@ -149,12 +383,51 @@ impl ModelConfig {
     }
 }
 
+/// Article content sent by the scene description check of `--check-ai`
+const CHECK_SCENE_CONTENT: &str = "A short note about watering plants on a sunny balcony.";
+
+/// Prompt sent by the image generation check of `--check-ai`
+const CHECK_IMAGE_PROMPT: &str = "A single green leaf on a plain white background";
+
+/// Result of one request made by [`UniversalAIClient::check_connectivity`]
+#[derive(Debug, Clone)]
+pub struct AiCheck {
+    /// What was checked, e.g. `scene description`
+    pub name: &'static str,
+    /// Model the request was made with
+    pub model: String,
+    /// Time until the response was received
+    pub latency: Duration,
+    /// Summary of the response, or the error with the API key redacted
+    pub outcome: std::result::Result<String, String>,
+    /// Classification of a failed request, e.g. a rejected API key or a timeout
+    pub failure: Option<AiFailure>,
+}
+
+impl AiCheck {
+    /// Returns true if the request succeeded
+    pub fn is_ok(&self) -> bool {
+        self.outcome.is_ok()
+    }
+}
+
 /// Universal AI client that works with multiple providers
 #[derive(Clone, Debug)]
 pub struct UniversalAIClient {
     config: ProviderConfig,
     model_config: ModelConfig,
     http_client: Client,
+    ai_scene_text: bool,
+    stream_scene: bool,
+    scene_prompts: ScenePrompts,
+    fallback_scene: Option<String>,
+    scene_cache: Option<SceneCache>,
+    cover_encoding: CoverEncoding,
+    embed_provenance: bool,
+    tracer: HttpTracer,
+    prices: AiPrices,
+    budget: Arc<AiBudget>,
+    retry: RetryPolicy,
 }
 
 impl UniversalAIClient {
@@ -169,6 +442,17 @@ impl UniversalAIClient {
             config,
             model_config,
             http_client: Client::new(),
+            ai_scene_text: true,
+            stream_scene: false,
+            scene_prompts: ScenePrompts::default(),
+            fallback_scene: None,
+            scene_cache: None,
+            cover_encoding: CoverEncoding::default(),
+            embed_provenance: false,
+            tracer: HttpTracer::disabled(),
+            prices: AiPrices::default(),
+            budget: Arc::new(AiBudget::unlimited()),
+            retry: RetryPolicy::NONE,
         }
     }
 
@@ -187,23 +471,328 @@ impl UniversalAIClient {
             config,
             model_config,
             http_client,
+            ai_scene_text: true,
+            stream_scene: false,
+            scene_prompts: ScenePrompts::default(),
+            fallback_scene: None,
+            scene_cache: None,
+            cover_encoding: CoverEncoding::default(),
+            embed_provenance: false,
+            tracer: HttpTracer::disabled(),
+            prices: AiPrices::default(),
+            budget: Arc::new(AiBudget::unlimited()),
+            retry: RetryPolicy::NONE,
         }
     }
 
-    /// Makes a POST request to the provider API
-    async fn post_request(&self, endpoint: &str, body: Value) -> Result<Value> {
-        let url = match &self.config {
+    /// Enables or disables the AI scene description request
+    ///
+    /// When disabled, cover prompts are built locally from the article title,
+    /// description and headings, so only the image generation call is made.
+    pub fn with_ai_scene_text(mut self, enabled: bool) -> Self {
+        self.ai_scene_text = enabled;
+        self
+    }
+
+    /// Streams scene descriptions, printing their text to stderr as it arrives
+    ///
+    /// Providers or gateways that cannot stream fall back to a regular request.
+    pub fn with_scene_streaming(mut self, enabled: bool) -> Self {
+        self.stream_scene = enabled;
+        self
+    }
+
+    /// Sets the per-language prompts used to request scene descriptions
+    pub fn with_scene_prompts(mut self, prompts: ScenePrompts) -> Self {
+        self.scene_prompts = prompts;
+        self
+    }
+
+    /// Sets the template of the scene description used when the AI returns none
+    ///
+    /// See [`ArticleSummary::fallback_scene_with`].
+    pub fn with_fallback_scene(mut self, template: Option<String>) -> Self {
+        self.fallback_scene = template;
+        self
+    }
+
+    /// Sets the cache that AI scene descriptions are reused from, if any
+    pub fn with_scene_cache(mut self, cache: Option<SceneCache>) -> Self {
+        self.scene_cache = cache;
+        self
+    }
+
+    /// Sets the format and quality downloaded covers are saved with
+    pub fn with_cover_encoding(mut self, encoding: CoverEncoding) -> Self {
+        self.cover_encoding = encoding;
+        self
+    }
+
+    /// Embeds the provider, model, prompt and time in generated PNG covers
+    ///
+    /// See [`crate::cover_provenance`].
+    pub fn with_embed_provenance(mut self, enabled: bool) -> Self {
+        self.embed_provenance = enabled;
+        self
+    }
+
+    /// Returns the scene description used for `article` when the AI provides none
+    fn fallback_scene_for(&self, article: &ArticleSummary) -> String {
+        article.fallback_scene_with(self.fallback_scene.as_deref())
+    }
+
+    /// Sets the tracer that logs this client's HTTP traffic
+    pub fn with_http_tracer(mut self, tracer: HttpTracer) -> Self {
+        self.tracer = tracer;
+        self
+    }
+
+    /// Sets the prices the cost of images and scene descriptions is estimated with
+    ///
+    /// Call it before [`with_budget`](Self::with_budget), which converts a
+    /// cost limit with the image price.
+    pub fn with_prices(mut self, prices: AiPrices) -> Self {
+        self.prices = prices;
+        self
+    }
+
+    /// Limits the images generated through this client and its clones
+    ///
+    /// A cost limit is converted to an image count with the price of the
+    /// configured image model.
+    pub fn with_budget(mut self, limits: BudgetLimits) -> Self {
+        let price = self.prices.image_price(&self.model_config.image_model);
+        self.budget = Arc::new(AiBudget::new(limits, price));
+        self
+    }
+
+    /// Sets how requests the provider answers with 429 are retried
+    ///
+    /// Without a policy such requests fail at once.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Returns the prices the AI cost of a run is estimated with
+    pub fn prices(&self) -> AiPrices {
+        self.prices
+    }
+
+    /// Returns the model covers are generated with
+    pub fn image_model(&self) -> &str {
+        &self.model_config.image_model
+    }
+
+    /// Returns true if scene descriptions are requested from the provider
+    pub fn uses_ai_scene_text(&self) -> bool {
+        self.ai_scene_text
+    }
+
+    /// Returns the name of the provider, e.g. `OpenAI`
+    pub fn provider_name(&self) -> &'static str {
+        self.config.provider_name()
+    }
+
+    /// Returns the image generation budget of the run
+    pub fn budget(&self) -> &AiBudget {
+        &self.budget
+    }
+
+    /// Returns a copy of this client using other models, e.g. for one article
+    ///
+    /// `provider` names the provider the models belong to, `openai` or
+    /// `gemini`. The credentials are this client's, so it must name the same
+    /// provider. The copy shares the image budget of the run.
+    ///
+    /// # Errors
+    ///
+    /// Returns a configuration error if `provider` is unknown or is not the
+    /// provider of this client
+    pub fn with_model_overrides(
+        &self,
+        provider: Option<&str>,
+        text_model: Option<&str>,
+        image_model: Option<&str>,
+    ) -> Result<Self> {
+        if let Some(provider) = provider.map(str::trim) {
+            let requested = match provider.to_lowercase().as_str() {
+                "openai" => "OpenAI",
+                "gemini" => "Gemini",
+                _ => {
+                    return Err(Error::config(format!(
+                        "unknown ai_provider '{}'; use openai or gemini",
+                        provider
+                    )));
+                }
+            };
+            if requested != self.provider_name() {
+                return Err(Error::config(format!(
+                    "ai_provider '{}' has no credentials configured; the configured AI provider is {}",
+                    provider,
+                    self.provider_name()
+                )));
+            }
+        }
+
+        let mut client = self.clone();
+        if let Some(model) = text_model {
+            client.model_config.text_model = model.to_string();
+        }
+        if let Some(model) = image_model {
+            client.model_config.image_model = model.to_string();
+        }
+        Ok(client)
+    }
+
+    /// Returns the API key the client authenticates with
+    pub fn api_key(&self) -> &str {
+        self.config.api_key()
+    }
+
+    /// Makes a tiny scene description request and, with `include_image`, a
+    /// single image request, timing each
+    ///
+    /// The requests bypass the scene cache, the fallback scene and the image
+    /// budget, so a failure is reported instead of being papered over. The
+    /// generated image is not downloaded. Error messages have the API key
+    /// redacted, since Gemini sends it in the request URL.
+    pub async fn check_connectivity(&self, include_image: bool) -> Vec<AiCheck> {
+        let mut checks = Vec::new();
+
+        let start = Instant::now();
+        let result = self.generate_scene_description(CHECK_SCENE_CONTENT, "en").await;
+        checks.push(self.ai_check(
+            "scene description",
+            &self.model_config.text_model,
+            start.elapsed(),
+            result.map(|scene| format!("\"{}\"", truncate_at_char_boundary(&scene, 60))),
+        ));
+
+        if include_image {
+            let start = Instant::now();
+            let result = self.generate_image(CHECK_IMAGE_PROMPT).await;
+            checks.push(self.ai_check(
+                "image generation",
+                &self.model_config.image_model,
+                start.elapsed(),
+                result.map(|image| {
+                    if image.starts_with("base64:") {
+                        "returned image data".to_string()
+                    } else {
+                        "returned an image URL".to_string()
+                    }
+                }),
+            ));
+        }
+
+        checks
+    }
+
+    /// Wraps the result of a connectivity check request, redacting the API key from errors
+    fn ai_check(
+        &self,
+        name: &'static str,
+        model: &str,
+        latency: Duration,
+        result: Result<String>,
+    ) -> AiCheck {
+        let failure = result.as_ref().err().and_then(Error::ai_failure);
+        let api_key = self.config.api_key();
+        let outcome = result.map_err(|e| {
+            let message = e.display_chain();
+            if api_key.is_empty() {
+                message
+            } else {
+                message.replace(api_key, crate::http_trace::REDACTED)
+            }
+        });
+        AiCheck {
+            name,
+            model: model.to_string(),
+            latency,
+            outcome,
+            failure,
+        }
+    }
+
+    /// Returns the scene description for the article's cover
+    ///
+    /// Cover generation is best-effort, so a failed AI text request falls back
+    /// to the local description instead of aborting. A rejected API key fails
+    /// fast, since the image request would be rejected the same way. AI
+    /// descriptions are reused from the scene cache, if one is set; fallback
+    /// descriptions are not cached.
+    async fn scene_description_for(&self, article: &ArticleSummary) -> Result<String> {
+        use crate::output::{FORMATTER, OutputFormatter};
+        use tracing::{info, warn};
+
+        if !self.ai_scene_text {
+            let description = self.fallback_scene_for(article);
+            info!("Using local scene description: {}", description);
+            return Ok(description);
+        }
+
+        // The key covers exactly what the request would send
+        let cache_key = self.scene_cache.as_ref().map(|_| {
+            SceneCache::key(
+                truncate_at_char_boundary(&article.description, MAX_SCENE_CONTENT_BYTES),
+                &article.lang,
+                self.scene_prompts.for_language(&article.lang),
+                &self.model_config.text_model,
+            )
+        });
+        if let (Some(cache), Some(key)) = (&self.scene_cache, &cache_key)
+            && let Some(description) = cache.get(key).await
+        {
+            info!("Using cached scene description: {}", description);
+            return Ok(description);
+        }
+
+        match self
+            .generate_scene_description(&article.description, &article.lang)
+            .await {
+            Ok(desc) => {
+                info!("Generated scene description: {}", desc);
+                if let (Some(cache), Some(key)) = (&self.scene_cache, &cache_key)
+                    && let Err(e) = cache.put(key, &desc).await
+                {
+                    warn!("Failed to cache scene description: {}", e);
+                }
+                Ok(desc)
+            }
+            Err(e) if matches!(
+                e.ai_failure(),
+                Some(AiFailure::InvalidApiKey | AiFailure::PermissionDenied)
+            ) => {
+                FORMATTER.print_error(&format!("Failed to generate scene description: {}", e));
+                Err(e)
+            }
+            Err(e) => {
+                let description = self.fallback_scene_for(article);
+                FORMATTER.print_warning(&format!(
+                    "Failed to generate scene description: {}; using the fallback scene instead",
+                    e
+                ));
+                info!("Fallback scene description: {}", description);
+                Ok(description)
+            }
+        }
+    }
+
+    /// Returns the URL of `endpoint`, which for Gemini is the complete model path
+    fn request_url(&self, endpoint: &str) -> String {
+        match &self.config {
             ProviderConfig::Gemini { .. } => {
-                // For Gemini, endpoint is the complete model path
                 format!("{}{}?key={}", self.config.base_url(), endpoint, self.config.api_key())
             }
             _ => format!("{}/{}", self.config.base_url(), endpoint)
-        };
-        
-        let mut request = self
-            .http_client
-            .post(&url)
-            .header("Content-Type", "application/json");
+        }
+    }
+
+    /// Sends `body` to `url` with the provider's headers, tracing the request
+    async fn send_request(&self, url: &str, body: &Value) -> Result<reqwest::Response> {
+        let mut headers = vec![("Content-Type", "application/json".to_string())];
 
         // Add provider-specific headers
         match &self.config {
@@ -211,52 +800,103 @@ impl UniversalAIClient {
                 // Gemini uses API key in URL, no Authorization header needed
             }
             ProviderConfig::OpenAI { .. } => {
-                request = request.header("Authorization", format!("Bearer {}", self.config.api_key()));
+                headers.push(("Authorization", format!("Bearer {}", self.config.api_key())));
             }
         }
 
-        let response = request.json(&body).send().await?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            
-            return Err(Error::openai(format!(
-                "{} API request failed with status {}: {}",
+        if self.tracer.is_enabled() {
+            let traced_headers: Vec<(&str, &str)> =
+                headers.iter().map(|(name, value)| (*name, value.as_str())).collect();
+            self.tracer.request(
                 self.config.provider_name(),
-                status,
-                error_text
-            )));
+                "POST",
+                url,
+                &traced_headers,
+                Some(&body.to_string()),
+            );
+        }
+
+        let mut request = self.http_client.post(url);
+        for (name, value) in &headers {
+            request = request.header(*name, value);
         }
+        Ok(request.json(body).send().await?)
+    }
 
-        let response_json: Value = response.json().await?;
+    /// Makes a POST request to the provider API
+    ///
+    /// A 429 answer is retried under the retry policy of the client, waiting
+    /// as long as its `Retry-After` header asks for.
+    async fn post_request(&self, endpoint: &str, body: Value) -> Result<Value> {
+        let mut retries = 0;
+        loop {
+            match self.post_request_once(endpoint, &body).await {
+                Err(e)
+                    if e.ai_failure() == Some(AiFailure::RateLimited)
+                        && retries < self.retry.max_retries =>
+                {
+                    let provider = self.config.provider_name();
+                    let wait = wait_before_retry(&self.retry, retries, e.retry_after(), provider).await;
+                    retries += 1;
+                    tracing::info!(
+                        "{} rate limit reached; retried after {:.1}s ({}/{})",
+                        provider,
+                        wait.as_secs_f64(),
+                        retries,
+                        self.retry.max_retries
+                    );
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Makes a single POST request to the provider API
+    async fn post_request_once(&self, endpoint: &str, body: &Value) -> Result<Value> {
+        let url = self.request_url(endpoint);
+        let provider = self.config.provider_name();
+        let response = self.send_request(&url, body).await?;
+        let status = response.status();
+        let retry_after = retry_after_header(response.headers());
+        let response_text = response.text().await;
+        if let Ok(text) = &response_text {
+            self.tracer.response(provider, &url, status.as_u16(), text);
+        }
+
+        if !status.is_success() {
+            let error_text = response_text.unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(Error::ai_request(provider, status.as_u16(), error_text)
+                .with_retry_after(retry_after));
+        }
+
+        let response_json: Value = serde_json::from_str(&response_text?)?;
         Ok(response_json)
     }
-}
 
-#[async_trait]
-impl SceneDescriptionGenerator for UniversalAIClient {
-    async fn generate_scene_description(&self, content: &str) -> Result<String> {
-        let (request_body, endpoint) = match &self.config {
+    /// Builds the scene description request for `content`, returning its
+    /// body and endpoint
+    fn scene_request(&self, content: &str, lang: &str, stream: bool) -> (Value, String) {
+        let prompt = self.scene_prompts.for_language(lang);
+        let content = truncate_at_char_boundary(content, MAX_SCENE_CONTENT_BYTES);
+        match &self.config {
             ProviderConfig::OpenAI { .. } => {
-                let body = json!({
+                let mut body = json!({
                     "model": self.model_config.text_model,
                     "messages": [
                         {
                             "role": "system",
-                            "content": "Generate a 2-sentence visual scene description in English for a cover image based on the article content."
+                            "content": prompt
                         },
                         {
                             "role": "user",
-                            "content": format!("Article content:\n\n{}\n\nScene description:",
-                                if content.len() > 2000 { &content[..2000] } else { content })
+                            "content": format!("Article content:\n\n{}\n\nScene description:", content)
                         }
                     ],
                     "temperature": self.model_config.temperature
                 });
+                if stream {
+                    body["stream"] = json!(true);
+                }
                 (body, "chat/completions".to_string())
             }
             ProviderConfig::Gemini { .. } => {
@@ -265,8 +905,7 @@ impl SceneDescriptionGenerator for UniversalAIClient {
                         {
                             "parts": [
                                 {
-                                    "text": format!("Generate a 2-sentence visual scene description in English for a cover image based on this article content:\n\n{}\n\nScene description:",
-                                        if content.len() > 2000 { &content[..2000] } else { content })
+                                    "text": format!("{}\n\nArticle content:\n\n{}\n\nScene description:", prompt, content)
                                 }
                             ]
                         }
@@ -275,31 +914,324 @@ impl SceneDescriptionGenerator for UniversalAIClient {
                         "temperature": self.model_config.temperature
                     }
                 });
-                (body, format!("/{}:generateContent", self.model_config.text_model))
+                let method = if stream { "streamGenerateContent" } else { "generateContent" };
+                (body, format!("/{}:{}", self.model_config.text_model, method))
+            }
+        }
+    }
+
+    /// Returns the text of a scene description response or streamed chunk
+    fn scene_text<'a>(&self, response: &'a Value) -> &'a str {
+        match &self.config {
+            ProviderConfig::OpenAI { .. } => {
+                let choice = &response["choices"][0];
+                choice["message"]["content"]
+                    .as_str()
+                    .or_else(|| choice["delta"]["content"].as_str())
+                    .unwrap_or("")
+            }
+            ProviderConfig::Gemini { .. } => {
+                response["candidates"][0]["content"]["parts"][0]["text"]
+                    .as_str()
+                    .unwrap_or("")
+            }
+        }
+    }
+
+    /// Downloads or decodes a generated image and saves it to `file_path`
+    ///
+    /// The image is checked to decode before anything is written, and a
+    /// download that arrives truncated or corrupt is retried. With provenance
+    /// embedding enabled, a PNG generated from `prompt` is saved with the
+    /// provider, model and prompt in its text chunks.
+    async fn save_image(&self, url: &str, file_path: &Path, prompt: Option<&str>) -> Result<()> {
+        use base64::Engine;
+        
+        let image_bytes = if let Some(base64_str) = url.strip_prefix("base64:") {
+            // Decode base64 data
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(base64_str)
+                .openai_context("Failed to decode base64 image")?;
+            // The same payload would fail again, so it is not retried
+            probe_image(&bytes).map_err(|reason| {
+                Error::cover_image(file_path, format!("generated image {}", reason))
+            })?;
+            bytes
+        } else {
+            self.fetch_image(url, file_path).await?
+        };
+
+        // Providers return PNG, so only other formats are transcoded
+        let encoding = self.cover_encoding;
+        let image_bytes = if encoding.format_for(file_path) == CoverFormat::Png {
+            match prompt.filter(|_| self.embed_provenance) {
+                Some(prompt) => {
+                    let provenance = CoverProvenance {
+                        provider: self.config.provider_name().to_string(),
+                        model: self.model_config.image_model.clone(),
+                        prompt: prompt.to_string(),
+                        created: chrono::Utc::now(),
+                    };
+                    // Provenance is optional, so a JPEG or WebP reply is saved as it is
+                    match embed_provenance(&image_bytes, &provenance, file_path) {
+                        Ok(tagged) => tagged,
+                        Err(e) => {
+                            tracing::warn!("{}; {} is saved without it", e, file_path.display());
+                            image_bytes
+                        }
+                    }
+                }
+                None => image_bytes,
+            }
+        } else {
+            if prompt.is_some() && self.embed_provenance {
+                tracing::warn!(
+                    "Provenance is only embedded in PNG covers; {} is saved without it",
+                    file_path.display()
+                );
+            }
+            let path = file_path.to_path_buf();
+            tokio::task::spawn_blocking(move || encoding.encode(image_bytes, &path))
+                .await
+                .context("cover encoding task failed")??
+        };
+
+        // Ensure the directory exists
+        if let Some(parent) = file_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        if let Err(e) = tokio::fs::write(file_path, image_bytes).await {
+            // A partly written cover would be uploaded as a corrupt image
+            let _ = tokio::fs::remove_file(file_path).await;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    /// Downloads a generated image, retrying downloads that arrive truncated
+    /// or do not decode as an image
+    ///
+    /// `file_path` is where the image will be saved, named in the error.
+    async fn fetch_image(&self, url: &str, file_path: &Path) -> Result<Vec<u8>> {
+        let mut retries = 0;
+        loop {
+            match self.fetch_image_once(url).await? {
+                Ok(bytes) => return Ok(bytes),
+                Err(reason) if retries < IMAGE_DOWNLOAD_RETRIES => {
+                    retries += 1;
+                    tracing::warn!(
+                        "Downloaded image for {} {}; retrying ({}/{})",
+                        file_path.display(),
+                        reason,
+                        retries,
+                        IMAGE_DOWNLOAD_RETRIES
+                    );
+                }
+                Err(reason) => {
+                    return Err(Error::cover_image(
+                        file_path,
+                        format!("downloaded image {} after {} attempts", reason, retries + 1),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Downloads a generated image once
+    ///
+    /// Returns the bytes, or why they are not a complete image: fewer bytes
+    /// than the `Content-Length` announced, or bytes that do not decode.
+    async fn fetch_image_once(&self, url: &str) -> Result<std::result::Result<Vec<u8>, String>> {
+        let provider = self.config.provider_name();
+        self.tracer.request(provider, "GET", url, &[], None);
+        let response = self.http_client.get(url).send().await?;
+        let status = response.status();
+
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            self.tracer.response(provider, url, status.as_u16(), &error_text);
+            return Err(Error::openai(format!(
+                "Failed to download image: HTTP {}",
+                status
+            )));
+        }
+
+        let expected = response.content_length();
+        let bytes = match response.bytes().await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                let announced = expected.map(|n| format!(" of {} bytes", n)).unwrap_or_default();
+                return Ok(Err(format!("was cut off{}: {}", announced, e)));
+            }
+        };
+        self.tracer
+            .binary_response(provider, url, status.as_u16(), bytes.len());
+
+        if let Some(expected) = expected
+            && bytes.len() as u64 != expected
+        {
+            return Ok(Err(format!(
+                "is truncated: received {} of {} bytes",
+                bytes.len(),
+                expected
+            )));
+        }
+        Ok(probe_image(&bytes).map(|()| bytes.to_vec()))
+    }
+
+    /// Streams a scene description request, printing the text as it arrives
+    ///
+    /// A successful response that is not an event stream, e.g. from a
+    /// gateway that ignores the streaming request, is read as a whole.
+    async fn stream_scene_description(&self, endpoint: &str, body: Value) -> Result<String> {
+        let mut url = self.request_url(endpoint);
+        if let ProviderConfig::Gemini { .. } = &self.config {
+            url.push_str("&alt=sse");
+        }
+        let provider = self.config.provider_name();
+        let mut response = self.send_request(&url, &body).await?;
+        let status = response.status();
+        let is_event_stream = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+
+        if !status.is_success() || !is_event_stream {
+            let text = response.text().await?;
+            self.tracer.response(provider, &url, status.as_u16(), &text);
+            if !status.is_success() {
+                return Err(Error::ai_request(provider, status.as_u16(), text));
+            }
+            let response_json: Value = serde_json::from_str(&text)?;
+            // Gemini answers a non-SSE streaming request with an array of chunks
+            let description: String = match &response_json {
+                Value::Array(chunks) => chunks.iter().map(|chunk| self.scene_text(chunk)).collect(),
+                response => self.scene_text(response).to_string(),
+            };
+            return Ok(description.trim().to_string());
+        }
+
+        let mut events = SseEvents::default();
+        let mut raw = Vec::new();
+        let mut description = String::new();
+        let streamed: Result<()> = async {
+            while let Some(chunk) = response.chunk().await? {
+                raw.extend_from_slice(&chunk);
+                for event in events.push(&chunk) {
+                    self.push_scene_event(&event, &mut description)?;
+                }
+            }
+            for event in events.finish() {
+                self.push_scene_event(&event, &mut description)?;
+            }
+            Ok(())
+        }
+        .await;
+        if !description.is_empty() {
+            eprintln!();
+        }
+        self.tracer
+            .response(provider, &url, status.as_u16(), &String::from_utf8_lossy(&raw));
+        streamed?;
+
+        Ok(description.trim().to_string())
+    }
+
+    /// Appends the text of a streamed event to `description` and prints it
+    fn push_scene_event(&self, event: &str, description: &mut String) -> Result<()> {
+        use std::io::Write;
+
+        // OpenAI ends the stream with a sentinel instead of JSON
+        if event == "[DONE]" {
+            return Ok(());
+        }
+        let event: Value = serde_json::from_str(event)?;
+        let text = self.scene_text(&event);
+        let mut stderr = std::io::stderr();
+        let _ = write!(stderr, "{}", text);
+        let _ = stderr.flush();
+        description.push_str(text);
+        Ok(())
+    }
+}
+
+/// Splits a server-sent event stream into the data of its events
+#[derive(Debug, Default)]
+struct SseEvents {
+    buffer: Vec<u8>,
+    data: Option<String>,
+}
+
+impl SseEvents {
+    /// Adds `chunk` to the stream, returning the data of the events it completes
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        // Lines are split as bytes so a character split across chunks stays intact
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if line.is_empty() {
+                events.extend(self.data.take());
+            } else if let Some(value) = line.strip_prefix("data:") {
+                let value = value.strip_prefix(' ').unwrap_or(value);
+                match &mut self.data {
+                    Some(data) => {
+                        data.push('\n');
+                        data.push_str(value);
+                    }
+                    None => self.data = Some(value.to_string()),
+                }
             }
-        };
+        }
+        events
+    }
 
-        let response_json = self.post_request(&endpoint, request_body).await?;
+    /// Ends the stream, returning the data of the events it leaves unterminated
+    fn finish(mut self) -> Vec<String> {
+        // A blank line terminates both a last line without a newline and its event
+        let mut events = self.push(b"\n\n");
+        events.extend(self.data.take());
+        events
+    }
+}
 
-        let mut scene_description = match &self.config {
-            ProviderConfig::OpenAI { .. } => {
-                response_json["choices"][0]["message"]["content"]
-                    .as_str()
-                    .unwrap_or("")
-                    .trim()
-                    .to_string()
+#[async_trait]
+impl SceneDescriptionGenerator for UniversalAIClient {
+    async fn generate_scene_description(&self, content: &str, lang: &str) -> Result<String> {
+        let mut streamed = None;
+        if self.stream_scene {
+            let (request_body, endpoint) = self.scene_request(content, lang, true);
+            match self.stream_scene_description(&endpoint, request_body).await {
+                Ok(description) => streamed = Some(description),
+                Err(e) if matches!(
+                    e.ai_failure(),
+                    Some(AiFailure::InvalidApiKey | AiFailure::PermissionDenied)
+                ) => return Err(e),
+                Err(e) => tracing::warn!(
+                    "Streaming the scene description failed: {}; retrying without streaming",
+                    e
+                ),
             }
-            ProviderConfig::Gemini { .. } => {
-                response_json["candidates"][0]["content"]["parts"][0]["text"]
-                    .as_str()
-                    .unwrap_or("")
-                    .trim()
-                    .to_string()
+        }
+
+        let scene_description = match streamed {
+            Some(description) => description,
+            None => {
+                let (request_body, endpoint) = self.scene_request(content, lang, false);
+                let response_json = self.post_request(&endpoint, request_body).await?;
+                self.scene_text(&response_json).trim().to_string()
             }
         };
 
         if scene_description.is_empty() {
-            scene_description = "A serene landscape with rolling hills under a soft, dreamy sky filled with gentle clouds. The scene evokes a sense of peaceful contemplation and infinite possibilities.".to_string();
+            return Err(Error::openai(format!(
+                "{} returned an empty scene description",
+                self.config.provider_name()
+            )));
         }
 
         Ok(scene_description)
@@ -377,36 +1309,49 @@ impl ImageGenerator for UniversalAIClient {
         }
     }
 
-    async fn download_image(&self, url: &str, file_path: &Path) -> Result<()> {
-        use base64::Engine;
-        
-        let image_bytes = if let Some(base64_str) = url.strip_prefix("base64:") {
-            // Decode base64 data
-            base64::engine::general_purpose::STANDARD
-                .decode(base64_str)
-                .map_err(|e| Error::openai(format!("Failed to decode base64 image: {}", e)))?
-        } else {
-            // Download from URL
-            let response = self.http_client.get(url).send().await?;
-
-            if !response.status().is_success() {
-                return Err(Error::openai(format!(
-                    "Failed to download image: HTTP {}",
-                    response.status()
-                )));
+    async fn generate_images(&self, prompt: &str, count: u32) -> Result<Vec<String>> {
+        // dall-e-3 only accepts n = 1, and Imagen is asked for one image here
+        let batched = matches!(self.config, ProviderConfig::OpenAI { .. })
+            && self.model_config.image_model != "dall-e-3";
+        if count <= 1 || !batched {
+            let mut images = Vec::new();
+            for _ in 0..count {
+                images.push(self.generate_image(prompt).await?);
             }
+            return Ok(images);
+        }
 
-            let bytes = response.bytes().await?;
-            bytes.to_vec()
-        };
+        let request_body = json!({
+            "model": self.model_config.image_model,
+            "prompt": prompt,
+            "size": self.model_config.image_size,
+            "quality": self.model_config.image_quality,
+            "n": count
+        });
+        let response_json = self.post_request("images/generations", request_body).await?;
 
-        // Ensure the directory exists
-        if let Some(parent) = file_path.parent() {
-            tokio::fs::create_dir_all(parent).await?;
+        let images: Vec<String> = response_json["data"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|image| {
+                image["url"]
+                    .as_str()
+                    .map(str::to_string)
+                    .or_else(|| image["b64_json"].as_str().map(|b64| format!("base64:{}", b64)))
+            })
+            .collect();
+        if images.is_empty() {
+            return Err(Error::openai(format!(
+                "Failed to extract image data from {} response",
+                self.config.provider_name()
+            )));
         }
+        Ok(images)
+    }
 
-        tokio::fs::write(file_path, image_bytes).await?;
-        Ok(())
+    async fn download_image(&self, url: &str, file_path: &Path) -> Result<()> {
+        self.save_image(url, file_path, None).await
     }
 }
 
@@ -423,31 +1368,22 @@ impl PromptBuilder for UniversalAIClient {
 impl CoverImageProcessor for UniversalAIClient {
     async fn generate_cover_image(
         &self,
-        content: &str,
+        article: &ArticleSummary,
         file_path: &Path,
         base_filename: &str,
     ) -> Result<String> {
         use crate::output::{FORMATTER, OutputFormatter, FilePathFormatter};
         use tracing::info;
 
-        // Generate scene description from content
-        let scene_description = match self.generate_scene_description(content).await {
-            Ok(desc) => {
-                info!("Generated scene description: {}", desc);
-                desc
-            }
-            Err(e) => {
-                FORMATTER.print_error(&format!("Failed to generate scene description: {}", e));
-                return Err(e);
-            }
-        };
+        // Describe the scene, falling back to a local description if the AI call fails
+        let scene_description = self.scene_description_for(article).await?;
 
         // Create DALL-E prompt
         let dalle_prompt = self.create_dalle_prompt(&scene_description);
         info!("DALL-E prompt: {}", dalle_prompt);
 
         // Show prompt in console for user visibility
-        println!("{}", FORMATTER.format_image_prompt(&dalle_prompt));
+        FORMATTER.print_detail(&FORMATTER.format_image_prompt(&dalle_prompt));
 
         // Generate image
         let image_url = match self.generate_image(&dalle_prompt).await {
@@ -462,10 +1398,10 @@ impl CoverImageProcessor for UniversalAIClient {
         };
 
         // Create filename for the cover image
-        let cover_filename = format!(
-            "{}_cover_{}.png",
+        let cover_filename = generated_cover_filename(
             base_filename,
-            uuid::Uuid::new_v4().simple()
+            &uuid::Uuid::new_v4().simple().to_string(),
+            self.cover_encoding.format,
         );
         let cover_path = file_path
             .parent()
@@ -473,40 +1409,31 @@ impl CoverImageProcessor for UniversalAIClient {
             .join(&cover_filename);
 
         // Download and save the image
-        self.download_image(&image_url, &cover_path).await?;
+        self.save_image(&image_url, &cover_path, Some(&dalle_prompt)).await?;
 
         Ok(cover_filename)
     }
 
     async fn generate_cover_image_to_path(
         &self,
-        content: &str,
+        article: &ArticleSummary,
         _markdown_file_path: &Path,
         target_cover_path: &Path,
     ) -> Result<()> {
         use crate::output::{FORMATTER, OutputFormatter, FilePathFormatter};
         use tracing::info;
 
-        println!("{}", FORMATTER.format_target_path(target_cover_path));
+        FORMATTER.print_detail(&FORMATTER.format_target_path(target_cover_path));
 
-        // Generate scene description from content
-        let scene_description = match self.generate_scene_description(content).await {
-            Ok(desc) => {
-                info!("Generated scene description: {}", desc);
-                desc
-            }
-            Err(e) => {
-                FORMATTER.print_error(&format!("Failed to generate scene description: {}", e));
-                return Err(e);
-            }
-        };
+        // Describe the scene, falling back to a local description if the AI call fails
+        let scene_description = self.scene_description_for(article).await?;
 
         // Create DALL-E prompt
         let dalle_prompt = self.create_dalle_prompt(&scene_description);
         info!("DALL-E prompt: {}", dalle_prompt);
 
         // Show prompt in console for user visibility
-        println!("{}", FORMATTER.format_image_prompt(&dalle_prompt));
+        FORMATTER.print_detail(&FORMATTER.format_image_prompt(&dalle_prompt));
 
         // Generate image
         let image_url = match self.generate_image(&dalle_prompt).await {
@@ -521,9 +1448,9 @@ impl CoverImageProcessor for UniversalAIClient {
         };
 
         // Download and save the image to the specified path
-        match self.download_image(&image_url, target_cover_path).await {
+        match self.save_image(&image_url, target_cover_path, Some(&dalle_prompt)).await {
             Ok(()) => {
-                println!("{}", FORMATTER.format_image_saved(target_cover_path));
+                FORMATTER.print_detail(&FORMATTER.format_image_saved(target_cover_path));
                 Ok(())
             }
             Err(e) => {
@@ -532,11 +1459,49 @@ impl CoverImageProcessor for UniversalAIClient {
             }
         }
     }
+
+    async fn generate_cover_variations(
+        &self,
+        article: &ArticleSummary,
+        dir: &Path,
+        base_filename: &str,
+        count: u32,
+    ) -> Result<Vec<PathBuf>> {
+        use crate::output::{FORMATTER, OutputFormatter, FilePathFormatter};
+        use tracing::info;
+
+        // All candidates share one scene, so they differ only in the rendering
+        let scene_description = self.scene_description_for(article).await?;
+        let dalle_prompt = self.create_dalle_prompt(&scene_description);
+        info!("DALL-E prompt: {}", dalle_prompt);
+        FORMATTER.print_detail(&FORMATTER.format_image_prompt(&dalle_prompt));
+
+        let images = match self.generate_images(&dalle_prompt, count).await {
+            Ok(images) => images,
+            Err(e) => {
+                FORMATTER.print_error(&format!("Failed to generate images: {}", e));
+                return Err(e);
+            }
+        };
+
+        let mut paths = Vec::new();
+        for (index, image) in images.iter().enumerate() {
+            let path = dir.join(cover_variation_filename(
+                base_filename,
+                index + 1,
+                self.cover_encoding.format,
+            ));
+            self.save_image(image, &path, Some(&dalle_prompt)).await?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashMap;
 
     #[test]
     fn test_provider_config() {
@@ -607,4 +1572,703 @@ mod tests {
         assert!(prompt.contains("Ghibli-style"));
         assert!(prompt.contains("A serene forest with morning mist"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_fallback_scene_description() {
+        let body = "Intro\n\n## Futures\n\ntext\n\n```rust\n## not a heading\n```\n\n### Detail\n\n## Executors ##\n\n## Wakers\n\n## Pinning\n";
+        let article = ArticleSummary::new(Some("Rust Async"), "How async works.", body);
+
+        assert_eq!(article.headings, vec!["Futures", "Executors", "Wakers"]);
+        assert_eq!(
+            article.fallback_scene_description(),
+            "An illustration for an article titled \"Rust Async\". How async works. Key topics: Futures, Executors, Wakers."
+        );
+    }
+
+    #[test]
+    fn test_fallback_scene_description_empty_article() {
+        let article = ArticleSummary::new(Some("  "), "", "no headings here");
+        assert!(article.title.is_none());
+        assert_eq!(article.fallback_scene_description(), DEFAULT_SCENE_DESCRIPTION);
+
+        let from_description = ArticleSummary::from("A quiet harbor");
+        assert_eq!(from_description.fallback_scene_description(), "A quiet harbor.");
+
+        let from_tags = ArticleSummary::new(None, "", "").with_tags(vec!["rust".to_string()]);
+        assert_eq!(from_tags.fallback_scene_description(), "Themes: rust.");
+    }
+
+    #[test]
+    fn test_fallback_scene_template() {
+        let article = ArticleSummary::new(Some("Rust Async"), "How async works.", "## Futures\n")
+            .with_tags(vec!["rust".to_string(), "async".to_string()]);
+        let template = "A flat teal illustration of {title}, about {tags}. {description}.";
+        assert_eq!(
+            article.fallback_scene_with(Some(template)),
+            "A flat teal illustration of Rust Async, about rust, async. How async works."
+        );
+        assert_eq!(
+            article.fallback_scene_with(Some("Our mascot exploring {headings}")),
+            "Our mascot exploring Futures"
+        );
+        assert_eq!(article.fallback_scene_with(None), article.fallback_scene_description());
+
+        // A template without placeholders is used as is
+        let empty = ArticleSummary::new(None, "", "");
+        assert_eq!(empty.fallback_scene_with(Some("Our mascot")), "Our mascot");
+
+        // A template with nothing to fill in falls back to the built-in scene
+        let untitled = ArticleSummary::new(None, "", "## Futures\n");
+        assert_eq!(
+            untitled.fallback_scene_with(Some("A cover for {title} ({tags})")),
+            "Key topics: Futures."
+        );
+        assert_eq!(
+            empty.fallback_scene_with(Some("A cover for {title}")),
+            DEFAULT_SCENE_DESCRIPTION
+        );
+    }
+
+    #[tokio::test]
+    async fn test_empty_scene_description_uses_fallback_scene() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{"message": {"content": "  "}}]
+            })))
+            .mount(&server)
+            .await;
+
+        let config = ProviderConfig::OpenAI {
+            api_key: "test-key".to_string(),
+            base_url: Some(server.uri()),
+        };
+        let client = UniversalAIClient::new(config, None);
+        let err = client.generate_scene_description("text", "en").await.unwrap_err();
+        assert!(err.to_string().contains("OpenAI returned an empty scene description"));
+
+        let article = ArticleSummary::new(Some("Rust Async"), "", "")
+            .with_tags(vec!["rust".to_string()]);
+        assert_eq!(
+            client.scene_description_for(&article).await.unwrap(),
+            "An illustration for an article titled \"Rust Async\". Themes: rust."
+        );
+
+        let client = client.with_fallback_scene(Some("Brand illustration: {title}".to_string()));
+        assert_eq!(
+            client.scene_description_for(&article).await.unwrap(),
+            "Brand illustration: Rust Async"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scene_description_falls_back_on_ai_failure() {
+        let config = ProviderConfig::OpenAI {
+            api_key: "test-key".to_string(),
+            base_url: Some("http://127.0.0.1:1".to_string()),
+        };
+        let article = ArticleSummary::new(Some("Title"), "Desc", "## One\n");
+
+        let client = UniversalAIClient::new(config.clone(), None);
+        assert_eq!(
+            client.scene_description_for(&article).await.unwrap(),
+            article.fallback_scene_description()
+        );
+
+        let local_only = UniversalAIClient::new(config, None).with_ai_scene_text(false);
+        assert!(!local_only.ai_scene_text);
+        assert_eq!(
+            local_only.scene_description_for(&article).await.unwrap(),
+            "An illustration for an article titled \"Title\". Desc. Key topics: One."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scene_descriptions_are_cached_by_content() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{"message": {"content": "A quiet harbor at dawn."}}]
+            })))
+            .expect(4)
+            .mount(&server)
+            .await;
+
+        let config = ProviderConfig::OpenAI {
+            api_key: "test-key".to_string(),
+            base_url: Some(server.uri()),
+        };
+        let cache_dir = tempfile::tempdir().unwrap();
+        let client = UniversalAIClient::new(config.clone(), None)
+            .with_scene_cache(Some(SceneCache::new(cache_dir.path())));
+        let article = ArticleSummary::new(Some("Title"), "Desc", "## One\n");
+
+        // The second call for the same content is served from the cache
+        for _ in 0..2 {
+            assert_eq!(
+                client.scene_description_for(&article).await.unwrap(),
+                "A quiet harbor at dawn."
+            );
+        }
+        // Changed content asks again
+        let edited = ArticleSummary::new(Some("Title"), "Edited desc", "## One\n");
+        client.scene_description_for(&edited).await.unwrap();
+
+        // Without a cache every call asks
+        let uncached = UniversalAIClient::new(config, None);
+        for _ in 0..2 {
+            uncached.scene_description_for(&article).await.unwrap();
+        }
+    }
+
+    #[test]
+    fn test_article_summary_language() {
+        assert_eq!(ArticleSummary::new(Some("Rust Async"), "", "text").lang, "en");
+
+        let chinese = ArticleSummary::new(Some("异步编程"), "介绍 Rust 的异步模型", "正文");
+        assert_eq!(chinese.lang, "zh");
+        assert_eq!(chinese.with_lang(Some("en")).lang, "en");
+
+        let unchanged = ArticleSummary::new(Some("异步编程"), "", "").with_lang(Some("  "));
+        assert_eq!(unchanged.lang, "zh");
+    }
+
+    #[test]
+    fn test_truncate_at_char_boundary() {
+        assert_eq!(truncate_at_char_boundary("hello", 10), "hello");
+        assert_eq!(truncate_at_char_boundary("hello", 3), "hel");
+        // Each character is three bytes; never split one
+        assert_eq!(truncate_at_char_boundary("异步编程", 7), "异步");
+    }
+
+    #[tokio::test]
+    async fn test_scene_description_uses_language_prompt() {
+        use wiremock::matchers::{body_partial_json, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(json!({
+                "messages": [{"role": "system", "content": "中文提示"}]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{"message": {"content": "A misty mountain temple."}}]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = ProviderConfig::OpenAI {
+            api_key: "test-key".to_string(),
+            base_url: Some(server.uri()),
+        };
+        let overrides = HashMap::from([("zh".to_string(), "中文提示".to_string())]);
+        let client = UniversalAIClient::new(config, None)
+            .with_scene_prompts(ScenePrompts::with_overrides(&overrides));
+
+        let content = "山".repeat(1000);
+        let description = client.generate_scene_description(&content, "zh-CN").await.unwrap();
+        assert_eq!(description, "A misty mountain temple.");
+    }
+
+    #[tokio::test]
+    async fn test_permanent_ai_errors_fail_fast() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(401).set_body_string("Incorrect API key"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/images/generations"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let config = ProviderConfig::OpenAI {
+            api_key: "bad-key".to_string(),
+            base_url: Some(server.uri()),
+        };
+        let client = UniversalAIClient::new(config, None);
+        let article = ArticleSummary::new(Some("Title"), "Desc", "");
+        let dir = tempfile::tempdir().unwrap();
+
+        let err = client
+            .generate_cover_image(&article, &dir.path().join("post.md"), "post")
+            .await
+            .unwrap_err();
+        assert!(err.is_permanent());
+        assert!(err.to_string().starts_with("OpenAI API request failed with status 401"));
+        assert!(err.to_string().contains("API key is missing, invalid or revoked"));
+    }
+
+    #[tokio::test]
+    async fn test_transient_ai_errors_fall_back() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(429).set_body_string("Rate limit reached"))
+            .mount(&server)
+            .await;
+
+        let config = ProviderConfig::OpenAI {
+            api_key: "test-key".to_string(),
+            base_url: Some(server.uri()),
+        };
+        let client = UniversalAIClient::new(config, None);
+        let err = client.generate_scene_description("text", "en").await.unwrap_err();
+        assert!(err.is_transient());
+        assert!(err.to_string().contains("transient: rate limit"));
+
+        let article = ArticleSummary::new(Some("Title"), "Desc", "");
+        assert_eq!(
+            client.scene_description_for(&article).await.unwrap(),
+            article.fallback_scene_description()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_request_retried_after_retry_after() {
+        use crate::rate_limit::RetryPolicy;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{"message": {"content": "A quiet harbor at dawn"}}]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = ProviderConfig::OpenAI {
+            api_key: "test-key".to_string(),
+            base_url: Some(server.uri()),
+        };
+        let client = UniversalAIClient::new(config.clone(), None)
+            .with_retry_policy(RetryPolicy::default());
+        assert_eq!(
+            client.generate_scene_description("text", "en").await.unwrap(),
+            "A quiet harbor at dawn"
+        );
+
+        // Without retries the 429 is returned at once, with the wait it asked for
+        server.reset().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "20"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = UniversalAIClient::new(config, None).with_retry_policy(RetryPolicy::NONE);
+        let err = client.generate_scene_description("text", "en").await.unwrap_err();
+        assert_eq!(err.ai_failure(), Some(AiFailure::RateLimited));
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(20)));
+    }
+
+    #[tokio::test]
+    async fn test_check_connectivity_reports_each_request() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{"message": {"content": "A leafy balcony at noon"}}]
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/images/generations"))
+            .respond_with(
+                ResponseTemplate::new(403)
+                    .set_body_string("Key sk-check-secret-1234 may not use gpt-image-1"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = ProviderConfig::OpenAI {
+            api_key: "sk-check-secret-1234".to_string(),
+            base_url: Some(server.uri()),
+        };
+        let client = UniversalAIClient::new(config, None);
+
+        let checks = client.check_connectivity(true).await;
+        assert_eq!(checks.len(), 2);
+        assert_eq!(checks[0].name, "scene description");
+        assert_eq!(checks[0].model, "gpt-4o-mini");
+        assert_eq!(checks[0].outcome, Ok("\"A leafy balcony at noon\"".to_string()));
+        assert_eq!(checks[0].failure, None);
+
+        assert_eq!(checks[1].name, "image generation");
+        assert_eq!(checks[1].failure, Some(AiFailure::PermissionDenied));
+        let error = checks[1].outcome.as_ref().unwrap_err();
+        assert!(error.contains("status 403"));
+        assert!(!error.contains("sk-check-secret-1234"));
+        assert!(error.contains("Key *** may not use"));
+
+        // Skipping the image makes only the text request
+        let checks = client.check_connectivity(false).await;
+        assert_eq!(checks.len(), 1);
+        assert!(checks[0].is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_model_overrides_use_the_configured_credentials() {
+        use wiremock::matchers::{body_partial_json, header, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(header("Authorization", "Bearer test-key"))
+            .and(body_partial_json(json!({"model": "gpt-4o"})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{"message": {"content": "A lighthouse"}}]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/images/generations"))
+            .and(body_partial_json(json!({"model": "gpt-image-1"})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": [{"b64_json": "YQ=="}]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = ProviderConfig::OpenAI {
+            api_key: "test-key".to_string(),
+            base_url: Some(server.uri()),
+        };
+        let client = UniversalAIClient::new(config, None);
+        let file_client = client
+            .with_model_overrides(Some("OpenAI"), Some("gpt-4o"), Some("gpt-image-1"))
+            .unwrap();
+        assert_eq!(file_client.generate_scene_description("text", "en").await.unwrap(), "A lighthouse");
+        assert_eq!(file_client.generate_image("prompt").await.unwrap(), "base64:YQ==");
+        // The configured client keeps its models
+        assert_eq!(client.model_config.image_model, "dall-e-3");
+
+        let err = client.with_model_overrides(Some("gemini"), None, None).unwrap_err();
+        assert!(err.is_config());
+        assert!(err.to_string().contains("ai_provider 'gemini' has no credentials configured"));
+        let err = client.with_model_overrides(Some("claude"), None, None).unwrap_err();
+        assert!(err.to_string().contains("unknown ai_provider 'claude'"));
+    }
+
+    #[tokio::test]
+    async fn test_generate_images_uses_n_or_loops() {
+        use wiremock::matchers::{body_partial_json, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/images/generations"))
+            .and(body_partial_json(json!({"model": "gpt-image-1", "n": 3})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": [{"b64_json": "YQ=="}, {"b64_json": "Yg=="}, {"url": "http://x/c.png"}]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/images/generations"))
+            .and(body_partial_json(json!({"model": "dall-e-3", "n": 1})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": [{"b64_json": "YQ=="}]
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let config = ProviderConfig::OpenAI {
+            api_key: "test-key".to_string(),
+            base_url: Some(server.uri()),
+        };
+        let batched = UniversalAIClient::new(
+            config.clone(),
+            Some(ModelConfig {
+                image_model: "gpt-image-1".to_string(),
+                ..ModelConfig::openai()
+            }),
+        );
+        assert_eq!(
+            batched.generate_images("harbor", 3).await.unwrap(),
+            ["base64:YQ==", "base64:Yg==", "http://x/c.png"]
+        );
+
+        // dall-e-3 only accepts n = 1, so each image is its own request
+        let single = UniversalAIClient::new(config, None);
+        assert_eq!(single.generate_images("harbor", 2).await.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_cover_variation_filename() {
+        assert_eq!(
+            cover_variation_filename("weekly-42", 1, CoverFormat::Png),
+            "weekly-42-cover-1.png"
+        );
+        assert_eq!(
+            cover_variation_filename("post", 10, CoverFormat::Jpeg),
+            "post-cover-10.jpg"
+        );
+    }
+
+    #[test]
+    fn test_sse_events_split_across_chunks() {
+        let mut events = SseEvents::default();
+        assert!(events.push(b"data: {\"a\"").is_empty());
+        assert_eq!(events.push(b":1}\r\n\r\n: comment\n"), vec!["{\"a\":1}"]);
+        // A character split between chunks is joined before decoding
+        let text = "data: 春天\n\n".as_bytes();
+        assert!(events.push(&text[..8]).is_empty());
+        assert_eq!(events.push(&text[8..]), vec!["春天"]);
+        assert_eq!(events.push(b"data: one\ndata: two\n\ndata: [DONE]"), vec!["one\ntwo"]);
+        assert_eq!(events.finish(), vec!["[DONE]"]);
+    }
+
+    #[tokio::test]
+    async fn test_streamed_scene_descriptions() {
+        use wiremock::matchers::{body_partial_json, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let stream = concat!(
+            "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"A quiet \"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"harbor at dawn\"}}]}\n\n",
+            "data: [DONE]\n\n",
+        );
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(json!({"stream": true})))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(stream, "text/event-stream"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = ProviderConfig::OpenAI {
+            api_key: "test-key".to_string(),
+            base_url: Some(server.uri()),
+        };
+        let client = UniversalAIClient::new(config, None).with_scene_streaming(true);
+        assert_eq!(
+            client.generate_scene_description("text", "en").await.unwrap(),
+            "A quiet harbor at dawn"
+        );
+
+        // A gateway that ignores the streaming request answers with plain JSON
+        let gateway = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{"message": {"content": "A lighthouse"}}]
+            })))
+            .expect(1)
+            .mount(&gateway)
+            .await;
+        let config = ProviderConfig::OpenAI {
+            api_key: "test-key".to_string(),
+            base_url: Some(gateway.uri()),
+        };
+        let client = UniversalAIClient::new(config, None).with_scene_streaming(true);
+        assert_eq!(
+            client.generate_scene_description("text", "en").await.unwrap(),
+            "A lighthouse"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_streaming_falls_back_to_a_regular_request() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/gemini-2.5-flash:streamGenerateContent"))
+            .respond_with(ResponseTemplate::new(404).set_body_string("streaming not supported"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/gemini-2.5-flash:generateContent"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "candidates": [{"content": {"parts": [{"text": "A mountain lake"}]}}]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = ProviderConfig::Gemini {
+            api_key: "test-key".to_string(),
+            base_url: Some(server.uri()),
+        };
+        let client = UniversalAIClient::new(config, None).with_scene_streaming(true);
+        assert_eq!(
+            client.generate_scene_description("text", "en").await.unwrap(),
+            "A mountain lake"
+        );
+
+        // A rejected key is not retried
+        let rejecting = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(401).set_body_string("API key not valid"))
+            .expect(1)
+            .mount(&rejecting)
+            .await;
+        let config = ProviderConfig::Gemini {
+            api_key: "test-key".to_string(),
+            base_url: Some(rejecting.uri()),
+        };
+        let client = UniversalAIClient::new(config, None).with_scene_streaming(true);
+        let err = client.generate_scene_description("text", "en").await.unwrap_err();
+        assert_eq!(err.ai_failure(), Some(AiFailure::InvalidApiKey));
+    }
+
+    #[tokio::test]
+    async fn test_truncated_image_download_is_retried() {
+        use base64::Engine;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let image = image::RgbaImage::from_pixel(8, 4, image::Rgba([10, 20, 30, 255]));
+        let mut png = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut png, image::ImageFormat::Png).unwrap();
+        let png = png.into_inner();
+        let truncated = png[..png.len() / 2].to_vec();
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/cover.png"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(truncated.clone()))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/cover.png"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(png.clone()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = ProviderConfig::OpenAI {
+            api_key: "test-key".to_string(),
+            base_url: None,
+        };
+        let client = UniversalAIClient::new(config, None);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let target = temp_dir.path().join("cover.png");
+        let url = format!("{}/cover.png", server.uri());
+        client.download_image(&url, &target).await.unwrap();
+        assert_eq!(std::fs::read(&target).unwrap(), png);
+        server.verify().await;
+
+        // A download that never arrives whole fails without leaving a file behind
+        server.reset().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(truncated.clone()))
+            .expect(u64::from(IMAGE_DOWNLOAD_RETRIES) + 1)
+            .mount(&server)
+            .await;
+        let broken = temp_dir.path().join("broken.png");
+        let err = client.download_image(&url, &broken).await.unwrap_err();
+        let message = err.to_string();
+        assert!(matches!(err, Error::CoverImage { .. }), "{}", message);
+        assert!(message.contains(&format!("({} bytes)", truncated.len())), "{}", message);
+        assert!(message.contains("after 3 attempts"), "{}", message);
+        assert!(!broken.exists());
+
+        // A corrupt base64 payload is not retried
+        let payload = format!(
+            "base64:{}",
+            base64::engine::general_purpose::STANDARD.encode(&truncated)
+        );
+        let err = client.download_image(&payload, &broken).await.unwrap_err();
+        assert!(err.to_string().contains("does not decode as an image"), "{}", err);
+        assert!(!broken.exists());
+    }
+
+    #[tokio::test]
+    async fn test_generated_covers_embed_provenance_when_enabled() {
+        use base64::Engine;
+
+        let image = image::RgbaImage::from_pixel(8, 4, image::Rgba([10, 20, 30, 255]));
+        let mut png = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut png, image::ImageFormat::Png).unwrap();
+        let png = png.into_inner();
+        let url = format!("base64:{}", base64::engine::general_purpose::STANDARD.encode(&png));
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = ProviderConfig::OpenAI {
+            api_key: "test-key".to_string(),
+            base_url: None,
+        };
+
+        // Off by default, and never for images saved without a prompt
+        let plain = UniversalAIClient::new(config.clone(), None);
+        let path = temp_dir.path().join("plain.png");
+        plain.save_image(&url, &path, Some("A harbor")).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), png);
+        let tagging = UniversalAIClient::new(config, None).with_embed_provenance(true);
+        tagging.download_image(&url, &path).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), png);
+
+        let path = temp_dir.path().join("tagged.png");
+        tagging.save_image(&url, &path, Some("A harbor")).await.unwrap();
+        let tagged = std::fs::read(&path).unwrap();
+        let reader = png::Decoder::new(tagged.as_slice()).read_info().unwrap();
+        let text = |keyword: &str| {
+            reader
+                .info()
+                .uncompressed_latin1_text
+                .iter()
+                .find(|chunk| chunk.keyword == keyword)
+                .map(|chunk| chunk.text.clone())
+        };
+        assert_eq!(text("AI Provider").as_deref(), Some("OpenAI"));
+        assert_eq!(text("AI Model"), Some(tagging.model_config.image_model.clone()));
+        assert_eq!(text("Prompt").as_deref(), Some("A harbor"));
+        assert!(text("Creation Time").is_some());
+
+        // A gateway answering with JPEG still yields the cover, untagged
+        let rgb = image::DynamicImage::ImageRgba8(image).into_rgb8();
+        let mut jpeg = std::io::Cursor::new(Vec::new());
+        rgb.write_to(&mut jpeg, image::ImageFormat::Jpeg).unwrap();
+        let jpeg = jpeg.into_inner();
+        let url = format!("base64:{}", base64::engine::general_purpose::STANDARD.encode(&jpeg));
+        let path = temp_dir.path().join("jpeg.png");
+        tagging.save_image(&url, &path, Some("A harbor")).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), jpeg);
+    }
+}
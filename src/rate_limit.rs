@@ -0,0 +1,716 @@
+//! Rate limiting of WeChat draft creation
+//!
+//! WeChat caps how often an account may call its APIs and answers with
+//! `errcode` 45009 once the cap is exceeded. Each account gets a token bucket
+//! that refills at `wechat_rpm` drafts per minute and is shared by every
+//! upload of the run, so concurrent uploads queue for tokens instead of
+//! tripping the limit together.
+//!
+//! When WeChat still answers 45009 (or HTTP 429), the upload backs off and is
+//! retried under the [`RetryPolicy`] of the run, and the bucket halves its
+//! rate for [`RATE_LIMIT_COOLDOWN`] before returning to the configured one.
+//! A `Retry-After` header replaces the backoff, clamped to the policy's
+//! `max_delay`; AI provider requests answered with 429 are retried the same way.
+//!
+//! WeChat also rejects bursts of drafts that stay under the rate. With
+//! `upload_delay_ms` (or `--delay`), an [`UploadPacer`] makes each upload
+//! worker pause that long after a draft was created before it creates the
+//! next one. Skipped files do not wait and are not waited for.
+
+use crate::content_check::ContentChecker;
+use crate::error::Result;
+use crate::output::{FORMATTER, OutputFormatter};
+use crate::wechat::{ArticleOptions, DraftSummary, WeChatUploader};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::info;
+
+/// Name of the header a rate-limited response says how long to wait in
+pub const RETRY_AFTER: &str = "retry-after";
+
+/// Drafts per minute an account may create when `wechat_rpm` is not configured
+pub const DEFAULT_WECHAT_RPM: u32 = 20;
+
+/// Drafts that may be created back to back before pacing starts
+pub const RATE_LIMIT_BURST: f64 = 5.0;
+
+/// How long the rate stays lowered after WeChat answered 45009
+pub const RATE_LIMIT_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// Wait before the first retry of a rate-limited upload; doubled for each further retry
+pub const RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Retries of a rate-limited request when `max_retries` is not configured
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// Longest wait before a retry when `max_retry_delay` is not configured
+pub const DEFAULT_MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// How often and how long rate-limited requests are retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; 0 returns the rate limit error at once
+    pub max_retries: u32,
+    /// Longest wait before a retry, also for a longer `Retry-After`
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES,
+            max_delay: DEFAULT_MAX_RETRY_DELAY,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries
+    pub const NONE: Self = Self {
+        max_retries: 0,
+        max_delay: Duration::ZERO,
+    };
+
+    /// Returns the wait before retry number `retry` (counting from 0)
+    ///
+    /// The `retry_after` a response asked for is honored; otherwise the wait
+    /// is [`RATE_LIMIT_BACKOFF`] doubled for each earlier retry. Either is
+    /// clamped to `max_delay`.
+    pub fn delay(&self, retry: u32, retry_after: Option<Duration>) -> Duration {
+        let wait = retry_after
+            .unwrap_or_else(|| RATE_LIMIT_BACKOFF.saturating_mul(2u32.saturating_pow(retry)));
+        wait.min(self.max_delay)
+    }
+}
+
+/// Parses a `Retry-After` value: delay seconds or an HTTP date
+///
+/// A date in the past means no wait.
+///
+/// # Examples
+///
+/// ```
+/// use chrono::{TimeZone, Utc};
+/// use std::time::Duration;
+/// use wx_uploader::rate_limit::parse_retry_after;
+///
+/// let now = Utc.with_ymd_and_hms(2015, 10, 21, 7, 27, 30).unwrap();
+/// assert_eq!(parse_retry_after("120", now), Some(Duration::from_secs(120)));
+/// assert_eq!(
+///     parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", now),
+///     Some(Duration::from_secs(30))
+/// );
+/// assert_eq!(parse_retry_after("soon", now), None);
+/// ```
+pub fn parse_retry_after(value: &str, now: chrono::DateTime<chrono::Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some((date.with_timezone(&chrono::Utc) - now).to_std().unwrap_or(Duration::ZERO))
+}
+
+/// Returns the wait the `Retry-After` header of a response asks for, if any
+pub fn retry_after_header(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?;
+    parse_retry_after(value, chrono::Utc::now())
+}
+
+/// Logs and sleeps the wait before retry number `retry` of a request to `service`
+///
+/// Returns the wait, so the caller can report it.
+pub async fn wait_before_retry(
+    policy: &RetryPolicy,
+    retry: u32,
+    retry_after: Option<Duration>,
+    service: &str,
+) -> Duration {
+    let wait = policy.delay(retry, retry_after);
+    if let Some(asked) = retry_after {
+        info!(
+            "Honoring Retry-After from {}: waiting {:.1}s (asked for {}s, max_retry_delay {}s)",
+            service,
+            wait.as_secs_f64(),
+            asked.as_secs(),
+            policy.max_delay.as_secs()
+        );
+    }
+    tokio::time::sleep(wait).await;
+    wait
+}
+
+/// Token bucket pacing the draft creation of one account
+#[derive(Debug)]
+pub struct RateLimiter {
+    configured_rpm: u32,
+    retry: RetryPolicy,
+    state: Mutex<BucketState>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    /// Effective rate, below the configured one during a cooldown
+    rpm: u32,
+    /// Available tokens; negative while callers wait for reserved ones
+    tokens: f64,
+    last_refill: Instant,
+    /// When the configured rate is restored after a 45009
+    recover_at: Option<Instant>,
+}
+
+impl BucketState {
+    /// Adds the tokens accumulated since the last refill and ends an elapsed cooldown
+    fn refill(&mut self, configured_rpm: u32, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * f64::from(self.rpm) / 60.0).min(RATE_LIMIT_BURST);
+        self.last_refill = now;
+        if self.recover_at.is_some_and(|at| now >= at) {
+            self.rpm = configured_rpm;
+            self.recover_at = None;
+        }
+    }
+}
+
+impl RateLimiter {
+    /// Creates a full bucket allowing `rpm` drafts per minute (at least one)
+    pub fn new(rpm: u32) -> Self {
+        let rpm = rpm.max(1);
+        Self {
+            configured_rpm: rpm,
+            retry: RetryPolicy::default(),
+            state: Mutex::new(BucketState {
+                rpm,
+                tokens: RATE_LIMIT_BURST,
+                last_refill: Instant::now(),
+                recover_at: None,
+            }),
+        }
+    }
+
+    /// Sets how uploads rejected for the rate limit are retried
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Returns how uploads rejected for the rate limit are retried
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry
+    }
+
+    /// Returns the current drafts per minute, lowered while cooling down from a 45009
+    pub fn rpm(&self) -> u32 {
+        let mut state = self.state.lock().unwrap();
+        state.refill(self.configured_rpm, Instant::now());
+        state.rpm
+    }
+
+    /// Waits until a draft may be created and returns how long that took
+    ///
+    /// A token is reserved before waiting, so concurrent callers are served
+    /// one after the other at the current rate.
+    pub async fn acquire(&self) -> Duration {
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            state.refill(self.configured_rpm, Instant::now());
+            state.tokens -= 1.0;
+            if state.tokens >= 0.0 {
+                Duration::ZERO
+            } else {
+                Duration::from_secs_f64(-state.tokens * 60.0 / f64::from(state.rpm))
+            }
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+        wait
+    }
+
+    /// Halves the rate for [`RATE_LIMIT_COOLDOWN`] and drops saved-up tokens
+    ///
+    /// Returns the lowered drafts per minute.
+    pub fn on_rate_limited(&self) -> u32 {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        state.refill(self.configured_rpm, now);
+        state.rpm = (state.rpm / 2).max(1);
+        state.tokens = state.tokens.min(0.0);
+        state.recover_at = Some(now + RATE_LIMIT_COOLDOWN);
+        state.rpm
+    }
+}
+
+/// Rate limiters of the accounts used in a run, created on first use
+#[derive(Debug)]
+pub struct RateLimiters {
+    rpm: u32,
+    retry: RetryPolicy,
+    limiters: Mutex<HashMap<String, Arc<RateLimiter>>>,
+}
+
+impl RateLimiters {
+    /// Creates the registry; an `rpm` of 0 disables rate limiting
+    pub fn new(rpm: u32) -> Self {
+        Self {
+            rpm,
+            retry: RetryPolicy::default(),
+            limiters: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sets how the uploads of every account are retried
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Returns the configured drafts per minute
+    pub fn rpm(&self) -> u32 {
+        self.rpm
+    }
+
+    /// Returns how uploads rejected for the rate limit are retried
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry
+    }
+
+    /// Returns the limiter shared by all clients of an account, or `None` if disabled
+    pub fn for_account(&self, name: &str) -> Option<Arc<RateLimiter>> {
+        if self.rpm == 0 {
+            return None;
+        }
+        let mut limiters = self.limiters.lock().unwrap();
+        let limiter = limiters
+            .entry(name.to_string())
+            .or_insert_with(|| Arc::new(RateLimiter::new(self.rpm).with_retry_policy(self.retry)));
+        Some(Arc::clone(limiter))
+    }
+}
+
+/// Uploader that paces draft creation and retries uploads rejected with 45009 or 429
+pub struct RateLimitedUploader {
+    inner: Box<dyn WeChatUploader>,
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimitedUploader {
+    /// Wraps `inner` so its uploads take tokens from `limiter`
+    pub fn new(inner: Box<dyn WeChatUploader>, limiter: Arc<RateLimiter>) -> Self {
+        Self { inner, limiter }
+    }
+}
+
+#[async_trait::async_trait]
+impl WeChatUploader for RateLimitedUploader {
+    async fn upload(&self, file_path: &str, options: &ArticleOptions) -> Result<String> {
+        let policy = self.limiter.retry_policy();
+        let mut retries = 0;
+        loop {
+            let waited = self.limiter.acquire().await;
+            if !waited.is_zero() {
+                info!(
+                    "Rate limiter: waited {:.1}s before uploading {}",
+                    waited.as_secs_f64(),
+                    file_path
+                );
+            }
+
+            match self.inner.upload(file_path, options).await {
+                Err(e) if e.is_rate_limited() && retries < policy.max_retries => {
+                    let rpm = self.limiter.on_rate_limited();
+                    let retry_after = e.retry_after();
+                    FORMATTER.print_warning(&format!(
+                        "WeChat rate limit reached; retrying in {}s{} at {} drafts per minute ({}/{})",
+                        policy.delay(retries, retry_after).as_secs(),
+                        if retry_after.is_some() { " as asked by Retry-After" } else { "" },
+                        rpm,
+                        retries + 1,
+                        policy.max_retries
+                    ));
+                    wait_before_retry(&policy, retries, retry_after, "WeChat").await;
+                    retries += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn refresh_token(&self) -> Result<String> {
+        self.inner.refresh_token().await
+    }
+
+    async fn content_checker(&self) -> Result<Box<dyn ContentChecker + '_>> {
+        self.inner.content_checker().await
+    }
+
+    async fn list_recent_drafts(&self, limit: u32) -> Result<Vec<DraftSummary>> {
+        self.inner.list_recent_drafts(limit).await
+    }
+
+    async fn upload_body_image(&self, image_path: &Path) -> Result<String> {
+        self.inner.upload_body_image(image_path).await
+    }
+}
+
+/// Pause each upload worker takes after creating a draft
+///
+/// Clones share their workers, so the pacing holds across the groups and
+/// accounts of a run. A worker is taken for each upload; `workers` should be
+/// the number of concurrent uploads of the run.
+#[derive(Debug, Clone)]
+pub struct UploadPacer {
+    delay: Duration,
+    /// When each idle worker last created a draft, if it did
+    workers: Arc<Mutex<Vec<Option<Instant>>>>,
+    max_workers: usize,
+}
+
+impl Default for UploadPacer {
+    fn default() -> Self {
+        Self::new(Duration::ZERO, 1)
+    }
+}
+
+impl PartialEq for UploadPacer {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.workers, &other.workers)
+    }
+}
+
+impl UploadPacer {
+    /// Creates a pacer of `workers` upload workers (at least one) pausing `delay`
+    pub fn new(delay: Duration, workers: usize) -> Self {
+        let workers = workers.max(1);
+        Self {
+            delay,
+            workers: Arc::new(Mutex::new(vec![None; workers])),
+            max_workers: workers,
+        }
+    }
+
+    /// Returns the pause after each draft
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// Takes the worker that has been idle longest and waits out its pause
+    ///
+    /// Call [`PacedUpload::created`] once the draft exists; the worker is
+    /// handed back when the returned value is dropped.
+    pub async fn start(&self) -> PacedUpload<'_> {
+        let last_draft = {
+            let mut workers = self.workers.lock().unwrap();
+            let idlest = (0..workers.len()).min_by_key(|&i| workers[i]);
+            idlest.and_then(|i| workers.swap_remove(i))
+        };
+        if let Some(at) = last_draft {
+            let wait = (at + self.delay).saturating_duration_since(Instant::now());
+            if !wait.is_zero() {
+                info!("Upload delay: waiting {:.1}s before the next draft", wait.as_secs_f64());
+                tokio::time::sleep(wait).await;
+            }
+        }
+        PacedUpload {
+            pacer: self,
+            last_draft,
+        }
+    }
+}
+
+/// An upload worker taken from an [`UploadPacer`]
+#[derive(Debug)]
+pub struct PacedUpload<'a> {
+    pacer: &'a UploadPacer,
+    last_draft: Option<Instant>,
+}
+
+impl PacedUpload<'_> {
+    /// Records that the worker created a draft, so its next upload waits
+    pub fn created(&mut self) {
+        self.last_draft = Some(Instant::now());
+    }
+}
+
+impl Drop for PacedUpload<'_> {
+    fn drop(&mut self) {
+        let mut workers = self.pacer.workers.lock().unwrap();
+        if workers.len() < self.pacer.max_workers {
+            workers.push(self.last_draft);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use wechat_pub_rs::WeChatError;
+
+    /// Records when each upload reached WeChat and rejects the first `rejections`
+    /// with 45009, or with 429 and `retry_after` if it is set
+    struct MockUploader {
+        started: Instant,
+        calls: Arc<Mutex<Vec<Duration>>>,
+        rejections: AtomicUsize,
+        retry_after: Option<Duration>,
+    }
+
+    impl MockUploader {
+        fn new(rejections: usize) -> (Self, Arc<Mutex<Vec<Duration>>>) {
+            let calls = Arc::new(Mutex::new(Vec::new()));
+            let uploader = Self {
+                started: Instant::now(),
+                calls: Arc::clone(&calls),
+                rejections: AtomicUsize::new(rejections),
+                retry_after: None,
+            };
+            (uploader, calls)
+        }
+
+        fn too_many_requests(
+            rejections: usize,
+            retry_after: Duration,
+        ) -> (Self, Arc<Mutex<Vec<Duration>>>) {
+            let (mut uploader, calls) = Self::new(rejections);
+            uploader.retry_after = Some(retry_after);
+            (uploader, calls)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl WeChatUploader for MockUploader {
+        async fn upload(&self, file_path: &str, _options: &ArticleOptions) -> Result<String> {
+            self.calls.lock().unwrap().push(self.started.elapsed());
+            let rejected = self
+                .rejections
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok();
+            if rejected && self.retry_after.is_some() {
+                return Err(Error::WeChatTooManyRequests {
+                    retry_after: self.retry_after,
+                });
+            }
+            if rejected {
+                return Err(Error::from_wechat(WeChatError::from_api_response(
+                    45009,
+                    "reach max api daily quota limit",
+                )));
+            }
+            Ok(format!("draft-{}", file_path))
+        }
+
+        async fn refresh_token(&self) -> Result<String> {
+            Ok("token".to_string())
+        }
+
+        async fn content_checker(&self) -> Result<Box<dyn ContentChecker + '_>> {
+            Err(Error::wechat("not supported"))
+        }
+
+        async fn list_recent_drafts(&self, _limit: u32) -> Result<Vec<DraftSummary>> {
+            Ok(Vec::new())
+        }
+
+        async fn upload_body_image(&self, _image_path: &Path) -> Result<String> {
+            Ok("https://mmbiz.qpic.cn/image".to_string())
+        }
+    }
+
+    fn secs(waits: &[Duration]) -> Vec<u64> {
+        waits.iter().map(Duration::as_secs).collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_acquire_paces_concurrent_callers() {
+        let limiter = RateLimiter::new(60);
+        let started = Instant::now();
+
+        let a = || limiter.acquire();
+        let (w1, w2, w3, w4, w5, w6, w7) = tokio::join!(a(), a(), a(), a(), a(), a(), a());
+        let mut waits = vec![w1, w2, w3, w4, w5, w6, w7];
+        waits.sort();
+
+        // The burst goes through at once, then one token per second
+        assert_eq!(secs(&waits), vec![0, 0, 0, 0, 0, 1, 2]);
+        assert_eq!(started.elapsed(), Duration::from_secs(2));
+
+        // An idle bucket refills up to the burst, not beyond it
+        tokio::time::advance(Duration::from_secs(600)).await;
+        for _ in 0..5 {
+            assert_eq!(limiter.acquire().await, Duration::ZERO);
+        }
+        assert_eq!(limiter.acquire().await, Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limited_lowers_rate_until_cooldown() {
+        let limiter = RateLimiter::new(60);
+        assert_eq!(limiter.acquire().await, Duration::ZERO);
+
+        // Saved-up tokens are dropped and the next draft waits at the halved rate
+        assert_eq!(limiter.on_rate_limited(), 30);
+        assert_eq!(limiter.rpm(), 30);
+        assert_eq!(limiter.acquire().await, Duration::from_secs(2));
+        assert_eq!(limiter.on_rate_limited(), 15);
+
+        tokio::time::advance(RATE_LIMIT_COOLDOWN).await;
+        assert_eq!(limiter.rpm(), 60);
+
+        // The rate never drops below one draft per minute
+        let slow = RateLimiter::new(1);
+        assert_eq!(slow.on_rate_limited(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_upload_retries_after_rate_limit() {
+        let limiter = Arc::new(RateLimiter::new(60));
+        let (mock, calls) = MockUploader::new(1);
+        let uploader = RateLimitedUploader::new(Box::new(mock), Arc::clone(&limiter));
+
+        let draft_id = uploader.upload("a.md", &ArticleOptions::default()).await.unwrap();
+        assert_eq!(draft_id, "draft-a.md");
+
+        // Retried after the backoff, with the rate halved for the cooldown
+        assert_eq!(secs(&calls.lock().unwrap()), vec![0, RATE_LIMIT_BACKOFF.as_secs()]);
+        assert_eq!(limiter.rpm(), 30);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_upload_gives_up_after_max_retries() {
+        let limiter = Arc::new(RateLimiter::new(60));
+        let (mock, calls) = MockUploader::new(usize::MAX);
+        let uploader = RateLimitedUploader::new(Box::new(mock), limiter);
+
+        let err = uploader.upload("a.md", &ArticleOptions::default()).await.unwrap_err();
+        assert!(err.is_rate_limited());
+        assert_eq!(calls.lock().unwrap().len(), DEFAULT_MAX_RETRIES as usize + 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_upload_honors_retry_after_up_to_max_delay() {
+        let policy = RetryPolicy {
+            max_retries: 2,
+            max_delay: Duration::from_secs(30),
+        };
+        let limiter = Arc::new(RateLimiter::new(60).with_retry_policy(policy));
+        let (mock, calls) = MockUploader::too_many_requests(1, Duration::from_secs(12));
+        let uploader = RateLimitedUploader::new(Box::new(mock), Arc::clone(&limiter));
+        uploader.upload("a.md", &ArticleOptions::default()).await.unwrap();
+        assert_eq!(secs(&calls.lock().unwrap()), vec![0, 12]);
+
+        // A longer Retry-After is clamped, and retries stop at max_retries
+        let (mock, calls) = MockUploader::too_many_requests(usize::MAX, Duration::from_secs(600));
+        let uploader = RateLimitedUploader::new(Box::new(mock), limiter);
+        let err = uploader.upload("a.md", &ArticleOptions::default()).await.unwrap_err();
+        assert!(err.is_rate_limited());
+        assert_eq!(secs(&calls.lock().unwrap()), vec![0, 30, 60]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_upload_without_retries_fails_at_once() {
+        let limiter = Arc::new(RateLimiter::new(60).with_retry_policy(RetryPolicy::NONE));
+        let (mock, calls) = MockUploader::new(1);
+        let uploader = RateLimitedUploader::new(Box::new(mock), Arc::clone(&limiter));
+
+        let err = uploader.upload("a.md", &ArticleOptions::default()).await.unwrap_err();
+        assert!(err.is_rate_limited());
+        assert_eq!(secs(&calls.lock().unwrap()), vec![0]);
+        assert_eq!(limiter.rpm(), 60);
+    }
+
+    #[test]
+    fn test_retry_delay_and_retry_after_parsing() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(0, None), RATE_LIMIT_BACKOFF);
+        assert_eq!(policy.delay(2, None), RATE_LIMIT_BACKOFF * 4);
+        assert_eq!(policy.delay(10, None), DEFAULT_MAX_RETRY_DELAY);
+        assert_eq!(policy.delay(0, Some(Duration::from_secs(2))), Duration::from_secs(2));
+
+        let now = chrono::DateTime::parse_from_rfc2822("Wed, 21 Oct 2015 07:28:00 GMT")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        assert_eq!(parse_retry_after(" 3 ", now), Some(Duration::from_secs(3)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:27:00 GMT", now), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("-1", now), None);
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        assert_eq!(retry_after_header(&headers), None);
+        headers.insert(RETRY_AFTER, "45".parse().unwrap());
+        assert_eq!(retry_after_header(&headers), Some(Duration::from_secs(45)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_pacer_pauses_each_worker_after_a_draft() {
+        let pacer = UploadPacer::new(Duration::from_secs(3), 1);
+        let started = Instant::now();
+
+        // Skipped or failed uploads leave no pause behind
+        drop(pacer.start().await);
+        pacer.start().await.created();
+        assert_eq!(started.elapsed(), Duration::ZERO);
+
+        // The next upload waits out the pause, counted from the draft
+        tokio::time::advance(Duration::from_secs(1)).await;
+        pacer.start().await.created();
+        assert_eq!(started.elapsed(), Duration::from_secs(3));
+
+        // Two workers upload at once, then each pauses before its next draft
+        let pacer = UploadPacer::new(Duration::from_secs(3), 2);
+        let started = Instant::now();
+        let upload = || async {
+            pacer.start().await.created();
+            started.elapsed()
+        };
+        let (first, second) = tokio::join!(upload(), upload());
+        assert_eq!((first, second), (Duration::ZERO, Duration::ZERO));
+        let (third, fourth) = tokio::join!(upload(), upload());
+        assert_eq!((third, fourth), (Duration::from_secs(3), Duration::from_secs(3)));
+
+        // Clones share the workers
+        let shared = pacer.clone();
+        assert_eq!(shared, pacer);
+        shared.start().await.created();
+        assert_eq!(started.elapsed(), Duration::from_secs(6));
+
+        // Without a delay nothing waits
+        let none = UploadPacer::default();
+        none.start().await.created();
+        none.start().await.created();
+        assert_eq!(started.elapsed(), Duration::from_secs(6));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_uploaders_of_an_account_share_its_limiter() {
+        let limiters = RateLimiters::new(30);
+        let (first, first_calls) = MockUploader::new(0);
+        let (second, second_calls) = MockUploader::new(0);
+        let first = RateLimitedUploader::new(Box::new(first), limiters.for_account("main").unwrap());
+        let second =
+            RateLimitedUploader::new(Box::new(second), limiters.for_account("main").unwrap());
+
+        let options = ArticleOptions::default();
+        let uploads = (0..7).map(|i| {
+            let uploader = if i % 2 == 0 { &first } else { &second };
+            uploader.upload("a.md", &options)
+        });
+        for result in futures::future::join_all(uploads).await {
+            result.unwrap();
+        }
+
+        // Seven drafts: five from the burst, then one every two seconds
+        let mut calls: Vec<_> = first_calls.lock().unwrap().clone();
+        calls.extend(second_calls.lock().unwrap().iter());
+        calls.sort();
+        assert_eq!(secs(&calls), vec![0, 0, 0, 0, 0, 2, 4]);
+
+        // Other accounts are limited separately, and a rate of 0 disables limiting
+        let other = limiters.for_account("other").unwrap();
+        assert_eq!(other.acquire().await, Duration::ZERO);
+        assert!(RateLimiters::new(0).for_account("main").is_none());
+    }
+}
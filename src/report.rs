@@ -0,0 +1,931 @@
+//! Upload run reporting
+//!
+//! Collects the per-file outcome of an upload run so callers (and `main`) can
+//! tell "nothing to upload" apart from partial or complete failure, together
+//! with the warnings printed along the way. The report is written as JSON for
+//! scripts and as Markdown for people, both rendered from the same results.
+
+use crate::build_info::BuildInfo;
+use crate::error::{Error, Result};
+use crate::models::PublicationStatus;
+use crate::output::{FORMATTER, OutputFormatter};
+use serde::{Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where the cover of an uploaded file came from
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CoverOutcome {
+    /// The cover named in the frontmatter already existed
+    Existing,
+    /// Generated with an AI provider
+    Generated {
+        /// Name of the provider, e.g. `OpenAI`
+        provider: String,
+    },
+    /// Rendered from the cover template
+    Template,
+    /// The account's `default_cover`, used because none could be generated
+    Default,
+    /// An existing WeChat material named by `cover_media_id`
+    Material,
+    /// The article has no cover
+    Absent,
+}
+
+impl CoverOutcome {
+    /// Returns true if the cover was set in the frontmatter during this run
+    pub fn is_new(&self) -> bool {
+        matches!(self, Self::Generated { .. } | Self::Template | Self::Default)
+    }
+
+    /// Returns the key used in JSON output, e.g. `existing` or `generated:openai`
+    pub fn key(&self) -> String {
+        match self {
+            Self::Existing => "existing".to_string(),
+            Self::Generated { provider } => format!("generated:{}", provider.to_lowercase()),
+            Self::Template => "template".to_string(),
+            Self::Default => "default".to_string(),
+            Self::Material => "material".to_string(),
+            Self::Absent => "absent".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for CoverOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Existing => write!(f, "existing"),
+            Self::Generated { provider } => write!(f, "generated by {}", provider),
+            Self::Template => write!(f, "rendered from template"),
+            Self::Default => write!(f, "default cover"),
+            Self::Material => write!(f, "existing material"),
+            Self::Absent => write!(f, "no cover"),
+        }
+    }
+}
+
+impl Serialize for CoverOutcome {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.key())
+    }
+}
+
+/// Outcome of processing a single file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum FileOutcome {
+    /// Uploaded successfully as a WeChat draft
+    Uploaded {
+        /// Media ID of the created draft
+        draft_id: String,
+        /// Title of the article, if known
+        #[serde(skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+        /// Where the cover came from, if known
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cover: Option<CoverOutcome>,
+    },
+    /// Not uploaded because it did not need to be (e.g. already published)
+    Skipped {
+        /// Why the file was skipped
+        reason: String,
+    },
+    /// Not uploaded because the AI image budget of the run ran out before
+    /// its cover could be generated; a later run picks it up
+    CoverDeferred,
+    /// Processing failed
+    Failed {
+        /// Error message
+        error: String,
+        /// Whether the failure was a WeChat authentication error
+        auth: bool,
+    },
+}
+
+impl FileOutcome {
+    /// Creates the outcome of a file that failed with `error`
+    pub fn failed(error: &Error) -> Self {
+        Self::Failed {
+            error: error.display_chain(),
+            auth: error.is_auth(),
+        }
+    }
+}
+
+/// Result of processing one file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileReport {
+    /// Path of the markdown file
+    pub path: PathBuf,
+    /// Account the file was uploaded with, if the run used more than one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    /// What happened to it
+    #[serde(flatten)]
+    pub outcome: FileOutcome,
+}
+
+/// A warning printed while a file was processed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileWarning {
+    /// Path of the markdown file
+    pub path: PathBuf,
+    /// The warning as printed
+    pub message: String,
+}
+
+/// Warnings of a run, recorded as they are printed
+///
+/// Clones share the same list, so the uploads of a run can record into it
+/// concurrently and the run collects them into its [`UploadReport`].
+#[derive(Debug, Clone, Default)]
+pub struct WarningLog {
+    warnings: Arc<Mutex<Vec<FileWarning>>>,
+}
+
+impl WarningLog {
+    /// Records a warning about `path` without printing it
+    pub fn record(&self, path: &Path, message: impl Into<String>) {
+        self.lock().push(FileWarning {
+            path: path.to_path_buf(),
+            message: message.into(),
+        });
+    }
+
+    /// Prints a warning and records it for `path`
+    pub fn warn(&self, path: &Path, message: &str) {
+        FORMATTER.print_warning(message);
+        self.record(path, message);
+    }
+
+    /// Prints a warning naming `path` and records it
+    pub fn warn_file(&self, path: &Path, message: &str) {
+        FORMATTER.print_file_warning(path, None, message);
+        self.record(path, message);
+    }
+
+    /// Removes and returns the warnings recorded so far
+    pub fn take(&self) -> Vec<FileWarning> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<FileWarning>> {
+        // A panic while pushing leaves the list intact
+        self.warnings.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Logs are equal if they are the same shared list
+impl PartialEq for WarningLog {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.warnings, &other.warnings)
+    }
+}
+
+impl Eq for WarningLog {}
+
+/// Summary of an upload run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UploadReport {
+    /// Per-file results in processing order
+    pub files: Vec<FileReport>,
+    /// Warnings printed during the run, in the order they were printed
+    pub warnings: Vec<FileWarning>,
+}
+
+impl UploadReport {
+    /// Creates an empty report
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the outcome for a file
+    pub fn record(&mut self, path: impl Into<PathBuf>, outcome: FileOutcome) {
+        self.files.push(FileReport {
+            path: path.into(),
+            account: None,
+            outcome,
+        });
+    }
+
+    /// Records the outcome for a file uploaded with a specific account
+    pub fn record_for_account(
+        &mut self,
+        path: impl Into<PathBuf>,
+        account: impl Into<String>,
+        outcome: FileOutcome,
+    ) {
+        self.files.push(FileReport {
+            path: path.into(),
+            account: Some(account.into()),
+            outcome,
+        });
+    }
+
+    /// Sets the account of every result that does not name one yet
+    pub fn tag_account(&mut self, account: &str) {
+        for file in &mut self.files {
+            file.account.get_or_insert_with(|| account.to_string());
+        }
+    }
+
+    /// Returns the accounts named in the report, in order of first appearance
+    pub fn accounts(&self) -> Vec<&str> {
+        let mut accounts = Vec::new();
+        for account in self.files.iter().filter_map(|file| file.account.as_deref()) {
+            if !accounts.contains(&account) {
+                accounts.push(account);
+            }
+        }
+        accounts
+    }
+
+    /// Returns the results of a single account
+    ///
+    /// Warnings are not recorded per account, so the returned report has none.
+    pub fn for_account(&self, account: &str) -> UploadReport {
+        UploadReport {
+            files: self
+                .files
+                .iter()
+                .filter(|file| file.account.as_deref() == Some(account))
+                .cloned()
+                .collect(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Records a successful upload
+    pub fn record_uploaded(&mut self, path: impl Into<PathBuf>, draft_id: impl Into<String>) {
+        self.record(
+            path,
+            FileOutcome::Uploaded {
+                draft_id: draft_id.into(),
+                title: None,
+                cover: None,
+            },
+        );
+    }
+
+    /// Records a skipped file
+    pub fn record_skipped(&mut self, path: impl Into<PathBuf>, reason: impl Into<String>) {
+        self.record(
+            path,
+            FileOutcome::Skipped {
+                reason: reason.into(),
+            },
+        );
+    }
+
+    /// Records a failed file
+    pub fn record_failed(&mut self, path: impl Into<PathBuf>, error: &Error) {
+        self.record(path, FileOutcome::failed(error));
+    }
+
+    /// Appends all results and warnings of another report
+    pub fn merge(&mut self, other: UploadReport) {
+        self.files.extend(other.files);
+        self.warnings.extend(other.warnings);
+    }
+
+    /// Number of uploaded files
+    pub fn uploaded(&self) -> usize {
+        self.count(|outcome| matches!(outcome, FileOutcome::Uploaded { .. }))
+    }
+
+    /// Number of skipped files
+    pub fn skipped(&self) -> usize {
+        self.count(|outcome| matches!(outcome, FileOutcome::Skipped { .. }))
+    }
+
+    /// Number of files whose cover was deferred by the AI image budget
+    pub fn cover_deferred(&self) -> usize {
+        self.count(|outcome| matches!(outcome, FileOutcome::CoverDeferred))
+    }
+
+    /// Number of failed files
+    pub fn failed(&self) -> usize {
+        self.count(|outcome| matches!(outcome, FileOutcome::Failed { .. }))
+    }
+
+    /// Returns the files skipped because they were already published
+    ///
+    /// A file skipped for several accounts is listed once.
+    pub fn skipped_published(&self) -> Vec<&Path> {
+        let published = PublicationStatus::Published.skip_reason();
+        let mut paths: Vec<&Path> = Vec::new();
+        for file in &self.files {
+            if let FileOutcome::Skipped { reason } = &file.outcome
+                && Some(reason.as_str()) == published
+                && !paths.contains(&file.path.as_path())
+            {
+                paths.push(&file.path);
+            }
+        }
+        paths
+    }
+
+    /// One-line count of the files skipped because they were already
+    /// published, e.g. `skipped 214 already-published files (use --show-skipped to list)`
+    ///
+    /// Returns `None` if no file was.
+    pub fn skipped_published_summary(&self) -> Option<String> {
+        let count = self.skipped_published().len();
+        (count > 0).then(|| {
+            format!(
+                "skipped {} already-published {} (use --show-skipped to list)",
+                count,
+                if count == 1 { "file" } else { "files" }
+            )
+        })
+    }
+
+    /// Returns true if any file failed with a WeChat authentication error
+    pub fn has_auth_failure(&self) -> bool {
+        self.files
+            .iter()
+            .any(|file| matches!(file.outcome, FileOutcome::Failed { auth: true, .. }))
+    }
+
+    /// Returns the failed files with their error messages
+    ///
+    /// Failures of a multi-account run name the account, e.g. `[north] boom`.
+    pub fn failures(&self) -> impl Iterator<Item = (&Path, String)> {
+        self.files.iter().filter_map(|file| match &file.outcome {
+            FileOutcome::Failed { error, .. } => Some((
+                file.path.as_path(),
+                match &file.account {
+                    Some(account) => format!("[{}] {}", account, error),
+                    None => error.clone(),
+                },
+            )),
+            _ => None,
+        })
+    }
+
+    /// One-line human readable summary, e.g. `3 uploaded, 1 skipped, 0 failed`
+    ///
+    /// Files deferred by the AI image budget and warnings are only mentioned
+    /// if there are any.
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{} uploaded, {} skipped, {} failed",
+            self.uploaded(),
+            self.skipped(),
+            self.failed()
+        );
+        let deferred = self.cover_deferred();
+        if deferred > 0 {
+            summary.push_str(&format!(", {} cover deferred", deferred));
+        }
+        match self.warnings.len() {
+            0 => {}
+            1 => summary.push_str(", 1 warning"),
+            count => summary.push_str(&format!(", {} warnings", count)),
+        }
+        summary
+    }
+
+    /// Returns how many uploaded files got their cover from each source
+    ///
+    /// A file uploaded to several accounts is counted once.
+    pub fn cover_counts(&self) -> BTreeMap<CoverOutcome, usize> {
+        let mut seen = Vec::new();
+        let mut counts = BTreeMap::new();
+        for file in &self.files {
+            if let FileOutcome::Uploaded { cover: Some(cover), .. } = &file.outcome
+                && !seen.contains(&&file.path)
+            {
+                seen.push(&file.path);
+                *counts.entry(cover.clone()).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    /// Returns the uploaded files with the source of their cover
+    pub fn covers(&self) -> impl Iterator<Item = (&Path, &CoverOutcome)> {
+        let mut seen = Vec::new();
+        self.files.iter().filter_map(move |file| match &file.outcome {
+            FileOutcome::Uploaded { cover: Some(cover), .. } if !seen.contains(&&file.path) => {
+                seen.push(&file.path);
+                Some((file.path.as_path(), cover))
+            }
+            _ => None,
+        })
+    }
+
+    /// One-line summary of the cover sources, e.g. `covers: 2 existing, 1 generated by OpenAI`
+    ///
+    /// Returns `None` if no uploaded file has a known cover source.
+    pub fn cover_summary(&self) -> Option<String> {
+        let counts = self.cover_counts();
+        if counts.is_empty() {
+            return None;
+        }
+        let parts: Vec<String> = counts
+            .iter()
+            .map(|(cover, count)| format!("{} {}", count, cover))
+            .collect();
+        Some(format!("covers: {}", parts.join(", ")))
+    }
+
+    /// Returns the report as JSON: the build that ran, the per-file results,
+    /// the warnings and the totals of the run
+    pub fn to_json(&self) -> serde_json::Value {
+        let covers: BTreeMap<String, usize> = self
+            .cover_counts()
+            .into_iter()
+            .map(|(cover, count)| (cover.key(), count))
+            .collect();
+        serde_json::json!({
+            "build": BuildInfo::current(),
+            "files": self.files,
+            "warnings": self.warnings,
+            "totals": {
+                "uploaded": self.uploaded(),
+                "skipped": self.skipped(),
+                "cover_deferred": self.cover_deferred(),
+                "failed": self.failed(),
+                "warnings": self.warnings.len(),
+                "covers": covers,
+            },
+        })
+    }
+
+    /// Writes the report as pretty-printed JSON to `path`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written
+    pub async fn write_json(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_string_pretty(&self.to_json())?;
+        tokio::fs::write(path, content + "\n").await?;
+        Ok(())
+    }
+
+    /// Returns the report as Markdown, e.g. to paste into a team chat
+    ///
+    /// Lists the same per-file results, warnings and totals as [`UploadReport::to_json`]
+    /// under a header with `metadata`. Sections without files are left out.
+    pub fn to_markdown(&self, metadata: &RunMetadata) -> String {
+        let mut out = String::from("# wx-uploader run report\n\n");
+        out.push_str(&format!("- Account: {}\n", metadata.account));
+        out.push_str(&format!("- Finished: {}\n", format_utc(metadata.finished_at)));
+        out.push_str(&format!("- Version: {}\n", metadata.version));
+        if let Some(commit) = &metadata.commit {
+            out.push_str(&format!("- Commit: {}\n", commit));
+        }
+
+        // Multi-account runs name the account of every file
+        let with_account = self.files.iter().any(|file| file.account.is_some());
+        let mut uploaded = Vec::new();
+        let mut skipped = Vec::new();
+        let mut failed = Vec::new();
+        for file in &self.files {
+            let mut row = vec![format!("`{}`", file.path.display())];
+            if with_account {
+                row.push(file.account.clone().unwrap_or_default());
+            }
+            match &file.outcome {
+                FileOutcome::Uploaded {
+                    draft_id,
+                    title,
+                    cover,
+                } => {
+                    row.push(title.clone().unwrap_or_default());
+                    row.push(format!("`{}`", draft_id));
+                    row.push(cover.as_ref().map(ToString::to_string).unwrap_or_default());
+                    uploaded.push(row);
+                }
+                FileOutcome::Skipped { reason } => {
+                    row.push(reason.clone());
+                    skipped.push(row);
+                }
+                FileOutcome::CoverDeferred => {
+                    row.push("cover deferred: the AI image budget of the run ran out".to_string());
+                    skipped.push(row);
+                }
+                FileOutcome::Failed { error, .. } => {
+                    row.push(error.clone());
+                    failed.push(row);
+                }
+            }
+        }
+
+        let columns = |names: &[&'static str]| {
+            let mut columns = vec!["File"];
+            if with_account {
+                columns.push("Account");
+            }
+            columns.extend_from_slice(names);
+            columns
+        };
+        push_table(&mut out, "Uploaded", &columns(&["Title", "Draft ID", "Cover"]), &uploaded);
+        push_table(&mut out, "Skipped", &columns(&["Reason"]), &skipped);
+        push_table(&mut out, "Failed", &columns(&["Error"]), &failed);
+        let warnings: Vec<Vec<String>> = self
+            .warnings
+            .iter()
+            .map(|warning| vec![format!("`{}`", warning.path.display()), warning.message.clone()])
+            .collect();
+        push_table(&mut out, "Warnings", &["File", "Warning"], &warnings);
+
+        let covers = self.cover_counts();
+        if !covers.is_empty() {
+            out.push_str("\n## Covers\n\n");
+            for (cover, count) in covers {
+                out.push_str(&format!("- {} {}\n", count, cover));
+            }
+        }
+
+        out.push_str("\n## Totals\n\n");
+        out.push_str("| Uploaded | Skipped | Cover deferred | Failed |\n");
+        out.push_str("|---:|---:|---:|---:|\n");
+        out.push_str(&format!(
+            "| {} | {} | {} | {} |\n",
+            self.uploaded(),
+            self.skipped(),
+            self.cover_deferred(),
+            self.failed()
+        ));
+        out
+    }
+
+    /// Writes the report as Markdown to `path`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written
+    pub async fn write_markdown(&self, path: &Path, metadata: &RunMetadata) -> Result<()> {
+        tokio::fs::write(path, self.to_markdown(metadata)).await?;
+        Ok(())
+    }
+
+    fn count(&self, predicate: impl Fn(&FileOutcome) -> bool) -> usize {
+        self.files
+            .iter()
+            .filter(|file| predicate(&file.outcome))
+            .count()
+    }
+}
+
+/// Context of a run shown at the top of the Markdown report
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunMetadata {
+    /// Account, or comma-separated accounts, the run uploaded with
+    pub account: String,
+    /// When the run finished, in seconds since the Unix epoch
+    pub finished_at: u64,
+    /// Version of wx-uploader
+    pub version: String,
+    /// Git commit wx-uploader was built from, if known
+    pub commit: Option<String>,
+}
+
+impl RunMetadata {
+    /// Creates the metadata of a run with `account` that finishes now
+    pub fn new(account: impl Into<String>) -> Self {
+        Self {
+            account: account.into(),
+            finished_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            commit: BuildInfo::current().git_commit,
+        }
+    }
+}
+
+/// Appends a table of `rows` under a `##` heading, unless there are none
+fn push_table(out: &mut String, heading: &str, columns: &[&str], rows: &[Vec<String>]) {
+    if rows.is_empty() {
+        return;
+    }
+    out.push_str(&format!("\n## {} ({})\n\n", heading, rows.len()));
+    out.push_str(&format!("| {} |\n", columns.join(" | ")));
+    out.push_str(&format!("|{}\n", "---|".repeat(columns.len())));
+    for row in rows {
+        // A pipe or line break would end the cell or the row
+        let cells: Vec<String> = row
+            .iter()
+            .map(|cell| cell.replace('|', "\\|").replace(['\r', '\n'], " "))
+            .collect();
+        out.push_str(&format!("| {} |\n", cells.join(" | ")));
+    }
+}
+
+/// Formats seconds since the Unix epoch as `YYYY-MM-DD HH:MM:SS UTC`
+fn format_utc(secs: u64) -> String {
+    // Civil date from days since 1970-01-01, after Howard Hinnant's `civil_from_days`
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    let time = secs % 86_400;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        time / 3_600,
+        time % 3_600 / 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_counts() {
+        let mut report = UploadReport::new();
+        report.record_uploaded("a.md", "draft-1");
+        report.record_skipped("b.md", "already published");
+        report.record_failed("c.md", &Error::wechat("boom"));
+
+        assert_eq!(report.uploaded(), 1);
+        assert_eq!(report.skipped(), 1);
+        assert_eq!(report.failed(), 1);
+        assert!(!report.has_auth_failure());
+        assert_eq!(report.summary(), "1 uploaded, 1 skipped, 1 failed");
+
+        let failures: Vec<_> = report.failures().collect();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0, Path::new("c.md"));
+    }
+
+    #[test]
+    fn test_report_cover_deferred() {
+        let mut report = UploadReport::new();
+        report.record_uploaded("a.md", "draft-1");
+        report.record("b.md", FileOutcome::CoverDeferred);
+        report.record("c.md", FileOutcome::CoverDeferred);
+
+        assert_eq!(report.cover_deferred(), 2);
+        assert_eq!(report.failed(), 0);
+        assert_eq!(report.summary(), "1 uploaded, 0 skipped, 0 failed, 2 cover deferred");
+    }
+
+    #[test]
+    fn test_report_skipped_published_summary() {
+        let mut report = UploadReport::new();
+        report.record_uploaded("a.md", "draft-1");
+        report.record_skipped("b.md", "marked skip");
+        assert_eq!(report.skipped_published_summary(), None);
+
+        report.record_for_account("c.md", "north", FileOutcome::Skipped {
+            reason: "already published".to_string(),
+        });
+        assert_eq!(
+            report.skipped_published_summary().as_deref(),
+            Some("skipped 1 already-published file (use --show-skipped to list)")
+        );
+
+        // A file skipped for several accounts counts once
+        report.record_for_account("c.md", "south", FileOutcome::Skipped {
+            reason: "already published".to_string(),
+        });
+        report.record_skipped("d.md", "already published");
+        assert_eq!(report.skipped_published(), vec![Path::new("c.md"), Path::new("d.md")]);
+        assert_eq!(
+            report.skipped_published_summary().as_deref(),
+            Some("skipped 2 already-published files (use --show-skipped to list)")
+        );
+        assert_eq!(report.skipped(), 4);
+    }
+
+    #[test]
+    fn test_report_auth_failure_and_merge() {
+        let mut report = UploadReport::new();
+        let mut other = UploadReport::new();
+        other.record_failed("a.md", &Error::auth("invalid appsecret"));
+
+        report.merge(other);
+        assert!(report.has_auth_failure());
+        assert_eq!(report.files.len(), 1);
+    }
+
+    #[test]
+    fn test_report_per_account_breakdown() {
+        let mut report = UploadReport::new();
+        report.record_for_account(
+            "a.md",
+            "north",
+            FileOutcome::Uploaded {
+                draft_id: "n-1".to_string(),
+                title: None,
+                cover: None,
+            },
+        );
+        report.record_failed("a.md", &Error::wechat("boom"));
+        report.tag_account("south");
+        report.record_skipped("b.md", "already published");
+        report.tag_account("north");
+
+        assert_eq!(report.accounts(), vec!["north", "south"]);
+        assert_eq!(report.for_account("north").summary(), "1 uploaded, 1 skipped, 0 failed");
+        assert_eq!(report.for_account("south").summary(), "0 uploaded, 0 skipped, 1 failed");
+
+        let failures: Vec<_> = report.failures().collect();
+        assert_eq!(failures, vec![(Path::new("a.md"), "[south] WeChat API error: boom".to_string())]);
+    }
+
+    #[test]
+    fn test_report_collects_warnings() {
+        let log = WarningLog::default();
+        let shared = log.clone();
+        shared.record(Path::new("a.md"), "theme fallback not applied");
+        log.record(Path::new("b.md"), "cover not optimized: bad PNG");
+        assert_eq!(log, shared);
+        assert_ne!(log, WarningLog::default());
+
+        let mut report = UploadReport::new();
+        report.record_uploaded("a.md", "draft-a");
+        report.warnings = log.take();
+        assert!(shared.take().is_empty());
+        assert_eq!(report.summary(), "1 uploaded, 0 skipped, 0 failed, 2 warnings");
+
+        let json = report.to_json();
+        assert_eq!(json["totals"]["warnings"], 2);
+        assert_eq!(json["warnings"][1]["path"], "b.md");
+        assert_eq!(json["warnings"][1]["message"], "cover not optimized: bad PNG");
+
+        let markdown = report.to_markdown(&RunMetadata::new("main"));
+        assert!(
+            markdown.contains("## Warnings (2)\n\n| File | Warning |\n|---|---|\n\
+                               | `a.md` | theme fallback not applied |\n"),
+            "{}",
+            markdown
+        );
+
+        // Warnings are kept when merging, but not per account
+        let mut merged = UploadReport::new();
+        merged.merge(report.clone());
+        assert_eq!(merged.warnings.len(), 2);
+        report.tag_account("main");
+        assert!(report.for_account("main").warnings.is_empty());
+    }
+
+    fn uploaded(cover: CoverOutcome) -> FileOutcome {
+        FileOutcome::Uploaded {
+            draft_id: "d".to_string(),
+            title: None,
+            cover: Some(cover),
+        }
+    }
+
+    #[test]
+    fn test_report_cover_summary_and_json() {
+        let openai = CoverOutcome::Generated {
+            provider: "OpenAI".to_string(),
+        };
+        let mut report = UploadReport::new();
+        report.record("a.md", uploaded(CoverOutcome::Existing));
+        report.record_for_account("b.md", "north", uploaded(openai.clone()));
+        report.record_for_account("b.md", "south", uploaded(openai.clone()));
+        report.record("c.md", uploaded(CoverOutcome::Existing));
+        report.record("d.md", uploaded(CoverOutcome::Default));
+        report.record("e.md", FileOutcome::CoverDeferred);
+        report.record_uploaded("f.md", "draft-f");
+
+        // A file uploaded to two accounts counts once; unknown covers are left out
+        assert_eq!(
+            report.cover_summary().as_deref(),
+            Some("covers: 2 existing, 1 generated by OpenAI, 1 default cover")
+        );
+        let covers: Vec<_> = report.covers().map(|(path, cover)| (path, cover.key())).collect();
+        assert_eq!(covers[1], (Path::new("b.md"), "generated:openai".to_string()));
+        assert_eq!(covers.len(), 4);
+        assert_eq!(UploadReport::new().cover_summary(), None);
+
+        let json = report.to_json();
+        assert_eq!(
+            json["files"][1],
+            serde_json::json!({
+                "path": "b.md",
+                "account": "north",
+                "status": "uploaded",
+                "draft_id": "d",
+                "cover": "generated:openai",
+            })
+        );
+        assert_eq!(
+            json["files"][5],
+            serde_json::json!({"path": "e.md", "status": "cover_deferred"})
+        );
+        assert!(json["files"][6].get("cover").is_none());
+        assert_eq!(json["totals"]["uploaded"], 6);
+        assert_eq!(json["build"]["version"], env!("CARGO_PKG_VERSION"));
+        assert!(json["build"].get("git_commit").is_some(), "{}", json["build"]);
+        assert_eq!(
+            json["totals"]["covers"],
+            serde_json::json!({"existing": 2, "generated:openai": 1, "default": 1})
+        );
+    }
+
+    fn metadata() -> RunMetadata {
+        RunMetadata {
+            account: "main".to_string(),
+            finished_at: 1_700_000_000,
+            version: "0.5.2".to_string(),
+            commit: Some("1a2b3c4".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_report_to_markdown() {
+        let mut report = UploadReport::new();
+        report.record(
+            "posts/a.md",
+            FileOutcome::Uploaded {
+                draft_id: "media-1".to_string(),
+                title: Some("Rust | Async".to_string()),
+                cover: Some(CoverOutcome::Generated {
+                    provider: "OpenAI".to_string(),
+                }),
+            },
+        );
+        report.record("posts/b.md", uploaded(CoverOutcome::Existing));
+        report.record_skipped("posts/c.md", "already published");
+        report.record("posts/d.md", FileOutcome::CoverDeferred);
+        report.record_failed("posts/e.md", &Error::wechat("quota\nexceeded"));
+
+        assert_eq!(
+            report.to_markdown(&metadata()),
+            "# wx-uploader run report
+
+- Account: main
+- Finished: 2023-11-14 22:13:20 UTC
+- Version: 0.5.2
+- Commit: 1a2b3c4
+
+## Uploaded (2)
+
+| File | Title | Draft ID | Cover |
+|---|---|---|---|
+| `posts/a.md` | Rust \\| Async | `media-1` | generated by OpenAI |
+| `posts/b.md` |  | `d` | existing |
+
+## Skipped (2)
+
+| File | Reason |
+|---|---|
+| `posts/c.md` | already published |
+| `posts/d.md` | cover deferred: the AI image budget of the run ran out |
+
+## Failed (1)
+
+| File | Error |
+|---|---|
+| `posts/e.md` | WeChat API error: quota exceeded |
+
+## Covers
+
+- 1 existing
+- 1 generated by OpenAI
+
+## Totals
+
+| Uploaded | Skipped | Cover deferred | Failed |
+|---:|---:|---:|---:|
+| 2 | 1 | 1 | 1 |
+"
+        );
+    }
+
+    #[test]
+    fn test_report_to_markdown_multi_account_and_empty() {
+        let mut report = UploadReport::new();
+        report.record_for_account("a.md", "north", uploaded(CoverOutcome::Absent));
+        report.record_for_account("a.md", "south", FileOutcome::failed(&Error::wechat("boom")));
+        let markdown = report.to_markdown(&metadata());
+        let header = "| File | Account | Title | Draft ID | Cover |\n";
+        assert!(markdown.contains(header), "{}", markdown);
+        assert!(markdown.contains("| `a.md` | south | WeChat API error: boom |\n"), "{}", markdown);
+
+        let empty = UploadReport::new().to_markdown(&metadata());
+        assert!(!empty.contains("## Uploaded") && !empty.contains("## Covers"), "{}", empty);
+        assert!(empty.ends_with("| 0 | 0 | 0 | 0 |\n"));
+    }
+
+    #[test]
+    fn test_format_utc() {
+        assert_eq!(format_utc(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_utc(951_782_400), "2000-02-29 00:00:00 UTC");
+        assert_eq!(format_utc(1_700_000_000), "2023-11-14 22:13:20 UTC");
+    }
+}
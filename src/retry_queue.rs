@@ -0,0 +1,387 @@
+//! Queue of failed uploads
+//!
+//! At the end of an upload run, failed files are recorded in
+//! `.wx-uploader/retry.json` in the working directory, with their account,
+//! error, number of failed attempts and a hash of the options of the run.
+//! Files that upload (or are skipped) in a later run leave the queue.
+//!
+//! `wx-uploader retry` re-attempts only the queued files. An entry recorded
+//! with other options than the current ones stays queued untouched, so a
+//! file is never retried with settings it did not fail with. An entry whose
+//! attempts reach `retry_max_attempts` is dropped and reported as given up.
+//! The queue is rewritten after each file, so an interrupted retry keeps the
+//! entries it already cleared out of the queue.
+
+use crate::backup::DATA_DIR;
+use crate::error::Result;
+use crate::models::Config;
+use crate::provenance::config_entries;
+use crate::report::{FileOutcome, UploadReport};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Attempts a failed upload gets when `retry_max_attempts` is not set
+pub const DEFAULT_RETRY_MAX_ATTEMPTS: u32 = 3;
+
+/// Settings that change how a run is carried out or reported, not what is
+/// uploaded, and so are left out of [`options_hash`]
+const RUN_ONLY_SETTINGS: &[&str] = &[
+    "verbose",
+    "stream_scene",
+    "trace_http",
+    "trace_body_limit",
+    "pipeline",
+    "wechat_rpm",
+    "max_retries",
+    "max_retry_delay",
+    "upload_delay_ms",
+    "retry_max_attempts",
+    "max_ai_images_per_run",
+    "max_ai_cost_per_run",
+    "confirm_ai_cost_above",
+    "only",
+    "follow_symlinks",
+    "fail_fast",
+    "allowed_hours",
+    "ignore_schedule",
+    "backup",
+    "backup_keep",
+];
+
+/// Returns the retry queue file of `work_dir`
+pub fn retry_queue_path(work_dir: &Path) -> PathBuf {
+    work_dir.join(DATA_DIR).join("retry.json")
+}
+
+/// Returns a short hash of the options that decide what an upload produces
+///
+/// Secrets are masked before hashing, and settings that only affect how the
+/// run is carried out, such as `verbose`, are left out.
+pub fn options_hash(config: &Config) -> String {
+    let options: serde_json::Map<_, _> = config_entries(config)
+        .into_iter()
+        .filter(|entry| !RUN_ONLY_SETTINGS.contains(&entry.key))
+        .map(|entry| (entry.key.to_string(), entry.value))
+        .collect();
+    let json = serde_json::Value::Object(options).to_string();
+    blake3::hash(json.as_bytes()).to_hex()[..16].to_string()
+}
+
+/// A failed upload waiting for `wx-uploader retry`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryEntry {
+    /// Absolute path of the markdown file
+    pub path: PathBuf,
+    /// Account the upload was made with; the current account if absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    /// Error of the latest attempt
+    pub error: String,
+    /// Number of failed attempts so far
+    pub attempts: u32,
+    /// [`options_hash`] of the run the file failed in
+    pub options_hash: String,
+    /// Whether the file was uploaded even if already published, as when it
+    /// is named on the command line
+    #[serde(default)]
+    pub force: bool,
+}
+
+impl RetryEntry {
+    fn is_for(&self, path: &Path, account: Option<&str>) -> bool {
+        self.path == path && self.account.as_deref() == account
+    }
+}
+
+/// What happened to a queued entry after another attempt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Settled {
+    /// The file no longer failed and left the queue
+    Cleared,
+    /// The file failed again, or its cover was deferred, and stays queued
+    Queued,
+    /// The file failed for the last allowed time; the dropped entry
+    GivenUp(RetryEntry),
+}
+
+/// Failed uploads, in the order they were first recorded
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryQueue {
+    /// Queued uploads
+    pub entries: Vec<RetryEntry>,
+}
+
+impl RetryQueue {
+    /// Loads the queue file, or returns an empty queue if it does not exist
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read or parsed
+    pub async fn load(path: &Path) -> Result<Self> {
+        match tokio::fs::read_to_string(path).await {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes the queue file, creating its directory if needed
+    ///
+    /// The file is replaced in one step, so an interrupted write leaves the
+    /// previous queue in place.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let content = serde_json::to_string_pretty(self)?;
+        let partial = path.with_extension("json.partial");
+        tokio::fs::write(&partial, content).await?;
+        tokio::fs::rename(&partial, path).await?;
+        Ok(())
+    }
+
+    /// Returns whether no upload is queued
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Records the outcome of an upload
+    ///
+    /// A failure is queued, or counts another attempt of a queued entry. A
+    /// file whose cover was deferred was not attempted and keeps its entry;
+    /// any other outcome takes the file out of the queue.
+    pub fn record(
+        &mut self,
+        path: &Path,
+        account: Option<&str>,
+        outcome: &FileOutcome,
+        options_hash: &str,
+        force: bool,
+    ) {
+        if matches!(outcome, FileOutcome::CoverDeferred) {
+            return;
+        }
+        let path = queue_key(path);
+        let position = self.entries.iter().position(|e| e.is_for(&path, account));
+        let FileOutcome::Failed { error, .. } = outcome else {
+            if let Some(index) = position {
+                self.entries.remove(index);
+            }
+            return;
+        };
+
+        match position {
+            Some(index) => {
+                let entry = &mut self.entries[index];
+                entry.error = error.clone();
+                entry.attempts += 1;
+                entry.options_hash = options_hash.to_string();
+                entry.force = force;
+            }
+            None => self.entries.push(RetryEntry {
+                path,
+                account: account.map(str::to_string),
+                error: error.clone(),
+                attempts: 1,
+                options_hash: options_hash.to_string(),
+                force,
+            }),
+        }
+    }
+
+    /// Records every file of a report and returns whether the queue changed
+    pub fn record_report(&mut self, report: &UploadReport, options_hash: &str, force: bool) -> bool {
+        let before = self.entries.clone();
+        for file in &report.files {
+            self.record(&file.path, file.account.as_deref(), &file.outcome, options_hash, force);
+        }
+        self.entries != before
+    }
+
+    /// Records the outcome of retrying a queued entry
+    ///
+    /// A failure that reaches `max_attempts` drops the entry.
+    pub fn settle(&mut self, entry: &RetryEntry, outcome: &FileOutcome, max_attempts: u32) -> Settled {
+        if matches!(outcome, FileOutcome::CoverDeferred) {
+            return Settled::Queued;
+        }
+        self.record(&entry.path, entry.account.as_deref(), outcome, &entry.options_hash, entry.force);
+        let Some(index) = self
+            .entries
+            .iter()
+            .position(|e| e.is_for(&entry.path, entry.account.as_deref()))
+        else {
+            return Settled::Cleared;
+        };
+        if self.entries[index].attempts >= max_attempts {
+            return Settled::GivenUp(self.entries.remove(index));
+        }
+        Settled::Queued
+    }
+}
+
+/// Records the failures of a run in the queue file, and clears the files
+/// that no longer fail
+///
+/// The file is only written if the queue changed.
+///
+/// # Errors
+///
+/// Returns an error if the queue file cannot be read, parsed or written
+pub async fn record_failures(
+    queue_file: &Path,
+    report: &UploadReport,
+    options_hash: &str,
+    force: bool,
+) -> Result<()> {
+    let mut queue = RetryQueue::load(queue_file).await?;
+    if queue.record_report(report, options_hash, force) {
+        queue.save(queue_file).await?;
+    }
+    Ok(())
+}
+
+/// Outcome of a `wx-uploader retry` run
+#[derive(Debug, Default)]
+pub struct RetryReport {
+    /// Results of the files that were retried
+    pub report: UploadReport,
+    /// Number of retried files that left the queue
+    pub cleared: usize,
+    /// Entries dropped after their last allowed attempt
+    pub given_up: Vec<RetryEntry>,
+    /// Entries left alone because they were queued with other options
+    pub held: Vec<RetryEntry>,
+    /// Entries still queued after the run
+    pub remaining: usize,
+}
+
+impl RetryReport {
+    /// Returns a one-line summary of the run
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "retried {} file(s): {} cleared, {} still queued, {} given up",
+            self.report.files.len(),
+            self.cleared,
+            self.remaining - self.held.len(),
+            self.given_up.len()
+        );
+        if !self.held.is_empty() {
+            summary.push_str(&format!(", {} queued with other options", self.held.len()));
+        }
+        summary
+    }
+}
+
+fn queue_key(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use tempfile::TempDir;
+
+    fn failure(message: &str) -> FileOutcome {
+        FileOutcome::failed(&Error::generic(message))
+    }
+
+    #[tokio::test]
+    async fn test_record_failures_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let article = temp_dir.path().join("a.md");
+        let other = temp_dir.path().join("b.md");
+        std::fs::write(&article, "# A").unwrap();
+        std::fs::write(&other, "# B").unwrap();
+        let queue_file = retry_queue_path(temp_dir.path());
+
+        let mut report = UploadReport::new();
+        report.record_failed(&article, &Error::generic("boom"));
+        report.record_uploaded(&other, "MEDIA_1");
+        record_failures(&queue_file, &report, "hash", true).await.unwrap();
+
+        let queue = RetryQueue::load(&queue_file).await.unwrap();
+        assert_eq!(queue.entries.len(), 1);
+        let entry = &queue.entries[0];
+        assert_eq!(entry.path, article.canonicalize().unwrap());
+        assert_eq!(entry.account, None);
+        assert_eq!(entry.attempts, 1);
+        assert_eq!(entry.options_hash, "hash");
+        assert!(entry.force);
+        assert!(entry.error.contains("boom"));
+
+        // A later success clears the entry
+        let mut report = UploadReport::new();
+        report.record_uploaded(&article, "MEDIA_2");
+        record_failures(&queue_file, &report, "hash", false).await.unwrap();
+        assert!(RetryQueue::load(&queue_file).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_record_failures_without_failures_writes_nothing() {
+        let temp_dir = TempDir::new().unwrap();
+        let queue_file = retry_queue_path(temp_dir.path());
+
+        let mut report = UploadReport::new();
+        report.record_uploaded(temp_dir.path().join("a.md"), "MEDIA_1");
+        record_failures(&queue_file, &report, "hash", false).await.unwrap();
+        assert!(!queue_file.exists());
+    }
+
+    #[test]
+    fn test_settle_counts_attempts_up_to_max() {
+        let path = PathBuf::from("/articles/a.md");
+        let mut queue = RetryQueue::default();
+        queue.record(&path, Some("north"), &failure("first"), "hash", false);
+        let entry = queue.entries[0].clone();
+
+        assert_eq!(queue.settle(&entry, &failure("second"), 3), Settled::Queued);
+        assert_eq!(queue.entries[0].attempts, 2);
+        assert!(queue.entries[0].error.contains("second"));
+        let Settled::GivenUp(dropped) = queue.settle(&entry, &failure("third"), 3) else {
+            panic!("entry not given up after 3 attempts");
+        };
+        assert_eq!(dropped.attempts, 3);
+        assert!(dropped.error.contains("third"));
+        assert!(queue.is_empty());
+
+        queue.record(&path, Some("north"), &failure("first"), "hash", false);
+        let cleared = FileOutcome::Skipped { reason: "already published".to_string() };
+        assert_eq!(queue.settle(&entry, &cleared, 3), Settled::Cleared);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_entries_are_kept_per_account() {
+        let path = PathBuf::from("/articles/a.md");
+        let mut queue = RetryQueue::default();
+        queue.record(&path, Some("north"), &failure("boom"), "hash", false);
+        queue.record(&path, Some("south"), &failure("boom"), "hash", false);
+        assert_eq!(queue.entries.len(), 2);
+
+        queue.record(&path, Some("north"), &FileOutcome::CoverDeferred, "hash", false);
+        assert_eq!(queue.entries.len(), 2);
+        queue.record(&path, Some("north"), &FileOutcome::Skipped { reason: "draft".into() }, "hash", false);
+        assert_eq!(queue.entries.len(), 1);
+        assert_eq!(queue.entries[0].account.as_deref(), Some("south"));
+    }
+
+    #[test]
+    fn test_options_hash_ignores_run_only_settings() {
+        let config = Config::new("app".to_string(), "secret".to_string(), None, false);
+        let mut verbose = config.clone();
+        verbose.verbose = true;
+        verbose.retry_max_attempts = 9;
+        assert_eq!(options_hash(&config), options_hash(&verbose));
+
+        let mut original = config.clone();
+        original.original = true;
+        assert_ne!(options_hash(&config), options_hash(&original));
+        assert_eq!(options_hash(&config).len(), 16);
+    }
+}
@@ -0,0 +1,104 @@
+//! Cache of AI scene descriptions
+//!
+//! A cover is generated from a scene description the AI writes for the
+//! article. Descriptions are kept in the cache directory, one file per
+//! request, so regenerating a cover for unchanged content reuses the earlier
+//! description instead of paying for another chat request.
+//!
+//! An entry is named after a hash of everything sent with the request (the
+//! article content, its language and the prompt) plus the text model. Editing
+//! the article changes the hash, so a description of older content is never
+//! used. `--no-cache` bypasses the cache.
+
+use crate::error::Result;
+use std::path::{Path, PathBuf};
+
+/// Returns the cache directory used when none is configured
+pub fn default_cache_dir() -> PathBuf {
+    std::env::temp_dir().join("wx-uploader").join("cache")
+}
+
+/// Scene descriptions stored under a cache directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SceneCache {
+    dir: PathBuf,
+}
+
+impl SceneCache {
+    /// Creates a cache that keeps its entries in `cache_dir`
+    pub fn new(cache_dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: cache_dir.as_ref().join("scenes"),
+        }
+    }
+
+    /// Returns the key of a scene description request
+    pub fn key(content: &str, lang: &str, prompt: &str, model: &str) -> String {
+        let mut hasher = blake3::Hasher::new();
+        for part in [lang, prompt, content] {
+            hasher.update(part.as_bytes());
+            // Separates the parts so moving text between them changes the hash
+            hasher.update(&[0]);
+        }
+        let model: String = model
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || "._-".contains(c) { c } else { '_' })
+            .collect();
+        format!("{}-{}", hasher.finalize().to_hex(), model)
+    }
+
+    /// Returns the cached description for `key`, if there is one
+    pub async fn get(&self, key: &str) -> Option<String> {
+        let description = tokio::fs::read_to_string(self.entry_path(key)).await.ok()?;
+        Some(description).filter(|description| !description.trim().is_empty())
+    }
+
+    /// Stores the description for `key`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache directory or entry cannot be written
+    pub async fn put(&self, key: &str, description: &str) -> Result<()> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        crate::markdown::write_file_atomic(&self.entry_path(key), description.as_bytes()).await
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.txt", key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_covers_request_and_model() {
+        let key = SceneCache::key("content", "en", "prompt", "gpt-4o");
+        assert!(key.ends_with("-gpt-4o"), "{}", key);
+        assert_eq!(key, SceneCache::key("content", "en", "prompt", "gpt-4o"));
+
+        for other in [
+            SceneCache::key("content!", "en", "prompt", "gpt-4o"),
+            SceneCache::key("content", "zh", "prompt", "gpt-4o"),
+            SceneCache::key("content", "en", "prompt2", "gpt-4o"),
+            SceneCache::key("content", "en", "prompt", "gpt-4o-mini"),
+            SceneCache::key("", "en", "promptcontent", "gpt-4o"),
+        ] {
+            assert_ne!(key, other);
+        }
+        assert!(SceneCache::key("c", "en", "p", "models/gemini").ends_with("-models_gemini"));
+    }
+
+    #[tokio::test]
+    async fn test_get_and_put() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = SceneCache::new(dir.path());
+        let key = SceneCache::key("content", "en", "prompt", "gpt-4o");
+
+        assert_eq!(cache.get(&key).await, None);
+        cache.put(&key, "A quiet harbor at dawn.").await.unwrap();
+        assert_eq!(cache.get(&key).await.as_deref(), Some("A quiet harbor at dawn."));
+        assert!(dir.path().join("scenes").join(format!("{}.txt", key)).is_file());
+    }
+}
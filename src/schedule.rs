@@ -0,0 +1,215 @@
+//! Upload schedule
+//!
+//! Implements the `allowed_hours` setting: uploads only run inside a daily
+//! window in a given time zone, e.g. `09:00-18:00 Asia/Shanghai`, so draft
+//! preview notifications do not go out at night. A window may wrap past
+//! midnight, e.g. `22:00-02:00 UTC`.
+//!
+//! The window is checked once, when an upload run starts; a run that started
+//! inside the window is allowed to finish. `--ignore-schedule` skips the
+//! check, and dry runs, validation and status overviews never check it.
+
+use crate::error::{Error, Result};
+use chrono::{DateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::fmt;
+use std::str::FromStr;
+
+/// Source of the current time, so the schedule can be checked against a fixed time in tests
+pub trait Clock: Send + Sync {
+    /// Returns the current time
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The system clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Daily window in which uploads are allowed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllowedHours {
+    /// Start of the window, inclusive
+    pub start: NaiveTime,
+    /// End of the window, exclusive; before `start` for a window past midnight
+    pub end: NaiveTime,
+    /// Time zone both times are in
+    pub time_zone: Tz,
+}
+
+impl AllowedHours {
+    /// Returns true if `now` is inside the window
+    pub fn contains(&self, now: DateTime<Utc>) -> bool {
+        let time = now.with_timezone(&self.time_zone).time();
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    /// Returns the next start of the window after `now`
+    ///
+    /// A start that falls into a daylight saving gap is skipped.
+    pub fn next_start(&self, now: DateTime<Utc>) -> DateTime<Tz> {
+        let mut date = now.with_timezone(&self.time_zone).date_naive();
+        loop {
+            if let Some(start) = self
+                .time_zone
+                .from_local_datetime(&date.and_time(self.start))
+                .earliest()
+                .filter(|start| *start > now)
+            {
+                return start;
+            }
+            date = date.succ_opt().expect("dates far from the end of the calendar");
+        }
+    }
+
+    /// Checks that an upload may start at the time `clock` returns
+    ///
+    /// # Errors
+    ///
+    /// Returns a configuration error naming the next allowed time outside the window
+    pub fn check(&self, clock: &dyn Clock) -> Result<()> {
+        let now = clock.now();
+        if self.contains(now) {
+            return Ok(());
+        }
+        Err(Error::config(format!(
+            "uploads are only allowed {} (allowed_hours); the next window opens at {}. \
+             Pass --ignore-schedule to upload anyway",
+            self,
+            self.next_start(now).format("%Y-%m-%d %H:%M %Z")
+        )))
+    }
+}
+
+impl FromStr for AllowedHours {
+    type Err = Error;
+
+    /// Parses `HH:MM-HH:MM <time zone>`, e.g. `09:00-18:00 Asia/Shanghai`
+    fn from_str(value: &str) -> Result<Self> {
+        let invalid = |reason: &str| {
+            Error::config(format!(
+                "Invalid allowed_hours '{}': {}; expected e.g. \"09:00-18:00 Asia/Shanghai\"",
+                value, reason
+            ))
+        };
+
+        let mut parts = value.split_whitespace();
+        let (Some(range), Some(time_zone), None) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(invalid("give a time range and a time zone"));
+        };
+        let (start, end) = range
+            .split_once('-')
+            .ok_or_else(|| invalid("the time range needs a start and an end"))?;
+        let parse_time = |time: &str| {
+            NaiveTime::parse_from_str(time, "%H:%M")
+                .map_err(|_| invalid(&format!("'{}' is not a time like 09:00", time)))
+        };
+        let (start, end) = (parse_time(start)?, parse_time(end)?);
+        if start == end {
+            return Err(invalid("the window is empty"));
+        }
+        let time_zone = time_zone
+            .parse::<Tz>()
+            .map_err(|_| invalid(&format!("unknown time zone '{}'", time_zone)))?;
+
+        Ok(Self {
+            start,
+            end,
+            time_zone,
+        })
+    }
+}
+
+impl fmt::Display for AllowedHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{} {}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M"),
+            self.time_zone
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedClock(DateTime<Utc>);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> DateTime<Utc> {
+            self.0
+        }
+    }
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        let hours: AllowedHours = "09:00-18:00 Asia/Shanghai".parse().unwrap();
+        assert_eq!(hours.start, NaiveTime::from_hms_opt(9, 0, 0).unwrap());
+        assert_eq!(hours.time_zone, chrono_tz::Asia::Shanghai);
+        assert_eq!(hours.to_string(), "09:00-18:00 Asia/Shanghai");
+
+        for (value, reason) in [
+            ("09:00-18:00", "give a time range and a time zone"),
+            ("09:00 Asia/Shanghai", "needs a start and an end"),
+            ("9am-18:00 Asia/Shanghai", "'9am' is not a time"),
+            ("09:00-09:00 UTC", "the window is empty"),
+            ("09:00-18:00 Mars/Olympus", "unknown time zone 'Mars/Olympus'"),
+        ] {
+            let err = value.parse::<AllowedHours>().unwrap_err();
+            assert!(err.to_string().contains(reason), "{}: {}", value, err);
+        }
+    }
+
+    #[test]
+    fn test_check_uses_time_zone_and_names_next_window() {
+        let hours: AllowedHours = "09:00-18:00 Asia/Shanghai".parse().unwrap();
+
+        // 10:00 in Shanghai
+        assert!(hours.check(&FixedClock(utc("2026-03-02T02:00:00Z"))).is_ok());
+        // 23:30 in Shanghai: the window opens the next morning
+        let err = hours
+            .check(&FixedClock(utc("2026-03-02T15:30:00Z")))
+            .unwrap_err();
+        assert!(err.is_config());
+        assert!(
+            err.to_string().contains("next window opens at 2026-03-03 09:00 CST"),
+            "{}",
+            err
+        );
+        // 08:00 in Shanghai: the window opens the same day
+        assert_eq!(
+            hours.next_start(utc("2026-03-02T00:00:00Z")),
+            utc("2026-03-02T01:00:00Z")
+        );
+        // The end is exclusive
+        assert!(!hours.contains(utc("2026-03-02T10:00:00Z")));
+    }
+
+    #[test]
+    fn test_window_past_midnight() {
+        let hours: AllowedHours = "22:00-02:00 UTC".parse().unwrap();
+        assert!(hours.contains(utc("2026-03-02T23:00:00Z")));
+        assert!(hours.contains(utc("2026-03-03T01:59:00Z")));
+        assert!(!hours.contains(utc("2026-03-03T12:00:00Z")));
+        assert_eq!(
+            hours.next_start(utc("2026-03-03T12:00:00Z")),
+            utc("2026-03-03T22:00:00Z")
+        );
+    }
+}
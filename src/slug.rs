@@ -0,0 +1,269 @@
+//! Article slugs
+//!
+//! A `slug` in the frontmatter names an article independently of its file
+//! name. Generated covers use it as their base name, so renaming the markdown
+//! file does not orphan them. With `--auto-slug`, files without a slug get one
+//! derived from their title: Chinese characters are transliterated to pinyin,
+//! everything is lowercased and words are joined with hyphens.
+
+use crate::markdown::{
+    ParseOptions, extract_h1_title, parse_markdown_file_with, update_frontmatter_with,
+};
+use crate::models::Frontmatter;
+use crate::wechat::ProcessOptions;
+use crate::output::{FORMATTER, OutputFormatter};
+use pinyin::ToPinyin;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Derived slugs are cut to at most this many bytes, at a word boundary
+pub const MAX_SLUG_LEN: usize = 64;
+
+/// Slug used when nothing usable can be derived
+const FALLBACK_SLUG: &str = "article";
+
+/// Returns true if `slug` only contains URL-safe characters: ASCII letters,
+/// digits, `-` and `_`
+pub fn is_valid_slug(slug: &str) -> bool {
+    !slug.is_empty()
+        && slug
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Derives a slug from text, e.g. `Rust 异步编程` becomes `rust-yi-bu-bian-cheng`
+///
+/// ASCII letters and digits are kept, Chinese characters become one pinyin
+/// syllable each and everything else separates words. Returns an empty string
+/// if the text contains nothing usable.
+pub fn derive_slug(text: &str) -> String {
+    let mut words: Vec<String> = Vec::new();
+    let mut word = String::new();
+
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() {
+            word.push(c.to_ascii_lowercase());
+            continue;
+        }
+
+        if !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        if let Some(pinyin) = c.to_pinyin() {
+            words.push(pinyin.plain().to_string());
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    let mut slug = String::new();
+    for word in words {
+        let separator = usize::from(!slug.is_empty());
+        if slug.len() + separator + word.len() > MAX_SLUG_LEN {
+            if slug.is_empty() {
+                slug = word[..MAX_SLUG_LEN].to_string();
+            }
+            break;
+        }
+        if separator == 1 {
+            slug.push('-');
+        }
+        slug.push_str(&word);
+    }
+    slug
+}
+
+/// Derives the slug of an article from its title, first H1 heading or file name
+pub fn slug_for(frontmatter: &Frontmatter, body: &str, path: &Path) -> String {
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    [
+        frontmatter.title.clone(),
+        extract_h1_title(body),
+        Some(stem.to_string()),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|text| derive_slug(&text))
+    .find(|slug| !slug.is_empty())
+    .unwrap_or_else(|| FALLBACK_SLUG.to_string())
+}
+
+/// Returns `slug`, or `slug-2`, `slug-3`, ... if it is already taken, and marks it taken
+pub fn disambiguate(slug: &str, taken: &mut HashSet<String>) -> String {
+    let mut candidate = slug.to_string();
+    let mut n = 2;
+    while taken.contains(&candidate) {
+        candidate = format!("{}-{}", slug, n);
+        n += 1;
+    }
+    taken.insert(candidate.clone());
+    candidate
+}
+
+/// Plans slugs for the files of a run that would be uploaded but have none
+///
+/// Existing slugs are reserved first; new slugs are then assigned in path
+/// order, so collisions are resolved the same way on every run. Published
+/// and unreadable files are left out. Only the sidecar option of `options`
+/// is used.
+pub async fn plan_slugs(files: &[PathBuf], options: &ParseOptions) -> BTreeMap<PathBuf, String> {
+    let scan_options = ParseOptions {
+        strict_frontmatter: false,
+        quiet: true,
+        sidecar_frontmatter: options.sidecar_frontmatter,
+        ..Default::default()
+    };
+
+    let mut sorted: Vec<&PathBuf> = files.iter().collect();
+    sorted.sort();
+
+    let mut taken = HashSet::new();
+    let mut missing = Vec::new();
+    for path in sorted {
+        let Ok((frontmatter, body)) = parse_markdown_file_with(path, &scan_options).await else {
+            continue;
+        };
+        match &frontmatter.slug {
+            Some(slug) if !taken.insert(slug.clone()) => {
+                FORMATTER.print_warning(&format!(
+                    "slug '{}' is used by more than one file: {}",
+                    slug,
+                    path.display()
+                ));
+            }
+            Some(_) => {}
+            None if frontmatter.publication_status().skip_reason().is_none() => {
+                missing.push((path, slug_for(&frontmatter, &body, path)));
+            }
+            None => {}
+        }
+    }
+
+    missing
+        .into_iter()
+        .map(|(path, slug)| (path.clone(), disambiguate(&slug, &mut taken)))
+        .collect()
+}
+
+/// Derives and saves slugs for the files of a directory run that have none
+///
+/// Returns the number of files that received a slug. A file whose slug cannot
+/// be saved is reported as a warning and gets its slug when it is uploaded.
+pub async fn assign_slugs(files: &[PathBuf], options: &ProcessOptions) -> usize {
+    let parse_options = options.parse_options();
+    let mut assigned = 0;
+    for (path, slug) in plan_slugs(files, &parse_options).await {
+        let result = async {
+            options.back_up(&path).await?;
+            update_frontmatter_with(&path, &parse_options, |fm| {
+                fm.slug.get_or_insert_with(|| slug.clone());
+                Ok(())
+            })
+            .await
+        }
+        .await;
+
+        match result {
+            Ok(()) => {
+                assigned += 1;
+                if options.verbose {
+                    info!("Assigned slug '{}' to {}", slug, path.display());
+                }
+            }
+            Err(e) => FORMATTER.print_warning(&format!(
+                "failed to save slug for {}: {}",
+                path.display(),
+                e
+            )),
+        }
+    }
+    assigned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_derive_slug() {
+        assert_eq!(derive_slug("Hello, World!"), "hello-world");
+        assert_eq!(
+            derive_slug("Rust 异步编程入门"),
+            "rust-yi-bu-bian-cheng-ru-men"
+        );
+        assert_eq!(
+            derive_slug("微信公众号：Markdown 上传"),
+            "wei-xin-gong-zhong-hao-markdown-shang-chuan"
+        );
+        assert_eq!(derive_slug("  --Tokio 1.0--  "), "tokio-1-0");
+        assert_eq!(derive_slug("!!!"), "");
+
+        let long = derive_slug(&"word ".repeat(30));
+        assert!(long.len() <= MAX_SLUG_LEN);
+        assert!(!long.ends_with('-'));
+    }
+
+    #[test]
+    fn test_is_valid_slug() {
+        assert!(is_valid_slug("rust-async_2024"));
+        assert!(!is_valid_slug(""));
+        assert!(!is_valid_slug("has space"));
+        assert!(!is_valid_slug("../escape"));
+        assert!(!is_valid_slug("中文"));
+    }
+
+    #[test]
+    fn test_slug_for_falls_back_to_heading_and_file_name() {
+        let titled = Frontmatter::with_title("你好");
+        assert_eq!(slug_for(&titled, "", Path::new("a.md")), "ni-hao");
+        assert_eq!(
+            slug_for(&Frontmatter::new(), "# Heading Title\n", Path::new("a.md")),
+            "heading-title"
+        );
+        assert_eq!(
+            slug_for(&Frontmatter::new(), "", Path::new("My Post.md")),
+            "my-post"
+        );
+        assert_eq!(
+            slug_for(&Frontmatter::new(), "", Path::new("???.md")),
+            "article"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_assign_slugs_disambiguates_collisions_deterministically() {
+        let temp_dir = TempDir::new().unwrap();
+        let write = |name: &str, content: &str| {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, content).unwrap();
+            path
+        };
+        let taken = write("a.md", "---\ntitle: Other\nslug: ni-hao\n---\nbody");
+        let second = write("c.md", "---\ntitle: 你好\n---\nbody");
+        let first = write("b.md", "---\ntitle: 你好\n---\nbody");
+        let published = write("d.md", "---\ntitle: 你好\npublished: \"true\"\n---\nbody");
+
+        // Walk order does not matter: slugs are assigned in path order
+        let files = vec![second.clone(), published.clone(), first.clone(), taken];
+        assert_eq!(assign_slugs(&files, &ProcessOptions::default()).await, 2);
+
+        let slug = |path: &Path| {
+            let content = fs::read_to_string(path).unwrap();
+            crate::markdown::parse_markdown(&content).unwrap().0.slug
+        };
+        assert_eq!(slug(&first).as_deref(), Some("ni-hao-2"));
+        assert_eq!(slug(&second).as_deref(), Some("ni-hao-3"));
+        assert_eq!(slug(&published), None);
+
+        // Saved slugs are kept on the next run
+        assert_eq!(assign_slugs(&files, &ProcessOptions::default()).await, 0);
+        assert_eq!(slug(&first).as_deref(), Some("ni-hao-2"));
+    }
+}
@@ -0,0 +1,186 @@
+//! External upload state
+//!
+//! With write-back disabled (`--no-write-back`), source files are never
+//! modified, so the publish state and draft ID of each upload are recorded in
+//! a JSON state file instead of the frontmatter. The same happens with
+//! `--no-status-update`, which only leaves the `published` field alone. The
+//! state file is `.wx-uploader/state.json` in the working directory unless
+//! `--state-file` names another. With write-back disabled, directory runs
+//! skip the files it records as uploaded.
+
+use crate::backup::DATA_DIR;
+use crate::error::Result;
+use crate::report::{FileOutcome, UploadReport};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Returns the state file of `work_dir`, used when none is configured
+pub fn default_state_file(work_dir: &Path) -> PathBuf {
+    work_dir.join(DATA_DIR).join("state.json")
+}
+
+/// Recorded state of one uploaded file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateEntry {
+    /// Publish state the frontmatter would have received
+    pub published: String,
+    /// Media ID of the created draft
+    pub draft_id: String,
+    /// Upload time in seconds since the Unix epoch
+    pub uploaded_at: u64,
+    /// Draft IDs keyed by account, for uploads made with a named account
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub uploads: BTreeMap<String, String>,
+}
+
+/// Upload state of all files, keyed by absolute path
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadState {
+    /// Entries keyed by the canonical path of the markdown file
+    pub files: BTreeMap<String, StateEntry>,
+}
+
+impl UploadState {
+    /// Loads the state file, or returns an empty state if it does not exist
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read or parsed
+    pub async fn load(path: &Path) -> Result<Self> {
+        match tokio::fs::read_to_string(path).await {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes the state file, creating its directory if needed
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let content = serde_json::to_string_pretty(self)?;
+        tokio::fs::write(path, content).await?;
+        Ok(())
+    }
+
+    /// Returns the recorded state of a markdown file
+    pub fn get(&self, file: &Path) -> Option<&StateEntry> {
+        self.files.get(&state_key(file))
+    }
+
+    /// Records a successful upload of a markdown file as a draft
+    ///
+    /// With an `account`, the draft is also recorded under that account so
+    /// uploads of the same file to several accounts are all kept.
+    pub fn record_upload(&mut self, file: &Path, account: Option<&str>, draft_id: &str) {
+        let uploaded_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        let entry = self.files.entry(state_key(file)).or_insert_with(|| StateEntry {
+            published: String::new(),
+            draft_id: String::new(),
+            uploaded_at,
+            uploads: BTreeMap::new(),
+        });
+        entry.published = "draft".to_string();
+        entry.draft_id = draft_id.to_string();
+        entry.uploaded_at = uploaded_at;
+        if let Some(account) = account {
+            entry.uploads.insert(account.to_string(), draft_id.to_string());
+        }
+    }
+
+    /// Records every uploaded file of a report and returns how many were recorded
+    pub fn record_report(&mut self, report: &UploadReport) -> usize {
+        let mut recorded = 0;
+        for file in &report.files {
+            if let FileOutcome::Uploaded { draft_id, .. } = &file.outcome {
+                self.record_upload(&file.path, file.account.as_deref(), draft_id);
+                recorded += 1;
+            }
+        }
+        recorded
+    }
+}
+
+/// Records the uploads of a report in the state file
+///
+/// # Errors
+///
+/// Returns an error if the state file cannot be read, parsed or written
+pub async fn record_uploads(state_file: &Path, report: &UploadReport) -> Result<()> {
+    if report.uploaded() == 0 {
+        return Ok(());
+    }
+
+    let mut state = UploadState::load(state_file).await?;
+    state.record_report(report);
+    state.save(state_file).await
+}
+
+fn state_key(file: &Path) -> String {
+    file.canonicalize()
+        .unwrap_or_else(|_| file.to_path_buf())
+        .display()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_record_uploads_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let article = temp_dir.path().join("a.md");
+        let skipped = temp_dir.path().join("b.md");
+        std::fs::write(&article, "# A").unwrap();
+        std::fs::write(&skipped, "# B").unwrap();
+        let state_file = temp_dir.path().join("state/state.json");
+
+        let mut report = UploadReport::new();
+        report.record_uploaded(&article, "MEDIA_1");
+        report.record_skipped(&skipped, "already published");
+        record_uploads(&state_file, &report).await.unwrap();
+
+        let state = UploadState::load(&state_file).await.unwrap();
+        assert_eq!(state.files.len(), 1);
+        let entry = state.get(&article).unwrap();
+        assert_eq!(entry.published, "draft");
+        assert_eq!(entry.draft_id, "MEDIA_1");
+        assert!(state.get(&skipped).is_none());
+        assert!(entry.uploads.is_empty());
+    }
+
+    #[test]
+    fn test_record_upload_keeps_every_account() {
+        let mut state = UploadState::default();
+        let file = Path::new("post.md");
+        state.record_upload(file, Some("north"), "N_1");
+        state.record_upload(file, Some("south"), "S_1");
+
+        let entry = state.get(file).unwrap();
+        assert_eq!(entry.draft_id, "S_1");
+        assert_eq!(entry.uploads["north"], "N_1");
+        assert_eq!(entry.uploads["south"], "S_1");
+    }
+
+    #[tokio::test]
+    async fn test_load_missing_state_file_is_empty() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = UploadState::load(&temp_dir.path().join("none.json"))
+            .await
+            .unwrap();
+        assert!(state.files.is_empty());
+    }
+}
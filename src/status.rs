@@ -0,0 +1,249 @@
+//! Publish status overview
+//!
+//! Implements `--show-status`: a read-only listing of every markdown file with
+//! its publish state and draft ID. It only parses frontmatter, so it needs no
+//! WeChat client or AI provider.
+
+use crate::markdown::{ParseOptions, parse_frontmatter_file_with};
+use crate::models::PublicationStatus;
+use crate::wechat::collect_markdown_files;
+use std::path::{Path, PathBuf};
+
+/// Status of a single markdown file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStatus {
+    /// Path of the markdown file
+    pub path: PathBuf,
+    /// Publish state, or the parse error if the frontmatter could not be read
+    pub state: std::result::Result<PublicationStatus, String>,
+    /// Draft media ID recorded by the last upload
+    pub draft_id: Option<String>,
+}
+
+impl FileStatus {
+    /// Returns true if a directory run would skip this file
+    pub fn would_skip(&self) -> bool {
+        self.state
+            .as_ref()
+            .is_ok_and(|state| state.skip_reason().is_some())
+    }
+}
+
+/// Publish status of all files under a path
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatusReport {
+    /// Per-file status in walk order
+    pub files: Vec<FileStatus>,
+}
+
+impl StatusReport {
+    /// Number of files in the given state
+    pub fn count(&self, state: PublicationStatus) -> usize {
+        self.files.iter().filter(|f| f.state == Ok(state)).count()
+    }
+
+    /// Number of files whose frontmatter could not be parsed
+    pub fn errors(&self) -> usize {
+        self.files.iter().filter(|f| f.state.is_err()).count()
+    }
+
+    /// Number of files that have a draft ID
+    pub fn with_draft_id(&self) -> usize {
+        self.files.iter().filter(|f| f.draft_id.is_some()).count()
+    }
+
+    /// Renders the report as an aligned table followed by a counts line
+    ///
+    /// Paths are shown relative to `base` when possible.
+    pub fn render_table(&self, base: &Path) -> String {
+        const HEADERS: [&str; 4] = ["FILE", "STATE", "DRAFT ID", "SKIPPED"];
+
+        let rows: Vec<[String; 4]> = self
+            .files
+            .iter()
+            .map(|file| {
+                let path = file.path.strip_prefix(base).unwrap_or(&file.path);
+                let state = match &file.state {
+                    Ok(state) => state.to_string(),
+                    Err(_) => "error".to_string(),
+                };
+                [
+                    path.display().to_string(),
+                    state,
+                    file.draft_id.clone().unwrap_or_else(|| "-".to_string()),
+                    if file.would_skip() { "yes" } else { "no" }.to_string(),
+                ]
+            })
+            .collect();
+
+        let mut widths = HEADERS.map(str::len);
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let format_row = |cells: [&str; 4]| {
+            let line = cells
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ");
+            line.trim_end().to_string()
+        };
+
+        let mut lines = vec![
+            format_row(HEADERS),
+            format_row(widths.map(|w| "-".repeat(w)).each_ref().map(String::as_str)),
+        ];
+        lines.extend(
+            rows.iter()
+                .map(|row| format_row(row.each_ref().map(String::as_str))),
+        );
+        lines.push(String::new());
+        lines.push(self.summary());
+
+        lines.join("\n")
+    }
+
+    /// One-line counts, e.g. `3 files: 1 unpublished, 1 draft, 1 published (2 with draft ID)`
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{} files: {} unpublished, {} draft, {} published ({} with draft ID)",
+            self.files.len(),
+            self.count(PublicationStatus::Unpublished),
+            self.count(PublicationStatus::Draft),
+            self.count(PublicationStatus::Published),
+            self.with_draft_id(),
+        );
+        if self.count(PublicationStatus::Skip) > 0 {
+            summary.push_str(&format!(", {} marked skip", self.count(PublicationStatus::Skip)));
+        }
+        if self.errors() > 0 {
+            summary.push_str(&format!(", {} unreadable", self.errors()));
+        }
+        summary
+    }
+}
+
+/// Collects the publish status of a markdown file or every markdown file in a directory
+pub async fn collect_status(path: &Path) -> StatusReport {
+    let files = if path.is_file() {
+        vec![path.to_path_buf()]
+    } else {
+        collect_markdown_files(path)
+    };
+
+    let options = ParseOptions {
+        quiet: true,
+        ..Default::default()
+    };
+
+    let mut report = StatusReport::default();
+    for path in files {
+        let status = match parse_frontmatter_file_with(&path, &options).await {
+            Ok(frontmatter) => FileStatus {
+                path,
+                state: Ok(frontmatter.publication_status()),
+                draft_id: frontmatter.draft_id,
+            },
+            Err(e) => FileStatus {
+                path,
+                state: Err(e.to_string()),
+                draft_id: None,
+            },
+        };
+        report.files.push(status);
+    }
+    report
+}
+
+/// Prints the status table for `--show-status`
+pub async fn show_status(path: &Path) -> StatusReport {
+    let report = collect_status(path).await;
+
+    if report.files.is_empty() {
+        println!("No markdown files found in {}", path.display());
+        return report;
+    }
+
+    let base = if path.is_file() {
+        path.parent().unwrap_or(path)
+    } else {
+        path
+    };
+    println!("{}", report.render_table(base));
+
+    for file in &report.files {
+        if let Err(error) = &file.state {
+            eprintln!("{}: {}", file.path.display(), error);
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_collect_status_and_table() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        fs::write(dir.join("a.md"), "# No frontmatter").unwrap();
+        fs::write(
+            dir.join("b.md"),
+            "---\npublished: draft\ndraft_id: MEDIA_1\n---\nbody",
+        )
+        .unwrap();
+        fs::create_dir(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/c.md"), "---\npublished: true\n---\nbody").unwrap();
+        fs::write(dir.join("d.md"), "---\ntheme: nope\n---\nbody").unwrap();
+
+        let mut report = collect_status(dir).await;
+        report.files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        assert_eq!(report.count(PublicationStatus::Unpublished), 1);
+        assert_eq!(report.count(PublicationStatus::Draft), 1);
+        assert_eq!(report.count(PublicationStatus::Published), 1);
+        assert_eq!(report.errors(), 1);
+        assert_eq!(report.with_draft_id(), 1);
+        assert!(report.files[3].would_skip());
+
+        let table = report.render_table(dir);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "FILE      STATE        DRAFT ID  SKIPPED");
+        assert_eq!(lines[1], "--------  -----------  --------  -------");
+        assert_eq!(lines[2], "a.md      unpublished  -         no");
+        assert_eq!(lines[3], "b.md      draft        MEDIA_1   no");
+        assert_eq!(lines[4], "d.md      error        -         no");
+        assert_eq!(lines[5], "sub/c.md  published    -         yes");
+        assert_eq!(
+            lines[7],
+            "4 files: 1 unpublished, 1 draft, 1 published (1 with draft ID), 1 unreadable"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_collect_status_single_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("post.md");
+        fs::write(&file, "---\npublished: \"true\"\n---\nbody").unwrap();
+
+        let report = collect_status(&file).await;
+        assert_eq!(report.files.len(), 1);
+        assert_eq!(report.files[0].state, Ok(PublicationStatus::Published));
+
+        fs::write(&file, "---\npublished: skip\n---\nbody").unwrap();
+        let report = collect_status(&file).await;
+        assert!(report.files[0].would_skip());
+        assert_eq!(
+            report.summary(),
+            "1 files: 0 unpublished, 0 draft, 0 published (0 with draft ID), 1 marked skip"
+        );
+    }
+}
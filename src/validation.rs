@@ -0,0 +1,806 @@
+//! Pre-publish validation of markdown articles
+//!
+//! This module scans article bodies for links and image references and reports
+//! the ones that would render as dead links in WeChat. Relative targets are
+//! resolved against the article's directory; remote `http(s)` targets are only
+//! checked when explicitly requested. Invalid frontmatter values are
+//! collected across all files and reported once, grouped by field.
+
+use crate::error::{Error, Result};
+use crate::lint::lint_file;
+use crate::markdown::{
+    ArticleContext, BodyTransform, ParseOptions, parse_frontmatter_file_with, parse_markdown_at,
+};
+use crate::models::{FrontmatterField, FrontmatterIssue, ThemeFallback};
+use crate::wechat::{drop_ignored, has_markdown_extension};
+use crate::output::{FORMATTER, OutputFormatter, annotations_enabled};
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::time::Duration;
+use tracing::{info, warn};
+use walkdir::WalkDir;
+
+/// Matches inline links and images: `[text](target "title")` / `![alt](target)`
+static INLINE_LINK_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(!?)\[[^\[\]]*\]\(\s*<?([^)\s>]+)>?(?:\s+["'(][^)]*)?\s*\)"#)
+        .expect("valid inline link regex")
+});
+
+/// Matches inline links to a heading of the same article: `[text](#anchor)`
+static ANCHOR_LINK_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\[([^\[\]]*)\]\(\s*<?#[^)\s>]*>?(?:\s+["'(][^)]*)?\s*\)"#)
+        .expect("valid anchor link regex")
+});
+
+/// Matches reference-style link definitions: `[id]: target "title"`
+static REFERENCE_DEF_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s{0,3}\[[^\]]+\]:\s*<?([^\s>]+)>?").expect("valid reference regex")
+});
+
+/// Matches inline code spans, whose contents must not be treated as links
+pub(crate) static INLINE_CODE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"`[^`]*`").expect("valid inline code regex"));
+
+/// Kind of markdown reference found in an article body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    /// A regular `[text](target)` link or reference definition
+    Link,
+    /// An image `![alt](target)`
+    Image,
+}
+
+impl LinkKind {
+    fn label(self) -> &'static str {
+        match self {
+            LinkKind::Link => "link",
+            LinkKind::Image => "image",
+        }
+    }
+}
+
+/// A link or image reference extracted from markdown
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkRef {
+    /// Whether this is a link or an image
+    pub kind: LinkKind,
+    /// Raw target as written in the markdown
+    pub target: String,
+    /// 1-based line number within the scanned text
+    pub line: usize,
+}
+
+impl LinkRef {
+    /// Returns true if the target is an `http://` or `https://` URL
+    pub fn is_remote(&self) -> bool {
+        self.target.starts_with("http://") || self.target.starts_with("https://")
+    }
+
+    /// Returns true if the target is a path relative to the article
+    ///
+    /// Fragment-only anchors, site-root paths, protocol-relative URLs and
+    /// other schemes (`mailto:`, `data:`, ...) are not considered relative.
+    pub fn is_relative(&self) -> bool {
+        let target = self.target.as_str();
+        !(target.is_empty()
+            || target.starts_with('#')
+            || target.starts_with('/')
+            || target.contains("://")
+            || has_scheme(target))
+    }
+}
+
+/// A link whose target could not be resolved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenLink {
+    /// The offending reference
+    pub link: LinkRef,
+    /// Human readable reason
+    pub reason: String,
+}
+
+/// Tracks fenced code blocks while a markdown body is read line by line
+#[derive(Default)]
+pub(crate) struct Fences {
+    open: Option<&'static str>,
+}
+
+impl Fences {
+    /// Returns true if `line` is a fence or inside a fenced code block
+    pub(crate) fn is_code(&mut self, line: &str) -> bool {
+        let trimmed = line.trim_start();
+        if let Some(marker) = self.open {
+            if trimmed.starts_with(marker) {
+                self.open = None;
+            }
+            return true;
+        }
+        self.open = ["```", "~~~"]
+            .into_iter()
+            .find(|marker| trimmed.starts_with(marker));
+        self.open.is_some()
+    }
+}
+
+/// Returns the 1-based number and text of each line of a markdown body outside
+/// fenced code blocks, with inline code spans removed
+pub(crate) fn prose_lines(body: &str) -> Vec<(usize, Cow<'_, str>)> {
+    let mut fences = Fences::default();
+    body.lines()
+        .enumerate()
+        .filter(|(_, line)| !fences.is_code(line))
+        .map(|(index, line)| (index + 1, INLINE_CODE_RE.replace_all(line, "")))
+        .collect()
+}
+
+/// The `anchor-links` body transform: [`unlink_anchor_links`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AnchorLinksTransform;
+
+impl AnchorLinksTransform {
+    /// Name the transform is selected by
+    pub const NAME: &'static str = "anchor-links";
+}
+
+impl BodyTransform for AnchorLinksTransform {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn apply(&self, _ctx: &ArticleContext<'_>, body: String) -> Result<String> {
+        Ok(match unlink_anchor_links(&body) {
+            Cow::Owned(unlinked) => unlinked,
+            Cow::Borrowed(_) => body,
+        })
+    }
+}
+
+/// Replaces links to headings of the same article, `[text](#anchor)`, with
+/// their text, since WeChat drops heading ids
+///
+/// Code blocks, inline code and images are left alone.
+pub fn unlink_anchor_links(body: &str) -> Cow<'_, str> {
+    let mut fences = Fences::default();
+    let mut changed = false;
+    let mut unlinked = String::with_capacity(body.len());
+
+    for line in body.split_inclusive('\n') {
+        if fences.is_code(line) {
+            unlinked.push_str(line);
+            continue;
+        }
+        let code: Vec<_> = INLINE_CODE_RE.find_iter(line).map(|m| m.range()).collect();
+        let replaced = ANCHOR_LINK_RE.replace_all(line, |captures: &Captures| {
+            let link = captures.get(0).expect("whole match");
+            let is_image = line[..link.start()].ends_with('!');
+            let in_code = code
+                .iter()
+                .any(|span| span.start < link.end() && link.start() < span.end);
+            if is_image || in_code {
+                link.as_str().to_string()
+            } else {
+                changed = true;
+                captures[1].to_string()
+            }
+        });
+        unlinked.push_str(&replaced);
+    }
+
+    if changed {
+        Cow::Owned(unlinked)
+    } else {
+        Cow::Borrowed(body)
+    }
+}
+
+/// Returns the number of file lines before `body`, so body line numbers can
+/// be reported against the whole file
+pub(crate) fn body_line_offset(content: &str, body: &str) -> usize {
+    content
+        .strip_suffix(body)
+        .map_or(0, |head| head.lines().count())
+}
+
+/// Extracts all link and image references from a markdown body.
+///
+/// Fenced code blocks and inline code spans are ignored.
+pub fn extract_links(body: &str) -> Vec<LinkRef> {
+    let mut links = Vec::new();
+
+    for (line_no, without_code) in prose_lines(body) {
+        if let Some(captures) = REFERENCE_DEF_RE.captures(&without_code) {
+            links.push(LinkRef {
+                kind: LinkKind::Link,
+                target: captures[1].to_string(),
+                line: line_no,
+            });
+            continue;
+        }
+
+        for captures in INLINE_LINK_RE.captures_iter(&without_code) {
+            let kind = if &captures[1] == "!" {
+                LinkKind::Image
+            } else {
+                LinkKind::Link
+            };
+            links.push(LinkRef {
+                kind,
+                target: captures[2].to_string(),
+                line: line_no,
+            });
+        }
+    }
+
+    links
+}
+
+/// Resolves a relative link target against the markdown file's directory.
+///
+/// Any `#fragment` or `?query` suffix is dropped and `%20` is decoded.
+pub fn resolve_link_target(markdown_path: &Path, target: &str) -> PathBuf {
+    let path_part = target
+        .split(['#', '?'])
+        .next()
+        .unwrap_or(target)
+        .replace("%20", " ");
+
+    markdown_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(path_part)
+}
+
+/// Finds relative links and images whose targets do not exist on disk.
+pub fn find_broken_relative_links(markdown_path: &Path, body: &str) -> Vec<BrokenLink> {
+    extract_links(body)
+        .into_iter()
+        .filter(|link| link.is_relative())
+        .filter_map(|link| {
+            let resolved = resolve_link_target(markdown_path, &link.target);
+            if resolved.exists() {
+                None
+            } else {
+                Some(BrokenLink {
+                    reason: format!("file not found: {}", resolved.display()),
+                    link,
+                })
+            }
+        })
+        .collect()
+}
+
+/// Checks remote `http(s)` links with HEAD requests.
+///
+/// Servers that reject HEAD with 405 are retried with GET. Each distinct URL is
+/// requested once; every occurrence of a failing URL is reported.
+pub async fn find_broken_remote_links(links: &[LinkRef]) -> Vec<BrokenLink> {
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("Failed to create HTTP client for link checking: {}", e);
+            return Vec::new();
+        }
+    };
+
+    let mut failures: Vec<(String, String)> = Vec::new();
+    let mut seen = HashSet::new();
+
+    for link in links.iter().filter(|link| link.is_remote()) {
+        if !seen.insert(link.target.clone()) {
+            continue;
+        }
+        if let Some(reason) = check_remote_url(&client, &link.target).await {
+            failures.push((link.target.clone(), reason));
+        }
+    }
+
+    links
+        .iter()
+        .filter_map(|link| {
+            failures
+                .iter()
+                .find(|(url, _)| url == &link.target)
+                .map(|(_, reason)| BrokenLink {
+                    link: link.clone(),
+                    reason: reason.clone(),
+                })
+        })
+        .collect()
+}
+
+/// Returns a failure reason if the remote URL is unreachable or returns an error status
+async fn check_remote_url(client: &reqwest::Client, url: &str) -> Option<String> {
+    let status = match client.head(url).send().await {
+        Ok(response) if response.status() == reqwest::StatusCode::METHOD_NOT_ALLOWED => {
+            match client.get(url).send().await {
+                Ok(response) => response.status(),
+                Err(e) => return Some(format!("request failed: {}", e)),
+            }
+        }
+        Ok(response) => response.status(),
+        Err(e) => return Some(format!("request failed: {}", e)),
+    };
+
+    if status.is_client_error() || status.is_server_error() {
+        Some(format!("HTTP {}", status))
+    } else {
+        None
+    }
+}
+
+/// Validates the links of a single markdown file.
+///
+/// Line numbers in the result refer to the whole file, including frontmatter.
+/// Frontmatter values are not validated; see [`collect_frontmatter_issues`].
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or its frontmatter cannot be parsed
+pub async fn validate_file_links(path: &Path, check_remote: bool) -> Result<Vec<BrokenLink>> {
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| Error::io_at(path, e))?;
+    let options = ParseOptions {
+        defer_validation: true,
+        ..Default::default()
+    };
+    let (_frontmatter, body) = parse_markdown_at(&content, path, &options)?;
+
+    let line_offset = body_line_offset(&content, &body);
+
+    let mut broken = find_broken_relative_links(path, &body);
+    if check_remote {
+        broken.extend(find_broken_remote_links(&extract_links(&body)).await);
+    }
+
+    for item in &mut broken {
+        item.link.line += line_offset;
+    }
+    broken.sort_by_key(|item| item.link.line);
+
+    Ok(broken)
+}
+
+/// Invalid frontmatter values of many files, grouped by field
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrontmatterIssues {
+    by_field: BTreeMap<FrontmatterField, Vec<(PathBuf, String)>>,
+}
+
+impl FrontmatterIssues {
+    /// Records the invalid values of the file at `path`
+    pub fn add(&mut self, path: &Path, issues: Vec<FrontmatterIssue>) {
+        for issue in issues {
+            self.by_field
+                .entry(issue.field)
+                .or_default()
+                .push((path.to_path_buf(), issue.value));
+        }
+    }
+
+    /// Returns true if no invalid value was recorded
+    pub fn is_empty(&self) -> bool {
+        self.by_field.is_empty()
+    }
+
+    /// Returns the number of invalid values
+    pub fn len(&self) -> usize {
+        self.by_field.values().map(Vec::len).sum()
+    }
+
+    /// Returns the number of files with at least one invalid value
+    pub fn file_count(&self) -> usize {
+        self.by_field
+            .values()
+            .flatten()
+            .map(|(path, _)| path)
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// Returns the files and invalid values of each field, in field order
+    pub fn groups(&self) -> impl Iterator<Item = (FrontmatterField, &[(PathBuf, String)])> {
+        self.by_field.iter().map(|(field, files)| (*field, files.as_slice()))
+    }
+
+    /// Prints one error per field listing its files, or one annotation per file
+    pub fn print(&self) {
+        for (field, files) in self.groups() {
+            if annotations_enabled() {
+                for (path, value) in files {
+                    let issue = FrontmatterIssue {
+                        field,
+                        value: value.clone(),
+                    };
+                    FORMATTER.print_file_error(path, None, &issue.to_string());
+                }
+                continue;
+            }
+
+            FORMATTER.print_error(&format!(
+                "invalid {} in {} file(s); {}",
+                field.label(),
+                files.len(),
+                field.hint()
+            ));
+            for (path, value) in files {
+                FORMATTER.print_detail(&format!("  {}: '{}'", path.display(), value));
+            }
+        }
+    }
+}
+
+/// Collects the invalid frontmatter values of `files` in one pass
+///
+/// The frontmatter is parsed with `options` but validated afterwards, so every
+/// invalid value is found rather than the first of each file. Files whose
+/// frontmatter cannot be parsed at all are left to the caller.
+pub async fn collect_frontmatter_issues(files: &[PathBuf], options: &ParseOptions) -> FrontmatterIssues {
+    let options = ParseOptions {
+        quiet: true,
+        defer_validation: true,
+        ..*options
+    };
+
+    let mut issues = FrontmatterIssues::default();
+    for path in files {
+        if let Ok(frontmatter) = parse_frontmatter_file_with(path, &options).await {
+            issues.add(path, frontmatter.issues(options.theme_fallback));
+        }
+    }
+    issues
+}
+
+/// Validates a markdown file or every markdown file under a directory.
+///
+/// Broken links are printed as they are found, followed by the invalid
+/// frontmatter values of all files grouped by field and a summary line.
+/// Markdown that renders poorly in WeChat is reported as a warning, except
+/// for the lint rules in `disabled_rules`. The files of a directory are
+/// the ones with one of `extensions`; those marked `ignore: true` are not
+/// articles and are left out.
+///
+/// # Returns
+///
+/// The total number of problems found (broken links, invalid frontmatter
+/// values and unreadable files)
+pub async fn validate_path(
+    path: &Path,
+    check_remote: bool,
+    disabled_rules: &[String],
+    extensions: &[String],
+    verbose: bool,
+) -> Result<usize> {
+    let files: Vec<PathBuf> = if path.is_dir() {
+        let files = WalkDir::new(path)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| has_markdown_extension(e.path(), extensions))
+            .map(|e| e.into_path())
+            .collect();
+        let scan_options = ParseOptions {
+            quiet: true,
+            theme_fallback: ThemeFallback::Default,
+            ..ParseOptions::default()
+        };
+        drop_ignored(files, &scan_options).await
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let issues = collect_frontmatter_issues(&files, &ParseOptions::default()).await;
+    let mut problems = issues.len();
+
+    for file in &files {
+        if verbose {
+            info!("Validating links in: {}", file.display());
+        }
+
+        match validate_file_links(file, check_remote).await {
+            Ok(broken) => {
+                for item in &broken {
+                    FORMATTER.print_file_error(file, Some(item.link.line), &describe_broken_link(item));
+                }
+                problems += broken.len();
+            }
+            Err(e) => {
+                FORMATTER.print_file_error(file, None, &e.to_string());
+                problems += 1;
+                continue;
+            }
+        }
+
+        // Warnings only; the article still uploads
+        if let Ok(warnings) = lint_file(file, disabled_rules).await {
+            for warning in &warnings {
+                FORMATTER.print_file_warning(file, Some(warning.line), &warning.to_string());
+            }
+        }
+    }
+    issues.print();
+
+    if annotations_enabled() {
+        // Annotations carry the result; the exit code reports failure
+    } else if problems == 0 {
+        FORMATTER.print_success(&format!(
+            "validation passed: {} file(s) checked",
+            files.len()
+        ));
+    } else {
+        FORMATTER.print_error(&format!(
+            "validation failed: {} problem(s) in {} file(s) checked",
+            problems,
+            files.len()
+        ));
+    }
+
+    Ok(problems)
+}
+
+/// Prints a warning for each broken relative link in a body about to be uploaded
+///
+/// Lines are numbered from the start of the file, as by `--validate`.
+pub async fn warn_broken_links(path: &Path, body: &str, verbose: bool) {
+    for item in find_broken_links_by_file_line(path, body).await {
+        if verbose {
+            warn!(
+                "Broken {} in {}:{}: {} ({})",
+                item.link.kind.label(),
+                path.display(),
+                item.link.line,
+                item.link.target,
+                item.reason
+            );
+        } else {
+            FORMATTER.print_file_warning(path, Some(item.link.line), &describe_broken_link(&item));
+        }
+    }
+}
+
+/// Finds the broken relative links of `body`, the body of the file at `path`,
+/// with their lines counted from the start of the file
+async fn find_broken_links_by_file_line(path: &Path, body: &str) -> Vec<BrokenLink> {
+    let line_offset = match tokio::fs::read_to_string(path).await {
+        Ok(content) => body_line_offset(&content, body),
+        Err(_) => 0,
+    };
+
+    let mut broken = find_broken_relative_links(path, body);
+    for item in &mut broken {
+        item.link.line += line_offset;
+    }
+    broken
+}
+
+/// Describes a broken link as `broken <kind>: <target> (<reason>)`
+fn describe_broken_link(item: &BrokenLink) -> String {
+    format!(
+        "broken {}: {} ({})",
+        item.link.kind.label(),
+        item.link.target,
+        item.reason
+    )
+}
+
+/// Returns true if the target starts with a URI scheme such as `mailto:`
+fn has_scheme(target: &str) -> bool {
+    match target.split_once(':') {
+        Some((scheme, _)) => {
+            scheme.len() > 1
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+    use crate::wechat::default_markdown_extensions;
+
+    #[test]
+    fn test_extract_links_and_images() {
+        let body = "# Title\n\nSee [other](./other.md) and ![diagram](images/d.png \"Diagram\").\n\n[ref]: ../notes.md\n";
+        let links = extract_links(body);
+
+        assert_eq!(links.len(), 3);
+        assert_eq!(links[0].kind, LinkKind::Link);
+        assert_eq!(links[0].target, "./other.md");
+        assert_eq!(links[0].line, 3);
+        assert_eq!(links[1].kind, LinkKind::Image);
+        assert_eq!(links[1].target, "images/d.png");
+        assert_eq!(links[2].target, "../notes.md");
+        assert_eq!(links[2].line, 5);
+    }
+
+    #[test]
+    fn test_extract_links_ignores_code() {
+        let body = "```\n[not](a-link.md)\n```\n\nInline `[code](x.md)` and [real](y.md)\n";
+        let links = extract_links(body);
+
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].target, "y.md");
+        assert_eq!(links[0].line, 5);
+    }
+
+    #[test]
+    fn test_unlink_anchor_links() {
+        let body = "See [setup](#setup) and [the FAQ](<#faq> \"FAQ\").\n\
+                    Keep [docs](https://example.com/#setup), ![logo](#logo) and `[x](#x)`.\n\
+                    ```\n[code](#code)\n```\n";
+        assert_eq!(
+            unlink_anchor_links(body),
+            "See setup and the FAQ.\n\
+             Keep [docs](https://example.com/#setup), ![logo](#logo) and `[x](#x)`.\n\
+             ```\n[code](#code)\n```\n"
+        );
+
+        let untouched = "No anchors, just [a link](other.md#section).\n";
+        assert!(matches!(unlink_anchor_links(untouched), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_link_classification() {
+        let link = |target: &str| LinkRef {
+            kind: LinkKind::Link,
+            target: target.to_string(),
+            line: 1,
+        };
+
+        assert!(link("./a.md").is_relative());
+        assert!(link("images/a%20b.png").is_relative());
+        assert!(!link("#section").is_relative());
+        assert!(!link("/site/root.md").is_relative());
+        assert!(!link("mailto:me@example.com").is_relative());
+        assert!(!link("https://example.com").is_relative());
+        assert!(link("https://example.com").is_remote());
+        assert!(!link("./a.md").is_remote());
+    }
+
+    #[test]
+    fn test_find_broken_relative_links() {
+        let temp_dir = TempDir::new().unwrap();
+        let md_file = temp_dir.path().join("post.md");
+        fs::write(temp_dir.path().join("exists.md"), "x").unwrap();
+        fs::write(temp_dir.path().join("my image.png"), "x").unwrap();
+
+        let body = "[ok](exists.md#intro)\n![ok](my%20image.png)\n[missing](./missing.md)\n![gone](gone.png)\n[web](https://example.com/x)\n";
+        let broken = find_broken_relative_links(&md_file, body);
+
+        let targets: Vec<_> = broken.iter().map(|b| b.link.target.as_str()).collect();
+        assert_eq!(targets, vec!["./missing.md", "gone.png"]);
+        assert_eq!(broken[1].link.kind, LinkKind::Image);
+    }
+
+    #[tokio::test]
+    async fn test_validate_file_links_reports_file_line_numbers() {
+        let temp_dir = TempDir::new().unwrap();
+        let md_file = temp_dir.path().join("post.md");
+        fs::write(
+            &md_file,
+            "---\ntitle: Test\n---\n# Heading\n\n[missing](missing.md)\n",
+        )
+        .unwrap();
+
+        let broken = validate_file_links(&md_file, false).await.unwrap();
+        assert_eq!(broken.len(), 1);
+        assert_eq!(broken[0].link.line, 6);
+    }
+
+    #[tokio::test]
+    async fn test_upload_warnings_count_lines_from_file_start() {
+        let temp_dir = TempDir::new().unwrap();
+        let md_file = temp_dir.path().join("post.md");
+        let content = "---\ntitle: Test\ndescription: >\n  Two lines\n  of summary\ntags:\n  - rust\n---\nIntro\n\n[missing](missing.md)\n";
+        fs::write(&md_file, content).unwrap();
+
+        let (_frontmatter, body) = crate::markdown::parse_markdown_file(&md_file).await.unwrap();
+        let broken = find_broken_links_by_file_line(&md_file, &body).await;
+        assert_eq!(broken.len(), 1);
+        assert_eq!(broken[0].link.line, 11);
+        assert_eq!(broken, validate_file_links(&md_file, false).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_collect_frontmatter_issues_groups_by_field() {
+        let temp_dir = TempDir::new().unwrap();
+        let write = |name: &str, frontmatter: &str| {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, format!("---\n{}---\n# Body\n", frontmatter)).unwrap();
+            path
+        };
+        let files = vec![
+            write("a.md", "theme: old\n"),
+            write("b.md", "theme: classic\ncode: prism\n"),
+            write("c.md", "code: monokai\n"),
+            write("d.md", "code: highlightjs\nslug: not a slug\n"),
+            write("broken.md", "title: [unclosed\n"),
+        ];
+
+        let issues = collect_frontmatter_issues(&files, &ParseOptions::default()).await;
+        assert_eq!((issues.len(), issues.file_count()), (5, 3));
+
+        let groups: Vec<_> = issues
+            .groups()
+            .map(|(field, files)| {
+                let values = files.iter().map(|(path, value)| {
+                    (path.file_name().unwrap().to_str().unwrap(), value.as_str())
+                });
+                (field, values.collect::<Vec<_>>())
+            })
+            .collect();
+        assert_eq!(
+            groups,
+            vec![
+                (FrontmatterField::Theme, vec![("a.md", "old"), ("b.md", "classic")]),
+                (
+                    FrontmatterField::CodeHighlighter,
+                    vec![("b.md", "prism"), ("d.md", "highlightjs")]
+                ),
+                (FrontmatterField::Slug, vec![("d.md", "not a slug")]),
+            ]
+        );
+
+        // Themes are left to the fallback policy, which replaces them
+        let fallback = ParseOptions {
+            theme_fallback: crate::models::ThemeFallback::Default,
+            ..Default::default()
+        };
+        let issues = collect_frontmatter_issues(&files, &fallback).await;
+        assert_eq!((issues.len(), issues.file_count()), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_validate_path_counts_every_invalid_value() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("a.md"), "---\ntheme: old\n---\n[x](missing.md)\n").unwrap();
+        fs::write(temp_dir.path().join("b.md"), "---\ncode: prism\n---\nBody\n").unwrap();
+        // Not an article, so neither its theme nor its link is a problem
+        fs::write(
+            temp_dir.path().join("snippet.md"),
+            "---\nignore: true\ntheme: old\n---\n[x](missing.md)\n",
+        )
+        .unwrap();
+
+        let problems = validate_path(temp_dir.path(), false, &[], &default_markdown_extensions(), false).await.unwrap();
+        assert_eq!(problems, 3);
+    }
+
+    #[tokio::test]
+    async fn test_find_broken_remote_links() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .and(path("/ok"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        Mock::given(method("HEAD"))
+            .and(path("/missing"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+
+        let body = format!(
+            "[ok]({0}/ok)\n[missing]({0}/missing)\n[local](local.md)\n",
+            server.uri()
+        );
+        let broken = find_broken_remote_links(&extract_links(&body)).await;
+
+        assert_eq!(broken.len(), 1);
+        assert!(broken[0].link.target.ends_with("/missing"));
+        assert!(broken[0].reason.contains("404"));
+    }
+}
@@ -3,23 +3,628 @@
 //! This module provides WeChat public account functionality for uploading
 //! markdown articles with automatic cover image generation and frontmatter management.
 
+use crate::backup::{Backup, DATA_DIR};
+use crate::budget::CostEstimate;
+use crate::content_check::{
+    ContentChecker, MSG_SEC_CHECK_PATH, SecCheckResponse, diagnose_file, print_diagnosis,
+};
+use crate::cover_format::{
+    CoverEncoding, CoverFormat, convert_cover, copy_cover, cover_name_with_format,
+};
+use crate::cover_optimize::{OPTIMIZE_THRESHOLD_BYTES, OptimizeOutcome, optimize_png};
+use crate::cover_template::{CoverTemplate, render_cover};
 use crate::error::{Error, Result};
-use crate::markdown::{parse_markdown_file, update_frontmatter, write_markdown_file};
-use crate::models::Frontmatter;
-use crate::providers::{UniversalAIClient, CoverImageProcessor};
-use crate::output::{FORMATTER, FilePathFormatter, OutputFormatter};
+use crate::hooks::Hooks;
+use crate::inline_images::{find_data_uris, upload_inline_images};
+use crate::markdown::{
+    ArticleContext, BodyTransform, FileFingerprint, ParseOptions, TransformPipeline, apply_stage, extract_h1_title, parse_frontmatter_file_with,
+    parse_markdown_file_with,
+    sidecar_path, update_frontmatter_with, uses_sidecar, write_markdown_file,
+    write_markdown_file_with,
+};
+use crate::models::{
+    AccountKind, AnchorLinks, Capability, Config, CoverSelect, DIGEST_MAX_CHARS, Frontmatter,
+    MAX_COVER_VARIATIONS, MaterialDenied, PipelineLimits, PublicationStatus, THUMB_FIRST_IMAGE, ThemeFallback,
+    WikiLinks, truncate_digest,
+};
+use crate::prompt::Prompter;
+use crate::rate_limit::UploadPacer;
+use crate::providers::{
+    ArticleSummary, CoverImageProcessor, UniversalAIClient, generated_cover_filename,
+};
+use crate::report::{CoverOutcome, FileOutcome, UploadReport, WarningLog};
+use crate::slug::slug_for;
+use crate::output::{FORMATTER, FilePathFormatter, OutputFormatter, annotations_enabled};
+use crate::lint::warn_unsupported;
+use crate::obsidian::ObsidianTransform;
+use crate::validation::{AnchorLinksTransform, LinkKind, extract_links, warn_broken_links};
+use crate::wechat_api::WeChatApiClient;
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::mpsc;
 use tracing::{info, warn};
 use walkdir::WalkDir;
 
 // Re-export the WeChat client type
 pub use wechat_pub_rs::WeChatClient;
+use wechat_pub_rs::UploadOptions;
+
+/// Options that apply to every file processed in an upload run
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessOptions {
+    /// Whether to enable detailed tracing logs
+    pub verbose: bool,
+    /// Fail on duplicate frontmatter keys instead of warning
+    pub strict_frontmatter: bool,
+    /// Locate the offending text when WeChat rejects content as risky
+    pub diagnose_content: bool,
+    /// Generate covers ahead of uploads in a pipeline, if set
+    pub pipeline: Option<PipelineLimits>,
+    /// Losslessly recompress PNG covers before upload
+    pub optimize_covers: bool,
+    /// Format and quality generated covers are saved with
+    pub cover_encoding: CoverEncoding,
+    /// Convert existing covers to the format of `cover_encoding`
+    pub convert_covers: bool,
+    /// Write publish state and generated cover names back to the source files
+    pub write_back: bool,
+    /// Set `published: draft` and the draft ID after an upload
+    pub set_draft_status: bool,
+    /// State file whose recorded uploads are skipped; set while write-back is disabled
+    pub state_file: Option<PathBuf>,
+    /// Derive a slug from the title for files without one
+    pub auto_slug: bool,
+    /// What to do with files whose theme or code highlighter is unknown
+    pub theme_fallback: ThemeFallback,
+    /// What to do when the account may not upload the cover as permanent material
+    pub on_material_denied: MaterialDenied,
+    /// Which image is used when `cover` names a directory of candidates
+    pub cover_select: CoverSelect,
+    /// Theme that replaces an unknown one under [`ThemeFallback::Default`]
+    pub default_theme: Option<String>,
+    /// Code highlighter that replaces an unknown one under [`ThemeFallback::Default`]
+    pub default_code_highlighter: Option<String>,
+    /// Directory that cover paths starting with `/` are resolved against
+    pub content_root: Option<PathBuf>,
+    /// Template that missing covers are rendered from instead of with AI
+    pub cover_template: Option<CoverTemplate>,
+    /// Shell commands run before and after each upload
+    pub hooks: Hooks,
+    /// Fail a file whose `pre_upload` hook fails instead of skipping it
+    pub strict_hooks: bool,
+    /// Number of candidate covers generated for a missing cover
+    pub cover_variations: u32,
+    /// Asks which cover candidate to use
+    pub prompt: Prompter,
+    /// Image used when a cover is missing and cannot be generated
+    pub default_cover: Option<PathBuf>,
+    /// Declare articles as original content unless their frontmatter says otherwise
+    pub original: bool,
+    /// Characters of `description` sent as the digest before it is truncated
+    pub digest_limit: usize,
+    /// Lint rules not reported for WeChat-unsupported markdown in verbose mode
+    pub disabled_lint_rules: Vec<String>,
+    /// What to do with links to headings of the same article
+    pub anchor_links: AnchorLinks,
+    /// Convert Obsidian embeds and wiki links in the uploaded body
+    pub obsidian: bool,
+    /// Vault root Obsidian embeds are resolved against
+    pub obsidian_vault: Option<PathBuf>,
+    /// What to do with Obsidian wiki links
+    pub wiki_links: WikiLinks,
+    /// Body transforms run on upload, in order; `None` runs the ones other
+    /// options turn on, followed by `custom_transforms`
+    pub transforms: Option<Vec<String>>,
+    /// Body transforms added through [`crate::WxUploader::with_transforms`]
+    pub custom_transforms: TransformPipeline,
+    /// Read and write the frontmatter in sidecar files; see [`crate::markdown`]
+    pub sidecar_frontmatter: bool,
+    /// Fail files that have no cover image after cover processing
+    pub require_cover: bool,
+    /// Stop the run at the first failed file
+    pub fail_fast: bool,
+    /// Where source files are backed up before the run first modifies them
+    pub backup: Option<Arc<Backup>>,
+    /// Themes replacing the frontmatter theme of the files they are keyed by,
+    /// for the upload only (from `--manifest` entries)
+    pub theme_overrides: HashMap<PathBuf, String>,
+    /// Where the warnings printed while processing files are recorded
+    pub warnings: WarningLog,
+    /// Pause of each upload worker after it created a draft
+    pub pacer: UploadPacer,
+    /// Extensions, without the dot, of the files a directory run uploads
+    pub markdown_extensions: Vec<String>,
+    /// Follow symbolic links while collecting the files of a directory
+    pub follow_symlinks: bool,
+}
+
+impl Default for ProcessOptions {
+    fn default() -> Self {
+        Self {
+            verbose: false,
+            strict_frontmatter: false,
+            diagnose_content: false,
+            pipeline: None,
+            optimize_covers: false,
+            cover_encoding: CoverEncoding::default(),
+            convert_covers: false,
+            write_back: true,
+            set_draft_status: true,
+            state_file: None,
+            auto_slug: false,
+            theme_fallback: ThemeFallback::default(),
+            on_material_denied: MaterialDenied::default(),
+            cover_select: CoverSelect::default(),
+            default_theme: None,
+            default_code_highlighter: None,
+            content_root: None,
+            cover_template: None,
+            hooks: Hooks::default(),
+            strict_hooks: false,
+            cover_variations: 1,
+            prompt: Prompter::default(),
+            default_cover: None,
+            original: false,
+            digest_limit: DIGEST_MAX_CHARS,
+            disabled_lint_rules: Vec::new(),
+            anchor_links: AnchorLinks::default(),
+            obsidian: false,
+            obsidian_vault: None,
+            wiki_links: WikiLinks::default(),
+            transforms: None,
+            custom_transforms: TransformPipeline::default(),
+            sidecar_frontmatter: false,
+            require_cover: false,
+            fail_fast: false,
+            backup: None,
+            theme_overrides: HashMap::new(),
+            warnings: WarningLog::default(),
+            pacer: UploadPacer::default(),
+            markdown_extensions: default_markdown_extensions(),
+            follow_symlinks: false,
+        }
+    }
+}
+
+impl ProcessOptions {
+    /// Returns the directory generated images are written to, if not next to the article
+    ///
+    /// With write-back disabled the source tree may be read-only, so covers go
+    /// to [`read_only_cover_dir`].
+    pub fn cover_dir(&self) -> Option<PathBuf> {
+        (!self.write_back).then(read_only_cover_dir)
+    }
+
+    /// Returns true if uploads are recorded in the frontmatter of the uploaded files
+    pub fn updates_status(&self) -> bool {
+        self.write_back && self.set_draft_status
+    }
+
+    /// Backs up `path`, and its sidecar if frontmatter is read from sidecars,
+    /// before the run first modifies it
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backup fails; the file must not be modified then
+    pub async fn back_up(&self, path: &Path) -> Result<()> {
+        let Some(backup) = &self.backup else {
+            return Ok(());
+        };
+        backup.save(path).await?;
+        let sidecar = sidecar_path(path);
+        if self.sidecar_frontmatter || sidecar.exists() {
+            backup.save(&sidecar).await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the markdown parse options for this run
+    pub fn parse_options(&self) -> ParseOptions {
+        ParseOptions {
+            strict_frontmatter: self.strict_frontmatter,
+            quiet: false,
+            theme_fallback: self.theme_fallback,
+            sidecar_frontmatter: self.sidecar_frontmatter,
+            defer_validation: false,
+        }
+    }
+
+    /// Returns the body transforms an upload of a file with `frontmatter` runs
+    ///
+    /// The frontmatter `transforms` list, or else the `transforms` setting,
+    /// names the stages in order, looked up among the custom transforms first
+    /// and then the built-in `obsidian` and `anchor-links`. Without either,
+    /// the built-ins turned on by `obsidian` and `anchor_links` run, followed
+    /// by the custom transforms.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a name matches no transform
+    pub fn transform_pipeline(&self, frontmatter: &Frontmatter) -> Result<TransformPipeline> {
+        let obsidian = || -> Arc<dyn BodyTransform> {
+            Arc::new(ObsidianTransform {
+                vault: self.obsidian_vault.clone().or_else(|| self.content_root.clone()),
+                wiki_links: self.wiki_links,
+            })
+        };
+        let Some(names) = frontmatter.transforms.as_ref().or(self.transforms.as_ref()) else {
+            let mut pipeline = TransformPipeline::default();
+            if self.obsidian {
+                pipeline.push(obsidian());
+            }
+            if self.anchor_links == AnchorLinks::Text {
+                pipeline.push(Arc::new(AnchorLinksTransform));
+            }
+            for stage in self.custom_transforms.stages() {
+                pipeline.push(stage.clone());
+            }
+            return Ok(pipeline);
+        };
+
+        let mut pipeline = TransformPipeline::default();
+        for name in names {
+            let stage = match self.custom_transforms.get(name) {
+                Some(stage) => stage.clone(),
+                None if name == ObsidianTransform::NAME => obsidian(),
+                None if name == AnchorLinksTransform::NAME => Arc::new(AnchorLinksTransform),
+                None => {
+                    let mut available = vec![ObsidianTransform::NAME, AnchorLinksTransform::NAME];
+                    available.extend(self.custom_transforms.names());
+                    return Err(Error::config(format!(
+                        "unknown body transform '{}' (available: {})",
+                        name,
+                        available.join(", ")
+                    )));
+                }
+            };
+            pipeline.push(stage);
+        }
+        Ok(pipeline)
+    }
+
+    /// Returns the parse options for reading frontmatter ahead of the run,
+    /// quietly and whatever the theme
+    pub fn scan_options(&self) -> ParseOptions {
+        ParseOptions {
+            strict_frontmatter: false,
+            quiet: true,
+            theme_fallback: ThemeFallback::Default,
+            sidecar_frontmatter: self.sidecar_frontmatter,
+            defer_validation: false,
+        }
+    }
+}
+
+impl From<&Config> for ProcessOptions {
+    fn from(config: &Config) -> Self {
+        Self {
+            verbose: config.verbose,
+            strict_frontmatter: config.strict_frontmatter,
+            diagnose_content: config.diagnose_content,
+            pipeline: config.pipeline,
+            optimize_covers: config.optimize_covers,
+            cover_encoding: config.cover_encoding(),
+            convert_covers: config.convert_covers,
+            write_back: config.write_back,
+            set_draft_status: config.set_draft_status,
+            state_file: (!config.write_back).then(|| config.state_file_path()),
+            auto_slug: config.auto_slug,
+            theme_fallback: config.theme_fallback,
+            on_material_denied: config.on_material_denied,
+            cover_select: config.cover_select,
+            default_theme: config.default_theme.clone(),
+            default_code_highlighter: config.default_code_highlighter.clone(),
+            content_root: config.content_root.clone(),
+            cover_template: config.cover_template.clone(),
+            hooks: config.hooks.clone(),
+            strict_hooks: config.strict_hooks,
+            cover_variations: config.cover_variations,
+            prompt: config.prompt.clone(),
+            default_cover: config.wechat_account.default_cover.clone(),
+            original: config.original,
+            digest_limit: config.digest_limit,
+            disabled_lint_rules: config.disabled_lint_rules.clone(),
+            anchor_links: config.anchor_links,
+            obsidian: config.obsidian,
+            obsidian_vault: config.obsidian_vault.clone(),
+            wiki_links: config.wiki_links,
+            transforms: config.transforms.clone(),
+            // Added in code only; see `WxUploader::with_transforms`
+            custom_transforms: TransformPipeline::default(),
+            sidecar_frontmatter: config.sidecar_frontmatter,
+            require_cover: config.require_cover,
+            fail_fast: config.fail_fast,
+            // One backup set per run; see `WxUploader`
+            backup: None,
+            theme_overrides: HashMap::new(),
+            warnings: WarningLog::default(),
+            pacer: config.upload_pacer(),
+            markdown_extensions: config.markdown_extensions.clone(),
+            follow_symlinks: config.follow_symlinks,
+        }
+    }
+}
+
+/// Per-article options for a draft upload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArticleOptions {
+    /// Image uploaded as the draft thumbnail; defaults to the frontmatter cover
+    pub thumb: Option<String>,
+    /// Existing permanent material used as the thumbnail instead of an
+    /// uploaded image (`cover_media_id`)
+    pub thumb_media_id: Option<String>,
+    /// Whether the frontmatter opts out of a cover (`cover: none`), so it must
+    /// not be used as the thumbnail
+    pub cover_disabled: bool,
+    /// Whether WeChat shows the thumbnail at the top of the article body
+    pub show_cover: bool,
+    /// Draft article fields taken from the frontmatter
+    pub fields: ArticleFields,
+    /// What to do when the account may not upload the thumbnail as permanent material
+    pub on_material_denied: MaterialDenied,
+    /// Markdown file the draft is made from; the uploaded file may be a
+    /// rewritten copy of it
+    pub article: PathBuf,
+    /// Where warnings about the upload are recorded, naming `article`
+    pub warnings: WarningLog,
+}
+
+impl Default for ArticleOptions {
+    fn default() -> Self {
+        Self {
+            thumb: None,
+            thumb_media_id: None,
+            cover_disabled: false,
+            show_cover: true,
+            fields: ArticleFields::default(),
+            on_material_denied: MaterialDenied::default(),
+            article: PathBuf::new(),
+            warnings: WarningLog::default(),
+        }
+    }
+}
+
+/// Draft article fields set from the frontmatter
+///
+/// Unset text fields fall back to what the markdown parser finds in the file:
+/// the title, the `author` field and the description or an excerpt.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArticleFields {
+    /// Article title (`title`)
+    pub title: Option<String>,
+    /// Author name (`author`)
+    pub author: Option<String>,
+    /// Summary shown in the article list (`description`)
+    pub digest: Option<String>,
+    /// "Read more" link (`content_source_url`)
+    pub content_source_url: Option<String>,
+    /// Whether comments are open (`need_open_comment`)
+    pub need_open_comment: bool,
+    /// Whether only followers can comment (`only_fans_can_comment`)
+    pub only_fans_can_comment: bool,
+    /// Whether the article is declared as original content (`original`)
+    pub original: bool,
+    /// Author named in the original declaration (`original_author`)
+    pub original_author: Option<String>,
+    /// 2.35:1 thumbnail crop (`pic_crop_235_1`)
+    pub pic_crop_235_1: Option<String>,
+    /// 1:1 thumbnail crop (`pic_crop_1_1`)
+    pub pic_crop_1_1: Option<String>,
+}
+
+impl From<&Frontmatter> for ArticleFields {
+    fn from(frontmatter: &Frontmatter) -> Self {
+        let non_empty = |value: Option<&str>| {
+            value
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+        };
+
+        Self {
+            title: non_empty(frontmatter.title.as_deref()),
+            author: non_empty(frontmatter.author()),
+            digest: non_empty(Some(&frontmatter.description)),
+            content_source_url: non_empty(frontmatter.content_source_url.as_deref()),
+            need_open_comment: frontmatter.need_open_comment.unwrap_or(false),
+            only_fans_can_comment: frontmatter.only_fans_can_comment.unwrap_or(false),
+            original: frontmatter.original.unwrap_or(false),
+            original_author: non_empty(frontmatter.original_author.as_deref()),
+            pic_crop_235_1: frontmatter.pic_crop_235_1.clone(),
+            pic_crop_1_1: frontmatter.pic_crop_1_1.clone(),
+        }
+    }
+}
+
+/// Article object of the WeChat `draft/add` request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DraftArticle {
+    /// Article title
+    pub title: String,
+    /// Author name
+    pub author: String,
+    /// Summary shown in the article list
+    pub digest: String,
+    /// Rendered HTML body
+    pub content: String,
+    /// "Read more" link
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_source_url: Option<String>,
+    /// Media ID of the permanent thumbnail material; left out when the
+    /// account may not upload any
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub thumb_media_id: String,
+    /// Show the thumbnail at the top of the body (0 or 1)
+    pub show_cover_pic: u8,
+    /// Comments open (0 or 1)
+    pub need_open_comment: u8,
+    /// Only followers can comment (0 or 1)
+    pub only_fans_can_comment: u8,
+    /// Declared as original content; only sent when declared
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_original: Option<u8>,
+    /// Author named in the original declaration
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_author: Option<String>,
+    /// 2.35:1 thumbnail crop, `X1_Y1_X2_Y2`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pic_crop_235_1: Option<String>,
+    /// 1:1 thumbnail crop, `X1_Y1_X2_Y2`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pic_crop_1_1: Option<String>,
+}
+
+impl DraftArticle {
+    /// Builds the draft article from resolved fields, the rendered body and the thumbnail
+    ///
+    /// Fields left unset become `Untitled`, `Anonymous` and an empty digest.
+    /// The original declaration names the author unless `original_author` is set.
+    pub fn new(
+        fields: &ArticleFields,
+        content: String,
+        thumb_media_id: String,
+        show_cover: bool,
+    ) -> Self {
+        let author = fields.author.clone().unwrap_or_else(|| "Anonymous".to_string());
+        let original_author = fields
+            .original
+            .then(|| fields.original_author.clone().unwrap_or_else(|| author.clone()));
+        Self {
+            title: fields.title.clone().unwrap_or_else(|| "Untitled".to_string()),
+            author,
+            digest: fields.digest.clone().unwrap_or_default(),
+            content,
+            content_source_url: fields.content_source_url.clone(),
+            thumb_media_id,
+            show_cover_pic: u8::from(show_cover),
+            need_open_comment: u8::from(fields.need_open_comment),
+            only_fans_can_comment: u8::from(fields.only_fans_can_comment),
+            is_original: fields.original.then_some(1),
+            original_author,
+            pic_crop_235_1: fields.pic_crop_235_1.clone(),
+            pic_crop_1_1: fields.pic_crop_1_1.clone(),
+        }
+    }
+}
+
+/// Most drafts the WeChat `draft/batchget` endpoint returns per request
+pub const DRAFT_PAGE_SIZE: u32 = 20;
+
+/// Number of recent drafts `--reconcile` matches files against
+pub const RECONCILE_DRAFT_LIMIT: u32 = 100;
+
+/// A draft in the WeChat draft box
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DraftSummary {
+    /// Media ID of the draft
+    pub media_id: String,
+    /// Title of the first article in the draft
+    pub title: String,
+    /// Last update time in seconds since the Unix epoch
+    pub update_time: u64,
+}
+
+/// Existing drafts keyed by title, for `--reconcile`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DraftIndex {
+    by_title: HashMap<String, (String, u64)>,
+}
+
+impl DraftIndex {
+    /// Indexes drafts by trimmed title; the most recently updated draft wins a shared title
+    pub fn new(drafts: &[DraftSummary]) -> Self {
+        let mut by_title: HashMap<String, (String, u64)> = HashMap::new();
+        for draft in drafts {
+            let title = draft.title.trim();
+            if title.is_empty() {
+                continue;
+            }
+            let newer = by_title
+                .get(title)
+                .is_none_or(|(_, update_time)| draft.update_time > *update_time);
+            if newer {
+                by_title.insert(title.to_string(), (draft.media_id.clone(), draft.update_time));
+            }
+        }
+        Self { by_title }
+    }
+
+    /// Returns the media ID of the draft with `title`, if there is one
+    pub fn find(&self, title: &str) -> Option<&str> {
+        self.by_title.get(title.trim()).map(|(media_id, _)| media_id.as_str())
+    }
+
+    /// Returns the media ID of the draft matching the title of `frontmatter`
+    pub fn find_for(&self, frontmatter: &Frontmatter) -> Option<&str> {
+        frontmatter.title.as_deref().and_then(|title| self.find(title))
+    }
+
+    /// Returns the number of distinct titles
+    pub fn len(&self) -> usize {
+        self.by_title.len()
+    }
+
+    /// Returns true if no draft has a title
+    pub fn is_empty(&self) -> bool {
+        self.by_title.is_empty()
+    }
+}
+
+/// Returns the reason a file with an existing draft is skipped
+pub fn existing_draft_reason(draft_id: &str) -> String {
+    format!("draft {} already exists on WeChat", draft_id)
+}
 
 /// Trait for uploading content to WeChat
 #[async_trait::async_trait]
-pub trait WeChatUploader {
+pub trait WeChatUploader: Send + Sync {
     /// Uploads a file to WeChat and returns the draft ID
-    async fn upload(&self, file_path: &str) -> Result<String>;
+    async fn upload(&self, file_path: &str, options: &ArticleOptions) -> Result<String>;
+
+    /// Forces a refresh of the access token and returns the new token
+    async fn refresh_token(&self) -> Result<String>;
+
+    /// Returns a checker backed by the WeChat `msg_sec_check` endpoint
+    async fn content_checker(&self) -> Result<Box<dyn ContentChecker + '_>>;
+
+    /// Lists up to `limit` drafts from the draft box, most recently updated first
+    async fn list_recent_drafts(&self, limit: u32) -> Result<Vec<DraftSummary>>;
+
+    /// Uploads an image shown in an article body and returns its WeChat URL
+    ///
+    /// Images are deduplicated by content, so uploading the same bytes again
+    /// returns the URL of the earlier upload.
+    async fn upload_body_image(&self, image_path: &Path) -> Result<String>;
+}
+
+/// Fetches up to `limit` drafts with `fetch_page(offset, count)`, one page at a time
+///
+/// Stops early when a page comes back short, i.e. the draft box is exhausted.
+pub(crate) async fn list_drafts_paged<F, Fut>(
+    limit: u32,
+    mut fetch_page: F,
+) -> Result<Vec<DraftSummary>>
+where
+    F: FnMut(u32, u32) -> Fut,
+    Fut: Future<Output = Result<Vec<DraftSummary>>>,
+{
+    let mut drafts = Vec::new();
+    while (drafts.len() as u32) < limit {
+        let offset = drafts.len() as u32;
+        let count = (limit - offset).min(DRAFT_PAGE_SIZE);
+        let page = fetch_page(offset, count).await?;
+        let exhausted = (page.len() as u32) < count;
+        drafts.extend(page);
+        if exhausted {
+            break;
+        }
+    }
+    drafts.truncate(limit as usize);
+    Ok(drafts)
 }
 
 /// Trait for processing cover images (local to wechat module)
@@ -35,7 +640,7 @@ pub trait LocalCoverImageProcessor {
     /// Generates a cover image if missing
     async fn ensure_cover_image(
         &self,
-        content: &str,
+        article: &ArticleSummary,
         markdown_path: &Path,
         cover_filename: Option<&str>,
     ) -> Result<Option<String>>;
@@ -44,21 +649,335 @@ pub trait LocalCoverImageProcessor {
 /// Default implementation of WeChat uploader
 #[async_trait::async_trait]
 impl WeChatUploader for WeChatClient {
-    async fn upload(&self, file_path: &str) -> Result<String> {
-        self.upload(file_path)
+    async fn upload(&self, file_path: &str, options: &ArticleOptions) -> Result<String> {
+        // Left to itself, wechat_pub_rs would upload the cover "none"
+        if options.cover_disabled && options.thumb.is_none() {
+            return Err(no_cover_thumb_error());
+        }
+        // wechat_pub_rs always uploads the thumbnail from a file
+        if let Some(media_id) = &options.thumb_media_id {
+            return Err(Error::config(format!(
+                "cover_media_id '{}' is only used when a WeChat base URL is configured; \
+                 set wechat_base_url: {}",
+                media_id, crate::wechat_api::DEFAULT_WECHAT_BASE_URL
+            )));
+        }
+
+        let fields = &options.fields;
+        if fields.pic_crop_235_1.is_some() || fields.pic_crop_1_1.is_some() {
+            options.warnings.warn(
+                &options.article,
+                "pic_crop_235_1 and pic_crop_1_1 are only sent when a WeChat base URL is configured",
+            );
+        }
+        if fields.original {
+            options.warnings.warn(
+                &options.article,
+                "the original declaration is only sent when a WeChat base URL is configured",
+            );
+        }
+
+        // The digest is read from the file's description by wechat_pub_rs itself
+        let upload_options = UploadOptions {
+            cover_image: options.thumb.clone(),
+            show_cover: options.show_cover,
+            title: fields.title.clone(),
+            author: fields.author.clone(),
+            source_url: fields.content_source_url.clone(),
+            enable_comments: fields.need_open_comment,
+            fans_only_comments: fields.only_fans_can_comment,
+            ..Default::default()
+        };
+
+        self.upload_with_options(file_path, upload_options)
+            .await
+            .map_err(Error::from_wechat)
+    }
+
+    async fn refresh_token(&self) -> Result<String> {
+        WeChatClient::refresh_token(self)
+            .await
+            .map_err(Error::from_wechat)
+    }
+
+    async fn content_checker(&self) -> Result<Box<dyn ContentChecker + '_>> {
+        // wechat_pub_rs does not expose its cached token, so fetch one for the checks
+        let access_token = WeChatUploader::refresh_token(self).await?;
+        Ok(Box::new(PubRsContentChecker {
+            client: self,
+            access_token,
+        }))
+    }
+
+    async fn list_recent_drafts(&self, limit: u32) -> Result<Vec<DraftSummary>> {
+        list_drafts_paged(limit, |offset, count| async move {
+            let drafts = self.list_drafts(offset, count).await.map_err(Error::from_wechat)?;
+            Ok(drafts
+                .into_iter()
+                .map(|draft| DraftSummary {
+                    title: draft
+                        .content
+                        .news_item
+                        .first()
+                        .map(|article| article.title.clone())
+                        .unwrap_or_default(),
+                    media_id: draft.media_id,
+                    update_time: draft.update_time,
+                })
+                .collect())
+        })
+        .await
+    }
+
+    async fn upload_body_image(&self, image_path: &Path) -> Result<String> {
+        // wechat_pub_rs deduplicates images by their BLAKE3 hash itself
+        let path = image_path
+            .to_str()
+            .ok_or_else(|| Error::generic("Path contains invalid UTF-8"))?;
+        self.upload_image(path).await.map_err(Error::from_wechat)
+    }
+}
+
+/// Uploader of accounts without a `wechat_base_url`
+///
+/// Articles are uploaded with wechat_pub_rs, except those needing what it
+/// cannot send: the original declaration, cover crops or a reused thumbnail
+/// material. Those go through a [`WeChatApiClient`] at
+/// [`DEFAULT_WECHAT_BASE_URL`](crate::wechat_api::DEFAULT_WECHAT_BASE_URL),
+/// as does an upload whose cover WeChat refused with errcode 48001, so
+/// `on_material_denied` applies to it.
+pub struct DefaultEndpointUploader {
+    client: Box<dyn WeChatUploader>,
+    api: WeChatApiClient,
+}
+
+impl DefaultEndpointUploader {
+    /// Combines `client`, the wechat_pub_rs client, with the API client used
+    /// for the articles it cannot upload
+    pub fn new(client: Box<dyn WeChatUploader>, api: WeChatApiClient) -> Self {
+        Self { client, api }
+    }
+}
+
+/// Returns true if an upload needs fields wechat_pub_rs does not send
+fn needs_api_client(options: &ArticleOptions) -> bool {
+    let fields = &options.fields;
+    fields.original
+        || fields.pic_crop_235_1.is_some()
+        || fields.pic_crop_1_1.is_some()
+        || options.thumb_media_id.is_some()
+}
+
+#[async_trait::async_trait]
+impl WeChatUploader for DefaultEndpointUploader {
+    async fn upload(&self, file_path: &str, options: &ArticleOptions) -> Result<String> {
+        if needs_api_client(options) {
+            return self.api.upload(file_path, options).await;
+        }
+        match self.client.upload(file_path, options).await {
+            Err(e) if e.is_api_unauthorized() => {
+                info!(
+                    "WeChat refused the material of {} (errcode 48001), retrying with on_material_denied",
+                    file_path
+                );
+                self.api.upload(file_path, options).await
+            }
+            result => result,
+        }
+    }
+
+    async fn refresh_token(&self) -> Result<String> {
+        self.client.refresh_token().await
+    }
+
+    async fn content_checker(&self) -> Result<Box<dyn ContentChecker + '_>> {
+        self.client.content_checker().await
+    }
+
+    async fn list_recent_drafts(&self, limit: u32) -> Result<Vec<DraftSummary>> {
+        self.client.list_recent_drafts(limit).await
+    }
+
+    async fn upload_body_image(&self, image_path: &Path) -> Result<String> {
+        self.client.upload_body_image(image_path).await
+    }
+}
+
+/// Returns the error for an article with `cover: none` and no thumbnail to use instead
+pub fn no_cover_thumb_error() -> Error {
+    Error::config(
+        "cover: none leaves the draft without the thumbnail WeChat requires; \
+         set thumb in the frontmatter or default_cover for the account",
+    )
+}
+
+/// Uploader that uses an account's `default_cover` as the thumbnail of articles with `cover: none`
+pub struct DefaultCoverUploader {
+    inner: Box<dyn WeChatUploader>,
+    default_cover: String,
+}
+
+impl DefaultCoverUploader {
+    /// Wraps `inner`; a relative `default_cover` is taken relative to the current directory
+    pub fn new(inner: Box<dyn WeChatUploader>, default_cover: &Path) -> Self {
+        // The uploaders resolve relative thumbnails against the article's directory
+        let default_cover = std::path::absolute(default_cover)
+            .unwrap_or_else(|_| default_cover.to_path_buf())
+            .display()
+            .to_string();
+        Self {
+            inner,
+            default_cover,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl WeChatUploader for DefaultCoverUploader {
+    async fn upload(&self, file_path: &str, options: &ArticleOptions) -> Result<String> {
+        if options.cover_disabled && options.thumb.is_none() {
+            let options = ArticleOptions {
+                thumb: Some(self.default_cover.clone()),
+                ..options.clone()
+            };
+            return self.inner.upload(file_path, &options).await;
+        }
+        self.inner.upload(file_path, options).await
+    }
+
+    async fn refresh_token(&self) -> Result<String> {
+        self.inner.refresh_token().await
+    }
+
+    async fn content_checker(&self) -> Result<Box<dyn ContentChecker + '_>> {
+        self.inner.content_checker().await
+    }
+
+    async fn list_recent_drafts(&self, limit: u32) -> Result<Vec<DraftSummary>> {
+        self.inner.list_recent_drafts(limit).await
+    }
+
+    async fn upload_body_image(&self, image_path: &Path) -> Result<String> {
+        self.inner.upload_body_image(image_path).await
+    }
+}
+
+/// Uploader for sandbox (mp test) accounts, which leaves out what they do not support
+///
+/// Sandbox accounts answer an original declaration with a bare errcode, so
+/// drafts are created without it and a warning says why.
+pub struct SandboxUploader {
+    inner: Box<dyn WeChatUploader>,
+    kind: AccountKind,
+}
+
+impl SandboxUploader {
+    /// Wraps `inner`, the client of an account of `kind`
+    pub fn new(inner: Box<dyn WeChatUploader>, kind: AccountKind) -> Self {
+        Self { inner, kind }
+    }
+}
+
+#[async_trait::async_trait]
+impl WeChatUploader for SandboxUploader {
+    async fn upload(&self, file_path: &str, options: &ArticleOptions) -> Result<String> {
+        if options.fields.original
+            && let Some(reason) = self.kind.unsupported(Capability::OriginalDeclaration)
+        {
+            options
+                .warnings
+                .warn_file(&options.article, &format!("{}; uploading without it", reason));
+            let mut options = options.clone();
+            options.fields.original = false;
+            options.fields.original_author = None;
+            return self.inner.upload(file_path, &options).await;
+        }
+        self.inner.upload(file_path, options).await
+    }
+
+    async fn refresh_token(&self) -> Result<String> {
+        self.inner.refresh_token().await
+    }
+
+    async fn content_checker(&self) -> Result<Box<dyn ContentChecker + '_>> {
+        self.inner.content_checker().await
+    }
+
+    async fn list_recent_drafts(&self, limit: u32) -> Result<Vec<DraftSummary>> {
+        self.inner.list_recent_drafts(limit).await
+    }
+
+    async fn upload_body_image(&self, image_path: &Path) -> Result<String> {
+        self.inner.upload_body_image(image_path).await
+    }
+}
+
+/// Content checker that reuses the `wechat_pub_rs` HTTP client
+struct PubRsContentChecker<'a> {
+    client: &'a WeChatClient,
+    access_token: String,
+}
+
+#[async_trait::async_trait]
+impl ContentChecker for PubRsContentChecker<'_> {
+    async fn is_text_safe(&self, text: &str) -> Result<bool> {
+        let response = self
+            .client
+            .http_client()
+            .post_json_with_token(
+                MSG_SEC_CHECK_PATH,
+                &self.access_token,
+                &serde_json::json!({ "content": text }),
+            )
             .await
-            .map_err(|e| Error::wechat(e.to_string()))
+            .map_err(Error::from_wechat)?;
+
+        let body: SecCheckResponse = response.json().await?;
+        body.into_verdict()
     }
 }
 
+/// Directory for images generated while write-back is disabled
+pub fn read_only_cover_dir() -> PathBuf {
+    std::env::temp_dir().join("wx-uploader").join("covers")
+}
+
 /// Default cover image processor implementation
 pub struct DefaultCoverImageProcessor<'a> {
     ai_client: Option<&'a UniversalAIClient>,
+    output_dir: Option<PathBuf>,
+    base_name: Option<String>,
+    content_root: Option<PathBuf>,
 }
 
 impl<'a> DefaultCoverImageProcessor<'a> {
     pub fn new(ai_client: Option<&'a UniversalAIClient>) -> Self {
-        Self { ai_client }
+        Self {
+            ai_client,
+            output_dir: None,
+            base_name: None,
+            content_root: None,
+        }
+    }
+
+    /// Names generated covers after `name` instead of the markdown file stem
+    pub fn with_base_name(mut self, name: Option<String>) -> Self {
+        self.base_name = name;
+        self
+    }
+
+    /// Writes generated images to `dir` instead of next to the article
+    ///
+    /// The returned cover names are then absolute paths into `dir`.
+    pub fn with_output_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.output_dir = dir;
+        self
+    }
+
+    /// Resolves cover paths starting with `/` against `root`
+    pub fn with_content_root(mut self, root: Option<PathBuf>) -> Self {
+        self.content_root = root;
+        self
     }
 }
 
@@ -69,12 +988,12 @@ impl LocalCoverImageProcessor for DefaultCoverImageProcessor<'_> {
         markdown_path: &Path,
         cover_filename: &str,
     ) -> (PathBuf, bool) {
-        resolve_and_check_cover_path(markdown_path, cover_filename)
+        resolve_cover_path_in_root(markdown_path, cover_filename, self.content_root.as_deref())
     }
 
     async fn ensure_cover_image(
         &self,
-        content: &str,
+        article: &ArticleSummary,
         markdown_path: &Path,
         cover_filename: Option<&str>,
     ) -> Result<Option<String>> {
@@ -82,19 +1001,35 @@ impl LocalCoverImageProcessor for DefaultCoverImageProcessor<'_> {
             return Ok(None);
         };
 
+        // A directory is no usable cover, so a new one is generated under the usual name
+        let content_root = self.content_root.as_deref();
+        let cover_filename = cover_filename
+            .filter(|filename| !cover_is_directory(markdown_path, filename, content_root));
+
         match cover_filename {
             None => {
                 // Generate with auto filename
-                let base_filename = markdown_path
-                    .file_stem()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or("article");
+                let base_filename = self.base_name.as_deref().unwrap_or_else(|| {
+                    markdown_path
+                        .file_stem()
+                        .and_then(|s| s.to_str())
+                        .unwrap_or("article")
+                });
+
+                // The cover is written next to this path
+                let anchor = match &self.output_dir {
+                    Some(dir) => dir.join(markdown_path.file_name().unwrap_or_default()),
+                    None => markdown_path.to_path_buf(),
+                };
 
                 match ai_client
-                    .generate_cover_image(content, markdown_path, base_filename)
+                    .generate_cover_image(article, &anchor, base_filename)
                     .await
                 {
-                    Ok(cover_filename) => Ok(Some(cover_filename)),
+                    Ok(cover_filename) => Ok(Some(match &self.output_dir {
+                        Some(dir) => dir.join(cover_filename).display().to_string(),
+                        None => cover_filename,
+                    })),
                     Err(e) => {
                         warn!(
                             "Failed to generate cover image: {}. Continuing without cover.",
@@ -106,15 +1041,21 @@ impl LocalCoverImageProcessor for DefaultCoverImageProcessor<'_> {
             }
             Some(filename) => {
                 // Generate to the specified path from frontmatter
-                let (target_cover_path, exists) =
+                let (mut target_cover_path, exists) =
                     self.resolve_cover_path(markdown_path, filename).await;
 
                 if !exists {
+                    let mut generated_name = filename.to_string();
+                    if let Some(dir) = &self.output_dir {
+                        target_cover_path = dir.join(Path::new(filename).file_name().unwrap_or_default());
+                        generated_name = target_cover_path.display().to_string();
+                    }
+
                     match ai_client
-                        .generate_cover_image_to_path(content, markdown_path, &target_cover_path)
+                        .generate_cover_image_to_path(article, markdown_path, &target_cover_path)
                         .await
                     {
-                        Ok(()) => Ok(Some(filename.to_string())),
+                        Ok(()) => Ok(Some(generated_name)),
                         Err(e) => {
                             warn!(
                                 "Failed to generate cover image to {}: {}. Continuing without cover.",
@@ -133,324 +1074,3005 @@ impl LocalCoverImageProcessor for DefaultCoverImageProcessor<'_> {
     }
 }
 
-/// Recursively processes all markdown files in a directory.
-///
-/// This function walks through the directory tree starting from `dir`,
-/// finds all files with `.md` extension, and uploads them to WeChat.
-/// Files that are already published (where `published: "true"`) will be skipped.
-///
-/// # Arguments
-///
-/// * `client` - WeChat client for API communication
-/// * `openai_client` - Optional OpenAI client for cover image generation
-/// * `dir` - Directory path to process recursively
-/// * `verbose` - Whether to enable detailed tracing logs
-///
-/// # Errors
-///
-/// Returns an error if:
-/// - Directory traversal fails
-/// - Any file upload fails
-pub async fn process_directory(
-    client: &WeChatClient,
-    ai_client: Option<&UniversalAIClient>,
-    dir: &Path,
-    verbose: bool,
-) -> Result<()> {
-    let entries: Vec<_> = WalkDir::new(dir)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.path().extension().and_then(|s| s.to_str()) == Some("md"))
-        .collect();
+/// File extensions of markdown files when `markdown_extensions` is not set
+pub const DEFAULT_MARKDOWN_EXTENSIONS: &[&str] = &["md"];
 
-    if entries.is_empty() {
-        FORMATTER.print_info("No markdown files found in directory");
-        return Ok(());
-    }
+/// Returns [`DEFAULT_MARKDOWN_EXTENSIONS`] as a `markdown_extensions` value
+pub fn default_markdown_extensions() -> Vec<String> {
+    DEFAULT_MARKDOWN_EXTENSIONS.iter().map(|s| s.to_string()).collect()
+}
+
+/// Returns true if the extension of `path` is one of `extensions`, ignoring case
+pub fn has_markdown_extension(path: &Path, extensions: &[String]) -> bool {
+    path.extension()
+        .and_then(|s| s.to_str())
+        .is_some_and(|ext| extensions.iter().any(|known| known.eq_ignore_ascii_case(ext)))
+}
 
-    for entry in entries {
-        upload_file(client, ai_client, entry.path(), false, verbose).await?;
+/// Returns the warning for a file given by path whose extension is not one
+/// of `extensions`, or `None` if it is recognized
+///
+/// Such a file is still uploaded, but a run over its directory skips it.
+pub fn unrecognized_extension_warning(path: &Path, extensions: &[String]) -> Option<String> {
+    if has_markdown_extension(path, extensions) {
+        return None;
     }
+    let problem = match path.extension().and_then(|s| s.to_str()) {
+        Some(extension) => format!("'.{}' is not a recognized markdown extension", extension),
+        None => "the file has no extension".to_string(),
+    };
+    Some(format!(
+        "{} (recognized: {}); a directory run would skip this file, \
+         add its extension to markdown_extensions to include it",
+        problem,
+        extensions.join(", ")
+    ))
+}
 
-    Ok(())
+/// Returns the markdown files under `dir` in walk order, excluding upload temp files
+pub fn collect_markdown_files(dir: &Path) -> Vec<PathBuf> {
+    collect_markdown_files_with(dir, false)
 }
 
-/// Uploads a single markdown file to WeChat public account.
-///
-/// This function orchestrates the complete upload workflow by delegating
-/// to specialized functions for each step.
-///
-/// # Arguments
-///
-/// * `client` - WeChat client for API communication
-/// * `openai_client` - Optional OpenAI client for cover image generation
-/// * `path` - Path to the markdown file
-/// * `force` - If true, uploads regardless of published status
-/// * `verbose` - Whether to enable detailed tracing logs
-///
-/// # Errors
+/// Returns the `.md` files under `dir` in walk order, descending into
+/// symlinked directories if `follow_symlinks` is set
+pub fn collect_markdown_files_with(dir: &Path, follow_symlinks: bool) -> Vec<PathBuf> {
+    collect_markdown_files_matching(dir, follow_symlinks, &default_markdown_extensions())
+}
+
+/// Returns the files under `dir` with one of `extensions` in walk order,
+/// descending into symlinked directories if `follow_symlinks` is set
 ///
-/// Returns an error if any step of the upload process fails
-pub async fn upload_file(
-    client: &WeChatClient,
-    ai_client: Option<&UniversalAIClient>,
-    path: &Path,
-    force: bool,
-    verbose: bool,
-) -> Result<()> {
-    // Parse the markdown file and check publication status
-    let (mut frontmatter, body) = match parse_and_check_file(path, force, verbose).await {
-        Ok(result) => result,
-        Err(_) => return Ok(()), // File was skipped
-    };
+/// A file reachable through several paths, e.g. symlinked into two
+/// collections, is returned once, at the first path walked. Broken symlinks
+/// are reported as warnings and left out.
+pub fn collect_markdown_files_matching(
+    dir: &Path,
+    follow_symlinks: bool,
+    extensions: &[String],
+) -> Vec<PathBuf> {
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
 
-    // Handle cover image processing if needed
-    let cover_updated = process_cover_image(&mut frontmatter, path, ai_client, verbose).await?;
+    let mut walk = WalkDir::new(dir).follow_links(follow_symlinks).into_iter();
+    while let Some(entry) = walk.next() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            // Following a broken link fails; a link back to an ancestor is just not descended
+            Err(e) => {
+                if let Some(path) = e.path()
+                    && e.loop_ancestor().is_none()
+                    && is_broken_symlink(path)
+                {
+                    FORMATTER.print_file_warning(path, None, "broken symbolic link, skipped");
+                }
+                continue;
+            }
+        };
 
-    // Save frontmatter if cover was updated
-    if cover_updated {
-        write_markdown_file(path, &frontmatter, &body).await?;
-        if verbose {
-            info!("Updated frontmatter with cover in: {}", path.display());
+        let path = entry.path();
+        if entry.file_type().is_dir() && entry.file_name() == DATA_DIR {
+            walk.skip_current_dir();
+            continue;
+        }
+        if !has_markdown_extension(path, extensions) || is_upload_temp_file(path) {
+            continue;
+        }
+        if entry.path_is_symlink() && is_broken_symlink(path) {
+            FORMATTER.print_file_warning(path, None, "broken symbolic link, skipped");
+            continue;
+        }
+
+        let canonical = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if seen.insert(canonical) {
+            files.push(entry.into_path());
+        } else {
+            info!("Skipping {}: same file as an earlier path", path.display());
         }
     }
 
-    // Execute the WeChat upload
-    execute_wechat_upload(client, path, verbose).await?;
+    files
+}
 
-    // Update the file with published status
-    update_published_status(path, verbose).await?;
+/// Returns true if `path` is a symlink whose target does not exist
+fn is_broken_symlink(path: &Path) -> bool {
+    path.symlink_metadata().is_ok_and(|meta| meta.file_type().is_symlink()) && !path.exists()
+}
 
-    Ok(())
+/// Leaves out the files whose frontmatter marks them `ignore: true`
+///
+/// Files whose frontmatter cannot be read are kept, so their errors are
+/// still reported.
+pub async fn drop_ignored(files: Vec<PathBuf>, parse_options: &ParseOptions) -> Vec<PathBuf> {
+    let mut kept = Vec::with_capacity(files.len());
+    for path in files {
+        match parse_frontmatter_file_with(&path, parse_options).await {
+            Ok(frontmatter) if frontmatter.is_ignored() => {
+                info!("Ignoring {}: marked ignore", path.display());
+            }
+            _ => kept.push(path),
+        }
+    }
+    kept
 }
 
-/// Parses markdown file and checks if it should be uploaded
+/// Keeps the files in `state`, read from the frontmatter like the upload does
 ///
-/// # Returns
+/// Files whose frontmatter cannot be read are kept and reported when they
+/// are uploaded.
+pub async fn filter_by_state(
+    files: Vec<PathBuf>,
+    state: PublicationStatus,
+    options: &ProcessOptions,
+) -> Vec<PathBuf> {
+    let scan_options = options.scan_options();
+    let mut kept = Vec::with_capacity(files.len());
+    for path in files {
+        let matches = match parse_frontmatter_file_with(&path, &scan_options).await {
+            Ok(frontmatter) => frontmatter.publication_status() == state,
+            Err(_) => true,
+        };
+        if matches {
+            kept.push(path);
+        }
+    }
+    kept
+}
+
+/// Returns the files a directory run over `dir` uploads, in upload order
 ///
-/// Returns the frontmatter and body if the file should be processed,
-/// or returns an error if the file should be skipped
-async fn parse_and_check_file(
-    path: &Path,
+/// Collects the files with one of `options.markdown_extensions`, following
+/// symbolic links with `options.follow_symlinks`, leaves out files marked
+/// ignore and, with `only`, keeps the files in that state. Directory runs and
+/// their dry-run plans both select files with this.
+pub async fn collect_directory_files(
+    dir: &Path,
+    only: Option<PublicationStatus>,
+    options: &ProcessOptions,
+) -> Vec<PathBuf> {
+    let files =
+        collect_markdown_files_matching(dir, options.follow_symlinks, &options.markdown_extensions);
+    let files = drop_ignored(files, &options.scan_options()).await;
+    match only {
+        Some(state) => filter_by_state(files, state, options).await,
+        None => files,
+    }
+}
+
+/// Uploads files with a single client, skipping published ones
+///
+/// Failures are recorded in the report and do not stop the run, except for
+/// authentication failures, after which every later upload would fail too.
+///
+/// With `options.pipeline` set, covers are generated ahead of the uploads; see
+/// [`upload_files_pipelined`].
+pub async fn upload_files(
+    client: &dyn WeChatUploader,
+    ai_client: Option<&UniversalAIClient>,
+    files: &[PathBuf],
+    options: &ProcessOptions,
+) -> UploadReport {
+    upload_files_with(client, ai_client, files, false, options).await
+}
+
+/// Uploads files with a single client, also uploading published ones if `force` is set
+///
+/// Otherwise the same as [`upload_files`].
+pub async fn upload_files_with(
+    client: &dyn WeChatUploader,
+    ai_client: Option<&UniversalAIClient>,
+    files: &[PathBuf],
     force: bool,
-    verbose: bool,
-) -> Result<(Frontmatter, String)> {
-    let (frontmatter, body) = parse_markdown_file(path).await?;
+    options: &ProcessOptions,
+) -> UploadReport {
+    if let Some(limits) = options.pipeline {
+        return upload_files_pipelined(client, ai_client, files, force, options, limits).await;
+    }
 
-    // Check if already published
-    if !force && frontmatter.is_published() {
-        if verbose {
-            info!("Skipping already published file: {}", path.display());
-        } else {
-            FORMATTER.print_skip(&FORMATTER.format_skip_published(path));
+    let mut report = UploadReport::new();
+    for path in files {
+        match upload_file(client, ai_client, path, force, options).await {
+            Ok(outcome) => report.record(path, outcome),
+            Err(e) => {
+                report.record_failed(path, &e);
+                if let Some(reason) = stop_reason(&e, options) {
+                    warn!("Stopping after {}: {}", reason, e);
+                    break;
+                }
+            }
         }
-        return Err(Error::generic("File already published"));
     }
-
-    Ok((frontmatter, body))
+    report
 }
 
-/// Processes cover image generation and updating
+/// Returns why a run stops after a file failed with `error`, if it does
 ///
-/// # Returns
+/// An authentication failure always stops it, since every later upload would
+/// fail the same way; with `fail_fast` any failure does.
+fn stop_reason(error: &Error, options: &ProcessOptions) -> Option<&'static str> {
+    if error.is_auth() {
+        Some("authentication failure")
+    } else if options.fail_fast {
+        Some("failure (--fail-fast)")
+    } else {
+        None
+    }
+}
+
+/// Uploads files in two overlapping stages
 ///
-/// Returns true if the frontmatter was updated with a new cover image
-async fn process_cover_image(
-    frontmatter: &mut Frontmatter,
-    path: &Path,
+/// Up to `limits.ai_jobs` files are prepared (parsed, cover generated,
+/// frontmatter saved) at once, in walk order, and handed over a bounded
+/// channel to up to `limits.upload_jobs` concurrent WeChat uploads. A file is
+/// only uploaded after its prepare stage has finished, so the two frontmatter
+/// writes of a file never race. After an authentication failure, or any
+/// failure with `fail_fast`, no further uploads are started. The report lists files in walk order. Published files
+/// are skipped unless `force` is set.
+pub async fn upload_files_pipelined(
+    client: &dyn WeChatUploader,
     ai_client: Option<&UniversalAIClient>,
-    verbose: bool,
-) -> Result<bool> {
-    let Some(ai_client) = ai_client else {
-        check_existing_cover(frontmatter, path, verbose);
-        return Ok(false);
+    files: &[PathBuf],
+    force: bool,
+    options: &ProcessOptions,
+    limits: PipelineLimits,
+) -> UploadReport {
+    let (tx, rx) = mpsc::channel(limits.upload_jobs.max(1));
+
+    let prepare = async move {
+        let mut prepared = stream::iter(files.iter().enumerate())
+            .map(|(index, path)| async move {
+                (index, path, prepare_file(ai_client, path, force, options).await)
+            })
+            .buffered(limits.ai_jobs.max(1));
+
+        while let Some(item) = prepared.next().await {
+            if tx.send(item).await.is_err() {
+                // The upload stage stopped after a failure
+                break;
+            }
+        }
     };
 
-    if verbose {
-        info!("AI client available for cover generation");
+    let upload = async move {
+        let received = stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|item| (item, rx))
+        });
+        let mut uploads = std::pin::pin!(received
+            .map(|(index, path, prepared)| async move {
+                let result = match prepared {
+                    Ok(Prepared::Ready(prepared)) => upload_prepared(client, &prepared, options).await,
+                    Ok(Prepared::Skipped { reason }) => Ok(FileOutcome::Skipped { reason }),
+                    Ok(Prepared::CoverDeferred) => Ok(FileOutcome::CoverDeferred),
+                    Err(e) => Err(e),
+                };
+                (index, path, result)
+            })
+            .buffer_unordered(limits.upload_jobs.max(1)));
+
+        let mut results = Vec::new();
+        while let Some((index, path, result)) = uploads.next().await {
+            let stop = result
+                .as_ref()
+                .err()
+                .and_then(|e| stop_reason(e, options).map(|reason| (reason, e.to_string())));
+            results.push((index, path, result));
+            if let Some((reason, error)) = stop {
+                warn!("Stopping after {}: {}", reason, error);
+                break;
+            }
+        }
+        results
+    };
+
+    let ((), mut results) = tokio::join!(prepare, upload);
+    results.sort_by_key(|(index, _, _)| *index);
+
+    let mut report = UploadReport::new();
+    for (_, path, result) in results {
+        match result {
+            Ok(outcome) => report.record(path, outcome),
+            Err(e) => report.record_failed(path, &e),
+        }
     }
+    report
+}
 
-    let should_generate = should_generate_cover(frontmatter, path, verbose).await;
+/// Files that should be uploaded with the same WeChat accounts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountGroup {
+    /// Accounts to upload to, one draft each; empty for the current account
+    pub accounts: Vec<String>,
+    /// Files in walk order
+    pub files: Vec<PathBuf>,
+}
 
-    if !should_generate {
-        return Ok(false);
+/// Groups files by the accounts they are uploaded to
+///
+/// A file's targets come from its frontmatter `account` or `accounts` field,
+/// where `@group` expands to the members of a configured account group.
+/// Files naming neither use `--account @group` if given, otherwise the
+/// current account. Files for the current account alone form the first group;
+/// other target sets follow in order of first appearance so each client is
+/// created once. Files whose frontmatter cannot be read are kept in the first
+/// group and reported when they are uploaded. With an account given by
+/// `--app-id`, all files form the first group.
+///
+/// # Errors
+///
+/// Returns a configuration error naming every unknown account or group and
+/// the files that reference it, before anything is uploaded
+pub async fn group_files_by_account(files: &[PathBuf], config: &Config) -> Result<Vec<AccountGroup>> {
+    group_files_by_account_with(files, config, &HashMap::new()).await
+}
+
+/// Groups files like [`group_files_by_account`], with the account or
+/// `@group` in `overrides` replacing the frontmatter of the files it names
+///
+/// # Errors
+///
+/// Returns a configuration error naming every unknown account or group and
+/// the files that reference it, before anything is uploaded
+pub async fn group_files_by_account_with(
+    files: &[PathBuf],
+    config: &Config,
+    overrides: &HashMap<PathBuf, String>,
+) -> Result<Vec<AccountGroup>> {
+    if config.cli_account {
+        let group = AccountGroup {
+            accounts: Vec::new(),
+            files: files.to_vec(),
+        };
+        return Ok((!files.is_empty()).then_some(group).into_iter().collect());
     }
 
-    let processor = DefaultCoverImageProcessor::new(Some(ai_client));
+    // The theme does not matter for picking accounts
+    let scan_options = ParseOptions {
+        strict_frontmatter: false,
+        quiet: true,
+        theme_fallback: ThemeFallback::Default,
+        sidecar_frontmatter: config.sidecar_frontmatter,
+        defer_validation: false,
+    };
 
-    match processor
-        .ensure_cover_image(&frontmatter.description, path, frontmatter.cover.as_deref())
-        .await?
-    {
-        Some(cover_filename) => {
-            frontmatter.set_cover(cover_filename.clone());
+    let mut groups = vec![AccountGroup {
+        accounts: Vec::new(),
+        files: Vec::new(),
+    }];
+    let mut unknown: Vec<(String, Vec<PathBuf>)> = Vec::new();
+    let mut conflicting: Vec<PathBuf> = Vec::new();
 
-            if verbose {
-                info!("Successfully generated cover image: {}", cover_filename);
-            } else {
-                FORMATTER.print_generation(&FORMATTER.format_cover_success(&cover_filename));
+    for path in files {
+        let specs = match overrides.get(path) {
+            Some(spec) => vec![spec.clone()],
+            None => match parse_frontmatter_file_with(path, &scan_options).await {
+                Ok(frontmatter) => match frontmatter.account_specs() {
+                    Ok(specs) => specs,
+                    Err(_) => {
+                        conflicting.push(path.clone());
+                        continue;
+                    }
+                },
+                Err(_) => Vec::new(),
+            },
+        };
+
+        let mut targets = if specs.is_empty() {
+            config.target_accounts.clone()
+        } else {
+            let mut targets = Vec::new();
+            let mut missing = false;
+            for spec in specs {
+                match config.resolve_accounts(std::slice::from_ref(&spec)) {
+                    Ok(accounts) => targets.extend(accounts),
+                    Err(_) => {
+                        missing = true;
+                        match unknown.iter_mut().find(|(n, _)| *n == spec) {
+                            Some((_, paths)) => paths.push(path.clone()),
+                            None => unknown.push((spec, vec![path.clone()])),
+                        }
+                    }
+                }
             }
-            Ok(true)
-        }
-        None => {
-            if verbose {
-                warn!("Cover generation failed but continuing without error");
-            } else {
-                FORMATTER.print_warning(&FORMATTER.format_cover_failure());
+            if missing {
+                continue;
             }
-            Ok(false)
+            targets
+        };
+        dedup_in_order(&mut targets);
+
+        if targets.len() == 1 && targets[0] == config.wechat_account.name {
+            targets.clear();
+        }
+
+        match groups.iter_mut().find(|g| g.accounts == targets) {
+            Some(group) => group.files.push(path.clone()),
+            None => groups.push(AccountGroup {
+                accounts: targets,
+                files: vec![path.clone()],
+            }),
+        }
+    }
+
+    let mut problems = Vec::new();
+    if !unknown.is_empty() {
+        let details = unknown
+            .iter()
+            .map(|(name, paths)| format!("'{}' ({})", name, display_paths(paths)))
+            .collect::<Vec<_>>()
+            .join("; ");
+        let mut message = format!(
+            "Unknown account in frontmatter: {}. Available accounts: {}",
+            details,
+            config.available_account_names().join(", ")
+        );
+        if !config.account_groups.is_empty() {
+            let mut group_names: Vec<String> =
+                config.account_groups.keys().map(|g| format!("@{}", g)).collect();
+            group_names.sort_unstable();
+            message.push_str(&format!("; groups: {}", group_names.join(", ")));
         }
+        problems.push(message);
     }
+    if !conflicting.is_empty() {
+        problems.push(format!(
+            "Frontmatter sets both account and accounts: {}",
+            display_paths(&conflicting)
+        ));
+    }
+    if !problems.is_empty() {
+        return Err(Error::config(problems.join(". ")));
+    }
+
+    groups.retain(|group| !group.files.is_empty());
+    Ok(groups)
 }
 
-/// Determines if a cover image should be generated
-async fn should_generate_cover(frontmatter: &Frontmatter, path: &Path, verbose: bool) -> bool {
-    match &frontmatter.cover {
-        None => {
-            if verbose {
-                info!("No cover image specified, generating one using AI...");
-            } else {
-                FORMATTER.print_generation(&FORMATTER.format_cover_generation(path));
-            }
-            true
+fn dedup_in_order(names: &mut Vec<String>) {
+    let mut seen = Vec::new();
+    names.retain(|name| {
+        let first = !seen.contains(name);
+        if first {
+            seen.push(name.clone());
         }
-        Some(cover_filename) => {
-            let (cover_path, exists) = resolve_and_check_cover_path(path, cover_filename);
-            if !exists {
-                if verbose {
-                    info!(
-                        "Cover image specified ({}) but file not found at {}, generating using AI...",
-                        cover_filename,
-                        cover_path.display()
+        first
+    });
+}
+
+fn display_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Uploads files to several accounts, creating one draft per account
+///
+/// Each file is prepared once, so a generated cover is shared by every
+/// account. The drafts are recorded in the frontmatter under `uploads`, keyed
+/// by account. A failure with one account does not stop the others; after an
+/// authentication failure no further files are uploaded with that account.
+/// With `fail_fast`, the run stops after the first file that failed with any
+/// account. Covers are generated in order even with `options.pipeline` set.
+pub async fn upload_files_to_accounts(
+    clients: &[(&str, &dyn WeChatUploader)],
+    ai_client: Option<&UniversalAIClient>,
+    files: &[PathBuf],
+    force: bool,
+    options: &ProcessOptions,
+) -> UploadReport {
+    let mut report = UploadReport::new();
+    let mut stopped: Vec<&str> = Vec::new();
+
+    for path in files {
+        let active: Vec<_> = clients
+            .iter()
+            .filter(|(name, _)| !stopped.contains(name))
+            .collect();
+        if active.is_empty() {
+            break;
+        }
+
+        let prepared = match prepare_file(ai_client, path, force, options).await {
+            Ok(Prepared::Ready(prepared)) => prepared,
+            Ok(Prepared::Skipped { reason }) => {
+                for (name, _) in &active {
+                    report.record_for_account(
+                        path,
+                        *name,
+                        FileOutcome::Skipped {
+                            reason: reason.clone(),
+                        },
                     );
-                } else {
-                    FORMATTER.print_generation(&format!(
-                        "cover missing ({}), generating: {}",
-                        cover_filename,
-                        path.display()
-                    ));
                 }
-                true
-            } else {
-                if verbose {
-                    info!("Cover image found at: {}", cover_path.display());
+                continue;
+            }
+            Ok(Prepared::CoverDeferred) => {
+                for (name, _) in &active {
+                    report.record_for_account(path, *name, FileOutcome::CoverDeferred);
+                }
+                continue;
+            }
+            Err(e) => {
+                for (name, _) in &active {
+                    report.record_for_account(path, *name, FileOutcome::failed(&e));
+                }
+                if let Some(reason) = stop_reason(&e, options) {
+                    warn!("Stopping after {}: {}", reason, e);
+                    break;
+                }
+                continue;
+            }
+        };
+
+        let mut drafts = Vec::new();
+        let mut failed = false;
+        for (name, client) in &active {
+            if options.verbose {
+                info!("Uploading {} with account '{}'", path.display(), name);
+            }
+            let result = send_prepared(*client, &prepared, options).await;
+            run_post_upload_hook(&prepared, &result, options).await;
+            match result {
+                Ok(draft_id) => {
+                    report.record_for_account(
+                        path,
+                        *name,
+                        FileOutcome::Uploaded {
+                            draft_id: draft_id.clone(),
+                            title: prepared.frontmatter.title.clone(),
+                            cover: Some(prepared.cover.clone()),
+                        },
+                    );
+                    drafts.push((*name, draft_id));
                 }
-                false
+                Err(e) => {
+                    report.record_for_account(path, *name, FileOutcome::failed(&e));
+                    failed = true;
+                    if e.is_auth() {
+                        warn!("Stopping uploads with account '{}' after authentication failure: {}", name, e);
+                        stopped.push(name);
+                    }
+                }
+            }
+        }
+
+        if options.updates_status() && !drafts.is_empty() {
+            let result = async {
+                options.back_up(path).await?;
+                update_frontmatter_with(path, &options.parse_options(), |fm| {
+                    mark_uploaded_to_accounts(fm, &drafts);
+                    Ok(())
+                })
+                .await
+            }
+            .await;
+            // The drafts exist either way, so this does not fail the uploads
+            if let Err(e) = result {
+                options.warnings.warn(
+                    path,
+                    &format!("failed to record drafts in {}: {}", path.display(), e),
+                );
             }
         }
+
+        if failed && options.fail_fast {
+            warn!("Stopping after failure (--fail-fast): {}", path.display());
+            break;
+        }
     }
+
+    report
 }
 
-/// Checks if existing cover file exists when no OpenAI client is available
-fn check_existing_cover(frontmatter: &Frontmatter, path: &Path, verbose: bool) {
-    if let Some(cover_filename) = &frontmatter.cover {
-        let (cover_path, exists) = resolve_and_check_cover_path(path, cover_filename);
-        if !exists {
+/// Uploads a single markdown file to WeChat public account.
+///
+/// This function orchestrates the complete upload workflow by delegating
+/// to specialized functions for each step.
+///
+/// # Arguments
+///
+/// * `client` - WeChat uploader used for API communication
+/// * `openai_client` - Optional OpenAI client for cover image generation
+/// * `path` - Path to the markdown file
+/// * `force` - If true, uploads regardless of published status
+/// * `options` - Run-wide processing options
+///
+/// # Returns
+///
+/// `FileOutcome::Uploaded` with the draft ID, `FileOutcome::Skipped` if the
+/// file is already published or skipped by the theme fallback, or
+/// `FileOutcome::CoverDeferred` if its cover exceeds the AI image budget
+///
+/// # Errors
+///
+/// Returns an error if any step of the upload process fails
+pub async fn upload_file(
+    client: &dyn WeChatUploader,
+    ai_client: Option<&UniversalAIClient>,
+    path: &Path,
+    force: bool,
+    options: &ProcessOptions,
+) -> Result<FileOutcome> {
+    match prepare_file(ai_client, path, force, options).await? {
+        Prepared::Ready(prepared) => upload_prepared(client, &prepared, options).await,
+        Prepared::Skipped { reason } => Ok(FileOutcome::Skipped { reason }),
+        Prepared::CoverDeferred => Ok(FileOutcome::CoverDeferred),
+    }
+}
+
+/// Markdown file whose cover is ready and whose frontmatter is saved
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreparedFile {
+    /// Path of the markdown file
+    pub path: PathBuf,
+    /// Frontmatter as written to disk by the prepare stage
+    pub frontmatter: Frontmatter,
+    /// Markdown body
+    pub body: String,
+    /// Whether the theme fallback replaced the theme or code highlighter of
+    /// the file, so the upload must not read its frontmatter from disk
+    pub style_replaced: bool,
+    /// Whether the frontmatter was read from a sidecar file, so the upload
+    /// must not read it from the markdown file
+    pub sidecar: bool,
+    /// Where the cover came from
+    pub cover: CoverOutcome,
+}
+
+/// Result of the prepare stage of an upload
+#[derive(Debug, Clone, PartialEq)]
+pub enum Prepared {
+    /// The file is ready for upload
+    Ready(Box<PreparedFile>),
+    /// The file is not uploaded
+    Skipped {
+        /// Why the file was skipped
+        reason: String,
+    },
+    /// The file needs an AI image but the budget of the run is used up
+    CoverDeferred,
+}
+
+/// Runs the local and AI stage of an upload: parse, cover, thumbnail and frontmatter update
+///
+/// # Returns
+///
+/// The prepared file, or why it is skipped: it is already published, or
+/// recorded as uploaded in the state file with write-back disabled, and
+/// `force` is false, or the theme fallback policy skips it. A file whose cover
+/// or thumbnail would exceed the AI image budget is deferred.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be parsed or its frontmatter cannot be
+/// saved, or if it has no cover image under `require_cover`
+pub async fn prepare_file(
+    ai_client: Option<&UniversalAIClient>,
+    path: &Path,
+    force: bool,
+    options: &ProcessOptions,
+) -> Result<Prepared> {
+    let verbose = options.verbose;
+
+    // Taken before parsing, so an edit made at any point afterwards shows up
+    let fingerprint = FileFingerprint::of(path, &options.parse_options()).await.ok();
+
+    // Parse the markdown file and check publication status
+    let (mut frontmatter, body) = match parse_and_check_file(path, force, options).await? {
+        Checked::Upload(frontmatter, body) => (*frontmatter, body),
+        Checked::Skip(reason) => return Ok(Prepared::Skipped { reason }),
+    };
+    let parsed = frontmatter.clone();
+
+    // The frontmatter may pick other models for this file's cover
+    let file_client = file_ai_client(ai_client, &frontmatter, path)?;
+    let ai_client = file_client.as_ref().or(ai_client);
+
+    // Warn about relative links that would render as dead links in WeChat
+    warn_broken_links(path, &body, verbose).await;
+    if verbose {
+        warn_unsupported(path, &body, &options.disabled_lint_rules);
+    }
+
+    // A directory run assigns slugs up front; a single file gets one here
+    let slug_added = options.auto_slug && frontmatter.slug.is_none();
+    if slug_added {
+        let slug = slug_for(&frontmatter, &body, path);
+        if verbose {
+            info!("Assigned slug '{}' to {}", slug, path.display());
+        }
+        frontmatter.slug = Some(slug);
+    }
+
+    // A cover naming a directory of candidates is replaced by the chosen image
+    let cover_selected = match select_directory_cover(&frontmatter, path, options) {
+        Some(selected) => {
             if verbose {
-                warn!(
-                    "Cover image specified ({}) but file not found at {} and no OpenAI API key provided. Upload may fail.",
-                    cover_filename,
-                    cover_path.display()
-                );
+                info!("Selected cover {} for {}", selected, path.display());
             } else {
-                FORMATTER.print_warning(&format!(
-                    "cover missing ({}), no OpenAI key to generate",
-                    cover_filename
-                ));
+                FORMATTER.print_info(&format!("selected cover {}: {}", selected, path.display()));
             }
+            frontmatter.set_cover(selected);
+            true
+        }
+        None => false,
+    };
+
+    // Handle cover image processing if needed
+    let cover_dir = options.cover_dir();
+    let content_root = options.content_root.as_deref();
+    let cover = process_cover_image(&mut frontmatter, &body, path, ai_client, options).await?;
+
+    // A directory or missing file left as the cover would only fail later, in the upload
+    let material = cover == Some(CoverOutcome::Material);
+    if cover.is_some()
+        && !material
+        && let Some(cover_filename) = frontmatter.cover_image()
+    {
+        let (cover_path, exists) = resolve_cover_path_in_root(path, cover_filename, content_root);
+        if cover_path.is_dir() {
+            return Err(cover_directory_error(&cover_path, cover_filename));
+        }
+        if !exists {
+            return Err(cover_not_found_error(&cover_path, cover_filename));
+        }
+    }
+    if options.require_cover && cover.is_some() && frontmatter.cover_image().is_none() {
+        return Err(missing_required_cover_error(path, &frontmatter));
+    }
+
+    // Generate a separate thumbnail if one is requested but missing
+    let thumb = if cover.is_none() {
+        CoverStatus::Deferred
+    } else if material {
+        CoverStatus::Unchanged
+    } else {
+        process_thumb_image(
+            &mut frontmatter,
+            &body,
+            path,
+            ai_client,
+            cover_dir.as_deref(),
+            content_root,
+            options,
+        )
+        .await?
+    };
+
+    // Converting renames the cover, so it needs write-back
+    let cover_converted = options.convert_covers
+        && options.write_back
+        && cover == Some(CoverOutcome::Existing)
+        && convert_existing_cover(&mut frontmatter, path, options).await;
+
+    if options.optimize_covers {
+        let cover_dir = cover_dir.as_deref();
+        let warnings = &options.warnings;
+        optimize_cover_images(&frontmatter, &body, path, cover_dir, content_root, verbose, warnings)
+            .await;
+    }
+
+    // Save frontmatter if cover or slug was updated
+    let cover_set =
+        cover.as_ref().is_some_and(CoverOutcome::is_new) || cover_converted || cover_selected;
+    if (cover_set || slug_added) && options.write_back {
+        options.back_up(path).await?;
+        save_prepared_frontmatter(path, &parsed, &frontmatter, &body, fingerprint, options).await?;
+        if verbose {
+            info!("Updated frontmatter in: {}", path.display());
+        }
+    }
+
+    // WeChat needs the missing image, so the file waits for a later run
+    let Some(cover) = cover.filter(|_| thumb != CoverStatus::Deferred) else {
+        if verbose {
+            info!("AI image budget used up, deferring: {}", path.display());
+        } else {
+            FORMATTER.print_skip(&format!(
+                "cover deferred (AI image budget used up): {}",
+                path.display()
+            ));
+        }
+        return Ok(Prepared::CoverDeferred);
+    };
+
+    // Only the upload sees a manifest theme or the fallback; the file keeps its own values
+    let style_replaced = apply_upload_style(&mut frontmatter, path, options);
+    let sidecar = uses_sidecar(path, &options.parse_options()).await?;
+
+    Ok(Prepared::Ready(Box::new(PreparedFile {
+        path: path.to_path_buf(),
+        frontmatter,
+        body,
+        style_replaced,
+        sidecar,
+        cover,
+    })))
+}
+
+/// Writes the cover, thumbnail and slug set while preparing a file back to it
+///
+/// The file is rewritten from `frontmatter` and `body` as they were parsed,
+/// unless it or its sidecar changed on disk since `fingerprint` was taken,
+/// e.g. because it was edited while its cover was generated. Then only the
+/// fields that differ from `parsed` are applied to the frontmatter as it is
+/// now, so the edits are kept.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or written
+async fn save_prepared_frontmatter(
+    path: &Path,
+    parsed: &Frontmatter,
+    frontmatter: &Frontmatter,
+    body: &str,
+    fingerprint: Option<FileFingerprint>,
+    options: &ProcessOptions,
+) -> Result<()> {
+    let parse_options = options.parse_options();
+    let unchanged = match fingerprint {
+        Some(fingerprint) => FileFingerprint::of(path, &parse_options).await? == fingerprint,
+        None => false,
+    };
+    if unchanged {
+        return write_markdown_file_with(path, frontmatter, body, &parse_options).await;
+    }
+
+    options.warnings.warn_file(
+        path,
+        "changed on disk while it was processed, only the new cover, thumbnail and slug are \
+         written to it",
+    );
+    update_frontmatter_with(path, &parse_options, |current| {
+        if frontmatter.cover != parsed.cover {
+            current.cover = frontmatter.cover.clone();
+        }
+        if frontmatter.thumb != parsed.thumb {
+            current.thumb = frontmatter.thumb.clone();
+        }
+        if frontmatter.slug != parsed.slug {
+            current.slug = frontmatter.slug.clone();
+        }
+        Ok(())
+    })
+    .await
+}
+
+/// Runs the WeChat stage of an upload and records the draft in the frontmatter
+///
+/// The `post_upload` hook runs afterwards, whether the upload succeeded or not.
+///
+/// # Errors
+///
+/// Returns an error if the upload or the frontmatter update fails
+pub async fn upload_prepared(
+    client: &dyn WeChatUploader,
+    prepared: &PreparedFile,
+    options: &ProcessOptions,
+) -> Result<FileOutcome> {
+    let result = async {
+        let mut paced = options.pacer.start().await;
+        let draft_id = send_prepared(client, prepared, options).await?;
+        paced.created();
+
+        // Update the file with published status
+        if options.updates_status() {
+            update_published_status(&prepared.path, &draft_id, options).await?;
+        }
+        Ok(draft_id)
+    }
+    .await;
+
+    run_post_upload_hook(prepared, &result, options).await;
+    result.map(|draft_id| FileOutcome::Uploaded {
+        draft_id,
+        title: prepared.frontmatter.title.clone(),
+        cover: Some(prepared.cover.clone()),
+    })
+}
+
+/// Runs the `post_upload` hook for an upload attempt
+///
+/// The upload already happened, so a failing hook is only a warning.
+async fn run_post_upload_hook(
+    prepared: &PreparedFile,
+    result: &Result<String>,
+    options: &ProcessOptions,
+) {
+    let path = prepared.path.as_path();
+    let title = prepared.frontmatter.title.as_deref();
+    if let Err(e) = options.hooks.post_upload(path, title, result, options.verbose).await {
+        options.warnings.warn_file(path, &e.to_string());
+    }
+}
+
+/// Uploads a prepared file as a draft and returns the draft ID
+async fn send_prepared(
+    client: &dyn WeChatUploader,
+    prepared: &PreparedFile,
+    options: &ProcessOptions,
+) -> Result<String> {
+    let verbose = options.verbose;
+    let path = prepared.path.as_path();
+
+    // Body transforms, e.g. Obsidian embeds becoming images the uploader finds
+    let pipeline = options.transform_pipeline(&prepared.frontmatter)?;
+    let ctx = ArticleContext {
+        path,
+        frontmatter: &prepared.frontmatter,
+        verbose,
+    };
+    let mut upload_body = None;
+    if options.write_back {
+        let body = pipeline.apply(&ctx, prepared.body.clone())?;
+        if body != prepared.body {
+            upload_body = Some(body);
+        }
+    } else {
+        for stage in pipeline.stages() {
+            if apply_stage(stage.as_ref(), &ctx, prepared.body.clone())? != prepared.body {
+                options.warnings.warn(
+                    path,
+                    &format!(
+                        "body transform {} not applied: source files are read-only",
+                        stage.name()
+                    ),
+                );
+            }
+        }
+    }
+
+    // WeChat does not render data URIs, so embedded images are uploaded first
+    let body = upload_body.as_deref().unwrap_or(&prepared.body);
+    if !options.write_back {
+        if !find_data_uris(body).is_empty() {
+            options
+                .warnings
+                .warn(path, "inline images not uploaded: source files are read-only");
+        }
+    } else if let Some(uploaded) = upload_inline_images(client, body, verbose).await? {
+        upload_body = Some(uploaded);
+    }
+
+    // Decide which image becomes the thumbnail and where the cover is shown
+    let (mut article_options, source) = prepare_article(
+        &prepared.frontmatter,
+        upload_body.as_deref().unwrap_or(&prepared.body),
+        path,
+        prepared.style_replaced,
+        upload_body.is_some(),
+        prepared.sidecar,
+        options,
+    )
+    .await?;
+
+    // The file on disk may lack a generated cover that was not written back
+    if !options.write_back
+        && article_options.thumb.is_none()
+        && article_options.thumb_media_id.is_none()
+    {
+        article_options.thumb = prepared
+            .frontmatter
+            .cover_image()
+            .map(|cover| upload_image_path(path, cover, options.content_root.as_deref()));
+    }
+
+    // Execute the WeChat upload
+    let upload =
+        execute_wechat_upload(client, path, source.path(), &article_options, verbose).await;
+    drop(source);
+
+    match upload {
+        Err(e) if e.is_content_risk() => {
+            report_content_risk(client, path, options).await;
+            Err(e)
+        }
+        result => result,
+    }
+}
+
+/// A parsed file that should be uploaded, or why it is skipped
+enum Checked {
+    Upload(Box<Frontmatter>, String),
+    Skip(String),
+}
+
+/// Returns the `published` value as the author wrote it, e.g. `ignore`
+fn published_as_written(frontmatter: &Frontmatter) -> &str {
+    let status = frontmatter.publication_status();
+    frontmatter
+        .published
+        .as_ref()
+        .and_then(|published| published.raw())
+        .map_or(status.as_str(), str::trim)
+}
+
+/// Returns why an upload would skip a file before touching it, or `None` to upload it
+///
+/// A file is skipped if it is marked ignore, published or marked skip, or if
+/// the state file records it as uploaded while write-back is disabled, unless
+/// `force` names it explicitly. It is also skipped if its theme or code
+/// highlighter is unknown under [`ThemeFallback::Skip`]. The upload and the
+/// dry-run plan both decide with this; the `pre_upload` hook, which only a
+/// real upload runs, can skip a file as well.
+pub async fn upload_skip_reason(
+    path: &Path,
+    frontmatter: &Frontmatter,
+    force: bool,
+    options: &ProcessOptions,
+) -> Option<String> {
+    match frontmatter_skip_reason(frontmatter, force, options) {
+        Some(reason) => Some(reason),
+        None => recorded_upload_reason(path, force, options).await,
+    }
+}
+
+/// Returns why the frontmatter of a file skips its upload, or `None`
+fn frontmatter_skip_reason(frontmatter: &Frontmatter, force: bool, options: &ProcessOptions) -> Option<String> {
+    if !force && frontmatter.is_ignored() {
+        return Some("marked ignore".to_string());
+    }
+    if !force && let Some(reason) = frontmatter.publication_status().skip_reason() {
+        return Some(reason.to_string());
+    }
+    if options.theme_fallback == ThemeFallback::Skip {
+        return frontmatter.style_error();
+    }
+    None
+}
+
+/// Returns why a file recorded as uploaded in the state file is skipped, or `None`
+///
+/// Only applies while write-back is disabled, when the state file takes the
+/// place of `published: draft`. A state file that cannot be read skips nothing.
+async fn recorded_upload_reason(path: &Path, force: bool, options: &ProcessOptions) -> Option<String> {
+    let state_file = options.state_file.as_deref().filter(|_| !force)?;
+    let state = crate::state::UploadState::load(state_file).await.ok()?;
+    let entry = state.get(path)?;
+    Some(format!(
+        "already uploaded as draft {} (recorded in {})",
+        entry.draft_id,
+        state_file.display()
+    ))
+}
+
+/// Parses markdown file and checks if it should be uploaded
+///
+/// A file with an unknown theme or code highlighter is rejected by the parser
+/// under [`ThemeFallback::Error`] and skipped under [`ThemeFallback::Skip`].
+/// The `pre_upload` hook runs last, only for files that would be uploaded.
+async fn parse_and_check_file(
+    path: &Path,
+    force: bool,
+    options: &ProcessOptions,
+) -> Result<Checked> {
+    let (frontmatter, body) = parse_markdown_file_with(path, &options.parse_options()).await?;
+
+    // Check if already published, marked skip or styled with an unknown theme
+    let status = frontmatter.publication_status();
+    if force && status == PublicationStatus::Skip {
+        options.warnings.warn_file(
+            path,
+            &format!(
+                "marked published: {}, uploading it because it was named explicitly",
+                published_as_written(&frontmatter)
+            ),
+        );
+    }
+    if force && frontmatter.is_ignored() {
+        options.warnings.warn_file(
+            path,
+            "marked ignore: true, uploading it because it was named explicitly",
+        );
+    }
+    if let Some(reason) = frontmatter_skip_reason(&frontmatter, force, options) {
+        match status {
+            // Without --verbose, the run summary counts or lists them
+            PublicationStatus::Published if !force => {
+                if options.verbose {
+                    info!("Skipping already published file: {}", path.display());
+                }
+            }
+            PublicationStatus::Skip if !force => {
+                if options.verbose {
+                    info!("Skipping {}: {}", path.display(), reason);
+                } else {
+                    FORMATTER.print_skip(&format!(
+                        "skipped (marked published: {}, never uploaded in directory runs): {}",
+                        published_as_written(&frontmatter),
+                        path.display()
+                    ));
+                }
+            }
+            _ => options.warnings.warn_file(path, &format!("{}; skipping file", reason)),
+        }
+        return Ok(Checked::Skip(reason));
+    }
+
+    // Like published files, the run summary counts them without --verbose
+    if let Some(reason) = recorded_upload_reason(path, force, options).await {
+        if options.verbose {
+            info!("Skipping {}: {}", path.display(), reason);
+        }
+        return Ok(Checked::Skip(reason));
+    }
+
+    // A failing pre_upload hook skips the file, or fails it under --strict-hooks
+    let title = frontmatter.title.as_deref();
+    if let Err(e) = options.hooks.pre_upload(path, title, options.verbose).await {
+        if options.strict_hooks {
+            return Err(e);
+        }
+        let reason = e.to_string();
+        options.warnings.warn_file(path, &format!("{}; skipping file", reason));
+        return Ok(Checked::Skip(reason));
+    }
+
+    Ok(Checked::Upload(Box::new(frontmatter), body))
+}
+
+/// Applies the manifest theme and, under [`ThemeFallback::Default`], replaces
+/// an unknown theme or code highlighter
+///
+/// The configured defaults are used, or the renderer's own defaults if none
+/// are configured. Returns true if the style was changed.
+fn apply_upload_style(frontmatter: &mut Frontmatter, path: &Path, options: &ProcessOptions) -> bool {
+    let overridden = match options.theme_overrides.get(path) {
+        Some(theme) if frontmatter.theme.as_deref() != Some(theme.as_str()) => {
+            frontmatter.set_theme(theme);
+            true
+        }
+        _ => false,
+    };
+    let replaced = resolve_upload_style(frontmatter, path, options);
+    if replaced.is_empty() {
+        return overridden;
+    }
+
+    options.warnings.warn_file(
+        path,
+        &format!("unknown {}, uploading with the default", replaced.join(" and ")),
+    );
+    true
+}
+
+/// Applies the manifest theme and the theme fallback to `frontmatter` as an
+/// upload renders it, without warnings
+///
+/// Returns a description of each unknown value the fallback replaced, e.g.
+/// `theme 'nope'`. The upload and the dry-run plan both resolve the style with this.
+pub fn resolve_upload_style(
+    frontmatter: &mut Frontmatter,
+    path: &Path,
+    options: &ProcessOptions,
+) -> Vec<String> {
+    if let Some(theme) = options.theme_overrides.get(path) {
+        frontmatter.set_theme(theme);
+    }
+    if options.theme_fallback != ThemeFallback::Default {
+        return Vec::new();
+    }
+    frontmatter.replace_unknown_style(
+        options.default_theme.as_deref(),
+        options.default_code_highlighter.as_deref(),
+    )
+}
+
+/// What the prepare stage did about a cover or thumbnail image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CoverStatus {
+    /// Nothing was generated: the image exists, no AI client is configured
+    /// or the generation failed
+    Unchanged,
+    /// An image was generated and set in the frontmatter
+    Generated,
+    /// An image is missing but the AI image budget of the run is used up
+    Deferred,
+}
+
+/// Where the cover of an upload comes from, as decided before anything is generated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoverPlan {
+    /// The frontmatter names an existing WeChat material (`cover_media_id`)
+    Material,
+    /// The frontmatter disables the cover (`cover: false`)
+    Disabled,
+    /// The cover named in the frontmatter exists and is reused
+    Existing,
+    /// The missing cover is rendered from a cover template
+    Template,
+    /// The missing cover is generated with AI
+    Generate {
+        /// Number of candidate images to generate
+        images: u32,
+    },
+    /// The account's `default_cover` is used for the missing cover
+    Default,
+    /// The article is uploaded without a cover
+    Absent,
+}
+
+impl std::fmt::Display for CoverPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Material => f.write_str("reuse WeChat material"),
+            Self::Disabled => f.write_str("disabled"),
+            Self::Existing => f.write_str("reuse existing"),
+            Self::Template => f.write_str("render from template"),
+            Self::Generate { images } => write!(f, "generate with AI ({} image(s))", images),
+            Self::Default => f.write_str("use default cover"),
+            Self::Absent => f.write_str("none"),
+        }
+    }
+}
+
+/// Decides where the cover of an upload of `path` comes from, without
+/// generating, copying or fetching anything
+///
+/// A `cover_media_id` wins over any cover file, which is then not even
+/// resolved. An existing cover is reused, as is an image of a directory of
+/// candidates (see [`select_directory_cover`]). A missing one is rendered from a cover
+/// template if there is one, generated if `ai_enabled`, or else replaced by
+/// the account's default cover. The upload and the dry-run plan both decide
+/// with this.
+pub fn plan_cover(
+    frontmatter: &Frontmatter,
+    path: &Path,
+    ai_enabled: bool,
+    options: &ProcessOptions,
+) -> CoverPlan {
+    if frontmatter.cover_media_id.is_some() {
+        return CoverPlan::Material;
+    }
+    if frontmatter.cover_disabled() {
+        return CoverPlan::Disabled;
+    }
+    if let Some(cover) = frontmatter.cover_image()
+        && (resolve_cover_path_in_root(path, cover, options.content_root.as_deref()).1
+            || select_directory_cover(frontmatter, path, options).is_some())
+    {
+        return CoverPlan::Existing;
+    }
+    if cover_template_for(frontmatter, path, options).is_some() {
+        return CoverPlan::Template;
+    }
+    if ai_enabled {
+        let images = frontmatter
+            .cover_variations
+            .unwrap_or(options.cover_variations)
+            .clamp(1, MAX_COVER_VARIATIONS);
+        return CoverPlan::Generate { images };
+    }
+    if options.default_cover.is_some() {
+        return CoverPlan::Default;
+    }
+    CoverPlan::Absent
+}
+
+/// Warning recorded when a cover or thumbnail could not be generated
+const COVER_FAILED_WARNING: &str = "cover generation failed, continuing";
+
+/// Processes cover image generation and updating
+///
+/// # Returns
+///
+/// Where the cover came from, or `None` if generating it is deferred by the
+/// AI image budget. A new cover is set in the frontmatter.
+///
+/// A cover template takes precedence over the AI client, so no AI calls are
+/// made for files that have one.
+async fn process_cover_image(
+    frontmatter: &mut Frontmatter,
+    body: &str,
+    path: &Path,
+    ai_client: Option<&UniversalAIClient>,
+    options: &ProcessOptions,
+) -> Result<Option<CoverOutcome>> {
+    let verbose = options.verbose;
+    let cover_dir = options.cover_dir();
+    let cover_dir = cover_dir.as_deref();
+    let content_root = options.content_root.as_deref();
+
+    let (ai_client, variations) = match (plan_cover(frontmatter, path, ai_client.is_some(), options), ai_client) {
+        (CoverPlan::Material, _) => {
+            if verbose {
+                info!("Using WeChat material as the cover of {}", path.display());
+            }
+            return Ok(Some(CoverOutcome::Material));
+        }
+        (CoverPlan::Disabled, _) => {
+            if verbose {
+                info!("Cover disabled for {}, not generating one", path.display());
+            }
+            return Ok(Some(CoverOutcome::Absent));
+        }
+        (CoverPlan::Existing, _) => {
+            if verbose {
+                info!("Cover image found for {}", path.display());
+            }
+            return Ok(Some(CoverOutcome::Existing));
+        }
+        (CoverPlan::Template, _) => {
+            announce_cover_generation(frontmatter, path, content_root, options);
+            if let Some(template) = cover_template_for(frontmatter, path, options) {
+                render_template_cover(frontmatter, body, path, &template, options).await?;
+            }
+            return Ok(Some(CoverOutcome::Template));
+        }
+        (CoverPlan::Generate { images }, Some(ai_client)) => {
+            if verbose {
+                info!("AI client available for cover generation");
+            }
+            announce_cover_generation(frontmatter, path, content_root, options);
+            (ai_client, images)
+        }
+        _ => {
+            if use_default_cover(frontmatter, path, options).await? {
+                return Ok(Some(CoverOutcome::Default));
+            }
+            return Ok(Some(existing_cover_outcome(frontmatter, path, content_root)));
+        }
+    };
+
+    if variations > 1 {
+        return generate_cover_candidates(frontmatter, body, path, ai_client, variations, options)
+            .await;
+    }
+
+    if !ai_client.budget().try_reserve() {
+        return Ok(None);
+    }
+
+    let processor = DefaultCoverImageProcessor::new(Some(ai_client))
+        .with_output_dir(cover_dir.map(Path::to_path_buf))
+        .with_base_name(frontmatter.slug.clone())
+        .with_content_root(content_root.map(Path::to_path_buf));
+
+    match processor
+        .ensure_cover_image(&article_summary(frontmatter, body), path, frontmatter.cover.as_deref())
+        .await?
+    {
+        Some(cover_filename) => {
+            frontmatter.set_cover(cover_filename.clone());
+
+            if verbose {
+                info!("Successfully generated cover image: {}", cover_filename);
+            } else {
+                FORMATTER.print_generation(&FORMATTER.format_cover_success(&cover_filename));
+            }
+            Ok(Some(CoverOutcome::Generated {
+                provider: ai_client.provider_name().to_string(),
+            }))
+        }
+        None => {
+            if verbose {
+                warn!("Cover generation failed but continuing without error");
+            } else {
+                FORMATTER.print_warning(&FORMATTER.format_cover_failure());
+            }
+            options.warnings.record(path, COVER_FAILED_WARNING);
+            if use_default_cover(frontmatter, path, options).await? {
+                return Ok(Some(CoverOutcome::Default));
+            }
+            Ok(Some(existing_cover_outcome(frontmatter, path, content_root)))
+        }
+    }
+}
+
+/// Returns whether the cover named in the frontmatter exists as a file
+fn existing_cover_outcome(
+    frontmatter: &Frontmatter,
+    path: &Path,
+    content_root: Option<&Path>,
+) -> CoverOutcome {
+    match frontmatter.cover_image() {
+        Some(cover) if resolve_cover_path_in_root(path, cover, content_root).1 => {
+            CoverOutcome::Existing
+        }
+        _ => CoverOutcome::Absent,
+    }
+}
+
+/// Returns the name a default cover is copied to next to an article
+pub fn default_cover_filename(base_filename: &str, default_cover: &Path) -> String {
+    let extension = default_cover
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("png");
+    format!("{}_cover_default.{}", base_filename, extension)
+}
+
+/// Uses the account's `default_cover` for a missing cover that cannot be generated
+///
+/// The image is copied to the named cover, or next to the article as
+/// [`default_cover_filename`] and set as the cover. With write-back disabled
+/// nothing is copied: the default cover is set in memory for this upload only.
+///
+/// # Returns
+///
+/// False if there is no default cover or the cover exists
+///
+/// # Errors
+///
+/// Returns an error if the default cover cannot be copied
+async fn use_default_cover(
+    frontmatter: &mut Frontmatter,
+    path: &Path,
+    options: &ProcessOptions,
+) -> Result<bool> {
+    let Some(default_cover) = options.default_cover.as_deref() else {
+        return Ok(false);
+    };
+    let named = frontmatter
+        .cover_image()
+        .map(|cover| resolve_cover_path_in_root(path, cover, options.content_root.as_deref()))
+        .filter(|(cover_path, _)| !cover_path.is_dir());
+    if named.as_ref().is_some_and(|(_, exists)| *exists) {
+        return Ok(false);
+    }
+
+    if options.write_back {
+        let target = match named {
+            Some((cover_path, _)) => cover_path,
+            None => {
+                let filename =
+                    default_cover_filename(&frontmatter.asset_base_name(path), default_cover);
+                frontmatter.set_cover(filename.clone());
+                path.with_file_name(filename)
+            }
+        };
+        if let Some(parent) = target.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::copy(default_cover, &target).await?;
+    } else {
+        frontmatter.set_cover(default_cover.display().to_string());
+    }
+
+    if options.verbose {
+        info!("Using default cover {} for {}", default_cover.display(), path.display());
+    } else {
+        FORMATTER.print_generation(&format!("using default cover: {}", path.display()));
+    }
+    Ok(true)
+}
+
+/// Converts the existing cover to the configured format for `--convert-covers`
+///
+/// The converted cover replaces the original and its name is set in the
+/// frontmatter. A cover that cannot be converted is uploaded as it is.
+///
+/// # Returns
+///
+/// True if the cover was converted
+async fn convert_existing_cover(
+    frontmatter: &mut Frontmatter,
+    path: &Path,
+    options: &ProcessOptions,
+) -> bool {
+    let Some(cover) = frontmatter.cover_image().map(str::to_string) else {
+        return false;
+    };
+    let (cover_path, exists) =
+        resolve_cover_path_in_root(path, &cover, options.content_root.as_deref());
+    if !exists {
+        return false;
+    }
+
+    // Converting removes the original
+    if CoverFormat::from_path(&cover_path) != Some(options.cover_encoding.format)
+        && let Err(e) = options.back_up(&cover_path).await
+    {
+        options.warnings.warn_file(path, &format!("Cover not converted: {}", e));
+        return false;
+    }
+    match convert_cover(&cover_path, options.cover_encoding).await {
+        Ok(Some(converted)) => {
+            let name = cover_name_with_format(&cover, options.cover_encoding.format);
+            if options.verbose {
+                info!("Converted cover {} to {}", cover_path.display(), converted.display());
+            } else {
+                FORMATTER.print_generation(&format!("converted cover: {}", name));
+            }
+            frontmatter.set_cover(name);
+            true
+        }
+        Ok(None) => false,
+        Err(e) => {
+            options.warnings.warn_file(path, &format!("Cover not converted: {}", e));
+            false
+        }
+    }
+}
+
+/// Generates several candidate covers and sets the chosen one in the frontmatter
+///
+/// The candidates are saved next to the article (or in the read-only cover
+/// directory) as `<stem>-cover-<n>.<ext>`. The user is asked to pick one
+/// under the prompt policy, otherwise the first is used. A cover named in the frontmatter keeps
+/// its name and gets a copy of the chosen candidate. Fewer candidates are
+/// generated if the AI image budget runs short. Returns `None` if the budget
+/// allows none.
+async fn generate_cover_candidates(
+    frontmatter: &mut Frontmatter,
+    body: &str,
+    path: &Path,
+    ai_client: &UniversalAIClient,
+    variations: u32,
+    options: &ProcessOptions,
+) -> Result<Option<CoverOutcome>> {
+    let granted = ai_client.budget().reserve_up_to(variations);
+    if granted == 0 {
+        return Ok(None);
+    }
+    if granted < variations {
+        options.warnings.warn_file(
+            path,
+            &format!(
+                "AI image budget allows {} of {} cover variations",
+                granted, variations
+            ),
+        );
+    }
+
+    let cover_dir = options.cover_dir();
+    let dir = match &cover_dir {
+        Some(dir) => {
+            tokio::fs::create_dir_all(dir).await?;
+            dir.clone()
+        }
+        None => path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+    };
+    let base_name = frontmatter.slug.clone().unwrap_or_else(|| {
+        path.file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("article")
+            .to_string()
+    });
+
+    let article = article_summary(frontmatter, body);
+    let candidates = match ai_client
+        .generate_cover_variations(&article, &dir, &base_name, granted)
+        .await
+    {
+        Ok(candidates) => candidates,
+        Err(e) => {
+            if options.verbose {
+                warn!("Failed to generate cover candidates: {}. Continuing without cover.", e);
+            } else {
+                FORMATTER.print_warning(&FORMATTER.format_cover_failure());
+            }
+            options.warnings.record(path, COVER_FAILED_WARNING);
+            return Ok(Some(CoverOutcome::Absent));
+        }
+    };
+
+    FORMATTER.print_generation(&format!(
+        "saved {} cover candidates for {}:",
+        candidates.len(),
+        path.display()
+    ));
+    for (index, candidate) in candidates.iter().enumerate() {
+        FORMATTER.print_detail(&format!("  {}. {}", index + 1, candidate.display()));
+    }
+
+    let question = format!("Choose a cover for {}", path.display());
+    let chosen = &candidates[options.prompt.choose(&question, candidates.len()).await?];
+
+    let content_root = options.content_root.as_deref();
+    let named_cover = frontmatter
+        .cover
+        .as_ref()
+        .filter(|name| !cover_is_directory(path, name, content_root));
+    let cover = match (named_cover, &cover_dir) {
+        (Some(name), None) => {
+            let (target, _) = resolve_cover_path_in_root(path, name, content_root);
+            if let Some(parent) = target.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            copy_cover(chosen, &target, options.cover_encoding).await?;
+            name.clone()
+        }
+        (_, Some(_)) => chosen.display().to_string(),
+        (None, None) => chosen
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+    };
+    frontmatter.set_cover(cover.clone());
+
+    if options.verbose {
+        info!("Using cover candidate {} as {}", chosen.display(), cover);
+    } else {
+        FORMATTER.print_generation(&FORMATTER.format_cover_success(&cover));
+    }
+    Ok(Some(CoverOutcome::Generated {
+        provider: ai_client.provider_name().to_string(),
+    }))
+}
+
+/// Returns the cover template of a file: its own fields over the configured ones
+///
+/// Paths in the frontmatter are resolved like the cover path.
+fn cover_template_for(
+    frontmatter: &Frontmatter,
+    path: &Path,
+    options: &ProcessOptions,
+) -> Option<CoverTemplate> {
+    let content_root = options.content_root.as_deref();
+    let own = frontmatter.cover_template.clone().map(|template| {
+        template.map_paths(|p| resolve_cover_path_in_root(path, p, content_root).0)
+    });
+    match (own, &options.cover_template) {
+        (Some(own), Some(base)) => Some(own.or(base)),
+        (own, base) => own.or_else(|| base.clone()),
+    }
+}
+
+/// Renders the missing cover from `template` and sets it in the frontmatter
+///
+/// The cover is written where the frontmatter names it, or next to the
+/// article with a generated name, like an AI cover.
+async fn render_template_cover(
+    frontmatter: &mut Frontmatter,
+    body: &str,
+    path: &Path,
+    template: &CoverTemplate,
+    options: &ProcessOptions,
+) -> Result<()> {
+    let cover_dir = options.cover_dir();
+    let cover_dir = cover_dir.as_deref();
+    let content_root = options.content_root.as_deref();
+    let named_cover = frontmatter
+        .cover
+        .as_ref()
+        .filter(|cover| !cover_is_directory(path, cover, content_root));
+    let (target, cover_filename) = match named_cover {
+        Some(cover) => match cover_dir {
+            Some(dir) => {
+                let target = dir.join(Path::new(cover).file_name().unwrap_or_default());
+                (target.clone(), target.display().to_string())
+            }
+            None => (resolve_cover_path_in_root(path, cover, content_root).0, cover.clone()),
+        },
+        None => {
+            let name = generated_cover_filename(
+                &frontmatter.asset_base_name(path),
+                &uuid::Uuid::new_v4().simple().to_string(),
+                options.cover_encoding.format,
+            );
+            match cover_dir {
+                Some(dir) => (dir.join(&name), dir.join(&name).display().to_string()),
+                None => (path.with_file_name(&name), name),
+            }
+        }
+    };
+
+    let title = frontmatter
+        .title
+        .clone()
+        .or_else(|| extract_h1_title(body))
+        .unwrap_or_else(|| frontmatter.asset_base_name(path));
+    render_cover(template, &title, &target, options.cover_encoding).await?;
+
+    if options.verbose {
+        info!("Rendered cover from template: {}", target.display());
+    } else {
+        FORMATTER.print_generation(&FORMATTER.format_cover_success(&cover_filename));
+    }
+    frontmatter.set_cover(cover_filename);
+    Ok(())
+}
+
+/// Summarizes the article for cover scene generation
+fn article_summary(frontmatter: &Frontmatter, body: &str) -> ArticleSummary {
+    ArticleSummary::new(frontmatter.title.as_deref(), &frontmatter.description, body)
+        .with_lang(frontmatter.lang.as_deref())
+        .with_tags(frontmatter.tags())
+}
+
+/// Reports that the missing cover of `path` is being generated
+fn announce_cover_generation(
+    frontmatter: &Frontmatter,
+    path: &Path,
+    content_root: Option<&Path>,
+    options: &ProcessOptions,
+) {
+    let verbose = options.verbose;
+    let Some(cover_filename) = &frontmatter.cover else {
+        if verbose {
+            info!("No cover image specified, generating one...");
+        } else {
+            FORMATTER.print_generation(&FORMATTER.format_cover_generation(path));
+        }
+        return;
+    };
+
+    let (cover_path, _) = resolve_cover_path_in_root(path, cover_filename, content_root);
+    if cover_path.is_dir() && verbose {
+        warn!(
+            "Cover {} is a directory at {}, generating a new cover...",
+            cover_filename,
+            cover_path.display()
+        );
+        let warning = format!("cover {} is a directory, generating a new cover", cover_filename);
+        options.warnings.record(path, warning);
+    } else if cover_path.is_dir() {
+        options.warnings.warn(
+            path,
+            &format!(
+                "cover {} is a directory, generating a new cover: {}",
+                cover_filename,
+                path.display()
+            ),
+        );
+    } else if verbose {
+        info!(
+            "Cover image specified ({}) but file not found at {}, generating it...",
+            cover_filename,
+            cover_path.display()
+        );
+    } else {
+        FORMATTER.print_generation(&format!(
+            "cover missing ({}), generating: {}",
+            cover_filename,
+            path.display()
+        ));
+    }
+}
+
+/// Generates the thumbnail with AI when `thumb` names a separate file that does not exist
+///
+/// Returns whether a thumbnail was generated, or deferred by the AI image budget.
+async fn process_thumb_image(
+    frontmatter: &mut Frontmatter,
+    body: &str,
+    path: &Path,
+    ai_client: Option<&UniversalAIClient>,
+    cover_dir: Option<&Path>,
+    content_root: Option<&Path>,
+    options: &ProcessOptions,
+) -> Result<CoverStatus> {
+    let (verbose, warnings) = (options.verbose, &options.warnings);
+    let Some(thumb) = frontmatter.thumb.clone() else {
+        return Ok(CoverStatus::Unchanged);
+    };
+    let thumb = thumb.as_str();
+    if thumb == THUMB_FIRST_IMAGE || frontmatter.cover_image() == Some(thumb) {
+        return Ok(CoverStatus::Unchanged);
+    }
+
+    let (thumb_path, exists) = resolve_cover_path_in_root(path, thumb, content_root);
+    if exists {
+        return Ok(CoverStatus::Unchanged);
+    }
+
+    let Some(ai_client) = ai_client else {
+        let warning = format!("thumb missing ({}), no AI key to generate", thumb);
+        if verbose {
+            warn!(
+                "Thumbnail specified ({}) but file not found at {} and no AI provider configured. Upload may fail.",
+                thumb,
+                thumb_path.display()
+            );
+            warnings.record(path, warning);
+        } else {
+            warnings.warn(path, &warning);
+        }
+        return Ok(CoverStatus::Unchanged);
+    };
+
+    if verbose {
+        info!("Thumbnail missing at {}, generating using AI...", thumb_path.display());
+    } else {
+        FORMATTER.print_generation(&format!("thumb missing ({}), generating: {}", thumb, path.display()));
+    }
+
+    if !ai_client.budget().try_reserve() {
+        return Ok(CoverStatus::Deferred);
+    }
+
+    let processor = DefaultCoverImageProcessor::new(Some(ai_client))
+        .with_output_dir(cover_dir.map(Path::to_path_buf))
+        .with_content_root(content_root.map(Path::to_path_buf));
+    let status = match processor
+        .ensure_cover_image(&article_summary(frontmatter, body), path, Some(thumb))
+        .await?
+    {
+        Some(thumb_filename) => {
+            // Only differs when generated outside the source tree; kept in memory
+            frontmatter.set_thumb(thumb_filename.clone());
+            if !verbose {
+                FORMATTER.print_generation(&FORMATTER.format_cover_success(&thumb_filename));
+            }
+            CoverStatus::Generated
+        }
+        None => {
+            if !verbose {
+                FORMATTER.print_warning(&FORMATTER.format_cover_failure());
+            }
+            warnings.record(path, COVER_FAILED_WARNING);
+            CoverStatus::Unchanged
+        }
+    };
+
+    Ok(status)
+}
+
+/// Losslessly recompresses the cover and separate thumbnail before upload
+///
+/// Best-effort: an image that cannot be optimized is uploaded unchanged.
+///
+/// With `cover_dir` set, source files are read-only and only images generated
+/// into that directory are optimized.
+async fn optimize_cover_images(
+    frontmatter: &Frontmatter,
+    body: &str,
+    path: &Path,
+    cover_dir: Option<&Path>,
+    content_root: Option<&Path>,
+    verbose: bool,
+    warnings: &WarningLog,
+) {
+    let images = frontmatter
+        .cover_image()
+        .map(str::to_string)
+        .into_iter()
+        .chain(resolve_separate_thumb(frontmatter, body, path, content_root));
+
+    for image in images {
+        let (image_path, exists) = resolve_cover_path_in_root(path, &image, content_root);
+        if !exists || cover_dir.is_some_and(|dir| !image_path.starts_with(dir)) {
+            continue;
+        }
+
+        match optimize_png(&image_path, OPTIMIZE_THRESHOLD_BYTES).await {
+            Ok(OptimizeOutcome::Optimized { before, after }) => {
+                if verbose {
+                    info!(
+                        "Optimized {}: {} KB -> {} KB (saved {}%)",
+                        image_path.display(),
+                        before / 1024,
+                        after / 1024,
+                        (before - after) * 100 / before
+                    );
+                }
+            }
+            Ok(OptimizeOutcome::Skipped { reason }) => {
+                if verbose {
+                    info!("Not optimizing {}: {}", image_path.display(), reason);
+                }
+            }
+            Err(e) => {
+                let warning = format!("cover not optimized: {}", e);
+                if verbose {
+                    warn!("Uploading {} unoptimized: {}", image_path.display(), e);
+                    warnings.record(path, warning);
+                } else {
+                    warnings.warn(path, &warning);
+                }
+            }
+        }
+    }
+}
+
+/// Resolves the thumbnail to upload when it differs from the cover
+///
+/// Returns `None` when the thumbnail is unset, cannot be determined, or points
+/// at the same file as the cover, in which case the cover is uploaded once and
+/// used for both.
+fn resolve_separate_thumb(
+    frontmatter: &Frontmatter,
+    body: &str,
+    path: &Path,
+    content_root: Option<&Path>,
+) -> Option<String> {
+    let thumb = match frontmatter.thumb.as_deref()? {
+        THUMB_FIRST_IMAGE => first_local_image(body)?,
+        thumb => thumb.to_string(),
+    };
+
+    if let Some(cover) = frontmatter.cover_image() {
+        let (cover_path, _) = resolve_cover_path_in_root(path, cover, content_root);
+        let (thumb_path, _) = resolve_cover_path_in_root(path, &thumb, content_root);
+        if cover_path == thumb_path {
+            return None;
+        }
+    }
+
+    Some(thumb)
+}
+
+/// Returns the first relative image referenced in the body
+fn first_local_image(body: &str) -> Option<String> {
+    extract_links(body)
+        .into_iter()
+        .find(|link| link.kind == LinkKind::Image && link.is_relative())
+        .map(|link| link.target)
+}
+
+/// Markdown file handed to the WeChat client
+///
+/// Usually the article itself; when the cover has to be injected as an in-body
+/// header image, a temporary sibling file is written and removed on drop.
+struct UploadSource {
+    path: PathBuf,
+    temporary: bool,
+}
+
+impl UploadSource {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for UploadSource {
+    fn drop(&mut self) {
+        if self.temporary {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Suffix of temporary files written next to articles during upload
+const UPLOAD_TEMP_SUFFIX: &str = ".wx-upload.md";
+
+/// Returns true for temporary upload files left behind by an interrupted run
+fn is_upload_temp_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.starts_with('.') && n.ends_with(UPLOAD_TEMP_SUFFIX))
+}
+
+/// Returns an image path the uploader can resolve
+///
+/// Paths starting with `/` become absolute paths under `content_root`, if set;
+/// anything else is resolved by the uploader as before.
+fn upload_image_path(path: &Path, image: &str, content_root: Option<&Path>) -> String {
+    if content_root.is_some() && image.starts_with('/') {
+        resolve_cover_path_in_root(path, image, content_root).0.display().to_string()
+    } else {
+        image.to_string()
+    }
+}
+
+/// Builds the article options and upload source for a file
+///
+/// When a separate thumbnail is used, it becomes the draft thumbnail and the
+/// cover (if any) is placed at the top of the body as the header image. With
+/// `style_replaced`, the file is uploaded with the theme and code highlighter
+/// of `frontmatter` rather than the ones on disk, and with `body_replaced`
+/// with `body` rather than the body on disk. The frontmatter of a `sidecar`
+/// file is written into the uploaded file, since the uploader only reads it
+/// from there. A cover starting with `/` is uploaded as its absolute path
+/// under `content_root`, if set.
+async fn prepare_article(
+    frontmatter: &Frontmatter,
+    body: &str,
+    path: &Path,
+    style_replaced: bool,
+    body_replaced: bool,
+    sidecar: bool,
+    options: &ProcessOptions,
+) -> Result<(ArticleOptions, UploadSource)> {
+    let content_root = options.content_root.as_deref();
+    let verbose = options.verbose;
+    let original = UploadSource {
+        path: path.to_path_buf(),
+        temporary: false,
+    };
+
+    if frontmatter.thumb.as_deref() == Some(THUMB_FIRST_IMAGE) && first_local_image(body).is_none()
+    {
+        let warning = "no local image found for thumb: first-image, using cover";
+        if verbose {
+            warn!("thumb: {} set but no local image found in body, using cover", THUMB_FIRST_IMAGE);
+            options.warnings.record(path, warning);
+        } else {
+            options.warnings.warn(path, warning);
+        }
+    }
+
+    let mut fields = ArticleFields::from(frontmatter);
+    fields.original = frontmatter.original.unwrap_or(options.original);
+
+    // The description on disk stays intact; only the uploaded digest is cut
+    let truncated_digest = frontmatter.digest_warning(options.digest_limit).map(|warning| {
+        options.warnings.warn_file(path, &warning);
+        truncate_digest(frontmatter.description.trim(), options.digest_limit).into_owned()
+    });
+    if let Some(digest) = &truncated_digest {
+        fields.digest = Some(digest.clone());
+    }
+    // A material named by cover_media_id replaces the cover and thumbnail files
+    let thumb_media_id = frontmatter.cover_media_id.clone();
+    let separate_thumb = resolve_separate_thumb(frontmatter, body, path, content_root)
+        .filter(|_| thumb_media_id.is_none());
+    let (article_options, header) = match separate_thumb {
+        Some(thumb) => {
+            if verbose {
+                info!("Using separate thumbnail: {}", thumb);
+            }
+            let article_options = ArticleOptions {
+                thumb: Some(upload_image_path(path, &thumb, content_root)),
+                thumb_media_id: None,
+                cover_disabled: frontmatter.cover_disabled(),
+                show_cover: false,
+                fields,
+                on_material_denied: options.on_material_denied,
+                article: path.to_path_buf(),
+                warnings: options.warnings.clone(),
+            };
+            (article_options, frontmatter.cover_image())
+        }
+        None => {
+            // A fallback thumbnail of a coverless article is not shown in the body
+            let cover_disabled = frontmatter.cover_disabled() && thumb_media_id.is_none();
+            let article_options = ArticleOptions {
+                thumb_media_id,
+                cover_disabled,
+                show_cover: !cover_disabled,
+                fields,
+                on_material_denied: options.on_material_denied,
+                article: path.to_path_buf(),
+                warnings: options.warnings.clone(),
+                ..Default::default()
+            };
+            (article_options, None)
+        }
+    };
+
+    // The uploader only knows filesystem paths, so the cover has to be rewritten
+    let root_cover = frontmatter
+        .cover_image()
+        .filter(|cover| content_root.is_some() && cover.starts_with('/'))
+        .filter(|_| article_options.thumb_media_id.is_none());
+
+    if header.is_none()
+        && !style_replaced
+        && !body_replaced
+        && !sidecar
+        && root_cover.is_none()
+        && truncated_digest.is_none()
+    {
+        return Ok((article_options, original));
+    }
+
+    // The rewritten file would be written next to the article, which may be read-only
+    if !options.write_back {
+        let warnings = &options.warnings;
+        if let Some(cover) = header {
+            let warning = "cover header image skipped: source files are read-only";
+            if verbose {
+                warn!("Not injecting cover {} as header image: write-back is disabled", cover);
+                warnings.record(path, warning);
+            } else {
+                warnings.warn(path, warning);
+            }
+        }
+        if style_replaced {
+            warnings.warn(path, "theme fallback not applied: source files are read-only");
+        }
+        if sidecar && (frontmatter.theme.is_some() || frontmatter.code.is_some()) {
+            warnings.warn(path, "sidecar theme not applied: source files are read-only");
+        }
+        if let Some(cover) = root_cover {
+            warnings.warn(
+                path,
+                &format!(
+                    "cover {} not resolved against content_root: source files are read-only",
+                    cover
+                ),
+            );
+        }
+        return Ok((article_options, original));
+    }
+
+    let file_stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("article");
+    let temp_path = path.with_file_name(format!(".{}{}", file_stem, UPLOAD_TEMP_SUFFIX));
+    let upload_body = match header {
+        Some(cover) => format!("![]({})\n\n{}", upload_image_path(path, cover, content_root), body),
+        None => body.to_string(),
+    };
+    let mut upload_frontmatter = frontmatter.clone();
+    if let Some(cover) = root_cover {
+        upload_frontmatter.set_cover(upload_image_path(path, cover, content_root));
+    }
+    // Without a WeChat base URL the digest is read from the uploaded file
+    if let Some(digest) = truncated_digest {
+        upload_frontmatter.description = digest;
+    }
+    write_markdown_file(&temp_path, &upload_frontmatter, &upload_body).await?;
+
+    Ok((
+        article_options,
+        UploadSource {
+            path: temp_path,
+            temporary: true,
+        },
+    ))
+}
+
+/// Executes the WeChat upload operation
+///
+/// `path` is the article as shown to the user; `upload_path` is the file sent
+/// to WeChat, which differs when a header cover had to be injected.
+async fn execute_wechat_upload(
+    client: &dyn WeChatUploader,
+    path: &Path,
+    upload_path: &Path,
+    options: &ArticleOptions,
+    verbose: bool,
+) -> Result<String> {
+    if verbose {
+        info!("Uploading file: {}", path.display());
+    } else {
+        FORMATTER.print_progress(&FORMATTER.format_file_operation("uploading", path));
+    }
+
+    let path_str = upload_path
+        .to_str()
+        .ok_or_else(|| Error::generic("Path contains invalid UTF-8"))?;
+
+    match client.upload(path_str, options).await {
+        Ok(draft_id) => {
+            if verbose {
+                info!("Successfully uploaded with draft ID: {}", draft_id);
+            } else {
+                FORMATTER.print_success(&FORMATTER.format_upload_success(path));
+            }
+            Ok(draft_id)
+        }
+        Err(e) => {
+            let error_msg = format!("WeChat upload failed: {}", e);
+            if verbose {
+                warn!("Failed to upload {}: {}", path.display(), error_msg);
+            } else if !annotations_enabled() {
+                // With annotations, the run report prints the failure once
+                FORMATTER.print_error(&FORMATTER.format_upload_failure(path));
+                eprintln!("Error: {}", error_msg);
+            }
+            // Keep authentication and content rejections distinguishable
+            Err(if e.is_auth() || e.is_content_risk() {
+                e
+            } else {
+                Error::wechat_with_source("WeChat upload failed", e)
+            })
+        }
+    }
+}
+
+/// Helps locate the text behind a content security rejection
+///
+/// With `diagnose_content` set, the article is bisected with `msg_sec_check`; otherwise
+/// only a hint about `--diagnose-content` is printed. Diagnosis problems are
+/// reported as warnings so the original upload error stays the result.
+async fn report_content_risk(client: &dyn WeChatUploader, path: &Path, options: &ProcessOptions) {
+    if !options.diagnose_content {
+        FORMATTER.print_info("re-run with --diagnose-content to locate the flagged text");
+        return;
+    }
+
+    FORMATTER.print_progress(&FORMATTER.format_file_operation("checking content", path));
+    let findings = match client.content_checker().await {
+        Ok(checker) => diagnose_file(checker.as_ref(), path).await,
+        Err(e) => Err(e),
+    };
+
+    match findings {
+        Ok(findings) => print_diagnosis(path, &findings),
+        Err(e) => options
+            .warnings
+            .warn(path, &format!("content diagnosis failed: {}", e)),
+    }
+}
+
+/// Applies the frontmatter changes made after a successful upload
+pub fn mark_uploaded(frontmatter: &mut Frontmatter, draft_id: &str) {
+    frontmatter.set_published(PublicationStatus::Draft);
+    frontmatter.set_draft_id(draft_id);
+}
+
+/// Applies the frontmatter changes made after uploading to several accounts
+///
+/// The top-level `draft_id` is left alone since no single draft represents
+/// the article; each account's draft is recorded under `uploads`.
+pub fn mark_uploaded_to_accounts(frontmatter: &mut Frontmatter, drafts: &[(&str, String)]) {
+    frontmatter.set_published(PublicationStatus::Draft);
+    for (account, draft_id) in drafts {
+        frontmatter.set_upload(*account, draft_id);
+    }
+}
+
+/// Returns the files whose title already has a draft in `index`, with that draft's media ID
+///
+/// Published files and files marked skip are left out unless `force` is set,
+/// since they are skipped anyway. So are files that cannot be parsed, so that their upload reports
+/// the error.
+pub async fn match_existing_drafts(
+    index: &DraftIndex,
+    files: &[PathBuf],
+    force: bool,
+    options: &ProcessOptions,
+) -> Vec<(PathBuf, String)> {
+    let parse_options = ParseOptions {
+        quiet: true,
+        ..options.parse_options()
+    };
+
+    let mut matched = Vec::new();
+    for path in files {
+        let Ok(frontmatter) = parse_frontmatter_file_with(path, &parse_options).await else {
+            continue;
+        };
+        if !force && frontmatter.publication_status().skip_reason().is_some() {
+            continue;
+        }
+        if let Some(draft_id) = index.find_for(&frontmatter) {
+            matched.push((path.clone(), draft_id.to_string()));
+        }
+    }
+    matched
+}
+
+/// Estimates the AI usage of generating `images` cover images for one file
+///
+/// The image model may be picked by the frontmatter; a scene description is
+/// counted if the client requests one.
+pub fn cover_estimate(frontmatter: &Frontmatter, images: u32, ai_client: &UniversalAIClient) -> CostEstimate {
+    let prices = ai_client.prices();
+    let model = frontmatter
+        .image_model
+        .as_deref()
+        .map(str::trim)
+        .filter(|model| !model.is_empty())
+        .unwrap_or(ai_client.image_model());
+    let description_price = ai_client.uses_ai_scene_text().then_some(prices.description);
+
+    let mut estimate = CostEstimate::default();
+    estimate.add_file(images, prices.image_price(model), description_price);
+    estimate
+}
+
+/// Estimates the AI usage of uploading `files`: the covers to generate and their cost
+///
+/// A file counts if it would be uploaded and has no cover, neither as a file
+/// nor from a cover template. Files that cannot be parsed are left out, since
+/// their upload fails before a cover is generated.
+pub async fn estimate_ai_cost(
+    files: &[PathBuf],
+    force: bool,
+    ai_client: &UniversalAIClient,
+    options: &ProcessOptions,
+) -> CostEstimate {
+    let parse_options = ParseOptions {
+        quiet: true,
+        ..options.parse_options()
+    };
+
+    let mut estimate = CostEstimate::default();
+    for path in files {
+        let Ok(frontmatter) = parse_frontmatter_file_with(path, &parse_options).await else {
+            continue;
+        };
+        if upload_skip_reason(path, &frontmatter, force, options).await.is_some() {
+            continue;
+        }
+        if let CoverPlan::Generate { images } = plan_cover(&frontmatter, path, true, options) {
+            estimate.merge(&cover_estimate(&frontmatter, images, ai_client));
+        }
+    }
+    estimate
+}
+
+/// Records a draft found by `--reconcile` in the frontmatter, as an upload would have
+///
+/// Without status updates nothing is written; the caller records the draft in
+/// the state file instead.
+///
+/// # Returns
+///
+/// `FileOutcome::Skipped` naming the existing draft
+///
+/// # Errors
+///
+/// Returns an error if the frontmatter cannot be updated
+pub async fn record_existing_draft(
+    path: &Path,
+    draft_id: &str,
+    options: &ProcessOptions,
+) -> Result<FileOutcome> {
+    if options.updates_status() {
+        update_published_status(path, draft_id, options).await?;
+    }
+
+    let reason = existing_draft_reason(draft_id);
+    if options.verbose {
+        info!("Skipping {}: {}", path.display(), reason);
+    } else {
+        FORMATTER.print_skip(&format!("skipped ({}): {}", reason, path.display()));
+    }
+    Ok(FileOutcome::Skipped { reason })
+}
+
+/// Updates the frontmatter with published status and draft ID after successful upload
+async fn update_published_status(path: &Path, draft_id: &str, options: &ProcessOptions) -> Result<()> {
+    options.back_up(path).await?;
+    update_frontmatter_with(path, &options.parse_options(), |fm| {
+        mark_uploaded(fm, draft_id);
+        Ok(())
+    })
+    .await?;
+
+    if options.verbose {
+        info!(
+            "Updated frontmatter with draft status in: {}",
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Resolves a cover image path relative to the markdown file and checks if it is a file
+///
+/// # Arguments
+///
+/// * `markdown_file_path` - Path to the markdown file
+/// * `cover_filename` - Relative or absolute path to the cover image
+///
+/// # Returns
+///
+/// A tuple containing the resolved path and whether it is an existing file.
+/// A directory, e.g. from `cover: images/`, is not a usable cover and counts
+/// as missing; see [`cover_is_directory`].
+pub fn resolve_and_check_cover_path(
+    markdown_file_path: &Path,
+    cover_filename: &str,
+) -> (PathBuf, bool) {
+    resolve_cover_path_in_root(markdown_file_path, cover_filename, None)
+}
+
+/// Resolves a cover image path like [`resolve_and_check_cover_path`], with
+/// paths starting with `/` taken relative to `content_root` if one is set
+///
+/// This follows static site generators, where `/assets/x.png` means the file
+/// at `assets/x.png` in the repository. An absolute path that exists on disk
+/// but not under the root, such as a cover generated into a temporary
+/// directory, is still used as is.
+pub fn resolve_cover_path_in_root(
+    markdown_file_path: &Path,
+    cover_filename: &str,
+    content_root: Option<&Path>,
+) -> (PathBuf, bool) {
+    if let Some(root) = content_root
+        && let Some(relative) = cover_filename.strip_prefix('/')
+    {
+        let cover_path = root.join(relative);
+        if !cover_path.exists() && Path::new(cover_filename).is_file() {
+            return (PathBuf::from(cover_filename), true);
+        }
+        let is_file = cover_path.is_file();
+        return (cover_path, is_file);
+    }
+
+    let cover_path = if Path::new(cover_filename).is_absolute() {
+        PathBuf::from(cover_filename)
+    } else {
+        // If cover filename is relative, resolve it relative to the markdown file's directory
+        markdown_file_path
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join(cover_filename)
+    };
+
+    let is_file = cover_path.is_file();
+    (cover_path, is_file)
+}
+
+/// What a cover path names on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoverPathKind {
+    /// An existing file, used as the cover
+    File,
+    /// A directory, whose images are candidates for the cover
+    Directory,
+    /// Nothing yet
+    Missing,
+}
+
+/// Resolves a cover path like [`resolve_cover_path_in_root`] and tells a
+/// file from a directory
+pub fn resolve_cover_path_kind(
+    markdown_file_path: &Path,
+    cover_filename: &str,
+    content_root: Option<&Path>,
+) -> (PathBuf, CoverPathKind) {
+    let (cover_path, is_file) =
+        resolve_cover_path_in_root(markdown_file_path, cover_filename, content_root);
+    let kind = if is_file {
+        CoverPathKind::File
+    } else if cover_path.is_dir() {
+        CoverPathKind::Directory
+    } else {
+        CoverPathKind::Missing
+    };
+    (cover_path, kind)
+}
+
+/// Returns true if a cover or thumbnail path names an existing directory rather than an image
+pub fn cover_is_directory(
+    markdown_file_path: &Path,
+    cover_filename: &str,
+    content_root: Option<&Path>,
+) -> bool {
+    resolve_cover_path_kind(markdown_file_path, cover_filename, content_root).1
+        == CoverPathKind::Directory
+}
+
+/// Picks an image of `dir` as the cover under `policy`
+///
+/// Candidates are the PNG, JPEG and WebP files directly in `dir`; hidden
+/// files and subdirectories are ignored.
+///
+/// # Returns
+///
+/// The chosen image, or `None` if the directory has no candidates or cannot be read
+pub fn select_cover_candidate(dir: &Path, policy: CoverSelect) -> Option<PathBuf> {
+    let mut candidates: Vec<_> = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && CoverFormat::from_path(path).is_some()
+                && !path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        })
+        .collect();
+    candidates.sort();
+
+    match policy {
+        CoverSelect::First => candidates.into_iter().next(),
+        // Ties go to the first by name, so the choice is stable
+        CoverSelect::Newest => candidates.into_iter().rev().max_by_key(|path| {
+            path.metadata()
+                .and_then(|metadata| metadata.modified())
+                .unwrap_or(std::time::SystemTime::UNIX_EPOCH)
+        }),
+        CoverSelect::Random => {
+            use std::hash::BuildHasher;
+            if candidates.is_empty() {
+                return None;
+            }
+            let index = std::hash::RandomState::new().hash_one(dir) as usize % candidates.len();
+            Some(candidates.swap_remove(index))
+        }
+    }
+}
+
+/// Returns the cover to record when the frontmatter's `cover` names a
+/// directory of candidates: the image [`select_cover_candidate`] picks under
+/// `options.cover_select`, named inside the directory as written, e.g.
+/// `covers/post/` becomes `covers/post/b.png`
+///
+/// `None` if the cover is not a directory or the directory has no images; the
+/// cover is then generated or defaulted under the usual rules. The upload and
+/// the dry-run plan both select with this.
+pub fn select_directory_cover(
+    frontmatter: &Frontmatter,
+    path: &Path,
+    options: &ProcessOptions,
+) -> Option<String> {
+    let cover = frontmatter.cover_image()?;
+    let (dir, kind) = resolve_cover_path_kind(path, cover, options.content_root.as_deref());
+    if kind != CoverPathKind::Directory {
+        return None;
+    }
+    let chosen = select_cover_candidate(&dir, options.cover_select)?;
+    let name = chosen.file_name()?.to_string_lossy();
+    Some(format!("{}/{}", cover.trim_end_matches(['/', '\\']), name))
+}
+
+/// Returns the error for a cover that does not exist and could not be generated
+fn cover_not_found_error(cover_path: &Path, cover_filename: &str) -> Error {
+    Error::cover_image(
+        cover_path,
+        format!(
+            "cover '{}' not found; add the image, fix the path or remove the cover \
+             to generate one",
+            cover_filename
+        ),
+    )
+}
+
+/// Returns the error for a file without a cover under `--require-cover`
+fn missing_required_cover_error(path: &Path, frontmatter: &Frontmatter) -> Error {
+    let reason = if frontmatter.cover_disabled() {
+        "cover is disabled with `cover: none`, but --require-cover needs one"
+    } else {
+        "no cover after cover processing; add one or check why generation failed \
+         (--require-cover)"
+    };
+    Error::cover_image(path, reason)
+}
+
+/// Returns the AI client for a file whose frontmatter sets `ai_provider`,
+/// `text_model` or `image_model`, or `None` if it sets none of them
+///
+/// # Errors
+///
+/// Returns a configuration error naming the file if no AI provider is
+/// configured or the frontmatter names another provider
+fn file_ai_client(
+    ai_client: Option<&UniversalAIClient>,
+    frontmatter: &Frontmatter,
+    path: &Path,
+) -> Result<Option<UniversalAIClient>> {
+    fn setting(value: &Option<String>) -> Option<&str> {
+        value.as_deref().map(str::trim).filter(|value| !value.is_empty())
+    }
+    let provider = setting(&frontmatter.ai_provider);
+    let text_model = setting(&frontmatter.text_model);
+    let image_model = setting(&frontmatter.image_model);
+    if provider.is_none() && text_model.is_none() && image_model.is_none() {
+        return Ok(None);
+    }
+
+    let Some(client) = ai_client else {
+        return Err(Error::config(format!(
+            "{}: the frontmatter sets an AI provider or model, but no AI provider is configured",
+            path.display()
+        )));
+    };
+    client
+        .with_model_overrides(provider, text_model, image_model)
+        .map(Some)
+        .map_err(|e| match e {
+            Error::Config { message } => Error::config(format!("{}: {}", path.display(), message)),
+            e => e,
+        })
+}
+
+/// Returns the error for a cover path that names a directory
+fn cover_directory_error(cover_path: &Path, cover_filename: &str) -> Error {
+    Error::cover_image(
+        cover_path,
+        format!(
+            "cover '{}' is a directory without images; add candidate images, name \
+             an image or remove the cover to generate one",
+            cover_filename
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markdown::FnTransform;
+    use crate::models::{AccountKind, WeChatAccount};
+    use std::collections::HashMap;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn config_with_accounts(names: &[&str]) -> Config {
+        let accounts: HashMap<_, _> = names
+            .iter()
+            .map(|name| {
+                (
+                    name.to_string(),
+                    WeChatAccount {
+                        name: name.to_string(),
+                        app_id: format!("wx_{}", name),
+                        app_secret: "secret".to_string(),
+                        description: None,
+                        wechat_base_url: None,
+                        default_cover: None,
+                        kind: AccountKind::Normal,
+                        tags: Vec::new(),
+                        owner: None,
+                    },
+                )
+            })
+            .collect();
+        Config::new_with_accounts(accounts, names[0], None, false).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_group_files_by_account() {
+        let temp_dir = TempDir::new().unwrap();
+        let write = |name: &str, content: &str| {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, content).unwrap();
+            path
+        };
+        let plain = write("a.md", "# No frontmatter");
+        let work_1 = write("b.md", "---\naccount: work\n---\nbody");
+        let main = write("c.md", "---\naccount: main\n---\nbody");
+        let broken = write("d.md", "---\ntitle: [unclosed\n---\nbody");
+        let work_2 = write("e.md", "---\naccount: work\n---\nbody");
+
+        let files = vec![plain.clone(), work_1.clone(), main.clone(), broken.clone(), work_2.clone()];
+        let groups = group_files_by_account(&files, &config_with_accounts(&["main", "work"]))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            groups,
+            vec![
+                AccountGroup {
+                    accounts: Vec::new(),
+                    files: vec![plain, main, broken],
+                },
+                AccountGroup {
+                    accounts: vec!["work".to_string()],
+                    files: vec![work_1, work_2],
+                },
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_collect_markdown_files_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let shared = root.join("shared");
+        fs::create_dir_all(&shared).unwrap();
+        fs::create_dir_all(root.join("posts/a")).unwrap();
+        fs::create_dir_all(root.join("posts/b")).unwrap();
+        fs::write(shared.join("common.md"), "# Shared").unwrap();
+        fs::write(root.join("posts/own.md"), "# Own").unwrap();
+        // The same article linked into two collections
+        symlink(shared.join("common.md"), root.join("posts/a/common.md")).unwrap();
+        symlink(shared.join("common.md"), root.join("posts/b/common.md")).unwrap();
+        symlink(root.join("missing.md"), root.join("posts/broken.md")).unwrap();
+        // A linked directory, and a loop back to an ancestor
+        symlink(&shared, root.join("posts/linked")).unwrap();
+        symlink(root.join("posts"), root.join("posts/b/loop")).unwrap();
+
+        let names = |files: Vec<PathBuf>| {
+            let mut names: Vec<String> = files
+                .iter()
+                .map(|path| path.strip_prefix(root).unwrap().display().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+
+        // Linked files are kept once; linked directories are not entered
+        let files = names(collect_markdown_files(&root.join("posts")));
+        assert_eq!(files.len(), 2, "{:?}", files);
+        assert!(files.contains(&"posts/own.md".to_string()));
+        assert!(files[0].ends_with("/common.md"), "{:?}", files);
+
+        // Following links reaches the shared directory, still uploading it once
+        let files = names(collect_markdown_files_with(&root.join("posts"), true));
+        assert_eq!(files.len(), 2, "{:?}", files);
+        assert!(files.contains(&"posts/own.md".to_string()));
+
+        let files = names(collect_markdown_files_with(&shared, true));
+        assert_eq!(files, vec!["shared/common.md"]);
+    }
+
+    /// Without a base URL, articles wechat_pub_rs cannot send go to the API client
+    #[tokio::test]
+    async fn test_default_endpoint_uploader_routes_declarations_to_the_api_client() {
+        use wiremock::matchers::{body_partial_json, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/cgi-bin/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "mock_token",
+                "expires_in": 7200
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/cgi-bin/material/add_material"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "media_id": "cover_media_id",
+                "url": "http://mmbiz.qpic.cn/mock/cover.png"
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/cgi-bin/draft/add"))
+            .and(body_partial_json(serde_json::json!({
+                "articles": [{ "is_original": 1 }]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "media_id": "original_draft"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/cgi-bin/draft/add"))
+            .and(body_partial_json(serde_json::json!({
+                "articles": [{ "thumb_media_id": "existing_material" }]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "media_id": "reused_draft"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let temp_dir = TempDir::new().unwrap();
+        let article = temp_dir.path().join("article.md");
+        fs::write(temp_dir.path().join("cover.png"), b"fake cover").unwrap();
+        fs::write(&article, "---\ntitle: Mine\ncover: cover.png\n---\n# Hello\n").unwrap();
+
+        // wechat_pub_rs would send these to the real WeChat endpoint
+        let client = WeChatClient::new(
+            "wx1234567890abcdef".to_string(),
+            "0123456789abcdef0123456789abcdef".to_string(),
+        )
+        .await
+        .unwrap();
+        let api = WeChatApiClient::new(
+            "wx1234567890abcdef".to_string(),
+            "0123456789abcdef0123456789abcdef".to_string(),
+            &server.uri(),
+        )
+        .unwrap();
+        let uploader = DefaultEndpointUploader::new(Box::new(client), api);
+        let file = article.to_str().unwrap();
+
+        let mut options = ArticleOptions::default();
+        options.fields.original = true;
+        assert_eq!(uploader.upload(file, &options).await.unwrap(), "original_draft");
+
+        let options = ArticleOptions {
+            thumb_media_id: Some("existing_material".to_string()),
+            ..ArticleOptions::default()
+        };
+        assert_eq!(uploader.upload(file, &options).await.unwrap(), "reused_draft");
+        server.verify().await;
+    }
+
+    /// Stands in for wechat_pub_rs on an account that may not upload material
+    struct MaterialDeniedUploader;
+
+    #[async_trait::async_trait]
+    impl WeChatUploader for MaterialDeniedUploader {
+        async fn upload(&self, _file_path: &str, _options: &ArticleOptions) -> Result<String> {
+            Err(Error::from_wechat(wechat_pub_rs::WeChatError::from_api_response(
+                48001,
+                "api unauthorized",
+            )))
+        }
+
+        async fn refresh_token(&self) -> Result<String> {
+            Ok("token".to_string())
+        }
+
+        async fn content_checker(&self) -> Result<Box<dyn ContentChecker + '_>> {
+            Err(Error::wechat("not supported"))
+        }
+
+        async fn list_recent_drafts(&self, _limit: u32) -> Result<Vec<DraftSummary>> {
+            Ok(Vec::new())
+        }
+
+        async fn upload_body_image(&self, _image_path: &Path) -> Result<String> {
+            Err(Error::wechat("not supported"))
+        }
+    }
+
+    /// A cover refused by wechat_pub_rs is retried under `on_material_denied`
+    #[tokio::test]
+    async fn test_default_endpoint_uploader_applies_material_denied_policy() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/cgi-bin/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "mock_token",
+                "expires_in": 7200
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/cgi-bin/material/add_material"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "errcode": 48001,
+                "errmsg": "api unauthorized"
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/cgi-bin/draft/add"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "media_id": "coverless_draft"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let temp_dir = TempDir::new().unwrap();
+        let article = temp_dir.path().join("article.md");
+        fs::write(temp_dir.path().join("cover.png"), b"fake cover").unwrap();
+        fs::write(&article, "---\ntitle: Plain\ncover: cover.png\n---\n# Hello\n").unwrap();
+
+        let api = WeChatApiClient::new(
+            "wx1234567890abcdef".to_string(),
+            "0123456789abcdef0123456789abcdef".to_string(),
+            &server.uri(),
+        )
+        .unwrap();
+        let uploader = DefaultEndpointUploader::new(Box::new(MaterialDeniedUploader), api);
+        let file = article.to_str().unwrap();
+
+        let options = ArticleOptions {
+            on_material_denied: MaterialDenied::Fail,
+            ..ArticleOptions::default()
+        };
+        let err = uploader.upload(file, &options).await.unwrap_err();
+        assert!(err.is_api_unauthorized(), "{}", err);
+
+        let options = ArticleOptions::default();
+        assert_eq!(uploader.upload(file, &options).await.unwrap(), "coverless_draft");
+        server.verify().await;
+    }
+
+    #[test]
+    fn test_collect_markdown_files_matching_extensions() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for name in ["a.md", "b.markdown", "c.MDX", "d.txt", ".e.wx-upload.md"] {
+            fs::write(root.join(name), "# Post").unwrap();
         }
+        let names = |extensions: &[&str]| {
+            let extensions: Vec<String> = extensions.iter().map(|s| s.to_string()).collect();
+            let mut names: Vec<String> = collect_markdown_files_matching(root, false, &extensions)
+                .iter()
+                .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(names(DEFAULT_MARKDOWN_EXTENSIONS), vec!["a.md"]);
+        assert_eq!(names(&["md", "markdown", "mdx"]), vec!["a.md", "b.markdown", "c.MDX"]);
+        assert_eq!(names(&["mdx"]), vec!["c.MDX"]);
+
+        let extensions = default_markdown_extensions();
+        assert_eq!(unrecognized_extension_warning(&root.join("a.md"), &extensions), None);
+        let warning = unrecognized_extension_warning(&root.join("b.markdown"), &extensions).unwrap();
+        assert!(warning.starts_with("'.markdown' is not a recognized markdown extension (recognized: md);"));
+        let warning = unrecognized_extension_warning(&root.join("README"), &extensions).unwrap();
+        assert!(warning.starts_with("the file has no extension"), "{}", warning);
     }
-}
 
-/// Executes the WeChat upload operation
-async fn execute_wechat_upload(
-    client: &WeChatClient,
-    path: &Path,
-    verbose: bool,
-) -> Result<String> {
-    if verbose {
-        info!("Uploading file: {}", path.display());
-    } else {
-        FORMATTER.print_progress(&FORMATTER.format_file_operation("uploading", path));
+    #[tokio::test]
+    async fn test_filter_by_state() {
+        let temp_dir = TempDir::new().unwrap();
+        let write = |name: &str, content: &str| {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, content).unwrap();
+            path
+        };
+        let new = write("a.md", "# No frontmatter");
+        let empty = write("b.md", "---\npublished: \"\"\n---\nbody");
+        let draft = write("c.md", "---\npublished: draft\ndraft_id: d1\n---\nbody");
+        let published = write("d.md", "---\npublished: true\n---\nbody");
+        let broken = write("e.md", "---\ntitle: [unclosed\n---\nbody");
+        let files = vec![
+            new.clone(),
+            empty.clone(),
+            draft.clone(),
+            published.clone(),
+            broken.clone(),
+        ];
+        let options = ProcessOptions::default();
+
+        assert_eq!(
+            filter_by_state(files.clone(), PublicationStatus::Unpublished, &options).await,
+            vec![new, empty, broken.clone()]
+        );
+        assert_eq!(
+            filter_by_state(files.clone(), PublicationStatus::Draft, &options).await,
+            vec![draft, broken.clone()]
+        );
+        assert_eq!(
+            filter_by_state(files, PublicationStatus::Published, &options).await,
+            vec![published, broken]
+        );
     }
 
-    let path_str = path
-        .to_str()
-        .ok_or_else(|| Error::generic("Path contains invalid UTF-8"))?;
+    #[tokio::test]
+    async fn test_group_files_by_account_rejects_unknown_account() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("post.md");
+        fs::write(&path, "---\naccount: missing\n---\nbody").unwrap();
 
-    match client.upload(path_str).await {
-        Ok(draft_id) => {
-            if verbose {
-                info!("Successfully uploaded with draft ID: {}", draft_id);
-            } else {
-                FORMATTER.print_success(&FORMATTER.format_upload_success(path));
-            }
-            Ok(draft_id)
-        }
-        Err(e) => {
-            let error_msg = format!("WeChat upload failed: {}", e);
-            if verbose {
-                warn!("Failed to upload {}: {}", path.display(), error_msg);
-            } else {
-                FORMATTER.print_error(&FORMATTER.format_upload_failure(path));
-                eprintln!("Error: {}", error_msg);
-            }
-            Err(Error::wechat(error_msg))
-        }
+        let err = group_files_by_account(&[path], &config_with_accounts(&["main", "work"]))
+            .await
+            .unwrap_err();
+
+        assert!(err.is_config());
+        let message = err.to_string();
+        assert!(message.contains("'missing'"));
+        assert!(message.contains("post.md"));
+        assert!(message.contains("main, work"));
     }
-}
 
-/// Updates the frontmatter with published status after successful upload
-async fn update_published_status(path: &Path, verbose: bool) -> Result<()> {
-    update_frontmatter(path, |fm| {
-        fm.set_published("draft");
-        Ok(())
-    })
-    .await?;
+    #[tokio::test]
+    async fn test_group_files_by_account_expands_groups() {
+        let temp_dir = TempDir::new().unwrap();
+        let write = |name: &str, content: &str| {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, content).unwrap();
+            path
+        };
+        let regional = write("a.md", "---\naccounts: [\"@regional\", main]\n---\nbody");
+        let single = write("b.md", "---\naccount: \"@regional\"\n---\nbody");
+        let plain = write("c.md", "body");
 
-    if verbose {
-        info!(
-            "Updated frontmatter with draft status in: {}",
-            path.display()
+        let mut config = config_with_accounts(&["main", "north", "south"]);
+        config.account_groups.insert(
+            "regional".to_string(),
+            vec!["north".to_string(), "south".to_string()],
         );
-    }
 
-    Ok(())
-}
+        let groups = group_files_by_account(&[regional.clone(), single.clone(), plain.clone()], &config)
+            .await
+            .unwrap();
+        assert_eq!(
+            groups,
+            vec![
+                AccountGroup {
+                    accounts: Vec::new(),
+                    files: vec![plain.clone()],
+                },
+                AccountGroup {
+                    accounts: vec!["north".to_string(), "south".to_string(), "main".to_string()],
+                    files: vec![regional],
+                },
+                AccountGroup {
+                    accounts: vec!["north".to_string(), "south".to_string()],
+                    files: vec![single],
+                },
+            ]
+        );
 
-/// Resolves a cover image path relative to the markdown file and checks if it exists
-///
-/// # Arguments
-///
-/// * `markdown_file_path` - Path to the markdown file
-/// * `cover_filename` - Relative or absolute path to the cover image
-///
-/// # Returns
-///
-/// A tuple containing the resolved path and whether the file exists
-pub fn resolve_and_check_cover_path(
-    markdown_file_path: &Path,
-    cover_filename: &str,
-) -> (PathBuf, bool) {
-    let cover_path = if Path::new(cover_filename).is_absolute() {
-        PathBuf::from(cover_filename)
-    } else {
-        // If cover filename is relative, resolve it relative to the markdown file's directory
-        markdown_file_path
-            .parent()
-            .unwrap_or_else(|| Path::new("."))
-            .join(cover_filename)
-    };
+        // `--account @regional` applies to files without their own accounts
+        config.target_accounts = vec!["north".to_string(), "south".to_string()];
+        let groups = group_files_by_account(std::slice::from_ref(&plain), &config)
+            .await
+            .unwrap();
+        assert_eq!(groups[0].accounts, vec!["north", "south"]);
 
-    let exists = cover_path.exists();
-    (cover_path, exists)
-}
+        let missing = write("d.md", "---\naccounts: [\"@nowhere\"]\n---\nbody");
+        let err = group_files_by_account(&[missing], &config).await.unwrap_err();
+        assert!(err.is_config());
+        assert!(err.to_string().contains("'@nowhere'"));
+        assert!(err.to_string().contains("@regional"));
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
-    use tempfile::TempDir;
+        // An account given with --app-id takes every file, whatever it names
+        config.use_cli_account("wx_cli".to_string(), "secret".to_string());
+        let named = write("e.md", "---\naccount: north\n---\nbody");
+        let groups = group_files_by_account(&[plain, named], &config)
+            .await
+            .unwrap();
+        assert_eq!(groups.len(), 1);
+        assert!(groups[0].accounts.is_empty());
+        assert_eq!(groups[0].files.len(), 2);
+    }
 
     #[test]
     fn test_resolve_and_check_cover_path() {
@@ -490,6 +4112,104 @@ mod tests {
         let (resolved_path, exists) = resolve_and_check_cover_path(&md_file, "images/cover.png");
         assert_eq!(resolved_path, subdir_cover);
         assert!(exists);
+
+        // Test with a directory, which is not a usable cover
+        let (resolved_path, exists) = resolve_and_check_cover_path(&md_file, "images");
+        assert_eq!(resolved_path, images_dir);
+        assert!(!exists);
+        assert!(cover_is_directory(&md_file, "images", None));
+        assert!(!cover_is_directory(&md_file, "images/cover.png", None));
+        assert!(!cover_is_directory(&md_file, "missing.png", None));
+        assert_eq!(
+            resolve_cover_path_kind(&md_file, "images", None).1,
+            CoverPathKind::Directory
+        );
+        assert_eq!(
+            resolve_cover_path_kind(&md_file, "images/cover.png", None).1,
+            CoverPathKind::File
+        );
+        assert_eq!(
+            resolve_cover_path_kind(&md_file, "missing.png", None).1,
+            CoverPathKind::Missing
+        );
+    }
+
+    #[test]
+    fn test_select_cover_candidate_policies() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("covers").join("post");
+        fs::create_dir_all(dir.join("old")).unwrap();
+        let now = std::time::SystemTime::now();
+        for (name, age) in [("a.png", 30), ("b.jpg", 10), ("c.webp", 20), (".d.png", 0), ("e.txt", 0)] {
+            let file = fs::File::create(dir.join(name)).unwrap();
+            file.set_modified(now - std::time::Duration::from_secs(age)).unwrap();
+        }
+
+        assert_eq!(select_cover_candidate(&dir, CoverSelect::Newest), Some(dir.join("b.jpg")));
+        assert_eq!(select_cover_candidate(&dir, CoverSelect::First), Some(dir.join("a.png")));
+        let random = select_cover_candidate(&dir, CoverSelect::Random).unwrap();
+        assert!(["a.png", "b.jpg", "c.webp"].map(|name| dir.join(name)).contains(&random));
+
+        // Without images every policy falls through
+        let empty = dir.join("old");
+        for policy in [CoverSelect::Newest, CoverSelect::First, CoverSelect::Random] {
+            assert_eq!(select_cover_candidate(&empty, policy), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prepare_file_selects_cover_from_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("covers").join("post");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.png"), b"first").unwrap();
+        fs::write(dir.join("b.png"), b"second").unwrap();
+        let md_file = temp_dir.path().join("post.md");
+        fs::write(&md_file, "---\ntitle: Post\ncover: covers/post/\n---\n# Body\n").unwrap();
+        let options = ProcessOptions {
+            cover_select: CoverSelect::First,
+            ..Default::default()
+        };
+
+        let Prepared::Ready(prepared) = prepare_file(None, &md_file, true, &options).await.unwrap()
+        else {
+            panic!("expected the file to be ready");
+        };
+        assert_eq!(prepared.frontmatter.cover.as_deref(), Some("covers/post/a.png"));
+        assert_eq!(prepared.cover, CoverOutcome::Existing);
+
+        // The chosen file is recorded, so later runs use it directly
+        let (frontmatter, _) = crate::markdown::parse_markdown_file(&md_file).await.unwrap();
+        assert_eq!(frontmatter.cover.as_deref(), Some("covers/post/a.png"));
+        assert_eq!(select_directory_cover(&frontmatter, &md_file, &options), None);
+    }
+
+    #[tokio::test]
+    async fn test_prepare_file_with_cover_media_id_leaves_cover_alone() {
+        let temp_dir = TempDir::new().unwrap();
+        let md_file = temp_dir.path().join("part-2.md");
+        let content = "---\ntitle: Part 2\ncover: missing.png\ncover_media_id: SERIES_COVER\n---\n# Body\n";
+        fs::write(&md_file, content).unwrap();
+        let options = ProcessOptions {
+            require_cover: true,
+            ..Default::default()
+        };
+
+        // The missing cover file is not an error: the material replaces it
+        let Prepared::Ready(prepared) = prepare_file(None, &md_file, true, &options).await.unwrap()
+        else {
+            panic!("expected the file to be ready");
+        };
+        assert_eq!(prepared.cover, CoverOutcome::Material);
+        assert_eq!(fs::read_to_string(&md_file).unwrap(), content);
+
+        let (article_options, _source) =
+            prepare_article(&prepared.frontmatter, &prepared.body, &md_file, false, false, false, &options)
+                .await
+                .unwrap();
+        assert_eq!(article_options.thumb_media_id.as_deref(), Some("SERIES_COVER"));
+        assert_eq!(article_options.thumb, None);
+        assert!(article_options.show_cover);
     }
 
     #[test]
@@ -514,39 +4234,553 @@ mod tests {
 
         // Without OpenAI client, should return None
         let result = processor
-            .ensure_cover_image("content", &md_file, None)
+            .ensure_cover_image(&"content".into(), &md_file, None)
             .await
             .unwrap();
         assert!(result.is_none());
 
         let result = processor
-            .ensure_cover_image("content", &md_file, Some("cover.png"))
+            .ensure_cover_image(&"content".into(), &md_file, Some("cover.png"))
             .await
             .unwrap();
         assert!(result.is_none());
     }
 
+    #[test]
+    fn test_resolve_separate_thumb() {
+        let temp_dir = TempDir::new().unwrap();
+        let md_file = temp_dir.path().join("post.md");
+        let body = "Intro\n\n![first](images/first.png)\n![second](second.png)\n";
+
+        let mut frontmatter = Frontmatter::new();
+        frontmatter.set_cover("cover.png");
+        assert_eq!(resolve_separate_thumb(&frontmatter, body, &md_file, None), None);
+
+        // Same file as cover is uploaded once
+        frontmatter.set_thumb("./cover.png");
+        assert_eq!(resolve_separate_thumb(&frontmatter, body, &md_file, None), None);
+
+        frontmatter.set_thumb("thumb.png");
+        assert_eq!(
+            resolve_separate_thumb(&frontmatter, body, &md_file, None),
+            Some("thumb.png".to_string())
+        );
+
+        frontmatter.set_thumb(THUMB_FIRST_IMAGE);
+        assert_eq!(
+            resolve_separate_thumb(&frontmatter, body, &md_file, None),
+            Some("images/first.png".to_string())
+        );
+        assert_eq!(resolve_separate_thumb(&frontmatter, "no images", &md_file, None), None);
+    }
+
     #[tokio::test]
-    async fn test_process_directory_empty() {
+    async fn test_prepare_article_injects_cover_header() {
         let temp_dir = TempDir::new().unwrap();
+        let md_file = temp_dir.path().join("post.md");
 
-        // Create a mock WeChat client (this will fail in real usage without proper credentials)
-        // In a real test environment, we'd use dependency injection or mocking
-        let client =
-            wechat_pub_rs::WeChatClient::new("test_id".to_string(), "test_secret".to_string())
-                .await;
+        let mut frontmatter = Frontmatter::with_title("Post");
+        frontmatter.set_cover("cover.png");
+        frontmatter.set_thumb("thumb.png");
+        let process = ProcessOptions::default();
 
-        // This test mainly verifies the directory processing logic
-        match client {
-            Ok(client) => {
-                let result = process_directory(&client, None, temp_dir.path(), false).await;
-                // Should succeed with empty directory
-                assert!(result.is_ok());
-            }
-            Err(_) => {
-                // Expected to fail without real credentials, but the test structure is correct
-                // In integration tests, we'd use proper mocking
+        let (options, source) =
+            prepare_article(&frontmatter, "# Body\n", &md_file, false, false, false, &process)
+                .await
+                .unwrap();
+        assert_eq!(options.thumb.as_deref(), Some("thumb.png"));
+        assert!(!options.show_cover);
+
+        let temp_path = source.path().to_path_buf();
+        assert!(is_upload_temp_file(&temp_path));
+        let content = fs::read_to_string(&temp_path).unwrap();
+        assert!(content.contains("![](cover.png)\n\n# Body"));
+
+        drop(source);
+        assert!(!temp_path.exists());
+
+        // Without a separate thumbnail the article is uploaded as-is
+        frontmatter.thumb = None;
+        let (options, source) =
+            prepare_article(&frontmatter, "# Body\n", &md_file, false, false, false, &process)
+                .await
+                .unwrap();
+        assert_eq!(options.thumb, None);
+        assert!(options.show_cover);
+        assert_eq!(options.fields.title.as_deref(), Some("Post"));
+        assert_eq!(source.path(), md_file.as_path());
+    }
+
+    #[test]
+    fn test_resolve_cover_path_in_root() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let md_file = root.join("posts").join("2024").join("post.md");
+        fs::create_dir_all(md_file.parent().unwrap()).unwrap();
+        fs::create_dir_all(root.join("assets")).unwrap();
+        fs::write(root.join("assets").join("cover.png"), "fake image data").unwrap();
+
+        // Root-relative paths resolve under the content root
+        let (resolved, exists) =
+            resolve_cover_path_in_root(&md_file, "/assets/cover.png", Some(root));
+        assert_eq!(resolved, root.join("assets").join("cover.png"));
+        assert!(exists);
+
+        let (resolved, exists) =
+            resolve_cover_path_in_root(&md_file, "/assets/new.png", Some(root));
+        assert_eq!(resolved, root.join("assets").join("new.png"));
+        assert!(!exists);
+
+        // Relative paths stay relative to the article
+        let (resolved, _) = resolve_cover_path_in_root(&md_file, "cover.png", Some(root));
+        assert_eq!(resolved, md_file.parent().unwrap().join("cover.png"));
+
+        // An existing absolute path outside the root is used as is
+        let outside = TempDir::new().unwrap();
+        let generated = outside.path().join("generated.png");
+        fs::write(&generated, "fake image data").unwrap();
+        let generated = generated.display().to_string();
+        let (resolved, exists) = resolve_cover_path_in_root(&md_file, &generated, Some(root));
+        assert_eq!(resolved, PathBuf::from(&generated));
+        assert!(exists);
+
+        // Without a root, paths starting with / are filesystem-absolute
+        let (resolved, exists) = resolve_cover_path_in_root(&md_file, "/assets/cover.png", None);
+        assert_eq!(resolved, PathBuf::from("/assets/cover.png"));
+        assert!(!exists);
+    }
+
+    #[tokio::test]
+    async fn test_prepare_article_resolves_root_cover() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let md_file = root.join("posts").join("post.md");
+        fs::create_dir_all(md_file.parent().unwrap()).unwrap();
+
+        let mut frontmatter = Frontmatter::with_title("Post");
+        frontmatter.set_cover("/assets/cover.png");
+        let cover = root.join("assets").join("cover.png").display().to_string();
+        let process = ProcessOptions {
+            content_root: Some(root.to_path_buf()),
+            ..ProcessOptions::default()
+        };
+
+        // The upload copy names the cover by its absolute path
+        let (options, source) =
+            prepare_article(&frontmatter, "# Body\n", &md_file, false, false, false, &process)
+                .await
+                .unwrap();
+        assert_eq!(options.thumb, None);
+        assert!(is_upload_temp_file(source.path()));
+        let (uploaded, _) = crate::markdown::parse_markdown_file(source.path()).await.unwrap();
+        assert_eq!(uploaded.cover.as_deref(), Some(cover.as_str()));
+
+        // A root-relative thumbnail is passed by its absolute path too
+        frontmatter.set_thumb("/assets/thumb.png");
+        let (options, _) =
+            prepare_article(&frontmatter, "# Body\n", &md_file, false, false, false, &process)
+                .await
+                .unwrap();
+        let thumb = root.join("assets").join("thumb.png").display().to_string();
+        assert_eq!(options.thumb.as_deref(), Some(thumb.as_str()));
+
+        // Without a content root the article is uploaded as-is
+        frontmatter.thumb = None;
+        let process = ProcessOptions::default();
+        let (_, source) =
+            prepare_article(&frontmatter, "# Body\n", &md_file, false, false, false, &process)
+                .await
+                .unwrap();
+        assert_eq!(source.path(), md_file.as_path());
+    }
+
+    #[tokio::test]
+    async fn test_prepare_file_rejects_ai_overrides_without_credentials() {
+        let temp_dir = TempDir::new().unwrap();
+        let md_file = temp_dir.path().join("post.md");
+        fs::write(&md_file, "---\ntitle: Post\nimage_model: gpt-image-1\n---\n# Body\n").unwrap();
+
+        let err = prepare_file(None, &md_file, true, &ProcessOptions::default())
+            .await
+            .unwrap_err();
+        assert!(err.is_config());
+        assert!(err.to_string().contains("post.md"));
+        assert!(err.to_string().contains("no AI provider is configured"));
+
+        fs::write(&md_file, "---\ntitle: Post\nai_provider: gemini\n---\n# Body\n").unwrap();
+        use crate::providers::ProviderConfig;
+        let client = UniversalAIClient::new(
+            ProviderConfig::OpenAI {
+                api_key: "test-key".to_string(),
+                base_url: None,
+            },
+            None,
+        );
+        let err = prepare_file(Some(&client), &md_file, true, &ProcessOptions::default())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Configuration error: {}: ai_provider 'gemini' has no credentials configured; \
+                 the configured AI provider is OpenAI",
+                md_file.display()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_save_prepared_frontmatter_keeps_concurrent_edits() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("post.md");
+        let options = ProcessOptions::default();
+        let parse_options = options.parse_options();
+        fs::write(&path, "---\ntitle: Post\n---\nDraft body\n").unwrap();
+
+        let prepare = async || {
+            let fingerprint = FileFingerprint::of(&path, &parse_options).await.ok();
+            let (parsed, body) = parse_markdown_file_with(&path, &parse_options).await.unwrap();
+            let mut frontmatter = parsed.clone();
+            frontmatter.set_cover("post_cover.png");
+            (fingerprint, parsed, frontmatter, body)
+        };
+
+        // Unchanged on disk: rewritten from what was parsed
+        let (fingerprint, parsed, frontmatter, body) = prepare().await;
+        save_prepared_frontmatter(&path, &parsed, &frontmatter, &body, fingerprint, &options)
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "---\ntitle: Post\ncover: post_cover.png\n---\nDraft body\n"
+        );
+
+        // Edited while the cover was generated: only the cover is applied
+        fs::write(&path, "---\ntitle: Post\n---\nDraft body\n").unwrap();
+        let (fingerprint, parsed, frontmatter, body) = prepare().await;
+        fs::write(&path, "---\ntitle: Edited\n---\nFinal body\n").unwrap();
+        save_prepared_frontmatter(&path, &parsed, &frontmatter, &body, fingerprint, &options)
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "---\ntitle: Edited\ncover: post_cover.png\n---\nFinal body\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_estimate_ai_cost_counts_missing_covers() {
+        use crate::budget::AiPrices;
+        use crate::providers::ProviderConfig;
+
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        fs::write(dir.join("a.md"), "---\ntitle: A\n---\nA\n").unwrap();
+        fs::write(dir.join("b.md"), "---\ntitle: B\npublished: true\n---\nB\n").unwrap();
+        fs::write(dir.join("c.md"), "---\ntitle: C\ncover: c.png\n---\nC\n").unwrap();
+        fs::write(dir.join("c.png"), b"png").unwrap();
+        fs::write(
+            dir.join("d.md"),
+            "---\ntitle: D\ncover_variations: 3\nimage_model: dall-e-2\n---\nD\n",
+        )
+        .unwrap();
+        fs::write(dir.join("e.md"), "---\ntitle: E\ncover: none\n---\nE\n").unwrap();
+        let files: Vec<PathBuf> = ["a.md", "b.md", "c.md", "d.md", "e.md"]
+            .iter()
+            .map(|name| dir.join(name))
+            .collect();
+
+        let client = UniversalAIClient::new(
+            ProviderConfig::OpenAI {
+                api_key: "test-key".to_string(),
+                base_url: None,
+            },
+            None,
+        );
+        let options = ProcessOptions::default();
+
+        // dall-e-3 for a.md, three dall-e-2 candidates for d.md, and a scene each
+        let estimate = estimate_ai_cost(&files, false, &client, &options).await;
+        assert_eq!((estimate.files, estimate.images, estimate.descriptions), (2, 4, 2));
+        assert!((estimate.cost - 0.142).abs() < 1e-9, "{}", estimate.cost);
+
+        let estimate = estimate_ai_cost(&files, true, &client, &options).await;
+        assert_eq!(estimate.files, 3);
+
+        let client = client.with_ai_scene_text(false).with_prices(AiPrices {
+            image: Some(0.05),
+            ..AiPrices::default()
+        });
+        let estimate = estimate_ai_cost(&files, false, &client, &options).await;
+        assert_eq!((estimate.images, estimate.descriptions), (4, 0));
+        assert!((estimate.cost - 0.2).abs() < 1e-9, "{}", estimate.cost);
+    }
+
+    #[tokio::test]
+    async fn test_prepare_file_applies_theme_fallback() {
+        let temp_dir = TempDir::new().unwrap();
+        let md_file = temp_dir.path().join("post.md");
+        let original = "---\ntitle: Post\ntheme: nope\ncode: github\n---\n# Body\n";
+        fs::write(&md_file, original).unwrap();
+
+        // error: the file fails as before
+        let err = prepare_file(None, &md_file, true, &ProcessOptions::default())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Invalid theme 'nope'"));
+
+        // skip: the file is skipped with the validation message as reason
+        let skip = ProcessOptions {
+            theme_fallback: ThemeFallback::Skip,
+            ..Default::default()
+        };
+        match prepare_file(None, &md_file, true, &skip).await.unwrap() {
+            Prepared::Skipped { reason } => assert!(reason.contains("Invalid theme 'nope'")),
+            other => panic!("expected a skip, got {:?}", other),
+        }
+
+        // default: the configured theme is used for the upload only
+        let default = ProcessOptions {
+            theme_fallback: ThemeFallback::Default,
+            default_theme: Some("lapis".to_string()),
+            ..Default::default()
+        };
+        let Prepared::Ready(prepared) = prepare_file(None, &md_file, true, &default).await.unwrap()
+        else {
+            panic!("expected the file to be prepared");
+        };
+        assert!(prepared.style_replaced);
+        assert_eq!(prepared.frontmatter.theme.as_deref(), Some("lapis"));
+        assert_eq!(prepared.frontmatter.code.as_deref(), Some("github"));
+        assert_eq!(fs::read_to_string(&md_file).unwrap(), original);
+
+        // The upload reads the replaced theme from a temporary copy
+        let (_, source) = prepare_article(
+            &prepared.frontmatter,
+            &prepared.body,
+            &md_file,
+            prepared.style_replaced,
+            false,
+            prepared.sidecar,
+            &default,
+        )
+        .await
+        .unwrap();
+        assert!(is_upload_temp_file(source.path()));
+        let uploaded = fs::read_to_string(source.path()).unwrap();
+        assert!(uploaded.contains("theme: lapis"));
+        assert!(uploaded.contains("# Body"));
+    }
+
+    #[tokio::test]
+    async fn test_prepare_file_applies_theme_override() {
+        let temp_dir = TempDir::new().unwrap();
+        let md_file = temp_dir.path().join("post.md");
+        let original = "---\ntitle: Post\ntheme: default\n---\n# Body\n";
+        fs::write(&md_file, original).unwrap();
+
+        let options = ProcessOptions {
+            theme_overrides: HashMap::from([(md_file.clone(), "lapis".to_string())]),
+            ..Default::default()
+        };
+        let Prepared::Ready(prepared) = prepare_file(None, &md_file, true, &options).await.unwrap()
+        else {
+            panic!("expected the file to be prepared");
+        };
+        assert!(prepared.style_replaced);
+        assert_eq!(prepared.frontmatter.theme.as_deref(), Some("lapis"));
+        assert_eq!(fs::read_to_string(&md_file).unwrap(), original);
+
+        // Other files keep their theme
+        let other = temp_dir.path().join("other.md");
+        fs::write(&other, original).unwrap();
+        let Prepared::Ready(prepared) = prepare_file(None, &other, true, &options).await.unwrap()
+        else {
+            panic!("expected the file to be prepared");
+        };
+        assert!(!prepared.style_replaced);
+        assert_eq!(prepared.frontmatter.theme.as_deref(), Some("default"));
+    }
+
+    #[test]
+    fn test_transform_pipeline_orders_builtin_and_custom_stages() {
+        let toc = FnTransform::new("toc", |_: &ArticleContext<'_>, body: String| {
+            Ok(format!("[Top](#top)\n\n{}", body))
+        });
+        let options = ProcessOptions {
+            obsidian: true,
+            anchor_links: AnchorLinks::Text,
+            custom_transforms: TransformPipeline::new(vec![Arc::new(toc)]),
+            ..Default::default()
+        };
+        let path = Path::new("notes/post.md");
+        let body = "See [[Other Note]] and [setup](#setup).\n";
+        let run = |frontmatter: &Frontmatter| {
+            let ctx = ArticleContext {
+                path,
+                frontmatter,
+                verbose: false,
+            };
+            let pipeline = options.transform_pipeline(frontmatter)?;
+            Ok::<_, Error>((pipeline.names().join(","), pipeline.apply(&ctx, body.to_string())?))
+        };
+
+        // Built-ins the options turn on, then the custom transforms
+        let mut frontmatter = Frontmatter::new();
+        let (names, uploaded) = run(&frontmatter).unwrap();
+        assert_eq!(names, "obsidian,anchor-links,toc");
+        assert_eq!(uploaded, "[Top](#top)\n\nSee Other Note and setup.\n");
+
+        // A list reorders them: the link toc adds is unlinked after it
+        frontmatter.transforms = Some(vec!["toc".into(), "anchor-links".into(), "obsidian".into()]);
+        let (names, uploaded) = run(&frontmatter).unwrap();
+        assert_eq!(names, "toc,anchor-links,obsidian");
+        assert_eq!(uploaded, "Top\n\nSee Other Note and setup.\n");
+
+        // An empty list uploads the body as written
+        frontmatter.transforms = Some(Vec::new());
+        assert_eq!(run(&frontmatter).unwrap(), (String::new(), body.to_string()));
+
+        frontmatter.transforms = Some(vec!["tco".into()]);
+        let err = run(&frontmatter).unwrap_err().to_string();
+        assert!(err.contains("unknown body transform 'tco'"), "{}", err);
+        assert!(err.contains("obsidian, anchor-links, toc"), "{}", err);
+    }
+
+    #[test]
+    fn test_article_fields_from_frontmatter() {
+        let content = "---\ntitle: \" Post \"\nauthor: Jane\ndescription: Summary\n\
+                       content_source_url: https://example.com/post\nneed_open_comment: true\n\
+                       only_fans_can_comment: true\npic_crop_235_1: 0_0.2_1_0.8\n\
+                       pic_crop_1_1: 0.2_0_0.8_1\noriginal: true\n\
+                       original_author: J. Doe\n---\nBody";
+        let (frontmatter, _) = crate::markdown::parse_markdown(content).unwrap();
+
+        assert_eq!(
+            ArticleFields::from(&frontmatter),
+            ArticleFields {
+                title: Some("Post".to_string()),
+                author: Some("Jane".to_string()),
+                digest: Some("Summary".to_string()),
+                content_source_url: Some("https://example.com/post".to_string()),
+                need_open_comment: true,
+                only_fans_can_comment: true,
+                original: true,
+                original_author: Some("J. Doe".to_string()),
+                pic_crop_235_1: Some("0_0.2_1_0.8".to_string()),
+                pic_crop_1_1: Some("0.2_0_0.8_1".to_string()),
             }
+        );
+
+        // Nothing set: every field falls back
+        assert_eq!(ArticleFields::from(&Frontmatter::new()), ArticleFields::default());
+    }
+
+    #[test]
+    fn test_draft_article_serialization() {
+        let fields = ArticleFields {
+            title: Some("Post".to_string()),
+            author: Some("Jane".to_string()),
+            digest: Some("Summary".to_string()),
+            content_source_url: Some("https://example.com/post".to_string()),
+            need_open_comment: true,
+            only_fans_can_comment: false,
+            original: false,
+            original_author: None,
+            pic_crop_235_1: Some("0_0.2_1_0.8".to_string()),
+            pic_crop_1_1: None,
+        };
+        let article = DraftArticle::new(
+            &fields,
+            "<p>Hi</p>".to_string(),
+            "thumb_id".to_string(),
+            false,
+        );
+
+        assert_eq!(
+            serde_json::to_value(&article).unwrap(),
+            serde_json::json!({
+                "title": "Post",
+                "author": "Jane",
+                "digest": "Summary",
+                "content": "<p>Hi</p>",
+                "content_source_url": "https://example.com/post",
+                "thumb_media_id": "thumb_id",
+                "show_cover_pic": 0,
+                "need_open_comment": 1,
+                "only_fans_can_comment": 0,
+                "pic_crop_235_1": "0_0.2_1_0.8"
+            })
+        );
+
+        let defaults =
+            DraftArticle::new(&ArticleFields::default(), String::new(), "id".to_string(), true);
+        assert_eq!(defaults.title, "Untitled");
+        assert_eq!(defaults.author, "Anonymous");
+        assert_eq!(defaults.digest, "");
+        assert_eq!(defaults.show_cover_pic, 1);
+        assert_eq!(defaults.is_original, None);
+
+        // The original declaration names the author unless told otherwise
+        let original = ArticleFields {
+            original: true,
+            ..fields
+        };
+        let article = DraftArticle::new(&original, String::new(), "id".to_string(), true);
+        let json = serde_json::to_value(&article).unwrap();
+        assert_eq!(json["is_original"], 1);
+        assert_eq!(json["original_author"], "Jane");
+    }
+
+    fn draft(media_id: &str, title: &str, update_time: u64) -> DraftSummary {
+        DraftSummary {
+            media_id: media_id.to_string(),
+            title: title.to_string(),
+            update_time,
         }
     }
+
+    #[test]
+    fn test_draft_index_prefers_newest_draft() {
+        let index = DraftIndex::new(&[
+            draft("old", "Release Notes", 100),
+            draft("new", " Release Notes ", 200),
+            draft("older", "Release Notes", 50),
+            draft("untitled", "", 300),
+        ]);
+
+        assert_eq!(index.len(), 1);
+        assert_eq!(index.find("Release Notes"), Some("new"));
+        assert_eq!(index.find("Other"), None);
+
+        let mut frontmatter = Frontmatter::default();
+        assert_eq!(index.find_for(&frontmatter), None);
+        frontmatter.title = Some("Release Notes".to_string());
+        assert_eq!(index.find_for(&frontmatter), Some("new"));
+    }
+
+    #[tokio::test]
+    async fn test_list_drafts_paged() {
+        let box_size = 45;
+        let requests = std::sync::Mutex::new(Vec::new());
+        let fetch = |offset: u32, count: u32| {
+            requests.lock().unwrap().push((offset, count));
+            let end = (offset + count).min(box_size);
+            async move {
+                Ok((offset..end)
+                    .map(|i| draft(&format!("media_{}", i), "", 0))
+                    .collect())
+            }
+        };
+
+        let drafts = list_drafts_paged(100, fetch).await.unwrap();
+        assert_eq!(drafts.len(), 45);
+        assert_eq!(*requests.lock().unwrap(), vec![(0, 20), (20, 20), (40, 20)]);
+
+        requests.lock().unwrap().clear();
+        let drafts = list_drafts_paged(25, fetch).await.unwrap();
+        assert_eq!(drafts.len(), 25);
+        assert_eq!(drafts[24].media_id, "media_24");
+        assert_eq!(*requests.lock().unwrap(), vec![(0, 20), (20, 5)]);
+    }
 }
@@ -0,0 +1,641 @@
+//! Raw WeChat Official Account API client
+//!
+//! `wechat_pub_rs` hardcodes the `api.weixin.qq.com` host for token requests,
+//! which makes it impossible to point the uploader at a mock server or an
+//! API gateway. This module reimplements the handful of endpoints the upload
+//! flow needs (token, body image upload, cover material upload, draft creation
+//! and listing) against a configurable base URL, while reusing the markdown parser, theme
+//! renderer from `wechat_pub_rs`.
+
+use crate::content_check::{ContentChecker, MSG_SEC_CHECK_PATH, SecCheckResponse};
+use crate::error::{API_UNAUTHORIZED_ERRCODE, Error, INVALID_MEDIA_ID_ERRCODE, Result};
+use crate::models::MaterialDenied;
+use crate::inline_images::BodyImageCache;
+use crate::output::{FORMATTER, OutputFormatter};
+use crate::rate_limit::retry_after_header;
+use crate::wechat::{
+    ArticleOptions, DraftArticle, DraftSummary, WeChatUploader, list_drafts_paged,
+    no_cover_thumb_error,
+};
+use reqwest::multipart;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{debug, info};
+use wechat_pub_rs::http::{
+    AccessTokenResponse, DraftResponse, ImageUploadResponse, MaterialUploadResponse,
+    WeChatResponse,
+};
+use wechat_pub_rs::markdown::{MarkdownContent, MarkdownParser};
+use wechat_pub_rs::theme::ThemeManager;
+
+/// Default base URL of the WeChat Official Account API
+pub const DEFAULT_WECHAT_BASE_URL: &str = "https://api.weixin.qq.com";
+
+/// Theme articles without a `theme` are rendered with
+pub const DEFAULT_THEME: &str = "default";
+
+/// Code highlighter articles without a `code` are rendered with
+pub const DEFAULT_CODE_HIGHLIGHTER: &str = "vscode";
+
+/// Seconds subtracted from the token lifetime so it is refreshed before expiry
+const TOKEN_EXPIRY_BUFFER_SECS: u64 = 300;
+
+/// Cached access token together with its expiry instant
+#[derive(Debug, Clone)]
+struct CachedToken {
+    token: String,
+    expires_at: Instant,
+}
+
+/// Response of the `draft/batchget` endpoint
+#[derive(Debug, Deserialize)]
+struct DraftBatchResponse {
+    #[serde(default)]
+    item: Vec<DraftBatchItem>,
+}
+
+/// One draft of a `draft/batchget` response
+#[derive(Debug, Deserialize)]
+struct DraftBatchItem {
+    media_id: String,
+    #[serde(default)]
+    update_time: u64,
+    content: DraftBatchContent,
+}
+
+/// Articles of a draft, without their HTML when requested with `no_content`
+#[derive(Debug, Deserialize)]
+struct DraftBatchContent {
+    #[serde(default)]
+    news_item: Vec<DraftBatchArticle>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DraftBatchArticle {
+    #[serde(default)]
+    title: String,
+}
+
+/// WeChat API client that talks to a configurable base URL
+pub struct WeChatApiClient {
+    app_id: String,
+    app_secret: String,
+    base_url: String,
+    http_client: reqwest::Client,
+    token: RwLock<Option<CachedToken>>,
+    body_images: BodyImageCache,
+    parser: MarkdownParser,
+    theme_manager: ThemeManager,
+    material_denied: AtomicBool,
+}
+
+impl WeChatApiClient {
+    /// Creates a new client for the given credentials and base URL
+    ///
+    /// # Arguments
+    ///
+    /// * `app_id` - WeChat application ID
+    /// * `app_secret` - WeChat application secret
+    /// * `base_url` - API base URL, e.g. `https://api.weixin.qq.com` or a mock server
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the base URL is not an http(s) URL or the HTTP
+    /// client cannot be built
+    pub fn new(app_id: String, app_secret: String, base_url: &str) -> Result<Self> {
+        let base_url = normalize_base_url(base_url)?;
+
+        let http_client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(60))
+            .build()?;
+
+        Ok(Self {
+            app_id,
+            app_secret,
+            base_url,
+            http_client,
+            token: RwLock::new(None),
+            body_images: BodyImageCache::new(),
+            parser: MarkdownParser::new(),
+            theme_manager: ThemeManager::new(),
+            material_denied: AtomicBool::new(false),
+        })
+    }
+
+    /// Returns the base URL used for API requests
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Returns a valid access token, fetching a new one if the cache is empty or expired
+    pub async fn access_token(&self) -> Result<String> {
+        if let Some(cached) = self.token.read().await.as_ref()
+            && cached.expires_at > Instant::now()
+        {
+            return Ok(cached.token.clone());
+        }
+
+        self.refresh_token().await
+    }
+
+    /// Fetches a new access token, replacing any cached one
+    pub async fn refresh_token(&self) -> Result<String> {
+        let mut guard = self.token.write().await;
+
+        let url = format!("{}/cgi-bin/token", self.base_url);
+        debug!("Requesting WeChat access token from {}", url);
+
+        let response = self
+            .http_client
+            .get(&url)
+            .query(&[
+                ("grant_type", "client_credential"),
+                ("appid", self.app_id.as_str()),
+                ("secret", self.app_secret.as_str()),
+            ])
+            .send()
+            .await?;
+
+        let token: AccessTokenResponse = parse_response(response).await?;
+        let lifetime = token.expires_in.saturating_sub(TOKEN_EXPIRY_BUFFER_SECS);
+
+        *guard = Some(CachedToken {
+            token: token.access_token.clone(),
+            expires_at: Instant::now() + Duration::from_secs(lifetime),
+        });
+
+        Ok(token.access_token)
+    }
+
+    /// Uploads an image used inside the article body and returns its WeChat URL
+    ///
+    /// An image with the same content as one uploaded before by this client
+    /// is not uploaded again.
+    pub async fn upload_body_image(&self, image_path: &Path) -> Result<String> {
+        self.body_images
+            .get_or_upload(image_path, || self.upload_image(image_path))
+            .await
+    }
+
+    /// Uploads a body image with the `media/uploadimg` endpoint
+    async fn upload_image(&self, image_path: &Path) -> Result<String> {
+        let access_token = self.access_token().await?;
+        let url = format!("{}/cgi-bin/media/uploadimg", self.base_url);
+
+        let response = self
+            .http_client
+            .post(&url)
+            .query(&[("access_token", access_token.as_str())])
+            .multipart(image_form(image_path).await?)
+            .send()
+            .await?;
+
+        let uploaded: ImageUploadResponse = parse_response(response).await?;
+        Ok(uploaded.url)
+    }
+
+    /// Uploads a cover image as permanent material and returns its media ID
+    pub async fn upload_cover_material(&self, image_path: &Path) -> Result<String> {
+        let access_token = self.access_token().await?;
+        let url = format!("{}/cgi-bin/material/add_material", self.base_url);
+
+        let response = self
+            .http_client
+            .post(&url)
+            .query(&[("access_token", access_token.as_str()), ("type", "image")])
+            .multipart(image_form(image_path).await?)
+            .send()
+            .await?;
+
+        let uploaded: MaterialUploadResponse = parse_response(response).await?;
+        Ok(uploaded.media_id)
+    }
+
+    /// Creates a draft containing the given articles and returns the draft media ID
+    pub async fn add_draft(&self, articles: Vec<DraftArticle>) -> Result<String> {
+        let access_token = self.access_token().await?;
+        let url = format!("{}/cgi-bin/draft/add", self.base_url);
+
+        let response = self
+            .http_client
+            .post(&url)
+            .query(&[("access_token", access_token.as_str())])
+            .json(&serde_json::json!({ "articles": articles }))
+            .send()
+            .await?;
+
+        let draft: DraftResponse = parse_response(response).await?;
+        Ok(draft.media_id)
+    }
+
+    /// Lists `count` drafts starting at `offset`, without their article HTML
+    pub async fn batchget_drafts(&self, offset: u32, count: u32) -> Result<Vec<DraftSummary>> {
+        let access_token = self.access_token().await?;
+        let url = format!("{}/cgi-bin/draft/batchget", self.base_url);
+
+        let response = self
+            .http_client
+            .post(&url)
+            .query(&[("access_token", access_token.as_str())])
+            .json(&serde_json::json!({ "offset": offset, "count": count, "no_content": 1 }))
+            .send()
+            .await?;
+
+        let batch: DraftBatchResponse = parse_response(response).await?;
+        Ok(batch
+            .item
+            .into_iter()
+            .map(|draft| DraftSummary {
+                title: draft
+                    .content
+                    .news_item
+                    .into_iter()
+                    .next()
+                    .map(|article| article.title)
+                    .unwrap_or_default(),
+                media_id: draft.media_id,
+                update_time: draft.update_time,
+            })
+            .collect())
+    }
+
+    /// Checks text with the content security endpoint; `Ok(false)` means it was flagged
+    pub async fn msg_sec_check(&self, text: &str) -> Result<bool> {
+        let access_token = self.access_token().await?;
+        let url = format!("{}{}", self.base_url, MSG_SEC_CHECK_PATH);
+
+        let response = self
+            .http_client
+            .post(&url)
+            .query(&[("access_token", access_token.as_str())])
+            .json(&serde_json::json!({ "content": text }))
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::wechat(format!(
+                "WeChat API request failed with status {}",
+                status
+            )));
+        }
+
+        let body: SecCheckResponse = response.json().await?;
+        body.into_verdict()
+    }
+
+    /// Uploads a markdown file as a WeChat draft
+    ///
+    /// Mirrors the `wechat_pub_rs` upload flow: local body images are uploaded
+    /// (once per distinct content) and rewritten, the thumbnail
+    /// (`options.thumb` or the frontmatter cover) is uploaded as permanent
+    /// material unless `options.thumb_media_id` names an existing one, and the
+    /// content is rendered with the requested theme. If
+    /// WeChat rejects the original declaration, e.g. because the account is
+    /// not eligible for it, the draft is created again without it. If the
+    /// account may not upload permanent material, the draft is created without
+    /// a thumbnail unless `options.on_material_denied` says to fail.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file has no cover (or opts out of it without a
+    /// thumbnail in `options`), references a missing file,
+    /// uses an unknown theme, or any API call fails
+    pub async fn upload_markdown(
+        &self,
+        markdown_path: &Path,
+        options: &ArticleOptions,
+    ) -> Result<String> {
+        if options.cover_disabled && options.thumb.is_none() {
+            return Err(no_cover_thumb_error());
+        }
+
+        let mut content = self
+            .parser
+            .parse_file(markdown_path)
+            .await
+            .map_err(|e| Error::markdown_parse(markdown_path, e.to_string()))?;
+
+        let base_dir = markdown_path.parent().unwrap_or_else(|| Path::new("."));
+
+        let mut url_mapping = HashMap::new();
+        for image in content.images.iter().filter(|image| image.is_local) {
+            let image_path = base_dir.join(&image.original_url);
+            if !image_path.exists() {
+                return Err(Error::file_not_found(image_path.display().to_string()));
+            }
+            let wechat_url = self.upload_body_image(&image_path).await?;
+            url_mapping.insert(image.original_url.clone(), wechat_url);
+        }
+        content
+            .replace_image_urls(&url_mapping)
+            .map_err(Error::from_wechat)?;
+
+        let thumb_media_id = match &options.thumb_media_id {
+            Some(media_id) => media_id.clone(),
+            None => self.upload_thumb(markdown_path, base_dir, &content, options).await?,
+        };
+
+        let html = self.render(&content)?;
+        let mut article = build_article(&content, html, thumb_media_id, options);
+        if article.thumb_media_id.is_empty() {
+            article.show_cover_pic = 0;
+        }
+
+        let draft_id = match self.add_draft(vec![article.clone()]).await {
+            Err(e) if article.is_original.is_some() && rejects_declaration(&e) => {
+                let warning = format!(
+                    "original declaration rejected, uploading without it: {}",
+                    e.display_chain()
+                );
+                FORMATTER.print_warning(&format!("{}: {}", markdown_path.display(), warning));
+                options.warnings.record(&options.article, warning);
+                article.is_original = None;
+                article.original_author = None;
+                self.add_draft(vec![article]).await
+            }
+            result => result,
+        }
+        .map_err(|e| match &options.thumb_media_id {
+            Some(media_id) if e.wechat_errcode() == Some(INVALID_MEDIA_ID_ERRCODE) => {
+                Error::wechat_with_source(
+                    format!(
+                        "cover_media_id '{}' names no permanent material of this account",
+                        media_id
+                    ),
+                    e,
+                )
+            }
+            _ => e,
+        })?;
+        info!("Created WeChat draft {} via {}", draft_id, self.base_url);
+        Ok(draft_id)
+    }
+
+    /// Uploads the thumbnail (`options.thumb` or the frontmatter cover) as
+    /// permanent material and returns its media ID
+    ///
+    /// The media ID is empty if the account may not upload permanent material
+    /// and `options.on_material_denied` does not say to fail.
+    async fn upload_thumb(
+        &self,
+        markdown_path: &Path,
+        base_dir: &Path,
+        content: &MarkdownContent,
+        options: &ArticleOptions,
+    ) -> Result<String> {
+        let thumb = options
+            .thumb
+            .clone()
+            .or_else(|| content.cover.clone())
+            .ok_or_else(|| {
+                Error::wechat("Cover image is required in frontmatter for WeChat drafts")
+            })?;
+        let thumb_path = base_dir.join(&thumb);
+        if !thumb_path.exists() {
+            return Err(Error::file_not_found(thumb_path.display().to_string()));
+        }
+        match self.upload_cover_material(&thumb_path).await {
+            Err(e) if e.is_api_unauthorized() => {
+                self.explain_material_denied(options.on_material_denied);
+                if options.on_material_denied == MaterialDenied::Fail {
+                    return Err(e);
+                }
+                let warning = "cover not uploaded: the account may not upload permanent material; \
+                               creating the draft without it";
+                FORMATTER.print_file_warning(markdown_path, None, warning);
+                options.warnings.record(&options.article, warning);
+                Ok(String::new())
+            }
+            result => result,
+        }
+    }
+
+    /// Explains once per client why covers cannot be uploaded to this account
+    fn explain_material_denied(&self, policy: MaterialDenied) {
+        if self.material_denied.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        let consequence = match policy {
+            MaterialDenied::Skip => {
+                "Drafts are created without a cover (on_material_denied: skip); \
+                 set on_material_denied: fail to stop instead"
+            }
+            MaterialDenied::Fail => "Uploads with a cover fail (on_material_denied: fail)",
+        };
+        FORMATTER.print_warning(&format!(
+            "WeChat refused the cover with errcode {}: subscription accounts and unverified \
+             accounts may not upload permanent material, which covers are. {}.",
+            API_UNAUTHORIZED_ERRCODE, consequence
+        ));
+    }
+
+    /// Renders markdown content to themed HTML
+    fn render(&self, content: &MarkdownContent) -> Result<String> {
+        render_content(&self.theme_manager, content)
+    }
+}
+
+#[async_trait::async_trait]
+impl WeChatUploader for WeChatApiClient {
+    async fn upload(&self, file_path: &str, options: &ArticleOptions) -> Result<String> {
+        self.upload_markdown(Path::new(file_path), options).await
+    }
+
+    async fn refresh_token(&self) -> Result<String> {
+        WeChatApiClient::refresh_token(self).await
+    }
+
+    async fn content_checker(&self) -> Result<Box<dyn ContentChecker + '_>> {
+        Ok(Box::new(self))
+    }
+
+    async fn list_recent_drafts(&self, limit: u32) -> Result<Vec<DraftSummary>> {
+        list_drafts_paged(limit, |offset, count| self.batchget_drafts(offset, count)).await
+    }
+
+    async fn upload_body_image(&self, image_path: &Path) -> Result<String> {
+        WeChatApiClient::upload_body_image(self, image_path).await
+    }
+}
+
+#[async_trait::async_trait]
+impl ContentChecker for &WeChatApiClient {
+    async fn is_text_safe(&self, text: &str) -> Result<bool> {
+        self.msg_sec_check(text).await
+    }
+}
+
+/// Renders markdown content to themed HTML with the theme and code
+/// highlighter of its frontmatter, or [`DEFAULT_THEME`] and
+/// [`DEFAULT_CODE_HIGHLIGHTER`] if unset
+pub(crate) fn render_content(theme_manager: &ThemeManager, content: &MarkdownContent) -> Result<String> {
+    let theme = content.theme.as_deref().unwrap_or(DEFAULT_THEME);
+    if !theme_manager.has_theme(theme) {
+        return Err(Error::wechat(format!("Theme not found: {}", theme)));
+    }
+
+    let mut metadata = content.metadata.clone();
+    if let Some(title) = &content.title {
+        metadata.insert("title".to_string(), title.clone());
+    }
+    if let Some(author) = &content.author {
+        metadata.insert("author".to_string(), author.clone());
+    }
+
+    theme_manager
+        .render(
+            &content.content,
+            theme,
+            content.code.as_deref().unwrap_or(DEFAULT_CODE_HIGHLIGHTER),
+            &metadata,
+        )
+        .map_err(Error::from_wechat)
+}
+
+/// Validates a base URL and strips any trailing slash
+pub(crate) fn normalize_base_url(base_url: &str) -> Result<String> {
+    let trimmed = base_url.trim().trim_end_matches('/');
+    if !(trimmed.starts_with("http://") || trimmed.starts_with("https://")) {
+        return Err(Error::config(format!(
+            "Invalid WeChat base URL '{}': must start with http:// or https://",
+            base_url
+        )));
+    }
+    Ok(trimmed.to_string())
+}
+
+/// Builds a multipart form with the image under the `media` field
+async fn image_form(image_path: &Path) -> Result<multipart::Form> {
+    let bytes = tokio::fs::read(image_path).await?;
+    let filename = image_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("image.png")
+        .to_string();
+
+    let mime = match image_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .as_deref()
+    {
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        _ => "image/png",
+    };
+
+    let part = multipart::Part::bytes(bytes)
+        .file_name(filename)
+        .mime_str(mime)?;
+
+    Ok(multipart::Form::new().part("media", part))
+}
+
+/// Decodes a WeChat JSON response, mapping non-zero `errcode` values to errors
+async fn parse_response<T>(response: reqwest::Response) -> Result<T>
+where
+    T: serde::de::DeserializeOwned + std::fmt::Debug,
+{
+    let status = response.status();
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(Error::WeChatTooManyRequests {
+            retry_after: retry_after_header(response.headers()),
+        });
+    }
+    if !status.is_success() {
+        return Err(Error::wechat(format!(
+            "WeChat API request failed with status {}",
+            status
+        )));
+    }
+
+    let body: WeChatResponse<T> = response.json().await?;
+    body.into_result().map_err(Error::from_wechat)
+}
+
+/// Returns true if a failed `draft/add` may be down to the original declaration
+///
+/// WeChat has no dedicated errcode for accounts that cannot declare originals,
+/// so any API rejection other than authentication, rate limiting, content
+/// risk and an unknown thumbnail material counts.
+fn rejects_declaration(error: &Error) -> bool {
+    matches!(error, Error::WeChat { .. })
+        && error
+            .wechat_errcode()
+            .is_some_and(|code| code != INVALID_MEDIA_ID_ERRCODE)
+        && !error.is_rate_limited()
+}
+
+/// Builds the draft article from the frontmatter fields
+///
+/// Title, author and digest fall back to the parsed markdown the same way
+/// `wechat_pub_rs` does: an excerpt of the body stands in for the digest.
+fn build_article(
+    content: &MarkdownContent,
+    html: String,
+    thumb_media_id: String,
+    options: &ArticleOptions,
+) -> DraftArticle {
+    let mut fields = options.fields.clone();
+    fields.title = fields.title.or_else(|| content.title.clone());
+    fields.author = fields.author.or_else(|| content.author.clone());
+    fields.digest = fields
+        .digest
+        .or_else(|| content.description.clone())
+        .or_else(|| Some(content.get_summary(120)));
+
+    DraftArticle::new(&fields, html, thumb_media_id, options.show_cover)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_base_url() {
+        assert_eq!(
+            normalize_base_url("http://127.0.0.1:8080/").unwrap(),
+            "http://127.0.0.1:8080"
+        );
+        assert_eq!(
+            normalize_base_url(DEFAULT_WECHAT_BASE_URL).unwrap(),
+            DEFAULT_WECHAT_BASE_URL
+        );
+        assert!(normalize_base_url("api.weixin.qq.com").is_err());
+        assert!(normalize_base_url("").is_err());
+    }
+
+    #[test]
+    fn test_client_creation() {
+        let client = WeChatApiClient::new(
+            "wx_app".to_string(),
+            "secret".to_string(),
+            "http://localhost:9000/",
+        )
+        .unwrap();
+        assert_eq!(client.base_url(), "http://localhost:9000");
+
+        assert!(WeChatApiClient::new("a".to_string(), "b".to_string(), "ftp://x").is_err());
+    }
+
+    #[test]
+    fn test_rejects_declaration() {
+        let api_error = |code| {
+            Error::from_wechat(wechat_pub_rs::WeChatError::WeChatApi {
+                code,
+                message: "rejected".to_string(),
+            })
+        };
+        assert!(rejects_declaration(&api_error(48001)));
+        assert!(!rejects_declaration(&api_error(40001)));
+        assert!(!rejects_declaration(&api_error(87014)));
+        assert!(!rejects_declaration(&api_error(45009)));
+        assert!(!rejects_declaration(&Error::wechat("connection reset")));
+    }
+}
@@ -3,7 +3,7 @@ use tempfile::TempDir;
 use wx_uploader::{
     error::Result,
     markdown::{parse_markdown_file, write_markdown_file},
-    models::Frontmatter,
+    models::{Frontmatter, PublicationStatus},
     wechat::{DefaultCoverImageProcessor, LocalCoverImageProcessor, resolve_and_check_cover_path},
 };
 
@@ -44,7 +44,7 @@ This is a test article for integration testing.
         frontmatter.title,
         Some("Integration Test Article".to_string())
     );
-    assert_eq!(frontmatter.published, Some("draft".to_string()));
+    assert_eq!(frontmatter.publication_status(), PublicationStatus::Draft);
     assert_eq!(
         frontmatter.description,
         "A test article for integration testing"
@@ -58,7 +58,7 @@ This is a test article for integration testing.
 
     // Test frontmatter modification
     let mut modified_frontmatter = frontmatter.clone();
-    modified_frontmatter.set_published("true");
+    modified_frontmatter.set_published(PublicationStatus::Published);
     modified_frontmatter.set_cover("new_cover.png".to_string());
 
     // Write back the modified file
@@ -66,7 +66,7 @@ This is a test article for integration testing.
 
     // Re-parse to verify changes
     let (updated_frontmatter, updated_body) = parse_markdown_file(&file_path).await?;
-    assert_eq!(updated_frontmatter.published, Some("true".to_string()));
+    assert_eq!(updated_frontmatter.publication_status(), PublicationStatus::Published);
     assert_eq!(updated_frontmatter.cover, Some("new_cover.png".to_string()));
     assert_eq!(updated_body, body); // Body should remain unchanged
 
@@ -114,6 +114,11 @@ fn test_cover_image_path_resolution() {
     let (resolved_path, exists) = resolve_and_check_cover_path(&md_file, "simple_cover.png");
     assert_eq!(resolved_path, simple_cover);
     assert!(exists);
+
+    // A directory exists but is not a usable cover
+    let (resolved_path, exists) = resolve_and_check_cover_path(&md_file, "images/");
+    assert_eq!(resolved_path, base_path.join("images/"));
+    assert!(!exists);
 }
 
 #[tokio::test]
@@ -127,13 +132,13 @@ async fn test_cover_image_processor_without_openai() {
 
     // Test that it returns None when no OpenAI client is available
     let result = processor
-        .ensure_cover_image("test content", &md_file, None)
+        .ensure_cover_image(&"test content".into(), &md_file, None)
         .await
         .unwrap();
     assert!(result.is_none());
 
     let result = processor
-        .ensure_cover_image("test content", &md_file, Some("cover.png"))
+        .ensure_cover_image(&"test content".into(), &md_file, Some("cover.png"))
         .await
         .unwrap();
     assert!(result.is_none());
@@ -249,23 +254,23 @@ async fn test_markdown_file_discovery_comprehensive() {
         match *file_path {
             "articles/published.md" => {
                 assert!(
-                    frontmatter.is_published(),
+                    frontmatter.publication_status().is_published(),
                     "File should be published: {:?}",
                     file_path
                 );
             }
             "articles/draft.md" => {
-                assert!(!frontmatter.is_published()); // "draft" is not considered published
+                assert!(!frontmatter.publication_status().is_published()); // "draft" is not considered published
             }
             "articles/tech/advanced.md" => {
-                assert!(!frontmatter.is_published()); // "false" is not considered published
+                assert!(!frontmatter.publication_status().is_published()); // "false" is not considered published
             }
             "drafts/ideas/concept.md" => {
                 assert_eq!(frontmatter.cover, Some("concept_cover.png".to_string()));
             }
             _ => {
                 // Other files should not be marked as published
-                assert!(!frontmatter.is_published());
+                assert!(!frontmatter.publication_status().is_published());
             }
         }
     }
@@ -286,11 +291,11 @@ fn test_frontmatter_publication_states() {
     ];
 
     for (published_value, expected_published) in &test_cases {
-        let mut frontmatter = Frontmatter::default();
-        frontmatter.set_published(*published_value);
+        let yaml = format!("published: {}\n", published_value);
+        let frontmatter: Frontmatter = serde_yaml::from_str(&yaml).unwrap();
 
         assert_eq!(
-            frontmatter.is_published(),
+            frontmatter.publication_status().is_published(),
             *expected_published,
             "Failed for published value: '{}'",
             published_value
@@ -330,7 +335,7 @@ async fn test_file_error_scenarios() {
 
     let (frontmatter, body) = result.unwrap();
     assert!(frontmatter.title.is_none());
-    assert!(!frontmatter.is_published());
+    assert!(!frontmatter.publication_status().is_published());
     assert!(body.contains("# Simple Markdown"));
 
     // Test parsing non-existent file
@@ -448,3 +453,3427 @@ async fn test_concurrent_file_operations() {
         task.await.unwrap();
     }
 }
+
+/// Starts a mock WeChat API server for the account of [`mock_config`]
+///
+/// It issues `mock_token` and stores covers as `cover_media_id`; see
+/// [`mount_wechat_defaults`]. Drafts are left to each test.
+async fn mock_wechat_server() -> wiremock::MockServer {
+    let server = wiremock::MockServer::start().await;
+    mount_wechat_defaults(&server).await;
+    server
+}
+
+/// Mounts the token and cover answers of [`mock_wechat_server`], e.g. after a reset
+///
+/// They have a low priority, so a test's own mocks of these endpoints win.
+async fn mount_wechat_defaults(server: &wiremock::MockServer) {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    Mock::given(method("GET"))
+        .and(path("/cgi-bin/token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "access_token": "mock_token",
+            "expires_in": 7200
+        })))
+        .with_priority(10)
+        .mount(server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/material/add_material"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "media_id": "cover_media_id",
+            "url": "http://mmbiz.qpic.cn/mock/cover.png"
+        })))
+        .with_priority(10)
+        .mount(server)
+        .await;
+}
+
+/// Answers every draft creation with `media_id` and expects `count` of them
+async fn mount_drafts(server: &wiremock::MockServer, media_id: &str, count: u64) {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/draft/add"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "media_id": media_id
+        })))
+        .expect(count)
+        .mount(server)
+        .await;
+}
+
+/// Returns the config of a single account uploading through `server`
+fn mock_config(server: &wiremock::MockServer) -> wx_uploader::Config {
+    let mut config = wx_uploader::Config::new(
+        "wx_mock_app".to_string(),
+        "mock_secret".to_string(),
+        None,
+        false,
+    );
+    config.set_wechat_base_url(server.uri());
+    config
+}
+
+/// Runs a full `upload_file` flow against a mock WeChat API server.
+#[tokio::test]
+async fn test_upload_file_against_mock_wechat_server() -> Result<()> {
+    use wiremock::matchers::{body_partial_json, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use wx_uploader::WxUploader;
+
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/cgi-bin/token"))
+        .and(query_param("appid", "wx_mock_app"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "access_token": "mock_token",
+            "expires_in": 7200
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/media/uploadimg"))
+        .and(query_param("access_token", "mock_token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "url": "http://mmbiz.qpic.cn/mock/body.png"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/material/add_material"))
+        .and(query_param("type", "image"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "media_id": "cover_media_id",
+            "url": "http://mmbiz.qpic.cn/mock/cover.png"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/draft/add"))
+        .and(body_partial_json(serde_json::json!({
+            "articles": [{ "title": "Mock Article", "thumb_media_id": "cover_media_id" }]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "media_id": "draft_media_id"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let temp_dir = TempDir::new().unwrap();
+    let article = temp_dir.path().join("article.md");
+    fs::write(temp_dir.path().join("cover.png"), b"fake cover").unwrap();
+    fs::write(temp_dir.path().join("body.png"), b"fake body image").unwrap();
+    fs::write(
+        &article,
+        "---\ntitle: Mock Article\ncover: cover.png\n---\n# Hello\n\n![diagram](body.png)\n",
+    )
+    .unwrap();
+
+    let config = mock_config(&server);
+
+    let uploader = WxUploader::new(config).await?;
+    uploader.upload_file(&article, true).await?;
+
+    let (frontmatter, _body) = parse_markdown_file(&article).await?;
+    assert!(frontmatter.publication_status().is_draft());
+    assert_eq!(frontmatter.draft_id.as_deref(), Some("draft_media_id"));
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Retries a draft answered with 429 after its `Retry-After`, and not at all with `max_retries: 0`.
+#[tokio::test]
+async fn test_too_many_requests_retried_unless_retries_disabled() -> Result<()> {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+    use wx_uploader::WxUploader;
+
+    let server = mock_wechat_server().await;
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/draft/add"))
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/draft/add"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "media_id": "draft_media_id"
+        })))
+        .mount(&server)
+        .await;
+
+    let temp_dir = TempDir::new().unwrap();
+    let article = temp_dir.path().join("article.md");
+    fs::write(temp_dir.path().join("cover.png"), b"fake cover").unwrap();
+    fs::write(&article, "---\ntitle: Busy\ncover: cover.png\n---\n# Hello\n").unwrap();
+
+    let config = || mock_config(&server);
+    let draft_calls = |requests: Vec<wiremock::Request>| {
+        requests.iter().filter(|r| r.url.path() == "/cgi-bin/draft/add").count()
+    };
+
+    WxUploader::new(config()).await?.upload_file(&article, true).await?;
+    let (frontmatter, _body) = parse_markdown_file(&article).await?;
+    assert_eq!(frontmatter.draft_id.as_deref(), Some("draft_media_id"));
+    assert_eq!(draft_calls(server.received_requests().await.unwrap()), 2);
+
+    // With retries disabled the 429 fails the upload right away
+    server.reset().await;
+    mount_wechat_defaults(&server).await;
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/draft/add"))
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+        .mount(&server)
+        .await;
+
+    let mut config = config();
+    config.max_retries = 0;
+    let err = WxUploader::new(config)
+        .await?
+        .upload_file(&article, true)
+        .await
+        .unwrap_err();
+    assert!(err.is_rate_limited(), "{}", err);
+    assert_eq!(draft_calls(server.received_requests().await.unwrap()), 1);
+    Ok(())
+}
+
+/// Leaves the original declaration out of drafts for sandbox accounts.
+#[tokio::test]
+async fn test_sandbox_account_uploads_without_original_declaration() -> Result<()> {
+    use wx_uploader::WxUploader;
+    use wx_uploader::models::AccountKind;
+
+    let server = mock_wechat_server().await;
+    mount_drafts(&server, "draft_media_id", 2).await;
+
+    let temp_dir = TempDir::new().unwrap();
+    let article = temp_dir.path().join("article.md");
+    fs::write(temp_dir.path().join("cover.png"), b"fake cover").unwrap();
+    fs::write(&article, "---\ntitle: Mine\ncover: cover.png\noriginal: true\n---\n# Hello\n")
+        .unwrap();
+
+    let config_with = |kind| {
+        let mut config = mock_config(&server);
+        config.wechat_account.kind = kind;
+        config
+    };
+    for kind in [AccountKind::Normal, AccountKind::Sandbox] {
+        WxUploader::new(config_with(kind)).await?.upload_file(&article, true).await?;
+    }
+
+    let drafts: Vec<serde_json::Value> = server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter(|r| r.url.path() == "/cgi-bin/draft/add")
+        .map(|r| serde_json::from_slice(&r.body).unwrap())
+        .collect();
+    assert_eq!(drafts[0]["articles"][0]["is_original"], 1);
+    assert!(drafts[1]["articles"][0].get("is_original").is_none(), "{}", drafts[1]);
+    server.verify().await;
+    Ok(())
+}
+
+/// Creates drafts without a cover when the account may not upload permanent material.
+#[tokio::test]
+async fn test_material_denied_uploads_without_cover_unless_fail() -> Result<()> {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+    use wx_uploader::WxUploader;
+    use wx_uploader::models::MaterialDenied;
+
+    let server = mock_wechat_server().await;
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/material/add_material"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "errcode": 48001,
+            "errmsg": "api unauthorized"
+        })))
+        .mount(&server)
+        .await;
+    // Only the upload under the `skip` policy reaches draft creation
+    mount_drafts(&server, "draft_media_id", 1).await;
+
+    let temp_dir = TempDir::new().unwrap();
+    let article = temp_dir.path().join("article.md");
+    fs::write(temp_dir.path().join("cover.png"), b"fake cover").unwrap();
+    fs::write(&article, "---\ntitle: Plain\ncover: cover.png\n---\n# Hello\n").unwrap();
+
+    let config_with = |on_material_denied| {
+        let mut config = mock_config(&server);
+        config.on_material_denied = on_material_denied;
+        config
+    };
+
+    let err = WxUploader::new(config_with(MaterialDenied::Fail))
+        .await?
+        .upload_file(&article, true)
+        .await
+        .unwrap_err();
+    assert!(err.is_api_unauthorized(), "{}", err);
+
+    WxUploader::new(config_with(MaterialDenied::Skip))
+        .await?
+        .upload_file(&article, true)
+        .await?;
+    let (frontmatter, _body) = parse_markdown_file(&article).await?;
+    assert_eq!(frontmatter.draft_id.as_deref(), Some("draft_media_id"));
+
+    let requests = server.received_requests().await.unwrap();
+    let draft = requests
+        .iter()
+        .find(|r| r.url.path() == "/cgi-bin/draft/add")
+        .map(|r| serde_json::from_slice::<serde_json::Value>(&r.body).unwrap())
+        .unwrap();
+    assert!(draft["articles"][0].get("thumb_media_id").is_none(), "{}", draft);
+    assert_eq!(draft["articles"][0]["show_cover_pic"], 0);
+    server.verify().await;
+    Ok(())
+}
+
+/// Uploads a file with an unknown theme under the `default` fallback and skips it under `skip`.
+#[tokio::test]
+async fn test_theme_fallback_policies_against_mock_wechat_server() -> Result<()> {
+    use wx_uploader::WxUploader;
+    use wx_uploader::models::ThemeFallback;
+
+    let server = mock_wechat_server().await;
+
+    // Only the upload under the `default` policy reaches WeChat
+    mount_drafts(&server, "draft_media_id", 1).await;
+
+    let temp_dir = TempDir::new().unwrap();
+    let article = temp_dir.path().join("article.md");
+    fs::write(temp_dir.path().join("cover.png"), b"fake cover").unwrap();
+    fs::write(
+        &article,
+        "---\ntitle: Migrated\ncover: cover.png\ntheme: nope\n---\n# Hello\n",
+    )
+    .unwrap();
+
+    let config_with = |theme_fallback| {
+        let mut config = mock_config(&server);
+        config.theme_fallback = theme_fallback;
+        config
+    };
+
+    let skip = WxUploader::new(config_with(ThemeFallback::Skip)).await?;
+    let report = skip.process_directory(temp_dir.path()).await?;
+    assert_eq!((report.uploaded(), report.skipped(), report.failed()), (0, 1, 0));
+
+    let default = WxUploader::new(config_with(ThemeFallback::Default)).await?;
+    default.upload_file(&article, true).await?;
+
+    // The draft is recorded, but the unknown theme is left for the author to fix
+    let content = fs::read_to_string(&article).unwrap();
+    assert!(content.contains("theme: nope"));
+    assert!(content.contains("draft_id: draft_media_id"));
+    assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 2);
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Collects the theme fallback warning of a directory run in the report.
+#[tokio::test]
+async fn test_directory_run_reports_warnings_per_file() -> Result<()> {
+    use wx_uploader::WxUploader;
+    use wx_uploader::exit_code::ExitCode;
+    use wx_uploader::models::ThemeFallback;
+
+    let server = mock_wechat_server().await;
+    mount_drafts(&server, "draft_media_id", 2).await;
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("cover.png"), b"fake cover").unwrap();
+    let migrated = temp_dir.path().join("migrated.md");
+    fs::write(&migrated, "---\ntitle: Migrated\ncover: cover.png\ntheme: nope\n---\n# Hi\n")
+        .unwrap();
+    fs::write(
+        temp_dir.path().join("clean.md"),
+        "---\ntitle: Clean\ncover: cover.png\ntheme: lapis\n---\n# Hi\n",
+    )
+    .unwrap();
+
+    let mut config = mock_config(&server);
+    config.theme_fallback = ThemeFallback::Default;
+
+    let uploader = WxUploader::new(config).await?;
+    let report = uploader.process_directory(temp_dir.path()).await?;
+
+    assert_eq!((report.uploaded(), report.failed()), (2, 0), "{:?}", report);
+    assert_eq!(report.warnings.len(), 1, "{:?}", report.warnings);
+    assert_eq!(report.warnings[0].path, migrated);
+    assert_eq!(report.warnings[0].message, "unknown theme 'nope', uploading with the default");
+    assert!(report.summary().ends_with(", 1 warning"), "{}", report.summary());
+    assert_eq!(ExitCode::from_report_with(&report, false), ExitCode::Success);
+    assert_eq!(ExitCode::from_report_with(&report, true), ExitCode::Warnings);
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Collects the files of the configured markdown extensions and warns about
+/// a file given by path with another extension.
+#[tokio::test]
+async fn test_markdown_extensions_in_directory_and_single_file_runs() -> Result<()> {
+    use wx_uploader::WxUploader;
+
+    let server = mock_wechat_server().await;
+    // One `.md` file, the `.markdown` file given by path, then all three files
+    mount_drafts(&server, "draft_media_id", 5).await;
+
+    let write_posts = |dir: &std::path::Path| {
+        fs::write(dir.join("cover.png"), b"fake cover").unwrap();
+        for name in ["a.md", "b.markdown", "c.mdx"] {
+            fs::write(dir.join(name), "---\ntitle: Post\ncover: cover.png\n---\n# Hi\n").unwrap();
+        }
+    };
+    let config = |extensions: Option<Vec<String>>| {
+        let mut config = mock_config(&server);
+        if let Some(extensions) = extensions {
+            config.markdown_extensions = extensions;
+        }
+        config
+    };
+
+    let defaults = TempDir::new().unwrap();
+    write_posts(defaults.path());
+    let uploader = WxUploader::new(config(None)).await?;
+    let report = uploader.process_directory(defaults.path()).await?;
+    assert_eq!(report.uploaded(), 1, "{:?}", report);
+
+    let markdown = defaults.path().join("b.markdown");
+    let report = uploader.upload_file_to_accounts(&markdown, true).await?;
+    assert_eq!(report.uploaded(), 1, "{:?}", report);
+    assert_eq!(report.warnings.len(), 1, "{:?}", report.warnings);
+    assert_eq!(report.warnings[0].path, markdown);
+    assert!(
+        report.warnings[0]
+            .message
+            .starts_with("'.markdown' is not a recognized markdown extension (recognized: md)"),
+        "{}",
+        report.warnings[0].message
+    );
+
+    let configured = TempDir::new().unwrap();
+    write_posts(configured.path());
+    let extensions = vec!["md".to_string(), "markdown".to_string(), "mdx".to_string()];
+    let uploader = WxUploader::new(config(Some(extensions))).await?;
+    let report = uploader.process_directory(configured.path()).await?;
+    assert_eq!(report.uploaded(), 3, "{:?}", report);
+    assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Pauses for `upload_delay_ms` between created drafts, but not for skipped files.
+#[tokio::test]
+async fn test_upload_delay_pauses_between_created_drafts() -> Result<()> {
+    use std::time::{Duration, Instant};
+    use wx_uploader::WxUploader;
+
+    let server = mock_wechat_server().await;
+    mount_drafts(&server, "draft_media_id", 2).await;
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("cover.png"), b"fake cover").unwrap();
+    fs::write(temp_dir.path().join("a.md"), "---\ntitle: A\ncover: cover.png\n---\n# A\n").unwrap();
+    fs::write(
+        temp_dir.path().join("b.md"),
+        "---\ntitle: B\ncover: cover.png\npublished: true\n---\n# B\n",
+    )
+    .unwrap();
+    fs::write(temp_dir.path().join("c.md"), "---\ntitle: C\ncover: cover.png\n---\n# C\n").unwrap();
+
+    let mut config = mock_config(&server);
+    config.upload_delay_ms = 1000;
+
+    let uploader = WxUploader::new(config).await?;
+    let started = Instant::now();
+    let report = uploader.process_directory(temp_dir.path()).await?;
+
+    assert_eq!((report.uploaded(), report.skipped()), (2, 1), "{:?}", report);
+    // The pause after the first draft; that none follows the last draft or
+    // the skip is checked with paused time in the pacer's unit tests
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_millis(1000), "{:?}", elapsed);
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Sends the WeChat article fields set in the frontmatter with the draft.
+#[tokio::test]
+async fn test_upload_file_sends_frontmatter_article_fields() -> Result<()> {
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, ResponseTemplate};
+    use wx_uploader::WxUploader;
+
+    let server = mock_wechat_server().await;
+
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/draft/add"))
+        .and(body_partial_json(serde_json::json!({
+            "articles": [{
+                "title": "Fields Article",
+                "author": "Jane",
+                "digest": "A short summary",
+                "content_source_url": "https://example.com/original",
+                "thumb_media_id": "cover_media_id",
+                "show_cover_pic": 1,
+                "need_open_comment": 1,
+                "only_fans_can_comment": 1,
+                "pic_crop_235_1": "0_0.2_1_0.8",
+                "pic_crop_1_1": "0.2_0_0.8_1"
+            }]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "media_id": "draft_media_id"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let temp_dir = TempDir::new().unwrap();
+    let article = temp_dir.path().join("article.md");
+    fs::write(temp_dir.path().join("cover.png"), b"fake cover").unwrap();
+    fs::write(
+        &article,
+        "---\ntitle: Fields Article\nauthor: Jane\ndescription: A short summary\ncover: cover.png\n\
+         content_source_url: https://example.com/original\nneed_open_comment: true\n\
+         only_fans_can_comment: true\npic_crop_235_1: 0_0.2_1_0.8\npic_crop_1_1: 0.2_0_0.8_1\n---\n# Hello\n",
+    )
+    .unwrap();
+
+    let config = mock_config(&server);
+
+    let uploader = WxUploader::new(config).await?;
+    uploader.upload_file(&article, true).await?;
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Uploads an article whose thumbnail differs from its cover against a mock server.
+#[tokio::test]
+async fn test_upload_file_with_separate_thumb() -> Result<()> {
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, ResponseTemplate};
+    use wx_uploader::WxUploader;
+
+    let server = mock_wechat_server().await;
+
+    // The cover becomes an in-body header image
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/media/uploadimg"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "url": "http://mmbiz.qpic.cn/mock/cover.png"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    // Only the thumbnail is uploaded as permanent material
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/material/add_material"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "media_id": "thumb_media_id",
+            "url": "http://mmbiz.qpic.cn/mock/thumb.png"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/draft/add"))
+        .and(body_partial_json(serde_json::json!({
+            "articles": [{ "thumb_media_id": "thumb_media_id", "show_cover_pic": 0 }]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "media_id": "draft_media_id"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let temp_dir = TempDir::new().unwrap();
+    let article = temp_dir.path().join("article.md");
+    fs::write(temp_dir.path().join("cover.png"), b"fake cover").unwrap();
+    fs::write(temp_dir.path().join("thumb.png"), b"fake thumb").unwrap();
+    fs::write(
+        &article,
+        "---\ntitle: Thumb Article\ncover: cover.png\nthumb: thumb.png\n---\n# Hello\n",
+    )
+    .unwrap();
+
+    let config = mock_config(&server);
+
+    let uploader = WxUploader::new(config).await?;
+    uploader.upload_file(&article, true).await?;
+
+    let (frontmatter, body) = parse_markdown_file(&article).await?;
+    assert!(frontmatter.publication_status().is_draft());
+    assert_eq!(frontmatter.thumb.as_deref(), Some("thumb.png"));
+    assert!(!body.contains("cover.png"));
+
+    // The temporary upload file is cleaned up
+    let leftovers: Vec<_> = fs::read_dir(temp_dir.path())
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().ends_with(".wx-upload.md"))
+        .collect();
+    assert!(leftovers.is_empty());
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Processes one subdirectory of a site whose covers are referenced from the content root.
+#[tokio::test]
+async fn test_process_subdirectory_with_root_relative_covers() -> Result<()> {
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, ResponseTemplate};
+    use wx_uploader::WxUploader;
+
+    let server = mock_wechat_server().await;
+
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/material/add_material"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "media_id": "cover_media_id",
+            "url": "http://mmbiz.qpic.cn/mock/cover.png"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/draft/add"))
+        .and(body_partial_json(serde_json::json!({
+            "articles": [{ "title": "New Post", "thumb_media_id": "cover_media_id" }]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "media_id": "draft_media_id"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir_all(root.join("assets/covers")).unwrap();
+    fs::create_dir_all(root.join("posts/2023")).unwrap();
+    fs::create_dir_all(root.join("posts/2024")).unwrap();
+    fs::write(root.join("assets/covers/new.png"), b"fake cover").unwrap();
+    let article = root.join("posts/2024/new.md");
+    fs::write(
+        &article,
+        "---\ntitle: New Post\ncover: /assets/covers/new.png\n---\n# Hello\n",
+    )
+    .unwrap();
+    let old = root.join("posts/2023/old.md");
+    let old_content = "---\ntitle: Old Post\ncover: /assets/covers/old.png\n---\n# Hello\n";
+    fs::write(&old, old_content).unwrap();
+
+    let mut config = mock_config(&server);
+    config.content_root = Some(wx_uploader::models::resolve_content_root(root)?);
+
+    let uploader = WxUploader::new(config).await?;
+    let report = uploader.process_directory(root.join("posts/2024")).await?;
+    assert_eq!(report.uploaded(), 1);
+    assert_eq!(report.failed(), 0);
+
+    // The article keeps its root-relative cover; files outside the subpath are untouched
+    let (frontmatter, _body) = parse_markdown_file(&article).await?;
+    assert!(frontmatter.publication_status().is_draft());
+    assert_eq!(frontmatter.cover.as_deref(), Some("/assets/covers/new.png"));
+    assert_eq!(fs::read_to_string(&old).unwrap(), old_content);
+
+    let leftovers: Vec<_> = fs::read_dir(root.join("posts/2024"))
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().ends_with(".wx-upload.md"))
+        .collect();
+    assert!(leftovers.is_empty());
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Routes files to the account named in their frontmatter during a directory run.
+#[tokio::test]
+async fn test_process_directory_routes_files_by_frontmatter_account() -> Result<()> {
+    use std::collections::HashMap;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use wx_uploader::models::{AccountKind, WeChatAccount};
+    use wx_uploader::{Config, WxUploader};
+
+    let server = MockServer::start().await;
+
+    for (app_id, token) in [("wx_main", "main_token"), ("wx_work", "work_token")] {
+        Mock::given(method("GET"))
+            .and(path("/cgi-bin/token"))
+            .and(query_param("appid", app_id))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": token,
+                "expires_in": 7200
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path("/cgi-bin/draft/add"))
+            .and(query_param("access_token", token))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "media_id": format!("draft_{}", token)
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+    }
+
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/material/add_material"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "media_id": "cover_media_id",
+            "url": "http://mmbiz.qpic.cn/mock/cover.png"
+        })))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("cover.png"), b"fake cover").unwrap();
+    fs::write(
+        temp_dir.path().join("personal.md"),
+        "---\ntitle: Personal\ncover: cover.png\n---\nHello\n",
+    )
+    .unwrap();
+    fs::write(
+        temp_dir.path().join("work.md"),
+        "---\ntitle: Work\ncover: cover.png\naccount: work\n---\nHello\n",
+    )
+    .unwrap();
+
+    let mut accounts = HashMap::new();
+    for (name, app_id) in [("main", "wx_main"), ("work", "wx_work")] {
+        accounts.insert(
+            name.to_string(),
+            WeChatAccount {
+                name: name.to_string(),
+                app_id: app_id.to_string(),
+                app_secret: "secret".to_string(),
+                description: None,
+                wechat_base_url: Some(server.uri()),
+                default_cover: None,
+                kind: AccountKind::Normal,
+                tags: Vec::new(),
+                owner: None,
+            },
+        );
+    }
+    let config = Config::new_with_accounts(accounts, "main", None, false)?;
+
+    let uploader = WxUploader::new(config).await?;
+    let report = uploader.process_directory(temp_dir.path()).await?;
+
+    assert_eq!(report.uploaded(), 2);
+    assert_eq!(report.failed(), 0);
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Uploads exactly the files of a plain-text manifest, reporting in its order.
+#[tokio::test]
+async fn test_text_manifest_uploads_listed_files_in_order() -> Result<()> {
+    use wx_uploader::WxUploader;
+    use wx_uploader::manifest::Manifest;
+
+    let server = mock_wechat_server().await;
+    mount_drafts(&server, "new_draft_id", 2).await;
+
+    let temp_dir = TempDir::new().unwrap();
+    let posts = temp_dir.path().join("posts");
+    fs::create_dir(&posts).unwrap();
+    fs::write(posts.join("cover.png"), b"fake cover").unwrap();
+    for name in ["a", "b", "c"] {
+        fs::write(
+            posts.join(format!("{}.md", name)),
+            format!("---\ntitle: {}\ncover: cover.png\n---\nBody\n", name),
+        )
+        .unwrap();
+    }
+    let unlisted = fs::read_to_string(posts.join("b.md")).unwrap();
+    let manifest_path = temp_dir.path().join("changed.txt");
+    fs::write(&manifest_path, "# release 1.2\nposts/c.md\n\nposts/a.md\n").unwrap();
+
+    let config = mock_config(&server);
+    let uploader = WxUploader::new(config).await?;
+    let manifest = Manifest::load(&manifest_path).await?;
+    let report = uploader.upload_manifest(&manifest).await?;
+
+    let paths: Vec<_> = report.files.iter().map(|file| file.path.clone()).collect();
+    assert_eq!(paths, vec![posts.join("c.md"), posts.join("a.md")]);
+    assert_eq!(report.uploaded(), 2);
+    assert_eq!(fs::read_to_string(posts.join("b.md")).unwrap(), unlisted);
+
+    server.verify().await;
+    Ok(())
+}
+
+/// A YAML manifest entry overrides the frontmatter account and theme of its file.
+#[tokio::test]
+async fn test_yaml_manifest_entry_overrides_account_and_theme() -> Result<()> {
+    use std::collections::HashMap;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, ResponseTemplate};
+    use wx_uploader::manifest::Manifest;
+    use wx_uploader::models::{AccountKind, WeChatAccount};
+    use wx_uploader::{Config, WxUploader};
+
+    let server = mock_wechat_server().await;
+    for (app_id, token) in [("wx_main", "main_token"), ("wx_work", "work_token")] {
+        Mock::given(method("GET"))
+            .and(path("/cgi-bin/token"))
+            .and(query_param("appid", app_id))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": token,
+                "expires_in": 7200
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/cgi-bin/draft/add"))
+            .and(query_param("access_token", token))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "media_id": format!("draft_{}", token)
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("cover.png"), b"fake cover").unwrap();
+    fs::write(
+        temp_dir.path().join("one.md"),
+        "---\ntitle: One\ncover: cover.png\n---\nHello\n",
+    )
+    .unwrap();
+    // The manifest entry wins over the account and theme in the frontmatter
+    fs::write(
+        temp_dir.path().join("two.md"),
+        "---\ntitle: Two\ncover: cover.png\naccount: main\ntheme: default\n---\nHello\n",
+    )
+    .unwrap();
+    let manifest_path = temp_dir.path().join("release.yaml");
+    fs::write(
+        &manifest_path,
+        "- path: two.md\n  account: work\n  theme: lapis\n- one.md\n",
+    )
+    .unwrap();
+
+    let mut accounts = HashMap::new();
+    for (name, app_id) in [("main", "wx_main"), ("work", "wx_work")] {
+        accounts.insert(
+            name.to_string(),
+            WeChatAccount {
+                name: name.to_string(),
+                app_id: app_id.to_string(),
+                app_secret: "secret".to_string(),
+                description: None,
+                wechat_base_url: Some(server.uri()),
+                default_cover: None,
+                kind: AccountKind::Normal,
+                tags: Vec::new(),
+                owner: None,
+            },
+        );
+    }
+    let config = Config::new_with_accounts(accounts, "main", None, false)?;
+    let uploader = WxUploader::new(config).await?;
+    let manifest = Manifest::load(&manifest_path).await?;
+    let report = uploader.upload_manifest(&manifest).await?;
+
+    // The main account's file is uploaded first, but reported in manifest order
+    let results: Vec<_> = report
+        .files
+        .iter()
+        .map(|file| (file.path.file_name().unwrap().to_owned(), file.account.clone()))
+        .collect();
+    assert_eq!(
+        results,
+        vec![
+            ("two.md".into(), Some("work".to_string())),
+            ("one.md".into(), Some("main".to_string())),
+        ]
+    );
+    assert_eq!(report.uploaded(), 2);
+
+    // The theme override applies to the upload only
+    let two = fs::read_to_string(temp_dir.path().join("two.md")).unwrap();
+    assert!(two.contains("theme: default"), "{}", two);
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Uploads a file naming an account group to every member, one draft per account.
+#[tokio::test]
+async fn test_process_directory_uploads_to_account_group() -> Result<()> {
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, ResponseTemplate};
+    use wx_uploader::{Config, WxUploader};
+
+    let server = mock_wechat_server().await;
+
+    // One token per account proves each client is created once
+    for (app_id, token, drafts) in [
+        ("wx_main", "main_token", 2),
+        ("wx_north", "north_token", 1),
+        ("wx_south", "south_token", 1),
+    ] {
+        Mock::given(method("GET"))
+            .and(path("/cgi-bin/token"))
+            .and(query_param("appid", app_id))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": token,
+                "expires_in": 7200
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path("/cgi-bin/draft/add"))
+            .and(query_param("access_token", token))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "media_id": format!("draft_{}", token)
+            })))
+            .expect(drafts)
+            .mount(&server)
+            .await;
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let posts = temp_dir.path().join("posts");
+    fs::create_dir(&posts).unwrap();
+    fs::write(posts.join("cover.png"), b"fake cover").unwrap();
+    let syndicated = posts.join("syndicated.md");
+    fs::write(
+        &syndicated,
+        "---\ntitle: Syndicated\ncover: cover.png\naccounts: [\"@everywhere\"]\n---\nHello\n",
+    )
+    .unwrap();
+    fs::write(
+        posts.join("local.md"),
+        "---\ntitle: Local\ncover: cover.png\n---\nHello\n",
+    )
+    .unwrap();
+
+    let config_path = temp_dir.path().join("config.yaml");
+    fs::write(
+        &config_path,
+        format!(
+            "accounts:
+  main: {{ name: main, app_id: wx_main, app_secret: secret }}
+  north: {{ name: north, app_id: wx_north, app_secret: secret }}
+  south: {{ name: south, app_id: wx_south, app_secret: secret }}
+default_account: main
+groups:
+  regional: [north, south]
+  everywhere: [\"@regional\", main]
+settings:
+  wechat_base_url: {}
+",
+            server.uri()
+        ),
+    )
+    .unwrap();
+    let config = Config::from_file(&config_path, None).await?;
+
+    let uploader = WxUploader::new(config).await?;
+    let report = uploader.process_directory(&posts).await?;
+
+    assert_eq!(report.uploaded(), 4);
+    assert_eq!(report.failed(), 0);
+    assert_eq!(report.accounts(), vec!["main", "north", "south"]);
+    assert_eq!(report.for_account("main").uploaded(), 2);
+    assert_eq!(report.for_account("north").uploaded(), 1);
+
+    let (frontmatter, _) = parse_markdown_file(&syndicated).await?;
+    assert_eq!(frontmatter.publication_status(), PublicationStatus::Draft);
+    assert_eq!(frontmatter.draft_id, None);
+    let uploads = frontmatter.uploads.unwrap();
+    assert_eq!(uploads["north"], "draft_north_token");
+    assert_eq!(uploads["south"], "draft_south_token");
+    assert_eq!(uploads["main"], "draft_main_token");
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Bisects a rejected article with a mocked `msg_sec_check` endpoint.
+#[tokio::test]
+async fn test_diagnose_content_against_mock_wechat_server() -> Result<()> {
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, ResponseTemplate};
+    use wx_uploader::content_check::diagnose_file;
+    use wx_uploader::wechat_api::WeChatApiClient;
+    use wx_uploader::{Config, WxUploader};
+
+    let server = mock_wechat_server().await;
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/draft/add"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "errcode": 87014,
+            "errmsg": "risky content"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/wxa/msg_sec_check"))
+        .and(body_string_contains("forbidden"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "errcode": 87014,
+            "errmsg": "risky content"
+        })))
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/wxa/msg_sec_check"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "errcode": 0,
+            "errmsg": "ok"
+        })))
+        .mount(&server)
+        .await;
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("cover.png"), b"fake cover").unwrap();
+    let article = temp_dir.path().join("article.md");
+    fs::write(
+        &article,
+        "---\ntitle: Risky\ncover: cover.png\n---\nIntro\n\n## Safe\n\nFine text.\n\n## Risky\n\nOk here.\n\nSome forbidden words.\n\n## Also Safe\n\nMore text.\n",
+    )
+    .unwrap();
+
+    let client = WeChatApiClient::new("wx_app".to_string(), "secret".to_string(), &server.uri())?;
+    let findings = diagnose_file(&&client, &article).await?;
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].heading.as_deref(), Some("Risky"));
+    assert_eq!((findings[0].start_line, findings[0].end_line), (15, 15));
+
+    let mut config = Config::new("wx_app".to_string(), "secret".to_string(), None, false);
+    config.set_wechat_base_url(server.uri());
+    config.diagnose_content = true;
+
+    let uploader = WxUploader::new(config).await?;
+    let error = uploader.upload_file(&article, true).await.unwrap_err();
+    assert!(error.is_content_risk());
+
+    let (frontmatter, _body) = parse_markdown_file(&article).await?;
+    assert!(!frontmatter.publication_status().is_draft());
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Encodes a small PNG, standing in for a generated cover.
+fn sample_png() -> Vec<u8> {
+    let mut png = std::io::Cursor::new(Vec::new());
+    image::RgbImage::from_pixel(16, 16, image::Rgb([20, 40, 80]))
+        .write_to(&mut png, image::ImageFormat::Png)
+        .unwrap();
+    png.into_inner()
+}
+
+/// Uploader that takes a while per upload and records how many covers exist when it finishes.
+struct SlowCountingUploader {
+    dir: std::path::PathBuf,
+    delay: std::time::Duration,
+    active: std::sync::atomic::AtomicUsize,
+    max_active: std::sync::atomic::AtomicUsize,
+    covers_after_upload: std::sync::Mutex<Vec<usize>>,
+}
+
+impl SlowCountingUploader {
+    fn new(dir: &std::path::Path, delay: std::time::Duration) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            delay,
+            active: Default::default(),
+            max_active: Default::default(),
+            covers_after_upload: Default::default(),
+        }
+    }
+
+    fn cover_count(&self) -> usize {
+        fs::read_dir(&self.dir)
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().contains("_cover_"))
+            .count()
+    }
+}
+
+#[async_trait::async_trait]
+impl wx_uploader::wechat::WeChatUploader for SlowCountingUploader {
+    async fn upload(
+        &self,
+        _file_path: &str,
+        _options: &wx_uploader::wechat::ArticleOptions,
+    ) -> Result<String> {
+        use std::sync::atomic::Ordering;
+
+        let active = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_active.fetch_max(active, Ordering::SeqCst);
+        tokio::time::sleep(self.delay).await;
+        self.active.fetch_sub(1, Ordering::SeqCst);
+
+        let mut counts = self.covers_after_upload.lock().unwrap();
+        counts.push(self.cover_count());
+        Ok(format!("draft_{}", counts.len()))
+    }
+
+    async fn refresh_token(&self) -> Result<String> {
+        Ok("token".to_string())
+    }
+
+    async fn content_checker(
+        &self,
+    ) -> Result<Box<dyn wx_uploader::content_check::ContentChecker + '_>> {
+        Err(wx_uploader::Error::generic("not used"))
+    }
+
+    async fn list_recent_drafts(
+        &self,
+        _limit: u32,
+    ) -> Result<Vec<wx_uploader::wechat::DraftSummary>> {
+        Err(wx_uploader::Error::generic("not used"))
+    }
+
+    async fn upload_body_image(&self, _image_path: &std::path::Path) -> Result<String> {
+        Err(wx_uploader::Error::generic("not used"))
+    }
+}
+
+/// Covers for later files are generated while earlier files upload.
+#[tokio::test]
+async fn test_pipelined_upload_overlaps_cover_generation() -> Result<()> {
+    use base64::Engine;
+    use std::time::Duration;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use wx_uploader::models::PipelineLimits;
+    use wx_uploader::providers::{ProviderConfig, UniversalAIClient};
+    use wx_uploader::wechat::{ProcessOptions, upload_files};
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_delay(Duration::from_millis(20))
+                .set_body_json(serde_json::json!({
+                    "choices": [{ "message": { "content": "A quiet harbor at dawn." } }]
+                })),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/images/generations"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_delay(Duration::from_millis(30))
+                .set_body_json(serde_json::json!({
+                    "data": [{
+                        "b64_json": base64::engine::general_purpose::STANDARD.encode(sample_png())
+                    }]
+                })),
+        )
+        .mount(&server)
+        .await;
+
+    let ai_client = UniversalAIClient::new(
+        ProviderConfig::OpenAI {
+            api_key: "test-key".to_string(),
+            base_url: Some(server.uri()),
+        },
+        None,
+    );
+
+    let temp_dir = TempDir::new().unwrap();
+    let files: Vec<_> = ["a", "b", "c"]
+        .iter()
+        .map(|name| {
+            let file = temp_dir.path().join(format!("{}.md", name));
+            fs::write(&file, format!("---\ntitle: Post {}\n---\n# Post\n", name)).unwrap();
+            file
+        })
+        .collect();
+
+    let uploader = SlowCountingUploader::new(temp_dir.path(), Duration::from_millis(250));
+    let options = ProcessOptions {
+        pipeline: Some(PipelineLimits {
+            ai_jobs: 1,
+            upload_jobs: 1,
+        }),
+        ..Default::default()
+    };
+
+    let report = upload_files(&uploader, Some(&ai_client), &files, &options).await;
+
+    assert_eq!(report.uploaded(), 3, "{:?}", report);
+    let paths: Vec<_> = report.files.iter().map(|f| f.path.clone()).collect();
+    assert_eq!(paths, files);
+
+    // Sequentially only the first cover would exist when the first upload ends
+    let counts = uploader.covers_after_upload.lock().unwrap().clone();
+    assert!(counts[0] >= 2, "no overlap, covers after each upload: {:?}", counts);
+    assert_eq!(
+        uploader.max_active.load(std::sync::atomic::Ordering::SeqCst),
+        1
+    );
+
+    // Both frontmatter writes of every file landed: the cover, then the draft status
+    for (index, file) in files.iter().enumerate() {
+        let (frontmatter, _body) = parse_markdown_file(file).await?;
+        assert!(frontmatter.cover.is_some(), "{}", file.display());
+        assert!(frontmatter.publication_status().is_draft(), "{}", file.display());
+        assert_eq!(
+            frontmatter.draft_id.as_deref(),
+            Some(format!("draft_{}", index + 1).as_str())
+        );
+    }
+
+    Ok(())
+}
+
+/// Once the AI image budget is used up, files needing a cover are deferred and the rest still upload.
+#[tokio::test]
+async fn test_image_budget_defers_covers_halfway_through_a_batch() -> Result<()> {
+    use base64::Engine;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use wx_uploader::budget::BudgetLimits;
+    use wx_uploader::models::PipelineLimits;
+    use wx_uploader::providers::{ProviderConfig, UniversalAIClient};
+    use wx_uploader::report::FileOutcome;
+    use wx_uploader::wechat::{ProcessOptions, upload_files};
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "choices": [{ "message": { "content": "A quiet harbor at dawn." } }]
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/images/generations"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "data": [{ "b64_json": base64::engine::general_purpose::STANDARD.encode(sample_png()) }]
+        })))
+        .expect(4)
+        .mount(&server)
+        .await;
+
+    let ai_client = |max_images| {
+        UniversalAIClient::new(
+            ProviderConfig::OpenAI {
+                api_key: "test-key".to_string(),
+                base_url: Some(server.uri()),
+            },
+            None,
+        )
+        .with_budget(BudgetLimits {
+            max_images,
+            max_cost: 0.0,
+        })
+    };
+
+    // Sequential: the first two files use up the budget, the third has its own cover
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("c.png"), b"existing cover").unwrap();
+    let files: Vec<_> = ["a", "b", "c", "d"]
+        .iter()
+        .map(|name| {
+            let file = temp_dir.path().join(format!("{}.md", name));
+            let cover = if *name == "c" { "cover: c.png\n" } else { "" };
+            fs::write(&file, format!("---\ntitle: Post {}\n{}---\n# Post\n", name, cover)).unwrap();
+            file
+        })
+        .collect();
+
+    let uploader = SlowCountingUploader::new(temp_dir.path(), std::time::Duration::ZERO);
+    let client = ai_client(2);
+    let report = upload_files(&uploader, Some(&client), &files, &ProcessOptions::default()).await;
+
+    let outcomes: Vec<_> = report.files.iter().map(|f| &f.outcome).collect();
+    assert!(matches!(outcomes[0], FileOutcome::Uploaded { .. }));
+    assert!(matches!(outcomes[1], FileOutcome::Uploaded { .. }));
+    assert!(matches!(outcomes[2], FileOutcome::Uploaded { .. }));
+    assert_eq!(outcomes[3], &FileOutcome::CoverDeferred);
+    assert_eq!(report.summary(), "3 uploaded, 0 skipped, 0 failed, 1 cover deferred");
+    assert_eq!(client.budget().generated(), 2);
+
+    // The deferred file is left as it was for the next run
+    let (frontmatter, _body) = parse_markdown_file(&files[3]).await?;
+    assert_eq!(frontmatter.cover, None);
+    assert!(!frontmatter.publication_status().is_draft());
+
+    // Pipelined: concurrent prepare tasks share one counter
+    let temp_dir = TempDir::new().unwrap();
+    let files: Vec<_> = (0..4)
+        .map(|index| {
+            let file = temp_dir.path().join(format!("{}.md", index));
+            fs::write(&file, format!("---\ntitle: Post {}\n---\n# Post\n", index)).unwrap();
+            file
+        })
+        .collect();
+    let options = ProcessOptions {
+        pipeline: Some(PipelineLimits {
+            ai_jobs: 4,
+            upload_jobs: 2,
+        }),
+        ..Default::default()
+    };
+
+    let uploader = SlowCountingUploader::new(temp_dir.path(), std::time::Duration::ZERO);
+    let client = ai_client(2);
+    let report = upload_files(&uploader, Some(&client), &files, &options).await;
+
+    assert_eq!((report.uploaded(), report.cover_deferred()), (2, 2), "{:?}", report);
+    assert_eq!(client.budget().generated(), 2);
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Cover variations are saved as numbered candidates and the first becomes the
+/// cover when nobody can be asked; frontmatter overrides the run-wide count.
+#[tokio::test]
+async fn test_cover_variations_save_candidates_and_pick_first() -> Result<()> {
+    use base64::Engine;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use wx_uploader::providers::{ProviderConfig, UniversalAIClient};
+    use wx_uploader::wechat::{ProcessOptions, upload_files};
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "choices": [{ "message": { "content": "A quiet harbor at dawn." } }]
+        })))
+        .expect(2)
+        .mount(&server)
+        .await;
+    // dall-e-3 takes one request per candidate: three for a.md, two for b.md
+    Mock::given(method("POST"))
+        .and(path("/images/generations"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "data": [{ "b64_json": base64::engine::general_purpose::STANDARD.encode(sample_png()) }]
+        })))
+        .expect(5)
+        .mount(&server)
+        .await;
+    let ai_client = UniversalAIClient::new(
+        ProviderConfig::OpenAI {
+            api_key: "test-key".to_string(),
+            base_url: Some(server.uri()),
+        },
+        None,
+    );
+
+    let temp_dir = TempDir::new().unwrap();
+    let a = temp_dir.path().join("a.md");
+    let b = temp_dir.path().join("b.md");
+    fs::write(&a, "---\ntitle: Post A\n---\n# A\n").unwrap();
+    fs::write(
+        &b,
+        "---\ntitle: Post B\ncover: covers/b.png\ncover_variations: 2\n---\n# B\n",
+    )
+    .unwrap();
+
+    let options = ProcessOptions {
+        cover_variations: 3,
+        ..Default::default()
+    };
+    let uploader = SlowCountingUploader::new(temp_dir.path(), std::time::Duration::ZERO);
+    let report = upload_files(&uploader, Some(&ai_client), &[a.clone(), b.clone()], &options).await;
+    assert_eq!(report.uploaded(), 2, "{:?}", report);
+
+    let candidates = ["a-cover-1.png", "a-cover-2.png", "a-cover-3.png", "b-cover-1.png", "b-cover-2.png"];
+    for candidate in candidates {
+        assert!(temp_dir.path().join(candidate).exists(), "{}", candidate);
+    }
+    assert!(!temp_dir.path().join("b-cover-3.png").exists());
+
+    let (frontmatter, _body) = parse_markdown_file(&a).await?;
+    assert_eq!(frontmatter.cover.as_deref(), Some("a-cover-1.png"));
+
+    // A cover named in the frontmatter keeps its name and gets the first candidate
+    let (frontmatter, _body) = parse_markdown_file(&b).await?;
+    assert_eq!(frontmatter.cover.as_deref(), Some("covers/b.png"));
+    assert_eq!(fs::read(temp_dir.path().join("covers/b.png")).unwrap(), sample_png());
+
+    server.verify().await;
+    Ok(())
+}
+
+/// The cover candidate is chosen from the answer when asked, and a run that
+/// may not ask fails the file instead of picking one.
+#[tokio::test]
+async fn test_cover_variations_choice_follows_prompt_policy() -> Result<()> {
+    use base64::Engine;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use wx_uploader::prompt::{PromptPolicy, Prompter};
+    use wx_uploader::providers::{ProviderConfig, UniversalAIClient};
+    use wx_uploader::wechat::{ProcessOptions, upload_files};
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "choices": [{ "message": { "content": "A quiet harbor at dawn." } }]
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/images/generations"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "data": [{ "b64_json": base64::engine::general_purpose::STANDARD.encode(sample_png()) }]
+        })))
+        .mount(&server)
+        .await;
+    let ai_client = UniversalAIClient::new(
+        ProviderConfig::OpenAI {
+            api_key: "test-key".to_string(),
+            base_url: Some(server.uri()),
+        },
+        None,
+    );
+
+    let temp_dir = TempDir::new().unwrap();
+    let article = temp_dir.path().join("a.md");
+    let original = "---\ntitle: Post A\ncover_variations: 2\n---\n# A\n";
+    fs::write(&article, original).unwrap();
+    let uploader = SlowCountingUploader::new(temp_dir.path(), std::time::Duration::ZERO);
+
+    let options = ProcessOptions {
+        prompt: Prompter::with_input(PromptPolicy::NoInput, std::io::Cursor::new("2\n")),
+        ..Default::default()
+    };
+    let files = std::slice::from_ref(&article);
+    let report = upload_files(&uploader, Some(&ai_client), files, &options).await;
+    assert_eq!(report.failed(), 1, "{:?}", report);
+    assert_eq!(fs::read_to_string(&article).unwrap(), original);
+
+    let options = ProcessOptions {
+        prompt: Prompter::with_input(PromptPolicy::Ask, std::io::Cursor::new("2\n")),
+        ..Default::default()
+    };
+    let files = std::slice::from_ref(&article);
+    let report = upload_files(&uploader, Some(&ai_client), files, &options).await;
+    assert_eq!(report.uploaded(), 1, "{:?}", report);
+    let (frontmatter, _body) = parse_markdown_file(&article).await?;
+    assert_eq!(frontmatter.cover.as_deref(), Some("a-cover-2.png"));
+    Ok(())
+}
+
+/// Generated covers are saved as JPEG with a matching name, and existing
+/// covers are only converted with `--convert-covers`.
+#[tokio::test]
+async fn test_cover_format_transcodes_generated_and_converted_covers() -> Result<()> {
+    use base64::Engine;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use wx_uploader::cover_format::{CoverEncoding, CoverFormat};
+    use wx_uploader::providers::{ProviderConfig, UniversalAIClient};
+    use wx_uploader::wechat::{ProcessOptions, upload_files};
+
+    let mut png = std::io::Cursor::new(Vec::new());
+    image::RgbImage::from_pixel(32, 32, image::Rgb([20, 40, 80]))
+        .write_to(&mut png, image::ImageFormat::Png)
+        .unwrap();
+    let png = png.into_inner();
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "choices": [{ "message": { "content": "A quiet harbor at dawn." } }]
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/images/generations"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "data": [{ "b64_json": base64::engine::general_purpose::STANDARD.encode(&png) }]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let encoding = CoverEncoding {
+        format: CoverFormat::Jpeg,
+        quality: 85,
+    };
+    let ai_client = UniversalAIClient::new(
+        ProviderConfig::OpenAI {
+            api_key: "test-key".to_string(),
+            base_url: Some(server.uri()),
+        },
+        None,
+    )
+    .with_cover_encoding(encoding);
+
+    let temp_dir = TempDir::new().unwrap();
+    let generated = temp_dir.path().join("generated.md");
+    let existing = temp_dir.path().join("existing.md");
+    fs::write(&generated, "---\ntitle: Generated\n---\n# Generated\n").unwrap();
+    fs::write(&existing, "---\ntitle: Existing\ncover: images/existing.png\n---\n# Existing\n")
+        .unwrap();
+    fs::create_dir(temp_dir.path().join("images")).unwrap();
+    fs::write(temp_dir.path().join("images/existing.png"), &png).unwrap();
+    let files = [generated.clone(), existing.clone()];
+
+    // Without --convert-covers the existing PNG is uploaded as it is
+    let options = ProcessOptions {
+        cover_encoding: encoding,
+        ..Default::default()
+    };
+    let uploader = SlowCountingUploader::new(temp_dir.path(), std::time::Duration::ZERO);
+    let report = upload_files(&uploader, Some(&ai_client), &files, &options).await;
+    assert_eq!(report.uploaded(), 2, "{:?}", report);
+
+    let (frontmatter, _body) = parse_markdown_file(&generated).await?;
+    let cover = frontmatter.cover.unwrap();
+    assert!(cover.starts_with("generated_cover_") && cover.ends_with(".jpg"), "{}", cover);
+    assert!(fs::read(temp_dir.path().join(&cover)).unwrap().starts_with(b"\xff\xd8\xff"));
+    let (frontmatter, _body) = parse_markdown_file(&existing).await?;
+    assert_eq!(frontmatter.cover.as_deref(), Some("images/existing.png"));
+
+    let options = ProcessOptions {
+        convert_covers: true,
+        ..options
+    };
+    let report = upload_files(&uploader, Some(&ai_client), &files[1..], &options).await;
+    assert_eq!(report.uploaded(), 1, "{:?}", report);
+
+    let (frontmatter, _body) = parse_markdown_file(&existing).await?;
+    assert_eq!(frontmatter.cover.as_deref(), Some("images/existing.jpg"));
+    let converted = fs::read(temp_dir.path().join("images/existing.jpg")).unwrap();
+    assert!(converted.starts_with(b"\xff\xd8\xff"));
+    assert!(!temp_dir.path().join("images/existing.png").exists());
+
+    server.verify().await;
+    Ok(())
+}
+
+/// `require_cover` fails files left without a cover image, and `fail_fast`
+/// stops the run at the first of them.
+#[tokio::test]
+async fn test_require_cover_fails_files_without_cover() -> Result<()> {
+    use wx_uploader::wechat::{ProcessOptions, upload_files};
+
+    let temp_dir = TempDir::new().unwrap();
+    let with_cover = temp_dir.path().join("a.md");
+    let without = temp_dir.path().join("b.md");
+    let disabled = temp_dir.path().join("c.md");
+    fs::write(&with_cover, "---\ntitle: A\ncover: a.png\n---\n# A\n").unwrap();
+    fs::write(temp_dir.path().join("a.png"), b"png").unwrap();
+    fs::write(&without, "---\ntitle: B\n---\n# B\n").unwrap();
+    fs::write(&disabled, "---\ntitle: C\ncover: none\n---\n# C\n").unwrap();
+    let files = [with_cover.clone(), without.clone(), disabled.clone()];
+
+    let uploader = SlowCountingUploader::new(temp_dir.path(), std::time::Duration::ZERO);
+    let options = ProcessOptions {
+        require_cover: true,
+        ..Default::default()
+    };
+    let report = upload_files(&uploader, None, &files, &options).await;
+    assert_eq!((report.uploaded(), report.failed()), (1, 2), "{:?}", report);
+    let failures: Vec<_> = report.failures().map(|(_, error)| error).collect();
+    assert!(failures[0].contains("no cover after cover processing"), "{}", failures[0]);
+    assert!(failures[1].contains("`cover: none`"), "{}", failures[1]);
+
+    // Nothing was written to the failed files
+    let (frontmatter, _body) = parse_markdown_file(&without).await?;
+    assert_eq!(frontmatter.published, None);
+
+    let options = ProcessOptions {
+        fail_fast: true,
+        ..options
+    };
+    let report = upload_files(&uploader, None, &files[1..], &options).await;
+    assert_eq!(report.files.len(), 1, "{:?}", report);
+    assert_eq!(report.failed(), 1);
+    Ok(())
+}
+
+/// With sidecar frontmatter, uploads record their draft in the sidecar and leave the body untouched.
+#[tokio::test]
+async fn test_sidecar_frontmatter_upload_leaves_body_untouched() -> Result<()> {
+    use wx_uploader::markdown::{ParseOptions, parse_frontmatter_file_with};
+    use wx_uploader::wechat::{ProcessOptions, upload_files};
+
+    let temp_dir = TempDir::new().unwrap();
+    let article = temp_dir.path().join("article.md");
+    let body = "# Generated\n\nNo frontmatter here.\n";
+    fs::write(&article, body).unwrap();
+    fs::write(
+        temp_dir.path().join("article.md.meta.yaml"),
+        "title: Sidecar Title\ncover: a.png\n",
+    )
+    .unwrap();
+    fs::write(temp_dir.path().join("a.png"), b"png").unwrap();
+
+    let uploader = SlowCountingUploader::new(temp_dir.path(), std::time::Duration::ZERO);
+    let options = ProcessOptions {
+        sidecar_frontmatter: true,
+        ..Default::default()
+    };
+    let report = upload_files(&uploader, None, std::slice::from_ref(&article), &options).await;
+    assert_eq!(report.uploaded(), 1, "{:?}", report);
+
+    assert_eq!(fs::read_to_string(&article).unwrap(), body);
+    let parse_options = ParseOptions {
+        sidecar_frontmatter: true,
+        ..Default::default()
+    };
+    let frontmatter = parse_frontmatter_file_with(&article, &parse_options).await?;
+    assert_eq!(frontmatter.title.as_deref(), Some("Sidecar Title"));
+    assert_eq!(frontmatter.publication_status(), PublicationStatus::Draft);
+    assert_eq!(frontmatter.draft_id.as_deref(), Some("draft_1"));
+    Ok(())
+}
+
+/// A sidecar next to a file with frontmatter is merged over it and gets the upload's write-backs.
+#[tokio::test]
+async fn test_sidecar_over_embedded_frontmatter_upload() -> Result<()> {
+    use wx_uploader::markdown::{ParseOptions, parse_frontmatter_file_with};
+    use wx_uploader::wechat::{ProcessOptions, upload_files};
+
+    let temp_dir = TempDir::new().unwrap();
+    let article = temp_dir.path().join("export.md");
+    let content = "---\ntitle: Exported\ncover: old.png\npublished: true\n---\n# Exported\n";
+    fs::write(&article, content).unwrap();
+    let sidecar = temp_dir.path().join("export.md.meta.yaml");
+    fs::write(&sidecar, "cover: a.png\npublished: false\n").unwrap();
+    fs::write(temp_dir.path().join("a.png"), b"png").unwrap();
+
+    let uploader = SlowCountingUploader::new(temp_dir.path(), std::time::Duration::ZERO);
+    let options = ProcessOptions {
+        sidecar_frontmatter: true,
+        ..Default::default()
+    };
+    let report = upload_files(&uploader, None, std::slice::from_ref(&article), &options).await;
+    assert_eq!(report.uploaded(), 1, "{:?}", report);
+
+    assert_eq!(fs::read_to_string(&article).unwrap(), content);
+    let written = fs::read_to_string(&sidecar).unwrap();
+    assert!(!written.contains("title"), "{}", written);
+    let parse_options = ParseOptions {
+        sidecar_frontmatter: true,
+        ..Default::default()
+    };
+    let frontmatter = parse_frontmatter_file_with(&article, &parse_options).await?;
+    assert_eq!(frontmatter.title.as_deref(), Some("Exported"));
+    assert_eq!(frontmatter.cover.as_deref(), Some("a.png"));
+    assert_eq!(frontmatter.publication_status(), PublicationStatus::Draft);
+    assert_eq!(frontmatter.draft_id.as_deref(), Some("draft_1"));
+    Ok(())
+}
+
+/// A run backs up each file before its first modification, and directory walks skip the backups.
+#[tokio::test]
+async fn test_backup_before_first_modification() -> Result<()> {
+    use std::sync::Arc;
+    use wx_uploader::backup::{self, Backup};
+    use wx_uploader::wechat::{ProcessOptions, collect_markdown_files, upload_files};
+
+    let temp_dir = TempDir::new().unwrap();
+    let article = temp_dir.path().join("posts/a.md");
+    fs::create_dir_all(article.parent().unwrap()).unwrap();
+    let original = "---\ntitle: A\ncover: a.png\n---\n# A\n";
+    fs::write(&article, original).unwrap();
+    fs::write(temp_dir.path().join("posts/a.png"), b"png").unwrap();
+
+    let uploader = SlowCountingUploader::new(temp_dir.path(), std::time::Duration::ZERO);
+    let options = ProcessOptions {
+        auto_slug: true,
+        backup: Some(Arc::new(Backup::new(temp_dir.path(), 3, "20261017-120000"))),
+        ..Default::default()
+    };
+    let report = upload_files(&uploader, None, std::slice::from_ref(&article), &options).await;
+    assert_eq!(report.uploaded(), 1, "{:?}", report);
+
+    // The slug and the draft ID were written, the backup holds the original
+    let uploaded = fs::read_to_string(&article).unwrap();
+    assert!(uploaded.contains("slug: a") && uploaded.contains("draft_id"), "{}", uploaded);
+    let set_dir = backup::backup_root(temp_dir.path()).join("20261017-120000");
+    assert_eq!(fs::read_to_string(set_dir.join("posts/a.md")).unwrap(), original);
+    assert_eq!(collect_markdown_files(temp_dir.path()), vec![article.clone()]);
+
+    backup::restore(temp_dir.path(), "20261017-120000").await?;
+    assert_eq!(fs::read_to_string(&article).unwrap(), original);
+    Ok(())
+}
+
+/// Files with a cover template get a rendered cover without any AI calls, even with AI configured.
+#[tokio::test]
+async fn test_cover_template_wins_over_ai() -> Result<()> {
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use wx_uploader::cover_template::CoverTemplate;
+    use wx_uploader::providers::{ProviderConfig, UniversalAIClient};
+    use wx_uploader::wechat::{ProcessOptions, upload_files};
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0)
+        .mount(&server)
+        .await;
+    let ai_client = UniversalAIClient::new(
+        ProviderConfig::OpenAI {
+            api_key: "test-key".to_string(),
+            base_url: Some(server.uri()),
+        },
+        None,
+    );
+
+    let temp_dir = TempDir::new().unwrap();
+    image::RgbaImage::from_pixel(900, 383, image::Rgba([0, 0, 0, 255]))
+        .save(temp_dir.path().join("weekly.png"))
+        .unwrap();
+    let font =
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/DejaVuSansMono.ttf");
+
+    // The font comes from the configuration, the background from the frontmatter
+    let article = temp_dir.path().join("digest.md");
+    fs::write(
+        &article,
+        "---\ntitle: Weekly Digest 42\nslug: weekly-42\n\
+         cover_template:\n  background: weekly.png\n  font_size: 48\n---\n# Digest\n",
+    )
+    .unwrap();
+    let named = temp_dir.path().join("named.md");
+    fs::write(
+        &named,
+        "---\ntitle: Named\ncover: covers/named.png\n\
+         cover_template:\n  background: weekly.png\n---\n# Named\n",
+    )
+    .unwrap();
+
+    let options = ProcessOptions {
+        cover_template: Some(CoverTemplate {
+            font: Some(font.display().to_string()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let uploader = SlowCountingUploader::new(temp_dir.path(), std::time::Duration::ZERO);
+    let files = [article.clone(), named.clone()];
+    let report = upload_files(&uploader, Some(&ai_client), &files, &options).await;
+    assert_eq!(report.uploaded(), 2, "{:?}", report);
+
+    // A generated name after the slug, like an AI cover
+    let (frontmatter, _body) = parse_markdown_file(&article).await?;
+    let cover = frontmatter.cover.unwrap();
+    assert!(cover.starts_with("weekly-42_cover_"), "{}", cover);
+    let rendered = image::open(temp_dir.path().join(&cover)).unwrap().into_rgba8();
+    assert_eq!(rendered.dimensions(), (900, 383));
+    assert!(rendered.pixels().any(|p| p[0] > 128));
+
+    // A missing cover named in the frontmatter is rendered at that path
+    let (frontmatter, _body) = parse_markdown_file(&named).await?;
+    assert_eq!(frontmatter.cover.as_deref(), Some("covers/named.png"));
+    assert!(temp_dir.path().join("covers/named.png").exists());
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Runs that never reach WeChat work with bogus credentials; the credential
+/// error only surfaces once a file is uploaded.
+#[tokio::test]
+async fn test_wechat_client_created_on_first_upload() -> Result<()> {
+    use wx_uploader::{Config, WxUploader};
+
+    let temp_dir = TempDir::new().unwrap();
+    let published = temp_dir.path().join("published.md");
+    fs::write(&published, "---\ntitle: Done\npublished: \"true\"\n---\n# Done\n").unwrap();
+
+    let config = Config::new("bogus".to_string(), "bogus".to_string(), None, false);
+    let uploader = WxUploader::new(config).await?;
+    assert_eq!(uploader.list_accounts().len(), 1);
+
+    let report = uploader.process_directory(temp_dir.path()).await?;
+    assert_eq!(report.summary(), "0 uploaded, 1 skipped, 0 failed");
+    assert!(!report.has_auth_failure());
+
+    // The first upload creates the client and fails on the credentials
+    let draft = temp_dir.path().join("draft.md");
+    fs::write(&draft, "---\ntitle: Draft\n---\n# Draft\n").unwrap();
+    let report = uploader.process_directory(temp_dir.path()).await?;
+    assert_eq!(report.failed(), 1);
+    assert!(report.has_auth_failure());
+    assert!(uploader.check_auth().await.unwrap_err().is_auth());
+    Ok(())
+}
+
+/// Writes an executable shell script for a hook test
+#[cfg(unix)]
+fn write_hook_script(path: &std::path::Path, script: &str) {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::write(path, format!("#!/bin/sh\n{}", script)).unwrap();
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+/// A failing pre_upload hook skips its file, post_upload sees the draft, and
+/// --strict-hooks turns the skip into a failure.
+#[cfg(unix)]
+#[tokio::test]
+async fn test_upload_hooks_skip_fail_and_env() -> Result<()> {
+    use wx_uploader::hooks::Hooks;
+    use wx_uploader::wechat::{ProcessOptions, upload_files};
+
+    let temp_dir = TempDir::new().unwrap();
+    let log = temp_dir.path().join("hooks.log");
+    let pre = temp_dir.path().join("pre.sh");
+    let post = temp_dir.path().join("post.sh");
+    write_hook_script(
+        &pre,
+        &format!(
+            "echo \"pre $WX_STATUS $WX_TITLE\" >> '{}'\n\
+             case \"$WX_TITLE\" in *WIP*) echo 'vale: 3 errors' >&2; exit 1;; esac\n",
+            log.display()
+        ),
+    );
+    write_hook_script(
+        &post,
+        &format!(
+            "echo \"post $WX_STATUS $WX_DRAFT_ID $(basename \"$WX_FILE\")\" >> '{}'\n",
+            log.display()
+        ),
+    );
+
+    let ready = temp_dir.path().join("ready.md");
+    let wip = temp_dir.path().join("wip.md");
+    fs::write(&ready, "---\ntitle: Ready\n---\n# Ready\n").unwrap();
+    fs::write(&wip, "---\ntitle: WIP notes\n---\n# WIP\n").unwrap();
+
+    let mut options = ProcessOptions {
+        hooks: Hooks {
+            pre_upload: Some(pre.display().to_string()),
+            post_upload: Some(post.display().to_string()),
+            ..Hooks::default()
+        },
+        ..Default::default()
+    };
+    let uploader = SlowCountingUploader::new(temp_dir.path(), std::time::Duration::ZERO);
+    let files = [ready.clone(), wip.clone()];
+    let report = upload_files(&uploader, None, &files, &options).await;
+    assert_eq!(report.uploaded(), 1, "{:?}", report);
+    assert_eq!(report.skipped(), 1);
+    assert_eq!(
+        fs::read_to_string(&log).unwrap(),
+        "pre pending Ready\npost uploaded draft_1 ready.md\npre pending WIP notes\n"
+    );
+
+    // The skipped file is still unpublished, so strict mode runs it again and fails it
+    options.strict_hooks = true;
+    let report = upload_files(&uploader, None, &files[1..], &options).await;
+    assert_eq!(report.failed(), 1);
+    let (_, error) = report.failures().next().unwrap();
+    assert_eq!(error, "pre_upload hook failed: exited with status 1");
+    let (frontmatter, _body) = parse_markdown_file(&wip).await?;
+    assert!(!frontmatter.publication_status().is_published());
+    Ok(())
+}
+
+/// With write-back disabled, a run over a read-only directory succeeds and leaves it untouched.
+#[cfg(unix)]
+#[tokio::test]
+async fn test_no_write_back_with_read_only_directory() -> Result<()> {
+    use base64::Engine;
+    use std::os::unix::fs::PermissionsExt;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+    use wx_uploader::models::AiProvider;
+    use wx_uploader::state::UploadState;
+    use wx_uploader::{Config, WxUploader};
+
+    let server = mock_wechat_server().await;
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/material/add_material"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "media_id": "cover_media_id",
+            "url": "http://mmbiz.qpic.cn/mock/cover.png"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    mount_drafts(&server, "draft_media_id", 1).await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "choices": [{ "message": { "content": "A quiet harbor at dawn." } }]
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/images/generations"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "data": [{ "b64_json": base64::engine::general_purpose::STANDARD.encode(sample_png()) }]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let content_dir = TempDir::new().unwrap();
+    let state_dir = TempDir::new().unwrap();
+    let article = content_dir.path().join("article.md");
+    let original = "---\ntitle: Read Only\n---\n# Hello\n\nNo cover yet.\n";
+    fs::write(&article, original).unwrap();
+
+    fs::set_permissions(&article, fs::Permissions::from_mode(0o444)).unwrap();
+    fs::set_permissions(content_dir.path(), fs::Permissions::from_mode(0o555)).unwrap();
+
+    let mut config = Config::new(
+        "wx_mock_app".to_string(),
+        "mock_secret".to_string(),
+        Some(AiProvider::OpenAI {
+            api_key: "test-key".to_string(),
+            base_url: Some(server.uri()),
+        }),
+        false,
+    );
+    config.set_wechat_base_url(server.uri());
+    config.write_back = false;
+    config.state_file = Some(state_dir.path().join("state.json"));
+    let state_file = config.state_file_path();
+
+    let uploader = WxUploader::new(config).await?;
+    let report = uploader.process_directory(content_dir.path()).await;
+
+    fs::set_permissions(content_dir.path(), fs::Permissions::from_mode(0o755)).unwrap();
+    let report = report?;
+    assert_eq!(report.uploaded(), 1, "{:?}", report);
+
+    // Nothing in the source tree changed
+    assert_eq!(fs::read_to_string(&article).unwrap(), original);
+    let entries: Vec<_> = fs::read_dir(content_dir.path())
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    assert_eq!(entries, vec![std::ffi::OsString::from("article.md")]);
+
+    // The upload is recorded in the external state file instead
+    let state = UploadState::load(&state_file).await?;
+    let entry = state.get(&article).expect("upload recorded in state file");
+    assert_eq!(entry.published, "draft");
+    assert_eq!(entry.draft_id, "draft_media_id");
+
+    // A second run skips the recorded file instead of creating another draft
+    let report = uploader.process_directory(content_dir.path()).await?;
+    assert_eq!(report.uploaded(), 0, "{:?}", report);
+    assert_eq!(report.skipped(), 1, "{:?}", report);
+
+    server.verify().await;
+    Ok(())
+}
+
+/// With status updates disabled, the cover is written back but `published` is left verbatim.
+#[tokio::test]
+async fn test_no_status_update_preserves_published_field() -> Result<()> {
+    use base64::Engine;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+    use wx_uploader::models::AiProvider;
+    use wx_uploader::state::UploadState;
+    use wx_uploader::{Config, WxUploader};
+
+    let server = mock_wechat_server().await;
+    mount_drafts(&server, "draft_media_id", 1).await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "choices": [{ "message": { "content": "A quiet harbor at dawn." } }]
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/images/generations"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "data": [{ "b64_json": base64::engine::general_purpose::STANDARD.encode(sample_png()) }]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let content_dir = TempDir::new().unwrap();
+    let state_dir = TempDir::new().unwrap();
+    let article = content_dir.path().join("article.md");
+    fs::write(
+        &article,
+        "---\ntitle: Managed Elsewhere\npublished: review\n---\n# Hello\n\nNo cover yet.\n",
+    )
+    .unwrap();
+
+    let mut config = Config::new(
+        "wx_mock_app".to_string(),
+        "mock_secret".to_string(),
+        Some(AiProvider::OpenAI {
+            api_key: "test-key".to_string(),
+            base_url: Some(server.uri()),
+        }),
+        false,
+    );
+    config.set_wechat_base_url(server.uri());
+    config.set_draft_status = false;
+    config.state_file = Some(state_dir.path().join("state.json"));
+    let state_file = config.state_file_path();
+
+    let uploader = WxUploader::new(config).await?;
+    let report = uploader.process_directory(content_dir.path()).await?;
+    assert_eq!(report.uploaded(), 1, "{:?}", report);
+
+    // The generated cover is written back, the published field is not touched
+    let content = fs::read_to_string(&article).unwrap();
+    assert!(content.contains("published: review\n"), "{}", content);
+    assert!(!content.contains("draft_media_id"), "{}", content);
+    let (frontmatter, _body) = parse_markdown_file(&article).await?;
+    assert_eq!(
+        frontmatter.published.as_ref().and_then(|p| p.raw()),
+        Some("review")
+    );
+    let cover = frontmatter.cover.expect("generated cover recorded");
+    assert!(content_dir.path().join(&cover).exists());
+
+    let state = UploadState::load(&state_file).await?;
+    let entry = state.get(&article).expect("upload recorded in state file");
+    assert_eq!(entry.draft_id, "draft_media_id");
+
+    server.verify().await;
+    Ok(())
+}
+
+/// With `--reconcile`, a file whose title already has a draft is not uploaded again.
+#[tokio::test]
+async fn test_reconcile_skips_files_with_existing_drafts() -> Result<()> {
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, ResponseTemplate};
+    use wx_uploader::{FileOutcome, WxUploader};
+
+    let server = mock_wechat_server().await;
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/draft/batchget"))
+        .and(body_partial_json(serde_json::json!({ "offset": 0, "no_content": 1 })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "total_count": 2,
+            "item_count": 2,
+            "item": [
+                {
+                    "media_id": "existing_draft",
+                    "content": { "news_item": [{ "title": "Already There" }] },
+                    "update_time": 1700000000
+                },
+                {
+                    "media_id": "unrelated_draft",
+                    "content": { "news_item": [{ "title": "Something Else" }] },
+                    "update_time": 1690000000
+                }
+            ]
+        })))
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/draft/add"))
+        .and(body_partial_json(serde_json::json!({ "articles": [{ "title": "Brand New" }] })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "media_id": "new_draft"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("cover.png"), b"fake cover").unwrap();
+    let existing = temp_dir.path().join("existing.md");
+    let fresh = temp_dir.path().join("fresh.md");
+    fs::write(&existing, "---\ntitle: Already There\ncover: cover.png\n---\n# Hi\n").unwrap();
+    fs::write(&fresh, "---\ntitle: Brand New\ncover: cover.png\n---\n# Hi\n").unwrap();
+
+    let mut config = mock_config(&server);
+    config.reconcile = true;
+
+    let uploader = WxUploader::new(config).await?;
+    assert_eq!(uploader.draft_index().await?.find("Already There"), Some("existing_draft"));
+    let report = uploader.process_directory(temp_dir.path()).await?;
+
+    assert_eq!(report.uploaded(), 1, "{:?}", report);
+    let skipped = report.files.iter().find(|f| f.path == existing).unwrap();
+    assert_eq!(
+        skipped.outcome,
+        FileOutcome::Skipped {
+            reason: "draft existing_draft already exists on WeChat".to_string()
+        }
+    );
+
+    // The recovered draft is written back like a fresh upload would be
+    let (frontmatter, _body) = parse_markdown_file(&existing).await?;
+    assert!(frontmatter.publication_status().is_draft());
+    assert_eq!(frontmatter.draft_id.as_deref(), Some("existing_draft"));
+    let (frontmatter, _body) = parse_markdown_file(&fresh).await?;
+    assert_eq!(frontmatter.draft_id.as_deref(), Some("new_draft"));
+
+    server.verify().await;
+    Ok(())
+}
+
+/// `cover: none` never generates a cover and uploads with the account's `default_cover`.
+#[tokio::test]
+async fn test_cover_none_uses_account_default_cover() -> Result<()> {
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, ResponseTemplate};
+    use wx_uploader::models::AiProvider;
+    use wx_uploader::{FileOutcome, WxUploader};
+
+    let server = mock_wechat_server().await;
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/material/add_material"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "media_id": "default_cover_media_id",
+            "url": "http://mmbiz.qpic.cn/mock/default.png"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/draft/add"))
+        .and(body_partial_json(serde_json::json!({
+            "articles": [{ "thumb_media_id": "default_cover_media_id", "show_cover_pic": 0 }]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "media_id": "draft_media_id"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    // A configured AI provider is never asked for a cover
+    Mock::given(method("POST"))
+        .and(path("/images/generations"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let content_dir = TempDir::new().unwrap();
+    let assets_dir = TempDir::new().unwrap();
+    let default_cover = assets_dir.path().join("default.png");
+    fs::write(&default_cover, b"fake default cover").unwrap();
+    let article = content_dir.path().join("notice.md");
+    fs::write(&article, "---\ntitle: Notice\ncover: none\n---\nText only.\n").unwrap();
+
+    let ai_provider = Some(AiProvider::OpenAI {
+        api_key: "test-key".to_string(),
+        base_url: Some(server.uri()),
+    });
+    let mut config = mock_config(&server);
+    config.ai_provider = ai_provider;
+
+    // Without a default cover the draft would have no thumbnail
+    let uploader = WxUploader::new(config.clone()).await?;
+    let report = uploader.process_directory(content_dir.path()).await?;
+    let FileOutcome::Failed { error, .. } = &report.files[0].outcome else {
+        panic!("expected a failure: {:?}", report);
+    };
+    assert!(error.contains("default_cover"), "{}", error);
+
+    config.wechat_account.default_cover = Some(default_cover);
+    let uploader = WxUploader::new(config).await?;
+    let report = uploader.process_directory(content_dir.path()).await?;
+    assert_eq!(report.uploaded(), 1, "{:?}", report);
+
+    // The opt-out survives the frontmatter update and no cover file appears
+    let content = fs::read_to_string(&article).unwrap();
+    assert!(content.contains("cover: none\n"), "{}", content);
+    let (frontmatter, _body) = parse_markdown_file(&article).await?;
+    assert_eq!(frontmatter.draft_id.as_deref(), Some("draft_media_id"));
+    assert_eq!(fs::read_dir(content_dir.path()).unwrap().count(), 1);
+
+    server.verify().await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_default_cover_used_when_generation_is_unavailable() -> Result<()> {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+    use wx_uploader::WxUploader;
+
+    let server = mock_wechat_server().await;
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/material/add_material"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "media_id": "default_cover_media_id",
+            "url": "http://mmbiz.qpic.cn/mock/default.png"
+        })))
+        .mount(&server)
+        .await;
+    mount_drafts(&server, "draft_media_id", 2).await;
+
+    let assets_dir = TempDir::new().unwrap();
+    let default_cover = assets_dir.path().join("default.png");
+    fs::write(&default_cover, b"fake default cover").unwrap();
+
+    // No AI provider, so the missing cover cannot be generated
+    let mut config = mock_config(&server);
+    config.wechat_account.default_cover = Some(default_cover.clone());
+
+    // With write-back the default is copied next to the article and recorded
+    let content_dir = TempDir::new().unwrap();
+    let article = content_dir.path().join("weekly.md");
+    fs::write(&article, "---\ntitle: Weekly\n---\nNews.\n").unwrap();
+    let uploader = WxUploader::new(config.clone()).await?;
+    let report = uploader.process_directory(content_dir.path()).await?;
+    assert_eq!(report.uploaded(), 1, "{:?}", report);
+
+    let copied = content_dir.path().join("weekly_cover_default.png");
+    assert_eq!(fs::read(&copied).unwrap(), b"fake default cover");
+    let (frontmatter, _body) = parse_markdown_file(&article).await?;
+    assert_eq!(frontmatter.cover.as_deref(), Some("weekly_cover_default.png"));
+    assert_eq!(frontmatter.draft_id.as_deref(), Some("draft_media_id"));
+
+    // Without write-back the default is only used for this upload
+    let content_dir = TempDir::new().unwrap();
+    let article = content_dir.path().join("weekly.md");
+    let original = "---\ntitle: Weekly\n---\nNews.\n";
+    fs::write(&article, original).unwrap();
+    config.write_back = false;
+    let state_dir = TempDir::new().unwrap();
+    config.state_file = Some(state_dir.path().join("state.json"));
+    let uploader = WxUploader::new(config).await?;
+    let report = uploader.process_directory(content_dir.path()).await?;
+    assert_eq!(report.uploaded(), 1, "{:?}", report);
+
+    assert_eq!(fs::read_to_string(&article).unwrap(), original);
+    assert_eq!(fs::read_dir(content_dir.path()).unwrap().count(), 1);
+
+    server.verify().await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_inline_base64_images_are_uploaded_once() -> Result<()> {
+    use base64::Engine;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+    use wx_uploader::WxUploader;
+
+    let server = mock_wechat_server().await;
+    // The embedded images and the local file share their content
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/media/uploadimg"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "url": "http://mmbiz.qpic.cn/mock/inline.png"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    mount_drafts(&server, "draft_media_id", 1).await;
+
+    let temp_dir = TempDir::new().unwrap();
+    let image = b"fake inline image";
+    let data_uri = format!(
+        "data:image/png;base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(image)
+    );
+    fs::write(temp_dir.path().join("cover.png"), b"fake cover").unwrap();
+    fs::write(temp_dir.path().join("local.png"), image).unwrap();
+    let article = temp_dir.path().join("article.md");
+    fs::write(
+        &article,
+        format!(
+            "---\ntitle: Inline\ncover: cover.png\n---\n![dot]({uri})\n\n\
+             <img alt=\"dot\" src=\"{uri}\">\n\n![local](local.png)\n",
+            uri = data_uri
+        ),
+    )
+    .unwrap();
+
+    let config = mock_config(&server);
+    let uploader = WxUploader::new(config).await?;
+    uploader.upload_file(&article, true).await?;
+
+    // The draft references the uploaded image instead of the data URIs
+    let requests = server.received_requests().await.unwrap();
+    let draft = requests
+        .iter()
+        .find(|request| request.url.path() == "/cgi-bin/draft/add")
+        .unwrap();
+    let draft = String::from_utf8_lossy(&draft.body);
+    assert!(!draft.contains("data:image"), "{}", draft);
+    assert_eq!(draft.matches("http://mmbiz.qpic.cn/mock/inline.png").count(), 3);
+
+    // The article keeps its embedded images; only the upload copy was rewritten
+    let content = fs::read_to_string(&article).unwrap();
+    assert_eq!(content.matches(data_uri.as_str()).count(), 2);
+    assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 3);
+
+    server.verify().await;
+    Ok(())
+}
+
+/// A cover naming a directory is regenerated with AI, and is a clear error without it.
+#[tokio::test]
+async fn test_directory_cover_is_regenerated_or_rejected() -> Result<()> {
+    use base64::Engine;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use wx_uploader::FileOutcome;
+    use wx_uploader::providers::{ProviderConfig, UniversalAIClient};
+    use wx_uploader::wechat::{ProcessOptions, upload_files};
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir(temp_dir.path().join("images")).unwrap();
+    let article = temp_dir.path().join("post.md");
+    let original = "---\ntitle: Post\ncover: images/\n---\n# Post\n";
+    fs::write(&article, original).unwrap();
+    let uploader = SlowCountingUploader::new(temp_dir.path(), std::time::Duration::ZERO);
+    let options = ProcessOptions::default();
+
+    // Without AI the file fails before the upload and is left untouched
+    let report = upload_files(&uploader, None, std::slice::from_ref(&article), &options).await;
+    let FileOutcome::Failed { error, .. } = &report.files[0].outcome else {
+        panic!("expected a failure: {:?}", report);
+    };
+    assert!(error.contains("cover 'images/' is a directory"), "{}", error);
+    assert_eq!(fs::read_to_string(&article).unwrap(), original);
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "choices": [{ "message": { "content": "A quiet harbor at dawn." } }]
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/images/generations"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "data": [{ "b64_json": base64::engine::general_purpose::STANDARD.encode(sample_png()) }]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let ai_client = UniversalAIClient::new(
+        ProviderConfig::OpenAI {
+            api_key: "test-key".to_string(),
+            base_url: Some(server.uri()),
+        },
+        None,
+    );
+
+    // With AI a new cover is generated next to the article and replaces the directory
+    let report =
+        upload_files(&uploader, Some(&ai_client), std::slice::from_ref(&article), &options).await;
+    assert_eq!(report.uploaded(), 1, "{:?}", report);
+    let (frontmatter, _body) = parse_markdown_file(&article).await?;
+    let cover = frontmatter.cover.expect("a generated cover");
+    assert!(cover.starts_with("post_cover_"), "{}", cover);
+    assert!(temp_dir.path().join(&cover).is_file());
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Missing articles, config files and covers are reported with their path.
+#[tokio::test]
+async fn test_missing_files_and_covers_name_their_path() -> Result<()> {
+    use wx_uploader::wechat::{ProcessOptions, upload_files};
+    use wx_uploader::{Config, Error, ExitCode, FileOutcome};
+
+    let temp_dir = TempDir::new().unwrap();
+    let missing = temp_dir.path().join("missing.md");
+    let error = parse_markdown_file(&missing).await.unwrap_err();
+    assert!(matches!(error, Error::FileNotFound { .. }));
+    assert_eq!(error.to_string(), format!("File not found: {}", missing.display()));
+
+    let config_path = temp_dir.path().join("missing.yaml");
+    let error = Config::from_file(&config_path, None).await.unwrap_err();
+    assert!(error.to_string().contains(&*config_path.to_string_lossy()), "{}", error);
+    assert_eq!(ExitCode::from_error(&error), ExitCode::ConfigError);
+
+    // Without AI a missing cover fails the file before anything is uploaded
+    let article = temp_dir.path().join("post.md");
+    fs::write(&article, "---\ntitle: Post\ncover: images/cover.png\n---\n# Post\n").unwrap();
+    let uploader = SlowCountingUploader::new(temp_dir.path(), std::time::Duration::ZERO);
+    let files = [article, missing.clone()];
+    let report = upload_files(&uploader, None, &files, &ProcessOptions::default()).await;
+
+    let errors: Vec<_> = report
+        .files
+        .iter()
+        .map(|file| match &file.outcome {
+            FileOutcome::Failed { error, .. } => error.clone(),
+            other => panic!("expected a failure: {:?}", other),
+        })
+        .collect();
+    let cover_path = temp_dir.path().join("images/cover.png");
+    assert!(errors[0].contains(&*cover_path.to_string_lossy()), "{}", errors[0]);
+    assert!(errors[0].contains("cover 'images/cover.png' not found"), "{}", errors[0]);
+    assert_eq!(errors[1], format!("File not found: {}", missing.display()));
+    Ok(())
+}
+
+/// The report names the source of each uploaded file's cover.
+#[tokio::test]
+async fn test_report_attributes_cover_sources() -> Result<()> {
+    use base64::Engine;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use wx_uploader::providers::{ProviderConfig, UniversalAIClient};
+    use wx_uploader::report::CoverOutcome;
+    use wx_uploader::wechat::{ProcessOptions, upload_files};
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "choices": [{ "message": { "content": "A quiet harbor at dawn." } }]
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/images/generations"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "data": [{ "b64_json": base64::engine::general_purpose::STANDARD.encode(sample_png()) }]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let ai_client = UniversalAIClient::new(
+        ProviderConfig::OpenAI {
+            api_key: "test-key".to_string(),
+            base_url: Some(server.uri()),
+        },
+        None,
+    );
+
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    fs::write(dir.join("existing.png"), b"fake cover").unwrap();
+    image::RgbaImage::from_pixel(90, 38, image::Rgba([0, 0, 0, 255]))
+        .save(dir.join("background.png"))
+        .unwrap();
+    let font =
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/DejaVuSansMono.ttf");
+    let write = |name: &str, frontmatter: &str| {
+        let file = dir.join(name);
+        fs::write(&file, format!("---\ntitle: Post\n{}---\n# Post\n", frontmatter)).unwrap();
+        file
+    };
+    let files = [
+        write("existing.md", "cover: existing.png\n"),
+        write("generated.md", ""),
+        write(
+            "template.md",
+            &format!(
+                "cover_template:\n  background: background.png\n  font: {}\n",
+                font.display()
+            ),
+        ),
+        write("none.md", "cover: none\n"),
+    ];
+
+    let uploader = SlowCountingUploader::new(dir, std::time::Duration::ZERO);
+    let options = ProcessOptions::default();
+    let mut report = upload_files(&uploader, Some(&ai_client), &files, &options).await;
+    assert_eq!(report.uploaded(), 4, "{:?}", report);
+
+    // Without an AI provider a missing cover falls back to the default, if there is one
+    let fallback = [write("fallback.md", "")];
+    let options = ProcessOptions {
+        default_cover: Some(dir.join("existing.png")),
+        ..Default::default()
+    };
+    report.merge(upload_files(&uploader, None, &fallback, &options).await);
+    let missing = [write("missing.md", "")];
+    report.merge(upload_files(&uploader, None, &missing, &ProcessOptions::default()).await);
+
+    let covers: Vec<_> = report.covers().map(|(_, cover)| cover.clone()).collect();
+    assert_eq!(
+        covers,
+        vec![
+            CoverOutcome::Existing,
+            CoverOutcome::Generated {
+                provider: "OpenAI".to_string()
+            },
+            CoverOutcome::Template,
+            CoverOutcome::Absent,
+            CoverOutcome::Default,
+            CoverOutcome::Absent,
+        ]
+    );
+
+    let json = report.to_json();
+    assert_eq!(json["files"][1]["cover"], "generated:openai");
+    assert_eq!(
+        json["totals"]["covers"],
+        serde_json::json!({
+            "existing": 1,
+            "generated:openai": 1,
+            "template": 1,
+            "default": 1,
+            "absent": 2,
+        })
+    );
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Declares originals by default and uploads without the declaration when WeChat rejects it.
+#[tokio::test]
+async fn test_original_declaration_rejection_does_not_fail_upload() -> Result<()> {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, Request, ResponseTemplate};
+    use wx_uploader::WxUploader;
+
+    fn declares_original(request: &Request) -> bool {
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        body["articles"][0].get("is_original").is_some()
+    }
+
+    let server = mock_wechat_server().await;
+    // The account may not declare originals
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/draft/add"))
+        .and(declares_original)
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "errcode": 48001,
+            "errmsg": "api unauthorized"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    mount_drafts(&server, "draft_media_id", 2).await;
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("cover.png"), b"fake cover").unwrap();
+    let declared = temp_dir.path().join("declared.md");
+    fs::write(&declared, "---\ntitle: Mine\nauthor: Jane\ncover: cover.png\n---\nBody\n").unwrap();
+    let opted_out = temp_dir.path().join("opted_out.md");
+    fs::write(
+        &opted_out,
+        "---\ntitle: Quoted\ncover: cover.png\noriginal: false\n---\nBody\n",
+    )
+    .unwrap();
+
+    let mut config = mock_config(&server);
+    config.original = true;
+
+    let uploader = WxUploader::new(config).await?;
+    uploader.upload_file(&declared, true).await?;
+    uploader.upload_file(&opted_out, true).await?;
+    let (frontmatter, _body) = parse_markdown_file(&declared).await?;
+    assert_eq!(frontmatter.draft_id.as_deref(), Some("draft_media_id"));
+
+    let drafts: Vec<serde_json::Value> = server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter(|request| request.url.path() == "/cgi-bin/draft/add")
+        .map(|request| serde_json::from_slice(&request.body).unwrap())
+        .collect();
+    assert_eq!(drafts.len(), 3);
+    assert_eq!(drafts[0]["articles"][0]["is_original"], 1);
+    assert_eq!(drafts[0]["articles"][0]["original_author"], "Jane");
+    assert!(drafts[1]["articles"][0].get("is_original").is_none());
+    assert!(drafts[2]["articles"][0].get("is_original").is_none());
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Sends an over-long description as a truncated digest without touching the file.
+#[tokio::test]
+async fn test_long_description_is_truncated_for_the_digest() -> Result<()> {
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, ResponseTemplate};
+    use wx_uploader::WxUploader;
+
+    let description = "这篇文章介绍如何把 Markdown 上传到微信公众号。".repeat(8);
+    let digest = format!("{}…", description.chars().take(119).collect::<String>());
+
+    let server = mock_wechat_server().await;
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/draft/add"))
+        .and(body_partial_json(serde_json::json!({ "articles": [{ "digest": digest }] })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "media_id": "draft_media_id"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("cover.png"), b"fake cover").unwrap();
+    let article = temp_dir.path().join("long.md");
+    fs::write(
+        &article,
+        format!("---\ntitle: Long\ncover: cover.png\ndescription: {}\n---\nBody\n", description),
+    )
+    .unwrap();
+
+    let config = mock_config(&server);
+
+    let uploader = WxUploader::new(config).await?;
+    uploader.upload_file(&article, true).await?;
+
+    let (frontmatter, _body) = parse_markdown_file(&article).await?;
+    assert_eq!(frontmatter.description, description);
+    assert_eq!(frontmatter.draft_id.as_deref(), Some("draft_media_id"));
+    // No temporary upload copy is left behind
+    assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 2);
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Processes only the drafts of a directory with `--only draft`.
+#[tokio::test]
+async fn test_only_filter_restricts_directory_to_drafts() -> Result<()> {
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, ResponseTemplate};
+    use wx_uploader::{PublicationStatus, WxUploader};
+
+    let server = mock_wechat_server().await;
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/draft/add"))
+        .and(body_partial_json(serde_json::json!({ "articles": [{ "title": "Draft" }] })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "media_id": "new_draft_id"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("cover.png"), b"fake cover").unwrap();
+    let new = "---\ntitle: New\ncover: cover.png\n---\nBody\n";
+    fs::write(temp_dir.path().join("new.md"), new).unwrap();
+    fs::write(
+        temp_dir.path().join("draft.md"),
+        "---\ntitle: Draft\ncover: cover.png\npublished: draft\ndraft_id: old_draft_id\n---\nBody\n",
+    )
+    .unwrap();
+    fs::write(
+        temp_dir.path().join("done.md"),
+        "---\ntitle: Done\ncover: cover.png\npublished: true\n---\nBody\n",
+    )
+    .unwrap();
+
+    let mut config = mock_config(&server);
+    config.only = Some(PublicationStatus::Draft);
+
+    let uploader = WxUploader::new(config).await?;
+    let report = uploader.process_directory(temp_dir.path()).await?;
+
+    assert_eq!(report.files.len(), 1, "{:?}", report);
+    assert_eq!(report.uploaded(), 1);
+    assert_eq!(fs::read_to_string(temp_dir.path().join("new.md")).unwrap(), new);
+    let (frontmatter, _body) = parse_markdown_file(temp_dir.path().join("draft.md")).await?;
+    assert_eq!(frontmatter.draft_id.as_deref(), Some("new_draft_id"));
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Leaves files marked `published: ignore` out of directory runs, but uploads
+/// one that is named explicitly.
+#[tokio::test]
+async fn test_ignored_files_are_only_uploaded_when_named() -> Result<()> {
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, ResponseTemplate};
+    use wx_uploader::WxUploader;
+    use wx_uploader::report::FileOutcome;
+
+    let server = mock_wechat_server().await;
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/draft/add"))
+        .and(body_partial_json(serde_json::json!({ "articles": [{ "title": "Ready" }] })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "media_id": "ready_draft_id"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/draft/add"))
+        .and(body_partial_json(serde_json::json!({ "articles": [{ "title": "Unfinished" }] })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "media_id": "unfinished_draft_id"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("cover.png"), b"fake cover").unwrap();
+    fs::write(
+        temp_dir.path().join("ready.md"),
+        "---\ntitle: Ready\ncover: cover.png\n---\nBody\n",
+    )
+    .unwrap();
+    let unfinished_path = temp_dir.path().join("unfinished.md");
+    let unfinished = "---\ntitle: Unfinished\ncover: cover.png\npublished: ignore\n---\nTODO\n";
+    fs::write(&unfinished_path, unfinished).unwrap();
+
+    let config = mock_config(&server);
+    let uploader = WxUploader::new(config).await?;
+
+    let report = uploader.process_directory(temp_dir.path()).await?;
+    assert_eq!(report.uploaded(), 1, "{:?}", report);
+    let skipped = report
+        .files
+        .iter()
+        .find(|file| file.path == unfinished_path)
+        .expect("the ignored file is reported");
+    assert_eq!(
+        skipped.outcome,
+        FileOutcome::Skipped {
+            reason: "marked skip".to_string()
+        }
+    );
+    assert_eq!(fs::read_to_string(&unfinished_path).unwrap(), unfinished);
+
+    // Naming the file uploads it anyway
+    let report = uploader.upload_file_to_accounts(&unfinished_path, true).await?;
+    assert_eq!(report.uploaded(), 1, "{:?}", report);
+
+    server.verify().await;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_follow_symlinks_uploads_linked_article_once() -> Result<()> {
+    use std::os::unix::fs::symlink;
+    use wx_uploader::WxUploader;
+
+    let server = mock_wechat_server().await;
+    mount_drafts(&server, "new_draft_id", 1).await;
+
+    // A shared article linked into two collections, plus a dangling link
+    let temp_dir = TempDir::new().unwrap();
+    let shared = temp_dir.path().join("shared");
+    let posts = temp_dir.path().join("posts");
+    fs::create_dir_all(&shared).unwrap();
+    fs::create_dir_all(posts.join("rust")).unwrap();
+    fs::write(shared.join("cover.png"), b"fake cover").unwrap();
+    let article = "---\ntitle: Shared\ncover: cover.png\n---\nBody\n";
+    fs::write(shared.join("shared.md"), article).unwrap();
+    symlink(&shared, posts.join("all")).unwrap();
+    symlink(&shared, posts.join("rust/picks")).unwrap();
+    symlink(temp_dir.path().join("gone.md"), posts.join("gone.md")).unwrap();
+
+    let mut config = mock_config(&server);
+    config.follow_symlinks = true;
+
+    let uploader = WxUploader::new(config).await?;
+    let report = uploader.process_directory(&posts).await?;
+
+    assert_eq!(report.files.len(), 1, "{:?}", report);
+    assert_eq!(report.uploaded(), 1);
+    assert_eq!(report.failed(), 0);
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Uploads links to headings as plain text with `anchor_links: text`, leaving the file alone.
+#[tokio::test]
+async fn test_anchor_links_are_uploaded_as_text() -> Result<()> {
+    use wx_uploader::WxUploader;
+    use wx_uploader::models::AnchorLinks;
+
+    let server = mock_wechat_server().await;
+    mount_drafts(&server, "draft_media_id", 1).await;
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("cover.png"), b"fake cover").unwrap();
+    let article = temp_dir.path().join("article.md");
+    let body = "## Setup\n\nAs shown in [the setup section](#setup), run it.\n";
+    fs::write(&article, format!("---\ntitle: Anchors\ncover: cover.png\n---\n{}", body)).unwrap();
+
+    let mut config = mock_config(&server);
+    config.anchor_links = AnchorLinks::Text;
+
+    let uploader = WxUploader::new(config).await?;
+    uploader.upload_file(&article, true).await?;
+
+    let requests = server.received_requests().await.unwrap();
+    let draft = requests
+        .iter()
+        .find(|request| request.url.path() == "/cgi-bin/draft/add")
+        .unwrap();
+    let draft: serde_json::Value = serde_json::from_slice(&draft.body).unwrap();
+    let content = draft["articles"][0]["content"].as_str().unwrap();
+    assert!(content.contains("the setup section"), "{}", content);
+    assert!(!content.contains("#setup"), "{}", content);
+
+    let (_frontmatter, saved_body) = parse_markdown_file(&article).await?;
+    assert_eq!(saved_body, body);
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Uploads Obsidian image embeds from a nested vault folder and wiki links as text.
+#[tokio::test]
+async fn test_obsidian_embeds_are_uploaded_as_images() -> Result<()> {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+    use wx_uploader::WxUploader;
+
+    let server = mock_wechat_server().await;
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/media/uploadimg"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "url": "http://mmbiz.qpic.cn/mock/flow.png"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    mount_drafts(&server, "draft_media_id", 1).await;
+
+    let vault = TempDir::new().unwrap();
+    let notes = vault.path().join("notes");
+    fs::create_dir_all(&notes).unwrap();
+    fs::create_dir_all(vault.path().join("attachments/diagrams")).unwrap();
+    fs::write(vault.path().join("attachments/diagrams/flow.png"), b"fake diagram").unwrap();
+    fs::write(notes.join("cover.png"), b"fake cover").unwrap();
+    let article = notes.join("article.md");
+    let body = "See [[Other Note|the other note]].\n\n![[flow.png|The flow]]\n";
+    fs::write(&article, format!("---\ntitle: Vault\ncover: cover.png\n---\n{}", body)).unwrap();
+
+    let mut config = mock_config(&server);
+    config.obsidian = true;
+    config.obsidian_vault = Some(vault.path().to_path_buf());
+
+    let uploader = WxUploader::new(config).await?;
+    uploader.upload_file(&article, true).await?;
+
+    let requests = server.received_requests().await.unwrap();
+    let draft = requests
+        .iter()
+        .find(|request| request.url.path() == "/cgi-bin/draft/add")
+        .unwrap();
+    let draft: serde_json::Value = serde_json::from_slice(&draft.body).unwrap();
+    let content = draft["articles"][0]["content"].as_str().unwrap();
+    assert!(content.contains("http://mmbiz.qpic.cn/mock/flow.png"), "{}", content);
+    assert!(content.contains("See the other note."), "{}", content);
+    assert!(!content.contains("[["), "{}", content);
+
+    // The vault note keeps its Obsidian syntax
+    let (_frontmatter, saved_body) = parse_markdown_file(&article).await?;
+    assert_eq!(saved_body, body);
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Runs a custom body transform added with `with_transforms` in the order the
+/// frontmatter lists it among the built-ins, leaving the file alone.
+#[tokio::test]
+async fn test_custom_body_transform_runs_in_listed_order() -> Result<()> {
+    use wx_uploader::WxUploader;
+    use wx_uploader::markdown::{ArticleContext, BodyTransform, FnTransform};
+
+    let server = mock_wechat_server().await;
+    mount_drafts(&server, "draft_media_id", 1).await;
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("cover.png"), b"fake cover").unwrap();
+    let article = temp_dir.path().join("article.md");
+    let body = "See [[Other Note]] and [the setup](#setup).\n";
+    fs::write(
+        &article,
+        format!(
+            "---\ntitle: Pipeline\ncover: cover.png\n\
+             transforms: [footer, anchor-links, obsidian]\n---\n{}",
+            body
+        ),
+    )
+    .unwrap();
+
+    let mut config = mock_config(&server);
+    // The frontmatter list takes precedence over the setting
+    config.transforms = Some(vec!["obsidian".to_string()]);
+
+    let footer = FnTransform::new("footer", |ctx: &ArticleContext<'_>, body: String| {
+        let title = ctx.frontmatter.title.as_deref().unwrap_or_default();
+        Ok(format!("{}\n[Back to {}](#top) [[Index]]\n", body, title))
+    });
+    let transforms: Vec<Box<dyn BodyTransform>> = vec![Box::new(footer)];
+    let uploader = WxUploader::new(config).await?.with_transforms(transforms);
+    uploader.upload_file(&article, true).await?;
+
+    let requests = server.received_requests().await.unwrap();
+    let draft = requests
+        .iter()
+        .find(|request| request.url.path() == "/cgi-bin/draft/add")
+        .unwrap();
+    let draft: serde_json::Value = serde_json::from_slice(&draft.body).unwrap();
+    let content = draft["articles"][0]["content"].as_str().unwrap();
+    assert!(content.contains("See Other Note and the setup."), "{}", content);
+    assert!(content.contains("Back to Pipeline Index"), "{}", content);
+    assert!(!content.contains("#top"), "{}", content);
+    assert!(!content.contains("[["), "{}", content);
+
+    let (_frontmatter, saved_body) = parse_markdown_file(&article).await?;
+    assert_eq!(saved_body, body);
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Uploads exactly the given files, isolating failures and honouring `force`.
+#[tokio::test]
+async fn test_upload_files_uploads_explicit_batch() -> Result<()> {
+    use wx_uploader::WxUploader;
+
+    let server = mock_wechat_server().await;
+    mount_drafts(&server, "draft_media_id", 2).await;
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("cover.png"), b"fake cover").unwrap();
+    let article = |title: &str, published: &str| {
+        format!("---\ntitle: {}\npublished: {}\ncover: cover.png\n---\nBody\n", title, published)
+    };
+    let draft = temp_dir.path().join("draft.md");
+    let done = temp_dir.path().join("done.md");
+    fs::write(&draft, article("Draft", "draft")).unwrap();
+    fs::write(&done, article("Done", "\"true\"")).unwrap();
+    // Not part of the batch, so never uploaded
+    fs::write(temp_dir.path().join("other.md"), article("Other", "draft")).unwrap();
+    let missing = temp_dir.path().join("missing.md");
+
+    let config = mock_config(&server);
+    let uploader = WxUploader::new(config).await?;
+
+    let report = uploader
+        .upload_files(&[draft.clone(), done.clone(), missing], false)
+        .await?;
+    assert_eq!(report.files.len(), 3, "{:?}", report);
+    assert_eq!((report.uploaded(), report.skipped(), report.failed()), (1, 1, 1));
+
+    let report = uploader.upload_files(std::slice::from_ref(&done), true).await?;
+    assert_eq!(report.uploaded(), 1, "{:?}", report);
+    assert!(uploader.upload_files(&[], false).await?.files.is_empty());
+
+    server.verify().await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_run_over_ai_cost_threshold_needs_confirmation() -> Result<()> {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use wx_uploader::WxUploader;
+    use wx_uploader::models::AiProvider;
+    use wx_uploader::prompt::{PromptPolicy, Prompter};
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/images/generations"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/draft/add"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let temp_dir = TempDir::new().unwrap();
+    let original = "---\ntitle: Post\n---\nBody\n";
+    for name in ["a.md", "b.md"] {
+        fs::write(temp_dir.path().join(name), original).unwrap();
+    }
+
+    let ai_provider = Some(AiProvider::OpenAI {
+        api_key: "test-key".to_string(),
+        base_url: Some(server.uri()),
+    });
+    let mut config = mock_config(&server);
+    config.ai_provider = ai_provider;
+    config.confirm_ai_cost_above = Some(0.1);
+
+    // Two dall-e-3 covers with scene descriptions come to about $0.16
+    let uploader = WxUploader::new(config.clone()).await?;
+    let err = uploader.process_directory(temp_dir.path()).await.unwrap_err();
+    assert!(err.is_config());
+    assert!(
+        err.to_string()
+            .contains("estimated AI cost of about $0.16 exceeds confirm_ai_cost_above ($0.10)"),
+        "{}",
+        err
+    );
+    assert_eq!(fs::read_to_string(temp_dir.path().join("a.md")).unwrap(), original);
+
+    // Declined when asked, and under --yes, which takes the default "no"
+    config.prompt = Prompter::with_input(PromptPolicy::Ask, std::io::Cursor::new("n\n"));
+    let uploader = WxUploader::new(config.clone()).await?;
+    let err = uploader.process_directory(temp_dir.path()).await.unwrap_err();
+    assert!(err.to_string().contains("pass --accept-ai-cost to run anyway"), "{}", err);
+
+    config.prompt = Prompter::with_input(PromptPolicy::AssumeYes, std::io::Cursor::new("y\n"));
+    let uploader = WxUploader::new(config.clone()).await?;
+    let err = uploader.process_directory(temp_dir.path()).await.unwrap_err();
+    assert!(err.to_string().contains("pass --accept-ai-cost to run anyway"), "{}", err);
+
+    // An error without asking under --no-input
+
+    config.prompt = Prompter::with_input(PromptPolicy::NoInput, std::io::Cursor::new("y\n"));
+    let uploader = WxUploader::new(config).await?;
+    let err = uploader.process_directory(temp_dir.path()).await.unwrap_err();
+    assert!(err.is_config());
+    assert!(err.to_string().contains("--no-input is set"), "{}", err);
+    assert_eq!(fs::read_to_string(temp_dir.path().join("a.md")).unwrap(), original);
+
+    server.verify().await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_directory_run_refuses_while_another_run_holds_the_lock() -> Result<()> {
+    use wx_uploader::{Config, WxUploader};
+
+    let temp_dir = TempDir::new().unwrap();
+    let article = temp_dir.path().join("article.md");
+    let original = "---\ntitle: Post\n---\nBody\n";
+    fs::write(&article, original).unwrap();
+    let lock = temp_dir.path().join(".wx-uploader/lock");
+    fs::create_dir_all(lock.parent().unwrap()).unwrap();
+    fs::write(&lock, format!("pid: {}\nstarted: earlier\n", std::process::id())).unwrap();
+
+    let config = Config::new("wx_mock_app".to_string(), "mock_secret".to_string(), None, false);
+    let uploader = WxUploader::new(config).await?;
+    let err = uploader.process_directory(temp_dir.path()).await.unwrap_err();
+    assert!(
+        err.to_string()
+            .contains(&format!("another run (PID {}, started earlier)", std::process::id())),
+        "{}",
+        err
+    );
+    assert!(lock.exists());
+    assert_eq!(fs::read_to_string(&article).unwrap(), original);
+    Ok(())
+}
+
+/// A directory run reports invalid frontmatter of every file up front;
+/// `fail_fast` refuses the run before anything is uploaded.
+#[tokio::test]
+async fn test_directory_run_reports_invalid_frontmatter_up_front() -> Result<()> {
+    use wx_uploader::WxUploader;
+
+    let server = mock_wechat_server().await;
+    mount_drafts(&server, "draft_media_id", 1).await;
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("cover.png"), b"fake cover").unwrap();
+    let article = |style: &str| format!("---\ntitle: Post\ncover: cover.png\n{}---\nBody\n", style);
+    fs::write(temp_dir.path().join("a.md"), article("theme: old\n")).unwrap();
+    fs::write(temp_dir.path().join("b.md"), article("code: prism\n")).unwrap();
+    fs::write(temp_dir.path().join("c.md"), article("theme: lapis\n")).unwrap();
+
+    let mut config = mock_config(&server);
+    config.fail_fast = true;
+    let uploader = WxUploader::new(config.clone()).await?;
+    let err = uploader.process_directory(temp_dir.path()).await.unwrap_err();
+    assert!(
+        err.to_string().contains("2 file(s) have invalid frontmatter"),
+        "{}",
+        err
+    );
+
+    // Without fail_fast, the invalid files fail and the valid one uploads
+    config.fail_fast = false;
+    let uploader = WxUploader::new(config).await?;
+    let report = uploader.process_directory(temp_dir.path()).await?;
+    assert_eq!((report.uploaded(), report.failed()), (1, 2), "{:?}", report);
+
+    server.verify().await;
+    Ok(())
+}
+
+/// A mostly-published tree is summarized in one line rather than one line per file.
+#[tokio::test]
+async fn test_mostly_published_tree_counts_skipped_files() -> Result<()> {
+    use wx_uploader::wechat::{ProcessOptions, collect_markdown_files, upload_files};
+
+    let temp_dir = TempDir::new().unwrap();
+    let archive = temp_dir.path().join("archive");
+    fs::create_dir(&archive).unwrap();
+    for index in 0..12 {
+        let dir = if index % 2 == 0 { temp_dir.path() } else { archive.as_path() };
+        fs::write(
+            dir.join(format!("old-{}.md", index)),
+            format!("---\ntitle: Old {}\npublished: true\n---\n# Old\n", index),
+        )
+        .unwrap();
+    }
+    fs::write(temp_dir.path().join("new.png"), b"existing cover").unwrap();
+    fs::write(
+        temp_dir.path().join("new.md"),
+        "---\ntitle: New\ncover: new.png\n---\n# New\n",
+    )
+    .unwrap();
+
+    let files = collect_markdown_files(temp_dir.path());
+    assert_eq!(files.len(), 13);
+    let uploader = SlowCountingUploader::new(temp_dir.path(), std::time::Duration::ZERO);
+    let report = upload_files(&uploader, None, &files, &ProcessOptions::default()).await;
+
+    assert_eq!(report.uploaded(), 1);
+    assert_eq!(report.skipped_published().len(), 12);
+    assert_eq!(
+        report.skipped_published_summary().as_deref(),
+        Some("skipped 12 already-published files (use --show-skipped to list)")
+    );
+
+    // The JSON report still lists every skipped file
+    let json = report.to_json();
+    let listed = json["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|file| file["path"].as_str().is_some_and(|path| path.contains("old-")))
+        .count();
+    assert_eq!(listed, 12);
+
+    Ok(())
+}
+
+/// A cover naming a directory of candidates uploads the newest image and records it.
+#[tokio::test]
+async fn test_cover_directory_uploads_newest_candidate() -> Result<()> {
+    use wx_uploader::FileOutcome;
+    use wx_uploader::wechat::{ProcessOptions, upload_files};
+
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path().join("covers").join("post");
+    fs::create_dir_all(&dir).unwrap();
+    let now = std::time::SystemTime::now();
+    for (name, age) in [("draft-1.png", 60), ("draft-2.png", 0), ("draft-3.png", 30)] {
+        let file = fs::File::create(dir.join(name)).unwrap();
+        file.set_modified(now - std::time::Duration::from_secs(age)).unwrap();
+    }
+    let article = temp_dir.path().join("post.md");
+    fs::write(&article, "---\ntitle: Post\ncover: covers/post\n---\n# Post\n").unwrap();
+
+    let uploader = SlowCountingUploader::new(temp_dir.path(), std::time::Duration::ZERO);
+    let report = upload_files(
+        &uploader,
+        None,
+        std::slice::from_ref(&article),
+        &ProcessOptions::default(),
+    )
+    .await;
+
+    assert!(matches!(report.files[0].outcome, FileOutcome::Uploaded { .. }), "{:?}", report);
+    let (frontmatter, _body) = parse_markdown_file(&article).await?;
+    assert_eq!(frontmatter.cover.as_deref(), Some("covers/post/draft-2.png"));
+
+    Ok(())
+}
+
+/// Files marked `ignore: true` are left out of directory runs, but upload when named.
+#[tokio::test]
+async fn test_ignored_files_left_out_of_directory_runs() -> Result<()> {
+    use wx_uploader::{FileOutcome, WxUploader};
+
+    let server = mock_wechat_server().await;
+    mount_drafts(&server, "draft_media_id", 2).await;
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("post.png"), b"existing cover").unwrap();
+    let article = temp_dir.path().join("post.md");
+    fs::write(&article, "---\ntitle: Post\ncover: post.png\n---\n# Post\n").unwrap();
+    let snippet = temp_dir.path().join("footer.md");
+    let original = "---\ntitle: Footer\nignore: true\ncover: post.png\n---\nShared footer\n";
+    fs::write(&snippet, original).unwrap();
+    let uploader = WxUploader::new(mock_config(&server)).await?;
+
+    let report = uploader.process_directory(temp_dir.path()).await?;
+    assert_eq!(report.files.len(), 1, "{:?}", report);
+    assert_eq!(report.files[0].path, article);
+    assert_eq!(fs::read_to_string(&snippet).unwrap(), original);
+
+    // Named explicitly, it is uploaded after a warning and keeps its marker
+    let outcome = uploader.upload_file(&snippet, true).await?;
+    assert!(matches!(outcome, FileOutcome::Uploaded { .. }), "{:?}", outcome);
+    let (frontmatter, _body) = parse_markdown_file(&snippet).await?;
+    assert!(frontmatter.is_ignored());
+    assert!(frontmatter.publication_status().is_draft());
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Directory runs collect the files of the configured extensions.
+#[tokio::test]
+async fn test_process_directory_honors_markdown_extensions() -> Result<()> {
+    use wx_uploader::WxUploader;
+
+    let server = mock_wechat_server().await;
+    mount_drafts(&server, "draft_media_id", 1).await;
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("post.png"), b"existing cover").unwrap();
+    let article = temp_dir.path().join("notes.markdown");
+    fs::write(&article, "---\ntitle: Notes\ncover: post.png\n---\n# Notes\n").unwrap();
+
+    let mut config = mock_config(&server);
+    let report = WxUploader::new(config.clone()).await?.process_directory(temp_dir.path()).await?;
+    assert!(report.files.is_empty(), "{:?}", report);
+
+    config.markdown_extensions = vec!["md".to_string(), "markdown".to_string()];
+    let report = WxUploader::new(config).await?.process_directory(temp_dir.path()).await?;
+    assert_eq!(report.files.len(), 1, "{:?}", report);
+    assert_eq!(report.files[0].path, article);
+
+    server.verify().await;
+    Ok(())
+}
+
+/// Queues failed uploads, clears them on a successful retry and gives up after the last attempt.
+#[tokio::test]
+async fn test_retry_queue_clears_retried_files_and_gives_up() -> Result<()> {
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use wx_uploader::retry_queue::{RetryQueue, options_hash, record_failures, retry_queue_path};
+    use wx_uploader::{Config, FileOutcome, WxUploader};
+
+    async fn mount_wechat(server: &MockServer, flaky_uploads: bool) {
+        Mock::given(method("GET"))
+            .and(path("/cgi-bin/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "mock_token",
+                "expires_in": 7200
+            })))
+            .mount(server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/cgi-bin/material/add_material"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "media_id": "cover_media_id",
+                "url": "http://mmbiz.qpic.cn/mock/cover.png"
+            })))
+            .mount(server)
+            .await;
+        if flaky_uploads {
+            Mock::given(method("POST"))
+                .and(path("/cgi-bin/draft/add"))
+                .and(body_partial_json(serde_json::json!({ "articles": [{ "title": "Flaky" }] })))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "media_id": "draft_media_id"
+                })))
+                .mount(server)
+                .await;
+        }
+        Mock::given(method("POST"))
+            .and(path("/cgi-bin/draft/add"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "errcode": 44004,
+                "errmsg": "empty content"
+            })))
+            .mount(server)
+            .await;
+    }
+
+    let server = MockServer::start().await;
+    mount_wechat(&server, false).await;
+
+    let temp_dir = TempDir::new().unwrap();
+    let posts = temp_dir.path().join("posts");
+    fs::create_dir(&posts).unwrap();
+    fs::write(posts.join("cover.png"), b"fake cover").unwrap();
+    let flaky = posts.join("flaky.md");
+    let broken = posts.join("broken.md");
+    fs::write(&flaky, "---\ntitle: Flaky\ncover: cover.png\n---\n# Flaky\n").unwrap();
+    fs::write(&broken, "---\ntitle: Broken\ncover: cover.png\n---\n# Broken\n").unwrap();
+    let queue_file = retry_queue_path(temp_dir.path());
+
+    let mut config = Config::new("wx_mock_app".to_string(), "mock_secret".to_string(), None, false);
+    config.set_wechat_base_url(server.uri());
+    config.backup = false;
+    let hash = options_hash(&config);
+
+    // Both files fail and are queued after the run
+    let report = WxUploader::new(config.clone()).await?.process_directory(&posts).await?;
+    assert_eq!(report.failed(), 2, "{:?}", report);
+    record_failures(&queue_file, &report, &hash, false).await?;
+    let queue = RetryQueue::load(&queue_file).await?;
+    assert_eq!(queue.entries.len(), 2);
+    assert!(queue.entries.iter().all(|e| e.attempts == 1 && e.error.contains("44004")));
+
+    // The flaky file now uploads and leaves the queue; the broken one stays
+    server.reset().await;
+    mount_wechat(&server, true).await;
+    let uploader = WxUploader::new(config.clone()).await?;
+    let retry = uploader.retry_queued(&queue_file).await?;
+    assert_eq!(retry.report.files.len(), 2);
+    assert_eq!(retry.cleared, 1);
+    assert_eq!(retry.remaining, 1);
+    assert!(retry.given_up.is_empty());
+    let (frontmatter, _body) = parse_markdown_file(&flaky).await?;
+    assert_eq!(frontmatter.draft_id.as_deref(), Some("draft_media_id"));
+    let queue = RetryQueue::load(&queue_file).await?;
+    assert_eq!(queue.entries.len(), 1);
+    assert_eq!(queue.entries[0].path, broken.canonicalize().unwrap());
+    assert_eq!(queue.entries[0].attempts, 2);
+
+    // Entries queued with other options are left alone
+    let mut other = config.clone();
+    other.original = true;
+    let retry = WxUploader::new(other).await?.retry_queued(&queue_file).await?;
+    assert!(retry.report.files.is_empty());
+    assert_eq!(retry.held.len(), 1);
+    assert_eq!(RetryQueue::load(&queue_file).await?.entries[0].attempts, 2);
+
+    // The third failure reaches retry_max_attempts and drops the entry
+    let retry = uploader.retry_queued(&queue_file).await?;
+    assert!(matches!(retry.report.files[0].outcome, FileOutcome::Failed { .. }));
+    assert_eq!(retry.given_up.len(), 1);
+    assert_eq!(retry.given_up[0].attempts, 3);
+    assert_eq!(retry.remaining, 0);
+    assert!(RetryQueue::load(&queue_file).await?.is_empty());
+    assert!(retry.summary().contains("1 given up"), "{}", retry.summary());
+
+    Ok(())
+}
+
+/// Uses a shared `cover_media_id` as the thumbnail without uploading any cover material.
+#[tokio::test]
+async fn test_cover_media_id_reuses_material_across_articles() -> Result<()> {
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, ResponseTemplate};
+    use wx_uploader::report::CoverOutcome;
+    use wx_uploader::{Config, FileOutcome, WxUploader};
+
+    let server = mock_wechat_server().await;
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/material/add_material"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/draft/add"))
+        .and(body_partial_json(serde_json::json!({
+            "articles": [{ "thumb_media_id": "SERIES_COVER" }]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "media_id": "draft_media_id"
+        })))
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/cgi-bin/draft/add"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "errcode": 40007,
+            "errmsg": "invalid media_id"
+        })))
+        .mount(&server)
+        .await;
+
+    let temp_dir = TempDir::new().unwrap();
+    for part in 1..=2 {
+        fs::write(
+            temp_dir.path().join(format!("part-{}.md", part)),
+            format!("---\ntitle: Part {}\ncover_media_id: SERIES_COVER\n---\n# Part\n", part),
+        )
+        .unwrap();
+    }
+    let deleted = temp_dir.path().join("deleted.md");
+    fs::write(&deleted, "---\ntitle: Gone\ncover_media_id: DELETED_COVER\n---\n# Gone\n").unwrap();
+    let malformed = temp_dir.path().join("malformed.md");
+    fs::write(&malformed, "---\ntitle: Typo\ncover_media_id: cover.png\n---\n# Typo\n").unwrap();
+
+    let mut config = Config::new("wx_mock_app".to_string(), "mock_secret".to_string(), None, false);
+    config.set_wechat_base_url(server.uri());
+    config.backup = false;
+    let report = WxUploader::new(config).await?.process_directory(temp_dir.path()).await?;
+
+    // The series shares the material; the other files fail without stopping the batch
+    assert_eq!(report.uploaded(), 2, "{:?}", report);
+    for file in report.files.iter().filter(|f| f.path != deleted && f.path != malformed) {
+        let cover = match &file.outcome {
+            FileOutcome::Uploaded { cover, .. } => cover.clone(),
+            outcome => panic!("{} not uploaded: {:?}", file.path.display(), outcome),
+        };
+        assert_eq!(cover, Some(CoverOutcome::Material));
+    }
+    let error = |path: &std::path::Path| {
+        match &report.files.iter().find(|f| f.path == path).unwrap().outcome {
+            FileOutcome::Failed { error, .. } => error.clone(),
+            outcome => panic!("{} not failed: {:?}", path.display(), outcome),
+        }
+    };
+    let deleted_error = error(&deleted);
+    assert!(
+        deleted_error.contains("cover_media_id 'DELETED_COVER' names no permanent material"),
+        "{}",
+        deleted_error
+    );
+    assert!(deleted_error.contains("40007"), "{}", deleted_error);
+    let malformed_error = error(&malformed);
+    assert!(malformed_error.contains("Invalid cover_media_id 'cover.png'"), "{}", malformed_error);
+
+    server.verify().await;
+    Ok(())
+}